@@ -30,11 +30,12 @@ extern crate threadpool;
 #[macro_use]
 extern crate serde_derive;
 
-use std::{env, fmt, io, num::ParseIntError, process, sync};
+use std::{env, fmt, fs, io, num::ParseIntError, process, sync};
 
 use cfxkey::{
     brain_recover, sign, verify_address, verify_public, Brain, BrainPrefix,
-    Error as EthkeyError, Generator, KeyPair, Prefix, Random,
+    DerivationPath, Error as EthkeyError, Generator, KeyPair, Mnemonic,
+    MnemonicError, Prefix, Random, TypedData, TypedDataError,
 };
 use docopt::Docopt;
 use rustc_hex::{FromHex, FromHexError};
@@ -50,6 +51,11 @@ Usage:
     cfxkey verify public <public> <signature> <message>
     cfxkey verify address <address> <signature> <message>
     cfxkey recover <address> <known-phrase>
+    cfxkey typed-data hash <file>
+    cfxkey typed-data sign <secret> <file> [--chain-id=<id>]
+    cfxkey mnemonic generate [--words=<count>] [--path=<path>] [--passphrase=<passphrase>] [options]
+    cfxkey mnemonic info <phrase> [--path=<path>] [--passphrase=<passphrase>] [options]
+    cfxkey mnemonic validate <phrase>
     cfxkey [-h | --help]
 
 Options:
@@ -58,6 +64,13 @@ Options:
     -p, --public       Display only the public key.
     -a, --address      Display only the address.
     -b, --brain        Use parity brain wallet algorithm. Not recommended.
+    --chain-id=<id>    Require the typed data domain to be bound to this
+                       chain id.
+    --words=<count>    Number of words of the generated mnemonic, one of
+                       12, 15, 18, 21 and 24 [default: 12].
+    --path=<path>      BIP32 derivation path of the key
+                       [default: m/44'/503'/0'/0/0].
+    --passphrase=<passphrase>  Optional BIP39 passphrase salting the seed.
 
 Commands:
     info               Display public key and address of the secret.
@@ -66,6 +79,11 @@ Commands:
     sign               Sign message using a secret key.
     verify             Verify signer of the signature by public key or address.
     recover            Try to find brain phrase matching given address from partial phrase.
+    typed-data hash    Display the hash of typed structured data read from a JSON file.
+    typed-data sign    Sign typed structured data read from a JSON file.
+    mnemonic generate  Generate a new BIP39 mnemonic and display the key at the derivation path.
+    mnemonic info      Display the key derived from a BIP39 mnemonic at the derivation path.
+    mnemonic validate  Check the words and the checksum of a BIP39 mnemonic.
 "#;
 
 #[derive(Debug, Deserialize)]
@@ -79,6 +97,10 @@ struct Args {
     cmd_public: bool,
     cmd_address: bool,
     cmd_recover: bool,
+    cmd_typed_data: bool,
+    cmd_hash: bool,
+    cmd_mnemonic: bool,
+    cmd_validate: bool,
     arg_prefix: String,
     arg_secret: String,
     arg_secret_or_phrase: String,
@@ -87,10 +109,16 @@ struct Args {
     arg_public: String,
     arg_address: String,
     arg_signature: String,
+    arg_file: String,
+    arg_phrase: String,
     flag_secret: bool,
     flag_public: bool,
     flag_address: bool,
     flag_brain: bool,
+    flag_chain_id: Option<u32>,
+    flag_words: usize,
+    flag_path: String,
+    flag_passphrase: Option<String>,
 }
 
 #[derive(Debug)]
@@ -100,6 +128,8 @@ enum Error {
     ParseInt(ParseIntError),
     Docopt(docopt::Error),
     Io(io::Error),
+    TypedData(TypedDataError),
+    Mnemonic(MnemonicError),
 }
 
 impl From<EthkeyError> for Error {
@@ -122,6 +152,14 @@ impl From<io::Error> for Error {
     fn from(err: io::Error) -> Self { Error::Io(err) }
 }
 
+impl From<TypedDataError> for Error {
+    fn from(err: TypedDataError) -> Self { Error::TypedData(err) }
+}
+
+impl From<MnemonicError> for Error {
+    fn from(err: MnemonicError) -> Self { Error::Mnemonic(err) }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         match *self {
@@ -130,6 +168,8 @@ impl fmt::Display for Error {
             Error::ParseInt(ref e) => write!(f, "{}", e),
             Error::Docopt(ref e) => write!(f, "{}", e),
             Error::Io(ref e) => write!(f, "{}", e),
+            Error::TypedData(ref e) => write!(f, "{}", e),
+            Error::Mnemonic(ref e) => write!(f, "{}", e),
         }
     }
 }
@@ -190,7 +230,27 @@ where
     let args: Args =
         Docopt::new(USAGE).and_then(|d| d.argv(command).deserialize())?;
 
-    if args.cmd_info {
+    if args.cmd_mnemonic {
+        if args.cmd_validate {
+            Mnemonic::from_phrase(&args.arg_phrase)?;
+            return Ok("The mnemonic phrase is valid.".to_string());
+        }
+        let display_mode = DisplayMode::new(&args);
+        let mnemonic = if args.cmd_generate {
+            Mnemonic::random(args.flag_words)?
+        } else {
+            Mnemonic::from_phrase(&args.arg_phrase)?
+        };
+        let path: DerivationPath = args.flag_path.parse()?;
+        let passphrase = args.flag_passphrase.as_deref().unwrap_or("");
+        let keypair = mnemonic.derive(passphrase, &path)?;
+        let extra_data = if args.cmd_generate {
+            format!("mnemonic: {}\npath:    {}", mnemonic.phrase(), path)
+        } else {
+            format!("path:    {}", path)
+        };
+        Ok(display((keypair, Some(extra_data)), display_mode))
+    } else if args.cmd_info {
         let display_mode = DisplayMode::new(&args);
 
         let result = if args.flag_brain {
@@ -247,6 +307,25 @@ where
             return Ok(USAGE.to_string());
         };
         Ok(display(result, display_mode))
+    } else if args.cmd_typed_data {
+        let typed_data =
+            TypedData::from_json(&fs::read_to_string(&args.arg_file)?)?;
+        if let Some(chain_id) = args.flag_chain_id {
+            typed_data.check_chain_id(chain_id.into())?;
+        }
+        let message = typed_data.hash()?;
+        if args.cmd_hash {
+            Ok(format!("{:x}", message))
+        } else if args.cmd_sign {
+            let secret = args
+                .arg_secret
+                .parse()
+                .map_err(|_| EthkeyError::InvalidSecret)?;
+            let signature = sign(&secret, &message)?;
+            Ok(format!("{}", signature))
+        } else {
+            Ok(USAGE.to_string())
+        }
     } else if args.cmd_sign {
         let secret = args
             .arg_secret
@@ -485,6 +564,106 @@ address: 10a33d9f95b22fe53024331c036db6e824a25bab".to_owned();
         assert_eq!(execute(command).unwrap(), expected);
     }
 
+    #[test]
+    fn typed_data_hash() {
+        // The example of EIP-712.
+        let path = std::env::temp_dir().join(format!(
+            "cfxkey_typed_data_hash_{}.json",
+            std::process::id()
+        ));
+        std::fs::write(
+            &path,
+            r#"{
+                "types": {
+                    "EIP712Domain": [
+                        { "name": "name", "type": "string" },
+                        { "name": "version", "type": "string" },
+                        { "name": "chainId", "type": "uint256" },
+                        { "name": "verifyingContract", "type": "address" }
+                    ],
+                    "Person": [
+                        { "name": "name", "type": "string" },
+                        { "name": "wallet", "type": "address" }
+                    ],
+                    "Mail": [
+                        { "name": "from", "type": "Person" },
+                        { "name": "to", "type": "Person" },
+                        { "name": "contents", "type": "string" }
+                    ]
+                },
+                "primaryType": "Mail",
+                "domain": {
+                    "name": "Ether Mail",
+                    "version": "1",
+                    "chainId": 1,
+                    "verifyingContract": "0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC"
+                },
+                "message": {
+                    "from": {
+                        "name": "Cow",
+                        "wallet": "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826"
+                    },
+                    "to": {
+                        "name": "Bob",
+                        "wallet": "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB"
+                    },
+                    "contents": "Hello, Bob!"
+                }
+            }"#,
+        )
+        .unwrap();
+        let file = path.to_str().unwrap().to_owned();
+
+        let hash = execute(vec!["cfxkey", "typed-data", "hash", file.as_str()]);
+        let sign_with_chain_id = |chain_id: &str| {
+            execute(vec![
+                "cfxkey",
+                "typed-data",
+                "sign",
+                "17d08f5fe8c77af811caa0c9a187e668ce3b74a99acc3f6d976f075fa8e0be55",
+                file.as_str(),
+                chain_id,
+            ])
+        };
+        let signed = sign_with_chain_id("--chain-id=1");
+        let mismatched = sign_with_chain_id("--chain-id=1029");
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            hash.unwrap(),
+            "be609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2"
+        );
+        assert!(signed.is_ok());
+        assert!(mismatched.is_err());
+    }
+
+    #[test]
+    fn mnemonic() {
+        let phrase =
+            "test test test test test test test test test test test junk";
+        let command = vec![
+            "cfxkey",
+            "mnemonic",
+            "info",
+            phrase,
+            "--path=m/44'/60'/0'/0/0",
+            "--secret",
+        ];
+        let expected =
+            "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80"
+                .to_owned();
+        assert_eq!(execute(command).unwrap(), expected);
+
+        let command = vec!["cfxkey", "mnemonic", "validate", phrase];
+        assert!(execute(command).is_ok());
+        let command = vec!["cfxkey", "mnemonic", "validate", "test test"];
+        assert!(execute(command).is_err());
+
+        let command =
+            vec!["cfxkey", "mnemonic", "generate", "--words=24", "--address"];
+        assert_eq!(execute(command).unwrap().len(), 40);
+    }
+
     #[test]
     fn verify_invalid() {
         let command = vec!["cfxkey", "verify", "public", "689268c0ff57a20cd299fa60d3fb374862aff565b20b5f1767906a99e6e09f3ff04ca2b2a5cd22f62941db103c0356df1a8ed20ce322cab2483db67685afd124", "c1878cf60417151c766a712653d26ef350c8c75393458b7a9be715f053215af63dfd3b02c2ae65a8677917a8efa3172acb71cb90196e42106953ea0363c5aaf200", "bd50b7370c3f96733b31744c6c45079e7ae6c8d299613246d28ebcef507ec986"]
@@ -16,6 +16,7 @@
 
 // #![warn(missing_docs)]
 
+extern crate bip39;
 extern crate cfx_types;
 extern crate edit_distance;
 extern crate parity_crypto;
@@ -28,6 +29,8 @@ extern crate rand;
 extern crate rustc_hex;
 extern crate secp256k1;
 extern crate serde;
+extern crate serde_json;
+extern crate solidity_abi;
 extern crate tiny_keccak;
 extern crate zeroize;
 
@@ -44,6 +47,7 @@ mod error;
 mod extended;
 mod keccak;
 mod keypair;
+mod mnemonic;
 mod password;
 mod prefix;
 mod random;
@@ -53,6 +57,7 @@ mod signature;
 pub mod brain_recover;
 pub mod crypto;
 pub mod math;
+pub mod typed_data;
 
 pub use self::{
     brain::Brain,
@@ -64,12 +69,14 @@ pub use self::{
     },
     keypair::{public_to_address, KeyPair},
     math::public_is_valid,
+    mnemonic::{DerivationPath, Mnemonic, MnemonicError, CFX_COIN_TYPE},
     parity_wordlist::Error as WordlistError,
     password::Password,
     prefix::Prefix,
     random::Random,
     secret::Secret,
     signature::{recover, sign, verify_address, verify_public, Signature},
+    typed_data::{TypedData, TypedDataError},
 };
 
 use cfx_types::H256;
@@ -0,0 +1,233 @@
+// Copyright 2020 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! BIP39 mnemonic phrases and BIP44 derivation paths.
+
+use bip39::{Language, Mnemonic as Bip39Mnemonic, MnemonicType, Seed};
+use extended::{Derivation, DerivationError, ExtendedKeyPair};
+use std::{fmt, str::FromStr};
+use Error;
+use KeyPair;
+
+/// The SLIP-44 coin type registered for Conflux.
+pub const CFX_COIN_TYPE: u32 = 503;
+
+const HARDENED_BIT: u32 = 1 << 31;
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum MnemonicError {
+        InvalidPhrase(reason: String) {
+            display("Invalid mnemonic phrase: {}", reason)
+        }
+        InvalidWordCount(count: usize) {
+            display(
+                "Invalid mnemonic word count {}, expected one of 12, 15, 18, 21 and 24",
+                count
+            )
+        }
+        InvalidPath(path: String) {
+            display("Invalid derivation path: {}", path)
+        }
+        Derivation(err: DerivationError) {
+            from()
+            display("Key derivation failed: {:?}", err)
+        }
+        Key(err: Error) {
+            from()
+            display("Invalid derived key: {}", err)
+        }
+    }
+}
+
+/// A BIP39 mnemonic phrase over the English wordlist.
+pub struct Mnemonic {
+    inner: Bip39Mnemonic,
+}
+
+impl Mnemonic {
+    /// Generate a random mnemonic of `word_count` words.
+    pub fn random(word_count: usize) -> Result<Mnemonic, MnemonicError> {
+        let mnemonic_type = MnemonicType::for_word_count(word_count)
+            .map_err(|_| MnemonicError::InvalidWordCount(word_count))?;
+        Ok(Mnemonic {
+            inner: Bip39Mnemonic::new(mnemonic_type, Language::English),
+        })
+    }
+
+    /// Parse a mnemonic phrase, checking the words and the checksum. Extra
+    /// whitespace between the words is ignored.
+    pub fn from_phrase(phrase: &str) -> Result<Mnemonic, MnemonicError> {
+        let phrase = phrase.to_lowercase();
+        let inner = Bip39Mnemonic::from_phrase(&phrase, Language::English)
+            .map_err(|e| MnemonicError::InvalidPhrase(e.to_string()))?;
+        Ok(Mnemonic { inner })
+    }
+
+    pub fn phrase(&self) -> &str { self.inner.phrase() }
+
+    /// The 64-byte BIP39 seed, salted by the optional `passphrase`.
+    pub fn seed(&self, passphrase: &str) -> Vec<u8> {
+        Seed::new(&self.inner, passphrase).as_bytes().to_vec()
+    }
+
+    /// Derive the key pair at `path` from the seed of this mnemonic.
+    pub fn derive(
+        &self, passphrase: &str, path: &DerivationPath,
+    ) -> Result<KeyPair, MnemonicError> {
+        let extended = path.derive(&self.seed(passphrase))?;
+        Ok(KeyPair::from_secret(extended.secret().as_raw().clone())?)
+    }
+}
+
+/// A BIP32 derivation path such as `m/44'/503'/0'/0/0`. Hardened indexes are
+/// stored with the hardened bit set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DerivationPath {
+    indexes: Vec<u32>,
+}
+
+impl DerivationPath {
+    /// The BIP44 path `m/44'/503'/account'/change/address_index`.
+    pub fn bip44(account: u32, change: u32, address_index: u32) -> Self {
+        DerivationPath {
+            indexes: vec![
+                44 | HARDENED_BIT,
+                CFX_COIN_TYPE | HARDENED_BIT,
+                account | HARDENED_BIT,
+                change,
+                address_index,
+            ],
+        }
+    }
+
+    pub fn indexes(&self) -> &[u32] { &self.indexes }
+
+    /// Derive the extended key pair at this path from a BIP32 seed.
+    pub fn derive(
+        &self, seed: &[u8],
+    ) -> Result<ExtendedKeyPair, DerivationError> {
+        let mut key = ExtendedKeyPair::with_seed(seed)?;
+        for index in &self.indexes {
+            key = key.derive(Derivation::from(*index))?;
+        }
+        Ok(key)
+    }
+}
+
+impl Default for DerivationPath {
+    fn default() -> Self { DerivationPath::bip44(0, 0, 0) }
+}
+
+impl FromStr for DerivationPath {
+    type Err = MnemonicError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || MnemonicError::InvalidPath(s.to_owned());
+        let mut components = s.trim().split('/');
+        if components.next() != Some("m") {
+            return Err(invalid());
+        }
+        let mut indexes = Vec::new();
+        for component in components {
+            let (number, hardened) =
+                match component.strip_suffix(|c| c == '\'' || c == 'h') {
+                    Some(number) => (number, true),
+                    None => (component, false),
+                };
+            let index: u32 = number.parse().map_err(|_| invalid())?;
+            if index & HARDENED_BIT != 0 {
+                return Err(invalid());
+            }
+            if hardened {
+                indexes.push(index | HARDENED_BIT);
+            } else {
+                indexes.push(index);
+            }
+        }
+        Ok(DerivationPath { indexes })
+    }
+}
+
+impl fmt::Display for DerivationPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "m")?;
+        for index in &self.indexes {
+            if index & HARDENED_BIT != 0 {
+                write!(f, "/{}'", index & !HARDENED_BIT)?;
+            } else {
+                write!(f, "/{}", index)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DerivationPath, Mnemonic, HARDENED_BIT};
+    use rustc_hex::ToHex;
+
+    #[test]
+    fn parse_path() {
+        let path: DerivationPath = "m/44'/503'/0'/0/7".parse().unwrap();
+        assert_eq!(path, DerivationPath::bip44(0, 0, 7));
+        assert_eq!(path.to_string(), "m/44'/503'/0'/0/7");
+        let path: DerivationPath = "m/0h/1".parse().unwrap();
+        assert_eq!(path.indexes(), &[HARDENED_BIT, 1]);
+        assert_eq!("m".parse::<DerivationPath>().unwrap().indexes(), &[]);
+
+        assert!("44'/503'".parse::<DerivationPath>().is_err());
+        assert!("m/x".parse::<DerivationPath>().is_err());
+        assert!("m/2147483648".parse::<DerivationPath>().is_err());
+    }
+
+    #[test]
+    fn validate_phrase() {
+        assert!(Mnemonic::from_phrase(
+            "abandon abandon abandon abandon abandon abandon abandon abandon \
+             abandon abandon abandon about"
+        )
+        .is_ok());
+        // Bad checksum.
+        assert!(Mnemonic::from_phrase(
+            "abandon abandon abandon abandon abandon abandon abandon abandon \
+             abandon abandon abandon abandon"
+        )
+        .is_err());
+        assert!(Mnemonic::random(13).is_err());
+
+        let mnemonic = Mnemonic::random(24).unwrap();
+        assert_eq!(mnemonic.phrase().split(' ').count(), 24);
+        assert!(Mnemonic::from_phrase(mnemonic.phrase()).is_ok());
+    }
+
+    #[test]
+    fn seed() {
+        // Test vector from the BIP39 reference implementation.
+        let mnemonic = Mnemonic::from_phrase(
+            "abandon abandon abandon abandon abandon abandon abandon abandon \
+             abandon abandon abandon about",
+        )
+        .unwrap();
+        assert_eq!(
+            mnemonic.seed("TREZOR").to_hex::<String>(),
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+        );
+    }
+
+    #[test]
+    fn derive() {
+        let mnemonic = Mnemonic::from_phrase(
+            "test test test test test test test test test test test junk",
+        )
+        .unwrap();
+        let path = "m/44'/60'/0'/0/0".parse().unwrap();
+        let keypair = mnemonic.derive("", &path).unwrap();
+        assert_eq!(
+            format!("{:x}", keypair.secret()),
+            "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80"
+        );
+    }
+}
@@ -0,0 +1,469 @@
+// Copyright 2020 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! Hashing of typed structured data (EIP-712 style).
+//!
+//! The signed message is `keccak256("\x19\x01" ‖ domainSeparator ‖
+//! hashStruct(message))`, where the domain separator is the struct hash of
+//! the domain object. The domain is expected to carry the Conflux chain id
+//! in its `chainId` field so that a signature cannot be replayed on another
+//! chain.
+
+use cfx_types::{Address, H256, U256};
+use keccak::Keccak256;
+use rustc_hex::FromHex;
+use serde_json::Value;
+use solidity_abi::ABIEncodable;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Accepted names of the domain struct type, in order of preference.
+pub const DOMAIN_TYPE_NAMES: [&str; 2] = ["CIP23Domain", "EIP712Domain"];
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum TypedDataError {
+        Json(e: ::serde_json::Error) {
+            display("invalid typed data json: {}", e)
+            cause(e)
+            from()
+        }
+        UnknownType(ty: String) {
+            display("unknown type {}", ty)
+        }
+        MissingDomainType {
+            display("missing domain type, expected one of {:?}", DOMAIN_TYPE_NAMES)
+        }
+        MissingField(ty: String, field: String) {
+            display("missing field {} of type {}", field, ty)
+        }
+        InvalidValue(ty: String, reason: String) {
+            display("invalid value of type {}: {}", ty, reason)
+        }
+        ChainIdMismatch(expected: U256, got: Option<U256>) {
+            display("domain chainId mismatch, expected {}, got {:?}", expected, got)
+        }
+    }
+}
+
+/// A member of a struct type.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemberType {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_: String,
+}
+
+/// Typed structured data to be hashed and signed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TypedData {
+    /// Struct type definitions, including the domain type.
+    pub types: BTreeMap<String, Vec<MemberType>>,
+    pub primary_type: String,
+    pub domain: Value,
+    pub message: Value,
+}
+
+impl TypedData {
+    pub fn from_json(json: &str) -> Result<Self, TypedDataError> {
+        Ok(::serde_json::from_str(json)?)
+    }
+
+    /// The hash to be signed.
+    pub fn hash(&self) -> Result<H256, TypedDataError> {
+        let mut encoded = vec![0x19u8, 0x01u8];
+        encoded.extend_from_slice(self.domain_separator()?.as_bytes());
+        encoded.extend_from_slice(
+            self.hash_struct(&self.primary_type, &self.message)?
+                .as_bytes(),
+        );
+        Ok(keccak(&encoded))
+    }
+
+    pub fn domain_separator(&self) -> Result<H256, TypedDataError> {
+        self.hash_struct(self.domain_type()?, &self.domain)
+    }
+
+    /// The `chainId` field of the domain, if any.
+    pub fn chain_id(&self) -> Option<U256> {
+        self.domain
+            .get("chainId")
+            .and_then(|value| parse_uint("uint256", value).ok())
+    }
+
+    /// Check that the domain is bound to `chain_id`.
+    pub fn check_chain_id(&self, chain_id: U256) -> Result<(), TypedDataError> {
+        match self.chain_id() {
+            Some(got) if got == chain_id => Ok(()),
+            got => Err(TypedDataError::ChainIdMismatch(chain_id, got)),
+        }
+    }
+
+    fn domain_type(&self) -> Result<&'static str, TypedDataError> {
+        DOMAIN_TYPE_NAMES
+            .iter()
+            .find(|name| self.types.contains_key(**name))
+            .cloned()
+            .ok_or(TypedDataError::MissingDomainType)
+    }
+
+    /// Encode a struct type and the struct types it references, e.g.
+    /// `Mail(Person from,Person to,string contents)Person(string
+    /// name,address wallet)`.
+    pub fn encode_type(&self, ty: &str) -> Result<String, TypedDataError> {
+        let mut dependencies = BTreeSet::new();
+        self.collect_dependencies(ty, &mut dependencies);
+        if !dependencies.remove(ty) {
+            return Err(TypedDataError::UnknownType(ty.into()));
+        }
+
+        let mut encoded = String::new();
+        for name in Some(ty)
+            .into_iter()
+            .chain(dependencies.iter().map(|s| &**s))
+        {
+            let members: Vec<String> = self.types[name]
+                .iter()
+                .map(|m| format!("{} {}", m.type_, m.name))
+                .collect();
+            encoded.push_str(&format!("{}({})", name, members.join(",")));
+        }
+        Ok(encoded)
+    }
+
+    fn collect_dependencies(&self, ty: &str, found: &mut BTreeSet<String>) {
+        let base = base_type(ty);
+        if found.contains(base) {
+            return;
+        }
+        if let Some(members) = self.types.get(base) {
+            found.insert(base.into());
+            for member in members {
+                self.collect_dependencies(&member.type_, found);
+            }
+        }
+    }
+
+    pub fn type_hash(&self, ty: &str) -> Result<H256, TypedDataError> {
+        Ok(keccak(self.encode_type(ty)?.as_bytes()))
+    }
+
+    pub fn hash_struct(
+        &self, ty: &str, value: &Value,
+    ) -> Result<H256, TypedDataError> {
+        Ok(keccak(&self.encode_data(ty, value)?))
+    }
+
+    fn encode_data(
+        &self, ty: &str, value: &Value,
+    ) -> Result<Vec<u8>, TypedDataError> {
+        let members = self
+            .types
+            .get(ty)
+            .ok_or_else(|| TypedDataError::UnknownType(ty.into()))?;
+        let object = value
+            .as_object()
+            .ok_or_else(|| invalid_value(ty, "expected an object"))?;
+
+        let mut encoded = self.type_hash(ty)?.as_bytes().to_vec();
+        for member in members {
+            let field = object.get(&member.name).ok_or_else(|| {
+                TypedDataError::MissingField(ty.into(), member.name.clone())
+            })?;
+            encoded.extend_from_slice(
+                self.encode_field(&member.type_, field)?.as_bytes(),
+            );
+        }
+        Ok(encoded)
+    }
+
+    /// Encode a field value into one 32-byte word. Dynamic values, arrays
+    /// and structs are replaced by their hashes.
+    fn encode_field(
+        &self, ty: &str, value: &Value,
+    ) -> Result<H256, TypedDataError> {
+        if let Some((item_type, length)) = parse_array_type(ty)? {
+            let items = value
+                .as_array()
+                .ok_or_else(|| invalid_value(ty, "expected an array"))?;
+            if let Some(length) = length {
+                if items.len() != length {
+                    return Err(invalid_value(
+                        ty,
+                        &format!("expected {} items", length),
+                    ));
+                }
+            }
+            let mut encoded = Vec::with_capacity(items.len() * 32);
+            for item in items {
+                encoded.extend_from_slice(
+                    self.encode_field(item_type, item)?.as_bytes(),
+                );
+            }
+            return Ok(keccak(&encoded));
+        }
+
+        if self.types.contains_key(ty) {
+            return self.hash_struct(ty, value);
+        }
+
+        match ty {
+            "string" => Ok(keccak(
+                value
+                    .as_str()
+                    .ok_or_else(|| invalid_value(ty, "expected a string"))?
+                    .as_bytes(),
+            )),
+            "bytes" => Ok(keccak(&parse_hex(ty, value)?)),
+            "bool" => Ok(word(
+                value
+                    .as_bool()
+                    .ok_or_else(|| invalid_value(ty, "expected a bool"))?
+                    .abi_encode(),
+            )),
+            "address" => {
+                let bytes = parse_hex(ty, value)?;
+                if bytes.len() != 20 {
+                    return Err(invalid_value(ty, "expected 20 bytes"));
+                }
+                Ok(word(Address::from_slice(&bytes).abi_encode()))
+            }
+            _ if ty.starts_with("bytes") => {
+                let size: usize = ty[5..]
+                    .parse()
+                    .map_err(|_| TypedDataError::UnknownType(ty.into()))?;
+                if size == 0 || size > 32 {
+                    return Err(TypedDataError::UnknownType(ty.into()));
+                }
+                let bytes = parse_hex(ty, value)?;
+                if bytes.len() != size {
+                    return Err(invalid_value(
+                        ty,
+                        &format!("expected {} bytes", size),
+                    ));
+                }
+                let mut encoded = H256::zero();
+                encoded.as_bytes_mut()[..size].copy_from_slice(&bytes);
+                Ok(encoded)
+            }
+            _ if ty.starts_with("uint") => {
+                Ok(word(parse_uint(ty, value)?.abi_encode()))
+            }
+            _ if ty.starts_with("int") => {
+                Ok(word(parse_int(ty, value)?.abi_encode()))
+            }
+            _ => Err(TypedDataError::UnknownType(ty.into())),
+        }
+    }
+}
+
+fn keccak(data: &[u8]) -> H256 { H256::from(data.keccak256()) }
+
+fn word(encoded: Vec<u8>) -> H256 { H256::from_slice(&encoded) }
+
+fn invalid_value(ty: &str, reason: &str) -> TypedDataError {
+    TypedDataError::InvalidValue(ty.into(), reason.into())
+}
+
+/// Strip all array suffixes, e.g. `Person[][2]` -> `Person`.
+fn base_type(ty: &str) -> &str { ty.split('[').next().unwrap_or(ty) }
+
+/// Split `T[n]` or `T[]` into `T` and the optional fixed length.
+fn parse_array_type(
+    ty: &str,
+) -> Result<Option<(&str, Option<usize>)>, TypedDataError> {
+    if !ty.ends_with(']') {
+        return Ok(None);
+    }
+    let open = ty
+        .rfind('[')
+        .ok_or_else(|| TypedDataError::UnknownType(ty.into()))?;
+    let length = &ty[open + 1..ty.len() - 1];
+    let length = if length.is_empty() {
+        None
+    } else {
+        Some(
+            length
+                .parse()
+                .map_err(|_| TypedDataError::UnknownType(ty.into()))?,
+        )
+    };
+    Ok(Some((&ty[..open], length)))
+}
+
+/// The bit size of `uint<N>` / `int<N>`, 256 if omitted.
+fn parse_bits(ty: &str, suffix: &str) -> Result<usize, TypedDataError> {
+    if suffix.is_empty() {
+        return Ok(256);
+    }
+    match suffix.parse::<usize>() {
+        Ok(bits) if bits > 0 && bits <= 256 && bits % 8 == 0 => Ok(bits),
+        _ => Err(TypedDataError::UnknownType(ty.into())),
+    }
+}
+
+fn parse_hex(ty: &str, value: &Value) -> Result<Vec<u8>, TypedDataError> {
+    let s = value
+        .as_str()
+        .ok_or_else(|| invalid_value(ty, "expected a hex string"))?;
+    if !s.starts_with("0x") {
+        return Err(invalid_value(ty, "missing 0x prefix"));
+    }
+    s[2..]
+        .from_hex()
+        .map_err(|e| invalid_value(ty, &format!("{}", e)))
+}
+
+/// Parse an unsigned magnitude given as a JSON number, a decimal string or a
+/// 0x-prefixed hex string.
+fn parse_magnitude(ty: &str, s: &str) -> Result<U256, TypedDataError> {
+    if s.starts_with("0x") {
+        s[2..]
+            .parse()
+            .map_err(|_| invalid_value(ty, "invalid hex number"))
+    } else {
+        U256::from_dec_str(s)
+            .map_err(|_| invalid_value(ty, "invalid decimal number"))
+    }
+}
+
+fn parse_uint(ty: &str, value: &Value) -> Result<U256, TypedDataError> {
+    let bits = parse_bits(ty, &ty["uint".len()..])?;
+    let number = match value {
+        Value::Number(n) => n
+            .as_u64()
+            .map(U256::from)
+            .ok_or_else(|| invalid_value(ty, "expected an unsigned integer"))?,
+        Value::String(s) => parse_magnitude(ty, s)?,
+        _ => return Err(invalid_value(ty, "expected a number")),
+    };
+    if bits < 256 && number >= U256::one() << bits {
+        return Err(invalid_value(ty, "number out of range"));
+    }
+    Ok(number)
+}
+
+/// Parse a signed integer into its two's complement representation.
+fn parse_int(ty: &str, value: &Value) -> Result<U256, TypedDataError> {
+    let bits = parse_bits(ty, &ty["int".len()..])?;
+    let (negative, magnitude) = match value {
+        Value::Number(n) => {
+            let n = n
+                .as_i64()
+                .ok_or_else(|| invalid_value(ty, "expected an integer"))?;
+            (n < 0, U256::from(n.wrapping_abs() as u64))
+        }
+        Value::String(s) if s.starts_with('-') => {
+            (true, parse_magnitude(ty, &s[1..])?)
+        }
+        Value::String(s) => (false, parse_magnitude(ty, s)?),
+        _ => return Err(invalid_value(ty, "expected a number")),
+    };
+    let limit = U256::one() << (bits - 1);
+    if (negative && magnitude > limit) || (!negative && magnitude >= limit) {
+        return Err(invalid_value(ty, "number out of range"));
+    }
+    if negative {
+        Ok(U256::zero().overflowing_sub(magnitude).0)
+    } else {
+        Ok(magnitude)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    const MAIL: &str = r#"{
+        "types": {
+            "EIP712Domain": [
+                { "name": "name", "type": "string" },
+                { "name": "version", "type": "string" },
+                { "name": "chainId", "type": "uint256" },
+                { "name": "verifyingContract", "type": "address" }
+            ],
+            "Person": [
+                { "name": "name", "type": "string" },
+                { "name": "wallet", "type": "address" }
+            ],
+            "Mail": [
+                { "name": "from", "type": "Person" },
+                { "name": "to", "type": "Person" },
+                { "name": "contents", "type": "string" }
+            ]
+        },
+        "primaryType": "Mail",
+        "domain": {
+            "name": "Ether Mail",
+            "version": "1",
+            "chainId": 1,
+            "verifyingContract": "0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC"
+        },
+        "message": {
+            "from": {
+                "name": "Cow",
+                "wallet": "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826"
+            },
+            "to": {
+                "name": "Bob",
+                "wallet": "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB"
+            },
+            "contents": "Hello, Bob!"
+        }
+    }"#;
+
+    fn h256(s: &str) -> H256 { H256::from_str(s).unwrap() }
+
+    #[test]
+    fn test_encode_type() {
+        let typed_data = TypedData::from_json(MAIL).unwrap();
+        assert_eq!(
+            typed_data.encode_type("Mail").unwrap(),
+            "Mail(Person from,Person to,string contents)Person(string name,address wallet)"
+        );
+        assert!(typed_data.encode_type("Unknown").is_err());
+    }
+
+    #[test]
+    fn test_hash() {
+        let typed_data = TypedData::from_json(MAIL).unwrap();
+        assert_eq!(
+            typed_data.domain_separator().unwrap(),
+            h256("f2cee375fa42b42143804025fc449deafd50cc031ca257e0b194a650a912090f")
+        );
+        assert_eq!(
+            typed_data
+                .hash_struct("Mail", &typed_data.message)
+                .unwrap(),
+            h256("c52c0ee5d84264471806290a3f2c4cecfc5490626bf912d01f240d7a274b371e")
+        );
+        assert_eq!(
+            typed_data.hash().unwrap(),
+            h256("be609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2")
+        );
+    }
+
+    #[test]
+    fn test_chain_id() {
+        let typed_data = TypedData::from_json(MAIL).unwrap();
+        assert_eq!(typed_data.chain_id(), Some(U256::one()));
+        assert!(typed_data.check_chain_id(U256::one()).is_ok());
+        assert!(typed_data.check_chain_id(U256::from(1029)).is_err());
+    }
+
+    #[test]
+    fn test_parse_int() {
+        assert_eq!(
+            parse_int("int8", &Value::from(-1)).unwrap(),
+            U256::max_value()
+        );
+        assert_eq!(parse_int("int8", &Value::from(127)).unwrap(), 127.into());
+        assert!(parse_int("int8", &Value::from(128)).is_err());
+        assert!(parse_int("int8", &Value::from("-128")).is_ok());
+        assert!(parse_int("int8", &Value::from("-129")).is_err());
+        assert!(parse_uint("uint8", &Value::from("0x100")).is_err());
+        assert!(parse_uint("uint7", &Value::from(1)).is_err());
+    }
+}
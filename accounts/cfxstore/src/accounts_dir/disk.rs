@@ -360,6 +360,16 @@ where T: KeyFileManager
         Ok(Box::new(vault_dir))
     }
 
+    fn delete(&self, name: &str, key: VaultKey) -> Result<(), Error> {
+        let vault_dir = VaultDiskDirectory::at(&self.path, name, key)?;
+        // accounts are never deleted along with the vault => they must be
+        // moved out or removed first
+        if !vault_dir.load()?.is_empty() {
+            return Err(Error::VaultNotEmpty);
+        }
+        vault_dir.delete()
+    }
+
     fn list_vaults(&self) -> Result<Vec<String>, Error> {
         Ok(fs::read_dir(&self.path)?
             .filter_map(|e| e.ok().map(|e| e.path()))
@@ -75,6 +75,8 @@ pub trait VaultKeyDirectoryProvider {
     fn open(
         &self, name: &str, key: VaultKey,
     ) -> Result<Box<dyn VaultKeyDirectory>, Error>;
+    /// Delete existing empty vault with given key
+    fn delete(&self, name: &str, key: VaultKey) -> Result<(), Error>;
     /// List all vaults
     fn list_vaults(&self) -> Result<Vec<String>, Error>;
     /// Get vault meta
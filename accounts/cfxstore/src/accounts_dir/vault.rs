@@ -131,7 +131,8 @@ impl VaultDiskDirectory {
         Ok(())
     }
 
-    fn delete(&self) -> Result<(), Error> {
+    /// Delete the vault directory with all its content
+    pub fn delete(&self) -> Result<(), Error> {
         let path = self.path().expect("self is instance of DiskDirectory; DiskDirectory always returns path; qed");
         fs::remove_dir_all(path).map_err(Into::into)
     }
@@ -179,6 +179,12 @@ impl SimpleSecretStore for CfxStore {
         self.store.close_vault(name)
     }
 
+    fn delete_vault(
+        &self, name: &str, password: &Password,
+    ) -> Result<(), Error> {
+        self.store.delete_vault(name, password)
+    }
+
     fn list_vaults(&self) -> Result<Vec<String>, Error> {
         self.store.list_vaults()
     }
@@ -819,6 +825,17 @@ impl SimpleSecretStore for CfxMultiStore {
         Ok(())
     }
 
+    fn delete_vault(
+        &self, name: &str, password: &Password,
+    ) -> Result<(), Error> {
+        let vault_provider = self
+            .dir
+            .as_vault_provider()
+            .ok_or(Error::VaultsAreNotSupported)?;
+        vault_provider.delete(name, VaultKey::new(password, self.iterations))?;
+        self.close_vault(name)
+    }
+
     fn list_vaults(&self) -> Result<Vec<String>, Error> {
         let vault_provider = self
             .dir
@@ -1359,6 +1376,41 @@ mod tests {
         assert_eq!(store.accounts().unwrap().len(), 0);
     }
 
+    #[test]
+    fn should_delete_empty_vault() {
+        // given
+        let mut dir = RootDiskDirectoryGuard::new();
+        let store = CfxStore::open(dir.key_dir.take().unwrap()).unwrap();
+        let name1 = "vault1";
+        let password1 = "password1".into();
+        let password2 = "password2".into();
+        let keypair1 = keypair();
+
+        // when
+        store.create_vault(name1, &password1).unwrap();
+        let account1 = store
+            .insert_account(
+                SecretVaultRef::Vault(name1.to_owned()),
+                keypair1.secret().clone(),
+                &password1,
+            )
+            .unwrap();
+
+        // then [can not delete with wrong password or accounts inside]
+        store.delete_vault(name1, &password2).unwrap_err();
+        store.delete_vault(name1, &password1).unwrap_err();
+        assert_eq!(store.list_vaults().unwrap(), vec![name1.to_owned()]);
+
+        // and when
+        store.remove_account(&account1, &password1).unwrap();
+        store.delete_vault(name1, &password1).unwrap();
+
+        // then [vault is closed and removed]
+        assert!(store.list_vaults().unwrap().is_empty());
+        assert!(store.list_opened_vaults().unwrap().is_empty());
+        store.open_vault(name1, &password1).unwrap_err();
+    }
+
     #[test]
     fn should_not_remove_account_from_vault_when_password_is_incorrect() {
         // given
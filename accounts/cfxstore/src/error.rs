@@ -14,7 +14,7 @@
 // You should have received a copy of the GNU General Public License
 // along with Parity Ethereum.  If not, see <http://www.gnu.org/licenses/>.
 
-use cfxkey::{self, DerivationError, Error as EthKeyError};
+use cfxkey::{self, DerivationError, Error as EthKeyError, MnemonicError};
 use crypto::{self, Error as EthCryptoError};
 use std::{fmt, io::Error as IoError};
 
@@ -43,6 +43,8 @@ pub enum Error {
     InvalidVaultName,
     /// Vault not found
     VaultNotFound,
+    /// Vault still contains accounts
+    VaultNotEmpty,
     /// Account creation failed.
     CreationFailed,
     /// `EthKey` error
@@ -53,6 +55,8 @@ pub enum Error {
     EthCrypto(EthCryptoError),
     /// Derivation error
     Derivation(DerivationError),
+    /// Mnemonic error
+    Mnemonic(MnemonicError),
     /// Custom error
     Custom(String),
 }
@@ -75,6 +79,7 @@ impl fmt::Display for Error {
             }
             Error::InvalidVaultName => "Invalid vault name".into(),
             Error::VaultNotFound => "Vault not found".into(),
+            Error::VaultNotEmpty => "Vault is not empty".into(),
             Error::CreationFailed => "Account creation failed".into(),
             Error::EthKey(ref err) => err.to_string(),
             Error::EthKeyCrypto(ref err) => err.to_string(),
@@ -82,6 +87,7 @@ impl fmt::Display for Error {
             Error::Derivation(ref err) => {
                 format!("Derivation error: {:?}", err)
             }
+            Error::Mnemonic(ref err) => err.to_string(),
             Error::Custom(ref s) => s.clone(),
         };
 
@@ -120,3 +126,7 @@ impl From<crypto::error::SymmError> for Error {
 impl From<DerivationError> for Error {
     fn from(err: DerivationError) -> Self { Error::Derivation(err) }
 }
+
+impl From<MnemonicError> for Error {
+    fn from(err: MnemonicError) -> Self { Error::Mnemonic(err) }
+}
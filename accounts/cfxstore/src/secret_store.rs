@@ -15,7 +15,10 @@
 // along with Parity Ethereum.  If not, see <http://www.gnu.org/licenses/>.
 
 use cfx_types::H256;
-use cfxkey::{Address, Message, Password, Public, Secret, Signature};
+use cfxkey::{
+    Address, DerivationPath, Message, Mnemonic, Password, Public, Secret,
+    Signature,
+};
 use json::{OpaqueKeyFile, Uuid};
 use std::{
     cmp::Ordering,
@@ -134,6 +137,10 @@ pub trait SimpleSecretStore: Send + Sync {
     fn open_vault(&self, name: &str, password: &Password) -> Result<(), Error>;
     /// Close vault
     fn close_vault(&self, name: &str) -> Result<(), Error>;
+    /// Delete empty vault with given password
+    fn delete_vault(
+        &self, name: &str, password: &Password,
+    ) -> Result<(), Error>;
     /// List all vaults
     fn list_vaults(&self) -> Result<Vec<String>, Error>;
     /// List all currently opened vaults
@@ -171,6 +178,16 @@ pub trait SecretStore: SimpleSecretStore {
         &self, vault: SecretVaultRef, json: &[u8], password: &Password,
         gen_id: bool,
     ) -> Result<StoreAccountRef, Error>;
+    /// Imports the account derived at `path` from a BIP39 mnemonic phrase
+    /// and an optional `passphrase`, encrypting it with `password`.
+    fn import_mnemonic(
+        &self, vault: SecretVaultRef, phrase: &str, passphrase: &str,
+        path: &DerivationPath, password: &Password,
+    ) -> Result<StoreAccountRef, Error>
+    {
+        let keypair = Mnemonic::from_phrase(phrase)?.derive(passphrase, path)?;
+        self.insert_account(vault, keypair.secret().clone(), password)
+    }
     /// Copies account between stores and vaults.
     fn copy_account(
         &self, new_store: &dyn SimpleSecretStore, new_vault: SecretVaultRef,
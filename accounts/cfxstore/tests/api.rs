@@ -23,8 +23,11 @@ mod util;
 use cfx_types::Address;
 use cfxstore::{
     accounts_dir::RootDiskDirectory,
-    cfxkey::{verify_address, Generator, KeyPair, Random, Secret},
-    CfxStore, SecretVaultRef, SimpleSecretStore, StoreAccountRef,
+    cfxkey::{
+        verify_address, DerivationPath, Generator, KeyPair, Mnemonic, Random,
+        Secret,
+    },
+    CfxStore, SecretStore, SecretVaultRef, SimpleSecretStore, StoreAccountRef,
 };
 use std::str::FromStr;
 use util::TransientDir;
@@ -113,6 +116,35 @@ fn secret_store_remove_account() {
     assert!(store.remove_account(&accounts[0], &"".into()).is_err());
 }
 
+#[test]
+fn secret_store_import_mnemonic() {
+    let dir = TransientDir::create().unwrap();
+    let store = CfxStore::open(Box::new(dir)).unwrap();
+    let mnemonic = Mnemonic::random(12).unwrap();
+    let path = DerivationPath::bip44(0, 0, 1);
+    let expected = mnemonic.derive("", &path).unwrap().address();
+
+    let account = store
+        .import_mnemonic(
+            SecretVaultRef::Root,
+            mnemonic.phrase(),
+            "",
+            &path,
+            &"".into(),
+        )
+        .unwrap();
+    assert_eq!(account.address, expected);
+    assert!(store
+        .import_mnemonic(
+            SecretVaultRef::Root,
+            "not a valid mnemonic",
+            "",
+            &path,
+            &"".into(),
+        )
+        .is_err());
+}
+
 fn test_path() -> &'static str {
     match ::std::fs::metadata("cfxstore") {
         Ok(_) => "cfxstore/tests/res/geth_keystore",
@@ -16,8 +16,11 @@
 
 use std::fmt;
 
+use cfxkey::TypedDataError;
 use cfxstore::Error as SSError;
 
+use crate::signer::SignerError;
+
 /// Signing error
 #[derive(Debug)]
 pub enum SignError {
@@ -27,6 +30,10 @@ pub enum SignError {
     NotFound,
     /// Low-level error from store
     SStore(SSError),
+    /// Typed data cannot be hashed
+    TypedData(TypedDataError),
+    /// Error from the external signer
+    External(SignerError),
 }
 
 impl fmt::Display for SignError {
@@ -35,6 +42,8 @@ impl fmt::Display for SignError {
             SignError::NotUnlocked => write!(f, "Account is locked"),
             SignError::NotFound => write!(f, "Account does not exist"),
             SignError::SStore(ref e) => write!(f, "{}", e),
+            SignError::TypedData(ref e) => write!(f, "{}", e),
+            SignError::External(ref e) => write!(f, "{}", e),
         }
     }
 }
@@ -42,3 +51,11 @@ impl fmt::Display for SignError {
 impl From<SSError> for SignError {
     fn from(e: SSError) -> Self { SignError::SStore(e) }
 }
+
+impl From<TypedDataError> for SignError {
+    fn from(e: TypedDataError) -> Self { SignError::TypedData(e) }
+}
+
+impl From<SignerError> for SignError {
+    fn from(e: SignerError) -> Self { SignError::External(e) }
+}
@@ -20,6 +20,7 @@
 
 mod account_data;
 mod error;
+mod signer;
 mod stores;
 
 use self::{
@@ -29,10 +30,14 @@ use self::{
 
 use std::{
     collections::HashMap,
+    sync::Arc,
     time::{Duration, Instant},
 };
 
-use cfxkey::{Address, Generator, Message, Password, Public, Random, Secret};
+use cfxkey::{
+    verify_address, Address, Generator, Message, Password, Public, Random,
+    Secret,
+};
 use cfxstore::{
     accounts_dir::MemoryDirectory, random_string, CfxMultiStore, CfxStore,
     OpaqueSecret, SecretStore, SecretVaultRef, SimpleSecretStore,
@@ -41,10 +46,17 @@ use cfxstore::{
 use log::warn;
 use parking_lot::RwLock;
 
-pub use cfxkey::Signature;
+pub use cfxkey::{Signature, TypedData, TypedDataError};
 pub use cfxstore::{Derivation, Error, IndexDerivation, KeyFile};
 
-pub use self::{account_data::AccountMeta, error::SignError};
+pub use self::{
+    account_data::AccountMeta,
+    error::SignError,
+    signer::{
+        ExternalSigner, SignRequest, SignRequestKind, SignerBackend,
+        SignerEndpoint, SignerError,
+    },
+};
 
 type AccountToken = Password;
 
@@ -75,6 +87,8 @@ pub struct AccountProvider {
     unlock_keep_secret: bool,
     /// Disallowed accounts.
     blacklisted_accounts: Vec<Address>,
+    /// Signs for the accounts which are not in the local store.
+    signer_backend: Option<Arc<dyn SignerBackend>>,
 }
 
 fn transient_sstore() -> CfxMultiStore {
@@ -111,6 +125,7 @@ impl AccountProvider {
             transient_sstore: transient_sstore(),
             unlock_keep_secret: settings.unlock_keep_secret,
             blacklisted_accounts: settings.blacklisted_accounts,
+            signer_backend: None,
         }
     }
 
@@ -127,9 +142,16 @@ impl AccountProvider {
             transient_sstore: transient_sstore(),
             unlock_keep_secret: false,
             blacklisted_accounts: vec![],
+            signer_backend: None,
         }
     }
 
+    /// Delegates signing for the accounts which are not in the local store to
+    /// `backend`.
+    pub fn set_signer_backend(&mut self, backend: Arc<dyn SignerBackend>) {
+        self.signer_backend = Some(backend);
+    }
+
     /// Creates new random account.
     pub fn new_account(&self, password: &Password) -> Result<Address, Error> {
         self.new_account_and_public(password).map(|d| d.0)
@@ -486,6 +508,54 @@ impl AccountProvider {
         }
     }
 
+    /// Signs the hash of `request`. Accounts in the local store are signed as
+    /// with `sign`, other accounts are delegated to the signer backend if one
+    /// is set. A signature from the backend is only accepted if it recovers
+    /// to the requested address.
+    pub fn sign_request(
+        &self, request: SignRequest, password: Option<Password>,
+    ) -> Result<Signature, SignError> {
+        if self.sstore.account_ref(&request.address).is_err() {
+            if let Some(ref backend) = self.signer_backend {
+                let signature = backend.sign(&request)?;
+                return match verify_address(
+                    &request.address,
+                    &signature,
+                    &request.hash,
+                ) {
+                    Ok(true) => Ok(signature),
+                    Ok(false) => Err(SignerError::InvalidResponse(format!(
+                        "signature is not from {:?}",
+                        request.address
+                    ))
+                    .into()),
+                    Err(e) => {
+                        Err(SignerError::InvalidResponse(e.to_string()).into())
+                    }
+                };
+            }
+        }
+        self.sign(request.address, password, request.hash)
+    }
+
+    /// Signs the hash of the typed structured data. The domain of the data
+    /// must be bound to `chain_id`. If password is not provided the account
+    /// must be unlocked.
+    pub fn sign_typed_data(
+        &self, address: Address, password: Option<Password>,
+        typed_data: &TypedData, chain_id: u32,
+    ) -> Result<Signature, SignError>
+    {
+        typed_data.check_chain_id(chain_id.into())?;
+        let request = SignRequest {
+            kind: SignRequestKind::TypedData,
+            address,
+            hash: typed_data.hash()?,
+            details: serde_json::to_value(typed_data).unwrap_or_default(),
+        };
+        self.sign_request(request, password)
+    }
+
     /// Signs message using the derived secret. If password is not provided the
     /// account must be unlocked.
     pub fn sign_derived(
@@ -634,6 +704,13 @@ impl AccountProvider {
         self.sstore.close_vault(name).map_err(Into::into)
     }
 
+    /// Delete existing empty vault.
+    pub fn delete_vault(
+        &self, name: &str, password: &Password,
+    ) -> Result<(), Error> {
+        self.sstore.delete_vault(name, password).map_err(Into::into)
+    }
+
     /// List all vaults
     pub fn list_vaults(&self) -> Result<Vec<String>, Error> {
         self.sstore.list_vaults().map_err(Into::into)
@@ -682,11 +759,44 @@ impl AccountProvider {
 
 #[cfg(test)]
 mod tests {
-    use super::{AccountProvider, Unlock};
+    use super::{
+        AccountProvider, SignError, SignRequest, SignRequestKind,
+        SignerBackend, SignerError, TypedData, Unlock,
+    };
     use cfx_types::H256;
-    use cfxkey::{Address, Generator, Random};
+    use cfxkey::{sign, Address, Generator, KeyPair, Random, Signature};
     use cfxstore::{Derivation, StoreAccountRef};
-    use std::time::{Duration, Instant};
+    use std::{
+        sync::Arc,
+        time::{Duration, Instant},
+    };
+
+    /// Signs with a key held outside of the provider.
+    struct StubSigner(Option<KeyPair>);
+
+    /// Signs every request with its own key, whatever the requested account.
+    struct WrongKeySigner(KeyPair);
+
+    impl SignerBackend for WrongKeySigner {
+        fn sign(
+            &self, request: &SignRequest,
+        ) -> Result<Signature, SignerError> {
+            Ok(sign(self.0.secret(), &request.hash).unwrap())
+        }
+    }
+
+    impl SignerBackend for StubSigner {
+        fn sign(
+            &self, request: &SignRequest,
+        ) -> Result<Signature, SignerError> {
+            match self.0 {
+                Some(ref kp) if kp.address() == request.address => {
+                    Ok(sign(kp.secret(), &request.hash).unwrap())
+                }
+                _ => Err(SignerError::Rejected("unknown account".into())),
+            }
+        }
+    }
 
     #[test]
     fn unlock_account_temp() {
@@ -894,4 +1004,89 @@ mod tests {
         );
         assert_eq!(ap.accounts().unwrap(), vec![]);
     }
+
+    #[test]
+    fn sign_request_should_delegate_unknown_accounts() {
+        let external = Random.generate().unwrap();
+        let request = |address| SignRequest {
+            kind: SignRequestKind::Message,
+            address,
+            hash: H256::from_low_u64_be(1),
+            details: serde_json::Value::Null,
+        };
+
+        let mut ap = AccountProvider::transient_provider();
+        let local = ap.new_account(&"test".into()).unwrap();
+        assert!(ap.sign_request(request(external.address()), None).is_err());
+
+        ap.set_signer_backend(Arc::new(StubSigner(Some(external.clone()))));
+        let signature =
+            ap.sign_request(request(external.address()), None).unwrap();
+        assert_eq!(
+            signature,
+            sign(external.secret(), &H256::from_low_u64_be(1)).unwrap()
+        );
+        // Local accounts are still signed by the store.
+        assert!(ap.sign_request(request(local), Some("test".into())).is_ok());
+
+        ap.set_signer_backend(Arc::new(StubSigner(None)));
+        match ap.sign_request(request(external.address()), None) {
+            Err(SignError::External(SignerError::Rejected(_))) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn sign_typed_data_should_delegate_unknown_accounts() {
+        let typed_data = TypedData::from_json(
+            r#"{
+                "types": {
+                    "EIP712Domain": [
+                        { "name": "name", "type": "string" },
+                        { "name": "chainId", "type": "uint256" }
+                    ],
+                    "Mail": [{ "name": "contents", "type": "string" }]
+                },
+                "primaryType": "Mail",
+                "domain": { "name": "Ether Mail", "chainId": 1 },
+                "message": { "contents": "Hello, Bob!" }
+            }"#,
+        )
+        .unwrap();
+        let external = Random.generate().unwrap();
+        let mut ap = AccountProvider::transient_provider();
+        ap.set_signer_backend(Arc::new(StubSigner(Some(external.clone()))));
+
+        let signature = ap
+            .sign_typed_data(external.address(), None, &typed_data, 1)
+            .unwrap();
+        assert_eq!(
+            signature,
+            sign(external.secret(), &typed_data.hash().unwrap()).unwrap()
+        );
+        // The chain id is checked before the signer is asked.
+        match ap.sign_typed_data(external.address(), None, &typed_data, 2) {
+            Err(SignError::TypedData(_)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn sign_request_should_reject_signature_from_wrong_key() {
+        let external = Random.generate().unwrap();
+        let mut ap = AccountProvider::transient_provider();
+        ap.set_signer_backend(Arc::new(WrongKeySigner(
+            Random.generate().unwrap(),
+        )));
+        let request = SignRequest {
+            kind: SignRequestKind::Transaction,
+            address: external.address(),
+            hash: H256::from_low_u64_be(1),
+            details: serde_json::Value::Null,
+        };
+        match ap.sign_request(request, None) {
+            Err(SignError::External(SignerError::InvalidResponse(_))) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
 }
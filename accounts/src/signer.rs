@@ -0,0 +1,388 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! Delegation of signing to an external signer process.
+//!
+//! The node sends a line-delimited JSON-RPC 2.0 request
+//! `signer_sign([SignRequest])` over a local TCP or Unix socket connection.
+//! The signer answers with the 65-byte `r || s || v` signature in hex, or a
+//! JSON-RPC error if the request is rejected.
+
+use cfx_types::{Address, H256};
+use cfxkey::Signature;
+use serde_derive::Serialize;
+use serde_json::{json, Value};
+#[cfg(unix)]
+use std::{os::unix::net::UnixStream, path::Path, sync::mpsc, thread};
+use std::{
+    fmt,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    path::PathBuf,
+    str::FromStr,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// What the signer is asked to sign.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SignRequestKind {
+    /// A transaction, `hash` is the transaction hash.
+    Transaction,
+    /// A message, `hash` is the prefixed message hash.
+    Message,
+    /// Typed structured data, `hash` is the typed data hash.
+    TypedData,
+}
+
+/// A structured request for the signer to approve and sign.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignRequest {
+    /// Kind of the signed payload.
+    pub kind: SignRequestKind,
+    /// The account to sign with.
+    pub address: Address,
+    /// The hash to sign.
+    pub hash: H256,
+    /// The payload to be shown to the approver, e.g. the transaction fields.
+    pub details: Value,
+}
+
+/// External signer errors.
+#[derive(Debug)]
+pub enum SignerError {
+    /// The signer did not answer in time.
+    Timeout,
+    /// The signer refused to sign the request.
+    Rejected(String),
+    /// The signer cannot be reached.
+    Transport(String),
+    /// The signer answered with a malformed response.
+    InvalidResponse(String),
+}
+
+impl fmt::Display for SignerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            SignerError::Timeout => write!(f, "External signer timed out"),
+            SignerError::Rejected(ref reason) => {
+                write!(f, "External signer rejected the request: {}", reason)
+            }
+            SignerError::Transport(ref reason) => {
+                write!(f, "External signer is unavailable: {}", reason)
+            }
+            SignerError::InvalidResponse(ref reason) => write!(
+                f,
+                "Invalid response from the external signer: {}",
+                reason
+            ),
+        }
+    }
+}
+
+impl From<io::Error> for SignerError {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => {
+                SignerError::Timeout
+            }
+            _ => SignerError::Transport(e.to_string()),
+        }
+    }
+}
+
+/// A backend which signs on behalf of accounts whose keys are not in the
+/// local key store.
+pub trait SignerBackend: Send + Sync {
+    /// Asks for approval of `request` and returns the signature.
+    fn sign(&self, request: &SignRequest) -> Result<Signature, SignerError>;
+}
+
+/// Where the external signer listens.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SignerEndpoint {
+    /// `tcp://<host>:<port>`
+    Tcp(SocketAddr),
+    /// `ipc://<path>`, a Unix domain socket.
+    Ipc(PathBuf),
+}
+
+impl FromStr for SignerEndpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(address) = s.strip_prefix("tcp://") {
+            let address = address
+                .to_socket_addrs()
+                .map_err(|e| format!("Invalid signer address {}: {}", s, e))?
+                .next()
+                .ok_or_else(|| format!("Invalid signer address {}", s))?;
+            Ok(SignerEndpoint::Tcp(address))
+        } else if let Some(path) = s.strip_prefix("ipc://") {
+            Ok(SignerEndpoint::Ipc(PathBuf::from(path)))
+        } else {
+            Err(format!(
+                "Invalid signer endpoint {}, expected tcp://<host>:<port> or ipc://<path>",
+                s
+            ))
+        }
+    }
+}
+
+/// Signs through an external process over a local JSON-RPC connection. A new
+/// connection is opened for every request.
+pub struct ExternalSigner {
+    endpoint: SignerEndpoint,
+    /// Bound of each of connecting, sending the request and waiting for the
+    /// answer. It should leave a human approver enough time.
+    timeout: Duration,
+    next_id: AtomicU64,
+}
+
+impl ExternalSigner {
+    /// Creates a signer talking to `endpoint`.
+    pub fn new(endpoint: SignerEndpoint, timeout: Duration) -> Self {
+        ExternalSigner {
+            endpoint,
+            timeout,
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    fn call(&self, method: &str, params: Value) -> Result<Value, SignerError> {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": self.next_id.fetch_add(1, Ordering::Relaxed),
+            "method": method,
+            "params": params,
+        });
+        match self.endpoint {
+            SignerEndpoint::Tcp(ref address) => {
+                let stream = TcpStream::connect_timeout(address, self.timeout)?;
+                stream.set_read_timeout(Some(self.timeout))?;
+                stream.set_write_timeout(Some(self.timeout))?;
+                roundtrip(stream, &request)
+            }
+            #[cfg(unix)]
+            SignerEndpoint::Ipc(ref path) => {
+                let stream = connect_unix_timeout(path, self.timeout)?;
+                stream.set_read_timeout(Some(self.timeout))?;
+                stream.set_write_timeout(Some(self.timeout))?;
+                roundtrip(stream, &request)
+            }
+            #[cfg(not(unix))]
+            SignerEndpoint::Ipc(_) => Err(SignerError::Transport(
+                "Unix sockets are not supported on this platform".into(),
+            )),
+        }
+    }
+}
+
+impl SignerBackend for ExternalSigner {
+    fn sign(&self, request: &SignRequest) -> Result<Signature, SignerError> {
+        let result = self.call("signer_sign", json!([request]))?;
+        let signature = result.as_str().ok_or_else(|| {
+            SignerError::InvalidResponse(format!(
+                "expected a hex signature, got {}",
+                result
+            ))
+        })?;
+        signature
+            .trim_start_matches("0x")
+            .parse()
+            .map_err(|e| SignerError::InvalidResponse(format!("{}", e)))
+    }
+}
+
+/// Connects to the Unix socket at `path` within `timeout`. There is no
+/// `connect_timeout` for Unix sockets, so the connection is made on a helper
+/// thread, which is abandoned if the signer never accepts it.
+#[cfg(unix)]
+fn connect_unix_timeout(
+    path: &Path, timeout: Duration,
+) -> Result<UnixStream, SignerError> {
+    let (sender, receiver) = mpsc::channel();
+    let path = path.to_owned();
+    thread::Builder::new()
+        .name("External Signer Connect".into())
+        .spawn(move || {
+            let _ = sender.send(UnixStream::connect(path));
+        })?;
+    match receiver.recv_timeout(timeout) {
+        Ok(stream) => Ok(stream?),
+        Err(mpsc::RecvTimeoutError::Timeout) => Err(SignerError::Timeout),
+        Err(mpsc::RecvTimeoutError::Disconnected) => Err(
+            SignerError::Transport("failed to connect to the signer".into()),
+        ),
+    }
+}
+
+/// Sends one line-delimited JSON-RPC request and reads the response line.
+fn roundtrip<S: Read + Write>(
+    mut stream: S, request: &Value,
+) -> Result<Value, SignerError> {
+    writeln!(stream, "{}", request)?;
+    stream.flush()?;
+
+    let mut line = String::new();
+    if BufReader::new(stream).read_line(&mut line)? == 0 {
+        return Err(SignerError::Transport(
+            "connection closed by the signer".into(),
+        ));
+    }
+    let mut response: Value = serde_json::from_str(&line)
+        .map_err(|e| SignerError::InvalidResponse(e.to_string()))?;
+    if response["id"] != request["id"] {
+        return Err(SignerError::InvalidResponse(format!(
+            "unexpected response id {}",
+            response["id"]
+        )));
+    }
+    if let Some(error) = response.get("error") {
+        let reason = error["message"]
+            .as_str()
+            .map(ToString::to_string)
+            .unwrap_or_else(|| error.to_string());
+        return Err(SignerError::Rejected(reason));
+    }
+    match response.get_mut("result") {
+        Some(result) => Ok(result.take()),
+        None => Err(SignerError::InvalidResponse("missing result".into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ExternalSigner, SignRequest, SignRequestKind, SignerBackend,
+        SignerEndpoint, SignerError,
+    };
+    use cfx_types::{Address, H256};
+    use serde_json::{json, Value};
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        thread,
+        time::Duration,
+    };
+
+    /// Serves one connection, answering with `respond(request)`.
+    fn serve_once<F>(respond: F) -> SignerEndpoint
+    where F: FnOnce(Value) -> Option<Value> + Send + 'static {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut line = String::new();
+            BufReader::new(stream.try_clone().unwrap())
+                .read_line(&mut line)
+                .unwrap();
+            match respond(serde_json::from_str(&line).unwrap()) {
+                Some(response) => writeln!(stream, "{}", response).unwrap(),
+                // Keep the connection open without answering.
+                None => thread::sleep(Duration::from_secs(2)),
+            }
+        });
+        SignerEndpoint::Tcp(address)
+    }
+
+    fn request() -> SignRequest {
+        SignRequest {
+            kind: SignRequestKind::Transaction,
+            address: Address::from_low_u64_be(1),
+            hash: H256::from_low_u64_be(2),
+            details: json!({ "nonce": "0x0" }),
+        }
+    }
+
+    #[test]
+    fn parse_endpoint() {
+        assert_eq!(
+            "tcp://127.0.0.1:12600".parse(),
+            Ok(SignerEndpoint::Tcp("127.0.0.1:12600".parse().unwrap()))
+        );
+        assert_eq!(
+            "ipc:///tmp/signer.ipc".parse(),
+            Ok(SignerEndpoint::Ipc("/tmp/signer.ipc".into()))
+        );
+        assert!("http://127.0.0.1:12600".parse::<SignerEndpoint>().is_err());
+    }
+
+    #[test]
+    fn sign_approved() {
+        let endpoint = serve_once(|request| {
+            assert_eq!(request["method"], "signer_sign");
+            assert_eq!(request["params"][0]["kind"], "transaction");
+            Some(json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "result": format!("0x{}", "11".repeat(65)),
+            }))
+        });
+        let signer = ExternalSigner::new(endpoint, Duration::from_secs(1));
+        let signature = signer.sign(&request()).unwrap();
+        assert_eq!(&signature[..], &[0x11; 65][..]);
+    }
+
+    #[test]
+    fn sign_rejected() {
+        let endpoint = serve_once(|request| {
+            Some(json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "error": { "code": -32000, "message": "denied" },
+            }))
+        });
+        let signer = ExternalSigner::new(endpoint, Duration::from_secs(1));
+        match signer.sign(&request()) {
+            Err(SignerError::Rejected(reason)) => assert_eq!(reason, "denied"),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn sign_timeout() {
+        let endpoint = serve_once(|_| None);
+        let signer = ExternalSigner::new(endpoint, Duration::from_millis(100));
+        match signer.sign(&request()) {
+            Err(SignerError::Timeout) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn sign_ipc_timeout() {
+        use std::{env, fs, os::unix::net::UnixListener};
+
+        let path = env::temp_dir()
+            .join(format!("conflux_signer_{}.ipc", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        thread::spawn(move || {
+            // Accept without ever answering.
+            let _stream = listener.accept().unwrap();
+            thread::sleep(Duration::from_secs(2));
+        });
+
+        let signer = ExternalSigner::new(
+            SignerEndpoint::Ipc(path.clone()),
+            Duration::from_millis(100),
+        );
+        let result = signer.sign(&request());
+        let _ = fs::remove_file(&path);
+        match result {
+            Err(SignerError::Timeout) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        // Nobody listens at the removed path.
+        match signer.sign(&request()) {
+            Err(SignerError::Transport(_)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,114 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_statedb::{StateDb, StateDbExt};
+use cfx_types::{Address, BigEndianHash, H256, U256};
+use cfxcore::{ConsensusGraph, ConsensusGraphTrait};
+use keccak_hash::keccak;
+use network::{node_table::NodeId, MembershipSource, NetworkService};
+use parking_lot::{Condvar, Mutex};
+use primitives::{EpochNumber, StorageKey, StorageValue};
+use std::{
+    collections::HashSet,
+    sync::{Arc, Weak},
+    thread,
+    time::Duration,
+};
+
+/// Upper bound of the members read from the registry, to bound the work of a
+/// poll if the registry is misconfigured.
+const MAX_REGISTRY_MEMBERS: u64 = 4096;
+
+/// Reads the consortium members from the registry contract in the latest
+/// state.
+///
+/// The members must be the first state variable of the registry, declared as
+/// `bytes32[2][] members` where each member is the 64-byte node id. The length
+/// is then stored in slot 0, and the member `i` in the two slots from
+/// `keccak256(0) + 2 * i`.
+pub fn read_registry_members(
+    consensus: &ConsensusGraph, registry: &Address,
+) -> Result<HashSet<NodeId>, String> {
+    let state_db = consensus
+        .get_state_db_by_epoch_number(EpochNumber::LatestState, "epoch_num")
+        .map_err(|e| format!("failed to get latest state: {}", e))?;
+
+    let len = read_slot(&state_db, registry, U256::zero())?.into_uint();
+    if len > U256::from(MAX_REGISTRY_MEMBERS) {
+        return Err(format!(
+            "too many members in registry: {}, max = {}",
+            len, MAX_REGISTRY_MEMBERS
+        ));
+    }
+
+    let start = keccak(H256::zero()).into_uint();
+    let mut members = HashSet::new();
+    for i in 0..len.as_u64() {
+        let slot = start + U256::from(2 * i);
+        let high = read_slot(&state_db, registry, slot)?;
+        let low = read_slot(&state_db, registry, slot + 1)?;
+        let mut id = NodeId::zero();
+        id.as_bytes_mut()[..32].copy_from_slice(high.as_bytes());
+        id.as_bytes_mut()[32..].copy_from_slice(low.as_bytes());
+        members.insert(id);
+    }
+    Ok(members)
+}
+
+fn read_slot(
+    state_db: &StateDb, address: &Address, slot: U256,
+) -> Result<H256, String> {
+    let position = H256::from_uint(&slot);
+    let key = StorageKey::new_storage_key(address, position.as_bytes());
+    match state_db.get::<StorageValue>(key) {
+        Ok(value) => {
+            Ok(value.map_or_else(H256::zero, |v| H256::from_uint(&v.value)))
+        }
+        Err(e) => Err(format!("failed to read registry storage: {}", e)),
+    }
+}
+
+/// Polls the registry and updates the members of the network until exit.
+pub fn start_registry_poller(
+    consensus: Weak<ConsensusGraph>, network: Weak<NetworkService>,
+    registry: Address, interval: Duration, exit: Arc<(Mutex<bool>, Condvar)>,
+) {
+    thread::Builder::new()
+        .name("consortium_registry".into())
+        .spawn(move || loop {
+            // Only hold the graph and the network during each poll, so that
+            // they can be released on shutdown.
+            match (consensus.upgrade(), network.upgrade()) {
+                (Some(consensus), Some(network)) => {
+                    poll_registry(&consensus, &network, &registry)
+                }
+                _ => return,
+            }
+
+            let mut exit_lock = exit.0.lock();
+            // The exit may be notified while polling.
+            if *exit_lock {
+                return;
+            }
+            if !exit.1.wait_for(&mut exit_lock, interval).timed_out() {
+                return;
+            }
+        })
+        .expect("Consortium registry thread spawn error");
+}
+
+fn poll_registry(
+    consensus: &ConsensusGraph, network: &NetworkService, registry: &Address,
+) {
+    match read_registry_members(consensus, registry) {
+        Ok(members) => {
+            if let Err(e) =
+                network.set_members(MembershipSource::Registry, members)
+            {
+                warn!("Failed to update registry members: {}", e);
+            }
+        }
+        Err(e) => warn!("Failed to read registry members: {}", e),
+    }
+}
@@ -47,6 +47,8 @@ pub trait ClientTrait {
     ) -> (Weak<BlockDataManager>, Option<Arc<dyn Stopable>>);
 }
 
+mod consortium;
+
 pub mod client_methods {
     pub fn run(
         this: Box<dyn ClientTrait>, exit_cond_var: Arc<(Mutex<bool>, Condvar)>,
@@ -85,7 +87,7 @@ pub mod client_methods {
     /// Most Conflux components references block data manager.
     /// When block data manager is freed, all background threads must have
     /// already stopped.
-    fn check_graceful_shutdown(
+    pub(super) fn check_graceful_shutdown(
         blockdata_manager_weak_ptr: Weak<BlockDataManager>,
     ) -> bool {
         let sleep_duration = Duration::from_secs(1);
@@ -144,12 +146,97 @@ pub fn initialize_common_modules(
 
     metrics::initialize(conf.metrics_config());
 
+    let (
+        machine,
+        secret_store,
+        genesis_accounts,
+        data_man,
+        pow,
+        txpool,
+        consensus,
+        sync_graph,
+        notifications,
+    ) = initialize_ledger_modules(conf, exit.clone(), node_type)?;
+
+    let network_config = conf.net_config()?;
+    let network = {
+        let mut network = NetworkService::new(network_config);
+        network.start().unwrap();
+        Arc::new(network)
+    };
+
+    let refresh_time =
+        Duration::from_millis(conf.raw_conf.account_provider_refresh_time_ms);
+
+    let mut accounts = account_provider(
+        Some(keys_path()),
+        None, /* sstore_iterations */
+        Some(refresh_time),
+    )
+    .expect("failed to initialize account provider");
+    if let Some(signer) = conf.external_signer()? {
+        accounts.set_signer_backend(Arc::new(signer));
+    }
+    let accounts = Arc::new(accounts);
+
+    let common_impl = Arc::new(CommonRpcImpl::new(
+        exit,
+        consensus.clone(),
+        network.clone(),
+        txpool.clone(),
+        accounts.clone(),
+    ));
+
+    let runtime = Runtime::with_default_thread_count();
+    let pubsub = PubSubClient::new(
+        runtime.executor(),
+        consensus.clone(),
+        notifications.clone(),
+        *network.get_network_type(),
+    );
+    Ok((
+        machine,
+        secret_store,
+        genesis_accounts,
+        data_man,
+        pow,
+        txpool,
+        consensus,
+        sync_graph,
+        network,
+        common_impl,
+        accounts,
+        notifications,
+        pubsub,
+        runtime,
+    ))
+}
+
+/// Initializes the modules which maintain the ledger, without the network.
+pub fn initialize_ledger_modules(
+    conf: &Configuration, exit: Arc<(Mutex<bool>, Condvar)>,
+    node_type: NodeType,
+) -> Result<
+    (
+        Arc<Machine>,
+        Arc<SecretStore>,
+        HashMap<Address, U256>,
+        Arc<BlockDataManager>,
+        Arc<PowComputer>,
+        Arc<TransactionPool>,
+        Arc<ConsensusGraph>,
+        Arc<SynchronizationGraph>,
+        Arc<Notifications>,
+    ),
+    String,
+>
+{
     let worker_thread_pool = Arc::new(Mutex::new(ThreadPool::with_name(
         "Tx Recover".into(),
         WORKER_COMPUTATION_PARALLELISM,
     )));
 
-    let network_config = conf.net_config()?;
+    let network_type = *conf.net_config()?.get_network_type();
     let cache_config = conf.cache_config();
 
     let (db_path, db_config) = conf.db_config();
@@ -192,10 +279,7 @@ pub fn initialize_common_modules(
     } else {
         match conf.raw_conf.genesis_accounts {
             Some(ref file) => genesis::load_file(file, |addr_str| {
-                parse_config_address_string(
-                    addr_str,
-                    network_config.get_network_type(),
-                )
+                parse_config_address_string(addr_str, &network_type)
             })?,
             None => genesis::default(conf.is_test_or_dev_mode()),
         }
@@ -235,7 +319,7 @@ pub fn initialize_common_modules(
         ledger_db.clone(),
         storage_manager,
         worker_thread_pool,
-        conf.data_mananger_config(),
+        conf.data_mananger_config()?,
         pow.clone(),
     ));
 
@@ -275,39 +359,6 @@ pub fn initialize_common_modules(
         machine.clone(),
     ));
 
-    let network = {
-        let mut network = NetworkService::new(network_config);
-        network.start().unwrap();
-        Arc::new(network)
-    };
-
-    let refresh_time =
-        Duration::from_millis(conf.raw_conf.account_provider_refresh_time_ms);
-
-    let accounts = Arc::new(
-        account_provider(
-            Some(keys_path()),
-            None, /* sstore_iterations */
-            Some(refresh_time),
-        )
-        .expect("failed to initialize account provider"),
-    );
-
-    let common_impl = Arc::new(CommonRpcImpl::new(
-        exit,
-        consensus.clone(),
-        network.clone(),
-        txpool.clone(),
-        accounts.clone(),
-    ));
-
-    let runtime = Runtime::with_default_thread_count();
-    let pubsub = PubSubClient::new(
-        runtime.executor(),
-        consensus.clone(),
-        notifications.clone(),
-        *network.get_network_type(),
-    );
     Ok((
         machine,
         secret_store,
@@ -317,12 +368,7 @@ pub fn initialize_common_modules(
         txpool,
         consensus,
         sync_graph,
-        network,
-        common_impl,
-        accounts,
         notifications,
-        pubsub,
-        runtime,
     ))
 }
 
@@ -384,6 +430,31 @@ pub fn initialize_not_light_node_modules(
     ));
     sync.register().unwrap();
 
+    if let Some(ref registry) = conf.raw_conf.consortium_registry_address {
+        let registry =
+            parse_config_address_string(registry, network.get_network_type())?;
+        consortium::start_registry_poller(
+            Arc::downgrade(&consensus),
+            Arc::downgrade(&network),
+            registry,
+            Duration::from_millis(
+                conf.raw_conf.consortium_registry_poll_interval_ms,
+            ),
+            exit.clone(),
+        );
+    }
+
+    if let Some(trace_backfill_config) = conf.trace_backfill_config() {
+        if !conf.raw_conf.executive_trace {
+            return Err("trace_backfill requires executive_trace = true".into());
+        }
+        trace_backfill::start_trace_backfill(
+            Arc::downgrade(&consensus),
+            trace_backfill_config,
+            exit.clone(),
+        );
+    }
+
     if let Some(print_memory_usage_period_s) =
         conf.raw_conf.print_memory_usage_period_s
     {
@@ -595,9 +666,130 @@ pub fn initialize_txgens(
     (maybe_multi_genesis_txgen, maybe_direct_txgen_with_contract)
 }
 
+/// Opens the ledger database and the state storage of a stopped node, for the
+/// commands run without starting the node.
+pub fn open_ledger_offline(
+    conf: &Configuration,
+) -> Result<(DBManager, Arc<StorageManager>), String> {
+    let db_manager = match conf.data_mananger_config()?.db_type {
+        DbType::Rocksdb => open_block_db_offline(conf)?.1,
+        DbType::Sqlite => DBManager::new_from_sqlite(
+            Path::new("./sqlite_db"),
+            Arc::new(PowComputer::new(conf.pow_config().use_octopus())),
+        ),
+    };
+    let storage_manager = Arc::new(
+        StorageManager::new(conf.storage_config())
+            .map_err(|e| format!("Failed to initialize storage {:?}", e))?,
+    );
+    Ok((db_manager, storage_manager))
+}
+
+/// Opens the rocksdb block db of a stopped node, and returns it with the
+/// DBManager on it.
+pub fn open_block_db_offline(
+    conf: &Configuration,
+) -> Result<(Arc<db::SystemDB>, DBManager), String> {
+    if let DbType::Sqlite = conf.data_mananger_config()?.db_type {
+        return Err("The block db is not stored in rocksdb".into());
+    }
+    let pow = Arc::new(PowComputer::new(conf.pow_config().use_octopus()));
+    let (db_path, db_config) = conf.db_config();
+    let ledger_db = db::open_database(db_path.to_str().unwrap(), &db_config)
+        .map_err(|e| format!("Failed to open database {:?}", e))?;
+    let db_manager = DBManager::new_from_rocksdb(ledger_db.clone(), pow);
+    Ok((ledger_db, db_manager))
+}
+
+/// Imports the blocks exported by `export_blocks` into the ledger of a stopped
+/// node, without connecting to the network.
+pub fn import_blocks_offline<F: FnMut(&BlockImportProgress)>(
+    conf: &Configuration, path: &Path, on_progress: F,
+) -> Result<BlockImportProgress, String> {
+    let node_type = conf.node_type();
+    if let NodeType::Light = node_type {
+        return Err("Blocks can't be imported by light nodes".into());
+    }
+    run_ledger_offline(conf, node_type, |sync_graph, _| {
+        import_blocks(sync_graph, path, on_progress)
+            .map_err(|e| format!("Failed to import blocks: {}", e))
+    })
+}
+
+/// Replays the epochs in [from_epoch, to_epoch] of the ledger of a stopped
+/// node, see `ConsensusGraph::replay_epoch`. `on_epoch` is called with the
+/// result of each epoch, and the replay stops when it returns `false`.
+pub fn replay_epochs_offline<F>(
+    conf: &Configuration, from_epoch: u64, to_epoch: u64, with_traces: bool,
+    mut on_epoch: F,
+) -> Result<(), String>
+where
+    F: FnMut(&ConsensusGraph, EpochReplayResult) -> Result<bool, String>,
+{
+    let node_type = conf.node_type();
+    if let NodeType::Light = node_type {
+        return Err("Epochs can't be replayed by light nodes".into());
+    }
+    run_ledger_offline(conf, node_type, |sync_graph, consensus| {
+        sync_graph.recover_graph_offline();
+        for height in from_epoch..=to_epoch {
+            let result = consensus.replay_epoch(height, with_traces, None)?;
+            if !on_epoch(consensus, result)? {
+                break;
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Runs `f` with the ledger modules of a stopped node, and shuts them down
+/// afterwards.
+fn run_ledger_offline<T, F>(
+    conf: &Configuration, node_type: NodeType, f: F,
+) -> Result<T, String>
+where F: FnOnce(&SynchronizationGraph, &ConsensusGraph) -> Result<T, String>
+{
+    let exit = Arc::new((Mutex::new(false), Condvar::new()));
+    // All the modules are dropped at the end of the block before shutdown.
+    let (result, data_manager_weak_ptr) = {
+        let (
+            _machine,
+            _secret_store,
+            _genesis_accounts,
+            data_man,
+            _pow,
+            _txpool,
+            consensus,
+            sync_graph,
+            _notifications,
+        ) = initialize_ledger_modules(conf, exit.clone(), node_type)?;
+        (f(&*sync_graph, &*consensus), Arc::downgrade(&data_man))
+    };
+    *exit.0.lock() = true;
+    exit.1.notify_all();
+    client_methods::check_graceful_shutdown(data_manager_weak_ptr);
+    result
+}
+
+/// Converts the state snapshots of a stopped node to the snapshot db backend
+/// `to_backend`, and returns the number of converted snapshots.
+pub fn convert_snapshots_offline(
+    conf: &Configuration, to_backend: &str,
+) -> Result<usize, String> {
+    let to_backend = SnapshotDbBackend::from_str(to_backend)?;
+    AnySnapshotDbManager::convert_snapshots(
+        &conf.storage_config().path_snapshot_dir,
+        to_backend,
+    )
+    .map_err(|e| format!("Failed to convert snapshots: {}", e))
+}
+
 pub mod delegate_convert {
     use crate::rpc::{
-        error_codes::{codes::EXCEPTION_ERROR, invalid_params},
+        error_codes::{
+            codes::{EXCEPTION_ERROR, SIGNER_REJECTED, SIGNER_UNAVAILABLE},
+            invalid_params,
+        },
         JsonRpcErrorKind, RpcBoxFuture, RpcError, RpcErrorKind, RpcResult,
     };
     use jsonrpc_core::{
@@ -631,6 +823,19 @@ pub mod delegate_convert {
                 RpcErrorKind::InvalidParam(param, details) => {
                     invalid_params(&param, details)
                 }
+                RpcErrorKind::SignerRejected(details) => JsonRpcError {
+                    code: jsonrpc_core::ErrorCode::ServerError(SIGNER_REJECTED),
+                    message: "External signer rejected the request".into(),
+                    data: Some(jsonrpc_core::Value::String(details)),
+                },
+                RpcErrorKind::SignerUnavailable(details) => JsonRpcError {
+                    code: jsonrpc_core::ErrorCode::ServerError(
+                        SIGNER_UNAVAILABLE,
+                    ),
+                    message: "External signer failed to sign the request"
+                        .into(),
+                    data: Some(jsonrpc_core::Value::String(details)),
+                },
                 RpcErrorKind::Msg(_)
                 | RpcErrorKind::Decoder(_)
 
@@ -720,15 +925,19 @@ use crate::{
 };
 use blockgen::BlockGenerator;
 use cfx_parameters::sync::CATCH_UP_EPOCH_LAG_THRESHOLD;
-use cfx_storage::StorageManager;
+use cfx_storage::{AnySnapshotDbManager, SnapshotDbBackend, StorageManager};
 use cfx_types::{address_util::AddressUtil, Address, U256};
 use cfxcore::{
-    block_data_manager::BlockDataManager,
+    block_data_manager::{db_manager::DBManager, BlockDataManager, DbType},
+    consensus::{epoch_replay::EpochReplayResult, trace_backfill},
     machine::{new_machine_with_builtin, Machine},
     pow::PowComputer,
     spec::genesis::{self, genesis_block, DEV_GENESIS_KEY_PAIR_2},
     statistics::Statistics,
-    sync::SyncPhaseType,
+    sync::{
+        block_archive::{import_blocks, BlockImportProgress},
+        SyncPhaseType,
+    },
     vm_factory::VmFactory,
     ConsensusGraph, LightProvider, NodeType, Notifications, Stopable,
     SynchronizationGraph, SynchronizationService, TransactionPool,
@@ -747,6 +956,7 @@ use runtime::Runtime;
 use secret_store::{SecretStore, SharedSecretStore};
 use std::{
     collections::HashMap,
+    path::Path,
     str::FromStr,
     sync::{Arc, Weak},
     thread,
@@ -11,11 +11,15 @@ use cfx_internal_common::{ChainIdParams, ChainIdParamsInner};
 use cfx_parameters::block::DEFAULT_TARGET_BLOCK_GAS_LIMIT;
 use cfx_storage::{
     defaults::DEFAULT_DEBUG_SNAPSHOT_CHECKER_THREADS, storage_dir,
-    ConsensusParam, ProvideExtraSnapshotSyncConfig, StorageConfiguration,
+    ConsensusParam, ProvideExtraSnapshotSyncConfig, SnapshotDbBackend,
+    StorageConfiguration,
 };
 use cfx_types::{Address, H256, U256};
 use cfxcore::{
-    block_data_manager::{DataManagerConfiguration, DbType},
+    block_data_manager::{
+        db_gc_manager::{GCDataClass, PruningPolicy},
+        DataManagerConfiguration, DbType,
+    },
     block_parameters::*,
     cache_config::{
         DEFAULT_INVALID_BLOCK_HASH_CACHE_SIZE_IN_COUNT,
@@ -24,7 +28,8 @@ use cfxcore::{
     },
     consensus::{
         consensus_inner::consensus_executor::ConsensusExecutionConfiguration,
-        ConsensusConfig, ConsensusInnerConfig,
+        trace_backfill::TraceBackfillConfig, ConsensusConfig,
+        ConsensusInnerConfig,
     },
     consensus_internal_parameters::*,
     consensus_parameters::*,
@@ -36,9 +41,20 @@ use cfxcore::{
     transaction_pool::TxPoolConfig,
     NodeType,
 };
+use cfxcore_accounts::ExternalSigner;
 use lazy_static::*;
 use metrics::MetricsConfiguration;
-use network::DiscoveryConfiguration;
+use network::{
+    compression::{DEFAULT_COMPRESSION_THRESHOLD, DEFAULT_ZSTD_LEVEL},
+    node_table::Node,
+    reputation::{
+        DEFAULT_BAN_THRESHOLD, DEFAULT_DISCONNECT_THRESHOLD,
+        DEFAULT_SCORE_RECOVERY_PER_MINUTE,
+    },
+    BandwidthConfig, CompressionConfig, DiscoveryConfiguration,
+    MembershipConfig, PeerGroupConfig, ProtocolId, ReputationConfig,
+    PROTOCOL_ID_SIZE,
+};
 use parking_lot::RwLock;
 use rand::Rng;
 use std::{convert::TryInto, path::PathBuf, sync::Arc};
@@ -172,6 +188,7 @@ build_config! {
         (chunk_size_byte, (u64), DEFAULT_CHUNK_SIZE)
         (demote_peer_for_timeout, (bool), false)
         (dev_allow_phase_change_without_peer, (bool), false)
+        (download_target_response_time_ms, (u64), 2_000)
         (egress_queue_capacity, (usize), 256)
         (egress_min_throttle, (usize), 10)
         (egress_max_throttle, (usize), 64)
@@ -219,8 +236,21 @@ build_config! {
         (enable_discovery, (bool), true)
         (netconf_dir, (Option<String>), None)
         (net_key, (Option<String>), None)
+        (network_compression, (String), "".into())
+        (network_compression_disabled_protocols, (String), "".into())
+        (network_compression_threshold, (usize), DEFAULT_COMPRESSION_THRESHOLD)
+        (network_compression_zstd_level, (i32), DEFAULT_ZSTD_LEVEL)
+        (network_egress_limits, (String), "".into())
+        (network_encryption, (String), "never".into())
         (node_table_timeout_s, (u64), 300)
         (node_table_promotion_timeout_s, (u64), 3 * 24 * 3600)
+        (peer_ban_duration_s, (u64), 24 * 3600)
+        (peer_group_reconnect_max_backoff_ms, (u64), 60_000)
+        (peer_group_reconnect_min_backoff_ms, (u64), 1_000)
+        (peer_groups, (Option<String>), None)
+        (peer_score_ban_threshold, (i64), DEFAULT_BAN_THRESHOLD)
+        (peer_score_disconnect_threshold, (i64), DEFAULT_DISCONNECT_THRESHOLD)
+        (peer_score_recovery_per_minute, (i64), DEFAULT_SCORE_RECOVERY_PER_MINUTE)
         (session_ip_limits, (String), "1,8,4,2".into())
         (subnet_quota, (usize), 128)
 
@@ -239,14 +269,21 @@ build_config! {
         (additional_maintained_reward_epoch_count, (Option<usize>), None)
         (additional_maintained_trace_epoch_count, (Option<usize>), None)
         (additional_maintained_transaction_index_epoch_count, (Option<usize>), None)
+        (archive_state, (bool), false)
+        // The `*_pruning_policy` options override the corresponding
+        // `additional_maintained*` options, see `PruningPolicy` for the format.
+        (block_body_pruning_policy, (Option<String>), None)
         (block_cache_gc_period_ms, (u64), 5_000)
         (block_db_dir, (Option<String>), None)
         (block_db_type, (String), "rocksdb".to_string())
         (checkpoint_gc_time_in_era_count, (f64), 0.5)
         // The conflux data dir, if unspecified, is the workdir where conflux is started.
         (conflux_data_dir, (String), "./blockchain_data".to_string())
+        (execution_result_pruning_policy, (Option<String>), None)
         (ledger_cache_size, (usize), DEFAULT_LEDGER_CACHE_SIZE)
         (invalid_block_hash_cache_size_in_count, (usize), DEFAULT_INVALID_BLOCK_HASH_CACHE_SIZE_IN_COUNT)
+        (pruning_watched_addresses, (Option<String>), None)
+        (reward_pruning_policy, (Option<String>), None)
         (rocksdb_cache_size, (Option<usize>), Some(128))
         (rocksdb_compaction_profile, (Option<String>), None)
         (storage_delta_mpts_cache_recent_lfu_factor, (f64), cfx_storage::defaults::DEFAULT_DELTA_MPTS_CACHE_RECENT_LFU_FACTOR)
@@ -259,11 +296,15 @@ build_config! {
         (sync_state_starting_epoch, (Option<u64>), None)
         (sync_state_epoch_gap, (Option<u64>), None)
         (target_difficulties_cache_size_in_count, (usize), DEFAULT_TARGET_DIFFICULTIES_CACHE_SIZE_IN_COUNT)
+        (trace_pruning_policy, (Option<String>), None)
+        (transaction_index_pruning_policy, (Option<String>), None)
 
         // General/Unclassified section.
         (account_provider_refresh_time_ms, (u64), 1000)
         (check_phase_change_period_ms, (u64), 1000)
         (enable_optimistic_execution, (bool), true)
+        (external_signer, (Option<String>), None)
+        (external_signer_timeout_ms, (u64), 30_000)
         (future_block_buffer_capacity, (usize), 32768)
         (get_logs_filter_max_limit, (Option<usize>), None)
         (get_logs_filter_max_epoch_range, (Option<u64>), None)
@@ -273,10 +314,18 @@ build_config! {
         (print_memory_usage_period_s, (Option<u64>), None)
         (target_block_gas_limit, (u64), DEFAULT_TARGET_BLOCK_GAS_LIMIT)
         (executive_trace, (bool), false)
+        (trace_backfill, (bool), false)
+        (trace_backfill_interval_ms, (u64), 10)
+        (trace_backfill_start_epoch, (Option<u64>), None)
 
         // TreeGraph Section.
         (candidate_pivot_waiting_timeout_ms, (u64), 10_000)
         (is_consortium, (bool), false)
+        (consortium_membership, (bool), false)
+        (consortium_members_file, (Option<String>), None)
+        (consortium_members_file_refresh_ms, (u64), 10_000)
+        (consortium_registry_address, (Option<String>), None)
+        (consortium_registry_poll_interval_ms, (u64), 10_000)
         (tg_config_path, (Option<String>), Some("./tg_config/tg_config.toml".to_string()))
 
         // Light node section
@@ -316,6 +365,8 @@ build_config! {
             (Vec<ProvideExtraSnapshotSyncConfig>),
             vec![ProvideExtraSnapshotSyncConfig::StableCheckpoint],
             ProvideExtraSnapshotSyncConfig::parse_config_list)
+        (snapshot_db_backend, (SnapshotDbBackend), SnapshotDbBackend::Sqlite,
+            SnapshotDbBackend::from_str)
         (node_type, (Option<NodeType>), None, NodeType::from_str)
         (public_rpc_apis, (ApiSet), ApiSet::Safe, ApiSet::from_str)
     }
@@ -439,9 +490,202 @@ impl Configuration {
         network_config.max_outgoing_peers = self.raw_conf.max_outgoing_peers;
         network_config.max_outgoing_peers_archive =
             self.raw_conf.max_outgoing_peers_archive.unwrap_or(0);
+        network_config.compression = self.compression_config()?;
+        network_config.bandwidth = self.bandwidth_config()?;
+        network_config.encryption_policy =
+            self.raw_conf.network_encryption.parse()?;
+        network_config.membership = self.membership_config()?;
+        network_config.reputation = self.reputation_config()?;
+        network_config.peer_groups = self.peer_group_config()?;
         Ok(network_config)
     }
 
+    /// Parses `peer_groups` in the format of
+    /// `group1=node_url,node_url;group2=node_url`.
+    fn peer_group_config(&self) -> Result<PeerGroupConfig, String> {
+        let min_reconnect_backoff = Duration::from_millis(
+            self.raw_conf.peer_group_reconnect_min_backoff_ms,
+        );
+        let max_reconnect_backoff = Duration::from_millis(
+            self.raw_conf.peer_group_reconnect_max_backoff_ms,
+        );
+        if min_reconnect_backoff.as_millis() == 0
+            || min_reconnect_backoff > max_reconnect_backoff
+        {
+            return Err(format!(
+                "invalid peer group reconnect backoff, required 0 < min <= max, \
+                 min = {:?}, max = {:?}",
+                min_reconnect_backoff, max_reconnect_backoff
+            ));
+        }
+
+        let mut config = PeerGroupConfig {
+            min_reconnect_backoff,
+            max_reconnect_backoff,
+            ..Default::default()
+        };
+        let groups = match self.raw_conf.peer_groups {
+            Some(ref groups) => groups,
+            None => return Ok(config),
+        };
+        for group in groups.split(';') {
+            let group = group.trim();
+            if group.is_empty() {
+                continue;
+            }
+            let (name, nodes) = match group.find('=') {
+                Some(pos) => (group[..pos].trim(), &group[pos + 1..]),
+                None => {
+                    return Err(format!(
+                        "failed to parse peer group {}, expected name=node_url",
+                        group
+                    ))
+                }
+            };
+            if name.is_empty() {
+                return Err(format!("empty name of peer group {}", group));
+            }
+            for node in nodes.split(',') {
+                let node = node.trim();
+                if node.is_empty() {
+                    continue;
+                }
+                node.parse::<Node>().map_err(|e| {
+                    format!("failed to parse node url {}: {:?}", node, e)
+                })?;
+                config
+                    .groups
+                    .entry(name.into())
+                    .or_insert_with(Vec::new)
+                    .push(node.into());
+            }
+        }
+        Ok(config)
+    }
+
+    fn reputation_config(&self) -> Result<ReputationConfig, String> {
+        let disconnect_threshold =
+            self.raw_conf.peer_score_disconnect_threshold;
+        let ban_threshold = self.raw_conf.peer_score_ban_threshold;
+        if disconnect_threshold >= 0 || ban_threshold > disconnect_threshold {
+            return Err(format!(
+                "invalid peer score thresholds, required ban <= disconnect < 0, \
+                 ban = {}, disconnect = {}",
+                ban_threshold, disconnect_threshold
+            ));
+        }
+        Ok(ReputationConfig {
+            disconnect_threshold,
+            ban_threshold,
+            ban_duration: Duration::from_secs(
+                self.raw_conf.peer_ban_duration_s,
+            ),
+            recovery_per_minute: self.raw_conf.peer_score_recovery_per_minute,
+        })
+    }
+
+    fn membership_config(&self) -> Result<MembershipConfig, String> {
+        let enabled = self.raw_conf.consortium_membership;
+        if enabled && !self.raw_conf.is_consortium {
+            return Err("consortium_membership requires is_consortium".into());
+        }
+        if !enabled && self.raw_conf.consortium_registry_address.is_some() {
+            return Err(
+                "consortium_registry_address requires consortium_membership"
+                    .into(),
+            );
+        }
+        Ok(MembershipConfig {
+            enabled,
+            members_file: self
+                .raw_conf
+                .consortium_members_file
+                .as_ref()
+                .map(PathBuf::from),
+            members_file_refresh_timeout: Duration::from_millis(
+                self.raw_conf.consortium_members_file_refresh_ms,
+            ),
+            allow_boot_nodes_until_registry: self
+                .raw_conf
+                .consortium_registry_address
+                .is_some(),
+        })
+    }
+
+    fn compression_config(&self) -> Result<CompressionConfig, String> {
+        let mut config = CompressionConfig::default();
+        for algorithm in self.raw_conf.network_compression.split(',') {
+            let algorithm = algorithm.trim();
+            if !algorithm.is_empty() {
+                config.algorithms.push(algorithm.parse().map_err(|e| {
+                    format!("failed to parse network_compression: {}", e)
+                })?);
+            }
+        }
+        for protocol in self
+            .raw_conf
+            .network_compression_disabled_protocols
+            .split(',')
+        {
+            let protocol = protocol.trim();
+            if protocol.is_empty() {
+                continue;
+            }
+            if protocol.len() != PROTOCOL_ID_SIZE {
+                return Err(format!(
+                    "invalid protocol {} in network_compression_disabled_protocols",
+                    protocol
+                ));
+            }
+            let mut id = ProtocolId::default();
+            id.copy_from_slice(protocol.as_bytes());
+            config.disabled_protocols.push(id);
+        }
+        config.threshold = self.raw_conf.network_compression_threshold;
+        config.zstd_level = self.raw_conf.network_compression_zstd_level;
+        Ok(config)
+    }
+
+    /// Parses `network_egress_limits` in the format of
+    /// `protocol=bytes_per_second,protocol=bytes_per_second`.
+    fn bandwidth_config(&self) -> Result<BandwidthConfig, String> {
+        let mut config = BandwidthConfig::default();
+        for limit in self.raw_conf.network_egress_limits.split(',') {
+            let limit = limit.trim();
+            if limit.is_empty() {
+                continue;
+            }
+            let (protocol, bytes) = match limit.find('=') {
+                Some(pos) => (limit[..pos].trim(), limit[pos + 1..].trim()),
+                None => {
+                    return Err(format!(
+                        "failed to parse network_egress_limits {}, expected protocol=bytes_per_second",
+                        limit
+                    ))
+                }
+            };
+            if protocol.len() != PROTOCOL_ID_SIZE {
+                return Err(format!(
+                    "invalid protocol {} in network_egress_limits",
+                    protocol
+                ));
+            }
+            let bytes: u64 = match bytes.parse() {
+                Ok(bytes) if bytes > 0 => bytes,
+                _ => {
+                    return Err(format!(
+                        "invalid limit {} in network_egress_limits",
+                        limit
+                    ))
+                }
+            };
+            let mut id = ProtocolId::default();
+            id.copy_from_slice(protocol.as_bytes());
+            config.egress_limits.insert(id, bytes);
+        }
+        Ok(config)
+    }
+
     pub fn cache_config(&self) -> CacheConfig {
         let mut cache_config = CacheConfig::default();
         cache_config.ledger = self.raw_conf.ledger_cache_size;
@@ -625,6 +869,7 @@ impl Configuration {
             additional_maintained_snapshot_count: self
                 .raw_conf
                 .additional_maintained_snapshot_count,
+            archive_state: self.raw_conf.archive_state,
             consensus_param: ConsensusParam {
                 snapshot_epoch_count: if self.is_test_mode() {
                     self.raw_conf.dev_snapshot_epoch_count
@@ -650,16 +895,21 @@ impl Configuration {
             max_open_snapshots: self.raw_conf.storage_max_open_snapshots,
             path_delta_mpts_dir: conflux_data_path
                 .join(&*storage_dir::DELTA_MPTS_DIR),
+            path_delta_mpts_warm_cache: conflux_data_path
+                .join(&*storage_dir::DELTA_MPTS_WARM_CACHE_PATH),
             path_snapshot_dir: conflux_data_path
                 .join(&*storage_dir::SNAPSHOT_DIR),
             path_snapshot_info_db: conflux_data_path
                 .join(&*storage_dir::SNAPSHOT_INFO_DB_PATH),
+            path_state_history_db: conflux_data_path
+                .join(&*storage_dir::STATE_HISTORY_DB_PATH),
             path_storage_dir: conflux_data_path
                 .join(&*storage_dir::STORAGE_DIR),
             provide_more_snapshot_for_sync: self
                 .raw_conf
                 .provide_more_snapshot_for_sync
                 .clone(),
+            snapshot_db_backend: self.raw_conf.snapshot_db_backend,
         }
     }
 
@@ -748,6 +998,9 @@ impl Configuration {
             allow_phase_change_without_peer: self
                 .raw_conf
                 .dev_allow_phase_change_without_peer,
+            download_target_response_time: Duration::from_millis(
+                self.raw_conf.download_target_response_time_ms,
+            ),
         }
     }
 
@@ -766,7 +1019,9 @@ impl Configuration {
         }
     }
 
-    pub fn data_mananger_config(&self) -> DataManagerConfiguration {
+    pub fn data_mananger_config(
+        &self,
+    ) -> Result<DataManagerConfiguration, String> {
         let mut conf = DataManagerConfiguration {
             persist_tx_index: self.raw_conf.persist_tx_index,
             tx_cache_index_maintain_timeout: Duration::from_millis(
@@ -775,23 +1030,9 @@ impl Configuration {
             db_type: match self.raw_conf.block_db_type.as_str() {
                 "rocksdb" => DbType::Rocksdb,
                 "sqlite" => DbType::Sqlite,
-                _ => panic!("Invalid block_db_type parameter!"),
+                _ => return Err("Invalid block_db_type parameter!".into()),
             },
-            additional_maintained_block_body_epoch_count: self
-                .raw_conf
-                .additional_maintained_block_body_epoch_count,
-            additional_maintained_execution_result_epoch_count: self
-                .raw_conf
-                .additional_maintained_execution_result_epoch_count,
-            additional_maintained_reward_epoch_count: self
-                .raw_conf
-                .additional_maintained_reward_epoch_count,
-            additional_maintained_trace_epoch_count: self
-                .raw_conf
-                .additional_maintained_trace_epoch_count,
-            additional_maintained_transaction_index_epoch_count: self
-                .raw_conf
-                .additional_maintained_transaction_index_epoch_count,
+            pruning_policies: Default::default(),
             checkpoint_gc_time_in_epoch_count: (self
                 .raw_conf
                 .checkpoint_gc_time_in_era_count
@@ -800,40 +1041,77 @@ impl Configuration {
             strict_tx_index_gc: self.raw_conf.strict_tx_index_gc,
         };
 
-        // By default, we do not keep the block data for additional period,
-        // but `node_type = "archive"` is a shortcut for keeping all them.
-        if !matches!(self.raw_conf.node_type, Some(NodeType::Archive)) {
-            if conf.additional_maintained_block_body_epoch_count.is_none() {
-                conf.additional_maintained_block_body_epoch_count = Some(0);
-            }
-            if conf
-                .additional_maintained_execution_result_epoch_count
-                .is_none()
-            {
-                conf.additional_maintained_execution_result_epoch_count =
-                    Some(0);
-            }
-            if conf
-                .additional_maintained_transaction_index_epoch_count
-                .is_none()
-            {
-                conf.additional_maintained_transaction_index_epoch_count =
-                    Some(0);
-            }
-            if conf.additional_maintained_reward_epoch_count.is_none() {
-                conf.additional_maintained_reward_epoch_count = Some(0);
-            }
-            if conf.additional_maintained_trace_epoch_count.is_none() {
-                conf.additional_maintained_trace_epoch_count = Some(0);
+        let is_archive =
+            matches!(self.raw_conf.node_type, Some(NodeType::Archive));
+        for (class, policy, additional_maintained) in vec![
+            (
+                GCDataClass::BlockBody,
+                &self.raw_conf.block_body_pruning_policy,
+                self.raw_conf.additional_maintained_block_body_epoch_count,
+            ),
+            (
+                GCDataClass::ExecutionResult,
+                &self.raw_conf.execution_result_pruning_policy,
+                self.raw_conf
+                    .additional_maintained_execution_result_epoch_count,
+            ),
+            (
+                GCDataClass::Reward,
+                &self.raw_conf.reward_pruning_policy,
+                self.raw_conf.additional_maintained_reward_epoch_count,
+            ),
+            (
+                GCDataClass::Trace,
+                &self.raw_conf.trace_pruning_policy,
+                self.raw_conf.additional_maintained_trace_epoch_count,
+            ),
+            (
+                GCDataClass::TransactionIndex,
+                &self.raw_conf.transaction_index_pruning_policy,
+                self.raw_conf
+                    .additional_maintained_transaction_index_epoch_count,
+            ),
+        ] {
+            let policy = match (policy, additional_maintained) {
+                (Some(policy), _) => {
+                    policy.parse::<PruningPolicy>().map_err(|e| {
+                        format!(
+                            "Invalid {}_pruning_policy: {}",
+                            class.name(),
+                            e
+                        )
+                    })?
+                }
+                (None, Some(epoch_count)) => {
+                    PruningPolicy::ByAge(epoch_count as u64)
+                }
+                // By default, we do not keep the block data for additional
+                // period, but `node_type = "archive"` is a shortcut for
+                // keeping all them.
+                (None, None) if !is_archive => PruningPolicy::ByAge(0),
+                (None, None) => PruningPolicy::KeepAll,
+            };
+            conf.pruning_policies.set(class, policy);
+        }
+        conf.pruning_policies.validate()?;
+        if let Some(addresses) = &self.raw_conf.pruning_watched_addresses {
+            let network = NetworkConfiguration::network_id_to_known_cfx_network(
+                self.network_id(),
+            );
+            for addr in addresses.split(',').map(str::trim) {
+                conf.pruning_policies.watched_addresses.insert(
+                    parse_config_address_string(addr, &network).map_err(
+                        |e| format!("Invalid pruning_watched_addresses: {}", e),
+                    )?,
+                );
             }
         }
-        if conf
-            .additional_maintained_transaction_index_epoch_count
-            .is_some()
+        if conf.pruning_policies.get(GCDataClass::TransactionIndex)
+            != &PruningPolicy::KeepAll
         {
             conf.persist_tx_index = true;
         }
-        conf
+        Ok(conf)
     }
 
     pub fn sync_graph_config(&self) -> SyncGraphConfig {
@@ -918,6 +1196,18 @@ impl Configuration {
         }
     }
 
+    pub fn trace_backfill_config(&self) -> Option<TraceBackfillConfig> {
+        if !self.raw_conf.trace_backfill {
+            return None;
+        }
+        Some(TraceBackfillConfig {
+            start_epoch: self.raw_conf.trace_backfill_start_epoch,
+            interval: Duration::from_millis(
+                self.raw_conf.trace_backfill_interval_ms,
+            ),
+        })
+    }
+
     pub fn discovery_protocol(&self) -> DiscoveryConfiguration {
         DiscoveryConfiguration {
             discover_node_count: self.raw_conf.discovery_discover_node_count,
@@ -1006,6 +1296,16 @@ impl Configuration {
     pub fn node_type(&self) -> NodeType {
         self.raw_conf.node_type.unwrap_or(NodeType::Full)
     }
+
+    pub fn external_signer(&self) -> Result<Option<ExternalSigner>, String> {
+        match self.raw_conf.external_signer {
+            Some(ref endpoint) => Ok(Some(ExternalSigner::new(
+                endpoint.parse()?,
+                Duration::from_millis(self.raw_conf.external_signer_timeout_ms),
+            ))),
+            None => Ok(None),
+        }
+    }
 }
 
 /// Validates and formats bootnodes option.
@@ -46,11 +46,13 @@ use self::{
             CfxHandler as LightCfxHandler, DebugRpcImpl as LightDebugRpcImpl,
             RpcImpl as LightImpl, TestRpcImpl as LightTestRpcImpl,
         },
+        personal::PersonalHandler,
         pubsub::PubSubClient,
         trace::TraceHandler,
     },
     traits::{
-        cfx::Cfx, debug::LocalRpc, pubsub::PubSub, test::TestRpc, trace::Trace,
+        cfx::Cfx, debug::LocalRpc, personal::Personal, pubsub::PubSub,
+        test::TestRpc, trace::Trace,
     },
 };
 
@@ -163,13 +165,17 @@ pub fn setup_debug_rpc_apis(
     conf: &Configuration,
 ) -> MetaIoHandler<Metadata>
 {
+    // `personal` is not in `ApiSet::All`, so that it's only exposed by the
+    // public interfaces if it's listed explicitly.
+    let mut debug_apis = ApiSet::All.list_apis();
+    debug_apis.insert(Api::Personal);
     setup_rpc_apis(
         common,
         rpc,
         pubsub,
         &conf.raw_conf.throttling_conf,
         "rpc_local",
-        ApiSet::All.list_apis(),
+        debug_apis,
     )
 }
 
@@ -197,6 +203,15 @@ fn setup_rpc_apis(
                         .to_delegate(),
                 );
             }
+            Api::Personal => {
+                handler.extend_with(
+                    PersonalHandler::new(
+                        common.account_provider(),
+                        common.network_type(),
+                    )
+                    .to_delegate(),
+                );
+            }
             Api::Pubsub => handler.extend_with(pubsub.clone().to_delegate()),
             Api::Test => {
                 handler.extend_with(
@@ -242,6 +257,7 @@ pub fn setup_debug_rpc_apis_light(
 ) -> MetaIoHandler<Metadata>
 {
     let mut light_debug_apis = ApiSet::All.list_apis();
+    light_debug_apis.insert(Api::Personal);
     light_debug_apis.remove(&Api::Trace);
     setup_rpc_apis_light(
         common,
@@ -277,6 +293,15 @@ fn setup_rpc_apis_light(
                         .to_delegate(),
                 );
             }
+            Api::Personal => {
+                handler.extend_with(
+                    PersonalHandler::new(
+                        common.account_provider(),
+                        common.network_type(),
+                    )
+                    .to_delegate(),
+                );
+            }
             Api::Pubsub => handler.extend_with(pubsub.clone().to_delegate()),
             Api::Test => {
                 handler.extend_with(
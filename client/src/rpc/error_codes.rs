@@ -41,7 +41,7 @@ pub mod codes {
     /// by 1.
     ///
     /// Do not recycle deprecated error codes.
-    const NEXT_SERVER_ERROR_CODE: i64 = -32078;
+    const NEXT_SERVER_ERROR_CODE: i64 = -32080;
     /// When the above number is equal to -32100, take the number below on the
     /// right for new error code, then increase it by 1.
     const CFX_EXTRA_SERVER_ERROR_CODE: i64 = -31999;
@@ -125,6 +125,11 @@ pub mod codes {
     pub const ACCOUNT_ERROR: i64 = -32023;
     /// Encoding error happened in signing structured data. Related to EIP712.
     pub const ENCODING_ERROR: i64 = -32058;
+    /// The external signer refused to sign the request.
+    pub const SIGNER_REJECTED: i64 = -32078;
+    /// The external signer cannot be reached, did not answer in time or
+    /// answered with an invalid response.
+    pub const SIGNER_UNAVAILABLE: i64 = -32079;
 
     /* Other application error codes */
     /// Call() execution error. This is clearly an application level error code,
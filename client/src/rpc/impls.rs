@@ -10,5 +10,6 @@ pub struct RpcImplConfiguration {
 pub mod cfx;
 pub mod common;
 pub mod light;
+pub mod personal;
 pub mod pubsub;
 pub mod trace;
@@ -4,13 +4,15 @@
 
 use crate::rpc::types::{
     call_request::rpc_call_request_network, errors::check_rpc_address_network,
-    RpcAddress, SponsorInfo, TokenSupplyInfo, MAX_GAS_CALL_REQUEST,
+    AccountRange, RpcAddress, SponsorInfo, StorageEntry, StorageRange,
+    TokenSupplyInfo, MAX_GAS_CALL_REQUEST, MAX_STATE_RANGE_LIMIT,
 };
 use blockgen::BlockGenerator;
 use cfx_state::state_trait::StateOpsTrait;
 use cfx_statedb::{StateDbExt, StateDbGetOriginalMethods};
 use cfx_types::{
-    address_util::AddressUtil, BigEndianHash, H256, H520, U128, U256, U64,
+    address_util::AddressUtil, Address, BigEndianHash, H256, H520, U128, U256,
+    U64,
 };
 use cfxcore::{
     executive::{ExecutionError, ExecutionOutcome, TxDropError},
@@ -19,12 +21,13 @@ use cfxcore::{
     vm, ConsensusGraph, ConsensusGraphTrait, PeerInfo, SharedConsensusGraph,
     SharedSynchronizationService, SharedTransactionPool,
 };
-use cfxcore_accounts::AccountProvider;
+use cfxcore_accounts::{AccountProvider, TypedData};
 use delegate::delegate;
 use jsonrpc_core::{BoxFuture, Error as JsonRpcError, Result as JsonRpcResult};
 use network::{
     node_table::{Node, NodeId},
-    throttling, SessionDetails, UpdateNodeOperation,
+    throttling, BannedNode, MemberInfo, NodeRecord, PeerGroupInfo, PeerScore,
+    SessionDetails, UpdateNodeOperation,
 };
 use parking_lot::Mutex;
 use primitives::{
@@ -54,11 +57,12 @@ use crate::{
             sign_call, Account as RpcAccount, BlameInfo, Block as RpcBlock,
             BlockHashOrEpochNumber, Bytes, CallRequest,
             CheckBalanceAgainstTransactionResponse, ConsensusGraphStates,
-            EpochNumber, EstimateGasAndCollateralResponse, Log as RpcLog,
-            LogFilter as RpcFilter, PackedOrExecuted, Receipt as RpcReceipt,
+            EpochNumber, EstimateGasAndCollateralResponse, FeeHistory,
+            GasPriceUrgency, GcStatus, Log as RpcLog, LogFilter as RpcFilter,
+            PackedOrExecuted, Receipt as RpcReceipt,
             RewardInfo as RpcRewardInfo, SendTxRequest, Status as RpcStatus,
-            SyncGraphStates, Transaction as RpcTransaction, TxPoolPendingInfo,
-            TxWithPoolInfo,
+            SyncGraphStates, TraceBackfillStatus,
+            Transaction as RpcTransaction, TxPoolPendingInfo, TxWithPoolInfo,
         },
         RpcResult,
     },
@@ -415,6 +419,71 @@ impl RpcImpl {
         })
     }
 
+    fn check_state_range_limit(&self, limit: U64) -> RpcResult<usize> {
+        if limit.as_u64() > MAX_STATE_RANGE_LIMIT {
+            bail!(invalid_params(
+                "limit",
+                format!("limit must not exceed {}", MAX_STATE_RANGE_LIMIT)
+            ));
+        }
+        Ok(limit.as_usize())
+    }
+
+    fn storage_range(
+        &self, address: RpcAddress, start_key: Option<H256>, limit: U64,
+        epoch_num: Option<EpochNumber>,
+    ) -> RpcResult<StorageRange>
+    {
+        self.check_address_network(address.network)?;
+        let limit = self.check_state_range_limit(limit)?;
+        let epoch_num = epoch_num.unwrap_or(EpochNumber::LatestState).into();
+
+        info!(
+            "RPC Request: cfx_getStorageRange address={:?}, start_key={:?}, limit={:?}, epoch_num={:?}",
+            address, start_key, limit, epoch_num
+        );
+
+        let state_db = self
+            .consensus
+            .get_state_db_by_epoch_number(epoch_num, "epoch_num")?;
+
+        let key_prefix = StorageKey::new_storage_root_key(&address.hex_address);
+        let start_key = start_key.unwrap_or_default();
+        let (kvs, maybe_next_key) = state_db.read_original_range(
+            Some(key_prefix),
+            &StorageKey::new_storage_key(
+                &address.hex_address,
+                start_key.as_ref(),
+            )
+            .to_key_bytes(),
+            limit,
+            None,
+        )?;
+
+        let key_prefix_len = key_prefix.to_key_bytes().len();
+        let to_position = |key: &[u8]| -> RpcResult<H256> {
+            let position = &key[key_prefix_len..];
+            if position.len() != H256::len_bytes() {
+                bail!(format!("Invalid storage key {:?}", key));
+            }
+            Ok(H256::from_slice(position))
+        };
+        let mut storage = Vec::with_capacity(kvs.len());
+        for (key, value) in kvs {
+            let entry = Rlp::new(&value).as_val::<StorageValue>()?;
+            storage.push(StorageEntry {
+                key: to_position(&key)?,
+                value: H256::from_uint(&entry.value),
+            });
+        }
+        let next_key = match maybe_next_key {
+            Some(next_key) => Some(to_position(&next_key)?),
+            None => None,
+        };
+
+        Ok(StorageRange { storage, next_key })
+    }
+
     fn send_transaction_with_signature(
         &self, tx: TransactionWithSignature,
     ) -> RpcResult<H256> {
@@ -1234,6 +1303,23 @@ impl RpcImpl {
         Ok(SyncGraphStates::new(sync_graph_states))
     }
 
+    pub fn gc_status(&self) -> RpcResult<GcStatus> {
+        let gc_status = self.consensus.get_data_manager().gc_status();
+        Ok(GcStatus::try_from(
+            gc_status,
+            *self.sync.network.get_network_type(),
+        )?)
+    }
+
+    pub fn trace_backfill_status(
+        &self,
+    ) -> RpcResult<Option<TraceBackfillStatus>> {
+        Ok(self
+            .consensus_graph()
+            .trace_backfill_status()
+            .map(TraceBackfillStatus::new))
+    }
+
     /// Return (block_info.status, state_valid)
     /// Return Error if either field is missing
     pub fn get_block_status(&self, block_hash: H256) -> RpcResult<(u8, bool)> {
@@ -1317,6 +1403,77 @@ impl RpcImpl {
 
         Ok(Some(epoch_receipts))
     }
+
+    fn account_range(
+        &self, start_address: Option<RpcAddress>, limit: U64,
+        epoch_num: Option<EpochNumber>,
+    ) -> RpcResult<AccountRange>
+    {
+        if let Some(start_address) = &start_address {
+            self.check_address_network(start_address.network)?;
+        }
+        let limit = self.check_state_range_limit(limit)?;
+        let epoch_num = epoch_num.unwrap_or(EpochNumber::LatestState).into();
+        let network = *self.sync.network.get_network_type();
+
+        info!(
+            "RPC Request: debug_accountRange start_address={:?}, limit={:?}, epoch_num={:?}",
+            start_address, limit, epoch_num
+        );
+
+        let state_db = self
+            .consensus
+            .get_state_db_by_epoch_number(epoch_num, "epoch_num")?;
+
+        let mut start_key = start_address
+            .map_or(Address::zero(), |a| a.hex_address)
+            .as_ref()
+            .to_vec();
+        let mut accounts = Vec::with_capacity(limit);
+        // The leftover keys of the deleted accounts are skipped, so the range
+        // is read until there are `limit` accounts.
+        let maybe_next_key = loop {
+            // An address prefixes all keys of the account, and the account key
+            // is the first of them.
+            let (kvs, maybe_next_key) = state_db.read_original_range(
+                None,
+                &start_key,
+                limit - accounts.len(),
+                Some(Address::len_bytes()),
+            )?;
+            for (key, value) in kvs {
+                if key.len() != Address::len_bytes() {
+                    continue;
+                }
+                let account = account_result_to_rpc_result(
+                    "address",
+                    Account::new_from_rlp(
+                        Address::from_slice(&key),
+                        &Rlp::new(&value),
+                    ),
+                )?;
+                accounts.push(RpcAccount::try_from(account, network)?);
+            }
+            match maybe_next_key {
+                Some(next_key) if accounts.len() < limit => {
+                    start_key = next_key
+                }
+                _ => break maybe_next_key,
+            }
+        };
+        let next_address = match maybe_next_key {
+            Some(next_key) => Some(RpcAddress::try_from_h160(
+                Address::from_slice(&next_key[..Address::len_bytes()]),
+                network,
+            )?),
+            None => None,
+        };
+
+        Ok(AccountRange {
+            accounts,
+            next_address,
+        })
+    }
 }
 
 #[allow(dead_code)]
@@ -1347,6 +1504,9 @@ impl Cfx for CfxHandler {
             fn skipped_blocks_by_epoch(&self, num: EpochNumber) -> JsonRpcResult<Vec<H256>>;
             fn epoch_number(&self, epoch_num: Option<EpochNumber>) -> JsonRpcResult<U256>;
             fn gas_price(&self) -> BoxFuture<U256>;
+            fn fee_history(&self, epoch_count: U64, newest_epoch: EpochNumber, percentiles: Vec<f64>)
+                -> BoxFuture<FeeHistory>;
+            fn suggest_gas_price(&self, urgency: Option<GasPriceUrgency>) -> BoxFuture<U256>;
             fn next_nonce(&self, address: RpcAddress, num: Option<BlockHashOrEpochNumber>)
                 -> BoxFuture<U256>;
             fn get_status(&self) -> JsonRpcResult<RpcStatus>;
@@ -1385,6 +1545,8 @@ impl Cfx for CfxHandler {
             fn transaction_by_hash(&self, hash: H256) -> BoxFuture<Option<RpcTransaction>>;
             fn transaction_receipt(&self, tx_hash: H256) -> BoxFuture<Option<RpcReceipt>>;
             fn storage_root(&self, address: RpcAddress, epoch_num: Option<EpochNumber>) -> BoxFuture<Option<StorageRoot>>;
+            fn storage_range(&self, address: RpcAddress, start_key: Option<H256>, limit: U64, epoch_num: Option<EpochNumber>)
+                -> BoxFuture<StorageRange>;
             fn get_supply_info(&self, epoch_num: Option<EpochNumber>) -> JsonRpcResult<TokenSupplyInfo>;
         }
     }
@@ -1464,8 +1626,19 @@ impl LocalRpc for LocalRpcImpl {
         to self.common {
             fn clear_tx_pool(&self) -> JsonRpcResult<()>;
             fn net_node(&self, id: NodeId) -> JsonRpcResult<Option<(String, Node)>>;
+            fn net_node_record(&self, id: NodeId) -> JsonRpcResult<Option<NodeRecord>>;
             fn net_disconnect_node(&self, id: NodeId, op: Option<UpdateNodeOperation>)
                 -> JsonRpcResult<bool>;
+            fn net_members(&self) -> JsonRpcResult<Vec<MemberInfo>>;
+            fn net_add_member(&self, id: NodeId) -> JsonRpcResult<bool>;
+            fn net_remove_member(&self, id: NodeId) -> JsonRpcResult<bool>;
+            fn net_peer_scores(&self) -> JsonRpcResult<Vec<PeerScore>>;
+            fn net_banned_peers(&self) -> JsonRpcResult<Vec<BannedNode>>;
+            fn net_ban_peer(&self, id: NodeId, duration_s: Option<u64>) -> JsonRpcResult<()>;
+            fn net_unban_peer(&self, id: NodeId) -> JsonRpcResult<bool>;
+            fn net_peer_groups(&self) -> JsonRpcResult<Vec<PeerGroupInfo>>;
+            fn net_add_group_peer(&self, group: String, node_url: String) -> JsonRpcResult<bool>;
+            fn net_remove_group_peer(&self, group: String, id: NodeId) -> JsonRpcResult<bool>;
             fn net_sessions(&self, node_id: Option<NodeId>) -> JsonRpcResult<Vec<SessionDetails>>;
             fn net_throttling(&self) -> JsonRpcResult<throttling::Service>;
             fn tx_inspect(&self, hash: H256) -> JsonRpcResult<TxWithPoolInfo>;
@@ -1483,6 +1656,8 @@ impl LocalRpc for LocalRpcImpl {
             fn lock_account(&self, address: RpcAddress) -> JsonRpcResult<bool>;
             fn sign(&self, data: Bytes, address: RpcAddress, password: Option<String>)
                 -> JsonRpcResult<H520>;
+            fn sign_typed_data(&self, typed_data: TypedData, address: RpcAddress, password: Option<String>)
+                -> JsonRpcResult<H520>;
             fn tx_inspect_pending(&self, address: RpcAddress) -> JsonRpcResult<TxPoolPendingInfo>;
 
         }
@@ -1491,7 +1666,11 @@ impl LocalRpc for LocalRpcImpl {
             fn current_sync_phase(&self) -> JsonRpcResult<String>;
             fn consensus_graph_state(&self) -> JsonRpcResult<ConsensusGraphStates>;
             fn epoch_receipts(&self, epoch: EpochNumber) -> JsonRpcResult<Option<Vec<Vec<RpcReceipt>>>>;
+            fn account_range(&self, start_address: Option<RpcAddress>, limit: U64, epoch_num: Option<EpochNumber>)
+                -> JsonRpcResult<AccountRange>;
             fn sync_graph_state(&self) -> JsonRpcResult<SyncGraphStates>;
+            fn gc_status(&self) -> JsonRpcResult<GcStatus>;
+            fn trace_backfill_status(&self) -> JsonRpcResult<Option<TraceBackfillStatus>>;
             fn send_transaction(
                 &self, tx: SendTxRequest, password: Option<String>) -> BoxFuture<H256>;
             fn sign_transaction(&self, tx: SendTxRequest, password: Option<String>) -> JsonRpcResult<String>;
@@ -6,8 +6,9 @@ use crate::rpc::{
     types::{
         errors::check_rpc_address_network, Block as RpcBlock,
         BlockHashOrEpochNumber, Bytes, CheckBalanceAgainstTransactionResponse,
-        EpochNumber, RpcAddress, Status as RpcStatus,
-        Transaction as RpcTransaction, TxPoolPendingInfo, TxWithPoolInfo,
+        EpochNumber, FeeHistory, GasPriceUrgency, RpcAddress,
+        Status as RpcStatus, Transaction as RpcTransaction, TxPoolPendingInfo,
+        TxWithPoolInfo,
     },
     RpcResult,
 };
@@ -18,10 +19,13 @@ use cfx_parameters::{
 };
 use cfx_types::{Address, H160, H256, H520, U128, U256, U512, U64};
 use cfxcore::{
-    rpc_errors::invalid_params_check, BlockDataManager, ConsensusGraph,
-    ConsensusGraphTrait, PeerInfo, SharedConsensusGraph, SharedTransactionPool,
+    rpc_errors::{invalid_params_check, sign_result_to_rpc_result},
+    BlockDataManager, ConsensusGraph, ConsensusGraphTrait, PeerInfo,
+    SharedConsensusGraph, SharedTransactionPool,
+};
+use cfxcore_accounts::{
+    AccountProvider, SignRequest, SignRequestKind, TypedData,
 };
-use cfxcore_accounts::AccountProvider;
 use cfxkey::Password;
 use clap::crate_version;
 use jsonrpc_core::{
@@ -31,11 +35,14 @@ use keccak_hash::keccak;
 use network::{
     node_table::{Node, NodeEndpoint, NodeEntry, NodeId},
     throttling::{self, THROTTLING_SERVICE},
-    NetworkService, SessionDetails, UpdateNodeOperation,
+    BannedNode, Error as NetworkError, MemberInfo, MembershipSource,
+    NetworkService, NodeRecord, PeerGroupInfo, PeerScore, SessionDetails,
+    UpdateNodeOperation,
 };
 use num_bigint::{BigInt, ToBigInt};
 use parking_lot::{Condvar, Mutex};
 use primitives::{Account, Action, SignedTransaction};
+use serde_json::json;
 use std::{
     collections::{BTreeMap, HashSet},
     net::SocketAddr,
@@ -43,6 +50,12 @@ use std::{
     time::Duration,
 };
 
+fn network_error(e: NetworkError) -> RpcError {
+    let mut error = RpcError::invalid_request();
+    error.message = e.to_string();
+    error
+}
+
 fn grouped_txs<T, F>(
     txs: Vec<Arc<SignedTransaction>>, converter: F,
 ) -> BTreeMap<String, BTreeMap<usize, Vec<T>>>
@@ -166,6 +179,12 @@ impl RpcImpl {
             .expect("downcast should succeed")
     }
 
+    pub fn account_provider(&self) -> Arc<AccountProvider> {
+        self.accounts.clone()
+    }
+
+    pub fn network_type(&self) -> Network { *self.network.get_network_type() }
+
     fn check_address_network(&self, network: Network) -> RpcResult<()> {
         invalid_params_check(
             "address",
@@ -193,6 +212,38 @@ impl RpcImpl {
             .into())
     }
 
+    pub fn fee_history(
+        &self, epoch_count: U64, newest_epoch: EpochNumber,
+        percentiles: Vec<f64>,
+    ) -> RpcResult<FeeHistory>
+    {
+        info!(
+            "RPC Request: cfx_feeHistory epoch_count={:?} newest_epoch={:?} percentiles={:?}",
+            epoch_count, newest_epoch, percentiles
+        );
+        let consensus_graph = self.consensus_graph();
+        Ok(consensus_graph
+            .fee_history(
+                epoch_count.as_u64(),
+                newest_epoch.into(),
+                &percentiles,
+            )
+            .map_err(RpcError::invalid_params)?
+            .into())
+    }
+
+    pub fn suggest_gas_price(
+        &self, urgency: Option<GasPriceUrgency>,
+    ) -> RpcResult<U256> {
+        let urgency = urgency.unwrap_or(GasPriceUrgency::Normal);
+        info!("RPC Request: cfx_suggestGasPrice urgency={:?}", urgency);
+        let consensus_graph = self.consensus_graph();
+        Ok(consensus_graph
+            .suggest_gas_price(urgency.into_primitive())
+            .map_err(RpcError::invalid_params)?
+            .unwrap_or(cfxcore::consensus_parameters::ONE_GDRIP_IN_DRIP.into()))
+    }
+
     pub fn epoch_number(
         &self, epoch_num: Option<EpochNumber>,
     ) -> JsonRpcResult<U256> {
@@ -604,12 +655,82 @@ impl RpcImpl {
         }
     }
 
+    pub fn net_node_record(
+        &self, id: NodeId,
+    ) -> JsonRpcResult<Option<NodeRecord>> {
+        Ok(self.network.node_record(&id))
+    }
+
     pub fn net_disconnect_node(
         &self, id: NodeId, op: Option<UpdateNodeOperation>,
     ) -> JsonRpcResult<bool> {
         Ok(self.network.disconnect_node(&id, op))
     }
 
+    pub fn net_members(&self) -> JsonRpcResult<Vec<MemberInfo>> {
+        self.network
+            .members()
+            .ok_or_else(|| network_error("Membership is not enabled".into()))
+    }
+
+    pub fn net_add_member(&self, id: NodeId) -> JsonRpcResult<bool> {
+        info!("RPC Request: net_add_member({:?})", id);
+        self.network
+            .add_member(MembershipSource::Admin, id)
+            .map_err(network_error)
+    }
+
+    pub fn net_remove_member(&self, id: NodeId) -> JsonRpcResult<bool> {
+        info!("RPC Request: net_remove_member({:?})", id);
+        self.network
+            .remove_member(MembershipSource::Admin, &id)
+            .map_err(network_error)
+    }
+
+    pub fn net_peer_scores(&self) -> JsonRpcResult<Vec<PeerScore>> {
+        Ok(self.network.peer_scores().unwrap_or_default())
+    }
+
+    pub fn net_banned_peers(&self) -> JsonRpcResult<Vec<BannedNode>> {
+        Ok(self.network.banned_nodes().unwrap_or_default())
+    }
+
+    pub fn net_ban_peer(
+        &self, id: NodeId, duration_s: Option<u64>,
+    ) -> JsonRpcResult<()> {
+        info!("RPC Request: net_ban_peer({:?}, {:?})", id, duration_s);
+        self.network
+            .ban_node(&id, duration_s.map(Duration::from_secs), "ban requested")
+            .map_err(network_error)
+    }
+
+    pub fn net_unban_peer(&self, id: NodeId) -> JsonRpcResult<bool> {
+        info!("RPC Request: net_unban_peer({:?})", id);
+        self.network.unban_node(&id).map_err(network_error)
+    }
+
+    pub fn net_peer_groups(&self) -> JsonRpcResult<Vec<PeerGroupInfo>> {
+        Ok(self.network.peer_groups().unwrap_or_default())
+    }
+
+    pub fn net_add_group_peer(
+        &self, group: String, node_url: String,
+    ) -> JsonRpcResult<bool> {
+        info!("RPC Request: net_add_group_peer({}, {})", group, node_url);
+        self.network
+            .add_group_peer(&group, &node_url)
+            .map_err(network_error)
+    }
+
+    pub fn net_remove_group_peer(
+        &self, group: String, id: NodeId,
+    ) -> JsonRpcResult<bool> {
+        info!("RPC Request: net_remove_group_peer({}, {:?})", group, id);
+        self.network
+            .remove_group_peer(&group, &id)
+            .map_err(network_error)
+    }
+
     pub fn net_sessions(
         &self, node_id: Option<NodeId>,
     ) -> JsonRpcResult<Vec<SessionDetails>> {
@@ -851,16 +972,38 @@ impl RpcImpl {
     ) -> RpcResult<H520> {
         self.check_address_network(address.network)?;
 
-        let message = eth_data_hash(data.0);
+        let request = SignRequest {
+            kind: SignRequestKind::Message,
+            address: address.hex_address,
+            hash: eth_data_hash(data.0.clone()),
+            details: json!({ "data": data }),
+        };
         let password = password.map(Password::from);
-        let signature =
-            match self.accounts.sign(address.into(), password, message) {
-                Ok(signature) => signature,
-                Err(err) => {
-                    warn!("Unable to sign the message. With error {:?}", err);
-                    bail!(RpcError::internal_error());
-                }
-            };
+        let signature = sign_result_to_rpc_result(
+            "data",
+            self.accounts.sign_request(request, password),
+        )?;
+        Ok(H520(signature.into()))
+    }
+
+    pub fn sign_typed_data(
+        &self, typed_data: TypedData, address: RpcAddress,
+        password: Option<String>,
+    ) -> RpcResult<H520>
+    {
+        self.check_address_network(address.network)?;
+
+        let chain_id = self.consensus.best_chain_id();
+        let password = password.map(Password::from);
+        let signature = sign_result_to_rpc_result(
+            "typed_data",
+            self.accounts.sign_typed_data(
+                address.into(),
+                password,
+                &typed_data,
+                chain_id,
+            ),
+        )?;
         Ok(H520(signature.into()))
     }
 
@@ -13,13 +13,14 @@ use cfxcore::{
     rpc_errors::{account_result_to_rpc_result, invalid_params_check},
     ConsensusGraph, LightQueryService, PeerInfo, SharedConsensusGraph,
 };
-use cfxcore_accounts::AccountProvider;
+use cfxcore_accounts::{AccountProvider, TypedData};
 use delegate::delegate;
 use futures::future::{self, FutureExt, TryFutureExt};
 use jsonrpc_core::{BoxFuture, Error as RpcError, Result as JsonRpcResult};
 use network::{
     node_table::{Node, NodeId},
-    throttling, SessionDetails, UpdateNodeOperation,
+    throttling, BannedNode, MemberInfo, NodeRecord, PeerGroupInfo, PeerScore,
+    SessionDetails, UpdateNodeOperation,
 };
 use primitives::{
     Account, DepositInfo, StorageRoot, TransactionWithSignature, VoteStakeInfo,
@@ -37,13 +38,14 @@ use crate::{
         },
         traits::{cfx::Cfx, debug::LocalRpc, test::TestRpc},
         types::{
-            Account as RpcAccount, BlameInfo, Block as RpcBlock,
+            Account as RpcAccount, AccountRange, BlameInfo, Block as RpcBlock,
             BlockHashOrEpochNumber, Bytes, CallRequest,
             CheckBalanceAgainstTransactionResponse, ConsensusGraphStates,
-            EpochNumber, EstimateGasAndCollateralResponse, Log as RpcLog,
-            LogFilter as RpcFilter, Receipt as RpcReceipt,
-            RewardInfo as RpcRewardInfo, RpcAddress, SendTxRequest,
-            SponsorInfo, Status as RpcStatus, SyncGraphStates, TokenSupplyInfo,
+            EpochNumber, EstimateGasAndCollateralResponse, FeeHistory,
+            GasPriceUrgency, GcStatus, Log as RpcLog, LogFilter as RpcFilter,
+            Receipt as RpcReceipt, RewardInfo as RpcRewardInfo, RpcAddress,
+            SendTxRequest, SponsorInfo, Status as RpcStatus, StorageRange,
+            SyncGraphStates, TokenSupplyInfo, TraceBackfillStatus,
             Transaction as RpcTransaction, TxPoolPendingInfo, TxWithPoolInfo,
         },
         RpcBoxFuture, RpcResult,
@@ -909,6 +911,56 @@ impl RpcImpl {
         Box::new(fut.boxed().compat())
     }
 
+    pub fn fee_history(
+        &self, epoch_count: U64, newest_epoch: EpochNumber,
+        percentiles: Vec<f64>,
+    ) -> RpcBoxFuture<FeeHistory>
+    {
+        info!(
+            "RPC Request: cfx_feeHistory epoch_count={:?} newest_epoch={:?} percentiles={:?}",
+            epoch_count, newest_epoch, percentiles
+        );
+
+        // clone to avoid lifetime issues due to capturing `self`
+        let light = self.light.clone();
+
+        let fut = async move {
+            Ok(light
+                .fee_history(
+                    epoch_count.as_u64(),
+                    newest_epoch.into(),
+                    percentiles,
+                )
+                .await
+                .map_err(|e| e.to_string())
+                .map_err(RpcError::invalid_params)?
+                .into())
+        };
+
+        Box::new(fut.boxed().compat())
+    }
+
+    pub fn suggest_gas_price(
+        &self, urgency: Option<GasPriceUrgency>,
+    ) -> RpcBoxFuture<U256> {
+        let urgency = urgency.unwrap_or(GasPriceUrgency::Normal);
+        info!("RPC Request: cfx_suggestGasPrice urgency={:?}", urgency);
+
+        // clone to avoid lifetime issues due to capturing `self`
+        let light = self.light.clone();
+
+        let fut = async move {
+            Ok(light
+                .suggest_gas_price(urgency.into_primitive())
+                .await
+                .map_err(|e| e.to_string())
+                .map_err(RpcError::invalid_params)?
+                .unwrap_or(ONE_GDRIP_IN_DRIP.into()))
+        };
+
+        Box::new(fut.boxed().compat())
+    }
+
     pub fn interest_rate(
         &self, epoch: Option<EpochNumber>,
     ) -> RpcBoxFuture<U256> {
@@ -1039,6 +1091,7 @@ impl Cfx for CfxHandler {
             fn collateral_for_storage(&self, address: RpcAddress, num: Option<EpochNumber>) -> BoxFuture<U256>;
             fn deposit_list(&self, address: RpcAddress, num: Option<EpochNumber>) -> BoxFuture<Vec<DepositInfo>>;
             fn epoch_number(&self, epoch_num: Option<EpochNumber>) -> JsonRpcResult<U256>;
+            fn fee_history(&self, epoch_count: U64, newest_epoch: EpochNumber, percentiles: Vec<f64>) -> BoxFuture<FeeHistory>;
             fn gas_price(&self) -> BoxFuture<U256>;
             fn get_logs(&self, filter: RpcFilter) -> BoxFuture<Vec<RpcLog>>;
             fn interest_rate(&self, num: Option<EpochNumber>) -> BoxFuture<U256>;
@@ -1048,6 +1101,7 @@ impl Cfx for CfxHandler {
             fn staking_balance(&self, address: RpcAddress, num: Option<EpochNumber>) -> BoxFuture<U256>;
             fn storage_at(&self, addr: RpcAddress, pos: H256, epoch_number: Option<EpochNumber>) -> BoxFuture<Option<H256>>;
             fn storage_root(&self, address: RpcAddress, epoch_num: Option<EpochNumber>) -> BoxFuture<Option<StorageRoot>>;
+            fn suggest_gas_price(&self, urgency: Option<GasPriceUrgency>) -> BoxFuture<U256>;
             fn transaction_by_hash(&self, hash: H256) -> BoxFuture<Option<RpcTransaction>>;
             fn transaction_receipt(&self, tx_hash: H256) -> BoxFuture<Option<RpcReceipt>>;
             fn vote_list(&self, address: RpcAddress, num: Option<EpochNumber>) -> BoxFuture<Vec<VoteStakeInfo>>;
@@ -1060,6 +1114,7 @@ impl Cfx for CfxHandler {
         fn estimate_gas_and_collateral(&self, request: CallRequest, epoch_num: Option<EpochNumber>) -> JsonRpcResult<EstimateGasAndCollateralResponse>;
         fn get_block_reward_info(&self, num: EpochNumber) -> JsonRpcResult<Vec<RpcRewardInfo>>;
         fn get_supply_info(&self, epoch_num: Option<EpochNumber>) -> JsonRpcResult<TokenSupplyInfo>;
+        fn storage_range(&self, address: RpcAddress, start_key: Option<H256>, limit: U64, epoch_num: Option<EpochNumber>) -> BoxFuture<StorageRange>;
     }
 }
 
@@ -1129,11 +1184,23 @@ impl LocalRpc for DebugRpcImpl {
             fn clear_tx_pool(&self) -> JsonRpcResult<()>;
             fn lock_account(&self, address: RpcAddress) -> JsonRpcResult<bool>;
             fn net_disconnect_node(&self, id: NodeId, op: Option<UpdateNodeOperation>) -> JsonRpcResult<bool>;
+            fn net_add_group_peer(&self, group: String, node_url: String) -> JsonRpcResult<bool>;
+            fn net_add_member(&self, id: NodeId) -> JsonRpcResult<bool>;
+            fn net_ban_peer(&self, id: NodeId, duration_s: Option<u64>) -> JsonRpcResult<()>;
+            fn net_banned_peers(&self) -> JsonRpcResult<Vec<BannedNode>>;
+            fn net_members(&self) -> JsonRpcResult<Vec<MemberInfo>>;
             fn net_node(&self, id: NodeId) -> JsonRpcResult<Option<(String, Node)>>;
+            fn net_node_record(&self, id: NodeId) -> JsonRpcResult<Option<NodeRecord>>;
+            fn net_peer_groups(&self) -> JsonRpcResult<Vec<PeerGroupInfo>>;
+            fn net_peer_scores(&self) -> JsonRpcResult<Vec<PeerScore>>;
+            fn net_remove_group_peer(&self, group: String, id: NodeId) -> JsonRpcResult<bool>;
+            fn net_remove_member(&self, id: NodeId) -> JsonRpcResult<bool>;
             fn net_sessions(&self, node_id: Option<NodeId>) -> JsonRpcResult<Vec<SessionDetails>>;
             fn net_throttling(&self) -> JsonRpcResult<throttling::Service>;
+            fn net_unban_peer(&self, id: NodeId) -> JsonRpcResult<bool>;
             fn new_account(&self, password: String) -> JsonRpcResult<RpcAddress>;
             fn sign(&self, data: Bytes, address: RpcAddress, password: Option<String>) -> JsonRpcResult<H520>;
+            fn sign_typed_data(&self, typed_data: TypedData, address: RpcAddress, password: Option<String>) -> JsonRpcResult<H520>;
             fn tx_inspect_pending(&self, address: RpcAddress) -> JsonRpcResult<TxPoolPendingInfo>;
             fn tx_inspect(&self, hash: H256) -> JsonRpcResult<TxWithPoolInfo>;
             fn txpool_content(&self, address: Option<RpcAddress>) -> JsonRpcResult<BTreeMap<String, BTreeMap<String, BTreeMap<usize, Vec<RpcTransaction>>>>>;
@@ -1149,10 +1216,13 @@ impl LocalRpc for DebugRpcImpl {
     }
 
     not_supported! {
+        fn account_range(&self, start_address: Option<RpcAddress>, limit: U64, epoch_num: Option<EpochNumber>) -> JsonRpcResult<AccountRange>;
         fn consensus_graph_state(&self) -> JsonRpcResult<ConsensusGraphStates>;
         fn current_sync_phase(&self) -> JsonRpcResult<String>;
         fn epoch_receipts(&self, epoch: EpochNumber) -> JsonRpcResult<Option<Vec<Vec<RpcReceipt>>>>;
+        fn gc_status(&self) -> JsonRpcResult<GcStatus>;
         fn sign_transaction(&self, tx: SendTxRequest, password: Option<String>) -> JsonRpcResult<String>;
         fn sync_graph_state(&self) -> JsonRpcResult<SyncGraphStates>;
+        fn trace_backfill_status(&self) -> JsonRpcResult<Option<TraceBackfillStatus>>;
     }
 }
@@ -0,0 +1,482 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::{
+    common::delegate_convert::into_jsonrpc_result,
+    rpc::{
+        traits::personal::Personal,
+        types::{errors::check_rpc_address_network, RpcAddress},
+        RpcResult,
+    },
+};
+use cfx_addr::Network;
+use cfx_types::{Address, H160};
+use cfxcore::rpc_errors::invalid_params_check;
+use cfxcore_accounts::{AccountProvider, KeyFile};
+use jsonrpc_core::Result as JsonRpcResult;
+use serde_json::Value;
+use std::sync::Arc;
+
+/// Handles the `personal_*` RPCs with the account provider of the node.
+pub struct PersonalHandler {
+    accounts: Arc<AccountProvider>,
+    network: Network,
+}
+
+impl PersonalHandler {
+    pub fn new(accounts: Arc<AccountProvider>, network: Network) -> Self {
+        PersonalHandler { accounts, network }
+    }
+
+    fn check_address_network(&self, address: &RpcAddress) -> RpcResult<()> {
+        invalid_params_check(
+            "address",
+            check_rpc_address_network(Some(address.network), &self.network),
+        )
+    }
+
+    fn to_rpc_address(&self, address: Address) -> RpcResult<RpcAddress> {
+        Ok(RpcAddress::try_from_h160(address, self.network)?)
+    }
+
+    fn list_accounts_impl(&self) -> RpcResult<Vec<RpcAddress>> {
+        info!("RPC Request: personal_listAccounts()");
+        let accounts = self.accounts.accounts().map_err(|e| {
+            format!("Could not fetch accounts. With error {:?}", e)
+        })?;
+        accounts
+            .into_iter()
+            .map(|address| self.to_rpc_address(address))
+            .collect()
+    }
+
+    fn set_account_name_impl(
+        &self, address: RpcAddress, name: String,
+    ) -> RpcResult<bool> {
+        info!(
+            "RPC Request: personal_setAccountName address={:?} name={:?}",
+            address, name
+        );
+        self.check_address_network(&address)?;
+        self.accounts
+            .set_account_name(address.into(), name)
+            .map_err(|e| {
+                format!("Could not set account name. With error {:?}", e)
+            })?;
+        Ok(true)
+    }
+
+    fn change_password_impl(
+        &self, address: RpcAddress, password: String, new_password: String,
+    ) -> RpcResult<bool> {
+        info!("RPC Request: personal_changePassword address={:?}", address);
+        self.check_address_network(&address)?;
+        self.accounts
+            .change_password(
+                &address.into(),
+                password.into(),
+                new_password.into(),
+            )
+            .map_err(|e| {
+                format!("Could not change password. With error {:?}", e)
+            })?;
+        Ok(true)
+    }
+
+    fn remove_account_impl(
+        &self, address: RpcAddress, password: String,
+    ) -> RpcResult<bool> {
+        info!("RPC Request: personal_removeAccount address={:?}", address);
+        self.check_address_network(&address)?;
+        self.accounts
+            .kill_account(&address.into(), &password.into())
+            .map_err(|e| {
+                format!("Could not remove account. With error {:?}", e)
+            })?;
+        Ok(true)
+    }
+
+    fn export_account_impl(
+        &self, address: RpcAddress, password: String,
+    ) -> RpcResult<KeyFile> {
+        info!("RPC Request: personal_exportAccount address={:?}", address);
+        self.check_address_network(&address)?;
+        Ok(self
+            .accounts
+            .export_account(&address.into(), password.into())
+            .map_err(|e| {
+                format!("Could not export account. With error {:?}", e)
+            })?)
+    }
+
+    fn import_account_impl(
+        &self, key_file: Value, password: String,
+    ) -> RpcResult<RpcAddress> {
+        info!("RPC Request: personal_importAccount");
+        let address = self
+            .accounts
+            .import_wallet(
+                key_file.to_string().as_bytes(),
+                &password.into(),
+                false, /* gen_id */
+            )
+            .map_err(|e| {
+                format!("Could not import account. With error {:?}", e)
+            })?;
+        self.to_rpc_address(address)
+    }
+
+    fn list_geth_accounts_impl(
+        &self, testnet: Option<bool>,
+    ) -> RpcResult<Vec<H160>> {
+        info!(
+            "RPC Request: personal_listGethAccounts testnet={:?}",
+            testnet
+        );
+        Ok(self.accounts.list_geth_accounts(testnet.unwrap_or(false)))
+    }
+
+    fn import_geth_accounts_impl(
+        &self, addresses: Vec<H160>, testnet: Option<bool>,
+    ) -> RpcResult<Vec<H160>> {
+        info!(
+            "RPC Request: personal_importGethAccounts addresses={:?} testnet={:?}",
+            addresses, testnet
+        );
+        Ok(self
+            .accounts
+            .import_geth_accounts(addresses, testnet.unwrap_or(false))
+            .map_err(|e| {
+                format!("Could not import geth accounts. With error {:?}", e)
+            })?)
+    }
+
+    fn new_vault_impl(
+        &self, name: String, password: String,
+    ) -> RpcResult<bool> {
+        info!("RPC Request: personal_newVault name={:?}", name);
+        self.accounts
+            .create_vault(&name, &password.into())
+            .map_err(|e| {
+                format!("Could not create vault. With error {:?}", e)
+            })?;
+        Ok(true)
+    }
+
+    fn open_vault_impl(
+        &self, name: String, password: String,
+    ) -> RpcResult<bool> {
+        info!("RPC Request: personal_openVault name={:?}", name);
+        self.accounts
+            .open_vault(&name, &password.into())
+            .map_err(|e| format!("Could not open vault. With error {:?}", e))?;
+        Ok(true)
+    }
+
+    fn close_vault_impl(&self, name: String) -> RpcResult<bool> {
+        info!("RPC Request: personal_closeVault name={:?}", name);
+        self.accounts.close_vault(&name).map_err(|e| {
+            format!("Could not close vault. With error {:?}", e)
+        })?;
+        Ok(true)
+    }
+
+    fn delete_vault_impl(
+        &self, name: String, password: String,
+    ) -> RpcResult<bool> {
+        info!("RPC Request: personal_deleteVault name={:?}", name);
+        self.accounts
+            .delete_vault(&name, &password.into())
+            .map_err(|e| {
+                format!("Could not delete vault. With error {:?}", e)
+            })?;
+        Ok(true)
+    }
+
+    fn list_vaults_impl(&self) -> RpcResult<Vec<String>> {
+        info!("RPC Request: personal_listVaults()");
+        Ok(self.accounts.list_vaults().map_err(|e| {
+            format!("Could not list vaults. With error {:?}", e)
+        })?)
+    }
+
+    fn list_opened_vaults_impl(&self) -> RpcResult<Vec<String>> {
+        info!("RPC Request: personal_listOpenedVaults()");
+        Ok(self.accounts.list_opened_vaults().map_err(|e| {
+            format!("Could not list opened vaults. With error {:?}", e)
+        })?)
+    }
+
+    fn change_vault_password_impl(
+        &self, name: String, new_password: String,
+    ) -> RpcResult<bool> {
+        info!("RPC Request: personal_changeVaultPassword name={:?}", name);
+        self.accounts
+            .change_vault_password(&name, &new_password.into())
+            .map_err(|e| {
+                format!("Could not change vault password. With error {:?}", e)
+            })?;
+        Ok(true)
+    }
+
+    fn change_vault_impl(
+        &self, address: RpcAddress, vault: String,
+    ) -> RpcResult<bool> {
+        info!(
+            "RPC Request: personal_changeVault address={:?} vault={:?}",
+            address, vault
+        );
+        self.check_address_network(&address)?;
+        self.accounts
+            .change_vault(address.into(), &vault)
+            .map_err(|e| {
+                format!("Could not change vault. With error {:?}", e)
+            })?;
+        Ok(true)
+    }
+
+    fn get_vault_meta_impl(&self, name: String) -> RpcResult<String> {
+        info!("RPC Request: personal_getVaultMeta name={:?}", name);
+        Ok(self.accounts.get_vault_meta(&name).map_err(|e| {
+            format!("Could not get vault metadata. With error {:?}", e)
+        })?)
+    }
+
+    fn set_vault_meta_impl(
+        &self, name: String, meta: String,
+    ) -> RpcResult<bool> {
+        info!("RPC Request: personal_setVaultMeta name={:?}", name);
+        self.accounts.set_vault_meta(&name, &meta).map_err(|e| {
+            format!("Could not set vault metadata. With error {:?}", e)
+        })?;
+        Ok(true)
+    }
+}
+
+impl Personal for PersonalHandler {
+    fn list_accounts(&self) -> JsonRpcResult<Vec<RpcAddress>> {
+        into_jsonrpc_result(self.list_accounts_impl())
+    }
+
+    fn set_account_name(
+        &self, address: RpcAddress, name: String,
+    ) -> JsonRpcResult<bool> {
+        into_jsonrpc_result(self.set_account_name_impl(address, name))
+    }
+
+    fn change_password(
+        &self, address: RpcAddress, password: String, new_password: String,
+    ) -> JsonRpcResult<bool> {
+        into_jsonrpc_result(self.change_password_impl(
+            address,
+            password,
+            new_password,
+        ))
+    }
+
+    fn remove_account(
+        &self, address: RpcAddress, password: String,
+    ) -> JsonRpcResult<bool> {
+        into_jsonrpc_result(self.remove_account_impl(address, password))
+    }
+
+    fn export_account(
+        &self, address: RpcAddress, password: String,
+    ) -> JsonRpcResult<KeyFile> {
+        into_jsonrpc_result(self.export_account_impl(address, password))
+    }
+
+    fn import_account(
+        &self, key_file: Value, password: String,
+    ) -> JsonRpcResult<RpcAddress> {
+        into_jsonrpc_result(self.import_account_impl(key_file, password))
+    }
+
+    fn list_geth_accounts(
+        &self, testnet: Option<bool>,
+    ) -> JsonRpcResult<Vec<H160>> {
+        into_jsonrpc_result(self.list_geth_accounts_impl(testnet))
+    }
+
+    fn import_geth_accounts(
+        &self, addresses: Vec<H160>, testnet: Option<bool>,
+    ) -> JsonRpcResult<Vec<H160>> {
+        into_jsonrpc_result(self.import_geth_accounts_impl(addresses, testnet))
+    }
+
+    fn new_vault(&self, name: String, password: String) -> JsonRpcResult<bool> {
+        into_jsonrpc_result(self.new_vault_impl(name, password))
+    }
+
+    fn open_vault(
+        &self, name: String, password: String,
+    ) -> JsonRpcResult<bool> {
+        into_jsonrpc_result(self.open_vault_impl(name, password))
+    }
+
+    fn close_vault(&self, name: String) -> JsonRpcResult<bool> {
+        into_jsonrpc_result(self.close_vault_impl(name))
+    }
+
+    fn delete_vault(
+        &self, name: String, password: String,
+    ) -> JsonRpcResult<bool> {
+        into_jsonrpc_result(self.delete_vault_impl(name, password))
+    }
+
+    fn list_vaults(&self) -> JsonRpcResult<Vec<String>> {
+        into_jsonrpc_result(self.list_vaults_impl())
+    }
+
+    fn list_opened_vaults(&self) -> JsonRpcResult<Vec<String>> {
+        into_jsonrpc_result(self.list_opened_vaults_impl())
+    }
+
+    fn change_vault_password(
+        &self, name: String, new_password: String,
+    ) -> JsonRpcResult<bool> {
+        into_jsonrpc_result(self.change_vault_password_impl(name, new_password))
+    }
+
+    fn change_vault(
+        &self, address: RpcAddress, vault: String,
+    ) -> JsonRpcResult<bool> {
+        into_jsonrpc_result(self.change_vault_impl(address, vault))
+    }
+
+    fn get_vault_meta(&self, name: String) -> JsonRpcResult<String> {
+        into_jsonrpc_result(self.get_vault_meta_impl(name))
+    }
+
+    fn set_vault_meta(
+        &self, name: String, meta: String,
+    ) -> JsonRpcResult<bool> {
+        into_jsonrpc_result(self.set_vault_meta_impl(name, meta))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::account_provider;
+    use std::{env, fs};
+
+    fn handler(name: &str) -> (PersonalHandler, std::path::PathBuf) {
+        let dir = env::temp_dir().join(format!(
+            "conflux_personal_rpc_{}_{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        let accounts = account_provider(
+            Some(dir.to_str().unwrap().into()),
+            Some(1024), /* sstore_iterations */
+            None,       /* refresh_time */
+        )
+        .unwrap();
+        (PersonalHandler::new(Arc::new(accounts), Network::Test), dir)
+    }
+
+    #[test]
+    fn test_account_management() {
+        let (personal, dir) = handler("accounts");
+        let address = personal
+            .to_rpc_address(
+                personal.accounts.new_account(&"password1".into()).unwrap(),
+            )
+            .unwrap();
+        assert_eq!(personal.list_accounts().unwrap(), vec![address.clone()]);
+
+        assert!(personal
+            .set_account_name(address.clone(), "alice".into())
+            .unwrap());
+        assert!(personal
+            .change_password(
+                address.clone(),
+                "password1".into(),
+                "password2".into()
+            )
+            .unwrap());
+        assert!(personal
+            .export_account(address.clone(), "password1".into())
+            .is_err());
+        let key_file = personal
+            .export_account(address.clone(), "password2".into())
+            .unwrap();
+        let key_file = serde_json::to_value(&key_file).unwrap();
+        assert_eq!(key_file["name"], "alice");
+
+        // An address of another network is rejected.
+        let mainnet_address =
+            RpcAddress::try_from_h160(address.hex_address, Network::Main)
+                .unwrap();
+        assert!(personal
+            .remove_account(mainnet_address, "password2".into())
+            .is_err());
+
+        assert!(personal
+            .remove_account(address.clone(), "password2".into())
+            .unwrap());
+        assert!(personal.list_accounts().unwrap().is_empty());
+
+        // The exported key file can be imported back.
+        assert_eq!(
+            personal
+                .import_account(key_file, "password2".into())
+                .unwrap(),
+            address
+        );
+        assert_eq!(personal.list_accounts().unwrap(), vec![address]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_vault_management() {
+        let (personal, dir) = handler("vaults");
+        let vault = "vault1".to_string();
+        assert!(personal
+            .new_vault(vault.clone(), "password1".into())
+            .unwrap());
+        assert_eq!(personal.list_vaults().unwrap(), vec![vault.clone()]);
+        assert_eq!(personal.list_opened_vaults().unwrap(), vec![vault.clone()]);
+
+        assert!(personal.set_vault_meta(vault.clone(), "{}".into()).unwrap());
+        assert_eq!(personal.get_vault_meta(vault.clone()).unwrap(), "{}");
+        assert!(personal
+            .change_vault_password(vault.clone(), "password2".into())
+            .unwrap());
+        assert!(personal.close_vault(vault.clone()).unwrap());
+        assert!(personal.list_opened_vaults().unwrap().is_empty());
+        assert!(personal
+            .open_vault(vault.clone(), "password1".into())
+            .is_err());
+        assert!(personal
+            .open_vault(vault.clone(), "password2".into())
+            .unwrap());
+
+        // A vault with accounts can not be deleted.
+        let address = personal
+            .to_rpc_address(
+                personal.accounts.new_account(&"password".into()).unwrap(),
+            )
+            .unwrap();
+        assert!(personal
+            .change_vault(address.clone(), vault.clone())
+            .unwrap());
+        assert!(personal
+            .delete_vault(vault.clone(), "password2".into())
+            .is_err());
+
+        assert!(personal.change_vault(address, "".into()).unwrap());
+        assert!(personal
+            .delete_vault(vault.clone(), "password1".into())
+            .is_err());
+        assert!(personal
+            .delete_vault(vault.clone(), "password2".into())
+            .unwrap());
+        assert!(personal.list_vaults().unwrap().is_empty());
+        assert!(personal.list_opened_vaults().unwrap().is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
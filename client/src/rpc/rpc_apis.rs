@@ -8,6 +8,7 @@ use std::{collections::HashSet, str::FromStr};
 pub enum Api {
     Cfx,
     Debug,
+    Personal,
     Pubsub,
     Test,
     Trace,
@@ -21,6 +22,7 @@ impl FromStr for Api {
         match s {
             "cfx" => Ok(Cfx),
             "debug" => Ok(Debug),
+            "personal" => Ok(Personal),
             "pubsub" => Ok(Pubsub),
             "test" => Ok(Test),
             "trace" => Ok(Trace),
@@ -5,9 +5,10 @@
 use super::super::types::{
     Account as RpcAccount, Block, Bytes, CallRequest,
     CheckBalanceAgainstTransactionResponse, EpochNumber,
-    EstimateGasAndCollateralResponse, Log as RpcLog, LogFilter as RpcFilter,
-    Receipt as RpcReceipt, RewardInfo as RpcRewardInfo, SponsorInfo,
-    Status as RpcStatus, TokenSupplyInfo, Transaction,
+    EstimateGasAndCollateralResponse, FeeHistory, GasPriceUrgency,
+    Log as RpcLog, LogFilter as RpcFilter, Receipt as RpcReceipt,
+    RewardInfo as RpcRewardInfo, SponsorInfo, Status as RpcStatus,
+    StorageRange, TokenSupplyInfo, Transaction,
 };
 use crate::rpc::types::{BlockHashOrEpochNumber, RpcAddress};
 use cfx_types::{H256, U256, U64};
@@ -38,6 +39,21 @@ pub trait Cfx {
     #[rpc(name = "cfx_gasPrice")]
     fn gas_price(&self) -> BoxFuture<U256>;
 
+    /// Returns the gas price percentiles of the transactions packed in the
+    /// `epoch_count` epochs ending at `newest_epoch`.
+    #[rpc(name = "cfx_feeHistory")]
+    fn fee_history(
+        &self, epoch_count: U64, newest_epoch: EpochNumber,
+        percentiles: Vec<f64>,
+    ) -> BoxFuture<FeeHistory>;
+
+    /// Returns a gas price suggestion for the given urgency (`slow`, `normal`
+    /// or `fast`, default `normal`).
+    #[rpc(name = "cfx_suggestGasPrice")]
+    fn suggest_gas_price(
+        &self, urgency: Option<GasPriceUrgency>,
+    ) -> BoxFuture<U256>;
+
     /// Returns highest epoch number.
     #[rpc(name = "cfx_epochNumber")]
     fn epoch_number(
@@ -103,6 +119,14 @@ pub trait Cfx {
         &self, address: RpcAddress, epoch_num: Option<EpochNumber>,
     ) -> BoxFuture<Option<StorageRoot>>;
 
+    /// Returns at most `limit` storage entries of a contract from `start_key`
+    /// in the order of keys, with the start key of the next page if any.
+    #[rpc(name = "cfx_getStorageRange")]
+    fn storage_range(
+        &self, address: RpcAddress, start_key: Option<H256>, limit: U64,
+        epoch_num: Option<EpochNumber>,
+    ) -> BoxFuture<StorageRange>;
+
     /// Returns block with given hash.
     #[rpc(name = "cfx_getBlockByHash")]
     fn block_by_hash(
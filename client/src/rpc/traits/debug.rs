@@ -3,17 +3,20 @@
 // See http://www.gnu.org/licenses/
 
 use super::super::types::{
-    Bytes as RpcBytes, ConsensusGraphStates, EpochNumber,
-    Receipt as RpcReceipt, RpcAddress, SyncGraphStates,
-    Transaction as RpcTransaction, TxPoolPendingInfo, TxWithPoolInfo,
+    AccountRange, Bytes as RpcBytes, ConsensusGraphStates, EpochNumber,
+    GcStatus, Receipt as RpcReceipt, RpcAddress, SyncGraphStates,
+    TraceBackfillStatus, Transaction as RpcTransaction, TxPoolPendingInfo,
+    TxWithPoolInfo,
 };
 use crate::rpc::types::SendTxRequest;
-use cfx_types::{H256, H520, U128};
+use cfx_types::{H256, H520, U128, U64};
+use cfxcore_accounts::TypedData;
 use jsonrpc_core::{BoxFuture, Result as JsonRpcResult};
 use jsonrpc_derive::rpc;
 use network::{
     node_table::{Node, NodeId},
-    throttling, SessionDetails, UpdateNodeOperation,
+    throttling, BannedNode, MemberInfo, NodeRecord, PeerGroupInfo, PeerScore,
+    SessionDetails, UpdateNodeOperation,
 };
 use std::collections::BTreeMap;
 
@@ -63,11 +66,71 @@ pub trait LocalRpc {
         &self, node_id: NodeId,
     ) -> JsonRpcResult<Option<(String, Node)>>;
 
+    /// Returns the node record received in discovery from the node, which
+    /// advertises its node type, protocols and snapshot range.
+    #[rpc(name = "net_node_record")]
+    fn net_node_record(
+        &self, node_id: NodeId,
+    ) -> JsonRpcResult<Option<NodeRecord>>;
+
     #[rpc(name = "net_disconnect_node")]
     fn net_disconnect_node(
         &self, id: NodeId, op: Option<UpdateNodeOperation>,
     ) -> JsonRpcResult<bool>;
 
+    /// Returns the members of the consortium and their sources.
+    #[rpc(name = "net_members")]
+    fn net_members(&self) -> JsonRpcResult<Vec<MemberInfo>>;
+
+    /// Adds a member of the consortium, returns false if already added by the
+    /// RPC.
+    #[rpc(name = "net_add_member")]
+    fn net_add_member(&self, node_id: NodeId) -> JsonRpcResult<bool>;
+
+    /// Removes a member added by the RPC, and disconnects the node if it is
+    /// no longer a member. Returns whether the node is no longer a member.
+    #[rpc(name = "net_remove_member")]
+    fn net_remove_member(&self, node_id: NodeId) -> JsonRpcResult<bool>;
+
+    /// Returns the reputation scores of the peers that have misbehaved,
+    /// ordered from the lowest score.
+    #[rpc(name = "net_peer_scores")]
+    fn net_peer_scores(&self) -> JsonRpcResult<Vec<PeerScore>>;
+
+    /// Returns the banned peers whose ban is not expired.
+    #[rpc(name = "net_banned_peers")]
+    fn net_banned_peers(&self) -> JsonRpcResult<Vec<BannedNode>>;
+
+    /// Bans a peer for `duration_s` seconds, or the configured
+    /// `peer_ban_duration_s` if not specified, and disconnects it.
+    #[rpc(name = "net_ban_peer")]
+    fn net_ban_peer(
+        &self, node_id: NodeId, duration_s: Option<u64>,
+    ) -> JsonRpcResult<()>;
+
+    /// Lifts the ban of a peer and resets its reputation score. Returns false
+    /// if the peer is not banned.
+    #[rpc(name = "net_unban_peer")]
+    fn net_unban_peer(&self, node_id: NodeId) -> JsonRpcResult<bool>;
+
+    /// Returns the static peer groups and whether their peers are connected.
+    #[rpc(name = "net_peer_groups")]
+    fn net_peer_groups(&self) -> JsonRpcResult<Vec<PeerGroupInfo>>;
+
+    /// Adds a static peer by its node url to the group, which is created if
+    /// not exist. Returns false if already in the group.
+    #[rpc(name = "net_add_group_peer")]
+    fn net_add_group_peer(
+        &self, group: String, node_url: String,
+    ) -> JsonRpcResult<bool>;
+
+    /// Removes a static peer from the group. Returns whether the node is no
+    /// longer a static peer of any group.
+    #[rpc(name = "net_remove_group_peer")]
+    fn net_remove_group_peer(
+        &self, group: String, node_id: NodeId,
+    ) -> JsonRpcResult<bool>;
+
     #[rpc(name = "net_sessions")]
     fn net_sessions(
         &self, node_id: Option<NodeId>,
@@ -82,6 +145,15 @@ pub trait LocalRpc {
     #[rpc(name = "sync_graph_state")]
     fn sync_graph_state(&self) -> JsonRpcResult<SyncGraphStates>;
 
+    #[rpc(name = "gc_status")]
+    fn gc_status(&self) -> JsonRpcResult<GcStatus>;
+
+    /// Return null if the trace backfill is not enabled or not started yet.
+    #[rpc(name = "trace_backfill_status")]
+    fn trace_backfill_status(
+        &self,
+    ) -> JsonRpcResult<Option<TraceBackfillStatus>>;
+
     #[rpc(name = "cfx_sendTransaction")]
     fn send_transaction(
         &self, tx: SendTxRequest, password: Option<String>,
@@ -110,6 +182,14 @@ pub trait LocalRpc {
         &self, data: RpcBytes, address: RpcAddress, password: Option<String>,
     ) -> JsonRpcResult<H520>;
 
+    /// Signs typed structured data. The domain `chainId` must match the chain
+    /// id of this node.
+    #[rpc(name = "cfx_signTypedData")]
+    fn sign_typed_data(
+        &self, typed_data: TypedData, address: RpcAddress,
+        password: Option<String>,
+    ) -> JsonRpcResult<H520>;
+
     #[rpc(name = "cfx_signTransaction")]
     fn sign_transaction(
         &self, tx: SendTxRequest, password: Option<String>,
@@ -119,4 +199,12 @@ pub trait LocalRpc {
     fn epoch_receipts(
         &self, epoch: EpochNumber,
     ) -> JsonRpcResult<Option<Vec<Vec<RpcReceipt>>>>;
+
+    /// Returns at most `limit` accounts from `start_address` in the order of
+    /// addresses, with the start address of the next page if any.
+    #[rpc(name = "debug_accountRange")]
+    fn account_range(
+        &self, start_address: Option<RpcAddress>, limit: U64,
+        epoch_num: Option<EpochNumber>,
+    ) -> JsonRpcResult<AccountRange>;
 }
@@ -4,12 +4,14 @@
 
 pub use cfx::Cfx;
 pub use debug::LocalRpc;
+pub use personal::Personal;
 pub use pubsub::PubSub;
 pub use test::TestRpc;
 pub use trace::Trace;
 
 pub mod cfx;
 pub mod debug;
+pub mod personal;
 pub mod pubsub;
 pub mod test;
 pub mod trace;
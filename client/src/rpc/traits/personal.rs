@@ -0,0 +1,109 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use super::super::types::RpcAddress;
+use cfx_types::H160;
+use cfxcore_accounts::KeyFile;
+use jsonrpc_core::Result as JsonRpcResult;
+use jsonrpc_derive::rpc;
+use serde_json::Value;
+
+/// Management of the accounts and vaults of the local key store.
+#[rpc(server)]
+pub trait Personal {
+    /// Returns the accounts in the root store and the opened vaults.
+    #[rpc(name = "personal_listAccounts")]
+    fn list_accounts(&self) -> JsonRpcResult<Vec<RpcAddress>>;
+
+    /// Sets the name of an account.
+    #[rpc(name = "personal_setAccountName")]
+    fn set_account_name(
+        &self, address: RpcAddress, name: String,
+    ) -> JsonRpcResult<bool>;
+
+    /// Changes the password of an account.
+    #[rpc(name = "personal_changePassword")]
+    fn change_password(
+        &self, address: RpcAddress, password: String, new_password: String,
+    ) -> JsonRpcResult<bool>;
+
+    /// Permanently removes an account from the key store.
+    #[rpc(name = "personal_removeAccount")]
+    fn remove_account(
+        &self, address: RpcAddress, password: String,
+    ) -> JsonRpcResult<bool>;
+
+    /// Returns the keystore JSON of an account.
+    #[rpc(name = "personal_exportAccount")]
+    fn export_account(
+        &self, address: RpcAddress, password: String,
+    ) -> JsonRpcResult<KeyFile>;
+
+    /// Imports an account from its keystore JSON.
+    #[rpc(name = "personal_importAccount")]
+    fn import_account(
+        &self, key_file: Value, password: String,
+    ) -> JsonRpcResult<RpcAddress>;
+
+    /// Returns the addresses of the accounts in the geth keystore.
+    #[rpc(name = "personal_listGethAccounts")]
+    fn list_geth_accounts(
+        &self, testnet: Option<bool>,
+    ) -> JsonRpcResult<Vec<H160>>;
+
+    /// Imports the given accounts from the geth keystore.
+    #[rpc(name = "personal_importGethAccounts")]
+    fn import_geth_accounts(
+        &self, addresses: Vec<H160>, testnet: Option<bool>,
+    ) -> JsonRpcResult<Vec<H160>>;
+
+    /// Creates a new vault and opens it.
+    #[rpc(name = "personal_newVault")]
+    fn new_vault(&self, name: String, password: String) -> JsonRpcResult<bool>;
+
+    /// Opens an existing vault.
+    #[rpc(name = "personal_openVault")]
+    fn open_vault(&self, name: String, password: String)
+        -> JsonRpcResult<bool>;
+
+    /// Closes an opened vault.
+    #[rpc(name = "personal_closeVault")]
+    fn close_vault(&self, name: String) -> JsonRpcResult<bool>;
+
+    /// Deletes an empty vault.
+    #[rpc(name = "personal_deleteVault")]
+    fn delete_vault(
+        &self, name: String, password: String,
+    ) -> JsonRpcResult<bool>;
+
+    /// Returns the names of all vaults.
+    #[rpc(name = "personal_listVaults")]
+    fn list_vaults(&self) -> JsonRpcResult<Vec<String>>;
+
+    /// Returns the names of the opened vaults.
+    #[rpc(name = "personal_listOpenedVaults")]
+    fn list_opened_vaults(&self) -> JsonRpcResult<Vec<String>>;
+
+    /// Changes the password of an opened vault.
+    #[rpc(name = "personal_changeVaultPassword")]
+    fn change_vault_password(
+        &self, name: String, new_password: String,
+    ) -> JsonRpcResult<bool>;
+
+    /// Moves an account into an opened vault, or back to the root store if
+    /// `vault` is empty.
+    #[rpc(name = "personal_changeVault")]
+    fn change_vault(
+        &self, address: RpcAddress, vault: String,
+    ) -> JsonRpcResult<bool>;
+
+    /// Returns the metadata of an opened vault.
+    #[rpc(name = "personal_getVaultMeta")]
+    fn get_vault_meta(&self, name: String) -> JsonRpcResult<String>;
+
+    /// Sets the metadata of an opened vault.
+    #[rpc(name = "personal_setVaultMeta")]
+    fn set_vault_meta(&self, name: String, meta: String)
+        -> JsonRpcResult<bool>;
+}
@@ -11,7 +11,9 @@ pub mod call_request;
 mod consensus_graph_states;
 mod epoch_number;
 pub mod errors;
+mod fee_history;
 mod filter;
+mod gc_status;
 mod index;
 mod log;
 mod provenance;
@@ -19,10 +21,12 @@ pub mod pubsub;
 mod receipt;
 mod reward_info;
 mod sponsor_info;
+mod state_range;
 mod status;
 mod sync_graph_states;
 mod token_supply_info;
 mod trace;
+mod trace_backfill_status;
 mod trace_filter;
 mod transaction;
 
@@ -38,17 +42,23 @@ pub use self::{
     },
     consensus_graph_states::ConsensusGraphStates,
     epoch_number::{BlockHashOrEpochNumber, EpochNumber},
+    fee_history::{FeeHistory, GasPriceUrgency},
     filter::LogFilter,
+    gc_status::GcStatus,
     index::Index,
     log::Log,
     provenance::Origin,
     receipt::Receipt,
     reward_info::RewardInfo,
     sponsor_info::SponsorInfo,
+    state_range::{
+        AccountRange, StorageEntry, StorageRange, MAX_STATE_RANGE_LIMIT,
+    },
     status::Status,
     sync_graph_states::SyncGraphStates,
     token_supply_info::TokenSupplyInfo,
     trace::{LocalizedBlockTrace, LocalizedTrace, LocalizedTransactionTrace},
+    trace_backfill_status::TraceBackfillStatus,
     trace_filter::TraceFilter,
     transaction::{
         PackedOrExecuted, Transaction, TxPoolPendingInfo, TxWithPoolInfo,
@@ -12,13 +12,14 @@ use crate::rpc::{
 };
 use cfx_addr::Network;
 use cfx_types::{address_util::AddressUtil, Address, U256, U64};
-use cfxcore::rpc_errors::invalid_params_check;
-use cfxcore_accounts::AccountProvider;
+use cfxcore::rpc_errors::{invalid_params_check, sign_result_to_rpc_result};
+use cfxcore_accounts::{AccountProvider, SignRequest, SignRequestKind};
 use cfxkey::Password;
 use primitives::{
     transaction::Action, SignedTransaction,
     Transaction as PrimitiveTransaction, TransactionWithSignature,
 };
+use serde_json::json;
 use std::{cmp::min, sync::Arc};
 
 // use serde_json::de::ParserNumber::U64;
@@ -110,7 +111,7 @@ impl SendTxRequest {
             gas: self.gas.into(),
             action: match self.to {
                 None => Action::Create,
-                Some(address) => Action::Call(address.into()),
+                Some(ref address) => Action::Call(address.hex_address),
             },
             value: self.value.into(),
             storage_limit: self.storage_limit.unwrap_or_default().as_usize()
@@ -123,11 +124,28 @@ impl SendTxRequest {
             data: self.data.unwrap_or(Bytes::new(vec![])).into(),
         };
 
+        let request = SignRequest {
+            kind: SignRequestKind::Transaction,
+            address: self.from.hex_address,
+            hash: tx.hash(),
+            details: json!({
+                "from": self.from,
+                "to": self.to,
+                "nonce": tx.nonce,
+                "gasPrice": tx.gas_price,
+                "gas": tx.gas,
+                "value": tx.value,
+                "storageLimit": U64::from(tx.storage_limit),
+                "epochHeight": U64::from(tx.epoch_height),
+                "chainId": U64::from(tx.chain_id),
+                "data": Bytes::new(tx.data.clone()),
+            }),
+        };
         let password = password.map(Password::from);
-        let sig = accounts
-            .sign(self.from.into(), password, tx.hash())
-            // TODO: sign error into secret store error codes.
-            .map_err(|e| format!("failed to sign transaction: {:?}", e))?;
+        let sig = sign_result_to_rpc_result(
+            "tx",
+            accounts.sign_request(request, password),
+        )?;
 
         Ok(tx.with_signature(sig))
     }
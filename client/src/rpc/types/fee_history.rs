@@ -0,0 +1,60 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::{U256, U64};
+use cfxcore::consensus::fee_history::{
+    FeeHistory as PrimitiveFeeHistory,
+    GasPriceUrgency as PrimitiveGasPriceUrgency,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeeHistory {
+    /// The first epoch in the returned range.
+    pub oldest_epoch: U64,
+    /// The number of blocks in each epoch.
+    pub block_count: Vec<U64>,
+    /// The number of distinct transactions packed in each epoch.
+    pub transaction_count: Vec<U64>,
+    /// The gas price at each requested percentile for each epoch.
+    pub gas_price: Vec<Vec<U256>>,
+}
+
+impl From<PrimitiveFeeHistory> for FeeHistory {
+    fn from(history: PrimitiveFeeHistory) -> Self {
+        FeeHistory {
+            oldest_epoch: history.oldest_epoch.into(),
+            block_count: history
+                .block_count
+                .into_iter()
+                .map(|c| (c as u64).into())
+                .collect(),
+            transaction_count: history
+                .tx_count
+                .into_iter()
+                .map(|c| (c as u64).into())
+                .collect(),
+            gas_price: history.gas_price_percentiles,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GasPriceUrgency {
+    Slow,
+    Normal,
+    Fast,
+}
+
+impl GasPriceUrgency {
+    pub fn into_primitive(self) -> PrimitiveGasPriceUrgency {
+        match self {
+            GasPriceUrgency::Slow => PrimitiveGasPriceUrgency::Slow,
+            GasPriceUrgency::Normal => PrimitiveGasPriceUrgency::Normal,
+            GasPriceUrgency::Fast => PrimitiveGasPriceUrgency::Fast,
+        }
+    }
+}
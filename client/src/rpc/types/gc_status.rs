@@ -0,0 +1,81 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use super::RpcAddress;
+use cfx_addr::Network;
+use cfx_types::U64;
+use cfxcore::block_data_manager::db_gc_manager::{
+    DataClassGCStatus as PrimitiveDataClassGcStatus,
+    GCStatus as PrimitiveGcStatus,
+};
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GcStatus {
+    /// The next base epoch to be processed by the GC.
+    pub next_to_process: U64,
+    /// The end of the base epochs to be processed by the GC.
+    pub gc_end: U64,
+    pub watched_addresses: Vec<RpcAddress>,
+    pub data_classes: Vec<DataClassGcStatus>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DataClassGcStatus {
+    /// One of `transaction_index`, `execution_result`, `reward`, `trace` and
+    /// `block_body`.
+    pub class: String,
+    /// The pruning policy in the same format as the configuration.
+    pub policy: String,
+    /// The earliest epoch whose data is retained. The data of the earlier
+    /// epochs is kept only for the watched addresses, if any.
+    pub earliest_retained_epoch: U64,
+    pub next_to_prune: U64,
+    /// The average data size of an epoch measured since start, which is only
+    /// available for the disk budget policy.
+    pub average_epoch_bytes: Option<U64>,
+    pub pruned_entries: U64,
+    pub kept_watched_entries: U64,
+}
+
+impl GcStatus {
+    pub fn try_from(
+        status: PrimitiveGcStatus, network: Network,
+    ) -> Result<Self, String> {
+        let mut watched_addresses =
+            Vec::with_capacity(status.watched_addresses.len());
+        for address in status.watched_addresses {
+            watched_addresses
+                .push(RpcAddress::try_from_h160(address, network)?);
+        }
+        Ok(GcStatus {
+            next_to_process: status.next_to_process.into(),
+            gc_end: status.gc_end.into(),
+            watched_addresses,
+            data_classes: status
+                .data_classes
+                .into_iter()
+                .map(DataClassGcStatus::new)
+                .collect(),
+        })
+    }
+}
+
+impl DataClassGcStatus {
+    fn new(status: PrimitiveDataClassGcStatus) -> Self {
+        DataClassGcStatus {
+            class: status.class.name().into(),
+            policy: status.policy.to_string(),
+            earliest_retained_epoch: status.earliest_retained_epoch.into(),
+            next_to_prune: status.progress.next_to_prune.into(),
+            average_epoch_bytes: status
+                .progress
+                .average_epoch_bytes()
+                .map(Into::into),
+            pruned_entries: status.progress.pruned_entries.into(),
+            kept_watched_entries: status.progress.kept_watched_entries.into(),
+        }
+    }
+}
@@ -0,0 +1,35 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use super::{Account, RpcAddress};
+use cfx_types::H256;
+
+/// The max number of entries returned in a page of `cfx_getStorageRange` and
+/// `debug_accountRange`.
+pub const MAX_STATE_RANGE_LIMIT: u64 = 1000;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageEntry {
+    pub key: H256,
+    pub value: H256,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageRange {
+    /// The storage entries ordered by key.
+    pub storage: Vec<StorageEntry>,
+    /// The start key of the next page, or null if this is the last page.
+    pub next_key: Option<H256>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountRange {
+    /// The accounts ordered by address.
+    pub accounts: Vec<Account>,
+    /// The start address of the next page, or null if this is the last page.
+    pub next_address: Option<RpcAddress>,
+}
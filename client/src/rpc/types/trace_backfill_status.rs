@@ -0,0 +1,39 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::U64;
+use cfxcore::consensus::trace_backfill::TraceBackfillStatus as PrimitiveTraceBackfillStatus;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceBackfillStatus {
+    /// The epochs in `[startEpoch, endEpoch)` are backfilled.
+    pub start_epoch: U64,
+    pub end_epoch: U64,
+    pub next_epoch: U64,
+    pub backfilled_epochs: U64,
+    pub backfilled_blocks: U64,
+    /// The epochs skipped because their parent states, blocks or execution
+    /// results are not available.
+    pub unavailable_epochs: U64,
+    /// The epochs skipped because their re-executed receipts don't match the
+    /// stored commitments.
+    pub mismatched_epochs: U64,
+    pub finished: bool,
+}
+
+impl TraceBackfillStatus {
+    pub fn new(status: PrimitiveTraceBackfillStatus) -> Self {
+        TraceBackfillStatus {
+            start_epoch: status.start_epoch.into(),
+            end_epoch: status.end_epoch.into(),
+            next_epoch: status.next_epoch.into(),
+            backfilled_epochs: status.backfilled_epochs.into(),
+            backfilled_blocks: status.backfilled_blocks.into(),
+            unavailable_epochs: status.unavailable_epochs.into(),
+            mismatched_epochs: status.mismatched_epochs.into(),
+            finished: status.finished,
+        }
+    }
+}
@@ -104,6 +104,14 @@ pub mod rpc {
     pub const GAS_PRICE_TRANSACTION_SAMPLE_SIZE: usize = 10000;
     pub const TRANSACTION_COUNT_PER_BLOCK_WATER_LINE_LOW: usize = 100;
     pub const TRANSACTION_COUNT_PER_BLOCK_WATER_LINE_MEDIUM: usize = 600;
+    /// The number of most recent per-epoch gas price samples kept in memory
+    /// for cfx_feeHistory and cfx_suggestGasPrice.
+    pub const FEE_HISTORY_CACHE_EPOCH_COUNT: usize = 1024;
+    /// The maximum number of epochs that can be queried in one
+    /// cfx_feeHistory request.
+    pub const MAX_FEE_HISTORY_EPOCH_COUNT: u64 = 1024;
+    /// The number of recent epochs sampled for cfx_suggestGasPrice.
+    pub const GAS_PRICE_SUGGESTION_EPOCH_COUNT: u64 = 20;
 }
 
 pub mod sync {
@@ -363,6 +371,9 @@ pub mod light {
 
     // Number of blocks we retrieve in parallel for the gas price sample.
     pub const GAS_PRICE_BATCH_SIZE: usize = 30;
+
+    // Number of recent epochs sampled for cfx_suggestGasPrice.
+    pub const GAS_PRICE_SUGGESTION_EPOCH_COUNT: u64 = 10;
 }
 
 pub const WORKER_COMPUTATION_PARALLELISM: usize = 8;
@@ -1,5 +1,286 @@
+use cfx_types::Address;
 use malloc_size_of_derive::MallocSizeOf as DeriveMallocSizeOf;
-use std::cmp::min;
+use primitives::{Action, SignedTransaction};
+use std::{
+    cmp::{max, min},
+    collections::HashSet,
+    fmt,
+    str::FromStr,
+};
+
+/// The maximal number of epochs of a data class pruned for each GC base epoch,
+/// which bounds the work to catch up when a policy retains fewer epochs than
+/// before.
+pub const MAX_PRUNED_EPOCHS_PER_BASE_EPOCH: u64 = 16;
+
+/// The classes of block data which are garbage collected independently, in
+/// the order of GC. The transaction indices are collected first and the block
+/// bodies last, because the block bodies are needed to find the transactions
+/// of a block and to check the watched addresses.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum GCDataClass {
+    TransactionIndex,
+    ExecutionResult,
+    Reward,
+    Trace,
+    BlockBody,
+}
+
+impl GCDataClass {
+    pub const ALL: [GCDataClass; 5] = [
+        GCDataClass::TransactionIndex,
+        GCDataClass::ExecutionResult,
+        GCDataClass::Reward,
+        GCDataClass::Trace,
+        GCDataClass::BlockBody,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GCDataClass::TransactionIndex => "transaction_index",
+            GCDataClass::ExecutionResult => "execution_result",
+            GCDataClass::Reward => "reward",
+            GCDataClass::Trace => "trace",
+            GCDataClass::BlockBody => "block_body",
+        }
+    }
+}
+
+/// The retention of a class of block data before the GC base epoch. The data
+/// of the epochs after the current checkpoint is always kept.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PruningPolicy {
+    /// Never garbage collect the data.
+    KeepAll,
+    /// Keep the data of the given number of epochs.
+    ByAge(u64),
+    /// Keep the data of as many recent epochs as the disk budget in bytes
+    /// allows, estimated by the average data size of the epochs measured
+    /// since start.
+    ByDiskBudget(u64),
+    /// Only keep the data of the blocks with transactions sent from or to the
+    /// watched addresses. For the transaction indices, only the indices of
+    /// such transactions are kept.
+    WatchedAddresses,
+}
+
+impl PruningPolicy {
+    /// The lower and upper bounds of the number of the epochs before the GC
+    /// base epoch whose data is retained in full, `u64::MAX` for unbounded.
+    fn retained_epochs_bounds(&self) -> (u64, u64) {
+        match self {
+            PruningPolicy::KeepAll => (u64::MAX, u64::MAX),
+            PruningPolicy::ByAge(epochs) => (*epochs, *epochs),
+            PruningPolicy::ByDiskBudget(_) => (0, u64::MAX),
+            PruningPolicy::WatchedAddresses => (0, 0),
+        }
+    }
+}
+
+impl FromStr for PruningPolicy {
+    type Err = String;
+
+    /// Parses `keep_all`, `age:<epochs>`, `disk_budget:<size>` where the
+    /// size is in bytes or with a unit of KB, MB, GB or TB, and
+    /// `watched_addresses`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, arg) = match s.find(':') {
+            Some(pos) => (s[..pos].trim(), Some(s[pos + 1..].trim())),
+            None => (s.trim(), None),
+        };
+        match (kind, arg) {
+            ("keep_all", None) => Ok(PruningPolicy::KeepAll),
+            ("age", Some(epochs)) => {
+                epochs.parse().map(PruningPolicy::ByAge).map_err(|_| {
+                    format!("invalid epoch count in pruning policy {}", s)
+                })
+            }
+            ("disk_budget", Some(size)) => parse_size(size)
+                .map(PruningPolicy::ByDiskBudget)
+                .ok_or_else(|| format!("invalid size in pruning policy {}", s)),
+            ("watched_addresses", None) => Ok(PruningPolicy::WatchedAddresses),
+            _ => Err(format!(
+                "invalid pruning policy {}, expected keep_all, age:<epochs>, \
+                 disk_budget:<size> or watched_addresses",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for PruningPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PruningPolicy::KeepAll => write!(f, "keep_all"),
+            PruningPolicy::ByAge(epochs) => write!(f, "age:{}", epochs),
+            PruningPolicy::ByDiskBudget(bytes) => {
+                write!(f, "disk_budget:{}", bytes)
+            }
+            PruningPolicy::WatchedAddresses => write!(f, "watched_addresses"),
+        }
+    }
+}
+
+fn parse_size(size: &str) -> Option<u64> {
+    let units = [
+        ("TB", 1u64 << 40),
+        ("GB", 1 << 30),
+        ("MB", 1 << 20),
+        ("KB", 1 << 10),
+    ];
+    for (unit, multiplier) in &units {
+        if let Some(number) = size.strip_suffix(unit) {
+            return number.trim().parse::<u64>().ok()?.checked_mul(*multiplier);
+        }
+    }
+    size.parse().ok()
+}
+
+/// The pruning policy of each class of block data.
+#[derive(Clone, Debug)]
+pub struct PruningPolicies {
+    policies: Vec<PruningPolicy>,
+    /// The addresses whose data is kept by `PruningPolicy::WatchedAddresses`.
+    pub watched_addresses: HashSet<Address>,
+}
+
+impl Default for PruningPolicies {
+    fn default() -> Self {
+        Self {
+            policies: vec![PruningPolicy::KeepAll; GCDataClass::ALL.len()],
+            watched_addresses: Default::default(),
+        }
+    }
+}
+
+impl PruningPolicies {
+    pub fn get(&self, class: GCDataClass) -> &PruningPolicy {
+        &self.policies[class as usize]
+    }
+
+    pub fn set(&mut self, class: GCDataClass, policy: PruningPolicy) {
+        self.policies[class as usize] = policy;
+    }
+
+    /// Checks that the block bodies are retained at least as long as the
+    /// transaction indices, because the GC of the transaction indices reads
+    /// the block bodies to find the transactions to prune.
+    pub fn validate(&self) -> Result<(), String> {
+        let block_body = self.get(GCDataClass::BlockBody);
+        let transaction_index = self.get(GCDataClass::TransactionIndex);
+        if block_body.retained_epochs_bounds().0
+            < transaction_index.retained_epochs_bounds().1
+        {
+            return Err(format!(
+                "block_body_pruning_policy {} may retain fewer epochs than \
+                 transaction_index_pruning_policy {}",
+                block_body, transaction_index
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn is_watched_transaction(&self, tx: &SignedTransaction) -> bool {
+        if self.watched_addresses.contains(&tx.sender()) {
+            return true;
+        }
+        match &tx.action {
+            Action::Call(address) => self.watched_addresses.contains(address),
+            Action::Create => false,
+        }
+    }
+}
+
+/// The pruning progress of a class of block data.
+#[derive(Clone, Debug, Default, DeriveMallocSizeOf)]
+pub struct DataClassPruningProgress {
+    /// The earliest epoch which isn't pruned. This is persisted to disk.
+    pub next_to_prune: u64,
+    /// The data size and the number of the epochs measured since start for
+    /// `PruningPolicy::ByDiskBudget`.
+    pub measured_bytes: u64,
+    pub measured_epochs: u64,
+    /// The numbers of the blocks, or the transactions for the transaction
+    /// indices, whose data is pruned or kept for the watched addresses since
+    /// start.
+    pub pruned_entries: u64,
+    pub kept_watched_entries: u64,
+}
+
+impl DataClassPruningProgress {
+    /// The initial progress if it's not persisted, which continues where the
+    /// `additional_maintained*` parameters stopped.
+    pub fn new(policy: &PruningPolicy, gc_next_to_process: u64) -> Self {
+        let next_to_prune = match policy {
+            PruningPolicy::KeepAll => 0,
+            PruningPolicy::ByAge(epochs) => {
+                gc_next_to_process.saturating_sub(*epochs)
+            }
+            _ => gc_next_to_process,
+        };
+        Self {
+            next_to_prune,
+            ..Default::default()
+        }
+    }
+
+    pub fn average_epoch_bytes(&self) -> Option<u64> {
+        if self.measured_epochs == 0 {
+            None
+        } else {
+            Some(self.measured_bytes / self.measured_epochs)
+        }
+    }
+
+    /// Returns the end of the epochs `[0, end)` which are pruned under
+    /// `policy` once the GC processes the base epochs before `base_end`.
+    /// Return `None` if nothing is pruned.
+    pub fn prune_end(
+        &self, policy: &PruningPolicy, base_end: u64,
+    ) -> Option<u64> {
+        match policy {
+            PruningPolicy::KeepAll => None,
+            PruningPolicy::ByAge(epochs) => {
+                Some(base_end.saturating_sub(*epochs))
+            }
+            PruningPolicy::ByDiskBudget(bytes) => {
+                let retained_epochs =
+                    bytes / max(self.average_epoch_bytes()?, 1);
+                Some(base_end.saturating_sub(retained_epochs))
+            }
+            PruningPolicy::WatchedAddresses => Some(base_end),
+        }
+    }
+
+    /// The earliest epoch whose data is retained once the GC reaches
+    /// `gc_end`.
+    pub fn earliest_retained_epoch(
+        &self, policy: &PruningPolicy, gc_end: u64,
+    ) -> u64 {
+        max(
+            self.next_to_prune,
+            self.prune_end(policy, gc_end).unwrap_or(0),
+        )
+    }
+}
+
+/// A snapshot of the GC progress and the retention of each class of block
+/// data.
+#[derive(Clone, Debug)]
+pub struct GCStatus {
+    pub next_to_process: u64,
+    pub gc_end: u64,
+    pub watched_addresses: Vec<Address>,
+    pub data_classes: Vec<DataClassGCStatus>,
+}
+
+#[derive(Clone, Debug)]
+pub struct DataClassGCStatus {
+    pub class: GCDataClass,
+    pub policy: PruningPolicy,
+    pub earliest_retained_epoch: u64,
+    pub progress: DataClassPruningProgress,
+}
 
 /// Each time we make a new checkpoint, we will mark more data as garbage
 /// depending on the parameters. To avoid the GC process affecting normal
@@ -24,20 +305,96 @@ pub struct GCProgress {
     // The epoch number that we want to finish garbage collection of
     // `self.gc_end`.
     pub expected_end_consensus_best_epoch: u64,
+
+    // The pruning progress of each class of block data, indexed by
+    // `GCDataClass`.
+    pub pruning: Vec<DataClassPruningProgress>,
 }
 
 impl GCProgress {
-    pub fn new(next_to_process: u64) -> Self {
+    /// `persisted_pruning` is the persisted `next_to_prune` of each class in
+    /// the order of `GCDataClass::ALL`.
+    pub fn new(
+        next_to_process: u64, policies: &PruningPolicies,
+        persisted_pruning: Option<Vec<u64>>,
+    ) -> Self
+    {
+        let pruning = GCDataClass::ALL
+            .iter()
+            .map(|class| {
+                let mut progress = DataClassPruningProgress::new(
+                    policies.get(*class),
+                    next_to_process,
+                );
+                if let Some(next_to_prune) = persisted_pruning
+                    .as_ref()
+                    .and_then(|persisted| persisted.get(*class as usize))
+                {
+                    progress.next_to_prune = *next_to_prune;
+                }
+                progress
+            })
+            .collect();
         Self {
             next_to_process,
+            pruning,
             ..Default::default()
         }
     }
 
+    pub fn pruning_progress(
+        &self, class: GCDataClass,
+    ) -> &DataClassPruningProgress {
+        &self.pruning[class as usize]
+    }
+
+    pub fn pruning_progress_mut(
+        &mut self, class: GCDataClass,
+    ) -> &mut DataClassPruningProgress {
+        &mut self.pruning[class as usize]
+    }
+
+    pub fn persisted_pruning(&self) -> Vec<u64> {
+        self.pruning
+            .iter()
+            .map(|progress| progress.next_to_prune)
+            .collect()
+    }
+
+    /// Returns the epochs `[start, end)` of `class` to prune once the GC
+    /// processes `base_epoch`, at most `MAX_PRUNED_EPOCHS_PER_BASE_EPOCH` of
+    /// them. Return `None` if nothing is pruned.
+    ///
+    /// The block bodies are never pruned past the progress of the other
+    /// classes under `PruningPolicy::WatchedAddresses`, because their GC
+    /// reads the block bodies to find the watched blocks and transactions.
+    pub fn prune_range(
+        &self, class: GCDataClass, policies: &PruningPolicies, base_epoch: u64,
+    ) -> Option<(u64, u64)> {
+        let progress = self.pruning_progress(class);
+        let mut end = min(
+            progress.prune_end(policies.get(class), base_epoch + 1)?,
+            progress.next_to_prune + MAX_PRUNED_EPOCHS_PER_BASE_EPOCH,
+        );
+        if class == GCDataClass::BlockBody {
+            for watched_class in GCDataClass::ALL.iter().filter(|c| {
+                **c != GCDataClass::BlockBody
+                    && policies.get(**c) == &PruningPolicy::WatchedAddresses
+            }) {
+                end = min(
+                    end,
+                    self.pruning_progress(*watched_class).next_to_prune,
+                );
+            }
+        }
+        // The genesis epoch is never garbage collected.
+        Some((max(progress.next_to_prune, 1), end))
+    }
+
     /// Compute the GC base range to make sure the GC progress is proportional
     /// to the consensus progress.
-    /// The actual GC range for each kind of data is the returned base range
-    /// minus the corresponding `additional_maintained*` offset.
+    /// The actual GC range for each kind of data is decided by its pruning
+    /// policy based on the returned base range.
     ///
     /// Return `Some((start_epoch, end_epoch))` and the range `[start_epoch,
     /// end_epoch)` will be GCed. Return `None` if there is no work to be
@@ -57,3 +414,160 @@ impl GCProgress {
         Some((self.next_to_process, self.next_to_process + batch_size))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        DataClassPruningProgress, GCDataClass, GCProgress, PruningPolicies,
+        PruningPolicy, MAX_PRUNED_EPOCHS_PER_BASE_EPOCH,
+    };
+
+    #[test]
+    fn test_parse_pruning_policy() {
+        assert_eq!("keep_all".parse(), Ok(PruningPolicy::KeepAll));
+        assert_eq!("age:100".parse(), Ok(PruningPolicy::ByAge(100)));
+        assert_eq!(
+            "disk_budget:2GB".parse(),
+            Ok(PruningPolicy::ByDiskBudget(2 << 30))
+        );
+        assert_eq!(
+            "disk_budget: 4096".parse(),
+            Ok(PruningPolicy::ByDiskBudget(4096))
+        );
+        assert_eq!(
+            "watched_addresses".parse(),
+            Ok(PruningPolicy::WatchedAddresses)
+        );
+        assert!("age".parse::<PruningPolicy>().is_err());
+        assert!("disk_budget:2XB".parse::<PruningPolicy>().is_err());
+        assert!("keep_all:1".parse::<PruningPolicy>().is_err());
+        for policy in &["keep_all", "age:10", "disk_budget:1024"] {
+            assert_eq!(
+                policy.parse::<PruningPolicy>().unwrap().to_string(),
+                *policy
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_pruning_policies() {
+        let validate = |block_body: &str, transaction_index: &str| {
+            let mut policies = PruningPolicies::default();
+            policies.set(GCDataClass::BlockBody, block_body.parse().unwrap());
+            policies.set(
+                GCDataClass::TransactionIndex,
+                transaction_index.parse().unwrap(),
+            );
+            policies.validate().is_ok()
+        };
+        assert!(validate("keep_all", "keep_all"));
+        assert!(validate("keep_all", "disk_budget:1GB"));
+        assert!(validate("age:100", "age:100"));
+        assert!(validate("age:0", "watched_addresses"));
+        assert!(validate("disk_budget:1GB", "age:0"));
+        assert!(validate("watched_addresses", "watched_addresses"));
+
+        assert!(!validate("age:100", "keep_all"));
+        assert!(!validate("age:10", "age:100"));
+        assert!(!validate("age:100", "disk_budget:1GB"));
+        assert!(!validate("disk_budget:1GB", "age:10"));
+        assert!(!validate("watched_addresses", "age:10"));
+    }
+
+    #[test]
+    fn test_prune_end() {
+        let mut progress = DataClassPruningProgress::default();
+        assert_eq!(progress.prune_end(&PruningPolicy::KeepAll, 100), None);
+        assert_eq!(
+            progress.prune_end(&PruningPolicy::ByAge(30), 100),
+            Some(70)
+        );
+        assert_eq!(
+            progress.prune_end(&PruningPolicy::ByAge(300), 100),
+            Some(0)
+        );
+        assert_eq!(
+            progress.prune_end(&PruningPolicy::WatchedAddresses, 100),
+            Some(100)
+        );
+
+        let budget = PruningPolicy::ByDiskBudget(1000);
+        // Nothing is pruned before any epoch is measured.
+        assert_eq!(progress.prune_end(&budget, 100), None);
+        progress.measured_bytes = 500;
+        progress.measured_epochs = 10;
+        assert_eq!(progress.prune_end(&budget, 100), Some(80));
+
+        progress.next_to_prune = 90;
+        assert_eq!(progress.earliest_retained_epoch(&budget, 100), 90);
+        assert_eq!(progress.earliest_retained_epoch(&budget, 120), 100);
+    }
+
+    #[test]
+    fn test_prune_range() {
+        let mut policies = PruningPolicies::default();
+        policies.set(GCDataClass::ExecutionResult, PruningPolicy::ByAge(10));
+        let mut gc_progress = GCProgress::new(0, &policies, None);
+        assert_eq!(
+            gc_progress.prune_range(GCDataClass::Trace, &policies, 100),
+            None
+        );
+        // The genesis epoch is kept, and the pruning catches up gradually.
+        assert_eq!(
+            gc_progress.prune_range(
+                GCDataClass::ExecutionResult,
+                &policies,
+                100
+            ),
+            Some((1, MAX_PRUNED_EPOCHS_PER_BASE_EPOCH))
+        );
+        gc_progress
+            .pruning_progress_mut(GCDataClass::ExecutionResult)
+            .next_to_prune = 85;
+        assert_eq!(
+            gc_progress.prune_range(
+                GCDataClass::ExecutionResult,
+                &policies,
+                100
+            ),
+            Some((85, 91))
+        );
+        // Nothing is pruned within the age.
+        let (start, end) = gc_progress
+            .prune_range(GCDataClass::ExecutionResult, &policies, 5)
+            .unwrap();
+        assert!(start >= end);
+    }
+
+    #[test]
+    fn test_prune_range_with_watched_addresses() {
+        let mut policies = PruningPolicies::default();
+        policies.set(GCDataClass::Trace, PruningPolicy::WatchedAddresses);
+        policies.set(GCDataClass::BlockBody, PruningPolicy::ByAge(0));
+        let mut gc_progress = GCProgress::new(50, &policies, None);
+        // The block bodies are kept until the watched traces are pruned.
+        assert_eq!(
+            gc_progress.prune_range(GCDataClass::BlockBody, &policies, 60),
+            Some((50, 50))
+        );
+        assert_eq!(
+            gc_progress.prune_range(GCDataClass::Trace, &policies, 60),
+            Some((50, 61))
+        );
+        gc_progress
+            .pruning_progress_mut(GCDataClass::Trace)
+            .next_to_prune = 58;
+        assert_eq!(
+            gc_progress.prune_range(GCDataClass::BlockBody, &policies, 60),
+            Some((50, 58))
+        );
+
+        // The block bodies under the policy aren't limited by themselves.
+        policies.set(GCDataClass::Trace, PruningPolicy::KeepAll);
+        policies.set(GCDataClass::BlockBody, PruningPolicy::WatchedAddresses);
+        assert_eq!(
+            gc_progress.prune_range(GCDataClass::BlockBody, &policies, 60),
+            Some((50, 61))
+        );
+    }
+}
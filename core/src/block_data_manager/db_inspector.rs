@@ -0,0 +1,233 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! Offline statistics and decoding of the entries in the block db, and the
+//! disk usage of the storage.
+
+use crate::{
+    block_data_manager::db_manager::{db_entry_kind, DBManager},
+    db::{
+        COL_BLAMED_HEADER_VERIFIED_ROOTS, COL_BLOCKS, COL_BLOCK_TRACES,
+        COL_EPOCH_NUMBER, COL_MISC, COL_TX_INDEX,
+    },
+};
+use cfx_types::H256;
+use db::SystemDB;
+use std::{collections::BTreeMap, fs, io, path::Path};
+
+/// The rocksdb columns of the block db with their names.
+pub const DB_COLUMNS: [(u32, &str); 6] = [
+    (COL_MISC, "misc"),
+    (COL_BLOCKS, "blocks"),
+    (COL_TX_INDEX, "transactions"),
+    (COL_EPOCH_NUMBER, "epoch_numbers"),
+    (
+        COL_BLAMED_HEADER_VERIFIED_ROOTS,
+        "blamed_header_verified_roots",
+    ),
+    (COL_BLOCK_TRACES, "block_traces"),
+];
+
+const LOAD_BATCH_SIZE: usize = 1024;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EntryStats {
+    pub count: u64,
+    pub key_bytes: u64,
+    pub value_bytes: u64,
+}
+
+impl EntryStats {
+    fn add(&mut self, key: &[u8], value: &[u8]) {
+        self.count += 1;
+        self.key_bytes += key.len() as u64;
+        self.value_bytes += value.len() as u64;
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ColumnStats {
+    pub col: u32,
+    pub name: &'static str,
+    pub total: EntryStats,
+    /// The stats of each kind of entries, e.g. block headers and block bodies
+    /// in the blocks column.
+    pub by_kind: BTreeMap<String, EntryStats>,
+}
+
+/// Counts the entries of the column `col` whose key starts with `key_prefix`.
+pub fn column_stats(
+    db: &SystemDB, col: u32, key_prefix: &[u8],
+) -> io::Result<ColumnStats> {
+    let name = DB_COLUMNS
+        .iter()
+        .find(|(column, _)| *column == col)
+        .map(|(_, name)| *name)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unknown column {}", col),
+            )
+        })?;
+    let mut stats = ColumnStats {
+        col,
+        name,
+        total: Default::default(),
+        by_kind: Default::default(),
+    };
+    let upper_bound_excl = prefix_upper_bound(key_prefix);
+    let mut lower_bound_incl = key_prefix.to_vec();
+    loop {
+        let kvs = db.key_value().get_range(
+            col,
+            &lower_bound_incl,
+            upper_bound_excl.as_ref().map(|key| &**key),
+            LOAD_BATCH_SIZE,
+        )?;
+        for (key, value) in &kvs {
+            stats.total.add(key, value);
+            stats
+                .by_kind
+                .entry(db_entry_kind(col, key))
+                .or_default()
+                .add(key, value);
+        }
+        match kvs.last() {
+            Some((key, _)) if kvs.len() == LOAD_BATCH_SIZE => {
+                lower_bound_incl = key.to_vec();
+                lower_bound_incl.push(0);
+            }
+            _ => return Ok(stats),
+        }
+    }
+}
+
+/// The entries which can be decoded, with their keys.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DbEntryKey {
+    BlockHeader(H256),
+    Block(H256),
+    Receipts(H256),
+    Traces(H256),
+    ExecutionCommitment(H256),
+    TransactionIndex(H256),
+    /// The executed and skipped blocks of the epoch at the height.
+    EpochSet(u64),
+    Checkpoint,
+    GcProgress,
+}
+
+/// Decodes the entry and formats it for display. Returns None if the entry
+/// isn't found.
+pub fn decode_entry(
+    db_manager: &DBManager, entry_key: &DbEntryKey,
+) -> Option<String> {
+    match entry_key {
+        DbEntryKey::BlockHeader(hash) => db_manager
+            .block_header_from_db(hash)
+            .map(|header| format!("{:#?}", header)),
+        DbEntryKey::Block(hash) => db_manager
+            .block_from_db(hash)
+            .map(|block| format!("{:#?}", block)),
+        DbEntryKey::Receipts(hash) => db_manager
+            .block_execution_result_from_db(hash)
+            .map(|result| format!("{:#?}", result)),
+        DbEntryKey::Traces(hash) => db_manager
+            .block_traces_from_db(hash)
+            .map(|traces| format!("{:#?}", traces)),
+        DbEntryKey::ExecutionCommitment(hash) => db_manager
+            .epoch_execution_commitment_from_db(hash)
+            .map(|commitment| format!("{:#?}", commitment)),
+        DbEntryKey::TransactionIndex(hash) => db_manager
+            .transaction_index_from_db(hash)
+            .map(|index| format!("{:#?}", index)),
+        DbEntryKey::EpochSet(height) => {
+            let executed =
+                db_manager.executed_epoch_set_hashes_from_db(*height);
+            let skipped = db_manager.skipped_epoch_set_hashes_from_db(*height);
+            if executed.is_none() && skipped.is_none() {
+                None
+            } else {
+                Some(format!(
+                    "executed: {:#?}\nskipped: {:#?}",
+                    executed, skipped
+                ))
+            }
+        }
+        DbEntryKey::Checkpoint => {
+            db_manager.checkpoint_hashes_from_db().map(|(prev, cur)| {
+                format!("previous: {:?}\ncurrent: {:?}", prev, cur)
+            })
+        }
+        DbEntryKey::GcProgress => {
+            db_manager.gc_progress_from_db().map(|next_to_process| {
+                format!("next epoch to process: {}", next_to_process)
+            })
+        }
+    }
+}
+
+/// The total size in bytes of the files under `path`, which is 0 if the path
+/// doesn't exist.
+pub fn disk_usage(path: &Path) -> io::Result<u64> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        size += disk_usage(&entry?.path())?;
+    }
+    Ok(size)
+}
+
+/// The disk usage of each entry in the directory, sorted by name.
+pub fn dir_entries_disk_usage(path: &Path) -> io::Result<Vec<(String, u64)>> {
+    let mut usage = vec![];
+    if !path.exists() {
+        return Ok(usage);
+    }
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        usage.push((
+            entry.file_name().to_string_lossy().into_owned(),
+            disk_usage(&entry.path())?,
+        ));
+    }
+    usage.sort();
+    Ok(usage)
+}
+
+fn prefix_upper_bound(key_prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut upper_bound = key_prefix.to_vec();
+    while let Some(last_byte) = upper_bound.pop() {
+        if last_byte != 0xff {
+            upper_bound.push(last_byte + 1);
+            return Some(upper_bound);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::prefix_upper_bound;
+
+    #[test]
+    fn test_prefix_upper_bound() {
+        assert_eq!(prefix_upper_bound(&[1, 2]), Some(vec![1, 3]));
+        assert_eq!(prefix_upper_bound(&[1, 0xfe]), Some(vec![1, 0xff]));
+        // The trailing 0xff bytes are dropped.
+        assert_eq!(prefix_upper_bound(&[1, 0xff]), Some(vec![2]));
+        assert_eq!(prefix_upper_bound(&[1, 0xff, 0xff]), Some(vec![2]));
+        assert_eq!(prefix_upper_bound(&[0xfe, 0xff]), Some(vec![0xff]));
+        // No upper bound for an empty prefix or all 0xff bytes.
+        assert_eq!(prefix_upper_bound(&[0xff, 0xff]), None);
+        assert_eq!(prefix_upper_bound(&[]), None);
+    }
+}
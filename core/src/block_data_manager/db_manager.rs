@@ -1,8 +1,9 @@
 use crate::{
     block_data_manager::{
-        db_decode_list, db_encode_list, BlamedHeaderVerifiedRoots,
-        BlockExecutionResultWithEpoch, BlockRewardResult, BlockTracesWithEpoch,
-        CheckpointHashes, EpochExecutionContext, LocalBlockInfo,
+        db_decode_list, db_encode_list, db_gc_manager::GCDataClass,
+        BlamedHeaderVerifiedRoots, BlockExecutionResultWithEpoch,
+        BlockRewardResult, BlockTracesWithEpoch, CheckpointHashes,
+        EpochExecutionContext, LocalBlockInfo,
     },
     db::{
         COL_BLAMED_HEADER_VERIFIED_ROOTS, COL_BLOCKS, COL_BLOCK_TRACES,
@@ -35,6 +36,8 @@ const EPOCH_SKIPPED_BLOCK_SET_SUFFIX_BYTE: u8 = 7;
 const BLOCK_REWARD_RESULT_SUFFIX_BYTE: u8 = 8;
 const BLOCK_TERMINAL_KEY: &[u8] = b"block_terminals";
 const GC_PROGRESS_KEY: &[u8] = b"gc_progress";
+const PRUNING_PROGRESS_KEY: &[u8] = b"pruning_progress";
+const TRACE_BACKFILL_PROGRESS_KEY: &[u8] = b"trace_backfill_progress";
 
 #[derive(Clone, Copy, Hash, Ord, PartialOrd, Eq, PartialEq)]
 enum DBTable {
@@ -468,6 +471,69 @@ impl DBManager {
         self.load_decodable_val(DBTable::Misc, GC_PROGRESS_KEY)
     }
 
+    /// `next_to_prune` is the pruning progress of each class in the order of
+    /// `GCDataClass::ALL`.
+    pub fn insert_pruning_progress_to_db(&self, next_to_prune: &Vec<u64>) {
+        self.insert_encodable_list(
+            DBTable::Misc,
+            PRUNING_PROGRESS_KEY,
+            next_to_prune,
+        );
+    }
+
+    pub fn pruning_progress_from_db(&self) -> Option<Vec<u64>> {
+        self.load_decodable_list(DBTable::Misc, PRUNING_PROGRESS_KEY)
+    }
+
+    pub fn insert_trace_backfill_progress_to_db(&self, next_epoch: u64) {
+        self.insert_encodable_val(
+            DBTable::Misc,
+            TRACE_BACKFILL_PROGRESS_KEY,
+            &next_epoch,
+        );
+    }
+
+    pub fn trace_backfill_progress_from_db(&self) -> Option<u64> {
+        self.load_decodable_val(DBTable::Misc, TRACE_BACKFILL_PROGRESS_KEY)
+    }
+
+    /// The size in bytes of the data of `class` of the block in the db.
+    pub fn block_data_size_from_db(
+        &self, class: GCDataClass, hash: &H256,
+    ) -> u64 {
+        let entry_size = |table, key: &[u8]| {
+            self.load_from_db(table, key)
+                .map_or(0, |value| value.len() as u64)
+        };
+        match class {
+            GCDataClass::TransactionIndex => {
+                self.block_body_from_db(hash).map_or(0, |transactions| {
+                    transactions
+                        .iter()
+                        .map(|tx| {
+                            entry_size(
+                                DBTable::Transactions,
+                                tx.hash().as_bytes(),
+                            )
+                        })
+                        .sum()
+                })
+            }
+            GCDataClass::ExecutionResult => {
+                entry_size(DBTable::Blocks, &block_execution_result_key(hash))
+            }
+            GCDataClass::Reward => {
+                entry_size(DBTable::Blocks, &block_reward_result_key(hash))
+            }
+            GCDataClass::Trace => {
+                entry_size(DBTable::BlockTraces, hash.as_bytes())
+            }
+            GCDataClass::BlockBody => {
+                entry_size(DBTable::Blocks, &block_body_key(hash))
+            }
+        }
+    }
+
     /// The functions below are private utils used by the DBManager to access
     /// database
     fn insert_to_db(&self, table: DBTable, db_key: &[u8], value: Vec<u8>) {
@@ -574,6 +640,49 @@ fn epoch_consensus_epoch_execution_commitment_key(hash: &H256) -> Vec<u8> {
     append_suffix(hash, EPOCH_CONSENSUS_EXECUTION_INFO_SUFFIX_BYTE)
 }
 
+/// The kind of the entry with `key` in the rocksdb column `col`, which is
+/// derived from the key layouts above.
+pub(super) fn db_entry_kind(col: u32, key: &[u8]) -> String {
+    let suffix_kind = |suffix| match suffix {
+        LOCAL_BLOCK_INFO_SUFFIX_BYTE => "local_block_info",
+        BLOCK_BODY_SUFFIX_BYTE => "block_body",
+        BLOCK_EXECUTION_RESULT_SUFFIX_BYTE => "block_execution_result",
+        EPOCH_EXECUTION_CONTEXT_SUFFIX_BYTE => "epoch_execution_context",
+        EPOCH_CONSENSUS_EXECUTION_INFO_SUFFIX_BYTE => {
+            "epoch_execution_commitment"
+        }
+        EPOCH_EXECUTED_BLOCK_SET_SUFFIX_BYTE => "executed_epoch_set",
+        EPOCH_SKIPPED_BLOCK_SET_SUFFIX_BYTE => "skipped_epoch_set",
+        BLOCK_REWARD_RESULT_SUFFIX_BYTE => "block_reward_result",
+        _ => "unknown",
+    };
+    match col {
+        COL_MISC => {
+            if !key.is_empty() && key.iter().all(u8::is_ascii_graphic) {
+                String::from_utf8_lossy(key).into()
+            } else {
+                "unknown".into()
+            }
+        }
+        COL_BLOCKS => {
+            if key.len() == H256::len_bytes() {
+                "block_header".into()
+            } else if key.len() == H256::len_bytes() + 1 {
+                suffix_kind(key[H256::len_bytes()]).into()
+            } else {
+                "unknown".into()
+            }
+        }
+        COL_EPOCH_NUMBER if key.len() == 9 => suffix_kind(key[8]).into(),
+        COL_TX_INDEX => "transaction_index".into(),
+        COL_BLAMED_HEADER_VERIFIED_ROOTS => {
+            "blamed_header_verified_roots".into()
+        }
+        COL_BLOCK_TRACES => "block_traces".into(),
+        _ => "unknown".into(),
+    }
+}
+
 impl MallocSizeOf for DBManager {
     fn size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
         // Here we only handle the case that all columns are stored within the
@@ -584,3 +693,60 @@ impl MallocSizeOf for DBManager {
             .size_of(ops)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        block_body_key, db_entry_kind,
+        epoch_consensus_epoch_execution_commitment_key, executed_epoch_set_key,
+        skipped_epoch_set_key,
+    };
+    use crate::db::{
+        COL_BLOCKS, COL_BLOCK_TRACES, COL_EPOCH_NUMBER, COL_MISC, COL_TX_INDEX,
+    };
+    use cfx_types::H256;
+
+    #[test]
+    fn test_db_entry_kind() {
+        let hash = H256::repeat_byte(0xff);
+        assert_eq!(db_entry_kind(COL_BLOCKS, hash.as_bytes()), "block_header");
+        assert_eq!(
+            db_entry_kind(COL_BLOCKS, &block_body_key(&hash)),
+            "block_body"
+        );
+        assert_eq!(
+            db_entry_kind(
+                COL_BLOCKS,
+                &epoch_consensus_epoch_execution_commitment_key(&hash)
+            ),
+            "epoch_execution_commitment"
+        );
+        let mut key = hash.as_bytes().to_vec();
+        key.push(0xff);
+        assert_eq!(db_entry_kind(COL_BLOCKS, &key), "unknown");
+        assert_eq!(db_entry_kind(COL_BLOCKS, &key[1..]), "unknown");
+        assert_eq!(db_entry_kind(COL_BLOCKS, &[]), "unknown");
+
+        assert_eq!(
+            db_entry_kind(COL_EPOCH_NUMBER, &executed_epoch_set_key(1)),
+            "executed_epoch_set"
+        );
+        assert_eq!(
+            db_entry_kind(COL_EPOCH_NUMBER, &skipped_epoch_set_key(1)),
+            "skipped_epoch_set"
+        );
+        assert_eq!(db_entry_kind(COL_EPOCH_NUMBER, &[0; 8]), "unknown");
+
+        assert_eq!(db_entry_kind(COL_MISC, b"gc_progress"), "gc_progress");
+        assert_eq!(db_entry_kind(COL_MISC, b"gc progress"), "unknown");
+        assert_eq!(db_entry_kind(COL_MISC, &[0xff]), "unknown");
+        assert_eq!(db_entry_kind(COL_MISC, &[]), "unknown");
+
+        assert_eq!(
+            db_entry_kind(COL_TX_INDEX, hash.as_bytes()),
+            "transaction_index"
+        );
+        assert_eq!(db_entry_kind(COL_BLOCK_TRACES, &[]), "block_traces");
+        assert_eq!(db_entry_kind(u32::MAX, hash.as_bytes()), "unknown");
+    }
+}
@@ -32,8 +32,10 @@ use std::{
 };
 use threadpool::ThreadPool;
 pub mod block_data_types;
+pub mod db_inspector;
 pub mod db_gc_manager;
 pub mod db_manager;
+pub mod storage_verifier;
 pub mod tx_data_manager;
 use crate::{
     block_data_manager::{
@@ -45,7 +47,10 @@ pub use block_data_types::*;
 use cfx_internal_common::{
     EpochExecutionCommitment, StateAvailabilityBoundary, StateRootWithAuxInfo,
 };
-use db_gc_manager::GCProgress;
+use db_gc_manager::{
+    DataClassGCStatus, GCDataClass, GCProgress, GCStatus, PruningPolicies,
+    PruningPolicy,
+};
 use metrics::{register_meter_with_group, Meter, MeterTimer};
 use std::{hash::Hash, path::Path, time::Duration};
 
@@ -192,6 +197,11 @@ impl BlockDataManager {
         };
         let previous_db_progress =
             db_manager.gc_progress_from_db().unwrap_or(0);
+        let gc_progress = GCProgress::new(
+            previous_db_progress,
+            &config.pruning_policies,
+            db_manager.pruning_progress_from_db(),
+        );
 
         let data_man = Self {
             block_headers: RwLock::new(HashMap::new()),
@@ -224,9 +234,7 @@ impl BlockDataManager {
             state_availability_boundary: RwLock::new(
                 StateAvailabilityBoundary::new(true_genesis.hash(), 0),
             ),
-            gc_progress: Arc::new(Mutex::new(GCProgress::new(
-                previous_db_progress,
-            ))),
+            gc_progress: Arc::new(Mutex::new(gc_progress)),
         };
 
         data_man.initialize_instance_id();
@@ -1424,26 +1432,52 @@ impl BlockDataManager {
     }
 
     pub fn earliest_epoch_with_block_body(&self) -> u64 {
-        match self.config.additional_maintained_block_body_epoch_count {
-            Some(defer) => self.gc_progress.lock().gc_end - defer as u64,
-            None => 0,
-        }
+        self.earliest_retained_epoch(GCDataClass::BlockBody)
     }
 
     pub fn earliest_epoch_with_execution_result(&self) -> u64 {
-        match self
-            .config
-            .additional_maintained_execution_result_epoch_count
-        {
-            Some(defer) => self.gc_progress.lock().gc_end - defer as u64,
-            None => 0,
-        }
+        self.earliest_retained_epoch(GCDataClass::ExecutionResult)
     }
 
     pub fn earliest_epoch_with_trace(&self) -> u64 {
-        match self.config.additional_maintained_trace_epoch_count {
-            Some(defer) => self.gc_progress.lock().gc_end - defer as u64,
-            None => 0,
+        self.earliest_retained_epoch(GCDataClass::Trace)
+    }
+
+    fn earliest_retained_epoch(&self, class: GCDataClass) -> u64 {
+        let gc_progress = self.gc_progress.lock();
+        gc_progress.pruning_progress(class).earliest_retained_epoch(
+            self.config.pruning_policies.get(class),
+            gc_progress.gc_end,
+        )
+    }
+
+    pub fn gc_status(&self) -> GCStatus {
+        let gc_progress = self.gc_progress.lock();
+        let policies = &self.config.pruning_policies;
+        GCStatus {
+            next_to_process: gc_progress.next_to_process,
+            gc_end: gc_progress.gc_end,
+            watched_addresses: policies
+                .watched_addresses
+                .iter()
+                .cloned()
+                .collect(),
+            data_classes: GCDataClass::ALL
+                .iter()
+                .map(|class| {
+                    let progress = gc_progress.pruning_progress(*class);
+                    DataClassGCStatus {
+                        class: *class,
+                        policy: policies.get(*class).clone(),
+                        earliest_retained_epoch: progress
+                            .earliest_retained_epoch(
+                                policies.get(*class),
+                                gc_progress.gc_end,
+                            ),
+                        progress: progress.clone(),
+                    }
+                })
+                .collect(),
         }
     }
 
@@ -1468,110 +1502,157 @@ impl BlockDataManager {
             gc_progress.last_consensus_best_epoch = best_epoch;
             gc_progress.next_to_process = end;
             self.db_manager.insert_gc_progress_to_db(end);
+            self.db_manager.insert_pruning_progress_to_db(
+                &gc_progress.persisted_pruning(),
+            );
             debug!("Database GC progress: {:?}", gc_progress);
         }
     }
 
-    /// Garbage collect different types of data in the corresponding epoch based
-    /// on `base_epoch` and the `additional_maintained*` parameters of these
-    /// data types.
+    /// Garbage collect different classes of data when the GC base reaches
+    /// `base_epoch`, based on their pruning policies.
     fn gc_base_epoch(&self, base_epoch: u64) {
-        // We must GC tx index before block body, otherwise we may be unable to
-        // get the transactions in this epoch.
-        if let Some(defer_epochs) = self
-            .config
-            .additional_maintained_transaction_index_epoch_count
-        {
-            if base_epoch > defer_epochs as u64 {
-                let epoch_to_remove = base_epoch - defer_epochs as u64;
-                match self.all_epoch_set_hashes_from_db(epoch_to_remove) {
-                    None => warn!(
-                        "GC epoch set is missing! epoch_to_remove: {}",
-                        epoch_to_remove
-                    ),
-                    Some(epoch_blocks) => {
-                        // Store all packed transactions in a set first to
-                        // deduplicate transactions for database operations.
-                        let mut transaction_set = HashSet::new();
-                        for b in &epoch_blocks {
-                            if let Some(transactions) =
-                                self.db_manager.block_body_from_db(&b)
-                            {
-                                for tx in transactions {
-                                    transaction_set.insert(tx.hash());
-                                }
-                            }
-                        }
-                        let epoch_block_set: HashSet<H256> =
-                            epoch_blocks.into_iter().collect();
-                        for tx in transaction_set {
-                            if self.config.strict_tx_index_gc {
-                                // Check if this tx is actually executed in the
-                                // processed epoch.
-                                if let Some(tx_index) = self
-                                    .db_manager
-                                    .transaction_index_from_db(&tx)
-                                {
-                                    if epoch_block_set
-                                        .contains(&tx_index.block_hash)
-                                    {
-                                        self.db_manager
-                                            .remove_transaction_index_from_db(
-                                                &tx,
-                                            );
-                                    }
-                                }
-                            } else {
-                                self.db_manager
-                                    .remove_transaction_index_from_db(&tx);
-                            }
-                        }
-                    }
-                }
-            }
-        };
-        self.gc_epoch_with_defer(
-            base_epoch,
-            self.config.additional_maintained_block_body_epoch_count,
-            |h| self.remove_block_body(h, true /* remove_db */),
-        );
-        self.gc_epoch_with_defer(
-            base_epoch,
-            self.config
-                .additional_maintained_execution_result_epoch_count,
-            |h| self.remove_block_result(h, true /* remove_db */),
-        );
-        self.gc_epoch_with_defer(
-            base_epoch,
-            self.config.additional_maintained_reward_epoch_count,
-            |h| self.db_manager.remove_block_reward_result_from_db(h),
-        );
-        self.gc_epoch_with_defer(
+        for class in GCDataClass::ALL.iter() {
+            self.gc_data_class(*class, base_epoch);
+        }
+    }
+
+    /// Prune the epochs of the data class which are not retained by its
+    /// policy, at most `MAX_PRUNED_EPOCHS_PER_BASE_EPOCH` of them.
+    fn gc_data_class(&self, class: GCDataClass, base_epoch: u64) {
+        let policy = self.config.pruning_policies.get(class);
+        if let PruningPolicy::ByDiskBudget(_) = policy {
+            let epoch_bytes = self.epoch_data_size(class, base_epoch);
+            let mut gc_progress = self.gc_progress.lock();
+            let progress = gc_progress.pruning_progress_mut(class);
+            progress.measured_bytes += epoch_bytes;
+            progress.measured_epochs += 1;
+        }
+        let (start, end) = match self.gc_progress.lock().prune_range(
+            class,
+            &self.config.pruning_policies,
             base_epoch,
-            self.config.additional_maintained_trace_epoch_count,
-            |h| self.db_manager.remove_block_trace_from_db(h),
-        );
+        ) {
+            Some(range) => range,
+            None => return,
+        };
+        for epoch_to_remove in start..end {
+            let (pruned, kept) = self.gc_epoch(class, epoch_to_remove);
+            let mut gc_progress = self.gc_progress.lock();
+            let progress = gc_progress.pruning_progress_mut(class);
+            progress.next_to_prune = epoch_to_remove + 1;
+            progress.pruned_entries += pruned;
+            progress.kept_watched_entries += kept;
+        }
+    }
+
+    /// The size in bytes of the data of `class` in the epoch.
+    fn epoch_data_size(&self, class: GCDataClass, epoch_number: u64) -> u64 {
+        self.all_epoch_set_hashes_from_db(epoch_number)
+            .unwrap_or_default()
+            .iter()
+            .map(|h| self.db_manager.block_data_size_from_db(class, h))
+            .sum()
     }
 
-    fn gc_epoch_with_defer<F>(
-        &self, epoch_number: u64, maybe_defer_epochs: Option<usize>, gc_func: F,
-    ) where F: Fn(&H256) -> () {
-        if let Some(defer_epochs) = maybe_defer_epochs {
-            if epoch_number > defer_epochs as u64 {
-                let epoch_to_remove = epoch_number - defer_epochs as u64;
-                match self.all_epoch_set_hashes_from_db(epoch_to_remove) {
-                    None => warn!(
+    /// Return the numbers of the entries which are pruned and which are kept
+    /// for the watched addresses.
+    fn gc_epoch(&self, class: GCDataClass, epoch_to_remove: u64) -> (u64, u64) {
+        let epoch_blocks =
+            match self.all_epoch_set_hashes_from_db(epoch_to_remove) {
+                None => {
+                    warn!(
                         "GC epoch set is missing! epoch_to_remove: {}",
                         epoch_to_remove
-                    ),
-                    Some(epoch_set) => {
-                        for b in epoch_set {
-                            gc_func(&b);
-                        }
+                    );
+                    return (0, 0);
+                }
+                Some(epoch_blocks) => epoch_blocks,
+            };
+        let watched = self.config.pruning_policies.get(class)
+            == &PruningPolicy::WatchedAddresses;
+        if class == GCDataClass::TransactionIndex {
+            return self.gc_transaction_indices(epoch_blocks, watched);
+        }
+
+        let (mut pruned, mut kept) = (0, 0);
+        for h in &epoch_blocks {
+            if watched && self.is_watched_block(h) {
+                kept += 1;
+                continue;
+            }
+            match class {
+                GCDataClass::ExecutionResult => {
+                    self.block_receipts.write().remove(h);
+                    self.db_manager.remove_block_execution_result_from_db(h);
+                }
+                GCDataClass::Reward => {
+                    self.block_rewards.write().remove(h);
+                    self.db_manager.remove_block_reward_result_from_db(h);
+                }
+                GCDataClass::Trace => {
+                    self.db_manager.remove_block_trace_from_db(h)
+                }
+                GCDataClass::BlockBody => {
+                    self.remove_block_body(h, true /* remove_db */)
+                }
+                GCDataClass::TransactionIndex => unreachable!(),
+            }
+            pruned += 1;
+        }
+        (pruned, kept)
+    }
+
+    fn gc_transaction_indices(
+        &self, epoch_blocks: Vec<H256>, watched: bool,
+    ) -> (u64, u64) {
+        let policies = &self.config.pruning_policies;
+        // Store all packed transactions in a set first to deduplicate
+        // transactions for database operations.
+        let mut transaction_set = HashSet::new();
+        let mut kept = 0;
+        for b in &epoch_blocks {
+            if let Some(transactions) = self.db_manager.block_body_from_db(&b)
+            {
+                for tx in transactions {
+                    if watched && policies.is_watched_transaction(&tx) {
+                        kept += 1;
+                    } else {
+                        transaction_set.insert(tx.hash());
+                    }
+                }
+            }
+        }
+        let epoch_block_set: HashSet<H256> = epoch_blocks.into_iter().collect();
+        let mut pruned = 0;
+        for tx in transaction_set {
+            if self.config.strict_tx_index_gc {
+                // Check if this tx is actually executed in the processed
+                // epoch.
+                if let Some(tx_index) =
+                    self.db_manager.transaction_index_from_db(&tx)
+                {
+                    if epoch_block_set.contains(&tx_index.block_hash) {
+                        self.db_manager.remove_transaction_index_from_db(&tx);
+                        pruned += 1;
                     }
                 }
+            } else {
+                self.db_manager.remove_transaction_index_from_db(&tx);
+                pruned += 1;
             }
         }
+        (pruned, kept)
+    }
+
+    fn is_watched_block(&self, hash: &H256) -> bool {
+        self.db_manager
+            .block_body_from_db(hash)
+            .map_or(false, |transactions| {
+                transactions.iter().any(|tx| {
+                    self.config.pruning_policies.is_watched_transaction(tx)
+                })
+            })
     }
 }
 
@@ -1585,11 +1666,7 @@ pub struct DataManagerConfiguration {
     pub persist_tx_index: bool,
     pub tx_cache_index_maintain_timeout: Duration,
     pub db_type: DbType,
-    pub additional_maintained_block_body_epoch_count: Option<usize>,
-    pub additional_maintained_execution_result_epoch_count: Option<usize>,
-    pub additional_maintained_reward_epoch_count: Option<usize>,
-    pub additional_maintained_trace_epoch_count: Option<usize>,
-    pub additional_maintained_transaction_index_epoch_count: Option<usize>,
+    pub pruning_policies: PruningPolicies,
     pub checkpoint_gc_time_in_epoch_count: usize,
     pub strict_tx_index_gc: bool,
 }
@@ -1608,11 +1685,7 @@ impl DataManagerConfiguration {
             persist_tx_index,
             tx_cache_index_maintain_timeout,
             db_type,
-            additional_maintained_block_body_epoch_count: None,
-            additional_maintained_execution_result_epoch_count: None,
-            additional_maintained_reward_epoch_count: None,
-            additional_maintained_trace_epoch_count: None,
-            additional_maintained_transaction_index_epoch_count: None,
+            pruning_policies: Default::default(),
             checkpoint_gc_time_in_epoch_count: 1,
             strict_tx_index_gc: true,
         }
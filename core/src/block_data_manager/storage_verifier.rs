@@ -0,0 +1,150 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! Offline integrity check of the persisted state against the
+//! `EpochExecutionCommitment`s in the block db.
+
+use crate::block_data_manager::db_manager::DBManager;
+use cfx_storage::{ErrorKind, Result, StorageManager, StorageVerifier};
+use primitives::EpochId;
+
+pub use cfx_storage::{StateTree, StorageCorruption};
+
+/// The corruptions found in a snapshot, or in the state of an epoch.
+#[derive(Clone, Debug)]
+pub struct CorruptedStorage {
+    pub epoch_id: EpochId,
+    pub height: u64,
+    pub corruptions: Vec<StorageCorruption>,
+    /// The error which stopped the verification, e.g. an undecodable trie
+    /// node.
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct StorageVerifyReport {
+    pub verified_snapshots: usize,
+    pub verified_epochs: u64,
+    /// The lowest and the highest height of the verified epochs.
+    pub verified_height_range: Option<(u64, u64)>,
+    pub corrupted_snapshots: Vec<CorruptedStorage>,
+    pub corrupted_states: Vec<CorruptedStorage>,
+}
+
+impl StorageVerifyReport {
+    pub fn is_clean(&self) -> bool {
+        self.corrupted_snapshots.is_empty() && self.corrupted_states.is_empty()
+    }
+}
+
+/// Verifies the persisted snapshots, then the states of the executed epochs
+/// from the highest one down to the lowest one whose delta mpts are kept.
+///
+/// A snapshot is verified against the snapshot root committed by the epoch
+/// `snapshot_epoch_count + 1` after it, or against the merkle root in its
+/// snapshot info if that epoch isn't executed yet. The key-values of
+/// snapshots are verified in ranges of about `range_rlp_size` bytes.
+pub fn verify_storage(
+    db_manager: &DBManager, storage_manager: &StorageManager,
+    range_rlp_size: u64,
+) -> Result<StorageVerifyReport>
+{
+    let mut report = StorageVerifyReport::default();
+    let mut verifier = StorageVerifier::new(storage_manager, range_rlp_size);
+    let pivot_hash = |height: u64| {
+        db_manager
+            .executed_epoch_set_hashes_from_db(height)
+            .and_then(|hashes| hashes.last().cloned())
+    };
+    let commitment = |height: u64| {
+        pivot_hash(height).and_then(|hash| {
+            db_manager
+                .epoch_execution_commitment_from_db(&hash)
+                .map(|commitment| (hash, commitment))
+        })
+    };
+
+    let snapshot_epoch_count = storage_manager
+        .get_storage_manager()
+        .get_snapshot_epoch_count() as u64;
+    let mut snapshot_infos = storage_manager
+        .get_storage_manager()
+        .get_persisted_snapshot_infos();
+    snapshot_infos.sort_by_key(|info| info.height);
+    for info in &snapshot_infos {
+        let snapshot_epoch_id = info.get_snapshot_epoch_id();
+        let merkle_root =
+            match commitment(info.height + snapshot_epoch_count + 1) {
+                Some((_, commitment))
+                    if commitment
+                        .state_root_with_aux_info
+                        .aux_info
+                        .snapshot_epoch_id
+                        == *snapshot_epoch_id =>
+                {
+                    commitment.state_root_with_aux_info.state_root.snapshot_root
+                }
+                _ => info.merkle_root,
+            };
+        let (corruptions, error) =
+            match verifier.verify_snapshot(snapshot_epoch_id, &merkle_root) {
+                Ok(corruptions) => (corruptions, None),
+                Err(e) => (vec![], Some(e.to_string())),
+            };
+        report.verified_snapshots += 1;
+        if !corruptions.is_empty() || error.is_some() {
+            report.corrupted_snapshots.push(CorruptedStorage {
+                epoch_id: *snapshot_epoch_id,
+                height: info.height,
+                corruptions,
+                error,
+            });
+        }
+    }
+
+    // The states above the latest snapshot are always kept.
+    let latest_snapshot_height =
+        snapshot_infos.last().map_or(0, |info| info.height);
+    let mut top_height = latest_snapshot_height;
+    while pivot_hash(top_height + 1).is_some() {
+        top_height += 1;
+    }
+    for height in (0..=top_height).rev() {
+        let (epoch_id, commitment) = match commitment(height) {
+            None => continue,
+            Some(x) => x,
+        };
+        let (corruptions, error) = match verifier
+            .verify_state(&epoch_id, &commitment.state_root_with_aux_info)
+        {
+            Ok(corruptions) => {
+                if corruptions == vec![StorageCorruption::MissingState]
+                    && height < latest_snapshot_height
+                {
+                    break;
+                }
+                (corruptions, None)
+            }
+            Err(e) => match e.kind() {
+                ErrorKind::DeltaMPTEntryNotFound => break,
+                _ => (vec![], Some(e.to_string())),
+            },
+        };
+        report.verified_epochs += 1;
+        report.verified_height_range = Some((
+            height,
+            report.verified_height_range.map_or(height, |range| range.1),
+        ));
+        if !corruptions.is_empty() || error.is_some() {
+            report.corrupted_states.push(CorruptedStorage {
+                epoch_id,
+                height,
+                corruptions,
+                error,
+            });
+        }
+    }
+
+    Ok(report)
+}
@@ -1663,6 +1663,119 @@ impl ConsensusExecutionHandler {
         )
     }
 
+    /// Re-executes the epoch on the stored state of its parent, without
+    /// changing the stored execution results: the receipts and traces are
+    /// only kept in memory, and the state is committed only if its root
+    /// matches the stored commitment of the epoch, so that the next epoch can
+    /// be replayed on it.
+    ///
+    /// Return `None` if the parent state or the blocks of the epoch are not
+    /// available.
+    pub fn replay_epoch(
+        &self, task: &EpochExecutionTask,
+        debug_record: Option<&mut ComputeEpochDebugRecord>,
+    ) -> DbResult<Option<EpochExecutionCommitment>>
+    {
+        self.reexecute_epoch(
+            &task.epoch_hash,
+            &task.epoch_block_hashes,
+            task.start_block_number,
+            &task.reward_info,
+            debug_record,
+        )
+    }
+
+    /// Like `replay_epoch`, but for the epochs which may not be in the
+    /// consensus graph, e.g. to recompute the traces of the blocks executed
+    /// before `executive_trace` is enabled. The rewards are only processed
+    /// with `reward_execution_info`, so without it the state root of an epoch
+    /// with rewards doesn't match and the state isn't committed, while the
+    /// receipts and traces are still recomputed.
+    pub fn reexecute_epoch(
+        &self, epoch_hash: &H256, epoch_block_hashes: &Vec<H256>,
+        start_block_number: u64,
+        reward_execution_info: &Option<RewardExecutionInfo>,
+        mut debug_record: Option<&mut ComputeEpochDebugRecord>,
+    ) -> DbResult<Option<EpochExecutionCommitment>>
+    {
+        let epoch_blocks = match self.data_man.blocks_by_hash_list(
+            epoch_block_hashes,
+            false, /* update_cache */
+        ) {
+            Some(blocks) => blocks,
+            None => return Ok(None),
+        };
+        let mut state = match self.parent_state_of_epoch(&epoch_blocks)? {
+            Some(state) => state,
+            None => return Ok(None),
+        };
+
+        let epoch_receipts = self.process_epoch_transactions(
+            &Spec::new_spec(),
+            *epoch_hash,
+            &mut state,
+            &epoch_blocks,
+            start_block_number,
+            false, /* on_local_pivot */
+        )?;
+        if let Some(reward_execution_info) = reward_execution_info {
+            self.process_rewards_and_fees(
+                &mut state,
+                reward_execution_info,
+                false, /* on_local_pivot */
+                debug_record.as_deref_mut(),
+            );
+        }
+
+        let mut state_root =
+            state.compute_state_root(debug_record.as_deref_mut())?;
+        let state_root_matches = self
+            .data_man
+            .get_epoch_execution_commitment_with_db(epoch_hash)
+            .map_or(false, |commitment| {
+                commitment.state_root_with_aux_info == state_root
+            });
+        if state_root_matches {
+            state_root = state.commit(*epoch_hash, debug_record)?;
+        }
+        Ok(Some(EpochExecutionCommitment {
+            state_root_with_aux_info: state_root,
+            receipts_root: compute_receipts_root(&epoch_receipts),
+            logs_bloom_hash: BlockHeaderBuilder::compute_block_logs_bloom_hash(
+                &epoch_receipts,
+            ),
+        }))
+    }
+
+    /// Return `None` if the stored state of the parent of the epoch is not
+    /// available.
+    fn parent_state_of_epoch(
+        &self, epoch_blocks: &Vec<Arc<Block>>,
+    ) -> DbResult<Option<State>> {
+        let pivot_block = epoch_blocks.last().expect("Not empty");
+        let parent_hash = pivot_block.block_header.parent_hash();
+        let parent_state_root = match self
+            .data_man
+            .get_epoch_execution_commitment_with_db(parent_hash)
+        {
+            Some(commitment) => commitment.state_root_with_aux_info,
+            None => return Ok(None),
+        };
+        let storage_state = match self
+            .data_man
+            .storage_manager
+            .get_state_for_next_epoch(StateIndex::new_for_next_epoch(
+                parent_hash,
+                &parent_state_root,
+                pivot_block.block_header.height() - 1,
+                self.data_man.get_snapshot_epoch_count(),
+            ))? {
+            Some(state) => state,
+            None => return Ok(None),
+        };
+        Ok(Some(State::new(StateDb::new(storage_state))?))
+    }
+
     pub fn call_virtual(
         &self, tx: &SignedTransaction, epoch_id: &H256, epoch_size: usize,
     ) -> RpcResult<ExecutionOutcome> {
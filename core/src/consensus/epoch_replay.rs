@@ -0,0 +1,188 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! Re-execution of the epochs on the pivot chain, to check the execution
+//! results against the stored commitments, e.g. after a change of the VM.
+
+/// The result of replaying an epoch.
+#[derive(Clone, Debug)]
+pub struct EpochReplayResult {
+    pub height: u64,
+    pub epoch_hash: H256,
+    pub block_count: usize,
+    pub transaction_count: usize,
+    /// The time spent in the execution of the epoch.
+    pub elapsed: Duration,
+    /// The stored commitment of the epoch.
+    pub expected: EpochExecutionCommitment,
+    pub computed: EpochExecutionCommitment,
+    /// The traces of the blocks in the epoch, which are only collected when
+    /// requested.
+    pub traces: Vec<(H256, BlockExecTraces)>,
+}
+
+impl EpochReplayResult {
+    pub fn state_root_matches(&self) -> bool {
+        self.computed.state_root_with_aux_info
+            == self.expected.state_root_with_aux_info
+    }
+
+    pub fn receipts_root_matches(&self) -> bool {
+        self.computed.receipts_root == self.expected.receipts_root
+    }
+
+    pub fn logs_bloom_hash_matches(&self) -> bool {
+        self.computed.logs_bloom_hash == self.expected.logs_bloom_hash
+    }
+
+    pub fn is_consistent(&self) -> bool {
+        self.state_root_matches()
+            && self.receipts_root_matches()
+            && self.logs_bloom_hash_matches()
+    }
+}
+
+/// Replays the epoch at `height` on the pivot chain. The epoch must be in the
+/// current era of the consensus graph, and the state of its parent must be
+/// available, e.g. the epoch right after a persisted snapshot, or an epoch
+/// whose parent has been replayed consistently.
+///
+/// The traces are read from the memory cache of the block data manager, so
+/// `with_traces` is only meaningful if `executive_trace` is enabled.
+pub fn replay_epoch(
+    inner: &mut ConsensusGraphInner, executor: &ConsensusExecutor,
+    height: u64, with_traces: bool,
+    debug_record: Option<&mut ComputeEpochDebugRecord>,
+) -> Result<EpochReplayResult, String>
+{
+    if height <= inner.cur_era_genesis_height
+        || height >= inner.get_pivot_height()
+    {
+        return Err(format!(
+            "Epoch {} is not in the consensus graph, which has the epochs \
+             from {} to {}",
+            height,
+            inner.cur_era_genesis_height + 1,
+            inner.get_pivot_height() - 1
+        ));
+    }
+    let epoch_arena_index = inner.get_pivot_block_arena_index(height);
+    let epoch_hash = inner.arena[epoch_arena_index].hash;
+    let expected = inner
+        .data_man
+        .get_epoch_execution_commitment_with_db(&epoch_hash)
+        .ok_or_else(|| format!("Epoch {} is not executed", height))?;
+
+    let reward_execution_info =
+        executor.get_reward_execution_info(inner, epoch_arena_index);
+    let task = EpochExecutionTask::new(
+        epoch_arena_index,
+        inner,
+        reward_execution_info,
+        false, /* on_local_pivot */
+        true,  /* force_recompute */
+    );
+    let start = Instant::now();
+    let computed = executor
+        .handler
+        .replay_epoch(&task, debug_record)
+        .map_err(|e| format!("Failed to replay epoch {}: {}", height, e))?
+        .ok_or_else(|| {
+            format!(
+                "The parent state or the blocks of epoch {} are not available",
+                height
+            )
+        })?;
+    let elapsed = start.elapsed();
+
+    let block_hashes = inner.get_epoch_block_hashes(epoch_arena_index);
+    let transaction_count = block_hashes
+        .iter()
+        .filter_map(|hash| {
+            inner.data_man.block_by_hash(hash, false /* update_cache */)
+        })
+        .map(|block| block.transactions.len())
+        .sum();
+    let traces = if with_traces {
+        block_hashes
+            .iter()
+            .filter_map(|hash| {
+                inner
+                    .data_man
+                    .block_traces_by_hash_with_epoch(
+                        hash,
+                        &epoch_hash,
+                        false, /* update_pivot_assumption */
+                        false, /* update_cache */
+                    )
+                    .map(|traces| (*hash, traces))
+            })
+            .collect()
+    } else {
+        vec![]
+    };
+
+    Ok(EpochReplayResult {
+        height,
+        epoch_hash,
+        block_count: block_hashes.len(),
+        transaction_count,
+        elapsed,
+        expected,
+        computed,
+        traces,
+    })
+}
+
+use crate::{
+    consensus::{
+        consensus_inner::consensus_executor::{
+            ConsensusExecutor, EpochExecutionTask,
+        },
+        ConsensusGraphInner,
+    },
+    trace::trace::BlockExecTraces,
+};
+use cfx_internal_common::EpochExecutionCommitment;
+use cfx_types::H256;
+use std::time::{Duration, Instant};
+
+pub use cfx_internal_common::debug::ComputeEpochDebugRecord;
+
+#[cfg(test)]
+mod tests {
+    use crate::sync::utils::{initialize_executed_chain, remove_test_db_dir};
+
+    const DB_DIR: &str = "./test_replay_epoch.db/";
+    const EPOCH_COUNT: u64 = 5;
+
+    #[test]
+    fn test_replay_executed_epochs() {
+        {
+            let (_sync, consensus, _data_man) = initialize_executed_chain(
+                DB_DIR,
+                EPOCH_COUNT,
+                EPOCH_COUNT,
+                false, /* executive_trace */
+            );
+            // The epochs are replayed in order, each on the state committed
+            // by the replay of its parent.
+            for height in 1..=EPOCH_COUNT {
+                let result = consensus
+                    .replay_epoch(height, false /* with_traces */, None)
+                    .unwrap();
+                assert_eq!(result.height, height);
+                assert_eq!(result.block_count, 1);
+                assert!(result.is_consistent(), "{:?}", result);
+            }
+            // The era genesis and the epochs after the pivot chain can't be
+            // replayed.
+            assert!(consensus.replay_epoch(0, false, None).is_err());
+            assert!(consensus
+                .replay_epoch(EPOCH_COUNT + 1, false, None)
+                .is_err());
+        }
+        remove_test_db_dir(DB_DIR);
+    }
+}
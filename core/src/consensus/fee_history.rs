@@ -0,0 +1,294 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::{H256, U256};
+use primitives::Block;
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::Arc,
+};
+
+/// Gas price statistics of the transactions packed in one epoch.
+#[derive(Debug, Clone)]
+pub struct EpochGasPriceSample {
+    pub epoch_number: u64,
+    /// The pivot block of the epoch. Used to invalidate the cached sample
+    /// after a pivot chain reorg.
+    pub pivot_hash: H256,
+    pub block_count: usize,
+    /// Gas prices of the distinct transactions packed in the epoch, sorted
+    /// in ascending order.
+    pub gas_prices: Vec<U256>,
+}
+
+impl EpochGasPriceSample {
+    /// Build the sample from the blocks of an epoch. A transaction packed in
+    /// several blocks of the same epoch is only counted once.
+    pub fn from_blocks<'a>(
+        epoch_number: u64, pivot_hash: H256,
+        blocks: impl IntoIterator<Item = &'a Block>,
+    ) -> Self
+    {
+        let mut tx_hashes = HashSet::new();
+        let mut gas_prices = Vec::new();
+        let mut block_count = 0;
+        for block in blocks {
+            block_count += 1;
+            for tx in &block.transactions {
+                if tx_hashes.insert(tx.hash()) {
+                    gas_prices.push(*tx.gas_price());
+                }
+            }
+        }
+        gas_prices.sort();
+        EpochGasPriceSample {
+            epoch_number,
+            pivot_hash,
+            block_count,
+            gas_prices,
+        }
+    }
+
+    pub fn tx_count(&self) -> usize { self.gas_prices.len() }
+
+    /// The gas price at `percentile`, or zero if the epoch is empty.
+    pub fn percentile(&self, percentile: f64) -> U256 {
+        percentile_of_sorted(&self.gas_prices, percentile).unwrap_or_default()
+    }
+}
+
+/// Return the value at `percentile` (within `[0, 100]`) of `sorted`.
+pub fn percentile_of_sorted(sorted: &[U256], percentile: f64) -> Option<U256> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let index = (sorted.len() - 1) as f64 * percentile / 100.0;
+    Some(sorted[(index.round() as usize).min(sorted.len() - 1)])
+}
+
+/// Check that the requested percentiles are within `[0, 100]` and
+/// monotonically increasing.
+pub fn validate_percentiles(percentiles: &[f64]) -> Result<(), String> {
+    let mut last = 0.0;
+    for p in percentiles {
+        if !(0.0..=100.0).contains(p) {
+            return Err(format!(
+                "Invalid percentile {}, expected a value within [0, 100]",
+                p
+            ));
+        }
+        if *p < last {
+            return Err(format!(
+                "Invalid percentiles {:?}, expected monotonically increasing values",
+                percentiles
+            ));
+        }
+        last = *p;
+    }
+    Ok(())
+}
+
+/// A sliding window of the most recent per-epoch gas price samples, so that
+/// repeated fee history queries do not need to reload the blocks.
+pub struct FeeHistoryCache {
+    capacity: usize,
+    samples: BTreeMap<u64, Arc<EpochGasPriceSample>>,
+}
+
+impl FeeHistoryCache {
+    pub fn new(capacity: usize) -> Self {
+        FeeHistoryCache {
+            capacity,
+            samples: BTreeMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize { self.samples.len() }
+
+    /// Return the cached sample of `epoch_number` if it was computed with the
+    /// same pivot block.
+    pub fn get(
+        &self, epoch_number: u64, pivot_hash: &H256,
+    ) -> Option<Arc<EpochGasPriceSample>> {
+        self.samples
+            .get(&epoch_number)
+            .filter(|sample| sample.pivot_hash == *pivot_hash)
+            .cloned()
+    }
+
+    /// Insert a sample and evict the oldest epochs beyond the capacity. A
+    /// sample older than the whole window is not kept.
+    pub fn insert(&mut self, sample: Arc<EpochGasPriceSample>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.samples.len() >= self.capacity {
+            if let Some(oldest) = self.samples.keys().next() {
+                if sample.epoch_number < *oldest {
+                    return;
+                }
+            }
+        }
+        self.samples.insert(sample.epoch_number, sample);
+        while self.samples.len() > self.capacity {
+            let oldest = *self.samples.keys().next().unwrap();
+            self.samples.remove(&oldest);
+        }
+    }
+}
+
+/// The gas price statistics of a consecutive range of epochs.
+#[derive(Debug, Clone, Default)]
+pub struct FeeHistory {
+    pub oldest_epoch: u64,
+    pub block_count: Vec<usize>,
+    pub tx_count: Vec<usize>,
+    /// For each epoch, the gas price at each requested percentile.
+    pub gas_price_percentiles: Vec<Vec<U256>>,
+}
+
+impl FeeHistory {
+    /// Assemble the fee history from the samples of consecutive epochs
+    /// starting at `oldest_epoch`.
+    pub fn from_samples(
+        oldest_epoch: u64, samples: &[Arc<EpochGasPriceSample>],
+        percentiles: &[f64],
+    ) -> Self
+    {
+        let mut history = FeeHistory {
+            oldest_epoch,
+            ..Default::default()
+        };
+        for sample in samples {
+            history.block_count.push(sample.block_count);
+            history.tx_count.push(sample.tx_count());
+            history.gas_price_percentiles.push(
+                percentiles.iter().map(|p| sample.percentile(*p)).collect(),
+            );
+        }
+        history
+    }
+}
+
+/// How fast the sender wants a transaction to be packed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GasPriceUrgency {
+    Slow,
+    Normal,
+    Fast,
+}
+
+impl GasPriceUrgency {
+    /// The percentile of the recently packed (and pending) gas prices that
+    /// the suggestion targets.
+    pub fn percentile(&self) -> f64 {
+        match self {
+            GasPriceUrgency::Slow => 25.0,
+            GasPriceUrgency::Normal => 50.0,
+            GasPriceUrgency::Fast => 90.0,
+        }
+    }
+}
+
+impl Default for GasPriceUrgency {
+    fn default() -> Self { GasPriceUrgency::Normal }
+}
+
+/// Suggest a gas price from recent epoch samples and, if available, the gas
+/// price of the pending transactions at the same percentile. The larger one
+/// is taken so that the transaction also competes with the current backlog.
+/// Return `None` if there is no data at all.
+pub fn suggest_gas_price(
+    samples: &[Arc<EpochGasPriceSample>], pending_gas_price: Option<U256>,
+    urgency: GasPriceUrgency,
+) -> Option<U256>
+{
+    let mut prices: Vec<U256> = samples
+        .iter()
+        .flat_map(|sample| sample.gas_prices.iter().cloned())
+        .collect();
+    prices.sort();
+    let packed_gas_price = percentile_of_sorted(&prices, urgency.percentile());
+    match (packed_gas_price, pending_gas_price) {
+        (Some(packed), Some(pending)) => Some(packed.max(pending)),
+        (packed, pending) => packed.or(pending),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(epoch_number: u64, prices: &[u64]) -> Arc<EpochGasPriceSample> {
+        let mut gas_prices: Vec<U256> =
+            prices.iter().map(|p| U256::from(*p)).collect();
+        gas_prices.sort();
+        Arc::new(EpochGasPriceSample {
+            epoch_number,
+            pivot_hash: H256::from_low_u64_be(epoch_number),
+            block_count: 1,
+            gas_prices,
+        })
+    }
+
+    #[test]
+    fn test_percentile_of_sorted() {
+        let prices: Vec<U256> = (1..=101u64).map(U256::from).collect();
+        assert_eq!(percentile_of_sorted(&prices, 0.0), Some(1.into()));
+        assert_eq!(percentile_of_sorted(&prices, 50.0), Some(51.into()));
+        assert_eq!(percentile_of_sorted(&prices, 100.0), Some(101.into()));
+        assert_eq!(percentile_of_sorted(&[], 50.0), None);
+    }
+
+    #[test]
+    fn test_validate_percentiles() {
+        assert!(validate_percentiles(&[]).is_ok());
+        assert!(validate_percentiles(&[10.0, 50.0, 90.0]).is_ok());
+        assert!(validate_percentiles(&[50.0, 10.0]).is_err());
+        assert!(validate_percentiles(&[101.0]).is_err());
+        assert!(validate_percentiles(&[-1.0]).is_err());
+    }
+
+    #[test]
+    fn test_cache_sliding_window() {
+        let mut cache = FeeHistoryCache::new(2);
+        cache.insert(sample(1, &[1]));
+        cache.insert(sample(2, &[2]));
+        cache.insert(sample(3, &[3]));
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(1, &H256::from_low_u64_be(1)).is_none());
+        assert!(cache.get(3, &H256::from_low_u64_be(3)).is_some());
+        // Reorged pivot block.
+        assert!(cache.get(3, &H256::from_low_u64_be(4)).is_none());
+        // Older than the whole window.
+        cache.insert(sample(0, &[0]));
+        assert!(cache.get(0, &H256::from_low_u64_be(0)).is_none());
+    }
+
+    #[test]
+    fn test_suggest_gas_price() {
+        let samples = vec![sample(1, &[1, 2, 3, 4]), sample(2, &[5, 6, 7, 8])];
+        assert_eq!(
+            suggest_gas_price(&samples, None, GasPriceUrgency::Slow),
+            Some(3.into())
+        );
+        assert_eq!(
+            suggest_gas_price(&samples, None, GasPriceUrgency::Fast),
+            Some(7.into())
+        );
+        assert_eq!(
+            suggest_gas_price(
+                &samples,
+                Some(100.into()),
+                GasPriceUrgency::Normal
+            ),
+            Some(100.into())
+        );
+        assert_eq!(
+            suggest_gas_price(&[], Some(10.into()), GasPriceUrgency::Normal),
+            Some(10.into())
+        );
+        assert_eq!(suggest_gas_price(&[], None, GasPriceUrgency::Fast), None);
+    }
+}
@@ -6,7 +6,10 @@ mod anticone_cache;
 pub mod consensus_inner;
 pub mod consensus_trait;
 pub mod debug_recompute;
+pub mod epoch_replay;
+pub mod fee_history;
 mod pastset_cache;
+pub mod trace_backfill;
 
 pub use crate::consensus::{
     consensus_inner::{ConsensusGraphInner, ConsensusInnerConfig},
@@ -20,8 +23,16 @@ use super::consensus::consensus_inner::{
 };
 use crate::{
     block_data_manager::{BlockDataManager, BlockExecutionResultWithEpoch},
-    consensus::consensus_inner::{
-        consensus_executor::ConsensusExecutionConfiguration, StateBlameInfo,
+    consensus::{
+        consensus_inner::{
+            consensus_executor::ConsensusExecutionConfiguration, StateBlameInfo,
+        },
+        epoch_replay::{replay_epoch, EpochReplayResult},
+        fee_history::{
+            suggest_gas_price, validate_percentiles, EpochGasPriceSample,
+            FeeHistory, FeeHistoryCache, GasPriceUrgency,
+        },
+        trace_backfill::TraceBackfillStatus,
     },
     executive::ExecutionOutcome,
     pow::{PowComputer, ProofOfWorkConfig},
@@ -36,19 +47,25 @@ use crate::{
     verification::VerificationConfig,
     NodeType, Notifications,
 };
-use cfx_internal_common::ChainIdParams;
+use cfx_internal_common::{
+    debug::ComputeEpochDebugRecord, ChainIdParams, StateAvailabilityBoundary,
+};
 use cfx_parameters::{
     consensus::*,
     consensus_internal::REWARD_EPOCH_COUNT,
     rpc::{
-        GAS_PRICE_BLOCK_SAMPLE_SIZE, GAS_PRICE_TRANSACTION_SAMPLE_SIZE,
+        FEE_HISTORY_CACHE_EPOCH_COUNT, GAS_PRICE_BLOCK_SAMPLE_SIZE,
+        GAS_PRICE_SUGGESTION_EPOCH_COUNT, GAS_PRICE_TRANSACTION_SAMPLE_SIZE,
+        MAX_FEE_HISTORY_EPOCH_COUNT,
         TRANSACTION_COUNT_PER_BLOCK_WATER_LINE_LOW,
         TRANSACTION_COUNT_PER_BLOCK_WATER_LINE_MEDIUM,
     },
 };
 use cfx_state::state_trait::StateOpsTrait;
 use cfx_statedb::StateDb;
-use cfx_storage::state_manager::StateManagerTrait;
+use cfx_storage::{
+    state_manager::StateManagerTrait, EpochByHeight, StorageState,
+};
 use cfx_types::{Bloom, H160, H256, U256};
 use either::Either;
 use itertools::Itertools;
@@ -188,7 +205,7 @@ pub struct ConsensusGraph {
     pub inner: Arc<RwLock<ConsensusGraphInner>>,
     pub txpool: SharedTransactionPool,
     pub data_man: Arc<BlockDataManager>,
-    executor: Arc<ConsensusExecutor>,
+    pub(crate) executor: Arc<ConsensusExecutor>,
     statistics: SharedStatistics,
     pub new_block_handler: ConsensusNewBlockHandler,
     pub confirmation_meter: ConfirmationMeter,
@@ -202,6 +219,13 @@ pub struct ConsensusGraph {
     /// This is always `None` for archive nodes.
     pub synced_epoch_id: Mutex<Option<EpochId>>,
     pub config: ConsensusConfig,
+
+    /// Gas price samples of recent epochs for fee history queries.
+    fee_history_cache: Mutex<FeeHistoryCache>,
+
+    /// The progress of the trace backfill, which is `None` if it's not
+    /// started.
+    trace_backfill_status: Mutex<Option<TraceBackfillStatus>>,
 }
 
 impl MallocSizeOf for ConsensusGraph {
@@ -267,6 +291,10 @@ impl ConsensusGraph {
             ready_for_mining: AtomicBool::new(false),
             synced_epoch_id: Default::default(),
             config: conf,
+            fee_history_cache: Mutex::new(FeeHistoryCache::new(
+                FEE_HISTORY_CACHE_EPOCH_COUNT,
+            )),
+            trace_backfill_status: Default::default(),
         };
         graph.update_best_info(false /* ready_for_mining */);
         graph
@@ -450,6 +478,80 @@ impl ConsensusGraph {
         }
     }
 
+    /// Get the gas price sample of the transactions packed in
+    /// `epoch_number`, from the fee history cache if possible.
+    pub fn epoch_gas_price_sample(
+        &self, epoch_number: u64,
+    ) -> Result<Arc<EpochGasPriceSample>, String> {
+        let hashes = self.inner.read().block_hashes_by_epoch(epoch_number)?;
+        let pivot_hash = *hashes.last().ok_or("Inconsistent state")?;
+        if let Some(sample) =
+            self.fee_history_cache.lock().get(epoch_number, &pivot_hash)
+        {
+            return Ok(sample);
+        }
+
+        let mut blocks = Vec::with_capacity(hashes.len());
+        for hash in &hashes {
+            blocks.push(
+                self.data_man
+                    .block_by_hash(hash, false /* update_cache */)
+                    .ok_or_else(|| {
+                        format!("Block {:?} not found in db", hash)
+                    })?,
+            );
+        }
+        let sample = Arc::new(EpochGasPriceSample::from_blocks(
+            epoch_number,
+            pivot_hash,
+            blocks.iter().map(|b| &**b),
+        ));
+        self.fee_history_cache.lock().insert(sample.clone());
+        Ok(sample)
+    }
+
+    /// Return the gas price percentiles of the transactions packed in the
+    /// `epoch_count` epochs ending at `newest_epoch`.
+    pub fn fee_history(
+        &self, epoch_count: u64, newest_epoch: EpochNumber,
+        percentiles: &[f64],
+    ) -> Result<FeeHistory, String>
+    {
+        validate_percentiles(percentiles)?;
+        if epoch_count == 0 || epoch_count > MAX_FEE_HISTORY_EPOCH_COUNT {
+            return Err(format!(
+                "Invalid epoch count {}, expected a value within [1, {}]",
+                epoch_count, MAX_FEE_HISTORY_EPOCH_COUNT
+            ));
+        }
+        let newest_epoch = self.get_height_from_epoch_number(newest_epoch)?;
+        let oldest_epoch = (newest_epoch + 1).saturating_sub(epoch_count);
+        let samples = (oldest_epoch..=newest_epoch)
+            .map(|epoch| self.epoch_gas_price_sample(epoch))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(FeeHistory::from_samples(
+            oldest_epoch,
+            &samples,
+            percentiles,
+        ))
+    }
+
+    /// Suggest a gas price for `urgency` based on the recently packed
+    /// transactions and the transactions pending in the ready pool.
+    pub fn suggest_gas_price(
+        &self, urgency: GasPriceUrgency,
+    ) -> Result<Option<U256>, String> {
+        let newest_epoch = self.best_epoch_number();
+        let oldest_epoch =
+            (newest_epoch + 1).saturating_sub(GAS_PRICE_SUGGESTION_EPOCH_COUNT);
+        let samples = (oldest_epoch..=newest_epoch)
+            .map(|epoch| self.epoch_gas_price_sample(epoch))
+            .collect::<Result<Vec<_>, _>>()?;
+        let pending_gas_price =
+            self.txpool.ready_gas_price_percentile(urgency.percentile());
+        Ok(suggest_gas_price(&samples, pending_gas_price, urgency))
+    }
+
     fn validate_stated_epoch(
         &self, epoch_number: &EpochNumber,
     ) -> Result<(), String> {
@@ -1041,6 +1143,28 @@ impl ConsensusGraph {
         self.executor.call_virtual(tx, &epoch_id, epoch_size)
     }
 
+    /// Re-executes the epoch at `height` on the pivot chain, see
+    /// `epoch_replay::replay_epoch`.
+    pub fn replay_epoch(
+        &self, height: u64, with_traces: bool,
+        debug_record: Option<&mut ComputeEpochDebugRecord>,
+    ) -> Result<EpochReplayResult, String>
+    {
+        replay_epoch(
+            &mut *self.inner.write(),
+            &self.executor,
+            height,
+            with_traces,
+            debug_record,
+        )
+    }
+
+    /// Return `None` if the trace backfill is not enabled, or it's waiting for
+    /// the node to enter the normal phase.
+    pub fn trace_backfill_status(&self) -> Option<TraceBackfillStatus> {
+        self.trace_backfill_status.lock().clone()
+    }
+
     /// Get the number of processed blocks (i.e., the number of calls to
     /// on_new_block()
     pub fn get_processed_block_count(&self) -> usize {
@@ -1055,6 +1179,15 @@ impl ConsensusGraph {
         let state_availability_boundary =
             self.data_man.state_availability_boundary.read();
         if !state_availability_boundary.check_availability(height, &hash) {
+            // In archive-state mode, the states which are no longer
+            // available can still be read from the state history.
+            if let Some(state) = self.get_state_from_history(
+                height,
+                hash,
+                &state_availability_boundary,
+            )? {
+                return Ok(StateDb::new(state));
+            }
             debug!(
                 "State for epoch (number={:?} hash={:?}) does not exist: out-of-bound {:?}",
                 height, hash, state_availability_boundary
@@ -1091,6 +1224,52 @@ impl ConsensusGraph {
         Ok(StateDb::new(state))
     }
 
+    /// Read the state of an executed pivot epoch from the state history.
+    /// Returns None if the archive-state mode is off or the epoch isn't
+    /// covered by the history.
+    fn get_state_from_history(
+        &self, height: u64, hash: &H256,
+        state_availability_boundary: &StateAvailabilityBoundary,
+    ) -> RpcResult<Option<StorageState>>
+    {
+        if self
+            .data_man
+            .storage_manager
+            .get_storage_manager()
+            .get_state_history()
+            .is_none()
+        {
+            return Ok(None);
+        }
+        let data_man = self.data_man.clone();
+        let epoch_by_height: EpochByHeight = Arc::new(move |height| {
+            data_man
+                .executed_epoch_set_hashes_from_db(height)
+                .and_then(|epoch_hashes| epoch_hashes.last().cloned())
+        });
+        if epoch_by_height(height) != Some(*hash) {
+            return Ok(None);
+        }
+        // The state trees of the latest available state are needed to
+        // construct the State, but they aren't read.
+        let base_epoch = &state_availability_boundary.pivot_chain
+            [(state_availability_boundary.upper_bound
+                - state_availability_boundary.lower_bound)
+                as usize];
+        let base_state_index =
+            match self.data_man.get_state_readonly_index(base_epoch) {
+                Some(state_index) => state_index,
+                None => return Ok(None),
+            };
+        Ok(self
+            .data_man
+            .storage_manager
+            .get_state_from_history(base_state_index, height, epoch_by_height)
+            .map_err(|e| {
+                format!("Error to get state from history, err={:?}", e)
+            })?)
+    }
+
     /// This function is called after a new block appended to the
     /// ConsensusGraph. Because BestInformation is often queried outside. We
     /// store a version of best_info outside the inner to prevent keep
@@ -0,0 +1,507 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! Backfill of the traces of the epochs executed before `executive_trace` is
+//! enabled, by re-executing them on the stored states in the background. The
+//! state of a re-executed epoch is committed if its root matches, so that the
+//! backfill replays forward from the first available state.
+
+/// The interval to check if the node has entered the normal phase, before
+/// which the backfill doesn't start.
+const NORMAL_PHASE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The number of epochs between the progress logs.
+const PROGRESS_LOG_EPOCH_COUNT: u64 = 1000;
+
+#[derive(Clone, Debug)]
+pub struct TraceBackfillConfig {
+    /// The first epoch to backfill. If it's `None`, the backfill resumes from
+    /// the persisted progress, or starts from the genesis.
+    pub start_epoch: Option<u64>,
+    /// The time to wait after each epoch, to throttle the re-execution.
+    pub interval: Duration,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct TraceBackfillStatus {
+    /// The epochs `[start_epoch, end_epoch)` to backfill, where `end_epoch` is
+    /// the latest executed epoch when the node enters the normal phase.
+    pub start_epoch: u64,
+    pub end_epoch: u64,
+    /// The next epoch to backfill, which is persisted so that the backfill
+    /// resumes from it after a restart.
+    pub next_epoch: u64,
+    pub backfilled_epochs: u64,
+    pub backfilled_blocks: u64,
+    /// The epochs skipped because the state of their parent, their blocks or
+    /// their execution results are not available.
+    pub unavailable_epochs: u64,
+    /// The epochs skipped because their re-executed receipts don't match the
+    /// stored commitments.
+    pub mismatched_epochs: u64,
+    pub finished: bool,
+}
+
+enum EpochBackfillResult {
+    AlreadyTraced,
+    Backfilled(usize),
+    Unavailable,
+    Mismatched,
+}
+
+/// Starts the backfill in the background. It stops when all the epochs are
+/// processed or on exit.
+pub fn start_trace_backfill(
+    consensus: Weak<ConsensusGraph>, config: TraceBackfillConfig,
+    exit: Arc<(Mutex<bool>, Condvar)>,
+)
+{
+    thread::Builder::new()
+        .name("trace_backfill".into())
+        .spawn(move || {
+            loop {
+                match consensus.upgrade() {
+                    Some(consensus) => {
+                        if consensus.ready_for_mining.load(Ordering::SeqCst) {
+                            init_status(&consensus, &config);
+                            break;
+                        }
+                    }
+                    None => return,
+                }
+                if wait_for_exit(&exit, NORMAL_PHASE_CHECK_INTERVAL) {
+                    return;
+                }
+            }
+            loop {
+                match consensus.upgrade() {
+                    Some(consensus) => {
+                        if !backfill_next_epoch(&consensus) {
+                            return;
+                        }
+                    }
+                    None => return,
+                }
+                if wait_for_exit(&exit, config.interval) {
+                    return;
+                }
+            }
+        })
+        .expect("Trace backfill thread spawn error");
+}
+
+/// Return `true` on exit.
+fn wait_for_exit(exit: &(Mutex<bool>, Condvar), timeout: Duration) -> bool {
+    let mut exit_lock = exit.0.lock();
+    !exit.1.wait_for(&mut exit_lock, timeout).timed_out()
+}
+
+fn init_status(consensus: &ConsensusGraph, config: &TraceBackfillConfig) {
+    let data_man = &consensus.data_man;
+    let start_epoch = config
+        .start_epoch
+        .or_else(|| data_man.db_manager.trace_backfill_progress_from_db())
+        .unwrap_or(0);
+    // The traces of the earlier epochs are garbage collected, and the genesis
+    // epoch has no traces.
+    let start_epoch = max(
+        max(start_epoch, 1),
+        max(
+            data_man.earliest_epoch_with_trace(),
+            data_man.earliest_epoch_with_block_body(),
+        ),
+    );
+    let end_epoch = consensus.best_executed_state_epoch_number() + 1;
+    info!(
+        "Start trace backfill from epoch {} to {}",
+        start_epoch, end_epoch
+    );
+    *consensus.trace_backfill_status.lock() = Some(TraceBackfillStatus {
+        start_epoch,
+        end_epoch,
+        next_epoch: start_epoch,
+        ..Default::default()
+    });
+}
+
+/// Return `false` if the backfill is finished.
+fn backfill_next_epoch(consensus: &ConsensusGraph) -> bool {
+    let height = match &*consensus.trace_backfill_status.lock() {
+        Some(status) if status.next_epoch < status.end_epoch => {
+            status.next_epoch
+        }
+        _ => return false,
+    };
+    let result = backfill_epoch(consensus, height);
+
+    let mut status_guard = consensus.trace_backfill_status.lock();
+    let status = status_guard.as_mut().expect("initialized");
+    match result {
+        EpochBackfillResult::AlreadyTraced => {}
+        EpochBackfillResult::Backfilled(block_count) => {
+            status.backfilled_epochs += 1;
+            status.backfilled_blocks += block_count as u64;
+        }
+        EpochBackfillResult::Unavailable => {
+            debug!("Skip trace backfill of unavailable epoch {}", height);
+            status.unavailable_epochs += 1;
+        }
+        EpochBackfillResult::Mismatched => {
+            warn!(
+                "Skip trace backfill of epoch {}: the receipts root doesn't \
+                 match the stored commitment",
+                height
+            );
+            status.mismatched_epochs += 1;
+        }
+    }
+    status.next_epoch = height + 1;
+    consensus
+        .data_man
+        .db_manager
+        .insert_trace_backfill_progress_to_db(status.next_epoch);
+    if status.next_epoch == status.end_epoch {
+        status.finished = true;
+        info!("Trace backfill finished: {:?}", status);
+    } else if (status.next_epoch - status.start_epoch)
+        % PROGRESS_LOG_EPOCH_COUNT
+        == 0
+    {
+        info!("Trace backfill progress: {:?}", status);
+    }
+    true
+}
+
+fn backfill_epoch(
+    consensus: &ConsensusGraph, height: u64,
+) -> EpochBackfillResult {
+    let data_man = &consensus.data_man;
+    let epoch_block_hashes =
+        match data_man.executed_epoch_set_hashes_from_db(height) {
+            Some(hashes) => hashes,
+            None => return EpochBackfillResult::Unavailable,
+        };
+    if epoch_block_hashes
+        .iter()
+        .all(|hash| data_man.db_manager.block_traces_from_db(hash).is_some())
+    {
+        return EpochBackfillResult::AlreadyTraced;
+    }
+    let epoch_hash = *epoch_block_hashes.last().expect("Epoch not empty");
+    let (start_block_number, receipts_root) = match (
+        data_man.get_epoch_execution_context(&epoch_hash),
+        data_man.get_epoch_execution_commitment_with_db(&epoch_hash),
+    ) {
+        (Some(context), Some(commitment)) => {
+            (context.start_block_number, commitment.receipts_root)
+        }
+        _ => return EpochBackfillResult::Unavailable,
+    };
+
+    let reward_execution_info =
+        reward_execution_info(consensus, height, &epoch_hash);
+    match consensus.executor.handler.reexecute_epoch(
+        &epoch_hash,
+        &epoch_block_hashes,
+        start_block_number,
+        &reward_execution_info,
+        None, /* debug_record */
+    ) {
+        Ok(Some(computed)) if computed.receipts_root == receipts_root => {}
+        Ok(Some(_)) => {
+            evict_reexecution_results(consensus, &epoch_block_hashes);
+            return EpochBackfillResult::Mismatched;
+        }
+        Ok(None) => return EpochBackfillResult::Unavailable,
+        Err(e) => {
+            warn!("Failed to re-execute epoch {} for traces: {}", height, e);
+            evict_reexecution_results(consensus, &epoch_block_hashes);
+            return EpochBackfillResult::Unavailable;
+        }
+    }
+    // The traces are kept in memory by the re-execution.
+    for hash in &epoch_block_hashes {
+        match data_man.block_traces_by_hash_with_epoch(
+            hash,
+            &epoch_hash,
+            false, /* update_pivot_assumption */
+            false, /* update_cache */
+        ) {
+            Some(traces) => data_man.insert_block_traces(
+                *hash, traces, epoch_hash, true, /* persistent */
+            ),
+            None => return EpochBackfillResult::Unavailable,
+        }
+    }
+    EpochBackfillResult::Backfilled(epoch_block_hashes.len())
+}
+
+/// The rewards are only available for the epochs on the pivot chain of the
+/// current era.
+fn reward_execution_info(
+    consensus: &ConsensusGraph, height: u64, epoch_hash: &H256,
+) -> Option<RewardExecutionInfo> {
+    let inner = &mut *consensus.inner.write();
+    if height <= inner.cur_era_genesis_height
+        || height >= inner.get_pivot_height()
+    {
+        return None;
+    }
+    let epoch_arena_index = inner.get_pivot_block_arena_index(height);
+    if inner.arena[epoch_arena_index].hash != *epoch_hash {
+        return None;
+    }
+    consensus
+        .executor
+        .get_reward_execution_info(inner, epoch_arena_index)
+}
+
+/// Evicts the receipts and traces cached by a re-execution whose results
+/// don't match, so that they are reloaded from the db.
+fn evict_reexecution_results(
+    consensus: &ConsensusGraph, epoch_block_hashes: &Vec<H256>,
+) {
+    for hash in epoch_block_hashes {
+        consensus
+            .data_man
+            .remove_block_result(hash, false /* remove_db */);
+        consensus
+            .data_man
+            .remove_block_traces(hash, false /* remove_db */);
+    }
+}
+
+use crate::consensus::{
+    consensus_inner::consensus_executor::RewardExecutionInfo, ConsensusGraph,
+};
+use cfx_types::H256;
+use parking_lot::{Condvar, Mutex};
+use std::{
+    cmp::max,
+    sync::{atomic::Ordering, Arc, Weak},
+    thread,
+    time::Duration,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        backfill_next_epoch, init_status, TraceBackfillConfig,
+        TraceBackfillStatus,
+    };
+    use crate::{
+        block_data_manager::BlockDataManager,
+        sync::utils::{initialize_executed_chain, remove_test_db_dir},
+    };
+    use cfx_storage::{state_manager::StateManagerTrait, StorageStateTrait};
+    use cfx_types::H256;
+    use std::time::Duration;
+
+    // The epoch sets are persisted with a delay, so the chain is longer than
+    // the executed epochs.
+    const BLOCK_COUNT: u64 = 10;
+    const EXECUTED_EPOCH_COUNT: u64 = 3;
+
+    fn config(start_epoch: Option<u64>) -> TraceBackfillConfig {
+        TraceBackfillConfig {
+            start_epoch,
+            interval: Duration::from_millis(0),
+        }
+    }
+
+    /// Removes the traces of the epoch at `height`, as if it was executed
+    /// before `executive_trace` is enabled. Return the hash of the epoch.
+    fn remove_epoch_traces(data_man: &BlockDataManager, height: u64) -> H256 {
+        let epoch_block_hashes =
+            data_man.executed_epoch_set_hashes_from_db(height).unwrap();
+        for hash in &epoch_block_hashes {
+            data_man.remove_block_traces(hash, true /* remove_db */);
+        }
+        *epoch_block_hashes.last().unwrap()
+    }
+
+    #[test]
+    fn test_trace_backfill() {
+        const DB_DIR: &str = "./test_trace_backfill.db/";
+        {
+            let (_sync, consensus, data_man) = initialize_executed_chain(
+                DB_DIR,
+                BLOCK_COUNT,
+                EXECUTED_EPOCH_COUNT,
+                true, /* executive_trace */
+            );
+            let mut epochs = Vec::new();
+            for height in 1..=EXECUTED_EPOCH_COUNT {
+                let epoch_hash = *data_man
+                    .executed_epoch_set_hashes_from_db(height)
+                    .unwrap()
+                    .last()
+                    .unwrap();
+                let traces = data_man
+                    .db_manager
+                    .block_traces_from_db(&epoch_hash)
+                    .unwrap();
+                assert_eq!(traces.0, epoch_hash);
+                assert_eq!(remove_epoch_traces(&data_man, height), epoch_hash);
+                assert!(data_man
+                    .db_manager
+                    .block_traces_from_db(&epoch_hash)
+                    .is_none());
+                epochs.push((epoch_hash, traces.1));
+            }
+
+            *consensus.trace_backfill_status.lock() =
+                Some(TraceBackfillStatus {
+                    start_epoch: 1,
+                    end_epoch: EXECUTED_EPOCH_COUNT + 1,
+                    next_epoch: 1,
+                    ..Default::default()
+                });
+            for _ in 1..=EXECUTED_EPOCH_COUNT {
+                assert!(backfill_next_epoch(&consensus));
+            }
+            assert!(!backfill_next_epoch(&consensus));
+            let status = consensus.trace_backfill_status().unwrap();
+            assert_eq!(status.backfilled_epochs, EXECUTED_EPOCH_COUNT);
+            assert_eq!(status.backfilled_blocks, EXECUTED_EPOCH_COUNT);
+            assert_eq!(status.unavailable_epochs, 0);
+            assert_eq!(status.mismatched_epochs, 0);
+            assert!(status.finished);
+
+            for (epoch_hash, traces) in epochs {
+                let backfilled = data_man
+                    .db_manager
+                    .block_traces_from_db(&epoch_hash)
+                    .unwrap();
+                assert_eq!(backfilled.0, epoch_hash);
+                assert_eq!(backfilled.1, traces);
+
+                // The state committed by the re-execution is the stored one.
+                let commitment = data_man
+                    .get_epoch_execution_commitment_with_db(&epoch_hash)
+                    .unwrap();
+                let state = data_man
+                    .storage_manager
+                    .get_state_no_commit(
+                        data_man.get_state_readonly_index(&epoch_hash).unwrap(),
+                        false, /* try_open */
+                    )
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(
+                    state.get_state_root().unwrap(),
+                    commitment.state_root_with_aux_info
+                );
+            }
+        }
+        remove_test_db_dir(DB_DIR);
+    }
+
+    #[test]
+    fn test_resume_trace_backfill() {
+        const DB_DIR: &str = "./test_resume_trace_backfill.db/";
+        {
+            let (_sync, consensus, data_man) = initialize_executed_chain(
+                DB_DIR,
+                BLOCK_COUNT,
+                EXECUTED_EPOCH_COUNT,
+                true, /* executive_trace */
+            );
+            init_status(&consensus, &config(None));
+            let status = consensus.trace_backfill_status().unwrap();
+            assert_eq!((status.start_epoch, status.next_epoch), (1, 1));
+
+            remove_epoch_traces(&data_man, 2);
+            consensus
+                .trace_backfill_status
+                .lock()
+                .as_mut()
+                .unwrap()
+                .end_epoch = EXECUTED_EPOCH_COUNT + 1;
+            assert!(backfill_next_epoch(&consensus));
+            assert!(backfill_next_epoch(&consensus));
+            let status = consensus.trace_backfill_status().unwrap();
+            // The traces of epoch 1 are not removed.
+            assert_eq!(status.backfilled_epochs, 1);
+            assert_eq!(
+                data_man.db_manager.trace_backfill_progress_from_db(),
+                Some(3)
+            );
+
+            // The backfill resumes from the persisted progress after a
+            // restart, unless the start epoch is configured.
+            init_status(&consensus, &config(None));
+            let status = consensus.trace_backfill_status().unwrap();
+            assert_eq!((status.start_epoch, status.next_epoch), (3, 3));
+            init_status(&consensus, &config(Some(2)));
+            let status = consensus.trace_backfill_status().unwrap();
+            assert_eq!((status.start_epoch, status.next_epoch), (2, 2));
+        }
+        remove_test_db_dir(DB_DIR);
+    }
+
+    #[test]
+    fn test_trace_backfill_mismatch() {
+        const DB_DIR: &str = "./test_trace_backfill_mismatch.db/";
+        {
+            let (_sync, consensus, data_man) = initialize_executed_chain(
+                DB_DIR,
+                BLOCK_COUNT,
+                EXECUTED_EPOCH_COUNT,
+                true, /* executive_trace */
+            );
+            let height = 2;
+            let epoch_hash = remove_epoch_traces(&data_man, height);
+            let commitment = data_man
+                .get_epoch_execution_commitment_with_db(&epoch_hash)
+                .unwrap();
+            data_man.insert_epoch_execution_commitment(
+                epoch_hash,
+                commitment.state_root_with_aux_info,
+                H256::repeat_byte(1), /* receipts_root */
+                commitment.logs_bloom_hash,
+            );
+            // Without the stored receipts, the receipts cached by the
+            // re-execution would be returned if they weren't evicted.
+            data_man
+                .remove_block_result(&epoch_hash, true /* remove_db */);
+
+            *consensus.trace_backfill_status.lock() =
+                Some(TraceBackfillStatus {
+                    start_epoch: height,
+                    end_epoch: height + 1,
+                    next_epoch: height,
+                    ..Default::default()
+                });
+            assert!(backfill_next_epoch(&consensus));
+            let status = consensus.trace_backfill_status().unwrap();
+            assert_eq!(status.mismatched_epochs, 1);
+            assert_eq!(status.backfilled_epochs, 0);
+            assert!(status.finished);
+            assert!(!backfill_next_epoch(&consensus));
+
+            assert!(data_man
+                .block_execution_result_by_hash_with_epoch(
+                    &epoch_hash,
+                    &epoch_hash,
+                    false, /* update_pivot_assumption */
+                    false, /* update_cache */
+                )
+                .is_none());
+            assert!(data_man
+                .block_traces_by_hash_with_epoch(
+                    &epoch_hash,
+                    &epoch_hash,
+                    false, /* update_pivot_assumption */
+                    false, /* update_cache */
+                )
+                .is_none());
+            assert!(data_man
+                .db_manager
+                .block_traces_from_db(&epoch_hash)
+                .is_none());
+        }
+        remove_test_db_dir(DB_DIR);
+    }
+}
@@ -38,7 +38,7 @@ use cfx_types::H256;
 use io::TimerToken;
 use network::{
     node_table::NodeId, service::ProtocolVersion, NetworkContext,
-    NetworkProtocolHandler,
+    NetworkProtocolHandler, NODE_TAG_LIGHT,
 };
 use parking_lot::RwLock;
 use rlp::Rlp;
@@ -947,6 +947,8 @@ impl NetworkProtocolHandler for Handler {
     }
 
     fn initialize(&self, io: &dyn NetworkContext) {
+        io.set_local_node_type(NODE_TAG_LIGHT);
+
         io.register_timer(SYNC_TIMER, *SYNC_PERIOD)
             .expect("Error registering sync timer");
 
@@ -3,7 +3,13 @@
 // See http://www.gnu.org/licenses/
 
 use crate::{
-    consensus::SharedConsensusGraph,
+    consensus::{
+        fee_history::{
+            suggest_gas_price, validate_percentiles, EpochGasPriceSample,
+            FeeHistory, FeeHistoryCache, GasPriceUrgency,
+        },
+        SharedConsensusGraph,
+    },
     light_protocol::{
         common::{FullPeerFilter, LedgerInfo},
         handler::sync::TxInfoValidated,
@@ -25,10 +31,12 @@ use cfx_parameters::{
     },
     light::{
         GAS_PRICE_BATCH_SIZE, GAS_PRICE_BLOCK_SAMPLE_SIZE,
-        GAS_PRICE_TRANSACTION_SAMPLE_SIZE, LOG_FILTERING_LOOKAHEAD,
-        MAX_POLL_TIME, TRANSACTION_COUNT_PER_BLOCK_WATER_LINE_LOW,
+        GAS_PRICE_SUGGESTION_EPOCH_COUNT, GAS_PRICE_TRANSACTION_SAMPLE_SIZE,
+        LOG_FILTERING_LOOKAHEAD, MAX_POLL_TIME,
+        TRANSACTION_COUNT_PER_BLOCK_WATER_LINE_LOW,
         TRANSACTION_COUNT_PER_BLOCK_WATER_LINE_MEDIUM,
     },
+    rpc::{FEE_HISTORY_CACHE_EPOCH_COUNT, MAX_FEE_HISTORY_EPOCH_COUNT},
 };
 use cfx_statedb::{ACCUMULATE_INTEREST_RATE_KEY, INTEREST_RATE_KEY};
 use cfx_types::{
@@ -40,6 +48,7 @@ use futures::{
     stream, FutureExt, StreamExt, TryFutureExt, TryStreamExt,
 };
 use network::{service::ProtocolVersion, NetworkContext, NetworkService};
+use parking_lot::Mutex;
 use primitives::{
     filter::{FilterError, LogFilter},
     log_entry::{LocalizedLogEntry, LogEntry},
@@ -98,6 +107,9 @@ pub struct QueryService {
 
     // shared network service
     network: Arc<NetworkService>,
+
+    // gas price samples of recently queried epochs
+    fee_history_cache: Mutex<FeeHistoryCache>,
 }
 
 impl QueryService {
@@ -122,6 +134,9 @@ impl QueryService {
             handler,
             ledger,
             network,
+            fee_history_cache: Mutex::new(FeeHistoryCache::new(
+                FEE_HISTORY_CACHE_EPOCH_COUNT,
+            )),
         }
     }
 
@@ -375,6 +390,117 @@ impl QueryService {
         }
     }
 
+    async fn epoch_gas_price_sample(
+        &self, epoch: u64,
+    ) -> Result<Arc<EpochGasPriceSample>, Error> {
+        let hashes = self
+            .consensus
+            .as_any()
+            .downcast_ref::<ConsensusGraph>()
+            .expect("downcast should succeed")
+            .inner
+            .read()
+            .block_hashes_by_epoch(epoch)?;
+
+        let pivot_hash = match hashes.last() {
+            Some(hash) => *hash,
+            None => bail!(ErrorKind::InternalError(format!(
+                "Epoch {} has no blocks",
+                epoch
+            ))),
+        };
+
+        // release the lock before awaiting on the block retrieval
+        let cached = self.fee_history_cache.lock().get(epoch, &pivot_hash);
+
+        if let Some(sample) = cached {
+            return Ok(sample);
+        }
+
+        // retrieve blocks in batches
+        let mut stream = stream::iter(hashes)
+            .map(|h| {
+                async move { self.retrieve_block(h).await.map(move |b| (h, b)) }
+            })
+            .buffered(GAS_PRICE_BATCH_SIZE);
+
+        let mut blocks = vec![];
+
+        while let Some(item) = stream.try_next().await? {
+            match item {
+                (_, Some(b)) => blocks.push(b),
+                (hash, None) => {
+                    bail!(ErrorKind::InternalError(format!(
+                        "Block {:?} not found during fee history sampling",
+                        hash
+                    )));
+                }
+            }
+        }
+
+        let sample = Arc::new(EpochGasPriceSample::from_blocks(
+            epoch,
+            pivot_hash,
+            blocks.iter(),
+        ));
+        self.fee_history_cache.lock().insert(sample.clone());
+        Ok(sample)
+    }
+
+    pub async fn fee_history(
+        &self, epoch_count: u64, newest_epoch: EpochNumber,
+        percentiles: Vec<f64>,
+    ) -> Result<FeeHistory, Error>
+    {
+        trace!(
+            "fee_history epoch_count = {:?}, newest_epoch = {:?}, percentiles = {:?}",
+            epoch_count,
+            newest_epoch,
+            percentiles
+        );
+
+        validate_percentiles(&percentiles)?;
+
+        if epoch_count == 0 || epoch_count > MAX_FEE_HISTORY_EPOCH_COUNT {
+            bail!(format!(
+                "Invalid epoch count {}, expected a value within [1, {}]",
+                epoch_count, MAX_FEE_HISTORY_EPOCH_COUNT
+            ));
+        }
+
+        let newest_epoch = self.get_height_from_epoch_number(newest_epoch)?;
+        let oldest_epoch = (newest_epoch + 1).saturating_sub(epoch_count);
+
+        let mut samples = vec![];
+        for epoch in oldest_epoch..=newest_epoch {
+            samples.push(self.epoch_gas_price_sample(epoch).await?);
+        }
+
+        Ok(FeeHistory::from_samples(
+            oldest_epoch,
+            &samples,
+            &percentiles,
+        ))
+    }
+
+    pub async fn suggest_gas_price(
+        &self, urgency: GasPriceUrgency,
+    ) -> Result<Option<U256>, Error> {
+        trace!("suggest_gas_price urgency = {:?}", urgency);
+
+        let newest_epoch = self.get_latest_verifiable_epoch_number()?;
+        let oldest_epoch =
+            (newest_epoch + 1).saturating_sub(GAS_PRICE_SUGGESTION_EPOCH_COUNT);
+
+        let mut samples = vec![];
+        for epoch in oldest_epoch..=newest_epoch {
+            samples.push(self.epoch_gas_price_sample(epoch).await?);
+        }
+
+        // light nodes do not have a transaction pool to sample from
+        Ok(suggest_gas_price(&samples, None, urgency))
+    }
+
     fn account_key(address: &H160) -> Vec<u8> {
         StorageKey::new_account_key(&address).to_key_bytes()
     }
@@ -24,6 +24,16 @@ error_chain! {
             description("Error as jsonrpc error InvalidParam.")
             display("Jsonrpc error InvalidParam {}: {}.", param, details)
         }
+
+        SignerRejected(details: String) {
+            description("The external signer refused to sign the request.")
+            display("External signer rejected the request: {}.", details)
+        }
+
+        SignerUnavailable(details: String) {
+            description("The external signer failed to sign the request.")
+            display("External signer failed to sign the request: {}.", details)
+        }
     }
 }
 
@@ -63,9 +73,26 @@ pub fn account_result_to_rpc_result<T>(
     }
 }
 
+pub fn sign_result_to_rpc_result<T>(
+    param: &str, result: std::result::Result<T, SignError>,
+) -> Result<T> {
+    match result {
+        Ok(t) => Ok(t),
+        Err(SignError::TypedData(_)) => invalid_params_check(param, result),
+        Err(SignError::External(SignerError::Rejected(reason))) => {
+            Err(ErrorKind::SignerRejected(reason).into())
+        }
+        Err(SignError::External(e)) => {
+            Err(ErrorKind::SignerUnavailable(format!("{}", e)).into())
+        }
+        Err(e) => Err(format!("Failed to sign: {}", e).into()),
+    }
+}
+
 use crate::light_protocol::Error as LightProtocolError;
 use cfx_statedb::Error as StateDbError;
 use cfx_storage::Error as StorageError;
+use cfxcore_accounts::{SignError, SignerError};
 use jsonrpc_core::Error as JsonRpcError;
 use primitives::{account::AccountError, filter::FilterError};
 use rlp::DecoderError;
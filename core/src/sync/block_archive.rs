@@ -0,0 +1,307 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! Export and import of the blocks of a range of epochs, to move block data
+//! between nodes without the network sync.
+//!
+//! The export is a stream of block rlps without any other framing. The blocks
+//! of each epoch are written in the consensus order: the skipped blocks of
+//! the epoch first, then the executed blocks ending with the pivot block, so
+//! that every block comes after its parent and referees.
+//!
+//! The import inserts the blocks into the synchronization graph with full
+//! verification, the same way as the blocks received from peers, and the
+//! consensus graph processes them in the normal phase.
+
+use crate::{
+    block_data_manager::db_manager::DBManager,
+    sync::{Error, ErrorKind, SynchronizationGraph},
+};
+use cfx_parameters::sync::MAX_PACKET_SIZE;
+use primitives::Block;
+use rlp::{Encodable, Rlp};
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, ErrorKind as IoErrorKind, Read, Write},
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// The number of blocks between two progress reports of the import.
+const IMPORT_PROGRESS_INTERVAL: u64 = 1000;
+/// The maximum size of a block rlp in the stream. A block larger than a sync
+/// packet can't be received from peers either.
+const MAX_BLOCK_RLP_SIZE: usize = MAX_PACKET_SIZE;
+
+#[derive(Clone, Debug, Default)]
+pub struct BlockImportProgress {
+    pub imported: u64,
+    /// The blocks which are already in the graph.
+    pub existing: u64,
+    pub invalid: u64,
+    pub elapsed: Duration,
+}
+
+impl BlockImportProgress {
+    pub fn processed(&self) -> u64 {
+        self.imported + self.existing + self.invalid
+    }
+
+    pub fn blocks_per_second(&self) -> f64 {
+        self.processed() as f64 / self.elapsed.as_secs_f64().max(1e-3)
+    }
+}
+
+/// Exports the blocks of the epochs in [from_epoch, to_epoch] to the file at
+/// `path`. Returns the number of exported blocks.
+pub fn export_blocks(
+    db_manager: &DBManager, from_epoch: u64, to_epoch: u64, path: &Path,
+) -> Result<u64, Error> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    let mut exported = 0;
+    for height in from_epoch..=to_epoch {
+        let executed = db_manager
+            .executed_epoch_set_hashes_from_db(height)
+            .ok_or_else(|| {
+                ErrorKind::InternalError(format!(
+                    "epoch {} not found in the db",
+                    height
+                ))
+            })?;
+        let skipped = db_manager
+            .skipped_epoch_set_hashes_from_db(height)
+            .unwrap_or_default();
+        for hash in skipped.iter().chain(executed.iter()) {
+            let block = db_manager.block_from_db(hash).ok_or_else(|| {
+                ErrorKind::InternalError(format!(
+                    "block {:?} of epoch {} not found in the db",
+                    hash, height
+                ))
+            })?;
+            writer.write_all(&block.rlp_bytes())?;
+            exported += 1;
+        }
+    }
+    writer.flush()?;
+    Ok(exported)
+}
+
+/// Imports the blocks in the file at `path` into the graphs of a node which
+/// isn't connected to the network, and waits for the consensus graph to
+/// process them. `on_progress` is called every `IMPORT_PROGRESS_INTERVAL`
+/// blocks.
+pub fn import_blocks<F: FnMut(&BlockImportProgress)>(
+    sync_graph: &SynchronizationGraph, path: &Path, mut on_progress: F,
+) -> Result<BlockImportProgress, Error> {
+    let mut reader = BufReader::new(File::open(path)?);
+    sync_graph.recover_graph_offline();
+
+    let start = Instant::now();
+    let mut progress = BlockImportProgress::default();
+    while let Some(block_rlp) = read_rlp_list(&mut reader)? {
+        let block: Block = Rlp::new(&block_rlp).as_val()?;
+        if sync_graph.contains_block(&block.hash()) {
+            progress.existing += 1;
+        } else if insert_block(sync_graph, block)? {
+            progress.imported += 1;
+        } else {
+            progress.invalid += 1;
+        }
+        if progress.processed() % IMPORT_PROGRESS_INTERVAL == 0 {
+            progress.elapsed = start.elapsed();
+            on_progress(&progress);
+        }
+    }
+    while sync_graph.is_consensus_worker_busy() {
+        thread::sleep(Duration::from_millis(100));
+    }
+    progress.elapsed = start.elapsed();
+    Ok(progress)
+}
+
+/// Returns whether the block is valid.
+fn insert_block(
+    sync_graph: &SynchronizationGraph, mut block: Block,
+) -> Result<bool, Error> {
+    if let Err(e) = sync_graph.data_man.recover_block(&mut block) {
+        warn!("Failed to recover block {:?}: {:?}", block.hash(), e);
+        return Ok(false);
+    }
+    let (insert_result, _) = sync_graph.insert_block_header(
+        &mut block.block_header,
+        true,  // need_to_verify
+        false, // bench_mode
+        false, // insert_into_consensus
+        true,  // persistent
+    );
+    if !insert_result.should_process_body() {
+        return Ok(!insert_result.is_invalid());
+    }
+    Ok(sync_graph
+        .insert_block(
+            block, true,  /* need_to_verify */
+            true,  /* persistent */
+            false, /* recover_from_db */
+        )
+        .is_valid())
+}
+
+/// Reads the next rlp list in the stream. Returns None at the end of the
+/// stream.
+fn read_rlp_list(reader: &mut dyn Read) -> Result<Option<Vec<u8>>, Error> {
+    let mut prefix = [0u8; 1];
+    match reader.read_exact(&mut prefix) {
+        Ok(()) => {}
+        Err(e) if e.kind() == IoErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let mut item = prefix.to_vec();
+    let payload_len = match prefix[0] {
+        0xc0..=0xf7 => (prefix[0] - 0xc0) as usize,
+        0xf8..=0xff => {
+            let mut len_bytes = vec![0u8; (prefix[0] - 0xf7) as usize];
+            reader.read_exact(&mut len_bytes)?;
+            item.extend_from_slice(&len_bytes);
+            // Stop before the length overflows.
+            let mut len = 0usize;
+            for byte in &len_bytes {
+                if len > MAX_BLOCK_RLP_SIZE {
+                    break;
+                }
+                len = (len << 8) | *byte as usize;
+            }
+            len
+        }
+        _ => bail!(ErrorKind::InternalError(
+            "the block stream is corrupted".into()
+        )),
+    };
+    if payload_len > MAX_BLOCK_RLP_SIZE {
+        bail!(ErrorKind::InternalError(format!(
+            "the block rlp size is larger than {} in the block stream",
+            MAX_BLOCK_RLP_SIZE
+        )));
+    }
+    let header_len = item.len();
+    item.resize(header_len + payload_len, 0);
+    reader.read_exact(&mut item[header_len..])?;
+    Ok(Some(item))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{export_blocks, read_rlp_list, MAX_BLOCK_RLP_SIZE};
+    use crate::{
+        block_data_manager::db_manager::DBManager, pow::PowComputer,
+        sync::utils::create_simple_block_impl,
+    };
+    use cfx_types::{H256, U256};
+    use primitives::Block;
+    use rlp::Rlp;
+    use std::{env, fs, io::BufReader, process, sync::Arc};
+
+    #[test]
+    fn test_export_blocks() {
+        let dir = env::temp_dir()
+            .join(format!("test_export_blocks_{}", process::id()));
+        fs::remove_dir_all(&dir).ok();
+        let db_manager = DBManager::new_from_sqlite(
+            &dir.join("db"),
+            Arc::new(PowComputer::new(true)),
+        );
+        // Each epoch has a pivot block and a skipped block referenced by it.
+        let mut expected = vec![];
+        let mut parent_hash = H256::zero();
+        for height in 0..3u64 {
+            let (skipped_hash, skipped) = create_simple_block_impl(
+                parent_hash,
+                vec![],
+                height,
+                U256::from(2 * height),
+                U256::from(10),
+                1,
+                false,
+            );
+            let (pivot_hash, pivot) = create_simple_block_impl(
+                parent_hash,
+                vec![skipped_hash],
+                height,
+                U256::from(2 * height + 1),
+                U256::from(10),
+                1,
+                false,
+            );
+            for block in &[&skipped, &pivot] {
+                db_manager.insert_block_header_to_db(&block.block_header);
+                db_manager.insert_block_body_to_db(block);
+            }
+            db_manager.insert_skipped_epoch_set_hashes_to_db(
+                height,
+                &vec![skipped_hash],
+            );
+            db_manager.insert_executed_epoch_set_hashes_to_db(
+                height,
+                &vec![pivot_hash],
+            );
+            expected.push(skipped_hash);
+            expected.push(pivot_hash);
+            parent_hash = pivot_hash;
+        }
+
+        let path = dir.join("blocks");
+        assert_eq!(export_blocks(&db_manager, 1, 2, &path).unwrap(), 4);
+        let mut reader = BufReader::new(fs::File::open(&path).unwrap());
+        let mut exported = vec![];
+        while let Some(block_rlp) = read_rlp_list(&mut reader).unwrap() {
+            let block: Block = Rlp::new(&block_rlp).as_val().unwrap();
+            exported.push(block.hash());
+        }
+        assert_eq!(exported, expected[2..].to_vec());
+
+        // The epochs must have been executed.
+        assert!(export_blocks(&db_manager, 2, 3, &path).is_err());
+        drop(db_manager);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn read(mut stream: &[u8]) -> Option<Vec<u8>> {
+        read_rlp_list(&mut stream).unwrap()
+    }
+
+    #[test]
+    fn test_read_rlp_list() {
+        assert_eq!(read(&[]), None);
+        assert_eq!(read(&[0xc0]), Some(vec![0xc0]));
+        // The rest of the stream is left to the next read.
+        let mut stream: &[u8] = &[0xc2, 1, 2, 0xc1, 3];
+        assert_eq!(read_rlp_list(&mut stream).unwrap(), Some(vec![0xc2, 1, 2]));
+        assert_eq!(read_rlp_list(&mut stream).unwrap(), Some(vec![0xc1, 3]));
+        assert_eq!(read_rlp_list(&mut stream).unwrap(), None);
+
+        let mut long_list = vec![0xf9, 0x01, 0x00];
+        long_list.resize(3 + 0x100, 0x80);
+        assert_eq!(read(&long_list), Some(long_list.clone()));
+        let mut long_list = vec![0xf8, 56];
+        long_list.resize(2 + 56, 0x80);
+        assert_eq!(read(&long_list), Some(long_list.clone()));
+    }
+
+    #[test]
+    fn test_read_corrupted_rlp_list() {
+        // Truncated payloads.
+        assert!(read_rlp_list(&mut &[0xc2, 1][..]).is_err());
+        assert!(read_rlp_list(&mut &[0xf8, 56, 0x80][..]).is_err());
+        // Truncated length.
+        assert!(read_rlp_list(&mut &[0xf9, 0x01][..]).is_err());
+        // Not a list.
+        assert!(read_rlp_list(&mut &[0x80][..]).is_err());
+        // Too large.
+        let len = (MAX_BLOCK_RLP_SIZE + 1).to_be_bytes();
+        let mut stream = vec![0xf7 + len.len() as u8];
+        stream.extend_from_slice(&len);
+        assert!(read_rlp_list(&mut stream.as_slice()).is_err());
+        assert!(read_rlp_list(&mut &[0xff; 9][..]).is_err());
+    }
+}
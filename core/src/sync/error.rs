@@ -86,6 +86,11 @@ error_chain! {
             display("invalid snapshot chunk: {}", reason),
         }
 
+        InvalidSnapshotArchive(reason: String) {
+            description("invalid snapshot archive"),
+            display("invalid snapshot archive: {}", reason),
+        }
+
         // FIXME: This works as a compatible fix when the snapshot provider cannot serve the chunk.
         // We should add another reply like `UnsupportedSnapshot` and remove this.
         EmptySnapshotChunk {
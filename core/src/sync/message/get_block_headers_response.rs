@@ -66,6 +66,11 @@ impl Handleable for GetBlockHeadersResponse {
         }
 
         let req = ctx.match_request(self.request_id)?;
+        ctx.manager.request_manager.download_received(
+            &ctx.node_id,
+            &req,
+            self.headers.len(),
+        );
         let delay = req.delay;
         let req = req.downcast_ref::<GetBlockHeaders>(
             ctx.io,
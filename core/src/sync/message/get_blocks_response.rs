@@ -55,6 +55,11 @@ impl Handleable for GetBlocksResponse {
         }
 
         let req = ctx.match_request(self.request_id)?;
+        ctx.manager.request_manager.download_received(
+            &ctx.node_id,
+            &req,
+            self.blocks.len(),
+        );
         let delay = req.delay;
         let requested_blocks: HashSet<H256> = req
             .downcast_ref::<GetBlocks>(ctx.io, &ctx.manager.request_manager)?
@@ -96,6 +101,11 @@ impl Handleable for GetBlocksWithPublicResponse {
                 .collect::<Vec<H256>>()
         );
         let req = ctx.match_request(self.request_id)?;
+        ctx.manager.request_manager.download_received(
+            &ctx.node_id,
+            &req,
+            self.blocks.len(),
+        );
         let delay = req.delay;
         let req_hashes: HashSet<H256> = if let Ok(req) = req
             .downcast_ref::<GetCompactBlocks>(
@@ -0,0 +1,263 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! Scheduling of header and block downloads in catch-up.
+//!
+//! The throughput and response time of each peer are measured from its
+//! responses, and the batch size of the requests sent to a peer is the number
+//! of items it is expected to deliver within the target response time. The
+//! items to download are spread over the peers with the highest throughput,
+//! with at most `MAX_INFLIGHT_BATCHES` batches in flight to each peer, so that
+//! the download proceeds from many peers in parallel instead of waiting on a
+//! few slow ones. A peer that times out or fails to deliver has its throughput
+//! halved, and the missing items are reassigned to the best other peer.
+
+use malloc_size_of_derive::MallocSizeOf as DeriveMallocSizeOf;
+use network::node_table::NodeId;
+use parking_lot::Mutex;
+use std::{cmp::Ordering, collections::HashMap, time::Duration};
+
+/// Number of batches that could be in flight to a peer at the same time.
+const MAX_INFLIGHT_BATCHES: usize = 2;
+/// Weight of the latest sample in the moving averages.
+const SAMPLE_WEIGHT: f64 = 0.3;
+
+#[derive(Debug, Default, DeriveMallocSizeOf)]
+struct PeerDownloadStat {
+    /// Moving average of the items received per second, `None` before the
+    /// first response.
+    throughput: Option<f64>,
+    /// Moving average of the response time in seconds.
+    latency: Option<f64>,
+    /// Number of items requested but not responded yet.
+    inflight: usize,
+}
+
+impl PeerDownloadStat {
+    /// Halves the throughput of a request timed out or responded with nothing.
+    fn on_failure(&mut self) {
+        if let Some(throughput) = self.throughput.as_mut() {
+            *throughput /= 2.0;
+        }
+    }
+}
+
+#[derive(DeriveMallocSizeOf)]
+pub struct DownloadScheduler {
+    max_batch_size: usize,
+    target_response_time: f64,
+    peers: Mutex<HashMap<NodeId, PeerDownloadStat>>,
+}
+
+impl DownloadScheduler {
+    pub fn new(max_batch_size: usize, target_response_time: Duration) -> Self {
+        DownloadScheduler {
+            max_batch_size: max_batch_size.max(1),
+            target_response_time: target_response_time.as_secs_f64().max(0.001),
+            peers: Default::default(),
+        }
+    }
+
+    /// Batch size of peers not measured yet.
+    fn initial_batch_size(&self) -> usize { (self.max_batch_size / 4).max(1) }
+
+    fn estimated_throughput(&self, stat: Option<&PeerDownloadStat>) -> f64 {
+        stat.and_then(|stat| stat.throughput).unwrap_or_else(|| {
+            self.initial_batch_size() as f64 / self.target_response_time
+        })
+    }
+
+    fn batch_size_of(&self, stat: Option<&PeerDownloadStat>) -> usize {
+        match stat.and_then(|stat| stat.throughput) {
+            Some(throughput) => ((throughput * self.target_response_time)
+                as usize)
+                .max(1)
+                .min(self.max_batch_size),
+            None => self.initial_batch_size(),
+        }
+    }
+
+    /// Returns the number of items to request from `peer` at a time.
+    pub fn batch_size(&self, peer: &NodeId) -> usize {
+        self.batch_size_of(self.peers.lock().get(peer))
+    }
+
+    /// Ranks `peers` by throughput and then by response time, the best first.
+    fn rank(
+        &self, peers: &[NodeId], stats: &HashMap<NodeId, PeerDownloadStat>,
+    ) -> Vec<NodeId> {
+        let mut ranked: Vec<(NodeId, f64, f64)> = peers
+            .iter()
+            .map(|peer| {
+                let stat = stats.get(peer);
+                let latency = stat.and_then(|stat| stat.latency);
+                (
+                    *peer,
+                    self.estimated_throughput(stat),
+                    latency.unwrap_or(self.target_response_time),
+                )
+            })
+            .collect();
+        ranked.sort_by(|a, b| {
+            b.1.partial_cmp(&a.1)
+                .unwrap_or(Ordering::Equal)
+                .then(a.2.partial_cmp(&b.2).unwrap_or(Ordering::Equal))
+        });
+        ranked.into_iter().map(|(peer, _, _)| peer).collect()
+    }
+
+    /// Assigns `items` to `peers` in batches, one batch to each peer in turn
+    /// from the best peer, until all peers have `MAX_INFLIGHT_BATCHES` batches
+    /// in flight. Returns the batches and the items left unassigned.
+    pub fn schedule<T>(
+        &self, peers: &[NodeId], mut items: Vec<T>,
+    ) -> (Vec<(NodeId, Vec<T>)>, Vec<T>) {
+        let stats = self.peers.lock();
+        let ranked = self.rank(peers, &stats);
+        let mut inflight: Vec<usize> = ranked
+            .iter()
+            .map(|peer| stats.get(peer).map_or(0, |stat| stat.inflight))
+            .collect();
+
+        let mut batches = Vec::new();
+        loop {
+            let mut assigned = false;
+            for (i, peer) in ranked.iter().enumerate() {
+                if items.is_empty() {
+                    break;
+                }
+                let batch_size = self.batch_size_of(stats.get(peer));
+                if inflight[i] >= batch_size * MAX_INFLIGHT_BATCHES {
+                    continue;
+                }
+                let n = batch_size.min(items.len());
+                let rest = items.split_off(n);
+                batches.push((*peer, std::mem::replace(&mut items, rest)));
+                inflight[i] += n;
+                assigned = true;
+            }
+            if !assigned || items.is_empty() {
+                break;
+            }
+        }
+        (batches, items)
+    }
+
+    /// Returns the best peer among `candidates` that is not fully loaded.
+    pub fn select_peer(&self, candidates: &[NodeId]) -> Option<NodeId> {
+        let stats = self.peers.lock();
+        self.rank(candidates, &stats).into_iter().find(|peer| {
+            let stat = stats.get(peer);
+            stat.map_or(0, |stat| stat.inflight)
+                < self.batch_size_of(stat) * MAX_INFLIGHT_BATCHES
+        })
+    }
+
+    pub fn on_request_sent(&self, peer: &NodeId, items: usize) {
+        self.peers.lock().entry(*peer).or_default().inflight += items;
+    }
+
+    /// Updates the stat of `peer` with a response of `received` items out of
+    /// the `requested` items, `elapsed` after the request is sent.
+    pub fn on_response(
+        &self, peer: &NodeId, requested: usize, received: usize,
+        elapsed: Duration,
+    )
+    {
+        let mut peers = self.peers.lock();
+        let stat = peers.entry(*peer).or_default();
+        stat.inflight = stat.inflight.saturating_sub(requested);
+        if received == 0 {
+            stat.on_failure();
+            return;
+        }
+
+        let elapsed = elapsed.as_secs_f64().max(0.001);
+        let throughput = received as f64 / elapsed;
+        stat.throughput = Some(match stat.throughput {
+            Some(t) => t + SAMPLE_WEIGHT * (throughput - t),
+            None => throughput,
+        });
+        stat.latency = Some(match stat.latency {
+            Some(l) => l + SAMPLE_WEIGHT * (elapsed - l),
+            None => elapsed,
+        });
+    }
+
+    /// Updates the stat of `peer` whose request of `requested` items has timed
+    /// out.
+    pub fn on_timeout(&self, peer: &NodeId, requested: usize) {
+        if let Some(stat) = self.peers.lock().get_mut(peer) {
+            stat.inflight = stat.inflight.saturating_sub(requested);
+            stat.on_failure();
+        }
+    }
+
+    pub fn remove_peer(&self, peer: &NodeId) { self.peers.lock().remove(peer); }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DownloadScheduler;
+    use network::node_table::NodeId;
+    use std::time::Duration;
+
+    #[test]
+    fn test_adaptive_batch_size() {
+        let scheduler = DownloadScheduler::new(128, Duration::from_secs(2));
+        let peer = NodeId::random();
+        assert_eq!(scheduler.batch_size(&peer), 32);
+
+        // 32 items in 0.5s, i.e. 64 items per second.
+        scheduler.on_request_sent(&peer, 32);
+        scheduler.on_response(&peer, 32, 32, Duration::from_millis(500));
+        assert_eq!(scheduler.batch_size(&peer), 128);
+
+        // Halved on each failure.
+        scheduler.on_request_sent(&peer, 128);
+        scheduler.on_timeout(&peer, 128);
+        assert_eq!(scheduler.batch_size(&peer), 64);
+        scheduler.on_request_sent(&peer, 64);
+        scheduler.on_response(&peer, 64, 0, Duration::from_millis(100));
+        assert_eq!(scheduler.batch_size(&peer), 32);
+    }
+
+    #[test]
+    fn test_schedule() {
+        let scheduler = DownloadScheduler::new(128, Duration::from_secs(2));
+        let (fast, slow, new) =
+            (NodeId::random(), NodeId::random(), NodeId::random());
+        scheduler.on_request_sent(&fast, 100);
+        scheduler.on_response(&fast, 100, 100, Duration::from_secs(1));
+        scheduler.on_request_sent(&slow, 10);
+        scheduler.on_response(&slow, 10, 10, Duration::from_secs(2));
+
+        // fast: batches of 128, slow: batches of 10, new: batches of 32.
+        let items: Vec<usize> = (0..400).collect();
+        let (batches, rest) = scheduler.schedule(&[slow, new, fast], items);
+        let sizes: Vec<(NodeId, usize)> =
+            batches.iter().map(|(peer, b)| (*peer, b.len())).collect();
+        assert_eq!(
+            sizes,
+            vec![
+                (fast, 128),
+                (new, 32),
+                (slow, 10),
+                (fast, 128),
+                (new, 32),
+                (slow, 10)
+            ]
+        );
+        assert_eq!(batches[0].1[0], 0);
+        assert_eq!(batches[1].1[0], 128);
+        assert_eq!(rest.len(), 60);
+        assert_eq!(rest[0], 340);
+
+        // Fully loaded peers are skipped.
+        scheduler.on_request_sent(&fast, 256);
+        assert_eq!(scheduler.select_peer(&[slow, fast]), Some(slow));
+        scheduler.remove_peer(&fast);
+        assert_eq!(scheduler.select_peer(&[slow, fast]), Some(fast));
+    }
+}
@@ -7,6 +7,7 @@ use super::{
     synchronization_state::SynchronizationState,
 };
 use crate::{
+    message::MsgId,
     sync::{
         message::{
             msgid, GetBlockHashesByEpoch, GetBlockHeaders, GetBlockTxn,
@@ -20,8 +21,11 @@ use crate::{
     },
     NodeType,
 };
-use cfx_parameters::sync::REQUEST_START_WAITING_TIME;
+use cfx_parameters::sync::{
+    MAX_BLOCKS_TO_SEND, MAX_HEADERS_TO_SEND, REQUEST_START_WAITING_TIME,
+};
 use cfx_types::H256;
+pub use download_scheduler::DownloadScheduler;
 use malloc_size_of::{MallocSizeOf, MallocSizeOfOps};
 use malloc_size_of_derive::MallocSizeOf as DeriveMallocSizeOf;
 use metrics::{
@@ -44,6 +48,7 @@ use tx_handler::{
     ReceivedTransactionContainer, SentTransactionContainer,
 };
 
+pub mod download_scheduler;
 mod request_batcher;
 mod request_handler;
 pub mod tx_handler;
@@ -91,6 +96,11 @@ lazy_static! {
         Duration::from_secs(90);
     static ref DEFAULT_REQUEST_BATCH_BUCKET_SIZE: Duration =
         Duration::from_secs(2);
+    /// Missing blocks in catch-up are reassigned to another peer at once until
+    /// the delay exceeds this bound, i.e. for the first 3 failures, and then
+    /// resent with the increasing delay.
+    static ref MAX_IMMEDIATE_REASSIGN_DELAY: Duration =
+        Duration::from_secs(3);
 }
 
 #[derive(Debug)]
@@ -136,6 +146,11 @@ pub struct RequestManager {
 
     #[ignore_malloc_size_of = "channels are not handled in MallocSizeOf"]
     recover_public_queue: Arc<AsyncTaskQueue<RecoverPublicTask>>,
+
+    /// Schedule the block and header downloads in catch-up by the measured
+    /// throughput of peers.
+    block_download: DownloadScheduler,
+    header_download: DownloadScheduler,
 }
 
 impl RequestManager {
@@ -173,9 +188,60 @@ impl RequestManager {
             request_handler: Arc::new(RequestHandler::new(protocol_config)),
             syn,
             recover_public_queue,
+            block_download: DownloadScheduler::new(
+                MAX_BLOCKS_TO_SEND as usize,
+                protocol_config.download_target_response_time,
+            ),
+            header_download: DownloadScheduler::new(
+                MAX_HEADERS_TO_SEND as usize,
+                protocol_config.download_target_response_time,
+            ),
+        }
+    }
+
+    /// Returns the download scheduler of the requests of `msg_id`, i.e.
+    /// `GET_BLOCKS` or `GET_BLOCK_HEADERS`.
+    pub fn download_scheduler(
+        &self, msg_id: MsgId,
+    ) -> Option<&DownloadScheduler> {
+        match msg_id {
+            msgid::GET_BLOCKS => Some(&self.block_download),
+            msgid::GET_BLOCK_HEADERS => Some(&self.header_download),
+            _ => None,
         }
     }
 
+    /// Returns the download scheduler and the number of items of `request`
+    /// if it's a block or header download.
+    fn download_items(
+        &self, request: &Box<dyn Request>,
+    ) -> Option<(&DownloadScheduler, usize)> {
+        let items = match request.msg_id() {
+            msgid::GET_BLOCKS => try_get_block_hashes(request)?.len(),
+            msgid::GET_BLOCK_HEADERS => request
+                .as_any()
+                .downcast_ref::<GetBlockHeaders>()?
+                .hashes
+                .len(),
+            _ => return None,
+        };
+        Some((self.download_scheduler(request.msg_id())?, items))
+    }
+
+    /// Send `request` to `peer` and count it in the download scheduler.
+    fn send_request(
+        &self, io: &dyn NetworkContext, peer: Option<NodeId>,
+        request: Box<dyn Request>, delay: Option<Duration>,
+    ) -> Result<(), Box<dyn Request>>
+    {
+        let download = self.download_items(&request);
+        self.request_handler.send_request(io, peer, request, delay)?;
+        if let (Some(peer), Some((scheduler, items))) = (peer, download) {
+            scheduler.on_request_sent(&peer, items);
+        }
+        Ok(())
+    }
+
     pub fn num_epochs_in_flight(&self) -> u64 {
         self.inflight_keys
             .read(msgid::GET_BLOCK_HASHES_BY_EPOCH)
@@ -198,8 +264,19 @@ impl RequestManager {
     /// Send request to remote peer with delay mechanism. If failed,
     /// add the request to waiting queue to resend later.
     pub fn request_with_delay(
+        &self, io: &dyn NetworkContext, request: Box<dyn Request>,
+        peer: Option<NodeId>, delay: Option<Duration>,
+    )
+    {
+        self.request_with_backoff(io, request, peer, delay, false)
+    }
+
+    /// Like `request_with_delay`, but if `immediate` is true, the request is
+    /// sent to `peer` at once even if a delay is given. The delay is still
+    /// increased for the next resend.
+    fn request_with_backoff(
         &self, io: &dyn NetworkContext, mut request: Box<dyn Request>,
-        mut peer: Option<NodeId>, delay: Option<Duration>,
+        mut peer: Option<NodeId>, delay: Option<Duration>, immediate: bool,
     )
     {
         // retain the request items that not in flight.
@@ -212,7 +289,7 @@ impl RequestManager {
         // Check block-related requests, and put them into waiting_requests
         // if we cannot process it.
         if peer.is_some()
-            && delay.is_none()
+            && (delay.is_none() || immediate)
             && !self.check_and_update_net_inflight_blocks(&request)
         {
             peer = None;
@@ -225,7 +302,7 @@ impl RequestManager {
         };
 
         // delay if no peer available or delay required
-        if peer.is_none() || delay.is_some() {
+        if peer.is_none() || (delay.is_some() && !immediate) {
             debug!("request_with_delay: add request to waiting_requests, peer={:?}, request={:?}, delay={:?}", peer, request, cur_delay);
             self.waiting_requests.lock().push(TimedWaitingRequest::new(
                 Instant::now() + cur_delay,
@@ -235,12 +312,9 @@ impl RequestManager {
             return;
         }
 
-        if let Err(e) = self.request_handler.send_request(
-            io,
-            peer,
-            request,
-            Some(next_delay),
-        ) {
+        if let Err(e) =
+            self.send_request(io, peer, request, Some(next_delay))
+        {
             debug!("request_with_delay: send_request fails, peer={:?}, request={:?}", peer, e);
             self.waiting_requests.lock().push(TimedWaitingRequest::new(
                 Instant::now() + cur_delay,
@@ -591,6 +665,23 @@ impl RequestManager {
         self.request_handler.match_request(peer_id, request_id)
     }
 
+    /// Updates the download stat of `peer` with the response of `msg`, which
+    /// has `received` items.
+    pub fn download_received(
+        &self, peer: &NodeId, msg: &RequestMessage, received: usize,
+    ) {
+        if let (Some(sent_time), Some((scheduler, requested))) =
+            (msg.sent_time, self.download_items(&msg.request))
+        {
+            scheduler.on_response(
+                peer,
+                requested,
+                received,
+                sent_time.elapsed(),
+            );
+        }
+    }
+
     /// Remove inflight keys when a header is received.
     ///
     /// If a request is removed from `req_hashes`, it's the caller's
@@ -685,6 +776,7 @@ impl RequestManager {
         mut received_blocks: HashSet<H256>, ask_full_block: bool,
         peer: Option<NodeId>, with_public: bool, delay: Option<Duration>,
         preferred_node_type_for_block_request: Option<NodeType>,
+        reassign: bool,
     )
     {
         let _timer = MeterTimer::time_func(REQUEST_MANAGER_TIMER.as_ref());
@@ -733,13 +825,23 @@ impl RequestManager {
                 PeerFilter::new(msg_id).select(&self.syn)
             });
             if ask_full_block {
-                self.request_blocks(
+                let request = GetBlocks {
+                    request_id: 0,
+                    with_public,
+                    hashes: missing_blocks,
+                    preferred_node_type: preferred_node_type_for_block_request,
+                };
+                // If `reassign` is true, `chosen_peer` is the best peer to
+                // reassign the missing blocks to without waiting.
+                let immediate = reassign
+                    && delay
+                        .map_or(true, |d| d <= *MAX_IMMEDIATE_REASSIGN_DELAY);
+                self.request_with_backoff(
                     io,
+                    Box::new(request),
                     chosen_peer,
-                    missing_blocks,
-                    with_public,
                     delay,
-                    preferred_node_type_for_block_request,
+                    immediate,
                 );
             } else {
                 self.request_compact_blocks(
@@ -866,6 +968,11 @@ impl RequestManager {
             self.request_handler.process_timeout_requests(io);
         for req in timeout_requests {
             debug!("Timeout requests: {:?}", req);
+            if let (Some(peer), Some((scheduler, requested))) =
+                (req.sent_to, self.download_items(&req.request))
+            {
+                scheduler.on_timeout(&peer, requested);
+            }
             self.resend_request_to_another_peer(io, &req);
         }
     }
@@ -949,7 +1056,7 @@ impl RequestManager {
                 request, chosen_peer, next_delay
             );
 
-            if let Err(request) = self.request_handler.send_request(
+            if let Err(request) = self.send_request(
                 io,
                 Some(chosen_peer),
                 request,
@@ -970,6 +1077,8 @@ impl RequestManager {
     }
 
     pub fn on_peer_disconnected(&self, io: &dyn NetworkContext, peer: &NodeId) {
+        self.block_download.remove_peer(peer);
+        self.header_download.remove_peer(peer);
         if let Some(unfinished_requests) =
             self.request_handler.remove_peer(peer)
         {
@@ -16,8 +16,8 @@ use cfx_parameters::sync::FAILED_REQUEST_RESEND_WAIT;
 use malloc_size_of::MallocSizeOf;
 use malloc_size_of_derive::MallocSizeOf as DeriveMallocSizeOf;
 use network::{
-    node_table::NodeId, ErrorKind as NetworkErrorKind, NetworkContext,
-    UpdateNodeOperation,
+    node_table::NodeId, ErrorKind as NetworkErrorKind, Misbehavior,
+    NetworkContext, UpdateNodeOperation,
 };
 use parking_lot::Mutex;
 use std::{
@@ -165,11 +165,13 @@ impl RequestHandler {
         let mut timeout_requests = Vec::new();
         let mut peers_to_disconnect = HashSet::new();
         let mut peers_to_send_pending_requests = HashSet::new();
+        let mut timeout_peers = Vec::new();
         for sync_req in self.get_timeout_sync_requests() {
             if let Ok(mut req) =
                 self.match_request(&sync_req.peer_id, sync_req.request_id)
             {
                 let peer_id = sync_req.peer_id.clone();
+                timeout_peers.push(peer_id);
                 if let Some(request_container) =
                     self.peers.lock().get_mut(&peer_id)
                 {
@@ -204,6 +206,11 @@ impl RequestHandler {
         for peer_id in peers_to_send_pending_requests {
             self.send_pending_requests(io, &peer_id);
         }
+        // Every timeout lowers the reputation score of the peer, which may
+        // ban the peer if it keeps timing out after reconnection.
+        for peer_id in timeout_peers {
+            io.report_peer(&peer_id, Misbehavior::Timeout, "request timeout");
+        }
 
         timeout_requests
     }
@@ -318,6 +325,8 @@ impl RequestContainer {
     )
     {
         request_message.request.set_request_id(request_id);
+        request_message.sent_to = Some(self.peer_id);
+        request_message.sent_time = Some(Instant::now());
         let res = request_message.request.send(io, &self.peer_id);
         let is_send_error = if let Err(e) = res {
             match e.kind() {
@@ -465,11 +474,19 @@ pub trait Request:
 pub struct RequestMessage {
     pub request: Box<dyn Request>,
     pub delay: Option<Duration>,
+    /// The peer and the time the request is sent to, `None` if pending.
+    pub sent_to: Option<NodeId>,
+    pub sent_time: Option<Instant>,
 }
 
 impl RequestMessage {
     pub fn new(request: Box<dyn Request>, delay: Option<Duration>) -> Self {
-        RequestMessage { request, delay }
+        RequestMessage {
+            request,
+            delay,
+            sent_to: None,
+            sent_time: None,
+        }
     }
 
     pub fn set_request_id(&mut self, request_id: u64) {
@@ -0,0 +1,657 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! Portable archive of a checkpoint snapshot, to bootstrap a node without
+//! syncing the state from peers.
+//!
+//! The archive starts with a header of the checkpoint: the pivot block
+//! headers from the snapshot epoch up to the block committing the state root
+//! that points to the snapshot, the state roots committed by that block, and
+//! the manifest of the snapshot. The chunks of the manifest follow in order.
+//! Each record is the rlp prefixed by its length in 4 bytes.
+//!
+//! The import verifies the checkpoint headers and the committed state root,
+//! and restores the chunks with the full sync verifier into the temp snapshot
+//! of the full sync, the same way as the chunks downloaded from peers. When
+//! the node syncs the checkpoint, the restoration is resumed with all chunks
+//! restored, once the snapshot merkle root is verified against the state root
+//! trusted by the blame of the consensus.
+
+use crate::{
+    block_data_manager::db_manager::DBManager,
+    sync::{
+        state::storage::{Chunk, RangedManifest, SnapshotSyncCandidate},
+        Error, ErrorKind,
+    },
+};
+use cfx_parameters::consensus::DEFERRED_STATE_EPOCH_COUNT;
+use cfx_storage::{FullSyncVerifier, StorageManager, TrieProof};
+use primitives::{
+    BlockHeader, BlockHeaderBuilder, EpochId, MerkleHash, StateRoot,
+};
+use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+const SNAPSHOT_ARCHIVE_MAGIC: &[u8; 8] = b"CFXSNAP1";
+/// The maximum size of a record, checked before the record is read. The
+/// chunks are about `chunk_size` bytes, and the header is dominated by the
+/// proofs of the chunk boundaries.
+const MAX_RECORD_SIZE: usize = 256 * 1024 * 1024;
+
+#[derive(Clone, Debug)]
+pub struct SnapshotArchiveHeader {
+    pub snapshot_epoch_id: EpochId,
+    pub merkle_root: MerkleHash,
+    /// The pivot block headers from the snapshot epoch. The last one commits
+    /// the state root pointing to the snapshot.
+    pub checkpoint_headers: Vec<BlockHeader>,
+    /// The state roots blamed by the last checkpoint header, from the lowest
+    /// epoch. The last one points to the snapshot.
+    pub state_roots: Vec<StateRoot>,
+    pub chunk_boundaries: Vec<Vec<u8>>,
+    pub chunk_boundary_proofs: Vec<TrieProof>,
+}
+
+impl Encodable for SnapshotArchiveHeader {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(6)
+            .append(&self.snapshot_epoch_id)
+            .append(&self.merkle_root)
+            .append_list(&self.checkpoint_headers)
+            .append_list(&self.state_roots)
+            .append_list::<Vec<u8>, Vec<u8>>(&self.chunk_boundaries)
+            .append_list(&self.chunk_boundary_proofs);
+    }
+}
+
+impl Decodable for SnapshotArchiveHeader {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        Ok(SnapshotArchiveHeader {
+            snapshot_epoch_id: rlp.val_at(0)?,
+            merkle_root: rlp.val_at(1)?,
+            checkpoint_headers: rlp.list_at(2)?,
+            state_roots: rlp.list_at(3)?,
+            chunk_boundaries: rlp.list_at(4)?,
+            chunk_boundary_proofs: rlp.list_at(5)?,
+        })
+    }
+}
+
+impl SnapshotArchiveHeader {
+    pub fn snapshot_height(&self) -> u64 {
+        self.checkpoint_headers
+            .first()
+            .map_or(0, |header| header.height())
+    }
+
+    /// Verifies that the checkpoint headers are a chain from the snapshot
+    /// epoch, and that the last one commits the state root pointing to the
+    /// snapshot merkle root.
+    ///
+    /// The state root of the epoch `snapshot_epoch_count + 1` after the
+    /// snapshot epoch points to the snapshot, which is committed by the
+    /// pivot block `DEFERRED_STATE_EPOCH_COUNT` epochs later.
+    pub fn verify_checkpoint(
+        &self, snapshot_epoch_count: u64,
+    ) -> Result<(), Error> {
+        let invalid = |reason: &str| {
+            Err(ErrorKind::InvalidSnapshotArchive(reason.into()).into())
+        };
+        let first = match self.checkpoint_headers.first() {
+            Some(first) => first,
+            None => return invalid("no checkpoint headers"),
+        };
+        if first.hash() != self.snapshot_epoch_id {
+            return invalid(
+                "first checkpoint header is not the snapshot epoch",
+            );
+        }
+        for pair in self.checkpoint_headers.windows(2) {
+            if *pair[1].parent_hash() != pair[0].hash()
+                || pair[1].height() != pair[0].height() + 1
+            {
+                return invalid("checkpoint headers are not a chain");
+            }
+        }
+        let last = self.checkpoint_headers.last().expect("not empty");
+        if last.height()
+            != first.height()
+                + snapshot_epoch_count
+                + 1
+                + DEFERRED_STATE_EPOCH_COUNT
+        {
+            return invalid("unexpected number of checkpoint headers");
+        }
+
+        if self.state_roots.len() != last.blame() as usize + 1 {
+            return invalid("state roots do not match the blame");
+        }
+        if self.state_roots.last().expect("not empty").snapshot_root
+            != self.merkle_root
+        {
+            return invalid("state root does not point to the snapshot");
+        }
+        // The blamed state roots from the deferred epoch of the last header.
+        let state_root_hashes: Vec<_> = self
+            .state_roots
+            .iter()
+            .rev()
+            .map(|state_root| state_root.compute_state_root_hash())
+            .collect();
+        let deferred_state_root = if last.blame() == 0 {
+            state_root_hashes[0]
+        } else {
+            BlockHeaderBuilder::compute_blame_state_root_vec_root(
+                state_root_hashes,
+            )
+        };
+        if deferred_state_root != *last.deferred_state_root() {
+            return invalid("state root is not committed by the checkpoint");
+        }
+        Ok(())
+    }
+}
+
+/// Exports the snapshot of `snapshot_epoch_id` to an archive at `path`, with
+/// chunks of about `chunk_size` bytes. Returns the header of the archive.
+pub fn export_snapshot(
+    db_manager: &DBManager, storage_manager: &StorageManager,
+    snapshot_epoch_id: &EpochId, path: &Path, chunk_size: u64,
+) -> Result<SnapshotArchiveHeader, Error>
+{
+    let internal = |reason: String| ErrorKind::InternalError(reason);
+    let snapshot_header = db_manager
+        .block_header_from_db(snapshot_epoch_id)
+        .ok_or_else(|| internal("snapshot epoch header not found".into()))?;
+    let snapshot_height = snapshot_header.height();
+    let snapshot_epoch_count = storage_manager
+        .get_storage_manager()
+        .get_snapshot_epoch_count() as u64;
+    let pointing_height = snapshot_height + snapshot_epoch_count + 1;
+    let commit_height = pointing_height + DEFERRED_STATE_EPOCH_COUNT;
+
+    let pivot_header = |height: u64| -> Result<BlockHeader, Error> {
+        db_manager
+            .executed_epoch_set_hashes_from_db(height)
+            .and_then(|hashes| hashes.last().cloned())
+            .and_then(|hash| db_manager.block_header_from_db(&hash))
+            .ok_or_else(|| {
+                internal(format!(
+                    "pivot block of epoch {} not found, the state root of \
+                     the snapshot may not be committed yet",
+                    height
+                ))
+                .into()
+            })
+    };
+    let mut checkpoint_headers = vec![snapshot_header];
+    for height in snapshot_height + 1..=commit_height {
+        let header = pivot_header(height)?;
+        if *header.parent_hash() != checkpoint_headers.last().unwrap().hash() {
+            bail!(internal(format!(
+                "snapshot epoch {:?} is not on the pivot chain",
+                snapshot_epoch_id
+            )));
+        }
+        checkpoint_headers.push(header);
+    }
+    let blame = checkpoint_headers.last().unwrap().blame() as u64;
+    let mut state_roots = Vec::with_capacity(blame as usize + 1);
+    for height in pointing_height - blame..=pointing_height {
+        let epoch_hash = pivot_header(height)?.hash();
+        let commitment = db_manager
+            .epoch_execution_commitment_from_db(&epoch_hash)
+            .ok_or_else(|| {
+                internal(format!("epoch {} is not executed", height))
+            })?;
+        state_roots.push(commitment.state_root_with_aux_info.state_root);
+    }
+
+    let snapshot_to_sync = SnapshotSyncCandidate::FullSync {
+        height: snapshot_height,
+        snapshot_epoch_id: *snapshot_epoch_id,
+    };
+    let (manifest, merkle_root) = RangedManifest::load(
+        &snapshot_to_sync,
+        None,
+        storage_manager,
+        chunk_size,
+        usize::MAX,
+    )?
+    .ok_or_else(|| internal("snapshot not found".into()))?;
+
+    let header = SnapshotArchiveHeader {
+        snapshot_epoch_id: *snapshot_epoch_id,
+        merkle_root,
+        checkpoint_headers,
+        state_roots,
+        chunk_boundaries: manifest.chunk_boundaries,
+        chunk_boundary_proofs: manifest.chunk_boundary_proofs,
+    };
+    header.verify_checkpoint(snapshot_epoch_count)?;
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(SNAPSHOT_ARCHIVE_MAGIC)?;
+    write_record(&mut writer, &header.rlp_bytes())?;
+    for chunk_key in RangedManifest::convert_boundaries_to_chunks(
+        header.chunk_boundaries.clone(),
+    ) {
+        let chunk =
+            Chunk::load(snapshot_epoch_id, &chunk_key, storage_manager)?
+                .ok_or_else(|| internal("snapshot not found".into()))?;
+        write_record(&mut writer, &chunk.rlp_bytes())?;
+    }
+    writer.flush()?;
+    Ok(header)
+}
+
+/// Imports the archive at `path` into the temp snapshot of the full sync,
+/// which is resumed when the node syncs the checkpoint. An interrupted import
+/// is resumed as well. Returns the header of the archive.
+pub fn import_snapshot(
+    storage_manager: &StorageManager, path: &Path,
+) -> Result<SnapshotArchiveHeader, Error> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != SNAPSHOT_ARCHIVE_MAGIC {
+        bail!(ErrorKind::InvalidSnapshotArchive(
+            "not a snapshot archive".into()
+        ));
+    }
+    let header: SnapshotArchiveHeader =
+        rlp::decode(&read_record(&mut reader)?)?;
+    let snapshot_epoch_count = storage_manager
+        .get_storage_manager()
+        .get_snapshot_epoch_count() as u64;
+    header.verify_checkpoint(snapshot_epoch_count)?;
+    RangedManifest {
+        chunk_boundaries: header.chunk_boundaries.clone(),
+        chunk_boundary_proofs: header.chunk_boundary_proofs.clone(),
+        next: None,
+    }
+    .validate(&header.merkle_root)?;
+
+    let mut verifier = FullSyncVerifier::new(
+        header.chunk_boundaries.len() + 1,
+        header.chunk_boundaries.clone(),
+        header.chunk_boundary_proofs.clone(),
+        header.merkle_root,
+        storage_manager
+            .get_storage_manager()
+            .get_snapshot_manager()
+            .get_snapshot_db_manager(),
+        &header.snapshot_epoch_id,
+    )?;
+    for chunk_key in RangedManifest::convert_boundaries_to_chunks(
+        header.chunk_boundaries.clone(),
+    ) {
+        let chunk: Chunk = rlp::decode(&read_record(&mut reader)?)?;
+        if verifier.is_chunk_restored(&chunk_key.upper_bound_excl) {
+            continue;
+        }
+        chunk.validate(&chunk_key)?;
+        if !verifier.restore_chunk(
+            &chunk_key.upper_bound_excl,
+            &chunk.keys,
+            chunk.values,
+        )? {
+            bail!(ErrorKind::InvalidSnapshotChunk(format!(
+                "chunk {:?} fails the verification",
+                chunk_key
+            )));
+        }
+    }
+    Ok(header)
+}
+
+fn write_record(writer: &mut dyn Write, record: &[u8]) -> Result<(), Error> {
+    if record.len() > MAX_RECORD_SIZE {
+        bail!(ErrorKind::InternalError(format!(
+            "the record size {} is larger than {}",
+            record.len(),
+            MAX_RECORD_SIZE
+        )));
+    }
+    writer.write_all(&(record.len() as u32).to_be_bytes())?;
+    writer.write_all(record)?;
+    Ok(())
+}
+
+fn read_record(reader: &mut dyn Read) -> Result<Vec<u8>, Error> {
+    let mut len_bytes = [0u8; 4];
+    reader.read_exact(&mut len_bytes)?;
+    let len = u32::from_be_bytes(len_bytes) as usize;
+    if len > MAX_RECORD_SIZE {
+        bail!(ErrorKind::InvalidSnapshotArchive(format!(
+            "the record size {} is larger than {}",
+            len, MAX_RECORD_SIZE
+        )));
+    }
+    let mut record = vec![0u8; len];
+    reader.read_exact(&mut record)?;
+    Ok(record)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        export_snapshot, import_snapshot, read_record, write_record,
+        SnapshotArchiveHeader, MAX_RECORD_SIZE,
+    };
+    use crate::{
+        block_data_manager::db_manager::DBManager,
+        pow::PowComputer,
+        sync::{state::storage::RangedManifest, Error, ErrorKind},
+    };
+    use cfx_internal_common::{EpochExecutionCommitment, StateRootWithAuxInfo};
+    use cfx_parameters::consensus::DEFERRED_STATE_EPOCH_COUNT;
+    use cfx_storage::{
+        tests::new_state_manager_for_unit_test_with_snapshot_epoch_count,
+        FullSyncVerifier, StateIndex, StorageManagerTrait, StorageState,
+        StorageStateTrait,
+    };
+    use cfx_types::{address_util::AddressUtil, Address, H256};
+    use primitives::{
+        BlockHeader, BlockHeaderBuilder, EpochId, MerkleHash, StateRoot,
+        StorageKey,
+    };
+    use std::{env, fs, process, sync::Arc};
+
+    const SNAPSHOT_EPOCH_COUNT: u32 = 3;
+
+    /// Returns `count` pivot headers following `parent`.
+    fn pivot_headers(
+        parent: Option<&BlockHeader>, count: usize,
+    ) -> Vec<BlockHeader> {
+        let mut headers: Vec<BlockHeader> = Vec::with_capacity(count);
+        for _ in 0..count {
+            let parent = headers.last().or(parent);
+            let mut header = BlockHeaderBuilder::new()
+                .with_parent_hash(parent.map_or(H256::zero(), |p| p.hash()))
+                .with_height(parent.map_or(0, |p| p.height() + 1))
+                .build();
+            header.compute_hash();
+            headers.push(header);
+        }
+        headers
+    }
+
+    /// Returns the header after `parent` which commits `state_roots`, blaming
+    /// all but the last one.
+    fn commit_header(
+        parent: &BlockHeader, state_roots: &[StateRoot],
+    ) -> BlockHeader {
+        let state_root_hashes: Vec<H256> = state_roots
+            .iter()
+            .rev()
+            .map(|state_root| state_root.compute_state_root_hash())
+            .collect();
+        let deferred_state_root = if state_root_hashes.len() == 1 {
+            state_root_hashes[0]
+        } else {
+            BlockHeaderBuilder::compute_blame_state_root_vec_root(
+                state_root_hashes,
+            )
+        };
+        let mut header = BlockHeaderBuilder::new()
+            .with_parent_hash(parent.hash())
+            .with_height(parent.height() + 1)
+            .with_blame(state_roots.len() as u32 - 1)
+            .with_deferred_state_root(deferred_state_root)
+            .build();
+        header.compute_hash();
+        header
+    }
+
+    fn checkpoint(blame: u32) -> SnapshotArchiveHeader {
+        let merkle_root = MerkleHash::repeat_byte(1);
+        let mut state_roots: Vec<StateRoot> = (0..=blame)
+            .map(|i| StateRoot {
+                snapshot_root: MerkleHash::repeat_byte(2),
+                intermediate_delta_root: MerkleHash::from_low_u64_be(i as u64),
+                delta_root: MerkleHash::repeat_byte(3),
+            })
+            .collect();
+        state_roots.last_mut().unwrap().snapshot_root = merkle_root;
+
+        let snapshot_header = pivot_headers(None, 1000).pop().unwrap();
+        let mut checkpoint_headers = vec![snapshot_header.clone()];
+        checkpoint_headers.extend(pivot_headers(
+            Some(&snapshot_header),
+            (SNAPSHOT_EPOCH_COUNT as u64 + DEFERRED_STATE_EPOCH_COUNT) as usize,
+        ));
+        let commit =
+            commit_header(checkpoint_headers.last().unwrap(), &state_roots);
+        checkpoint_headers.push(commit);
+
+        SnapshotArchiveHeader {
+            snapshot_epoch_id: snapshot_header.hash(),
+            merkle_root,
+            checkpoint_headers,
+            state_roots,
+            chunk_boundaries: vec![],
+            chunk_boundary_proofs: vec![],
+        }
+    }
+
+    fn is_invalid_archive(result: Result<(), Error>) -> bool {
+        match result {
+            Err(e) => matches!(e.0, ErrorKind::InvalidSnapshotArchive(_)),
+            Ok(()) => false,
+        }
+    }
+
+    #[test]
+    fn test_verify_checkpoint() {
+        let count = SNAPSHOT_EPOCH_COUNT as u64;
+        checkpoint(0).verify_checkpoint(count).unwrap();
+        checkpoint(2).verify_checkpoint(count).unwrap();
+        assert_eq!(checkpoint(0).snapshot_height(), 999);
+
+        // Wrong number of checkpoint headers.
+        assert!(is_invalid_archive(
+            checkpoint(0).verify_checkpoint(count + 1)
+        ));
+
+        let mut header = checkpoint(0);
+        header.checkpoint_headers.clear();
+        assert!(is_invalid_archive(header.verify_checkpoint(count)));
+
+        let mut header = checkpoint(0);
+        header.snapshot_epoch_id = H256::repeat_byte(9);
+        assert!(is_invalid_archive(header.verify_checkpoint(count)));
+
+        let mut header = checkpoint(0);
+        header.checkpoint_headers.swap(1, 2);
+        assert!(is_invalid_archive(header.verify_checkpoint(count)));
+
+        // The state root points to another snapshot.
+        let mut header = checkpoint(0);
+        header.merkle_root = MerkleHash::repeat_byte(4);
+        assert!(is_invalid_archive(header.verify_checkpoint(count)));
+
+        // The state roots do not match the blame of the last header.
+        let mut header = checkpoint(2);
+        header.state_roots.remove(0);
+        assert!(is_invalid_archive(header.verify_checkpoint(count)));
+
+        // A blamed state root is not committed by the last header.
+        let mut header = checkpoint(2);
+        header.state_roots[0].delta_root = MerkleHash::repeat_byte(5);
+        assert!(is_invalid_archive(header.verify_checkpoint(count)));
+    }
+
+    fn commit_epoch(
+        state: &mut StorageState, epoch_id: &EpochId, height: u64,
+    ) -> StateRootWithAuxInfo {
+        for i in 0..100 {
+            let mut address = Address::from_low_u64_be(height * 1000 + i);
+            address.set_user_account_type_bits();
+            state
+                .set(
+                    StorageKey::new_account_key(&address),
+                    vec![height as u8; 32].into(),
+                )
+                .unwrap();
+        }
+        state.compute_state_root().unwrap();
+        state.commit(*epoch_id).unwrap()
+    }
+
+    #[test]
+    fn test_export_import_snapshot() {
+        let state_manager =
+            new_state_manager_for_unit_test_with_snapshot_epoch_count(
+                SNAPSHOT_EPOCH_COUNT,
+            );
+        let snapshot_height = SNAPSHOT_EPOCH_COUNT as u64;
+        let pointing_height = snapshot_height + SNAPSHOT_EPOCH_COUNT as u64 + 1;
+        let mut headers = pivot_headers(
+            None,
+            (pointing_height + DEFERRED_STATE_EPOCH_COUNT) as usize,
+        );
+
+        // Commit the states up to the epoch pointing to the snapshot.
+        let mut state = state_manager.get_state_for_genesis_write();
+        let mut state_roots =
+            vec![commit_epoch(&mut state, &headers[0].hash(), 0)];
+        for height in 1..=pointing_height {
+            let mut state = state_manager
+                .get_state_for_next_epoch(StateIndex::new_for_next_epoch(
+                    &headers[height as usize - 1].hash(),
+                    state_roots.last().unwrap(),
+                    height,
+                    SNAPSHOT_EPOCH_COUNT,
+                ))
+                .unwrap()
+                .unwrap();
+            state_roots.push(commit_epoch(
+                &mut state,
+                &headers[height as usize].hash(),
+                height,
+            ));
+        }
+        let snapshot_epoch_id = headers[snapshot_height as usize].hash();
+        state_manager
+            .get_storage_manager()
+            .wait_for_snapshot(&snapshot_epoch_id, /* try_open = */ false)
+            .unwrap()
+            .expect("snapshot should be made");
+        let commit = commit_header(
+            headers.last().unwrap(),
+            &[state_roots.last().unwrap().state_root.clone()],
+        );
+        headers.push(commit);
+
+        let dir = env::temp_dir()
+            .join(format!("test_export_import_snapshot_{}", process::id()));
+        fs::remove_dir_all(&dir).ok();
+        let db_manager = DBManager::new_from_sqlite(
+            &dir.join("db"),
+            Arc::new(PowComputer::new(true)),
+        );
+        for (height, header) in headers.iter().enumerate() {
+            db_manager.insert_block_header_to_db(header);
+            db_manager.insert_executed_epoch_set_hashes_to_db(
+                height as u64,
+                &vec![header.hash()],
+            );
+        }
+        for (height, state_root) in state_roots.into_iter().enumerate() {
+            db_manager.insert_epoch_execution_commitment_to_db(
+                &headers[height].hash(),
+                &EpochExecutionCommitment {
+                    state_root_with_aux_info: state_root,
+                    receipts_root: Default::default(),
+                    logs_bloom_hash: Default::default(),
+                },
+            );
+        }
+
+        let path = dir.join("archive");
+        let exported = export_snapshot(
+            &db_manager,
+            &state_manager,
+            &snapshot_epoch_id,
+            &path,
+            4096, /* chunk_size */
+        )
+        .unwrap();
+        assert!(exported.chunk_boundaries.len() > 1);
+
+        let target_manager =
+            new_state_manager_for_unit_test_with_snapshot_epoch_count(
+                SNAPSHOT_EPOCH_COUNT,
+            );
+        let imported = import_snapshot(&target_manager, &path).unwrap();
+        assert_eq!(imported.snapshot_epoch_id, snapshot_epoch_id);
+        assert_eq!(imported.merkle_root, exported.merkle_root);
+        assert_eq!(imported.chunk_boundaries, exported.chunk_boundaries);
+
+        // All chunks are restored into the temp snapshot of the full sync.
+        let verifier = FullSyncVerifier::new(
+            imported.chunk_boundaries.len() + 1,
+            imported.chunk_boundaries.clone(),
+            imported.chunk_boundary_proofs.clone(),
+            imported.merkle_root,
+            target_manager
+                .get_storage_manager()
+                .get_snapshot_manager()
+                .get_snapshot_db_manager(),
+            &snapshot_epoch_id,
+        )
+        .unwrap();
+        for chunk_key in RangedManifest::convert_boundaries_to_chunks(
+            imported.chunk_boundaries.clone(),
+        ) {
+            assert!(verifier.is_chunk_restored(&chunk_key.upper_bound_excl));
+        }
+        drop(verifier);
+
+        // A corrupted value in the first chunk fails the import.
+        let mut archive = fs::read(&path).unwrap();
+        let record_len = |offset: usize| {
+            let mut len_bytes = [0u8; 4];
+            len_bytes.copy_from_slice(&archive[offset..offset + 4]);
+            u32::from_be_bytes(len_bytes) as usize
+        };
+        let chunk_offset = 8 + 4 + record_len(8);
+        let chunk_end = chunk_offset + 4 + record_len(chunk_offset);
+        archive[chunk_end - 1] ^= 1;
+        fs::write(&path, archive).unwrap();
+        let target_manager =
+            new_state_manager_for_unit_test_with_snapshot_epoch_count(
+                SNAPSHOT_EPOCH_COUNT,
+            );
+        assert!(import_snapshot(&target_manager, &path).is_err());
+
+        drop(db_manager);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_read_record() {
+        let mut archive = vec![];
+        write_record(&mut archive, b"record").unwrap();
+        assert_eq!(read_record(&mut &archive[..]).unwrap(), b"record");
+        // Truncated record.
+        assert!(read_record(&mut &archive[..archive.len() - 1]).is_err());
+
+        // The length is checked before the record is allocated.
+        let len = (MAX_RECORD_SIZE as u32 + 1).to_be_bytes();
+        match read_record(&mut &len[..]) {
+            Err(e) => {
+                assert!(matches!(e.0, ErrorKind::InvalidSnapshotArchive(_)))
+            }
+            Ok(_) => panic!("oversized record is read"),
+        }
+        let len = u32::MAX.to_be_bytes();
+        assert!(read_record(&mut &len[..]).is_err());
+    }
+}
@@ -24,7 +24,7 @@ use crate::sync::{
 use cfx_parameters::consensus_internal::REWARD_EPOCH_COUNT;
 use cfx_storage::Result as StorageResult;
 use cfx_types::H256;
-use network::{node_table::NodeId, NetworkContext};
+use network::{node_table::NodeId, NetworkContext, NodeRecord};
 use parking_lot::RwLock;
 use primitives::EpochId;
 use std::{
@@ -142,8 +142,17 @@ impl Inner {
         sync_handler: &SynchronizationProtocolHandler,
     )
     {
-        let peers = PeerFilter::new(msgid::STATE_SYNC_CANDIDATE_REQUEST)
-            .select_all(&sync_handler.syn);
+        let peers: Vec<NodeId> =
+            PeerFilter::new(msgid::STATE_SYNC_CANDIDATE_REQUEST)
+                .select_all(&sync_handler.syn)
+                .into_iter()
+                .filter(|peer| {
+                    may_serve_candidates(
+                        io.get_peer_node_record(peer),
+                        &candidates,
+                    )
+                })
+                .collect();
         if peers.is_empty() {
             return;
         }
@@ -178,6 +187,35 @@ impl Inner {
     }
 }
 
+/// Skips the peers whose discovery record advertises a snapshot range not
+/// covering any candidate. A record advertising snapshots older than the sync
+/// target is dropped, because the peer may have made the snapshot of the
+/// target since then. Peers without a record may still serve the snapshots.
+fn may_serve_candidates(
+    record: Option<NodeRecord>, candidates: &[SnapshotSyncCandidate],
+) -> bool {
+    let target_height = match candidates
+        .iter()
+        .map(|candidate| candidate.get_height())
+        .max()
+    {
+        Some(height) => height,
+        None => return false,
+    };
+    match record {
+        Some(NodeRecord {
+            snapshot_range: Some((_, high)),
+            ..
+        }) if high < target_height => true,
+        Some(record) => candidates.iter().any(|candidate| {
+            record.snapshot_range.map_or(false, |(low, high)| {
+                (low..=high).contains(&candidate.get_height())
+            })
+        }),
+        None => true,
+    }
+}
+
 impl Debug for Inner {
     fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
         write!(
@@ -222,25 +260,34 @@ impl SnapshotChunkSync {
             let r = manifest_manager
                 .handle_snapshot_manifest_response(ctx, response, request)?;
             if let Some(related_data) = r {
+                let chunk_manager = SnapshotChunkManager::new_and_start(
+                    ctx,
+                    manifest_manager.snapshot_candidate.clone(),
+                    related_data.snapshot_info.clone(),
+                    manifest_manager.chunk_boundaries.clone(),
+                    manifest_manager.chunk_boundary_proofs.clone(),
+                    manifest_manager.active_peers.clone(),
+                    self.config.chunk_config(),
+                )?;
                 // update status
-                inner.status = Status::DownloadingChunks(Instant::now());
-                inner.chunk_manager =
-                    Some(SnapshotChunkManager::new_and_start(
-                        ctx,
-                        manifest_manager.snapshot_candidate.clone(),
-                        related_data.snapshot_info.clone(),
-                        manifest_manager.chunk_boundaries.clone(),
-                        manifest_manager.chunk_boundary_proofs.clone(),
-                        manifest_manager.active_peers.clone(),
-                        self.config.chunk_config(),
-                    )?);
+                inner.status = if chunk_manager.is_restored() {
+                    // All chunks were restored before the restoration is
+                    // resumed.
+                    Status::Completed
+                } else {
+                    Status::DownloadingChunks(Instant::now())
+                };
+                inner.chunk_manager = Some(chunk_manager);
                 inner.related_data = Some(related_data);
             }
             debug!("sync state progress: {:?}", *inner);
         } else {
             error!("manifest manager is None in status {:?}", inner.status);
         }
-        if matches!(inner.status, Status::DownloadingChunks(_)) {
+        if matches!(
+            inner.status,
+            Status::DownloadingChunks(_) | Status::Completed
+        ) {
             inner.manifest_manager = None;
         }
         Ok(())
@@ -521,3 +568,44 @@ impl StateSyncConfiguration {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::may_serve_candidates;
+    use crate::sync::state::storage::SnapshotSyncCandidate;
+    use cfx_types::H256;
+    use network::NodeRecord;
+
+    fn record(snapshot_range: Option<(u64, u64)>) -> Option<NodeRecord> {
+        Some(NodeRecord {
+            seq: 1,
+            node_type: "full".into(),
+            protocols: vec![],
+            snapshot_range,
+        })
+    }
+
+    #[test]
+    fn test_may_serve_candidates() {
+        let candidates = vec![SnapshotSyncCandidate::FullSync {
+            height: 4000,
+            snapshot_epoch_id: H256::zero(),
+        }];
+        assert!(may_serve_candidates(None, &candidates));
+        assert!(may_serve_candidates(
+            record(Some((2000, 6000))),
+            &candidates
+        ));
+        assert!(!may_serve_candidates(
+            record(Some((5000, 6000))),
+            &candidates
+        ));
+        assert!(!may_serve_candidates(record(None), &candidates));
+        // The record is older than the sync target.
+        assert!(may_serve_candidates(
+            record(Some((1000, 2000))),
+            &candidates
+        ));
+        assert!(!may_serve_candidates(None, &[]));
+    }
+}
@@ -6,7 +6,7 @@ use crate::sync::state::storage::{Chunk, ChunkKey};
 use cfx_storage::{
     state_manager::StateManager,
     storage_db::{SnapshotDbManagerTrait, SnapshotInfo},
-    FullSyncVerifier, Result as StorageResult, SnapshotDbManagerSqlite,
+    AnySnapshotDbManager, FullSyncVerifier, Result as StorageResult,
 };
 use primitives::{EpochId, MerkleHash};
 use std::sync::Arc;
@@ -17,7 +17,7 @@ pub struct Restorer {
 
     /// The verifier for chunks.
     /// Initialized after receiving a valid manifest.
-    verifier: Option<FullSyncVerifier<SnapshotDbManagerSqlite>>,
+    verifier: Option<FullSyncVerifier<AnySnapshotDbManager>>,
 }
 
 impl Restorer {
@@ -32,11 +32,20 @@ impl Restorer {
     }
 
     pub fn initialize_verifier(
-        &mut self, verifier: FullSyncVerifier<SnapshotDbManagerSqlite>,
+        &mut self, verifier: FullSyncVerifier<AnySnapshotDbManager>,
     ) {
         self.verifier = Some(verifier);
     }
 
+    /// Returns if the chunk has been restored, e.g. before the restoration is
+    /// resumed.
+    pub fn is_chunk_restored(&self, key: &ChunkKey) -> bool {
+        match &self.verifier {
+            None => false,
+            Some(verifier) => verifier.is_chunk_restored(&key.upper_bound_excl),
+        }
+    }
+
     /// Append a chunk for restoration.
     pub fn append(&mut self, key: ChunkKey, chunk: Chunk) -> bool {
         match &mut self.verifier {
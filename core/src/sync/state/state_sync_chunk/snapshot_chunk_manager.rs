@@ -1,5 +1,6 @@
 use crate::sync::{
     message::{msgid, Context, SnapshotChunkRequest},
+    random,
     state::{
         state_sync_chunk::restore::Restorer,
         storage::{Chunk, ChunkKey, RangedManifest, SnapshotSyncCandidate},
@@ -12,6 +13,7 @@ use cfx_storage::{
 };
 use malloc_size_of_derive::MallocSizeOf as DeriveMallocSizeOf;
 use network::node_table::NodeId;
+use rand::prelude::SliceRandom;
 use std::{
     collections::{HashMap, HashSet, VecDeque},
     fmt::{Debug, Formatter},
@@ -43,39 +45,70 @@ impl SnapshotChunkManager {
             *snapshot_candidate.get_snapshot_epoch_id(),
             snapshot_info.merkle_root,
         );
+        let snapshot_db_manager = ctx
+            .manager
+            .graph
+            .data_man
+            .storage_manager
+            .get_storage_manager()
+            .get_snapshot_manager()
+            .get_snapshot_db_manager();
 
+        // An interrupted restoration of the same snapshot is resumed with the
+        // chunks in its manifest, which may be sliced differently by the peer
+        // serving the new manifest.
+        let (chunk_boundaries, chunk_boundary_proofs) =
+            match FullSyncVerifier::load_resumable_manifest(
+                snapshot_db_manager,
+                snapshot_info.get_snapshot_epoch_id(),
+                &snapshot_info.merkle_root,
+            )? {
+                Some(manifest) => manifest,
+                None => (chunk_boundaries, chunk_boundary_proofs),
+            };
         let verifier = FullSyncVerifier::new(
             chunk_boundaries.len() + 1,
             chunk_boundaries.clone(),
-            chunk_boundary_proofs.clone(),
+            chunk_boundary_proofs,
             snapshot_info.merkle_root,
-            ctx.manager
-                .graph
-                .data_man
-                .storage_manager
-                .get_storage_manager()
-                .get_snapshot_manager()
-                .get_snapshot_db_manager(),
+            snapshot_db_manager,
             snapshot_info.get_snapshot_epoch_id(),
         )?;
 
         restorer.initialize_verifier(verifier);
-        let chunks =
-            RangedManifest::convert_boundaries_to_chunks(chunk_boundaries);
+        let chunks: VecDeque<ChunkKey> =
+            RangedManifest::convert_boundaries_to_chunks(chunk_boundaries)
+                .into_iter()
+                .filter(|chunk| !restorer.is_chunk_restored(chunk))
+                .collect();
         let mut chunk_manager = Self {
             snapshot_candidate,
             snapshot_info,
             active_peers,
-            pending_chunks: chunks.into(),
+            pending_chunks: chunks,
             downloading_chunks: Default::default(),
             num_downloaded: 0,
             config,
             restorer,
         };
-        chunk_manager.request_chunks(ctx);
+        if chunk_manager.pending_chunks.is_empty() {
+            debug!("Snapshot chunks are all restored before resuming");
+            chunk_manager.restorer.finalize_restoration(
+                ctx.manager.graph.data_man.storage_manager.clone(),
+                chunk_manager.snapshot_info.clone(),
+            )?;
+        } else {
+            chunk_manager.request_chunks(ctx);
+        }
         Ok(chunk_manager)
     }
 
+    /// Returns if all chunks have been received and the snapshot is
+    /// reconstructed.
+    pub fn is_restored(&self) -> bool {
+        self.pending_chunks.is_empty() && self.downloading_chunks.is_empty()
+    }
+
     /// Add a received chunk, and request new ones if needed.
     /// Return `Ok(true)` if all chunks have been received and the snapshot is
     /// reconstructed. Return `Ok(false)` if there are chunks missing.
@@ -148,19 +181,35 @@ impl SnapshotChunkManager {
         Some(chunk_key)
     }
 
-    /// Request multiple chunks from random peers.
+    /// Request chunks from all active peers, each from the peer with the
+    /// fewest chunks in downloading, until `max_downloading_chunks` chunks are
+    /// in downloading.
     fn request_chunks(&mut self, ctx: &Context) {
-        let chosen_peers = PeerFilter::new(msgid::GET_SNAPSHOT_CHUNK)
+        let mut peers = PeerFilter::new(msgid::GET_SNAPSHOT_CHUNK)
             .choose_from(&self.active_peers)
-            .select_n(
-                self.config.max_downloading_chunks
-                    - self.downloading_chunks.len(),
-                &ctx.manager.syn,
-            );
-        for peer in chosen_peers {
+            .select_all(&ctx.manager.syn);
+        if peers.is_empty() {
+            return;
+        }
+        // Break ties randomly.
+        peers.shuffle(&mut random::new());
+        let mut downloading_by_peer: HashMap<NodeId, usize> = HashMap::new();
+        for status in self.downloading_chunks.values() {
+            *downloading_by_peer.entry(status.peer).or_default() += 1;
+        }
+
+        while self.downloading_chunks.len() < self.config.max_downloading_chunks
+        {
+            let peer = *peers
+                .iter()
+                .min_by_key(|peer| {
+                    downloading_by_peer.get(*peer).cloned().unwrap_or(0)
+                })
+                .expect("peers not empty");
             if self.request_chunk_from_peer(ctx, &peer).is_none() {
                 break;
             }
+            *downloading_by_peer.entry(peer).or_default() += 1;
         }
     }
 
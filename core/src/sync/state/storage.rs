@@ -45,6 +45,14 @@ impl SnapshotSyncCandidate {
         }
     }
 
+    pub fn get_height(&self) -> u64 {
+        match self {
+            SnapshotSyncCandidate::OneStepSync { height, .. } => *height,
+            SnapshotSyncCandidate::FullSync { height, .. } => *height,
+            SnapshotSyncCandidate::IncSync { height, .. } => *height,
+        }
+    }
+
     pub fn get_snapshot_epoch_id(&self) -> &EpochId {
         match self {
             SnapshotSyncCandidate::OneStepSync {
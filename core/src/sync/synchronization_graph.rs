@@ -15,6 +15,7 @@ use crate::{
     verification::*,
     ConsensusGraph, Notifications,
 };
+use cfx_internal_common::StateAvailabilityBoundary;
 use cfx_types::{H256, U256};
 use dag::{Graph, RichDAG, RichTreeGraph, TreeGraph, DAG};
 use futures::executor::block_on;
@@ -1846,6 +1847,31 @@ impl SynchronizationGraph {
         self.consensus.construct_pivot_state();
         true
     }
+
+    /// Goes through the catch-up phases with the blocks in the db, as the
+    /// synchronization phases do without peers, and waits for the consensus
+    /// graph to process the blocks. This is used by the offline tools of a
+    /// node which isn't connected to the network.
+    pub fn recover_graph_offline(&self) {
+        self.recover_graph_from_db();
+        let cur_era_stable_hash =
+            self.data_man.get_cur_consensus_era_stable_hash();
+        let cur_era_stable_height = self
+            .data_man
+            .block_header_by_hash(&cur_era_stable_hash)
+            .expect("stable era block header must exist")
+            .height();
+        *self.data_man.state_availability_boundary.write() =
+            StateAvailabilityBoundary::new(
+                cur_era_stable_hash,
+                cur_era_stable_height,
+            );
+        self.complete_filling_block_bodies();
+        self.consensus.enter_normal_phase();
+        while self.is_consensus_worker_busy() {
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
 }
 
 impl Graph for SynchronizationGraphInner {
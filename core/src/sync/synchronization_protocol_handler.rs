@@ -36,7 +36,8 @@ use metrics::{register_meter_with_group, Meter, MeterTimer};
 use network::{
     node_table::NodeId, service::ProtocolVersion,
     throttling::THROTTLING_SERVICE, Error as NetworkError, HandlerWorkType,
-    NetworkContext, NetworkProtocolHandler, UpdateNodeOperation,
+    Misbehavior, NetworkContext, NetworkProtocolHandler, UpdateNodeOperation,
+    NODE_TAG_ARCHIVE, NODE_TAG_FULL,
 };
 use parking_lot::{Mutex, RwLock};
 use primitives::{Block, BlockHeader, EpochId, SignedTransaction};
@@ -413,6 +414,9 @@ pub struct ProtocolConfiguration {
     pub max_unprocessed_block_size: usize,
     pub max_chunk_number_in_manifest: usize,
     pub allow_phase_change_without_peer: bool,
+    /// Expected response time of block and header requests in catch-up, by
+    /// which the batch size of each peer is adapted to its throughput.
+    pub download_target_response_time: Duration,
 }
 
 impl SynchronizationProtocolHandler {
@@ -588,11 +592,18 @@ impl SynchronizationProtocolHandler {
         let reason = format!("{}", e.0);
         let error_reason = format!("{:?}", e);
         let mut op = None;
+        // Misbehaviors lower the reputation score of the peer, which bans the
+        // peer if the score drops too low.
+        let mut misbehavior = None;
 
         // NOTE, DO NOT USE WILDCARD IN THE FOLLOWING MATCH STATEMENT!
         // COMPILER WILL HELP TO FIND UNHANDLED ERROR CASES.
         match e.0 {
-            ErrorKind::InvalidBlock => op = Some(UpdateNodeOperation::Failure),
+            // The peer is only disconnected if its score drops too low.
+            ErrorKind::InvalidBlock => {
+                disconnect = false;
+                misbehavior = Some(Misbehavior::InvalidBlock);
+            }
             ErrorKind::InvalidGetBlockTxn(_) => {
                 op = Some(UpdateNodeOperation::Demotion)
             }
@@ -608,7 +619,8 @@ impl SynchronizationProtocolHandler {
             // TODO handle the unexpected response case (timeout or real invalid
             // message type)
             ErrorKind::UnexpectedResponse => {
-                op = Some(UpdateNodeOperation::Demotion)
+                op = Some(UpdateNodeOperation::Demotion);
+                misbehavior = Some(Misbehavior::UselessResponse);
             }
             ErrorKind::RequestNotFound => disconnect = false,
             ErrorKind::InCatchUpMode(_) => {
@@ -626,8 +638,11 @@ impl SynchronizationProtocolHandler {
                 op = Some(UpdateNodeOperation::Demotion)
             }
             ErrorKind::EmptySnapshotChunk => disconnect = false,
+            // Only raised by the offline snapshot import.
+            ErrorKind::InvalidSnapshotArchive(_) => disconnect = false,
             ErrorKind::AlreadyThrottled(_) => {
-                op = Some(UpdateNodeOperation::Remove)
+                op = Some(UpdateNodeOperation::Remove);
+                misbehavior = Some(Misbehavior::ThrottlingViolation);
             }
             ErrorKind::Throttled(_, msg) => {
                 disconnect = false;
@@ -708,6 +723,9 @@ impl SynchronizationProtocolHandler {
             );
         }
 
+        if let Some(misbehavior) = misbehavior {
+            io.report_peer(peer, misbehavior, reason.as_str());
+        }
         if disconnect {
             io.disconnect_peer(peer, op, reason.as_str());
         }
@@ -789,7 +807,7 @@ impl SynchronizationProtocolHandler {
         }
     }
 
-    /// Request missing block bodies from random peers in batches.
+    /// Request missing block bodies from peers in batches.
     pub fn request_block_bodies(&self, io: &dyn NetworkContext) {
         let in_flight_blocks = self.request_manager.in_flight_blocks();
         let to_request_blocks: Vec<_> = self
@@ -934,12 +952,63 @@ impl SynchronizationProtocolHandler {
         // Headers may have been inserted into sync graph before as dependent
         // blocks
         header_hashes.retain(|h| !self.graph.contains_block_header(h));
-        self.request_manager.request_block_headers(
-            io,
+        for (peer, hashes) in self.schedule_download(
+            msgid::GET_BLOCK_HEADERS,
             peer,
             header_hashes,
-            None,
-        );
+        ) {
+            self.request_manager
+                .request_block_headers(io, peer, hashes, None);
+        }
+    }
+
+    /// Spread the download of `hashes` over peers by their measured
+    /// throughput in catch-up. The hashes not scheduled because all peers
+    /// are busy are left to `peer`.
+    fn schedule_download(
+        &self, msg_id: MsgId, peer: Option<NodeId>, hashes: Vec<H256>,
+    ) -> Vec<(Option<NodeId>, Vec<H256>)> {
+        let scheduler = match self.request_manager.download_scheduler(msg_id)
+        {
+            Some(scheduler) if self.catch_up_mode() && !hashes.is_empty() => {
+                scheduler
+            }
+            _ => return vec![(peer, hashes)],
+        };
+
+        let mut filter = PeerFilter::new(msg_id);
+        if msg_id == msgid::GET_BLOCKS {
+            if let Some(node_type) =
+                self.preferred_peer_node_type_for_get_block()
+            {
+                filter = filter.with_preferred_node_type(node_type);
+            }
+        }
+        let (batches, rest) =
+            scheduler.schedule(&filter.select_all(&self.syn), hashes);
+
+        let mut scheduled: Vec<_> = batches
+            .into_iter()
+            .map(|(peer, batch)| (Some(peer), batch))
+            .collect();
+        if !rest.is_empty() {
+            scheduled.push((peer, rest));
+        }
+        scheduled
+    }
+
+    /// Select a peer other than `failed_peer` to request the missing blocks
+    /// from, the best by measured throughput in catch-up.
+    fn select_block_peer(&self, failed_peer: NodeId) -> Option<NodeId> {
+        let filter = PeerFilter::new(msgid::GET_BLOCKS).exclude(failed_peer);
+        if !self.catch_up_mode() {
+            return filter.select(&self.syn);
+        }
+        let candidates = filter.select_all(&self.syn);
+        self.request_manager
+            .download_scheduler(msgid::GET_BLOCKS)
+            .and_then(|scheduler| scheduler.select_peer(&candidates))
+            .or_else(|| candidates.choose(&mut random::new()).cloned())
     }
 
     /// Try to get the block header from db. Return `true` if the block header
@@ -1073,9 +1142,7 @@ impl SynchronizationProtocolHandler {
                 need_to_relay.push(hash);
             }
         }
-        let chosen_peer = PeerFilter::new(msgid::GET_BLOCKS)
-            .exclude(task.failed_peer)
-            .select(&self.syn);
+        let chosen_peer = self.select_block_peer(task.failed_peer);
         self.blocks_received(
             io,
             task.requested,
@@ -1559,6 +1626,18 @@ impl SynchronizationProtocolHandler {
 
     fn log_statistics(&self) { self.graph.log_statistics(); }
 
+    /// Advertises the range of the local snapshots in the node record, so
+    /// that the nodes in catch-up could find the peers to sync snapshot from.
+    fn update_local_snapshot_range(&self, io: &dyn NetworkContext) {
+        let range = self
+            .graph
+            .data_man
+            .storage_manager
+            .get_storage_manager()
+            .get_snapshot_height_range();
+        io.set_local_snapshot_range(range);
+    }
+
     fn update_total_weight_delta_heartbeat(&self) {
         self.graph.update_total_weight_delta_heartbeat();
     }
@@ -1639,14 +1718,18 @@ impl SynchronizationProtocolHandler {
     )
     {
         let preferred_node_type = self.preferred_peer_node_type_for_get_block();
-        self.request_manager.request_blocks(
-            io,
-            peer_id,
-            hashes,
-            self.request_block_need_public(),
-            None,
-            preferred_node_type,
-        );
+        for (peer_id, hashes) in
+            self.schedule_download(msgid::GET_BLOCKS, peer_id, hashes)
+        {
+            self.request_manager.request_blocks(
+                io,
+                peer_id,
+                hashes,
+                self.request_block_need_public(),
+                None,
+                preferred_node_type,
+            );
+        }
     }
 
     /// Try to get the block from db. Return `true` if the block exists in db or
@@ -1701,6 +1784,7 @@ impl SynchronizationProtocolHandler {
             self.request_block_need_public(),
             delay,
             preferred_node_type_for_block_request,
+            self.catch_up_mode(),
         )
     }
 
@@ -1738,6 +1822,13 @@ impl NetworkProtocolHandler for SynchronizationProtocolHandler {
     }
 
     fn initialize(&self, io: &dyn NetworkContext) {
+        match self.node_type() {
+            NodeType::Archive => io.set_local_node_type(NODE_TAG_ARCHIVE),
+            NodeType::Full => io.set_local_node_type(NODE_TAG_FULL),
+            _ => {}
+        }
+        self.update_local_snapshot_range(io);
+
         io.register_timer(TX_TIMER, self.protocol_config.send_tx_period)
             .expect("Error registering transactions timer");
         io.register_timer(
@@ -1877,6 +1968,7 @@ impl NetworkProtocolHandler for SynchronizationProtocolHandler {
             }
             CHECK_CATCH_UP_MODE_TIMER => {
                 self.update_sync_phase(io);
+                self.update_local_snapshot_range(io);
             }
             LOG_STATISTIC_TIMER => {
                 self.log_statistics();
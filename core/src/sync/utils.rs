@@ -27,7 +27,9 @@ use cfx_types::{address_util::AddressUtil, Address, H256, U256};
 use core::str::FromStr;
 use parking_lot::Mutex;
 use primitives::{Block, BlockHeaderBuilder};
-use std::{collections::HashMap, path::Path, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap, fs, path::Path, sync::Arc, thread, time::Duration,
+};
 use threadpool::ThreadPool;
 
 pub fn create_simple_block_impl(
@@ -160,6 +162,30 @@ pub fn initialize_synchronization_graph_with_data_manager(
     data_man: Arc<BlockDataManager>, beta: u64, h: u64, tcr: u64, tcb: u64,
     era_epoch_count: u64, pow: Arc<PowComputer>, vm: VmFactory,
 ) -> (Arc<SynchronizationGraph>, Arc<ConsensusGraph>)
+{
+    initialize_synchronization_graph_with_execution_config(
+        data_man,
+        beta,
+        h,
+        tcr,
+        tcb,
+        era_epoch_count,
+        pow,
+        vm,
+        ConsensusExecutionConfiguration {
+            executive_trace: false,
+        },
+    )
+}
+
+/// The execution is skipped in the bench mode of the consensus graph, so
+/// `execution_config` only applies to the epochs executed explicitly, e.g. by
+/// `initialize_executed_chain`.
+pub fn initialize_synchronization_graph_with_execution_config(
+    data_man: Arc<BlockDataManager>, beta: u64, h: u64, tcr: u64, tcb: u64,
+    era_epoch_count: u64, pow: Arc<PowComputer>, vm: VmFactory,
+    execution_config: ConsensusExecutionConfiguration,
+) -> (Arc<SynchronizationGraph>, Arc<ConsensusGraph>)
 {
     let machine = Arc::new(new_machine_with_builtin(Default::default(), vm));
     let verification_config = VerificationConfig::new(
@@ -223,9 +249,7 @@ pub fn initialize_synchronization_graph_with_data_manager(
         pow_config.clone(),
         pow.clone(),
         notifications.clone(),
-        ConsensusExecutionConfiguration {
-            executive_trace: false,
-        },
+        execution_config,
         verification_config.clone(),
         NodeType::Archive,
     ));
@@ -273,3 +297,100 @@ pub fn initialize_synchronization_graph(
 
     (sync, consensus, data_man, genesis_block)
 }
+
+/// Initializes the graphs in `db_dir` with a chain of `block_count` blocks
+/// after the genesis, and executes the first `executed_epoch_count` epochs.
+/// This method is only used in tests.
+pub fn initialize_executed_chain(
+    db_dir: &str, block_count: u64, executed_epoch_count: u64,
+    executive_trace: bool,
+) -> (
+    Arc<SynchronizationGraph>,
+    Arc<ConsensusGraph>,
+    Arc<BlockDataManager>,
+)
+{
+    let vm = VmFactory::new(1024 * 32);
+    let pow = Arc::new(PowComputer::new(true));
+    let (data_man, genesis_block) = initialize_data_manager(
+        db_dir,
+        DbType::Rocksdb,
+        pow.clone(),
+        vm.clone(),
+    );
+    let (sync, consensus) =
+        initialize_synchronization_graph_with_execution_config(
+            data_man.clone(),
+            1,
+            1,
+            1,
+            1,
+            50000,
+            pow,
+            vm,
+            ConsensusExecutionConfiguration { executive_trace },
+        );
+
+    let mut parent_hash = genesis_block.hash();
+    for height in 1..=block_count {
+        let (hash, mut block) = create_simple_block(
+            sync.clone(),
+            parent_hash,
+            vec![],
+            height,
+            1,
+            false,
+        );
+        sync.insert_block_header(
+            &mut block.block_header,
+            false, // need_to_verify
+            true,  // bench_mode
+            false, // insert_to_consensus
+            true,  // persistent
+        );
+        sync.insert_block(
+            block, false, /* need_to_verify */
+            true,  /* persistent */
+            false, /* recover_from_db */
+        );
+        parent_hash = hash;
+    }
+    while sync.is_consensus_worker_busy() {
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    // The execution is skipped in the bench mode of the consensus graph.
+    {
+        let inner = &mut *consensus.inner.write();
+        assert_eq!(inner.get_pivot_height(), block_count + 1);
+        for height in 1..=executed_epoch_count {
+            let epoch_arena_index = inner.get_pivot_block_arena_index(height);
+            let reward_execution_info = consensus
+                .executor
+                .get_reward_execution_info(inner, epoch_arena_index);
+            consensus.executor.handler.compute_epoch(
+                &inner.arena[epoch_arena_index].hash,
+                &inner.get_epoch_block_hashes(epoch_arena_index),
+                // Each epoch has a single block after the genesis.
+                height, /* start_block_number */
+                &reward_execution_info,
+                true,  /* on_local_pivot */
+                None,  /* debug_record */
+                false, /* force_recompute */
+            );
+        }
+    }
+    (sync, consensus, data_man)
+}
+
+/// Removes the db directory of a test after the graphs are dropped, retrying
+/// while the db is still being closed.
+pub fn remove_test_db_dir(db_dir: &str) {
+    let mut retry = 3;
+    while let Err(e) = fs::remove_dir_all(db_dir) {
+        println!("failed to remove directory {}, err = {:?}", db_dir, e);
+        assert!(retry > 0);
+        retry -= 1;
+        thread::sleep(Duration::from_millis(300));
+    }
+}
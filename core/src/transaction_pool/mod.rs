@@ -630,6 +630,13 @@ impl TransactionPool {
         )
     }
 
+    /// Return the gas price at `percentile` of the transactions in the ready
+    /// pool.
+    pub fn ready_gas_price_percentile(&self, percentile: f64) -> Option<U256> {
+        let inner = self.inner.read();
+        inner.ready_gas_price_percentile(percentile)
+    }
+
     /// content retrieves the ready and deferred transactions.
     pub fn content(
         &self, address: Option<Address>,
@@ -4,12 +4,14 @@ use super::{
     impls::TreapMap,
     nonce_pool::{InsertResult, NoncePool, TxWithReadyInfo},
 };
+use crate::consensus::fee_history::percentile_of_sorted;
 use cfx_statedb::Result as StateDbResult;
 use cfx_types::{address_util::AddressUtil, Address, H256, U256};
 use malloc_size_of_derive::MallocSizeOf as DeriveMallocSizeOf;
 use metrics::{
     register_meter_with_group, Counter, CounterUsize, Meter, MeterTimer,
 };
+use parking_lot::Mutex;
 use primitives::{
     Account, Action, SignedTransaction, TransactionWithSignature,
 };
@@ -144,6 +146,10 @@ struct ReadyAccountPool {
     treap: TreapMap<Address, Arc<SignedTransaction>, WeightType>,
     tx_weight_scaling: u64,
     tx_weight_exp: u8,
+    /// The sorted gas prices of `treap`, computed on demand and invalidated
+    /// whenever the ready transactions change.
+    #[ignore_malloc_size_of = "cache of the gas prices in treap"]
+    sorted_gas_prices_cache: Mutex<Option<Arc<Vec<U256>>>>,
 }
 
 impl ReadyAccountPool {
@@ -152,6 +158,7 @@ impl ReadyAccountPool {
             treap: TreapMap::new(),
             tx_weight_scaling,
             tx_weight_exp,
+            sorted_gas_prices_cache: Mutex::new(None),
         }
     }
 
@@ -168,7 +175,11 @@ impl ReadyAccountPool {
     }
 
     fn remove(&mut self, address: &Address) -> Option<Arc<SignedTransaction>> {
-        self.treap.remove(address)
+        let removed = self.treap.remove(address);
+        if removed.is_some() {
+            *self.sorted_gas_prices_cache.get_mut() = None;
+        }
+        removed
     }
 
     fn update(
@@ -202,9 +213,26 @@ impl ReadyAccountPool {
             weight *= base_weight;
         }
 
+        *self.sorted_gas_prices_cache.get_mut() = None;
         self.treap.insert(tx.sender(), tx.clone(), weight)
     }
 
+    /// The gas prices of the ready transactions, sorted in ascending order.
+    /// The result is cached until the ready transactions change, so that
+    /// repeated gas price suggestions do not sort the whole pool each time.
+    fn sorted_gas_prices(&self) -> Arc<Vec<U256>> {
+        let mut cache = self.sorted_gas_prices_cache.lock();
+        if let Some(gas_prices) = &*cache {
+            return gas_prices.clone();
+        }
+        let mut gas_prices: Vec<U256> =
+            self.treap.iter().map(|(_, tx)| tx.gas_price).collect();
+        gas_prices.sort();
+        let gas_prices = Arc::new(gas_prices);
+        *cache = Some(gas_prices.clone());
+        gas_prices
+    }
+
     fn pop(&mut self) -> Option<Arc<SignedTransaction>> {
         if self.treap.len() == 0 {
             return None;
@@ -686,6 +714,15 @@ impl TransactionPoolInner {
         }
     }
 
+    /// Return the gas price at `percentile` of the transactions in the ready
+    /// pool, or `None` if the ready pool is empty.
+    pub fn ready_gas_price_percentile(&self, percentile: f64) -> Option<U256> {
+        percentile_of_sorted(
+            &self.ready_account_pool.sorted_gas_prices(),
+            percentile,
+        )
+    }
+
     /// content retrieves the ready and deferred transactions.
     pub fn content(
         &self, address: Option<Address>,
@@ -820,7 +857,9 @@ impl TransactionPoolInner {
 
 #[cfg(test)]
 mod test_transaction_pool_inner {
-    use super::{DeferredPool, InsertResult, TxWithReadyInfo};
+    use super::{
+        DeferredPool, InsertResult, ReadyAccountPool, TxWithReadyInfo,
+    };
     use cfx_types::{Address, U256};
     use keylib::{Generator, KeyPair, Random};
     use primitives::{Action, SignedTransaction, Transaction};
@@ -1072,4 +1111,33 @@ mod test_transaction_pool_inner {
             None
         );
     }
+
+    #[test]
+    fn test_ready_pool_gas_price_percentile() {
+        let mut ready_pool = ReadyAccountPool::new(1, 1);
+        assert_eq!(*ready_pool.sorted_gas_prices(), Vec::<U256>::new());
+
+        let mut senders = Vec::new();
+        for gas_price in &[30, 10, 20] {
+            let sender = Random.generate().unwrap();
+            ready_pool.insert(new_test_tx(&sender, 0, *gas_price, 100));
+            senders.push(sender);
+        }
+        assert_eq!(
+            *ready_pool.sorted_gas_prices(),
+            vec![U256::from(10), U256::from(20), U256::from(30)]
+        );
+
+        // The cached prices are refreshed after the pool changes.
+        ready_pool.remove(&senders[1].address());
+        assert_eq!(
+            *ready_pool.sorted_gas_prices(),
+            vec![U256::from(20), U256::from(30)]
+        );
+        ready_pool.insert(new_test_tx(&senders[1], 0, 40, 100));
+        assert_eq!(
+            *ready_pool.sorted_gas_prices(),
+            vec![U256::from(20), U256::from(30), U256::from(40)]
+        );
+    }
 }
@@ -66,6 +66,13 @@ mod impls {
         fn get_original_storage_root_with_proof(
             &self, address: &Address,
         ) -> Result<(StorageRoot, StorageRootProof)>;
+
+        /// Read a page of at most `limit` key-values with `key_prefix` from
+        /// `start_key`, and the key of the next page if any.
+        fn read_original_range(
+            &self, key_prefix: Option<StorageKey>, start_key: &[u8],
+            limit: usize, distinct_prefix_len: Option<usize>,
+        ) -> Result<(Vec<MptKeyValue>, Option<Vec<u8>>)>;
     }
 
     pub trait StateDbCheckpointMethods {
@@ -520,6 +527,19 @@ mod impls {
                 .get_node_merkle_all_versions::<WithProof>(key)
                 .map_err(Into::into)
         }
+
+        fn read_original_range(
+            &self, key_prefix: Option<StorageKey>, start_key: &[u8],
+            limit: usize, distinct_prefix_len: Option<usize>,
+        ) -> Result<(Vec<MptKeyValue>, Option<Vec<u8>>)>
+        {
+            Ok(self.storage.read_range(
+                key_prefix,
+                start_key,
+                limit,
+                distinct_prefix_len,
+            )?)
+        }
     }
 
     impl<Storage: StorageStateTrait> StateDbCheckpointMethods for StateDb<Storage> {
@@ -7,7 +7,7 @@ use super::{
     CacheAlgorithm, CacheIndexTrait, CacheStoreUtil, MyInto, PrimitiveNum,
 };
 use malloc_size_of_derive::MallocSizeOf as MallocSizeOfDerive;
-use std::{mem::replace, vec::Vec};
+use std::{cmp::min, mem::replace, vec::Vec};
 
 #[derive(Clone, Copy, MallocSizeOfDerive)]
 pub struct LRUHandle<PosT: PrimitiveNum> {
@@ -360,6 +360,20 @@ impl<PosT: PrimitiveNum, CacheIndexT: CacheIndexTrait> LRU<PosT, CacheIndexT> {
         return &mut self.get_unchecked_mut(pos).cache_index;
     }
 
+    /// Returns at most `limit` cache indices, from the most recently accessed
+    /// one.
+    pub fn most_recently_accessed(&self, limit: usize) -> Vec<CacheIndexT> {
+        let count = min(limit, MyInto::<usize>::into(self.size));
+        let mut cache_indices = Vec::with_capacity(count);
+        let mut pos = self.head;
+        for _ in 0..count {
+            let node = &self.recent[MyInto::<usize>::into(pos)];
+            cache_indices.push(node.cache_index);
+            pos = node.next;
+        }
+        cache_indices
+    }
+
     pub fn has_space(&self) -> bool { self.capacity != self.size }
 
     pub fn is_full(&self) -> bool { self.capacity == self.size }
@@ -634,6 +634,105 @@ impl CowNodeRef {
         Ok(())
     }
 
+    /// Recompute the merkle of the subtree from the trie nodes without
+    /// trusting the stored merkles, and return it with whether the subtree is
+    /// corrupted. The key prefix of a node is pushed to corrupted_key_prefixes
+    /// when its stored merkle doesn't match while its children do, or when a
+    /// child fails to load. The results of committed nodes are kept in
+    /// verified_nodes, so that the nodes shared by the tries of different
+    /// epochs are verified only once.
+    ///
+    /// parent_node_path_steps_plus_one is defined the same as in
+    /// get_or_compute_merkle.
+    pub fn verify_merkle_internal(
+        &self, owned_node_set: &OwnedNodeSet, trie: &DeltaMpt,
+        guarded_trie_node: GuardedMaybeOwnedTrieNodeAsCowCallParam,
+        key_prefix: CompressedPathRaw, parent_node_path_steps_plus_one: u16,
+        verified_nodes: &mut HashMap<DeltaMptDbKey, (MerkleHash, bool)>,
+        corrupted_key_prefixes: &mut Vec<CompressedPathRaw>,
+        db: &mut DeltaDbOwnedReadTraitObj,
+    ) -> Result<(MerkleHash, bool)>
+    {
+        let trie_node = guarded_trie_node.as_ref().as_ref();
+        let compressed_path =
+            CompressedPathRaw::from(trie_node.compressed_path_ref());
+        let node_path_steps =
+            parent_node_path_steps_plus_one + compressed_path.path_steps();
+        let maybe_value = trie_node.value_clone().into_option();
+        let stored_merkle = trie_node.get_merkle().clone();
+        let children_table = trie_node.children_table.clone();
+        // Free the lock for trie_node.
+        drop(guarded_trie_node);
+
+        let node_memory_manager = trie.get_node_memory_manager();
+        let allocator = node_memory_manager.get_allocator();
+        let mut children_merkles = [MERKLE_NULL_NODE; CHILDREN_COUNT];
+        let mut children_corrupted = false;
+        for (i, node_ref) in children_table.iter() {
+            let node_ref: NodeRefDeltaMpt = (*node_ref).into();
+            if let NodeRefDeltaMpt::Committed { db_key } = &node_ref {
+                if let Some((merkle, corrupted)) = verified_nodes.get(db_key) {
+                    children_merkles[i as usize] = *merkle;
+                    children_corrupted |= *corrupted;
+                    continue;
+                }
+            }
+            let mut cow_child_node =
+                Self::new(node_ref.clone(), owned_node_set, self.mpt_id);
+            let child_node = match cow_child_node.get_trie_node(
+                node_memory_manager,
+                &allocator,
+                db,
+            ) {
+                Ok(child_node) => child_node,
+                Err(_) => {
+                    children_corrupted = true;
+                    corrupted_key_prefixes.push(key_prefix.clone());
+                    continue;
+                }
+            };
+            let child_key_prefix = CompressedPathRaw::join_connected_paths(
+                &key_prefix,
+                i,
+                &child_node.compressed_path_ref(),
+            );
+            let child_node = GuardedValue::take(child_node);
+            let (merkle, corrupted) = cow_child_node.verify_merkle_internal(
+                owned_node_set,
+                trie,
+                child_node,
+                child_key_prefix,
+                // +1 for the child_index.
+                node_path_steps + 1,
+                verified_nodes,
+                corrupted_key_prefixes,
+                db,
+            )?;
+            if let NodeRefDeltaMpt::Committed { db_key } = node_ref {
+                verified_nodes.insert(db_key, (merkle, corrupted));
+            }
+            children_merkles[i as usize] = merkle;
+            children_corrupted |= corrupted;
+        }
+
+        let merkle = compute_merkle(
+            compressed_path.as_ref(),
+            (parent_node_path_steps_plus_one % 2) == 1,
+            if children_table.get_children_count() == 0 {
+                None
+            } else {
+                Some(&children_merkles)
+            },
+            maybe_value.as_ref().map(|value| &**value),
+        );
+        let corrupted = merkle != stored_merkle;
+        if corrupted && !children_corrupted {
+            corrupted_key_prefixes.push(key_prefix);
+        }
+
+        Ok((merkle, corrupted || children_corrupted))
+    }
+
     /// Recursively commit dirty nodes.
     pub fn commit_dirty_recursively<
         Transaction: BorrowMut<DeltaDbTransactionTraitObj>,
@@ -920,6 +1019,7 @@ use super::{
         state::ChildrenMerkleMap,
     },
     node_memory_manager::*,
+    node_ref_map::DeltaMptDbKey,
     owned_node_set::OwnedNodeSet,
     AtomicCommitTransaction, DeltaMpt, *,
 };
@@ -927,6 +1027,6 @@ use parking_lot::MutexGuard;
 use primitives::{MerkleHash, MptValue, MERKLE_NULL_NODE};
 use rlp::*;
 use std::{
-    borrow::BorrowMut, cell::Cell, hint::unreachable_unchecked, ops::Deref,
-    sync::atomic::Ordering,
+    borrow::BorrowMut, cell::Cell, collections::HashMap,
+    hint::unreachable_unchecked, ops::Deref, sync::atomic::Ordering,
 };
@@ -18,6 +18,7 @@ pub(super) mod row_number;
 /// giant lock.
 mod slab;
 pub mod subtrie_visitor;
+pub(super) mod warm_cache;
 
 #[cfg(test)]
 mod tests;
@@ -138,6 +139,10 @@ impl MultiVersionMerklePatriciaTrie {
 
     pub fn get_mpt_id(&self) -> DeltaMptId { self.mpt_id }
 
+    pub fn get_snapshot_epoch_id(&self) -> &EpochId {
+        &self.delta_mpts_releaser.snapshot_epoch_id
+    }
+
     pub fn start_commit(
         &self,
     ) -> Result<AtomicCommitTransaction<Box<DeltaDbTransactionTraitObj>>> {
@@ -358,6 +363,27 @@ impl MultiVersionMerklePatriciaTrie {
         }
     }
 
+    /// Loads the committed nodes into the cache, in the given order so that
+    /// the last one becomes the most recently accessed. Returns the number of
+    /// nodes loaded from db.
+    pub fn preload_nodes<'a>(
+        &self, db_keys: impl Iterator<Item = &'a DeltaMptDbKey>,
+    ) -> Result<usize> {
+        let arc_db = self.get_arc_db()?;
+        let mut db = arc_db.to_owned_read()?;
+        let mut loaded = 0;
+        for db_key in db_keys {
+            if self.node_memory_manager.preload_node(
+                &mut *db,
+                self.mpt_id,
+                *db_key,
+            )? {
+                loaded += 1;
+            }
+        }
+        Ok(loaded)
+    }
+
     pub fn log_usage(&self) { self.node_memory_manager.log_usage(); }
 }
 
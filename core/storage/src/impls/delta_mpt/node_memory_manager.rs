@@ -37,6 +37,17 @@ pub type DeltaMptsCacheManager =
 
 impl CacheIndexTrait for DeltaMptDbKey {}
 
+lazy_static! {
+    static ref WARM_START_CACHE_HIT_METER: Arc<dyn Meter> =
+        register_meter_with_group("delta_mpts_cache", "warm_start_hit");
+    static ref WARM_START_CACHE_MISS_METER: Arc<dyn Meter> =
+        register_meter_with_group("delta_mpts_cache", "warm_start_miss");
+    static ref COLD_START_CACHE_HIT_METER: Arc<dyn Meter> =
+        register_meter_with_group("delta_mpts_cache", "cold_start_hit");
+    static ref COLD_START_CACHE_MISS_METER: Arc<dyn Meter> =
+        register_meter_with_group("delta_mpts_cache", "cold_start_miss");
+}
+
 #[derive(MallocSizeOfDerive)]
 pub struct NodeMemoryManager<
     CacheAlgoDataT: CacheAlgoDataTrait,
@@ -57,6 +68,12 @@ pub struct NodeMemoryManager<
     /// that the get is always successful when exiting the critical
     /// section.
     db_load_lock: Mutex<()>,
+    /// Whether the cache is preloaded with the nodes saved at the last
+    /// graceful shutdown, which decides the meters of cache hits and misses.
+    warm_start: AtomicBool,
+    /// The cache hits and misses which aren't marked on the meters yet.
+    unmetered_cache_hits: AtomicUsize,
+    unmetered_cache_misses: AtomicUsize,
 
     // FIXME use other atomic integer types as they are in rust stable.
     db_load_counter: AtomicUsize,
@@ -71,6 +88,9 @@ impl<
         CacheAlgorithmT: CacheAlgorithm<CacheAlgoData = CacheAlgoDataT>,
     > NodeMemoryManager<CacheAlgoDataT, CacheAlgorithmT>
 {
+    /// Marking a meter takes a lock, so the cache accesses are counted and
+    /// marked on the meters in batches of this size.
+    const CACHE_ACCESS_METER_BATCH: usize = 4096;
     /// In disk hybrid solution, the nodes in memory are merely LRU cache of
     /// non-leaf nodes. So the memory consumption is (192B Trie + 10B R_LFU +
     /// 12B*4x LRU) * number of nodes + 200M * 4B NodeRef. 5GB + extra 800M
@@ -122,6 +142,9 @@ impl<
                 cache_algorithm,
             }),
             db_load_lock: Default::default(),
+            warm_start: Default::default(),
+            unmetered_cache_hits: Default::default(),
+            unmetered_cache_misses: Default::default(),
             db_load_counter: Default::default(),
             uncached_leaf_db_loads: Default::default(),
             uncached_leaf_load_times: Default::default(),
@@ -180,6 +203,101 @@ impl<
         }
     }
 
+    pub fn set_warm_start(&self) {
+        // The accesses so far are counted as cold start.
+        self.flush_cache_access_meters();
+        self.warm_start.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_warm_start(&self) -> bool {
+        self.warm_start.load(Ordering::Relaxed)
+    }
+
+    fn cache_access_meter(&self, is_hit: bool) -> &'static dyn Meter {
+        match (self.is_warm_start(), is_hit) {
+            (true, true) => &**WARM_START_CACHE_HIT_METER,
+            (true, false) => &**WARM_START_CACHE_MISS_METER,
+            (false, true) => &**COLD_START_CACHE_HIT_METER,
+            (false, false) => &**COLD_START_CACHE_MISS_METER,
+        }
+    }
+
+    fn unmetered_cache_accesses(&self, is_hit: bool) -> &AtomicUsize {
+        if is_hit {
+            &self.unmetered_cache_hits
+        } else {
+            &self.unmetered_cache_misses
+        }
+    }
+
+    fn mark_cache_access(&self, is_hit: bool) {
+        let unmetered = self.unmetered_cache_accesses(is_hit);
+        if unmetered.fetch_add(1, Ordering::Relaxed) + 1
+            >= Self::CACHE_ACCESS_METER_BATCH
+        {
+            self.flush_cache_access_meter(is_hit);
+        }
+    }
+
+    fn flush_cache_access_meter(&self, is_hit: bool) {
+        let count = self
+            .unmetered_cache_accesses(is_hit)
+            .swap(0, Ordering::Relaxed);
+        if count != 0 {
+            self.cache_access_meter(is_hit).mark(count);
+        }
+    }
+
+    /// Marks the counted cache accesses on the meters.
+    pub fn flush_cache_access_meters(&self) {
+        self.flush_cache_access_meter(/* is_hit = */ true);
+        self.flush_cache_access_meter(/* is_hit = */ false);
+    }
+
+    pub fn is_cached(&self, mpt_id: DeltaMptId, db_key: DeltaMptDbKey) -> bool {
+        self.cache
+            .lock()
+            .node_ref_map
+            .get_cache_info((mpt_id, db_key))
+            .and_then(|x| x.get_slot())
+            .is_some()
+    }
+
+    /// Loads the committed node into the cache if it's not cached, without
+    /// counting it as a cache access. Returns whether the node is loaded from
+    /// db. A node which doesn't exist in db is skipped.
+    pub fn preload_node(
+        &self, db: &mut DeltaDbOwnedReadTraitObj, mpt_id: DeltaMptId,
+        db_key: DeltaMptDbKey,
+    ) -> Result<bool>
+    {
+        self.enlarge()?;
+        let allocator = self.get_allocator();
+        let _db_load_mutex = self.db_load_lock.lock();
+        if self.is_cached(mpt_id, db_key) {
+            return Ok(false);
+        }
+        let rlp_bytes = match db.get_mut_with_number_key(db_key.into())? {
+            None => return Ok(false),
+            Some(rlp_bytes) => rlp_bytes,
+        };
+        self.db_load_counter.fetch_add(1, Ordering::Relaxed);
+        let (mut cache_manager_locked, _) = self
+            .insert_loaded_node(
+                &allocator,
+                &self.cache,
+                rlp_bytes.as_ref(),
+                mpt_id,
+                db_key,
+            )?
+            .into();
+        self.call_cache_algorithm_access(
+            &mut *cache_manager_locked,
+            (mpt_id, db_key),
+        );
+        Ok(true)
+    }
+
     pub fn log_uncached_key_access(&self, db_load_count: i32) {
         if db_load_count != 0 {
             self.uncached_leaf_db_loads
@@ -221,7 +339,33 @@ impl<
         self.db_load_counter.fetch_add(1, Ordering::Relaxed);
         // We never save null node in db.
         let rlp_bytes = db.get_mut_with_number_key(db_key.into())?.unwrap();
-        let rlp = Rlp::new(rlp_bytes.as_ref());
+        self.insert_loaded_node(
+            allocator,
+            cache_manager,
+            rlp_bytes.as_ref(),
+            mpt_id,
+            db_key,
+        )
+    }
+
+    /// Inserts the node loaded from db into the cache.
+    fn insert_loaded_node<'c: 'a, 'a>(
+        &self, allocator: AllocatorRefRef<'a, CacheAlgoDataT>,
+        cache_manager: &'c Mutex<
+            CacheManagerDeltaMpts<CacheAlgoDataT, CacheAlgorithmT>,
+        >,
+        rlp_bytes: &[u8], mpt_id: DeltaMptId, db_key: DeltaMptDbKey,
+    ) -> Result<
+        GuardedValue<
+            MutexGuard<
+                'c,
+                CacheManagerDeltaMpts<CacheAlgoDataT, CacheAlgorithmT>,
+            >,
+            &'a TrieNodeCell<CacheAlgoDataT>,
+        >,
+    >
+    {
+        let rlp = Rlp::new(rlp_bytes);
         let mut trie_node = MemOptimizedTrieNode::decode(&rlp)?;
 
         let mut cache_manager_locked = cache_manager.lock();
@@ -437,6 +581,7 @@ impl<
                     .get_cache_info((mpt_id, *db_key))
                     .and_then(|x| x.get_slot());
 
+                let mut is_hit = true;
                 let trie_node = match maybe_cache_slot {
                     Some(cache_slot) => {
                         // Fast path.
@@ -489,6 +634,7 @@ impl<
                                 cache_manager_mut_wrapped = Some(guard);
 
                                 *is_loaded_from_db = true;
+                                is_hit = false;
 
                                 loaded_trie_node
                             }
@@ -500,6 +646,7 @@ impl<
                     cache_manager_mut_wrapped.as_mut().unwrap(),
                     (mpt_id, *db_key),
                 );
+                self.mark_cache_access(is_hit);
 
                 Ok(GuardedValue::new(cache_manager_mut_wrapped, trie_node))
             }
@@ -641,6 +788,7 @@ impl<
     }
 
     pub fn log_usage(&self) {
+        self.flush_cache_access_meters();
         self.cache.lock().log_usage();
         let allocator_ref = self.get_allocator();
         debug!(
@@ -674,6 +822,19 @@ impl<
     }
 }
 
+impl DeltaMptsNodeMemoryManager {
+    /// Returns at most `limit` cached nodes, from the most recently accessed
+    /// one.
+    pub fn most_recently_accessed_nodes(
+        &self, limit: usize,
+    ) -> Vec<(DeltaMptId, DeltaMptDbKey)> {
+        self.cache
+            .lock()
+            .cache_algorithm
+            .most_recently_accessed(limit)
+    }
+}
+
 struct NodeCacheUtil<
     'a,
     CacheAlgoDataT: CacheAlgoDataTrait,
@@ -788,6 +949,7 @@ use super::{
     NodeRefDeltaMpt,
 };
 use malloc_size_of_derive::MallocSizeOf as MallocSizeOfDerive;
+use metrics::{register_meter_with_group, Meter};
 use parking_lot::{
     Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockUpgradableReadGuard,
 };
@@ -796,5 +958,8 @@ use rlp::*;
 use std::{
     cell::UnsafeCell,
     hint::unreachable_unchecked,
-    sync::atomic::{AtomicUsize, Ordering},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
 };
@@ -4,3 +4,4 @@
 
 mod children_table;
 mod rlp_encode_decode;
+mod warm_cache;
@@ -0,0 +1,204 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+#[test]
+fn test_warm_cache_order_and_truncate() {
+    let epoch_a = EpochId::repeat_byte(1);
+    let epoch_b = EpochId::repeat_byte(2);
+    let mut warm_cache = DeltaMptsWarmCache::from_nodes(vec![
+        (epoch_a, 5),
+        (epoch_a, 3),
+        (epoch_b, 7),
+        (epoch_a, 1),
+    ]);
+    assert_eq!(warm_cache.len(), 4);
+    assert_eq!(
+        warm_cache.iter_from_least_recent().collect::<Vec<_>>(),
+        vec![
+            (&epoch_a, &[1][..]),
+            (&epoch_b, &[7][..]),
+            (&epoch_a, &[5, 3][..]),
+        ]
+    );
+
+    warm_cache.truncate(3);
+    assert_eq!(
+        warm_cache.iter_from_least_recent().collect::<Vec<_>>(),
+        vec![(&epoch_b, &[7][..]), (&epoch_a, &[5, 3][..])]
+    );
+    warm_cache.truncate(1);
+    assert_eq!(
+        warm_cache.iter_from_least_recent().collect::<Vec<_>>(),
+        vec![(&epoch_a, &[5][..])]
+    );
+}
+
+#[test]
+fn test_warm_cache_save_and_take() {
+    let dir = std::env::temp_dir()
+        .join(format!("delta_mpts_warm_cache_test_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("delta_mpts_warm_cache");
+
+    let warm_cache = DeltaMptsWarmCache::from_nodes(vec![
+        (EpochId::repeat_byte(1), 2),
+        (EpochId::repeat_byte(3), 4),
+    ]);
+    warm_cache.save(&path).unwrap();
+    assert_eq!(DeltaMptsWarmCache::take(&path).unwrap(), Some(warm_cache));
+    // The warm cache is removed once taken.
+    assert_eq!(DeltaMptsWarmCache::take(&path).unwrap(), None);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+fn storage_conf(data_dir: &str) -> StorageConfiguration {
+    // No snapshot is made, so all the nodes are in the delta mpt of the
+    // genesis snapshot.
+    let mut storage_conf = StorageConfiguration::new_default(data_dir, 10000);
+    storage_conf.delta_mpts_cache_size = 200_000;
+    storage_conf.delta_mpts_cache_start_size = 100_000;
+    storage_conf.delta_mpts_node_map_vec_size = 200_000;
+    storage_conf.delta_mpts_slab_idle_size = 20_000;
+    storage_conf
+}
+
+fn with_data_dir<F: FnOnce(&str)>(f: F) {
+    let data_dir = format!("./conflux_unit_test_data_dir{}", random::<u64>());
+    fs::create_dir_all(&data_dir).unwrap();
+    f(&data_dir);
+    fs::remove_dir_all(&data_dir).ok();
+}
+
+/// Commits the genesis state and reads it back, so that its nodes are the
+/// most recently accessed ones of the cache.
+fn populate_state(state_manager: &Arc<StateManager>) {
+    let addresses: Vec<Address> = (0..1000)
+        .map(|i| {
+            let mut address = Address::from_low_u64_be(i);
+            address.set_user_account_type_bits();
+            address
+        })
+        .collect();
+    let mut state = state_manager.get_state_for_genesis_write();
+    for address in &addresses {
+        state
+            .set(
+                StorageKey::new_account_key(address),
+                address.as_bytes().into(),
+            )
+            .unwrap();
+    }
+    let epoch_id = EpochId::repeat_byte(1);
+    state.compute_state_root().unwrap();
+    let state_root = state.commit(epoch_id).unwrap();
+
+    let state = state_manager
+        .get_state_no_commit(
+            StateIndex::new_for_readonly(&epoch_id, &state_root),
+            /* try_open = */ false,
+        )
+        .unwrap()
+        .unwrap();
+    for address in &addresses {
+        assert_eq!(
+            state
+                .get(StorageKey::new_account_key(address))
+                .unwrap()
+                .as_deref(),
+            Some(address.as_bytes())
+        );
+    }
+}
+
+#[test]
+fn test_preload_warm_cache() {
+    with_data_dir(|data_dir| {
+        let storage_conf = storage_conf(data_dir);
+        let state_manager =
+            Arc::new(StateManager::new(storage_conf.clone()).unwrap());
+        populate_state(&state_manager);
+        // The warm cache is saved at the graceful shutdown.
+        drop(state_manager);
+
+        let warm_cache =
+            DeltaMptsWarmCache::take(&storage_conf.path_delta_mpts_warm_cache)
+                .unwrap()
+                .expect("warm cache should be saved");
+        assert!(!warm_cache.is_empty());
+        warm_cache
+            .save(&storage_conf.path_delta_mpts_warm_cache)
+            .unwrap();
+
+        let state_manager = StateManager::new(storage_conf.clone()).unwrap();
+        assert!(!storage_conf.path_delta_mpts_warm_cache.exists());
+        let delta_mpt = state_manager
+            .get_storage_manager()
+            .get_delta_mpt(&NULL_EPOCH)
+            .unwrap();
+        let node_memory_manager = delta_mpt.get_node_memory_manager();
+        assert!(node_memory_manager.is_warm_start());
+
+        // The nodes are preloaded in background.
+        let all_cached = || {
+            warm_cache.iter_from_least_recent().all(
+                |(snapshot_epoch_id, db_keys)| {
+                    assert_eq!(*snapshot_epoch_id, NULL_EPOCH);
+                    db_keys.iter().all(|db_key| {
+                        node_memory_manager
+                            .is_cached(delta_mpt.get_mpt_id(), *db_key)
+                    })
+                },
+            )
+        };
+        let start = Instant::now();
+        while !all_cached() {
+            assert!(
+                start.elapsed() < Duration::from_secs(30),
+                "warm cache isn't preloaded"
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
+    });
+}
+
+#[test]
+fn test_corrupt_warm_cache() {
+    with_data_dir(|data_dir| {
+        let storage_conf = storage_conf(data_dir);
+        let state_manager =
+            Arc::new(StateManager::new(storage_conf.clone()).unwrap());
+        populate_state(&state_manager);
+        drop(state_manager);
+        assert!(storage_conf.path_delta_mpts_warm_cache.exists());
+        fs::write(&storage_conf.path_delta_mpts_warm_cache, b"corrupt")
+            .unwrap();
+
+        // A corrupt warm cache falls back to a cold start and is removed.
+        let state_manager = StateManager::new(storage_conf.clone()).unwrap();
+        assert!(!storage_conf.path_delta_mpts_warm_cache.exists());
+        assert!(!state_manager
+            .get_storage_manager()
+            .get_delta_mpt(&NULL_EPOCH)
+            .unwrap()
+            .get_node_memory_manager()
+            .is_warm_start());
+    });
+}
+
+use super::super::warm_cache::DeltaMptsWarmCache;
+use crate::{
+    state::StateTrait,
+    state_manager::{StateIndex, StateManager, StateManagerTrait},
+    StorageConfiguration,
+};
+use cfx_types::{address_util::AddressUtil, Address};
+use primitives::{EpochId, StorageKey, NULL_EPOCH};
+use rand::random;
+use std::{
+    fs,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
@@ -0,0 +1,98 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+/// The delta mpt nodes which are cached at graceful shutdown, saved to be
+/// preloaded into the cache at the next start.
+///
+/// Delta mpt ids are allocated at runtime, so the nodes are identified by the
+/// snapshot epoch id of their delta mpt and their row number in the delta db.
+#[derive(Debug, Default, PartialEq, RlpEncodable, RlpDecodable)]
+pub struct DeltaMptsWarmCache {
+    /// Consecutive nodes of the same delta mpt, in the order of cache recency
+    /// from the most recently accessed one.
+    runs: Vec<WarmCacheRun>,
+}
+
+#[derive(Debug, PartialEq, RlpEncodable, RlpDecodable)]
+struct WarmCacheRun {
+    snapshot_epoch_id: EpochId,
+    db_keys: Vec<DeltaMptDbKey>,
+}
+
+impl DeltaMptsWarmCache {
+    /// `nodes` are in the order of cache recency from the most recently
+    /// accessed one.
+    pub fn from_nodes(
+        nodes: impl IntoIterator<Item = (EpochId, DeltaMptDbKey)>,
+    ) -> Self {
+        let mut runs: Vec<WarmCacheRun> = vec![];
+        for (snapshot_epoch_id, db_key) in nodes {
+            match runs.last_mut() {
+                Some(run) if run.snapshot_epoch_id == snapshot_epoch_id => {
+                    run.db_keys.push(db_key)
+                }
+                _ => runs.push(WarmCacheRun {
+                    snapshot_epoch_id,
+                    db_keys: vec![db_key],
+                }),
+            }
+        }
+        Self { runs }
+    }
+
+    pub fn len(&self) -> usize {
+        self.runs.iter().map(|run| run.db_keys.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool { self.runs.is_empty() }
+
+    /// Keeps at most `limit` most recently accessed nodes.
+    pub fn truncate(&mut self, mut limit: usize) {
+        let mut kept_runs = 0;
+        for run in &mut self.runs {
+            if limit == 0 {
+                break;
+            }
+            run.db_keys.truncate(limit);
+            limit -= run.db_keys.len();
+            kept_runs += 1;
+        }
+        self.runs.truncate(kept_runs);
+    }
+
+    /// Returns the nodes grouped by delta mpt, from the least recently
+    /// accessed one, which is the order to load them into the cache.
+    pub fn iter_from_least_recent(
+        &self,
+    ) -> impl Iterator<Item = (&EpochId, &[DeltaMptDbKey])> {
+        self.runs
+            .iter()
+            .rev()
+            .map(|run| (&run.snapshot_epoch_id, run.db_keys.as_slice()))
+    }
+
+    /// Loads and removes the saved warm cache, so that it's never loaded
+    /// again after an unclean shutdown.
+    pub fn take(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let bytes = fs::read(path)?;
+        fs::remove_file(path)?;
+        Ok(Some(Rlp::new(&bytes).as_val()?))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, self.rlp_bytes())?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+}
+
+use super::{super::errors::*, node_ref_map::DeltaMptDbKey};
+use primitives::EpochId;
+use rlp::{Encodable, Rlp};
+use rlp_derive::{RlpDecodable, RlpEncodable};
+use std::{fs, path::Path};
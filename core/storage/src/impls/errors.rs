@@ -101,6 +101,16 @@ error_chain! {
             display("Attempting to create or modify a Snapshot which already exists."),
         }
 
+        SnapshotDbBackendMismatch(found: &'static str, configured: &'static str) {
+            description("The snapshot dir has snapshots of another snapshot db backend."),
+            display("The snapshot dir has {} snapshots but the snapshot db backend is {}, convert them with `conflux snapshot convert` first.", found, configured),
+        }
+
+        SnapshotConvertMerkleRootMismatch {
+            description("The converted snapshot has a different merkle root."),
+            display("The converted snapshot has a different merkle root."),
+        }
+
         SnapshotMPTTrieNodeNotFound {
             description("Trie node not found when loading Snapshot MPT."),
             display("Trie node not found when loading Snapshot MPT."),
@@ -135,6 +145,16 @@ error_chain! {
             display("The operation \"{}\" isn't possible on freshly synced snapshot.", op),
         }
 
+        UnsupportedByStateHistory(op: &'static str) {
+            description("The operation isn't possible on a state read from the state history."),
+            display("The operation \"{}\" isn't possible on a state read from the state history.", op),
+        }
+
+        StateHistoryIncomplete(start_height: u64) {
+            description("The state history doesn't have the requested value."),
+            display("The state history starts from height {} and doesn't have the value set before it.", start_height),
+        }
+
         InvalidTrieProof {
             description("Trie proof is invalid."),
             display("Trie proof is invalid."),
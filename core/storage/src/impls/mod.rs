@@ -11,10 +11,12 @@ pub(super) mod proof_merger;
 pub(super) mod recording_storage;
 pub(super) mod snapshot_sync;
 pub(super) mod state;
+pub(super) mod state_history;
 pub(super) mod state_manager;
 pub(super) mod state_proof;
 pub(super) mod storage_db;
 pub(super) mod storage_manager;
+pub(super) mod storage_verifier;
 
 pub mod defaults {
     pub use super::delta_mpt::DEFAULT_NODE_MAP_SIZE;
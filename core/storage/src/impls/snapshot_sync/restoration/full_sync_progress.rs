@@ -0,0 +1,202 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! Persisted progress of a full sync restoration.
+//!
+//! The manifest of the snapshot being restored is written at the head of a
+//! log next to the temp snapshot db, and a record is appended for each chunk
+//! once it's committed to the db, with the boundary nodes and the boundary
+//! subtree sizes needed to finalize the snapshot. An interrupted restoration
+//! is resumed from the log. A chunk committed but not logged before the
+//! interruption is simply restored again, which rewrites the same key-values
+//! and nodes into the db.
+//!
+//! Each record is the rlp prefixed by its length in 4 bytes, so that a record
+//! truncated by the interruption is detected and dropped.
+
+/// The manifest of a snapshot being restored.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FullSyncManifest {
+    pub snapshot_epoch_id: EpochId,
+    pub merkle_root: MerkleHash,
+    pub chunk_boundaries: Vec<Vec<u8>>,
+    pub chunk_boundary_proofs: Vec<TrieProof>,
+}
+
+impl Encodable for FullSyncManifest {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(4)
+            .append(&self.snapshot_epoch_id)
+            .append(&self.merkle_root)
+            .append_list::<Vec<u8>, _>(&self.chunk_boundaries)
+            .append_list(&self.chunk_boundary_proofs);
+    }
+}
+
+impl Decodable for FullSyncManifest {
+    fn decode(rlp: &Rlp) -> std::result::Result<Self, DecoderError> {
+        Ok(Self {
+            snapshot_epoch_id: rlp.val_at(0)?,
+            merkle_root: rlp.val_at(1)?,
+            chunk_boundaries: rlp.list_at(2)?,
+            chunk_boundary_proofs: rlp.list_at(3)?,
+        })
+    }
+}
+
+/// The boundary nodes and the boundary subtree sizes of a restored chunk.
+#[derive(Default)]
+pub struct RestoredChunk {
+    pub chunk_index: usize,
+    pub boundary_nodes: Vec<(CompressedPathRaw, SnapshotMptNode)>,
+    /// The subtree size of each (parent node, child index).
+    pub boundary_subtree_sizes: Vec<(MerkleHash, u8, u64)>,
+}
+
+impl Encodable for RestoredChunk {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(3).append(&self.chunk_index);
+        s.begin_list(self.boundary_nodes.len());
+        for (path, node) in &self.boundary_nodes {
+            s.begin_list(2).append(path).append(&node.0);
+        }
+        s.begin_list(self.boundary_subtree_sizes.len());
+        for (parent_node, child_index, subtree_size) in
+            &self.boundary_subtree_sizes
+        {
+            s.begin_list(3)
+                .append(parent_node)
+                .append(child_index)
+                .append(subtree_size);
+        }
+    }
+}
+
+impl Decodable for RestoredChunk {
+    fn decode(rlp: &Rlp) -> std::result::Result<Self, DecoderError> {
+        let mut boundary_nodes = Vec::new();
+        for item in rlp.at(1)?.iter() {
+            boundary_nodes.push((item.val_at(0)?, item.val_at(1)?));
+        }
+        let mut boundary_subtree_sizes = Vec::new();
+        for item in rlp.at(2)?.iter() {
+            boundary_subtree_sizes.push((
+                item.val_at(0)?,
+                item.val_at(1)?,
+                item.val_at(2)?,
+            ));
+        }
+        Ok(Self {
+            chunk_index: rlp.val_at(0)?,
+            boundary_nodes,
+            boundary_subtree_sizes,
+        })
+    }
+}
+
+pub struct FullSyncProgress {
+    file: File,
+}
+
+impl FullSyncProgress {
+    /// Creates a new log for `manifest`, which replaces the existing one.
+    pub fn create(path: &Path, manifest: &FullSyncManifest) -> Result<Self> {
+        let mut progress = Self {
+            file: File::create(path)?,
+        };
+        progress.write_record(&manifest.rlp_bytes())?;
+        Ok(progress)
+    }
+
+    /// Loads the manifest from the log, or returns `None` if there is no log
+    /// or the manifest is corrupted.
+    pub fn load_manifest(path: &Path) -> Result<Option<FullSyncManifest>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let mut file = File::open(path)?;
+        let mut len_bytes = [0u8; 4];
+        if file.read_exact(&mut len_bytes).is_err() {
+            return Ok(None);
+        }
+        let mut record = vec![0u8; u32::from_be_bytes(len_bytes) as usize];
+        if file.read_exact(&mut record).is_err() {
+            return Ok(None);
+        }
+        Ok(rlp::decode(&record).ok())
+    }
+
+    /// Loads the manifest and the restored chunks from the log, and reopens
+    /// the log to append the chunks restored later. Returns `None` if there
+    /// is no log or the manifest is corrupted.
+    pub fn resume(
+        path: &Path,
+    ) -> Result<Option<(Self, FullSyncManifest, Vec<RestoredChunk>)>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let data = fs::read(path)?;
+        let mut manifest = None;
+        let mut chunks = Vec::new();
+        let mut offset = 0;
+        while data.len() >= offset + 4 {
+            let mut len_bytes = [0u8; 4];
+            len_bytes.copy_from_slice(&data[offset..offset + 4]);
+            let end = offset + 4 + u32::from_be_bytes(len_bytes) as usize;
+            if end > data.len() {
+                break;
+            }
+            let record = &data[offset + 4..end];
+            if manifest.is_none() {
+                match rlp::decode(record) {
+                    Ok(decoded) => manifest = Some(decoded),
+                    Err(_) => return Ok(None),
+                }
+            } else {
+                match rlp::decode(record) {
+                    Ok(chunk) => chunks.push(chunk),
+                    Err(_) => break,
+                }
+            }
+            offset = end;
+        }
+        let manifest = match manifest {
+            Some(manifest) => manifest,
+            None => return Ok(None),
+        };
+
+        // Drop the truncated or corrupted records, if any.
+        let file = OpenOptions::new().write(true).open(path)?;
+        file.set_len(offset as u64)?;
+        let mut progress = Self { file };
+        progress.file.seek(SeekFrom::End(0))?;
+        Ok(Some((progress, manifest, chunks)))
+    }
+
+    pub fn append(&mut self, chunk: &RestoredChunk) -> Result<()> {
+        self.write_record(&chunk.rlp_bytes())
+    }
+
+    fn write_record(&mut self, record: &[u8]) -> Result<()> {
+        let mut bytes = Vec::with_capacity(record.len() + 4);
+        bytes.extend_from_slice(&(record.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(record);
+        self.file.write_all(&bytes)?;
+        self.file.sync_data()?;
+        Ok(())
+    }
+}
+
+use crate::{
+    impls::{errors::*, merkle_patricia_trie::CompressedPathRaw},
+    storage_db::SnapshotMptNode,
+    TrieProof,
+};
+use primitives::{EpochId, MerkleHash};
+use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path,
+};
@@ -15,6 +15,8 @@ pub struct FullSyncVerifier<SnapshotDbManager: SnapshotDbManagerTrait> {
     chunk_index_by_upper_key: HashMap<Vec<u8>, usize>,
 
     temp_snapshot_db: SnapshotDbManager::SnapshotDb,
+    /// The log of the restored chunks, to resume an interrupted restoration.
+    progress: Option<FullSyncProgress>,
 }
 
 impl<SnapshotDbManager: SnapshotDbManagerTrait>
@@ -54,41 +56,162 @@ impl<SnapshotDbManager: SnapshotDbManagerTrait>
                 .insert(chunk_boundary.clone(), chunk_index);
         }
 
-        Ok(Self {
-            number_chunks,
+        let manifest = FullSyncManifest {
+            snapshot_epoch_id: *epoch_id,
             merkle_root,
             chunk_boundaries,
             chunk_boundary_proofs,
+        };
+        let mut restored_chunks = vec![];
+        let (temp_snapshot_db, progress) = match snapshot_db_manager
+            .get_full_sync_progress_path(epoch_id, &merkle_root)
+        {
+            None => (
+                snapshot_db_manager
+                    .new_temp_snapshot_for_full_sync(epoch_id, &merkle_root)?,
+                None,
+            ),
+            Some(progress_path) => {
+                let mut resumed = None;
+                if let Some((progress, resumed_manifest, chunks)) =
+                    FullSyncProgress::resume(&progress_path)?
+                {
+                    if resumed_manifest == manifest {
+                        if let Some(temp_snapshot_db) = snapshot_db_manager
+                            .open_temp_snapshot_for_full_sync(
+                                epoch_id,
+                                &merkle_root,
+                            )?
+                        {
+                            restored_chunks = chunks;
+                            resumed = Some((temp_snapshot_db, progress));
+                        }
+                    }
+                }
+                match resumed {
+                    Some((temp_snapshot_db, progress)) => {
+                        (temp_snapshot_db, Some(progress))
+                    }
+                    None => {
+                        // The temp snapshot db is recreated before the log,
+                        // which is in the same directory.
+                        let temp_snapshot_db = snapshot_db_manager
+                            .new_temp_snapshot_for_full_sync(
+                                epoch_id,
+                                &merkle_root,
+                            )?;
+                        (
+                            temp_snapshot_db,
+                            Some(FullSyncProgress::create(
+                                &progress_path,
+                                &manifest,
+                            )?),
+                        )
+                    }
+                }
+            }
+        };
+
+        let mut verifier = Self {
+            number_chunks,
+            merkle_root,
+            chunk_boundaries: manifest.chunk_boundaries,
+            chunk_boundary_proofs: manifest.chunk_boundary_proofs,
             chunk_verified: vec![false; number_chunks],
             number_incomplete_chunk: number_chunks,
             pending_boundary_nodes: Default::default(),
             boundary_subtree_total_size: Default::default(),
             chunk_index_by_upper_key,
-            temp_snapshot_db: snapshot_db_manager
-                .new_temp_snapshot_for_full_sync(epoch_id, &merkle_root)?,
-        })
+            temp_snapshot_db,
+            progress,
+        };
+        if !restored_chunks.is_empty() {
+            for chunk in restored_chunks {
+                if chunk.chunk_index < number_chunks
+                    && !verifier.chunk_verified[chunk.chunk_index]
+                {
+                    verifier.apply_restored_chunk(chunk);
+                }
+            }
+            info!(
+                "Resume full sync restoration of snapshot {:?}, {} of {} \
+                 chunks restored",
+                epoch_id,
+                number_chunks - verifier.number_incomplete_chunk,
+                number_chunks
+            );
+            // The restoration may be interrupted after the last chunk.
+            if verifier.is_completed() {
+                verifier.finalize()?;
+            }
+        }
+        Ok(verifier)
+    }
+
+    /// Returns the chunk boundaries and proofs in the log of the restoration
+    /// of the snapshot, if any, so that an interrupted restoration is resumed
+    /// with the same chunks.
+    pub fn load_resumable_manifest(
+        snapshot_db_manager: &SnapshotDbManager, epoch_id: &EpochId,
+        merkle_root: &MerkleHash,
+    ) -> Result<Option<(Vec<Vec<u8>>, Vec<TrieProof>)>>
+    {
+        let progress_path = match snapshot_db_manager
+            .get_full_sync_progress_path(epoch_id, merkle_root)
+        {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        Ok(FullSyncProgress::load_manifest(&progress_path)?
+            .filter(|manifest| {
+                manifest.snapshot_epoch_id == *epoch_id
+                    && manifest.merkle_root == *merkle_root
+            })
+            .map(|manifest| {
+                (manifest.chunk_boundaries, manifest.chunk_boundary_proofs)
+            }))
     }
 
     pub fn is_completed(&self) -> bool { self.number_incomplete_chunk == 0 }
 
+    /// Returns if the chunk has been restored, either in this run or before
+    /// the restoration is resumed.
+    pub fn is_chunk_restored(&self, chunk_upper_key: &Option<Vec<u8>>) -> bool {
+        match self.chunk_index(chunk_upper_key) {
+            Some(chunk_index) => self.chunk_verified[chunk_index],
+            None => false,
+        }
+    }
+
+    fn chunk_index(&self, chunk_upper_key: &Option<Vec<u8>>) -> Option<usize> {
+        match chunk_upper_key {
+            None => Some(self.number_chunks - 1),
+            Some(upper_key) => {
+                self.chunk_index_by_upper_key.get(upper_key).cloned()
+            }
+        }
+    }
+
     // FIXME: multi-threading, where &mut can be dropped.
     pub fn restore_chunk<Key: Borrow<[u8]> + Debug>(
         &mut self, chunk_upper_key: &Option<Vec<u8>>, keys: &Vec<Key>,
         values: Vec<Vec<u8>>,
     ) -> Result<bool>
     {
-        let chunk_index = match chunk_upper_key {
-            None => self.number_chunks - 1,
-            Some(upper_key) => {
-                match self.chunk_index_by_upper_key.get(upper_key) {
-                    Some(index) => *index,
-                    None => {
-                        warn!("chunk key {:?} does not match boundaries in manifest", upper_key);
-                        return Ok(false);
-                    }
-                }
+        let chunk_index = match self.chunk_index(chunk_upper_key) {
+            Some(index) => index,
+            None => {
+                warn!(
+                    "chunk key {:?} does not match boundaries in manifest",
+                    chunk_upper_key
+                );
+                return Ok(false);
             }
         };
+        if self.chunk_verified[chunk_index] {
+            // Already restored, e.g. before the restoration is resumed.
+            return Ok(true);
+        }
         // Check key monotone.
         if !keys.is_empty() {
             let mut previous = keys.first().unwrap();
@@ -157,9 +280,6 @@ impl<SnapshotDbManager: SnapshotDbManagerTrait>
 
         let chunk_rebuilder = chunk_verifier.restore(keys, &values)?;
         if chunk_rebuilder.is_valid {
-            self.chunk_verified[chunk_index] = true;
-            self.number_incomplete_chunk -= 1;
-
             self.temp_snapshot_db.start_transaction()?;
             // Commit key-values.
             for (key, value) in keys.into_iter().zip(values.into_iter()) {
@@ -176,6 +296,10 @@ impl<SnapshotDbManager: SnapshotDbManagerTrait>
             self.temp_snapshot_db.commit_transaction()?;
 
             // Combine changes around boundary nodes.
+            let mut restored_chunk = RestoredChunk {
+                chunk_index,
+                ..Default::default()
+            };
             for (path, node) in chunk_rebuilder.boundary_nodes {
                 let mut children_table = VanillaChildrenTable::default();
                 unsafe {
@@ -192,7 +316,7 @@ impl<SnapshotDbManager: SnapshotDbManagerTrait>
                     *children_table.get_children_count_mut() =
                         node.get_children_count();
                 }
-                self.pending_boundary_nodes.insert(
+                restored_chunk.boundary_nodes.push((
                     path,
                     SnapshotMptNode(VanillaTrieNode::new(
                         node.get_merkle().clone(),
@@ -202,16 +326,21 @@ impl<SnapshotDbManager: SnapshotDbManagerTrait>
                             .map(|ref_v| ref_v.into()),
                         node.compressed_path_ref().into(),
                     )),
-                );
+                ));
             }
             for (subtree_index, subtree_size) in
                 chunk_rebuilder.boundary_subtree_total_size
             {
-                *self
-                    .boundary_subtree_total_size
-                    .entry(subtree_index)
-                    .or_default() += subtree_size;
+                restored_chunk.boundary_subtree_sizes.push((
+                    subtree_index.parent_node,
+                    subtree_index.child_index,
+                    subtree_size,
+                ));
+            }
+            if let Some(progress) = self.progress.as_mut() {
+                progress.append(&restored_chunk)?;
             }
+            self.apply_restored_chunk(restored_chunk);
         }
 
         if self.is_completed() {
@@ -221,6 +350,23 @@ impl<SnapshotDbManager: SnapshotDbManagerTrait>
         Ok(chunk_rebuilder.is_valid)
     }
 
+    fn apply_restored_chunk(&mut self, chunk: RestoredChunk) {
+        self.chunk_verified[chunk.chunk_index] = true;
+        self.number_incomplete_chunk -= 1;
+        self.pending_boundary_nodes.extend(chunk.boundary_nodes);
+        for (parent_node, child_index, subtree_size) in
+            chunk.boundary_subtree_sizes
+        {
+            *self
+                .boundary_subtree_total_size
+                .entry(BoundarySubtreeIndex {
+                    parent_node,
+                    child_index,
+                })
+                .or_default() += subtree_size;
+        }
+    }
+
     // FIXME: multi-threading
     /// Combine and write boundary subtree nodes after all chunks have been
     /// completed.
@@ -263,8 +409,11 @@ use crate::{
             trie_node::TrieNodeTrait, CompressedPathRaw, VanillaChildrenTable,
             VanillaTrieNode, CHILDREN_COUNT,
         },
-        snapshot_sync::restoration::mpt_slice_verifier::{
-            BoundarySubtreeIndex, MptSliceVerifier,
+        snapshot_sync::restoration::{
+            full_sync_progress::{
+                FullSyncManifest, FullSyncProgress, RestoredChunk,
+            },
+            mpt_slice_verifier::{BoundarySubtreeIndex, MptSliceVerifier},
         },
     },
     storage_db::{
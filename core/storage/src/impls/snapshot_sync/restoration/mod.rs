@@ -2,6 +2,7 @@
 // Conflux is free software and distributed under GNU General Public License.
 // See http://www.gnu.org/licenses/
 
+pub mod full_sync_progress;
 pub mod full_sync_verifier;
 pub(in super::super::super) mod mpt_slice_verifier;
 mod slice_restore_read_write_path_node;
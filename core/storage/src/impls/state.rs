@@ -32,10 +32,21 @@ pub struct State {
     // FIXME: this is a hack to get pivot chain from parent snapshot to a
     // FIXME: snapshot. it should be done in consensus.
     parent_epoch_id: EpochId,
+
+    /// The keys modified in this epoch, to be recorded in the state history
+    /// at commit. None if the archive-state mode is off.
+    history_changes: Option<BTreeMap<Vec<u8>, Option<Box<[u8]>>>>,
+    /// Set when the state is read from the state history. The state trees
+    /// are then the trees of a later available state and must not be read.
+    maybe_history_view: Option<StateHistoryView>,
 }
 
 impl State {
     pub fn new(manager: Arc<StateManager>, state_trees: StateTrees) -> Self {
+        let history_changes = manager
+            .get_storage_manager()
+            .get_state_history()
+            .map(|_| Default::default());
         Self {
             manager,
             snapshot_db: state_trees.snapshot_db,
@@ -57,9 +68,22 @@ impl State {
             dirty: false,
             children_merkle_map: ChildrenMerkleMap::new(),
             parent_epoch_id: state_trees.parent_epoch_id,
+            history_changes,
+            maybe_history_view: None,
         }
     }
 
+    pub fn new_history_view(
+        manager: Arc<StateManager>, state_trees: StateTrees,
+        history_view: StateHistoryView,
+    ) -> Self
+    {
+        let mut state = Self::new(manager, state_trees);
+        state.history_changes = None;
+        state.maybe_history_view = Some(history_view);
+        state
+    }
+
     fn state_root(&self, merkle_root: MerkleHash) -> StateRootWithAuxInfo {
         let state_root = StateRoot {
             snapshot_root: self.snapshot_merkle_root,
@@ -93,6 +117,47 @@ impl State {
         }
     }
 
+    fn check_history_view(&self, op: &'static str) -> Result<()> {
+        if self.maybe_history_view.is_none() {
+            Ok(())
+        } else {
+            Err(ErrorKind::UnsupportedByStateHistory(op).into())
+        }
+    }
+
+    fn record_history_change(
+        &mut self, key: Vec<u8>, maybe_value: Option<Box<[u8]>>,
+    ) {
+        if let Some(history_changes) = self.history_changes.as_mut() {
+            history_changes.insert(key, maybe_value);
+        }
+    }
+
+    fn record_state_history(&mut self, epoch_id: &EpochId) -> Result<()> {
+        let history =
+            match self.manager.get_storage_manager().get_state_history() {
+                Some(history) => history,
+                None => return Ok(()),
+            };
+        // The genesis state is written on top of NULL_EPOCH.
+        let height = if self.parent_epoch_id == NULL_EPOCH {
+            0
+        } else {
+            match self.height {
+                Some(height) => height,
+                None => return Ok(()),
+            }
+        };
+        if let Some(history_changes) = self.history_changes.as_mut() {
+            history.record_epoch(
+                height,
+                epoch_id,
+                &mem::take(history_changes),
+            )?;
+        }
+        Ok(())
+    }
+
     fn get_from_delta<WithProof: StaticBool>(
         &self, mpt: &DeltaMpt, maybe_root_node: Option<NodeRefDeltaMpt>,
         access_key: &[u8],
@@ -228,6 +293,9 @@ impl Drop for State {
 
 impl StateTrait for State {
     fn get(&self, access_key: StorageKey) -> Result<Option<Box<[u8]>>> {
+        if let Some(history_view) = &self.maybe_history_view {
+            return history_view.get(&access_key.to_key_bytes());
+        }
         self.ensure_temp_slab_for_db_load();
 
         self.get_from_all_tries::<NoProof>(access_key)
@@ -235,8 +303,20 @@ impl StateTrait for State {
     }
 
     fn set(&mut self, access_key: StorageKey, value: Box<[u8]>) -> Result<()> {
+        self.check_history_view("set")?;
         self.pre_modification();
 
+        if self.history_changes.is_some() {
+            self.record_history_change(
+                access_key.to_key_bytes(),
+                if value.is_empty() {
+                    None
+                } else {
+                    Some(value.clone())
+                },
+            );
+        }
+
         let root_node = self.get_or_create_delta_root_node()?;
         self.delta_trie_root = SubTrieVisitor::new(
             &self.delta_trie,
@@ -260,6 +340,7 @@ impl StateTrait for State {
     fn delete_test_only(
         &mut self, access_key: StorageKey,
     ) -> Result<Option<Box<[u8]>>> {
+        self.check_history_view("delete")?;
         self.pre_modification();
 
         match self.get_delta_root_node() {
@@ -294,6 +375,13 @@ impl StateTrait for State {
     fn delete_all<AM: access_mode::AccessMode>(
         &mut self, access_key_prefix: StorageKey,
     ) -> Result<Option<Vec<MptKeyValue>>> {
+        if let Some(history_view) = &self.maybe_history_view {
+            if !AM::is_read_only() {
+                bail!(ErrorKind::UnsupportedByStateHistory("delete_all"));
+            }
+            return history_view
+                .get_all_with_prefix(&access_key_prefix.to_key_bytes());
+        }
         if AM::is_read_only() {
             self.ensure_temp_slab_for_db_load();
         } else {
@@ -383,6 +471,9 @@ impl StateTrait for State {
             for (k, v) in kvs {
                 let storage_key = StorageKey::from_delta_mpt_key(&k);
                 let k = storage_key.to_key_bytes();
+                if !AM::is_read_only() {
+                    self.record_history_change(k.clone(), None);
+                }
                 deleted_keys.insert(k.clone());
                 if v.len() > 0 {
                     result.push((k, v));
@@ -427,6 +518,7 @@ impl StateTrait for State {
     }
 
     fn compute_state_root(&mut self) -> Result<StateRootWithAuxInfo> {
+        self.check_history_view("compute_state_root")?;
         self.ensure_temp_slab_for_db_load();
 
         let merkle_root = self.compute_merkle_root()?;
@@ -434,6 +526,7 @@ impl StateTrait for State {
     }
 
     fn get_state_root(&self) -> Result<StateRootWithAuxInfo> {
+        self.check_history_view("get_state_root")?;
         self.ensure_temp_slab_for_db_load();
 
         Ok(self.state_root(self.state_root_check()?))
@@ -441,10 +534,16 @@ impl StateTrait for State {
 
     // TODO(yz): replace coarse lock with a queue.
     fn commit(&mut self, epoch_id: EpochId) -> Result<StateRootWithAuxInfo> {
+        self.check_history_view("commit")?;
         self.ensure_temp_slab_for_db_load();
 
         let merkle_root = self.state_root_check()?;
 
+        // Record the history before the state is committed, so that a
+        // committed epoch is never missing in the history. Recording again
+        // at re-execution is harmless.
+        self.record_state_history(&epoch_id)?;
+
         // TODO(yz): Think about leaving these node dirty and only commit when
         // the dirty node is removed from cache.
         let commit_result = self.do_db_commit(epoch_id, &merkle_root);
@@ -497,6 +596,7 @@ impl StateTraitExt for State {
     ) -> Result<(Option<Box<[u8]>>, StateProof)> {
         self.ensure_temp_slab_for_db_load();
 
+        self.check_history_view("proof")?;
         self.check_freshly_synced_snapshot("proof")?;
         self.get_from_all_tries::<WithProof>(access_key)
     }
@@ -504,6 +604,7 @@ impl StateTraitExt for State {
     fn get_node_merkle_all_versions<WithProof: StaticBool>(
         &self, access_key: StorageKey,
     ) -> Result<(NodeMerkleTriplet, NodeMerkleProof)> {
+        self.check_history_view("proof")?;
         self.check_freshly_synced_snapshot("proof")?;
         let mut proof = NodeMerkleProof::default();
 
@@ -634,9 +735,229 @@ impl StateTraitExt for State {
 
         Ok((triplet, proof))
     }
+
+    fn read_range(
+        &self, key_prefix: Option<StorageKey>, start_key: &[u8], limit: usize,
+        distinct_prefix_len: Option<usize>,
+    ) -> Result<(Vec<MptKeyValue>, Option<Vec<u8>>)>
+    {
+        self.ensure_temp_slab_for_db_load();
+
+        let key_prefix_bytes =
+            key_prefix.map_or(vec![], |key_prefix| key_prefix.to_key_bytes());
+        let upper_bound_excl =
+            to_key_prefix_iter_upper_bound(&key_prefix_bytes);
+        let upper_bound_excl = upper_bound_excl.as_ref().map(|v| &**v);
+        let mut cursor = max(key_prefix_bytes.as_slice(), start_key).to_vec();
+
+        // The key-values from the delta and intermediate tries, where an empty
+        // value is a tombstone, or the key-values from the state history. Only
+        // the smallest `overlay_batch` ones from `cursor` are loaded at a
+        // time, and all overlay keys up to the largest loaded one are known.
+        let overlay_batch =
+            max(limit.saturating_add(1), Self::READ_RANGE_SNAPSHOT_BATCH);
+        let (mut overlay_kvs, mut overlay_truncated) = self
+            .read_range_overlay(
+                key_prefix,
+                &cursor,
+                upper_bound_excl,
+                overlay_batch,
+            )?;
+        let mut snapshot_exhausted = self.maybe_history_view.is_some();
+
+        // The snapshot is read in batches, because the keys skipped for
+        // `distinct_prefix_len` can be far more than `limit`.
+        let mut snapshot_kvs = VecDeque::<MptKeyValue>::new();
+        let mut snapshot_lower_bound_incl = cursor.clone();
+        let mut kvs = Vec::new();
+        loop {
+            while snapshot_kvs.front().map_or(false, |(key, _)| *key < cursor)
+            {
+                snapshot_kvs.pop_front();
+            }
+            if snapshot_kvs.is_empty() && !snapshot_exhausted {
+                let lower_bound_incl =
+                    max(&cursor, &snapshot_lower_bound_incl).clone();
+                let batch = self.read_range_from_snapshot(
+                    &lower_bound_incl,
+                    upper_bound_excl,
+                    Self::READ_RANGE_SNAPSHOT_BATCH,
+                )?;
+                snapshot_exhausted =
+                    batch.len() < Self::READ_RANGE_SNAPSHOT_BATCH;
+                if let Some((key, _)) = batch.last() {
+                    snapshot_lower_bound_incl = next_key(key);
+                }
+                snapshot_kvs.extend(batch);
+            }
+
+            if overlay_truncated
+                && overlay_kvs.range(cursor.clone()..).next().is_none()
+            {
+                let (reloaded_kvs, truncated) = self.read_range_overlay(
+                    key_prefix,
+                    &cursor,
+                    upper_bound_excl,
+                    overlay_batch,
+                )?;
+                overlay_kvs = reloaded_kvs;
+                overlay_truncated = truncated;
+            }
+
+            // The delta tries override the snapshot.
+            let overlay_kv = overlay_kvs.range(cursor.clone()..).next();
+            let (key, value) = match (overlay_kv, snapshot_kvs.front()) {
+                (None, None) => break,
+                (Some((key, value)), None) => (key.clone(), value.clone()),
+                (Some((key, value)), Some((snapshot_key, _)))
+                    if key <= snapshot_key =>
+                {
+                    (key.clone(), value.clone())
+                }
+                (_, Some(snapshot_kv)) => snapshot_kv.clone(),
+            };
+            if value.is_empty() {
+                cursor = next_key(&key);
+                continue;
+            }
+            if kvs.len() == limit {
+                return Ok((kvs, Some(key)));
+            }
+            cursor = match distinct_prefix_len {
+                Some(len) if key.len() >= len => {
+                    match to_key_prefix_iter_upper_bound(&key[..len]) {
+                        Some(next_prefix) => next_prefix,
+                        None => {
+                            kvs.push((key, value));
+                            break;
+                        }
+                    }
+                }
+                _ => next_key(&key),
+            };
+            kvs.push((key, value));
+        }
+
+        Ok((kvs, None))
+    }
 }
 
 impl State {
+    const READ_RANGE_SNAPSHOT_BATCH: usize = 256;
+
+    /// Read the smallest `max_count` key-values in the range from the state
+    /// history, or merged from the intermediate and delta tries with empty
+    /// values as tombstones. Also return whether any key-value in the range
+    /// is left out, which are all larger than the returned ones.
+    fn read_range_overlay(
+        &self, key_prefix: Option<StorageKey>, lower_bound_incl: &[u8],
+        upper_bound_excl: Option<&[u8]>, max_count: usize,
+    ) -> Result<(BTreeMap<Vec<u8>, Box<[u8]>>, bool)>
+    {
+        if let Some(history_view) = &self.maybe_history_view {
+            let kvs = history_view.history.get_range(
+                lower_bound_incl,
+                upper_bound_excl,
+                history_view.height,
+                &history_view.epoch_by_height,
+                max_count,
+            )?;
+            let truncated = kvs.len() == max_count;
+            return Ok((kvs.into_iter().collect(), truncated));
+        }
+
+        let mut intermediate_kvs = DeltaRangeInserter::new(
+            lower_bound_incl,
+            upper_bound_excl,
+            max_count,
+        );
+        if let (Some(intermediate_trie), Some(key_padding)) = (
+            self.maybe_intermediate_trie.as_ref(),
+            self.maybe_intermediate_trie_key_padding.as_ref(),
+        ) {
+            self.read_range_from_delta(
+                intermediate_trie,
+                self.intermediate_trie_root.clone(),
+                key_prefix,
+                key_padding,
+                &mut intermediate_kvs,
+            )?;
+        }
+        let mut delta_kvs = DeltaRangeInserter::new(
+            lower_bound_incl,
+            upper_bound_excl,
+            max_count,
+        );
+        self.read_range_from_delta(
+            &self.delta_trie,
+            self.delta_trie_root.clone(),
+            key_prefix,
+            &self.delta_trie_key_padding,
+            &mut delta_kvs,
+        )?;
+
+        // A truncated trie may hide keys after its largest loaded key, which
+        // may override or add to the keys loaded from the other trie, so the
+        // merged key-values are only complete up to the smaller of them.
+        let complete_until = [&intermediate_kvs, &delta_kvs]
+            .iter()
+            .filter(|inserter| inserter.truncated)
+            .filter_map(|inserter| inserter.kvs.keys().next_back())
+            .min()
+            .cloned();
+        let mut kvs = intermediate_kvs.kvs;
+        kvs.extend(delta_kvs.kvs);
+        if let Some(complete_until) = &complete_until {
+            kvs.split_off(&next_key(complete_until));
+        }
+        Ok((kvs, complete_until.is_some()))
+    }
+
+    fn read_range_from_delta(
+        &self, mpt: &DeltaMpt, maybe_root_node: Option<NodeRefDeltaMpt>,
+        key_prefix: Option<StorageKey>, key_padding: &DeltaMptKeyPadding,
+        kvs: &mut DeltaRangeInserter,
+    ) -> Result<()>
+    {
+        let root_node = match maybe_root_node {
+            None => return Ok(()),
+            Some(root_node) => root_node,
+        };
+        let delta_mpt_key_prefix = key_prefix.map_or(vec![], |key_prefix| {
+            key_prefix.to_delta_mpt_key_bytes(key_padding)
+        });
+        // Traversal won't create any new nodes so it's fine to pass an empty
+        // owned_node_set.
+        let mut empty_owned_node_set: Option<OwnedNodeSet> =
+            Some(Default::default());
+        SubTrieVisitor::new(mpt, root_node, &mut empty_owned_node_set)?
+            .traversal_with_inserter(
+                &delta_mpt_key_prefix,
+                &delta_mpt_key_prefix,
+                kvs,
+            )?;
+        Ok(())
+    }
+
+    fn read_range_from_snapshot(
+        &self, lower_bound_incl: &[u8], upper_bound_excl: Option<&[u8]>,
+        max_count: usize,
+    ) -> Result<Vec<MptKeyValue>>
+    {
+        let mut kv_iterator = self.snapshot_db.snapshot_kv_iterator()?.take();
+        let mut kv_iter = kv_iterator
+            .iter_range(lower_bound_incl, upper_bound_excl)?
+            .take();
+        let mut kvs = Vec::new();
+        while kvs.len() < max_count {
+            match kv_iter.next()? {
+                Some(kv) => kvs.push(kv),
+                None => break,
+            }
+        }
+        Ok(kvs)
+    }
+
     fn ensure_temp_slab_for_db_load(&self) {
         self.delta_trie.get_node_memory_manager().enlarge().ok();
     }
@@ -892,6 +1213,67 @@ impl State {
     }
 }
 
+/// The smallest key after `key`.
+/// Keeps the smallest `max_count` key-values of a delta trie in the range, in
+/// the order of the storage keys. The delta mpt keys are padded with hashes,
+/// so the trie can't be seeked by the storage key, but the key-values past
+/// the smallest ones aren't kept in memory.
+struct DeltaRangeInserter<'a> {
+    lower_bound_incl: &'a [u8],
+    upper_bound_excl: Option<&'a [u8]>,
+    max_count: usize,
+    kvs: BTreeMap<Vec<u8>, Box<[u8]>>,
+    truncated: bool,
+}
+
+impl<'a> DeltaRangeInserter<'a> {
+    fn new(
+        lower_bound_incl: &'a [u8], upper_bound_excl: Option<&'a [u8]>,
+        max_count: usize,
+    ) -> Self
+    {
+        Self {
+            lower_bound_incl,
+            upper_bound_excl,
+            max_count,
+            kvs: BTreeMap::new(),
+            truncated: false,
+        }
+    }
+}
+
+impl KVInserter<MptKeyValue> for DeltaRangeInserter<'_> {
+    fn push(&mut self, (delta_mpt_key, value): MptKeyValue) -> Result<()> {
+        let key = StorageKey::from_delta_mpt_key(&delta_mpt_key).to_key_bytes();
+        if key.as_slice() < self.lower_bound_incl
+            || self
+                .upper_bound_excl
+                .map_or(false, |upper| key.as_slice() >= upper)
+        {
+            return Ok(());
+        }
+        if self.kvs.len() == self.max_count {
+            self.truncated = true;
+            match self.kvs.keys().next_back() {
+                Some(largest) if key < *largest => {
+                    let largest = largest.clone();
+                    self.kvs.remove(&largest);
+                }
+                _ => return Ok(()),
+            }
+        }
+        self.kvs.insert(key, value);
+        Ok(())
+    }
+}
+
+fn next_key(key: &[u8]) -> Vec<u8> {
+    let mut next_key = Vec::with_capacity(key.len() + 1);
+    next_key.extend_from_slice(key);
+    next_key.push(0);
+    next_key
+}
+
 use crate::{
     impls::{
         delta_mpt::{node_memory_manager::ActualSlabIndex, *},
@@ -901,6 +1283,7 @@ use crate::{
             KVInserter, MptKeyValue, TrieProof, VanillaChildrenTable,
         },
         node_merkle_proof::NodeMerkleProof,
+        state_history::StateHistoryView,
         state_manager::*,
         state_proof::StateProof,
     },
@@ -918,7 +1301,9 @@ use primitives::{
 use rustc_hex::ToHex;
 use std::{
     cell::UnsafeCell,
-    collections::{BTreeMap, HashSet},
+    cmp::max,
+    collections::{BTreeMap, HashSet, VecDeque},
     hint::unreachable_unchecked,
+    mem,
     sync::{atomic::Ordering, Arc},
 };
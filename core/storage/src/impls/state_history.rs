@@ -0,0 +1,325 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+/// Map a height to the pivot epoch at the height, or None if the height isn't
+/// executed yet.
+pub type EpochByHeight = Arc<dyn Fn(u64) -> Option<EpochId> + Send + Sync>;
+
+/// The state history keeps the values written by every executed epoch, so
+/// that the state of a historical epoch can be read after its snapshot and
+/// delta mpts are removed.
+///
+/// The values are indexed by (key, height, epoch). A key set by forked epochs
+/// at the same height has a row for each of them, and a read at height T only
+/// takes the rows of the pivot epochs at or below T. An empty value means that
+/// the key is deleted.
+///
+/// The history is only complete from `start_height` to `latest_height`. It
+/// restarts from the current height when epochs are skipped, e.g. after the
+/// node syncs a new snapshot.
+pub struct StateHistory {
+    connection: Mutex<SqliteConnection>,
+    height_range: RwLock<Option<(u64, u64)>>,
+}
+
+impl StateHistory {
+    const CREATE_HEIGHT_RANGE_TABLE: &'static str =
+        "CREATE TABLE IF NOT EXISTS height_range ( id INTEGER PRIMARY KEY, \
+         start_height INTEGER, latest_height INTEGER )";
+    const CREATE_HISTORY_TABLE: &'static str =
+        "CREATE TABLE IF NOT EXISTS history ( key BLOB, height INTEGER, \
+         epoch BLOB, value BLOB, PRIMARY KEY (key, height, epoch) ) \
+         WITHOUT ROWID";
+    const GET: &'static str =
+        "SELECT height, epoch, value FROM history WHERE key = ? AND \
+         height >= ? AND height <= ? ORDER BY height DESC";
+    const GET_RANGE: &'static str =
+        "SELECT key, height, epoch, value FROM history WHERE key >= ? AND \
+         key < ? AND height >= ? AND height <= ? \
+         ORDER BY key ASC, height DESC";
+    const GET_RANGE_TILL_END: &'static str =
+        "SELECT key, height, epoch, value FROM history WHERE key >= ? AND \
+         height >= ? AND height <= ? ORDER BY key ASC, height DESC";
+    const PUT: &'static str = "INSERT OR REPLACE INTO history \
+                               (key, height, epoch, value) VALUES (?, ?, ?, ?)";
+    const PUT_HEIGHT_RANGE: &'static str = "INSERT OR REPLACE INTO \
+                                            height_range (id, start_height, \
+                                            latest_height) VALUES (0, ?, ?)";
+    const SELECT_HEIGHT_RANGE: &'static str =
+        "SELECT start_height, latest_height FROM height_range WHERE id = 0";
+
+    pub fn open_or_create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut connection = if path.as_ref().exists() {
+            SqliteConnection::open(
+                path,
+                /* readonly = */ false,
+                SqliteConnection::default_open_flags(),
+            )?
+        } else {
+            SqliteConnection::create_and_open(
+                path,
+                SqliteConnection::default_open_flags(),
+                /* unsafe_mode = */ false,
+            )?
+        };
+        connection
+            .execute(Self::CREATE_HISTORY_TABLE, SQLITE_NO_PARAM)?
+            .finish_ignore_rows()?;
+        connection
+            .execute(Self::CREATE_HEIGHT_RANGE_TABLE, SQLITE_NO_PARAM)?
+            .finish_ignore_rows()?;
+        let height_range = connection
+            .execute(Self::SELECT_HEIGHT_RANGE, SQLITE_NO_PARAM)?
+            .map(|row| -> Result<(u64, u64)> {
+                Ok((
+                    i64::from_column(row, 0)? as u64,
+                    i64::from_column(row, 1)? as u64,
+                ))
+            })
+            .expect_one_row()?
+            .transpose()?;
+
+        Ok(Self {
+            connection: Mutex::new(connection),
+            height_range: RwLock::new(height_range),
+        })
+    }
+
+    /// The heights where the history is complete, inclusively.
+    pub fn height_range(&self) -> Option<(u64, u64)> {
+        *self.height_range.read()
+    }
+
+    pub fn covers_height(&self, height: u64) -> bool {
+        match self.height_range() {
+            Some((start_height, latest_height)) => {
+                start_height <= height && height <= latest_height
+            }
+            None => false,
+        }
+    }
+
+    /// Record the changes committed by the epoch. A None value is a deletion.
+    pub fn record_epoch(
+        &self, height: u64, epoch_id: &EpochId,
+        changes: &BTreeMap<Vec<u8>, Option<Box<[u8]>>>,
+    ) -> Result<()>
+    {
+        let mut connection = self.connection.lock();
+        let mut height_range = self.height_range.write();
+        let epoch: &[u8] = epoch_id.as_ref();
+        let new_height_range = match *height_range {
+            Some((start_height, latest_height))
+                if height <= latest_height + 1 =>
+            {
+                (start_height, max(latest_height, height))
+            }
+            // There are missing epochs before the height, so the history
+            // restarts from here.
+            _ => (height, height),
+        };
+
+        connection.execute("BEGIN IMMEDIATE", SQLITE_NO_PARAM)?;
+        let write_result = (|| -> Result<()> {
+            for (key, maybe_value) in changes {
+                let value: &[u8] = match maybe_value {
+                    Some(value) => &**value,
+                    None => &[],
+                };
+                connection
+                    .execute(
+                        Self::PUT,
+                        &[
+                            &key as SqlBindableRef,
+                            &(height as i64),
+                            &&epoch,
+                            &&value,
+                        ],
+                    )?
+                    .finish_ignore_rows()?;
+            }
+            connection
+                .execute(
+                    Self::PUT_HEIGHT_RANGE,
+                    &[
+                        &(new_height_range.0 as i64) as SqlBindableRef,
+                        &(new_height_range.1 as i64),
+                    ],
+                )?
+                .finish_ignore_rows()?;
+            connection.execute("COMMIT", SQLITE_NO_PARAM)?;
+            Ok(())
+        })();
+        if write_result.is_err() {
+            connection.execute("ROLLBACK", SQLITE_NO_PARAM).ok();
+        } else {
+            *height_range = Some(new_height_range);
+        }
+
+        write_result
+    }
+
+    /// Get the value of the key in the state of the pivot epoch at `height`.
+    /// The caller must check that the height is covered by the history.
+    pub fn get(
+        &self, key: &[u8], height: u64, epoch_by_height: &EpochByHeight,
+    ) -> Result<Option<Box<[u8]>>> {
+        let start_height = self.start_height();
+        let mut connection = self.connection.lock();
+        let mut rows = connection
+            .execute(
+                Self::GET,
+                &[
+                    &&key as SqlBindableRef,
+                    &(start_height as i64),
+                    &(height as i64),
+                ],
+            )?
+            .map(|row| Self::read_history_row(row, 0));
+        while let Some((row_height, epoch, maybe_value)) = rows.next()? {
+            if epoch_by_height(row_height) == Some(epoch) {
+                return Ok(maybe_value);
+            }
+        }
+
+        if start_height == 0 {
+            Ok(None)
+        } else {
+            Err(ErrorKind::StateHistoryIncomplete(start_height).into())
+        }
+    }
+
+    /// Get all key-values in [lower_bound_incl, upper_bound_excl) in the state
+    /// of the pivot epoch at `height`. It's only possible when the history is
+    /// kept from genesis, otherwise the keys untouched since `start_height`
+    /// are missing. At most `max_count` key-values are returned, the smallest
+    /// ones.
+    pub fn get_range(
+        &self, lower_bound_incl: &[u8], upper_bound_excl: Option<&[u8]>,
+        height: u64, epoch_by_height: &EpochByHeight, max_count: usize,
+    ) -> Result<Vec<MptKeyValue>>
+    {
+        let start_height = self.start_height();
+        if start_height != 0 {
+            bail!(ErrorKind::StateHistoryIncomplete(start_height));
+        }
+        let mut connection = self.connection.lock();
+        let rows = match upper_bound_excl {
+            Some(upper_bound_excl) => connection.execute(
+                Self::GET_RANGE,
+                &[
+                    &&lower_bound_incl as SqlBindableRef,
+                    &&upper_bound_excl,
+                    &(start_height as i64),
+                    &(height as i64),
+                ],
+            )?,
+            None => connection.execute(
+                Self::GET_RANGE_TILL_END,
+                &[
+                    &&lower_bound_incl as SqlBindableRef,
+                    &(start_height as i64),
+                    &(height as i64),
+                ],
+            )?,
+        };
+        let mut rows = rows.map(|row| -> Result<_> {
+            Ok((
+                Vec::<u8>::from_column(row, 0)?,
+                Self::read_history_row(row, 1)?,
+            ))
+        });
+
+        let mut kvs = vec![];
+        let mut last_found_key = None;
+        while kvs.len() < max_count {
+            let (key, (row_height, epoch, maybe_value)) = match rows.next()? {
+                Some(row) => row,
+                None => break,
+            };
+            // Rows of the same key are ordered by height descendingly, so the
+            // first row from the pivot chain has the latest value.
+            if last_found_key.as_ref() == Some(&key)
+                || epoch_by_height(row_height) != Some(epoch)
+            {
+                continue;
+            }
+            if let Some(value) = maybe_value {
+                kvs.push((key.clone(), value));
+            }
+            last_found_key = Some(key);
+        }
+
+        Ok(kvs)
+    }
+
+    fn start_height(&self) -> u64 {
+        self.height_range()
+            .map_or(0, |(start_height, _)| start_height)
+    }
+
+    fn read_history_row(
+        row: &Statement<'_>, first_column: usize,
+    ) -> Result<(u64, EpochId, Option<Box<[u8]>>)> {
+        let height = i64::from_column(row, first_column)? as u64;
+        let epoch = Vec::<u8>::from_column(row, first_column + 1)?;
+        let value = Box::<[u8]>::from_column(row, first_column + 2)?;
+        if epoch.len() != EpochId::len_bytes() {
+            bail!(ErrorKind::DbValueError);
+        }
+
+        Ok((
+            height,
+            EpochId::from_slice(&epoch),
+            if value.is_empty() { None } else { Some(value) },
+        ))
+    }
+}
+
+/// The state of the pivot epoch at `height`, read from the state history.
+pub struct StateHistoryView {
+    pub history: Arc<StateHistory>,
+    pub height: u64,
+    pub epoch_by_height: EpochByHeight,
+}
+
+impl StateHistoryView {
+    pub fn get(&self, key: &[u8]) -> Result<Option<Box<[u8]>>> {
+        self.history.get(key, self.height, &self.epoch_by_height)
+    }
+
+    pub fn get_all_with_prefix(
+        &self, key_prefix: &[u8],
+    ) -> Result<Option<Vec<MptKeyValue>>> {
+        let upper_bound_excl = to_key_prefix_iter_upper_bound(key_prefix);
+        let kvs = self.history.get_range(
+            key_prefix,
+            upper_bound_excl.as_ref().map(|v| &**v),
+            self.height,
+            &self.epoch_by_height,
+            usize::MAX,
+        )?;
+        if kvs.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(kvs))
+        }
+    }
+}
+
+use crate::{
+    impls::{
+        errors::*,
+        merkle_patricia_trie::MptKeyValue,
+        storage_db::sqlite::{
+            SqlBindableRef, SqlReadable, SqliteConnection, SQLITE_NO_PARAM,
+        },
+    },
+    utils::to_key_prefix_iter_upper_bound,
+};
+use fallible_iterator::FallibleIterator;
+use parking_lot::{Mutex, RwLock};
+use primitives::EpochId;
+use sqlite::Statement;
+use std::{cmp::max, collections::BTreeMap, path::Path, sync::Arc};
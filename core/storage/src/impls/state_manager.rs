@@ -3,7 +3,7 @@
 // See http://www.gnu.org/licenses/
 
 pub type DeltaDbManager = DeltaDbManagerRocksdb;
-pub type SnapshotDbManager = SnapshotDbManagerSqlite;
+pub type SnapshotDbManager = AnySnapshotDbManager;
 pub type SnapshotDb = <SnapshotDbManager as SnapshotDbManagerTrait>::SnapshotDb;
 
 pub struct StateTrees {
@@ -539,6 +539,37 @@ impl StateManager {
             }),
         )
     }
+
+    /// Read the state of the pivot epoch at `height` from the state history.
+    /// Returns None if the archive-state mode is off or the history doesn't
+    /// cover the height.
+    ///
+    /// The state trees of `base_state_index`, which must be an available
+    /// state, are only kept to satisfy the State struct and are never read.
+    pub fn get_state_from_history(
+        self: &Arc<Self>, base_state_index: StateIndex, height: u64,
+        epoch_by_height: EpochByHeight,
+    ) -> Result<Option<State>>
+    {
+        let history = match self.storage_manager.get_state_history() {
+            Some(history) if history.covers_height(height) => history.clone(),
+            _ => return Ok(None),
+        };
+        let maybe_state_trees = self
+            .get_state_trees(&base_state_index, /* try_open = */ true)?;
+        match maybe_state_trees {
+            None => Ok(None),
+            Some(state_trees) => Ok(Some(State::new_history_view(
+                self.clone(),
+                state_trees,
+                StateHistoryView {
+                    history,
+                    height,
+                    epoch_by_height,
+                },
+            ))),
+        }
+    }
 }
 
 impl StateManagerTrait for StateManager {
@@ -619,9 +650,10 @@ use crate::{
     impls::{
         delta_mpt::*,
         errors::*,
+        state_history::{EpochByHeight, StateHistoryView},
         storage_db::{
             delta_db_manager_rocksdb::DeltaDbManagerRocksdb,
-            snapshot_db_manager_sqlite::SnapshotDbManagerSqlite,
+            snapshot_db_manager_any::AnySnapshotDbManager,
         },
         storage_manager::storage_manager::StorageManager,
     },
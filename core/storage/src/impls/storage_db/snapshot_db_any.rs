@@ -0,0 +1,460 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+/// The snapshot db of the backend selected by
+/// StorageConfiguration::snapshot_db_backend.
+///
+/// The snapshot db of the backend is kept in Arc because the backend
+/// managers share the snapshots open for read. A snapshot open for write is
+/// never shared, therefore it can be mutated through Arc::get_mut.
+pub enum AnySnapshotDb {
+    Sqlite(Arc<SnapshotDbSqlite>),
+    Rocksdb(Arc<SnapshotDbRocksdb>),
+}
+
+/// The snapshot mpt of AnySnapshotDb.
+pub enum AnySnapshotMpt<SqliteMpt, RocksdbMpt> {
+    Sqlite(SqliteMpt),
+    Rocksdb(RocksdbMpt),
+}
+
+/// The snapshot key-value iterator of AnySnapshotDb.
+pub enum AnySnapshotKvIter {
+    Sqlite(KvdbSqliteSharded<Box<[u8]>>),
+    Rocksdb(SnapshotDbRocksdbTable),
+}
+
+pub struct AnySnapshotDbIteratorTag();
+
+impl AnySnapshotDb {
+    pub fn backend(&self) -> SnapshotDbBackend {
+        match self {
+            AnySnapshotDb::Sqlite(_) => SnapshotDbBackend::Sqlite,
+            AnySnapshotDb::Rocksdb(_) => SnapshotDbBackend::Rocksdb,
+        }
+    }
+
+    /// Returns the SnapshotDbSqlite for code which relies on the sqlite
+    /// tables, e.g. the debug snapshot checker.
+    pub fn to_sqlite(&self) -> Option<Arc<SnapshotDbSqlite>> {
+        match self {
+            AnySnapshotDb::Sqlite(db) => Some(db.clone()),
+            AnySnapshotDb::Rocksdb(_) => None,
+        }
+    }
+
+    pub fn to_rocksdb(&self) -> Option<Arc<SnapshotDbRocksdb>> {
+        match self {
+            AnySnapshotDb::Sqlite(_) => None,
+            AnySnapshotDb::Rocksdb(db) => Some(db.clone()),
+        }
+    }
+}
+
+fn exclusive<T>(db: &mut Arc<T>) -> &mut T {
+    Arc::get_mut(db).expect("The snapshot db open for write is not shared.")
+}
+
+impl KeyValueDbTypes for AnySnapshotDb {
+    type ValueType = Box<[u8]>;
+}
+
+impl KeyValueDbTraitRead for AnySnapshotDb {
+    fn get(&self, key: &[u8]) -> Result<Option<Box<[u8]>>> {
+        match self {
+            AnySnapshotDb::Sqlite(db) => KeyValueDbTraitRead::get(&**db, key),
+            AnySnapshotDb::Rocksdb(db) => KeyValueDbTraitRead::get(&**db, key),
+        }
+    }
+}
+
+impl KeyValueDbTraitOwnedRead for AnySnapshotDb {
+    fn get_mut(&mut self, key: &[u8]) -> Result<Option<Box<[u8]>>> {
+        self.get(key)
+    }
+}
+
+impl KeyValueDbTraitSingleWriter for AnySnapshotDb {
+    fn delete(&mut self, key: &[u8]) -> Result<Option<Option<Box<[u8]>>>> {
+        match self {
+            AnySnapshotDb::Sqlite(db) => {
+                KeyValueDbTraitSingleWriter::delete(exclusive(db), key)
+            }
+            AnySnapshotDb::Rocksdb(db) => {
+                KeyValueDbTraitSingleWriter::delete(exclusive(db), key)
+            }
+        }
+    }
+
+    fn put(
+        &mut self, key: &[u8], value: &[u8],
+    ) -> Result<Option<Option<Box<[u8]>>>> {
+        match self {
+            AnySnapshotDb::Sqlite(db) => {
+                KeyValueDbTraitSingleWriter::put(exclusive(db), key, value)
+            }
+            AnySnapshotDb::Rocksdb(db) => {
+                KeyValueDbTraitSingleWriter::put(exclusive(db), key, value)
+            }
+        }
+    }
+}
+
+impl<'db> OpenSnapshotMptTrait<'db> for AnySnapshotDb {
+    type SnapshotDbAsOwnedType = AnySnapshotMpt<
+        <SnapshotDbSqlite as OpenSnapshotMptTrait<'db>>::SnapshotDbAsOwnedType,
+        <SnapshotDbRocksdb as OpenSnapshotMptTrait<'db>>::SnapshotDbAsOwnedType,
+    >;
+    type SnapshotDbBorrowMutType = AnySnapshotMpt<
+        <SnapshotDbSqlite as OpenSnapshotMptTrait<'db>>::SnapshotDbBorrowMutType,
+        <SnapshotDbRocksdb as OpenSnapshotMptTrait<'db>>::SnapshotDbBorrowMutType,
+    >;
+    type SnapshotDbBorrowSharedType = AnySnapshotMpt<
+        <SnapshotDbSqlite as OpenSnapshotMptTrait<'db>>::SnapshotDbBorrowSharedType,
+        <SnapshotDbRocksdb as OpenSnapshotMptTrait<'db>>::SnapshotDbBorrowSharedType,
+    >;
+
+    fn open_snapshot_mpt_owned(
+        &'db mut self,
+    ) -> Result<Self::SnapshotDbBorrowMutType> {
+        Ok(match self {
+            AnySnapshotDb::Sqlite(db) => {
+                AnySnapshotMpt::Sqlite(exclusive(db).open_snapshot_mpt_owned()?)
+            }
+            AnySnapshotDb::Rocksdb(db) => AnySnapshotMpt::Rocksdb(
+                exclusive(db).open_snapshot_mpt_owned()?,
+            ),
+        })
+    }
+
+    fn open_snapshot_mpt_as_owned(
+        &'db self,
+    ) -> Result<Self::SnapshotDbAsOwnedType> {
+        Ok(match self {
+            AnySnapshotDb::Sqlite(db) => {
+                AnySnapshotMpt::Sqlite(db.open_snapshot_mpt_as_owned()?)
+            }
+            AnySnapshotDb::Rocksdb(db) => {
+                AnySnapshotMpt::Rocksdb(db.open_snapshot_mpt_as_owned()?)
+            }
+        })
+    }
+
+    fn open_snapshot_mpt_shared(
+        &'db self,
+    ) -> Result<Self::SnapshotDbBorrowSharedType> {
+        Ok(match self {
+            AnySnapshotDb::Sqlite(db) => {
+                AnySnapshotMpt::Sqlite(db.open_snapshot_mpt_shared()?)
+            }
+            AnySnapshotDb::Rocksdb(db) => {
+                AnySnapshotMpt::Rocksdb(db.open_snapshot_mpt_shared()?)
+            }
+        })
+    }
+}
+
+impl SnapshotDbTrait for AnySnapshotDb {
+    type SnapshotKvdbIterTraitTag = AnySnapshotDbIteratorTag;
+    type SnapshotKvdbIterType = AnySnapshotKvIter;
+
+    /// The empty snapshot has no db, it's the same for all backends.
+    fn get_null_snapshot() -> Self {
+        AnySnapshotDb::Sqlite(Arc::new(SnapshotDbSqlite::get_null_snapshot()))
+    }
+
+    fn open(
+        _snapshot_path: &Path, _readonly: bool,
+        _already_open_snapshots: &AlreadyOpenSnapshots<Self>,
+        _open_semaphore: &Arc<Semaphore>,
+    ) -> Result<Self>
+    {
+        unreachable!("AnySnapshotDb is open by the backend manager.")
+    }
+
+    fn create(
+        _snapshot_path: &Path,
+        _already_open_snapshots: &AlreadyOpenSnapshots<Self>,
+        _open_semaphore: &Arc<Semaphore>,
+    ) -> Result<Self>
+    {
+        unreachable!("AnySnapshotDb is created by the backend manager.")
+    }
+
+    fn direct_merge(&mut self) -> Result<MerkleHash> {
+        match self {
+            AnySnapshotDb::Sqlite(db) => exclusive(db).direct_merge(),
+            AnySnapshotDb::Rocksdb(db) => exclusive(db).direct_merge(),
+        }
+    }
+
+    fn copy_and_merge(
+        &mut self, old_snapshot_db: &AnySnapshotDb,
+    ) -> Result<MerkleHash> {
+        match (self, old_snapshot_db) {
+            (AnySnapshotDb::Sqlite(db), AnySnapshotDb::Sqlite(old_db)) => {
+                exclusive(db).copy_and_merge(old_db)
+            }
+            (AnySnapshotDb::Rocksdb(db), AnySnapshotDb::Rocksdb(old_db)) => {
+                exclusive(db).copy_and_merge(old_db)
+            }
+            (db, old_db) => bail!(ErrorKind::SnapshotDbBackendMismatch(
+                old_db.backend().name(),
+                db.backend().name(),
+            )),
+        }
+    }
+
+    fn start_transaction(&mut self) -> Result<()> {
+        match self {
+            AnySnapshotDb::Sqlite(db) => exclusive(db).start_transaction(),
+            AnySnapshotDb::Rocksdb(db) => exclusive(db).start_transaction(),
+        }
+    }
+
+    fn commit_transaction(&mut self) -> Result<()> {
+        match self {
+            AnySnapshotDb::Sqlite(db) => exclusive(db).commit_transaction(),
+            AnySnapshotDb::Rocksdb(db) => exclusive(db).commit_transaction(),
+        }
+    }
+
+    fn snapshot_kv_iterator(
+        &self,
+    ) -> Result<
+        Wrap<
+            Self::SnapshotKvdbIterType,
+            dyn KeyValueDbIterableTrait<
+                MptKeyValue,
+                [u8],
+                AnySnapshotDbIteratorTag,
+            >,
+        >,
+    > {
+        Ok(Wrap(match self {
+            AnySnapshotDb::Sqlite(db) => {
+                AnySnapshotKvIter::Sqlite(db.snapshot_kv_iterator()?.take())
+            }
+            AnySnapshotDb::Rocksdb(db) => {
+                AnySnapshotKvIter::Rocksdb(db.snapshot_kv_iterator()?.take())
+            }
+        }))
+    }
+}
+
+impl<SqliteMpt: SnapshotMptTraitRead, RocksdbMpt: SnapshotMptTraitRead>
+    SnapshotMptTraitRead for AnySnapshotMpt<SqliteMpt, RocksdbMpt>
+{
+    fn get_merkle_root(&self) -> MerkleHash {
+        match self {
+            AnySnapshotMpt::Sqlite(mpt) => mpt.get_merkle_root(),
+            AnySnapshotMpt::Rocksdb(mpt) => mpt.get_merkle_root(),
+        }
+    }
+
+    fn load_node(
+        &mut self, path: &dyn CompressedPathTrait,
+    ) -> Result<Option<SnapshotMptNode>> {
+        match self {
+            AnySnapshotMpt::Sqlite(mpt) => mpt.load_node(path),
+            AnySnapshotMpt::Rocksdb(mpt) => mpt.load_node(path),
+        }
+    }
+}
+
+impl<
+        SqliteMpt: SnapshotMptTraitReadAndIterate,
+        RocksdbMpt: SnapshotMptTraitReadAndIterate,
+    > SnapshotMptTraitReadAndIterate for AnySnapshotMpt<SqliteMpt, RocksdbMpt>
+{
+    fn iterate_subtree_trie_nodes_without_root(
+        &mut self, path: &dyn CompressedPathTrait,
+    ) -> Result<Box<dyn SnapshotMptIteraterTrait + '_>> {
+        match self {
+            AnySnapshotMpt::Sqlite(mpt) => {
+                mpt.iterate_subtree_trie_nodes_without_root(path)
+            }
+            AnySnapshotMpt::Rocksdb(mpt) => {
+                mpt.iterate_subtree_trie_nodes_without_root(path)
+            }
+        }
+    }
+}
+
+impl<SqliteMpt: SnapshotMptTraitRw, RocksdbMpt: SnapshotMptTraitRw>
+    SnapshotMptTraitRw for AnySnapshotMpt<SqliteMpt, RocksdbMpt>
+{
+    fn delete_node(&mut self, path: &dyn CompressedPathTrait) -> Result<()> {
+        match self {
+            AnySnapshotMpt::Sqlite(mpt) => mpt.delete_node(path),
+            AnySnapshotMpt::Rocksdb(mpt) => mpt.delete_node(path),
+        }
+    }
+
+    fn write_node(
+        &mut self, path: &dyn CompressedPathTrait, trie_node: &SnapshotMptNode,
+    ) -> Result<()> {
+        match self {
+            AnySnapshotMpt::Sqlite(mpt) => mpt.write_node(path, trie_node),
+            AnySnapshotMpt::Rocksdb(mpt) => mpt.write_node(path, trie_node),
+        }
+    }
+}
+
+impl WrappedTrait<dyn FallibleIterator<Item = MptKeyValue, Error = Error>>
+    for KvdbIterIterator<MptKeyValue, [u8], AnySnapshotDbIteratorTag>
+{
+}
+
+impl<'a>
+    WrappedLifetimeFamily<
+        'a,
+        dyn FallibleIterator<Item = MptKeyValue, Error = Error>,
+    > for KvdbIterIterator<MptKeyValue, [u8], AnySnapshotDbIteratorTag>
+{
+    type Out =
+        Box<dyn 'a + FallibleIterator<Item = MptKeyValue, Error = Error>>;
+}
+
+impl KeyValueDbIterableTrait<MptKeyValue, [u8], AnySnapshotDbIteratorTag>
+    for AnySnapshotKvIter
+{
+    fn iter_range(
+        &mut self, lower_bound_incl: &[u8], upper_bound_excl: Option<&[u8]>,
+    ) -> Result<
+        Wrap<
+            KvdbIterIterator<MptKeyValue, [u8], AnySnapshotDbIteratorTag>,
+            dyn FallibleIterator<Item = MptKeyValue, Error = Error>,
+        >,
+    > {
+        let iter: Box<
+            dyn '_ + FallibleIterator<Item = MptKeyValue, Error = Error>,
+        > = match self {
+            AnySnapshotKvIter::Sqlite(kvdb) => Box::new(
+                KeyValueDbIterableTrait::<
+                    MptKeyValue,
+                    [u8],
+                    KvdbSqliteShardedIteratorTag,
+                >::iter_range(
+                    kvdb, lower_bound_incl, upper_bound_excl
+                )?
+                .take(),
+            ),
+            AnySnapshotKvIter::Rocksdb(table) => Box::new(
+                KeyValueDbIterableTrait::<
+                    MptKeyValue,
+                    [u8],
+                    SnapshotDbRocksdbIteratorTag,
+                >::iter_range(
+                    table, lower_bound_incl, upper_bound_excl
+                )?
+                .take(),
+            ),
+        };
+        Ok(Wrap(iter))
+    }
+
+    fn iter_range_excl(
+        &mut self, lower_bound_excl: &[u8], upper_bound_excl: &[u8],
+    ) -> Result<
+        Wrap<
+            KvdbIterIterator<MptKeyValue, [u8], AnySnapshotDbIteratorTag>,
+            dyn FallibleIterator<Item = MptKeyValue, Error = Error>,
+        >,
+    > {
+        let iter: Box<
+            dyn '_ + FallibleIterator<Item = MptKeyValue, Error = Error>,
+        > = match self {
+            AnySnapshotKvIter::Sqlite(kvdb) => Box::new(
+                KeyValueDbIterableTrait::<
+                    MptKeyValue,
+                    [u8],
+                    KvdbSqliteShardedIteratorTag,
+                >::iter_range_excl(
+                    kvdb, lower_bound_excl, upper_bound_excl
+                )?
+                .take(),
+            ),
+            AnySnapshotKvIter::Rocksdb(table) => Box::new(
+                KeyValueDbIterableTrait::<
+                    MptKeyValue,
+                    [u8],
+                    SnapshotDbRocksdbIteratorTag,
+                >::iter_range_excl(
+                    table, lower_bound_excl, upper_bound_excl
+                )?
+                .take(),
+            ),
+        };
+        Ok(Wrap(iter))
+    }
+}
+
+enable_impl_transmute_for_element_satisfy! {
+    generic ;
+    trait 'static + KeyValueDbIterableTrait<
+        MptKeyValue,
+        [u8],
+        AnySnapshotDbIteratorTag,
+    >;
+    for AnySnapshotKvIter;
+}
+
+impl
+    WrappedTrait<
+        dyn KeyValueDbIterableTrait<
+            MptKeyValue,
+            [u8],
+            AnySnapshotDbIteratorTag,
+        >,
+    > for AnySnapshotKvIter
+{
+}
+
+impl<'a>
+    WrappedLifetimeFamily<
+        'a,
+        dyn KeyValueDbIterableTrait<
+            MptKeyValue,
+            [u8],
+            AnySnapshotDbIteratorTag,
+        >,
+    > for AnySnapshotKvIter
+{
+    type Out = Self;
+}
+
+use crate::{
+    impls::{
+        errors::*,
+        merkle_patricia_trie::{CompressedPathTrait, MptKeyValue},
+        storage_db::{
+            kvdb_sqlite_sharded::{
+                KvdbSqliteSharded, KvdbSqliteShardedIteratorTag,
+            },
+            snapshot_db_manager_sqlite::AlreadyOpenSnapshots,
+            snapshot_db_rocksdb::{
+                SnapshotDbRocksdb, SnapshotDbRocksdbIteratorTag,
+                SnapshotDbRocksdbTable,
+            },
+            snapshot_db_sqlite::SnapshotDbSqlite,
+        },
+    },
+    storage_db::{
+        KeyValueDbIterableTrait, KeyValueDbTraitOwnedRead, KeyValueDbTraitRead,
+        KeyValueDbTraitSingleWriter, KeyValueDbTypes, KvdbIterIterator,
+        OpenSnapshotMptTrait, SnapshotDbTrait, SnapshotMptIteraterTrait,
+        SnapshotMptNode, SnapshotMptTraitRead, SnapshotMptTraitReadAndIterate,
+        SnapshotMptTraitRw,
+    },
+    utils::{
+        tuple::ElementSatisfy,
+        wrap::{Wrap, WrappedLifetimeFamily, WrappedTrait},
+    },
+    SnapshotDbBackend,
+};
+use fallible_iterator::FallibleIterator;
+use primitives::MerkleHash;
+use std::{path::Path, sync::Arc};
+use tokio::sync::Semaphore;
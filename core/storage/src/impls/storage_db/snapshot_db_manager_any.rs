@@ -0,0 +1,441 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+/// The snapshot db manager of the backend selected by
+/// StorageConfiguration::snapshot_db_backend.
+pub enum AnySnapshotDbManager {
+    Sqlite(SnapshotDbManagerSqlite),
+    Rocksdb(SnapshotDbManagerRocksdb),
+}
+
+impl AnySnapshotDbManager {
+    const CONVERT_MAX_OPEN_SNAPSHOTS: u16 = 2;
+    const CONVERT_TEMP_DIR_INFIX: &'static str = "convert_temp_";
+
+    /// Fails if the snapshot dir has snapshots of the other backend, because
+    /// they would be removed as unexpected files by scan_persist_state.
+    pub fn new(
+        snapshot_path: PathBuf, max_open_snapshots: u16,
+        backend: SnapshotDbBackend,
+    ) -> Result<Self>
+    {
+        if snapshot_path.exists() {
+            let other_backend = Self::other_backend(backend);
+            for entry in fs::read_dir(&snapshot_path)? {
+                let has_other_backend_prefix =
+                    entry?.file_name().to_str().map_or(false, |name| {
+                        name.starts_with(Self::dir_prefix(other_backend))
+                    });
+                if has_other_backend_prefix {
+                    bail!(ErrorKind::SnapshotDbBackendMismatch(
+                        other_backend.name(),
+                        backend.name(),
+                    ));
+                }
+            }
+        }
+        Self::new_unchecked(snapshot_path, max_open_snapshots, backend)
+    }
+
+    fn new_unchecked(
+        snapshot_path: PathBuf, max_open_snapshots: u16,
+        backend: SnapshotDbBackend,
+    ) -> Result<Self>
+    {
+        Ok(match backend {
+            SnapshotDbBackend::Sqlite => {
+                AnySnapshotDbManager::Sqlite(SnapshotDbManagerSqlite::new(
+                    snapshot_path,
+                    max_open_snapshots,
+                )?)
+            }
+            SnapshotDbBackend::Rocksdb => {
+                AnySnapshotDbManager::Rocksdb(SnapshotDbManagerRocksdb::new(
+                    snapshot_path,
+                    max_open_snapshots,
+                )?)
+            }
+        })
+    }
+
+    pub fn backend(&self) -> SnapshotDbBackend {
+        match self {
+            AnySnapshotDbManager::Sqlite(_) => SnapshotDbBackend::Sqlite,
+            AnySnapshotDbManager::Rocksdb(_) => SnapshotDbBackend::Rocksdb,
+        }
+    }
+
+    fn other_backend(backend: SnapshotDbBackend) -> SnapshotDbBackend {
+        match backend {
+            SnapshotDbBackend::Sqlite => SnapshotDbBackend::Rocksdb,
+            SnapshotDbBackend::Rocksdb => SnapshotDbBackend::Sqlite,
+        }
+    }
+
+    fn dir_prefix(backend: SnapshotDbBackend) -> &'static str {
+        match backend {
+            SnapshotDbBackend::Sqlite => {
+                SnapshotDbManagerSqlite::SNAPSHOT_DB_SQLITE_DIR_PREFIX
+            }
+            SnapshotDbBackend::Rocksdb => {
+                SnapshotDbManagerRocksdb::SNAPSHOT_DB_ROCKSDB_DIR_PREFIX
+            }
+        }
+    }
+
+    /// Converts the snapshots in snapshot_path to the format of to_backend
+    /// and returns the number of converted snapshots. The temp snapshots of
+    /// merging and full sync are removed because they can't be resumed by the
+    /// other backend.
+    ///
+    /// The snapshots are converted in place, the node must be stopped.
+    pub fn convert_snapshots(
+        snapshot_path: &Path, to_backend: SnapshotDbBackend,
+    ) -> Result<usize> {
+        let from_backend = Self::other_backend(to_backend);
+        let from_manager = Self::new_unchecked(
+            snapshot_path.to_path_buf(),
+            Self::CONVERT_MAX_OPEN_SNAPSHOTS,
+            from_backend,
+        )?;
+        let to_manager = Self::new_unchecked(
+            snapshot_path.to_path_buf(),
+            Self::CONVERT_MAX_OPEN_SNAPSHOTS,
+            to_backend,
+        )?;
+        let from_prefix = Self::dir_prefix(from_backend);
+
+        // Collect the entries first because the dir is modified below.
+        let mut snapshot_dirs = vec![];
+        for entry in fs::read_dir(snapshot_path)? {
+            let path = entry?.path();
+            let dir_name = path.file_name().and_then(|name| name.to_str());
+            match dir_name {
+                Some(dir_name) if dir_name.starts_with(from_prefix) => {
+                    let maybe_epoch_id =
+                        Self::parse_epoch_id(&dir_name[from_prefix.len()..]);
+                    snapshot_dirs.push((path.clone(), maybe_epoch_id));
+                }
+                _ => {}
+            }
+        }
+
+        let mut converted = 0;
+        for (path, maybe_epoch_id) in snapshot_dirs {
+            match maybe_epoch_id {
+                None => {
+                    info!("Remove temp snapshot {}", path.display());
+                    fs::remove_dir_all(&path)?;
+                }
+                Some(snapshot_epoch_id) => {
+                    info!(
+                        "Convert snapshot {:?} from {} to {}",
+                        snapshot_epoch_id,
+                        from_backend.name(),
+                        to_backend.name()
+                    );
+                    Self::convert_snapshot(
+                        &from_manager,
+                        &to_manager,
+                        &snapshot_epoch_id,
+                    )?;
+                    converted += 1;
+                }
+            }
+        }
+        Ok(converted)
+    }
+
+    fn parse_epoch_id(hex: &str) -> Option<EpochId> {
+        let bytes: Vec<u8> = hex.from_hex().ok()?;
+        if bytes.len() == EpochId::len_bytes() {
+            Some(EpochId::from_slice(&bytes))
+        } else {
+            None
+        }
+    }
+
+    fn convert_snapshot(
+        from_manager: &AnySnapshotDbManager, to_manager: &AnySnapshotDbManager,
+        snapshot_epoch_id: &EpochId,
+    ) -> Result<()>
+    {
+        let old_snapshot_db = from_manager
+            .get_snapshot_by_epoch_id(
+                snapshot_epoch_id,
+                /* try_open = */ false,
+            )?
+            .ok_or(Error::from(ErrorKind::SnapshotNotFound))?;
+        let temp_db_path = to_manager.get_snapshot_dir().join(
+            Self::dir_prefix(to_manager.backend()).to_string()
+                + Self::CONVERT_TEMP_DIR_INFIX
+                + &snapshot_epoch_id.as_ref().to_hex::<String>(),
+        );
+        if temp_db_path.exists() {
+            fs::remove_dir_all(&temp_db_path)?;
+        }
+        let mut new_snapshot_db = match to_manager.backend() {
+            SnapshotDbBackend::Sqlite => {
+                AnySnapshotDb::Sqlite(Arc::new(SnapshotDbSqlite::create(
+                    &temp_db_path,
+                    &Default::default(),
+                    &Arc::new(Semaphore::new(0)),
+                )?))
+            }
+            SnapshotDbBackend::Rocksdb => {
+                AnySnapshotDb::Rocksdb(Arc::new(SnapshotDbRocksdb::create(
+                    &temp_db_path,
+                    &Default::default(),
+                    &Arc::new(Semaphore::new(0)),
+                )?))
+            }
+        };
+
+        new_snapshot_db.start_transaction()?;
+        // The iterators and mpts are scoped so that the dbs are closed before
+        // the old snapshot is removed.
+        {
+            let mut kv_iterator =
+                old_snapshot_db.snapshot_kv_iterator()?.take();
+            let mut kvs = kv_iterator.iter_range(&[], None)?.take();
+            while let Some((key, value)) = kvs.next()? {
+                new_snapshot_db.put(&key, &value)?;
+            }
+        }
+        let merkle_root;
+        {
+            let mut old_mpt = old_snapshot_db.open_snapshot_mpt_as_owned()?;
+            merkle_root = old_mpt.get_merkle_root();
+            let mut new_mpt = new_snapshot_db.open_snapshot_mpt_owned()?;
+            let root_path = CompressedPathRaw::default();
+            if let Some(root_node) = old_mpt.load_node(&root_path)? {
+                new_mpt.write_node(&root_path, &root_node)?;
+                let mut nodes = old_mpt
+                    .iterate_subtree_trie_nodes_without_root(&root_path)?;
+                while let Some((path, node)) = nodes.next()? {
+                    new_mpt.write_node(&path, &node)?;
+                }
+            }
+        }
+        new_snapshot_db.commit_transaction()?;
+
+        let new_merkle_root = new_snapshot_db
+            .open_snapshot_mpt_shared()?
+            .get_merkle_root();
+        if new_merkle_root != merkle_root {
+            error!(
+                "Converted snapshot {:?} has merkle root {:?}, expected {:?}",
+                snapshot_epoch_id, new_merkle_root, merkle_root
+            );
+            bail!(ErrorKind::SnapshotConvertMerkleRootMismatch);
+        }
+
+        drop(new_snapshot_db);
+        drop(old_snapshot_db);
+        fs::rename(
+            &temp_db_path,
+            to_manager.get_snapshot_db_path(snapshot_epoch_id),
+        )?;
+        from_manager.destroy_snapshot(snapshot_epoch_id)
+    }
+}
+
+impl SnapshotDbManagerTrait for AnySnapshotDbManager {
+    type SnapshotDb = AnySnapshotDb;
+
+    fn get_snapshot_dir(&self) -> &Path {
+        match self {
+            AnySnapshotDbManager::Sqlite(manager) => manager.get_snapshot_dir(),
+            AnySnapshotDbManager::Rocksdb(manager) => {
+                manager.get_snapshot_dir()
+            }
+        }
+    }
+
+    fn get_snapshot_db_name(&self, snapshot_epoch_id: &EpochId) -> String {
+        match self {
+            AnySnapshotDbManager::Sqlite(manager) => {
+                manager.get_snapshot_db_name(snapshot_epoch_id)
+            }
+            AnySnapshotDbManager::Rocksdb(manager) => {
+                manager.get_snapshot_db_name(snapshot_epoch_id)
+            }
+        }
+    }
+
+    fn get_snapshot_db_path(&self, snapshot_epoch_id: &EpochId) -> PathBuf {
+        match self {
+            AnySnapshotDbManager::Sqlite(manager) => {
+                manager.get_snapshot_db_path(snapshot_epoch_id)
+            }
+            AnySnapshotDbManager::Rocksdb(manager) => {
+                manager.get_snapshot_db_path(snapshot_epoch_id)
+            }
+        }
+    }
+
+    fn new_snapshot_by_merging<'m>(
+        &self, old_snapshot_epoch_id: &EpochId, snapshot_epoch_id: EpochId,
+        delta_mpt: DeltaMptIterator, in_progress_snapshot_info: SnapshotInfo,
+        snapshot_info_map_rwlock: &'m RwLock<PersistedSnapshotInfoMap>,
+    ) -> Result<(RwLockWriteGuard<'m, PersistedSnapshotInfoMap>, SnapshotInfo)>
+    {
+        match self {
+            AnySnapshotDbManager::Sqlite(manager) => manager
+                .new_snapshot_by_merging(
+                    old_snapshot_epoch_id,
+                    snapshot_epoch_id,
+                    delta_mpt,
+                    in_progress_snapshot_info,
+                    snapshot_info_map_rwlock,
+                ),
+            AnySnapshotDbManager::Rocksdb(manager) => manager
+                .new_snapshot_by_merging(
+                    old_snapshot_epoch_id,
+                    snapshot_epoch_id,
+                    delta_mpt,
+                    in_progress_snapshot_info,
+                    snapshot_info_map_rwlock,
+                ),
+        }
+    }
+
+    fn get_snapshot_by_epoch_id(
+        &self, snapshot_epoch_id: &EpochId, try_open: bool,
+    ) -> Result<Option<Arc<AnySnapshotDb>>> {
+        Ok(match self {
+            AnySnapshotDbManager::Sqlite(manager) => manager
+                .get_snapshot_by_epoch_id(snapshot_epoch_id, try_open)?
+                .map(|db| Arc::new(AnySnapshotDb::Sqlite(db))),
+            AnySnapshotDbManager::Rocksdb(manager) => manager
+                .get_snapshot_by_epoch_id(snapshot_epoch_id, try_open)?
+                .map(|db| Arc::new(AnySnapshotDb::Rocksdb(db))),
+        })
+    }
+
+    fn destroy_snapshot(&self, snapshot_epoch_id: &EpochId) -> Result<()> {
+        match self {
+            AnySnapshotDbManager::Sqlite(manager) => {
+                manager.destroy_snapshot(snapshot_epoch_id)
+            }
+            AnySnapshotDbManager::Rocksdb(manager) => {
+                manager.destroy_snapshot(snapshot_epoch_id)
+            }
+        }
+    }
+
+    fn new_temp_snapshot_for_full_sync(
+        &self, snapshot_epoch_id: &EpochId, merkle_root: &MerkleHash,
+    ) -> Result<AnySnapshotDb> {
+        Ok(match self {
+            AnySnapshotDbManager::Sqlite(manager) => AnySnapshotDb::Sqlite(
+                Arc::new(manager.new_temp_snapshot_for_full_sync(
+                    snapshot_epoch_id,
+                    merkle_root,
+                )?),
+            ),
+            AnySnapshotDbManager::Rocksdb(manager) => AnySnapshotDb::Rocksdb(
+                Arc::new(manager.new_temp_snapshot_for_full_sync(
+                    snapshot_epoch_id,
+                    merkle_root,
+                )?),
+            ),
+        })
+    }
+
+    fn open_temp_snapshot_for_full_sync(
+        &self, snapshot_epoch_id: &EpochId, merkle_root: &MerkleHash,
+    ) -> Result<Option<AnySnapshotDb>> {
+        Ok(match self {
+            AnySnapshotDbManager::Sqlite(manager) => manager
+                .open_temp_snapshot_for_full_sync(
+                    snapshot_epoch_id,
+                    merkle_root,
+                )?
+                .map(|db| AnySnapshotDb::Sqlite(Arc::new(db))),
+            AnySnapshotDbManager::Rocksdb(manager) => manager
+                .open_temp_snapshot_for_full_sync(
+                    snapshot_epoch_id,
+                    merkle_root,
+                )?
+                .map(|db| AnySnapshotDb::Rocksdb(Arc::new(db))),
+        })
+    }
+
+    fn get_full_sync_progress_path(
+        &self, snapshot_epoch_id: &EpochId, merkle_root: &MerkleHash,
+    ) -> Option<PathBuf> {
+        match self {
+            AnySnapshotDbManager::Sqlite(manager) => manager
+                .get_full_sync_progress_path(snapshot_epoch_id, merkle_root),
+            AnySnapshotDbManager::Rocksdb(manager) => manager
+                .get_full_sync_progress_path(snapshot_epoch_id, merkle_root),
+        }
+    }
+
+    fn is_temp_snapshot_for_full_sync(&self, dir_name: &str) -> bool {
+        match self {
+            AnySnapshotDbManager::Sqlite(manager) => {
+                manager.is_temp_snapshot_for_full_sync(dir_name)
+            }
+            AnySnapshotDbManager::Rocksdb(manager) => {
+                manager.is_temp_snapshot_for_full_sync(dir_name)
+            }
+        }
+    }
+
+    fn finalize_full_sync_snapshot<'m>(
+        &self, snapshot_epoch_id: &EpochId, merkle_root: &MerkleHash,
+        snapshot_info_map_rwlock: &'m RwLock<PersistedSnapshotInfoMap>,
+    ) -> Result<RwLockWriteGuard<'m, PersistedSnapshotInfoMap>>
+    {
+        match self {
+            AnySnapshotDbManager::Sqlite(manager) => manager
+                .finalize_full_sync_snapshot(
+                    snapshot_epoch_id,
+                    merkle_root,
+                    snapshot_info_map_rwlock,
+                ),
+            AnySnapshotDbManager::Rocksdb(manager) => manager
+                .finalize_full_sync_snapshot(
+                    snapshot_epoch_id,
+                    merkle_root,
+                    snapshot_info_map_rwlock,
+                ),
+        }
+    }
+}
+
+use crate::{
+    impls::{
+        delta_mpt::DeltaMptIterator,
+        errors::*,
+        merkle_patricia_trie::CompressedPathRaw,
+        storage_db::{
+            snapshot_db_any::AnySnapshotDb,
+            snapshot_db_manager_rocksdb::SnapshotDbManagerRocksdb,
+            snapshot_db_manager_sqlite::SnapshotDbManagerSqlite,
+            snapshot_db_rocksdb::SnapshotDbRocksdb,
+            snapshot_db_sqlite::SnapshotDbSqlite,
+        },
+        storage_manager::PersistedSnapshotInfoMap,
+    },
+    storage_db::{
+        KeyValueDbIterableTrait, KeyValueDbTraitSingleWriter,
+        OpenSnapshotMptTrait, SnapshotDbManagerTrait, SnapshotDbTrait,
+        SnapshotInfo, SnapshotMptTraitRead, SnapshotMptTraitReadAndIterate,
+        SnapshotMptTraitRw,
+    },
+    SnapshotDbBackend,
+};
+use fallible_iterator::FallibleIterator;
+use parking_lot::{RwLock, RwLockWriteGuard};
+use primitives::{EpochId, MerkleHash};
+use rustc_hex::{FromHex, ToHex};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::sync::Semaphore;
@@ -0,0 +1,437 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+pub struct SnapshotDbManagerRocksdb {
+    snapshot_path: PathBuf,
+    already_open_snapshots: AlreadyOpenSnapshots<SnapshotDbRocksdb>,
+    /// Set a limit on the number of open snapshots. When the limit is reached,
+    /// consensus initiated open should wait, other non-critical opens such as
+    /// rpc initiated opens should simply abort when the limit is reached.
+    open_snapshot_semaphore: Arc<Semaphore>,
+    open_create_delete_lock: Mutex<()>,
+}
+
+impl SnapshotDbManagerRocksdb {
+    const FULL_SYNC_PROGRESS_FILE_NAME: &'static str = "full_sync_progress";
+    const FULL_SYNC_TEMP_DIR_PREFIX: &'static str = "rocksdb_full_sync_temp_";
+    pub const SNAPSHOT_DB_ROCKSDB_DIR_PREFIX: &'static str = "rocksdb_";
+
+    pub fn new(
+        snapshot_path: PathBuf, max_open_snapshots: u16,
+    ) -> Result<Self> {
+        if !snapshot_path.exists() {
+            fs::create_dir_all(snapshot_path.clone())?;
+        }
+
+        Ok(Self {
+            snapshot_path,
+            already_open_snapshots: Default::default(),
+            open_snapshot_semaphore: Arc::new(Semaphore::new(
+                max_open_snapshots as usize,
+            )),
+            open_create_delete_lock: Default::default(),
+        })
+    }
+
+    fn open_snapshot_readonly(
+        &self, snapshot_path: PathBuf, try_open: bool,
+    ) -> Result<Option<Arc<SnapshotDbRocksdb>>> {
+        if let Some(already_open) =
+            self.get_already_open_snapshot(&snapshot_path)
+        {
+            return Ok(already_open);
+        }
+        if !snapshot_path.exists() {
+            return Ok(None);
+        }
+        let semaphore_permit = if try_open {
+            self.open_snapshot_semaphore
+                .try_acquire()
+                // Unfortunately we have to use map_error because the
+                // TryAcquireError isn't public.
+                .map_err(|_err| ErrorKind::SemaphoreTryAcquireError)?
+        } else {
+            executor::block_on(self.open_snapshot_semaphore.acquire())
+        };
+
+        // To serialize simultaneous opens.
+        let _open_lock = self.open_create_delete_lock.lock();
+        if let Some(already_open) =
+            self.get_already_open_snapshot(&snapshot_path)
+        {
+            return Ok(already_open);
+        }
+
+        let snapshot_db = Arc::new(SnapshotDbRocksdb::open(
+            snapshot_path.as_path(),
+            /* readonly = */ true,
+            &self.already_open_snapshots,
+            &self.open_snapshot_semaphore,
+        )?);
+
+        semaphore_permit.forget();
+        self.already_open_snapshots
+            .write()
+            .insert(snapshot_path, Some(Arc::downgrade(&snapshot_db)));
+
+        Ok(Some(snapshot_db))
+    }
+
+    /// Returns Some(None) if the snapshot is already open for exclusive
+    /// write, Some(Some(snapshot)) if it's already open for read, or None if
+    /// it isn't open.
+    fn get_already_open_snapshot(
+        &self, snapshot_path: &Path,
+    ) -> Option<Option<Arc<SnapshotDbRocksdb>>> {
+        match self.already_open_snapshots.read().get(snapshot_path) {
+            None => None,
+            Some(None) => Some(None),
+            Some(Some(open_shared_weak)) => {
+                Weak::upgrade(open_shared_weak).map(Some)
+            }
+        }
+    }
+
+    fn open_snapshot_write(
+        &self, snapshot_path: PathBuf, create: bool,
+    ) -> Result<SnapshotDbRocksdb> {
+        if self
+            .already_open_snapshots
+            .read()
+            .get(&snapshot_path)
+            .is_some()
+        {
+            bail!(ErrorKind::SnapshotAlreadyExists)
+        }
+
+        let semaphore_permit =
+            executor::block_on(self.open_snapshot_semaphore.acquire());
+        // When an open happens around the same time, we should make sure that
+        // the open returns None.
+        let _open_lock = self.open_create_delete_lock.lock();
+
+        // Simultaneous creation fails here.
+        if self
+            .already_open_snapshots
+            .read()
+            .get(&snapshot_path)
+            .is_some()
+        {
+            bail!(ErrorKind::SnapshotAlreadyExists)
+        }
+
+        let snapshot_db = if create {
+            SnapshotDbRocksdb::create(
+                snapshot_path.as_path(),
+                &self.already_open_snapshots,
+                &self.open_snapshot_semaphore,
+            )
+        } else if snapshot_path.exists() {
+            SnapshotDbRocksdb::open(
+                snapshot_path.as_path(),
+                /* readonly = */ false,
+                &self.already_open_snapshots,
+                &self.open_snapshot_semaphore,
+            )
+        } else {
+            bail!(ErrorKind::SnapshotNotFound);
+        }?;
+
+        semaphore_permit.forget();
+        self.already_open_snapshots
+            .write()
+            .insert(snapshot_path, None);
+        Ok(snapshot_db)
+    }
+
+    pub fn on_close(
+        already_open_snapshots: &AlreadyOpenSnapshots<SnapshotDbRocksdb>,
+        open_semaphore: &Arc<Semaphore>, path: &Path, remove_on_close: bool,
+    )
+    {
+        // Destroy at close. See SnapshotDbManagerSqlite::on_close for why the
+        // error is ignored.
+        if remove_on_close {
+            Self::fs_remove_snapshot(path).ok();
+        }
+        already_open_snapshots.write().remove(path);
+        open_semaphore.add_permits(1);
+    }
+
+    fn fs_remove_snapshot(path: &Path) -> Result<()> {
+        debug!("Remove snapshot at {}", path.display());
+        Ok(fs::remove_dir_all(path)?)
+    }
+
+    fn get_merge_temp_snapshot_db_path(
+        &self, old_snapshot_epoch_id: &EpochId, new_snapshot_epoch_id: &EpochId,
+    ) -> PathBuf {
+        self.snapshot_path.join(
+            Self::SNAPSHOT_DB_ROCKSDB_DIR_PREFIX.to_string()
+                + "merge_temp_"
+                + &old_snapshot_epoch_id.as_ref().to_hex::<String>()
+                + &new_snapshot_epoch_id.as_ref().to_hex::<String>(),
+        )
+    }
+
+    fn get_full_sync_temp_snapshot_db_path(
+        &self, snapshot_epoch_id: &EpochId, merkle_root: &MerkleHash,
+    ) -> PathBuf {
+        self.snapshot_path.join(
+            Self::FULL_SYNC_TEMP_DIR_PREFIX.to_string()
+                + &snapshot_epoch_id.as_ref().to_hex::<String>()
+                + &merkle_root.as_ref().to_hex::<String>(),
+        )
+    }
+
+    /// Removes the temp snapshots of other full sync restorations, which
+    /// are no longer resumable once a new restoration starts.
+    fn remove_stale_full_sync_temp_snapshots(
+        &self, current_path: &Path,
+    ) -> Result<()> {
+        for entry in fs::read_dir(&self.snapshot_path)? {
+            let path = entry?.path();
+            let is_full_sync_temp = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .map_or(false, |name| {
+                    self.is_temp_snapshot_for_full_sync(name)
+                });
+            if !is_full_sync_temp
+                || path == current_path
+                || self.already_open_snapshots.read().contains_key(&path)
+            {
+                continue;
+            }
+            info!("Remove stale full sync temp snapshot {}", path.display());
+            Self::fs_remove_snapshot(&path)?;
+        }
+        Ok(())
+    }
+
+    fn rename_snapshot_db<P: AsRef<Path>>(
+        old_path: P, new_path: P,
+    ) -> Result<()> {
+        Ok(fs::rename(old_path, new_path)?)
+    }
+}
+
+impl SnapshotDbManagerTrait for SnapshotDbManagerRocksdb {
+    type SnapshotDb = SnapshotDbRocksdb;
+
+    fn get_snapshot_dir(&self) -> &Path { self.snapshot_path.as_path() }
+
+    fn get_snapshot_db_name(&self, snapshot_epoch_id: &EpochId) -> String {
+        Self::SNAPSHOT_DB_ROCKSDB_DIR_PREFIX.to_string()
+            + &snapshot_epoch_id.as_ref().to_hex::<String>()
+    }
+
+    fn get_snapshot_db_path(&self, snapshot_epoch_id: &EpochId) -> PathBuf {
+        self.snapshot_path
+            .join(&self.get_snapshot_db_name(snapshot_epoch_id))
+    }
+
+    fn new_snapshot_by_merging<'m>(
+        &self, old_snapshot_epoch_id: &EpochId, snapshot_epoch_id: EpochId,
+        delta_mpt: DeltaMptIterator,
+        mut in_progress_snapshot_info: SnapshotInfo,
+        snapshot_info_map_rwlock: &'m RwLock<PersistedSnapshotInfoMap>,
+    ) -> Result<(RwLockWriteGuard<'m, PersistedSnapshotInfoMap>, SnapshotInfo)>
+    {
+        debug!(
+            "new_snapshot_by_merging: old={:?} new={:?}",
+            old_snapshot_epoch_id, snapshot_epoch_id
+        );
+        if delta_mpt.maybe_root_node.is_none() {
+            // The merge of the empty snapshot and the empty intermediate mpt,
+            // see SnapshotDbManagerSqlite::new_snapshot_by_merging.
+            in_progress_snapshot_info.merkle_root = MERKLE_NULL_NODE;
+            return Ok((
+                snapshot_info_map_rwlock.write(),
+                in_progress_snapshot_info,
+            ));
+        }
+
+        let temp_db_path = self.get_merge_temp_snapshot_db_path(
+            old_snapshot_epoch_id,
+            &snapshot_epoch_id,
+        );
+        // Remove the leftover of an interrupted merge, as the full sync does.
+        let already_open = self
+            .already_open_snapshots
+            .read()
+            .contains_key(&temp_db_path);
+        if temp_db_path.exists() && !already_open {
+            Self::fs_remove_snapshot(&temp_db_path)?;
+        }
+        let mut snapshot_db = self.open_snapshot_write(
+            temp_db_path.clone(),
+            /* create = */ true,
+        )?;
+        snapshot_db.dump_delta_mpt(&delta_mpt)?;
+        let new_snapshot_root = if *old_snapshot_epoch_id == NULL_EPOCH {
+            // direct merge the first snapshot
+            snapshot_db.direct_merge()?
+        } else {
+            // A RocksDB snapshot can't be copied while it's open, so the
+            // key-values are copied into the new snapshot instead.
+            let old_snapshot_db = self
+                .open_snapshot_readonly(
+                    self.get_snapshot_db_path(old_snapshot_epoch_id),
+                    /* try_open = */ false,
+                )?
+                .ok_or(Error::from(ErrorKind::SnapshotNotFound))?;
+            snapshot_db.copy_and_merge(&old_snapshot_db)?
+        };
+        in_progress_snapshot_info.merkle_root = new_snapshot_root.clone();
+        drop(snapshot_db);
+        let locked = snapshot_info_map_rwlock.write();
+        Self::rename_snapshot_db(
+            &temp_db_path,
+            &self.get_snapshot_db_path(&snapshot_epoch_id),
+        )?;
+
+        Ok((locked, in_progress_snapshot_info))
+    }
+
+    fn get_snapshot_by_epoch_id(
+        &self, snapshot_epoch_id: &EpochId, try_open: bool,
+    ) -> Result<Option<Arc<Self::SnapshotDb>>> {
+        if snapshot_epoch_id.eq(&NULL_EPOCH) {
+            return Ok(Some(Arc::new(Self::SnapshotDb::get_null_snapshot())));
+        } else {
+            let path = self.get_snapshot_db_path(snapshot_epoch_id);
+            self.open_snapshot_readonly(path, try_open)
+        }
+    }
+
+    fn destroy_snapshot(&self, snapshot_epoch_id: &EpochId) -> Result<()> {
+        let path = self.get_snapshot_db_path(snapshot_epoch_id);
+        let maybe_snapshot = match self.already_open_snapshots.read().get(&path)
+        {
+            Some(Some(snapshot)) => Weak::upgrade(snapshot),
+            Some(None) => {
+                // This should not happen because Conflux always write on a
+                // snapshot db under a temporary name. All completed snapshots
+                // are readonly.
+                unreachable!(
+                    "Try to destroy a snapshot being open exclusively for write."
+                )
+            }
+            None => None,
+        };
+
+        match maybe_snapshot {
+            None => {
+                if snapshot_epoch_id.ne(&NULL_EPOCH) {
+                    Self::fs_remove_snapshot(&path)?;
+                }
+            }
+            Some(snapshot) => {
+                snapshot.set_remove_on_last_close();
+            }
+        };
+
+        Ok(())
+    }
+
+    fn new_temp_snapshot_for_full_sync(
+        &self, snapshot_epoch_id: &EpochId, merkle_root: &MerkleHash,
+    ) -> Result<Self::SnapshotDb> {
+        let temp_db_path = self.get_full_sync_temp_snapshot_db_path(
+            snapshot_epoch_id,
+            merkle_root,
+        );
+        self.remove_stale_full_sync_temp_snapshots(&temp_db_path)?;
+        // Restart the restoration from scratch.
+        let already_open = self
+            .already_open_snapshots
+            .read()
+            .contains_key(&temp_db_path);
+        if temp_db_path.exists() && !already_open {
+            Self::fs_remove_snapshot(&temp_db_path)?;
+        }
+        self.open_snapshot_write(temp_db_path, /* create = */ true)
+    }
+
+    fn open_temp_snapshot_for_full_sync(
+        &self, snapshot_epoch_id: &EpochId, merkle_root: &MerkleHash,
+    ) -> Result<Option<Self::SnapshotDb>> {
+        let temp_db_path = self.get_full_sync_temp_snapshot_db_path(
+            snapshot_epoch_id,
+            merkle_root,
+        );
+        if !temp_db_path.exists() {
+            return Ok(None);
+        }
+        self.remove_stale_full_sync_temp_snapshots(&temp_db_path)?;
+        match self.open_snapshot_write(temp_db_path, /* create = */ false) {
+            Ok(snapshot_db) => Ok(Some(snapshot_db)),
+            Err(e) => {
+                warn!("Failed to resume the full sync temp snapshot: {}", e);
+                Ok(None)
+            }
+        }
+    }
+
+    fn get_full_sync_progress_path(
+        &self, snapshot_epoch_id: &EpochId, merkle_root: &MerkleHash,
+    ) -> Option<PathBuf> {
+        Some(
+            self.get_full_sync_temp_snapshot_db_path(
+                snapshot_epoch_id,
+                merkle_root,
+            )
+            .join(Self::FULL_SYNC_PROGRESS_FILE_NAME),
+        )
+    }
+
+    fn is_temp_snapshot_for_full_sync(&self, dir_name: &str) -> bool {
+        dir_name.starts_with(Self::FULL_SYNC_TEMP_DIR_PREFIX)
+    }
+
+    fn finalize_full_sync_snapshot<'m>(
+        &self, snapshot_epoch_id: &EpochId, merkle_root: &MerkleHash,
+        snapshot_info_map_rwlock: &'m RwLock<PersistedSnapshotInfoMap>,
+    ) -> Result<RwLockWriteGuard<'m, PersistedSnapshotInfoMap>>
+    {
+        let temp_db_path = self.get_full_sync_temp_snapshot_db_path(
+            snapshot_epoch_id,
+            merkle_root,
+        );
+        let final_db_path = self.get_snapshot_db_path(snapshot_epoch_id);
+        // The restoration is completed and no longer resumed.
+        let progress_path =
+            temp_db_path.join(Self::FULL_SYNC_PROGRESS_FILE_NAME);
+        if progress_path.exists() {
+            fs::remove_file(&progress_path)?;
+        }
+        let locked = snapshot_info_map_rwlock.write();
+        Self::rename_snapshot_db(&temp_db_path, &final_db_path)?;
+        Ok(locked)
+    }
+}
+
+use crate::{
+    impls::{
+        delta_mpt::DeltaMptIterator,
+        errors::*,
+        storage_db::{
+            snapshot_db_manager_sqlite::AlreadyOpenSnapshots,
+            snapshot_db_rocksdb::SnapshotDbRocksdb,
+        },
+        storage_manager::PersistedSnapshotInfoMap,
+    },
+    storage_db::{SnapshotDbManagerTrait, SnapshotDbTrait, SnapshotInfo},
+};
+use futures::executor;
+use parking_lot::{Mutex, RwLock, RwLockWriteGuard};
+use primitives::{EpochId, MerkleHash, MERKLE_NULL_NODE, NULL_EPOCH};
+use rustc_hex::ToHex;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Weak},
+};
+use tokio::sync::Semaphore;
@@ -23,7 +23,9 @@ pub type AlreadyOpenSnapshots<T> =
     Arc<RwLock<HashMap<PathBuf, Option<Weak<T>>>>>;
 
 impl SnapshotDbManagerSqlite {
-    const SNAPSHOT_DB_SQLITE_DIR_PREFIX: &'static str = "sqlite_";
+    const FULL_SYNC_PROGRESS_FILE_NAME: &'static str = "full_sync_progress";
+    const FULL_SYNC_TEMP_DIR_PREFIX: &'static str = "sqlite_full_sync_temp_";
+    pub const SNAPSHOT_DB_SQLITE_DIR_PREFIX: &'static str = "sqlite_";
 
     pub fn new(
         snapshot_path: PathBuf, max_open_snapshots: u16,
@@ -212,13 +214,37 @@ impl SnapshotDbManagerSqlite {
         &self, snapshot_epoch_id: &EpochId, merkle_root: &MerkleHash,
     ) -> PathBuf {
         self.snapshot_path.join(
-            Self::SNAPSHOT_DB_SQLITE_DIR_PREFIX.to_string()
-                + "full_sync_temp_"
+            Self::FULL_SYNC_TEMP_DIR_PREFIX.to_string()
                 + &snapshot_epoch_id.as_ref().to_hex::<String>()
                 + &merkle_root.as_ref().to_hex::<String>(),
         )
     }
 
+    /// Removes the temp snapshots of other full sync restorations, which
+    /// are no longer resumable once a new restoration starts.
+    fn remove_stale_full_sync_temp_snapshots(
+        &self, current_path: &Path,
+    ) -> Result<()> {
+        for entry in fs::read_dir(&self.snapshot_path)? {
+            let path = entry?.path();
+            let is_full_sync_temp = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .map_or(false, |name| {
+                    self.is_temp_snapshot_for_full_sync(name)
+                });
+            if !is_full_sync_temp
+                || path == current_path
+                || self.already_open_snapshots.read().contains_key(&path)
+            {
+                continue;
+            }
+            info!("Remove stale full sync temp snapshot {}", path.display());
+            Self::fs_remove_snapshot(&path)?;
+        }
+        Ok(())
+    }
+
     /// Returns error when cow copy fails; Ok(true) when cow copy succeeded;
     /// Ok(false) when we are running on a system where cow copy isn't
     /// available.
@@ -502,12 +528,57 @@ impl SnapshotDbManagerTrait for SnapshotDbManagerSqlite {
             snapshot_epoch_id,
             merkle_root,
         );
+        self.remove_stale_full_sync_temp_snapshots(&temp_db_path)?;
+        // Restart the restoration from scratch.
+        let already_open = self
+            .already_open_snapshots
+            .read()
+            .contains_key(&temp_db_path);
+        if temp_db_path.exists() && !already_open {
+            Self::fs_remove_snapshot(&temp_db_path)?;
+        }
         self.open_snapshot_write(
             temp_db_path.to_path_buf(),
             /* create = */ true,
         )
     }
 
+    fn open_temp_snapshot_for_full_sync(
+        &self, snapshot_epoch_id: &EpochId, merkle_root: &MerkleHash,
+    ) -> Result<Option<Self::SnapshotDb>> {
+        let temp_db_path = self.get_full_sync_temp_snapshot_db_path(
+            snapshot_epoch_id,
+            merkle_root,
+        );
+        if !temp_db_path.exists() {
+            return Ok(None);
+        }
+        self.remove_stale_full_sync_temp_snapshots(&temp_db_path)?;
+        match self.open_snapshot_write(temp_db_path, /* create = */ false) {
+            Ok(snapshot_db) => Ok(Some(snapshot_db)),
+            Err(e) => {
+                warn!("Failed to resume the full sync temp snapshot: {}", e);
+                Ok(None)
+            }
+        }
+    }
+
+    fn get_full_sync_progress_path(
+        &self, snapshot_epoch_id: &EpochId, merkle_root: &MerkleHash,
+    ) -> Option<PathBuf> {
+        Some(
+            self.get_full_sync_temp_snapshot_db_path(
+                snapshot_epoch_id,
+                merkle_root,
+            )
+            .join(Self::FULL_SYNC_PROGRESS_FILE_NAME),
+        )
+    }
+
+    fn is_temp_snapshot_for_full_sync(&self, dir_name: &str) -> bool {
+        dir_name.starts_with(Self::FULL_SYNC_TEMP_DIR_PREFIX)
+    }
+
     fn finalize_full_sync_snapshot<'m>(
         &self, snapshot_epoch_id: &EpochId, merkle_root: &MerkleHash,
         snapshot_info_map_rwlock: &'m RwLock<PersistedSnapshotInfoMap>,
@@ -518,6 +589,12 @@ impl SnapshotDbManagerTrait for SnapshotDbManagerSqlite {
             merkle_root,
         );
         let final_db_path = self.get_snapshot_db_path(snapshot_epoch_id);
+        // The restoration is completed and no longer resumed.
+        let progress_path =
+            temp_db_path.join(Self::FULL_SYNC_PROGRESS_FILE_NAME);
+        if progress_path.exists() {
+            fs::remove_file(&progress_path)?;
+        }
         let locked = snapshot_info_map_rwlock.write();
         Self::rename_snapshot_db(&temp_db_path, &final_db_path)?;
         Ok(locked)
@@ -0,0 +1,658 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+/// The snapshot db on RocksDB. The tables of SnapshotDbSqlite are kept in
+/// columns of a single database.
+pub struct SnapshotDbRocksdb {
+    // Option because we need an empty snapshot db for empty snapshot.
+    maybe_db: Option<Arc<Database>>,
+    already_open_snapshots: AlreadyOpenSnapshots<Self>,
+    open_semaphore: Arc<Semaphore>,
+    path: PathBuf,
+    remove_on_close: AtomicBool,
+}
+
+/// A column of SnapshotDbRocksdb.
+#[derive(Clone)]
+pub struct SnapshotDbRocksdbTable {
+    maybe_db: Option<Arc<Database>>,
+    col: u32,
+}
+
+pub struct SnapshotDbRocksdbTableIterator {
+    table: SnapshotDbRocksdbTable,
+    // None when there is nothing more to load from the db.
+    maybe_next_lower_bound_incl: Option<Vec<u8>>,
+    upper_bound_excl: Option<Vec<u8>>,
+    loaded: VecDeque<KeyValuePair>,
+}
+
+pub struct SnapshotDbRocksdbIteratorTag();
+
+type KeyValuePair = (Box<[u8]>, Box<[u8]>);
+
+impl Drop for SnapshotDbRocksdb {
+    fn drop(&mut self) {
+        if !self.path.as_os_str().is_empty() {
+            self.maybe_db.take();
+            SnapshotDbManagerRocksdb::on_close(
+                &self.already_open_snapshots,
+                &self.open_semaphore,
+                &self.path,
+                self.remove_on_close.load(Ordering::Relaxed),
+            )
+        }
+    }
+}
+
+impl SnapshotDbRocksdb {
+    /// The delta mpt key-values to set and to delete, which are temporary
+    /// tables for the merging process.
+    pub const DELTA_KV_DELETE_COL: u32 = 3;
+    pub const DELTA_KV_SET_COL: u32 = 2;
+    const ROCKSDB_CONFIG: DatabaseConfig = DatabaseConfig {
+        max_open_files: 512,
+        memory_budget: None,
+        compaction: CompactionProfile {
+            initial_file_size: 512 * 1048576 as u64,
+            block_size: 16 * 1024,
+            write_rate_limit: Some(64 * 1048576 as u64),
+        },
+        columns: 4,
+        disable_wal: false,
+    };
+    /// Key-Value table.
+    pub const SNAPSHOT_KV_COL: u32 = 0;
+    /// MPT Table.
+    pub const SNAPSHOT_MPT_COL: u32 = 1;
+
+    fn open_db(snapshot_path: &Path) -> Result<Arc<Database>> {
+        Ok(Arc::new(Database::open(
+            &Self::ROCKSDB_CONFIG,
+            snapshot_path.to_str().unwrap(),
+        )?))
+    }
+
+    fn table(&self, col: u32) -> SnapshotDbRocksdbTable {
+        SnapshotDbRocksdbTable {
+            maybe_db: self.maybe_db.clone(),
+            col,
+        }
+    }
+
+    pub fn set_remove_on_last_close(&self) {
+        self.remove_on_close.store(true, Ordering::Relaxed);
+    }
+
+    fn write_batch(&self) -> Result<SnapshotDbRocksdbWriteBatch> {
+        match &self.maybe_db {
+            None => Err(Error::from(ErrorKind::DbNotExist)),
+            Some(db) => Ok(SnapshotDbRocksdbWriteBatch::new(db.clone())),
+        }
+    }
+
+    /// The delta mpt key-values are only kept during the merge, so these
+    /// tables are empty for a completed snapshot.
+    pub fn dumped_delta_kv_set_keys_iterator(
+        &self,
+    ) -> Result<SnapshotDbRocksdbTable> {
+        Ok(self.table(Self::DELTA_KV_SET_COL))
+    }
+
+    pub fn dumped_delta_kv_delete_keys_iterator(
+        &self,
+    ) -> Result<SnapshotDbRocksdbTable> {
+        Ok(self.table(Self::DELTA_KV_DELETE_COL))
+    }
+
+    pub fn dump_delta_mpt(
+        &mut self, delta_mpt: &DeltaMptIterator,
+    ) -> Result<()> {
+        debug!("dump_delta_mpt starts");
+        let mut dumper = DeltaMptMergeDumperRocksdb {
+            batch: self.write_batch()?,
+        };
+        delta_mpt.iterate(&mut dumper)?;
+        dumper.batch.flush()
+    }
+
+    fn apply_update_to_kvdb(&mut self) -> Result<()> {
+        let mut batch = self.write_batch()?;
+        let mut delete_keys_iter = self
+            .table(Self::DELTA_KV_DELETE_COL)
+            .iter_range_impl(&[], None);
+        while let Some((key, _)) = delete_keys_iter.next()? {
+            batch.delete(Self::SNAPSHOT_KV_COL, &key)?;
+        }
+        let mut set_keys_iter = self
+            .table(Self::DELTA_KV_SET_COL)
+            .iter_range_impl(&[], None);
+        while let Some((key, value)) = set_keys_iter.next()? {
+            batch.put(Self::SNAPSHOT_KV_COL, &key, &value)?;
+        }
+        batch.flush()
+    }
+
+    /// Empty the temporary tables of the delta mpt key-values after the
+    /// merge, so that they aren't kept in the snapshot.
+    fn clear_delta_kvs(&mut self) -> Result<()> {
+        let mut batch = self.write_batch()?;
+        for col in &[Self::DELTA_KV_DELETE_COL, Self::DELTA_KV_SET_COL] {
+            let mut keys_iter = self.table(*col).iter_range_impl(&[], None);
+            while let Some((key, _)) = keys_iter.next()? {
+                batch.delete(*col, &key)?;
+            }
+        }
+        batch.flush()
+    }
+
+    fn merge(
+        &mut self, maybe_old_snapshot_db: Option<&SnapshotDbRocksdb>,
+    ) -> Result<MerkleHash> {
+        let delete_keys_iter = self
+            .table(Self::DELTA_KV_DELETE_COL)
+            .iter_range_impl(&[], None)
+            .map(|(key, _)| Ok((key, ())));
+        let set_keys_iter = self
+            .table(Self::DELTA_KV_SET_COL)
+            .iter_range_impl(&[], None);
+        let mut maybe_base_mpt = match maybe_old_snapshot_db {
+            None => None,
+            Some(old_snapshot_db) => {
+                Some(old_snapshot_db.open_snapshot_mpt_as_owned()?)
+            }
+        };
+        let mut mpt_to_modify = self.open_snapshot_mpt_owned()?;
+        MptMerger::new(
+            maybe_base_mpt
+                .as_mut()
+                .map(|mpt| mpt as &mut dyn SnapshotMptTraitReadAndIterate),
+            &mut mpt_to_modify as &mut dyn SnapshotMptTraitRw,
+        )
+        .merge_insertion_deletion_separated(delete_keys_iter, set_keys_iter)
+    }
+}
+
+impl KeyValueDbTypes for SnapshotDbRocksdb {
+    type ValueType = Box<[u8]>;
+}
+
+impl KeyValueDbTraitRead for SnapshotDbRocksdb {
+    fn get(&self, key: &[u8]) -> Result<Option<Box<[u8]>>> {
+        self.table(Self::SNAPSHOT_KV_COL).get(key)
+    }
+}
+
+impl KeyValueDbTraitOwnedRead for SnapshotDbRocksdb {
+    fn get_mut(&mut self, key: &[u8]) -> Result<Option<Box<[u8]>>> {
+        self.get(key)
+    }
+}
+
+impl KeyValueDbTraitSingleWriter for SnapshotDbRocksdb {
+    fn delete(&mut self, key: &[u8]) -> Result<Option<Option<Box<[u8]>>>> {
+        self.table(Self::SNAPSHOT_KV_COL).delete(key)?;
+        Ok(None)
+    }
+
+    fn put(
+        &mut self, key: &[u8], value: &[u8],
+    ) -> Result<Option<Option<Box<[u8]>>>> {
+        self.table(Self::SNAPSHOT_KV_COL).put(key, value)?;
+        Ok(None)
+    }
+}
+
+impl<'db> OpenSnapshotMptTrait<'db> for SnapshotDbRocksdb {
+    type SnapshotDbAsOwnedType =
+        SnapshotMpt<SnapshotDbRocksdbTable, SnapshotDbRocksdbTable>;
+    type SnapshotDbBorrowMutType =
+        SnapshotMpt<SnapshotDbRocksdbTable, SnapshotDbRocksdbTable>;
+    type SnapshotDbBorrowSharedType =
+        SnapshotMpt<SnapshotDbRocksdbTable, SnapshotDbRocksdbTable>;
+
+    fn open_snapshot_mpt_owned(
+        &'db mut self,
+    ) -> Result<Self::SnapshotDbBorrowMutType> {
+        SnapshotMpt::new(self.table(Self::SNAPSHOT_MPT_COL))
+    }
+
+    fn open_snapshot_mpt_as_owned(
+        &'db self,
+    ) -> Result<Self::SnapshotDbAsOwnedType> {
+        SnapshotMpt::new(self.table(Self::SNAPSHOT_MPT_COL))
+    }
+
+    fn open_snapshot_mpt_shared(
+        &'db self,
+    ) -> Result<Self::SnapshotDbBorrowSharedType> {
+        SnapshotMpt::new(self.table(Self::SNAPSHOT_MPT_COL))
+    }
+}
+
+impl SnapshotDbTrait for SnapshotDbRocksdb {
+    type SnapshotKvdbIterTraitTag = SnapshotDbRocksdbIteratorTag;
+    type SnapshotKvdbIterType = SnapshotDbRocksdbTable;
+
+    fn get_null_snapshot() -> Self {
+        Self {
+            maybe_db: None,
+            already_open_snapshots: Default::default(),
+            open_semaphore: Arc::new(Semaphore::new(0)),
+            path: Default::default(),
+            remove_on_close: Default::default(),
+        }
+    }
+
+    /// RocksDB doesn't allow a second instance to open the same db, so the
+    /// snapshot is always open for read and write.
+    fn open(
+        snapshot_path: &Path, _readonly: bool,
+        already_open_snapshots: &AlreadyOpenSnapshots<Self>,
+        open_semaphore: &Arc<Semaphore>,
+    ) -> Result<SnapshotDbRocksdb>
+    {
+        Ok(Self {
+            maybe_db: Some(Self::open_db(snapshot_path)?),
+            already_open_snapshots: already_open_snapshots.clone(),
+            open_semaphore: open_semaphore.clone(),
+            path: snapshot_path.to_path_buf(),
+            remove_on_close: Default::default(),
+        })
+    }
+
+    fn create(
+        snapshot_path: &Path,
+        already_open_snapshots: &AlreadyOpenSnapshots<Self>,
+        open_snapshots_semaphore: &Arc<Semaphore>,
+    ) -> Result<SnapshotDbRocksdb>
+    {
+        fs::create_dir_all(snapshot_path)?;
+        match Self::open_db(snapshot_path) {
+            Err(e) => {
+                fs::remove_dir_all(&snapshot_path)?;
+                bail!(e);
+            }
+            Ok(db) => Ok(SnapshotDbRocksdb {
+                maybe_db: Some(db),
+                already_open_snapshots: already_open_snapshots.clone(),
+                open_semaphore: open_snapshots_semaphore.clone(),
+                path: snapshot_path.to_path_buf(),
+                remove_on_close: Default::default(),
+            }),
+        }
+    }
+
+    fn direct_merge(&mut self) -> Result<MerkleHash> {
+        debug!("direct_merge begins.");
+        self.apply_update_to_kvdb()?;
+        let merkle_root = self.merge(None)?;
+        self.clear_delta_kvs()?;
+        Ok(merkle_root)
+    }
+
+    fn copy_and_merge(
+        &mut self, old_snapshot_db: &SnapshotDbRocksdb,
+    ) -> Result<MerkleHash> {
+        debug!("copy_and_merge begins.");
+        let mut batch = self.write_batch()?;
+        let mut iter = old_snapshot_db
+            .table(Self::SNAPSHOT_KV_COL)
+            .iter_range_impl(&[], None);
+        while let Some((k, v)) = iter.next()? {
+            batch.put(Self::SNAPSHOT_KV_COL, &k, &v)?;
+        }
+        batch.flush()?;
+        self.apply_update_to_kvdb()?;
+
+        let merkle_root = self.merge(Some(old_snapshot_db))?;
+        self.clear_delta_kvs()?;
+        Ok(merkle_root)
+    }
+
+    /// Writes go to the db immediately. A snapshot is written under a
+    /// temporary name and renamed when completed, so the transaction isn't
+    /// needed for atomicity.
+    fn start_transaction(&mut self) -> Result<()> { Ok(()) }
+
+    fn commit_transaction(&mut self) -> Result<()> { Ok(()) }
+
+    fn snapshot_kv_iterator(
+        &self,
+    ) -> Result<
+        Wrap<
+            Self::SnapshotKvdbIterType,
+            dyn KeyValueDbIterableTrait<
+                MptKeyValue,
+                [u8],
+                SnapshotDbRocksdbIteratorTag,
+            >,
+        >,
+    > {
+        Ok(Wrap(self.table(Self::SNAPSHOT_KV_COL)))
+    }
+}
+
+impl SnapshotDbRocksdbTable {
+    fn get(&self, key: &[u8]) -> Result<Option<Box<[u8]>>> {
+        match &self.maybe_db {
+            None => Ok(None),
+            Some(db) => Ok(db
+                .get(self.col, key)?
+                .map(|db_value| db_value.into_boxed_slice())),
+        }
+    }
+
+    fn put(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        let db = self.db()?;
+        let mut transaction = db.transaction();
+        transaction.put(self.col, key, value);
+        Ok(db.write(transaction)?)
+    }
+
+    fn delete(&self, key: &[u8]) -> Result<()> {
+        let db = self.db()?;
+        let mut transaction = db.transaction();
+        transaction.delete(self.col, key);
+        Ok(db.write(transaction)?)
+    }
+
+    fn db(&self) -> Result<&Database> {
+        match &self.maybe_db {
+            None => Err(Error::from(ErrorKind::DbNotExist)),
+            Some(db) => Ok(db),
+        }
+    }
+
+    fn iter_range_impl(
+        &self, lower_bound_incl: &[u8], upper_bound_excl: Option<&[u8]>,
+    ) -> SnapshotDbRocksdbTableIterator {
+        SnapshotDbRocksdbTableIterator {
+            table: self.clone(),
+            maybe_next_lower_bound_incl: self
+                .maybe_db
+                .as_ref()
+                .map(|_| lower_bound_incl.to_vec()),
+            upper_bound_excl: upper_bound_excl.map(|v| v.to_vec()),
+            loaded: Default::default(),
+        }
+    }
+}
+
+impl SnapshotDbRocksdbTableIterator {
+    const LOAD_BATCH_SIZE: usize = 1024;
+}
+
+/// Buffers the writes to the snapshot db, which go to the db in batches of
+/// LOAD_BATCH_SIZE instead of one write per key.
+struct SnapshotDbRocksdbWriteBatch {
+    db: Arc<Database>,
+    transaction: DBTransaction,
+    len: usize,
+}
+
+impl SnapshotDbRocksdbWriteBatch {
+    fn new(db: Arc<Database>) -> Self {
+        Self {
+            transaction: db.transaction(),
+            db,
+            len: 0,
+        }
+    }
+
+    fn put(&mut self, col: u32, key: &[u8], value: &[u8]) -> Result<()> {
+        self.transaction.put(col, key, value);
+        self.on_write()
+    }
+
+    fn delete(&mut self, col: u32, key: &[u8]) -> Result<()> {
+        self.transaction.delete(col, key);
+        self.on_write()
+    }
+
+    fn on_write(&mut self) -> Result<()> {
+        self.len += 1;
+        if self.len == SnapshotDbRocksdbTableIterator::LOAD_BATCH_SIZE {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if self.len > 0 {
+            let transaction =
+                mem::replace(&mut self.transaction, self.db.transaction());
+            self.db.write(transaction)?;
+            self.len = 0;
+        }
+        Ok(())
+    }
+}
+
+impl FallibleIterator for SnapshotDbRocksdbTableIterator {
+    type Error = Error;
+    type Item = MptKeyValue;
+
+    fn next(&mut self) -> Result<Option<MptKeyValue>> {
+        if self.loaded.is_empty() {
+            if let Some(lower_bound_incl) =
+                self.maybe_next_lower_bound_incl.take()
+            {
+                let kvs = self.table.db()?.get_range(
+                    self.table.col,
+                    &lower_bound_incl,
+                    self.upper_bound_excl.as_ref().map(|v| &**v),
+                    Self::LOAD_BATCH_SIZE,
+                )?;
+                if kvs.len() == Self::LOAD_BATCH_SIZE {
+                    let mut next_lower_bound_incl =
+                        kvs.last().unwrap().0.to_vec();
+                    next_lower_bound_incl.push(0);
+                    self.maybe_next_lower_bound_incl =
+                        Some(next_lower_bound_incl);
+                }
+                self.loaded.extend(kvs);
+            }
+        }
+        Ok(self.loaded.pop_front().map(|(k, v)| (k.into_vec(), v)))
+    }
+}
+
+impl SnapshotMptLoadNode for SnapshotDbRocksdbTable {
+    fn load_node_rlp(
+        &mut self, key: &[u8],
+    ) -> Result<Option<SnapshotMptDbValue>> {
+        self.get(key)
+    }
+}
+
+impl SnapshotMptTraitReadAndIterate
+    for SnapshotMpt<SnapshotDbRocksdbTable, SnapshotDbRocksdbTable>
+{
+    fn iterate_subtree_trie_nodes_without_root(
+        &mut self, path: &dyn CompressedPathTrait,
+    ) -> Result<Box<dyn SnapshotMptIteraterTrait + '_>> {
+        let begin_key_excl = mpt_node_path_to_db_key(path);
+
+        let mut end_key_excl = begin_key_excl.clone();
+        // The key is non empty. See also comment for compressed_path_to_db_key.
+        *end_key_excl.last_mut().unwrap() += 1;
+
+        Ok(Box::new(
+            self.db
+                .iter_range_excl(&begin_key_excl, &end_key_excl)?
+                .take()
+                .map(|(key, value)| {
+                    Ok((
+                        mpt_node_path_from_db_key(&key)?,
+                        SnapshotMptNode::decode(&Rlp::new(&value))?,
+                    ))
+                }),
+        ))
+    }
+}
+
+impl SnapshotMptTraitRw
+    for SnapshotMpt<SnapshotDbRocksdbTable, SnapshotDbRocksdbTable>
+{
+    fn delete_node(&mut self, path: &dyn CompressedPathTrait) -> Result<()> {
+        self.db.delete(&mpt_node_path_to_db_key(path))
+    }
+
+    fn write_node(
+        &mut self, path: &dyn CompressedPathTrait, trie_node: &SnapshotMptNode,
+    ) -> Result<()> {
+        self.db
+            .put(&mpt_node_path_to_db_key(path), &trie_node.rlp_bytes())
+    }
+}
+
+impl WrappedTrait<dyn FallibleIterator<Item = MptKeyValue, Error = Error>>
+    for KvdbIterIterator<MptKeyValue, [u8], SnapshotDbRocksdbIteratorTag>
+{
+}
+
+impl<'a>
+    WrappedLifetimeFamily<
+        'a,
+        dyn FallibleIterator<Item = MptKeyValue, Error = Error>,
+    > for KvdbIterIterator<MptKeyValue, [u8], SnapshotDbRocksdbIteratorTag>
+{
+    type Out = SnapshotDbRocksdbTableIterator;
+}
+
+impl KeyValueDbIterableTrait<MptKeyValue, [u8], SnapshotDbRocksdbIteratorTag>
+    for SnapshotDbRocksdbTable
+{
+    fn iter_range(
+        &mut self, lower_bound_incl: &[u8], upper_bound_excl: Option<&[u8]>,
+    ) -> Result<
+        Wrap<
+            KvdbIterIterator<MptKeyValue, [u8], SnapshotDbRocksdbIteratorTag>,
+            dyn FallibleIterator<Item = MptKeyValue, Error = Error>,
+        >,
+    > {
+        Ok(Wrap(
+            self.iter_range_impl(lower_bound_incl, upper_bound_excl),
+        ))
+    }
+
+    fn iter_range_excl(
+        &mut self, lower_bound_excl: &[u8], upper_bound_excl: &[u8],
+    ) -> Result<
+        Wrap<
+            KvdbIterIterator<MptKeyValue, [u8], SnapshotDbRocksdbIteratorTag>,
+            dyn FallibleIterator<Item = MptKeyValue, Error = Error>,
+        >,
+    > {
+        let mut lower_bound_incl = lower_bound_excl.to_vec();
+        lower_bound_incl.push(0);
+        Ok(Wrap(self.iter_range_impl(
+            &lower_bound_incl,
+            Some(upper_bound_excl),
+        )))
+    }
+}
+
+enable_impl_transmute_for_element_satisfy! {
+    generic ;
+    trait 'static + KeyValueDbIterableTrait<
+        MptKeyValue,
+        [u8],
+        SnapshotDbRocksdbIteratorTag,
+    >;
+    for SnapshotDbRocksdbTable;
+}
+
+impl
+    WrappedTrait<
+        dyn KeyValueDbIterableTrait<
+            MptKeyValue,
+            [u8],
+            SnapshotDbRocksdbIteratorTag,
+        >,
+    > for SnapshotDbRocksdbTable
+{
+}
+
+impl<'a>
+    WrappedLifetimeFamily<
+        'a,
+        dyn KeyValueDbIterableTrait<
+            MptKeyValue,
+            [u8],
+            SnapshotDbRocksdbIteratorTag,
+        >,
+    > for SnapshotDbRocksdbTable
+{
+    type Out = Self;
+}
+
+pub struct DeltaMptMergeDumperRocksdb {
+    batch: SnapshotDbRocksdbWriteBatch,
+}
+
+impl KVInserter<MptKeyValue> for DeltaMptMergeDumperRocksdb {
+    fn push(&mut self, x: MptKeyValue) -> Result<()> {
+        let (mpt_key, value) = x;
+        let snapshot_key =
+            StorageKey::from_delta_mpt_key(&mpt_key).to_key_bytes();
+        if value.len() > 0 {
+            self.batch.put(
+                SnapshotDbRocksdb::DELTA_KV_SET_COL,
+                &snapshot_key,
+                &value,
+            )
+        } else {
+            self.batch.put(
+                SnapshotDbRocksdb::DELTA_KV_DELETE_COL,
+                &snapshot_key,
+                &[],
+            )
+        }
+    }
+}
+
+use crate::{
+    impls::{
+        delta_mpt::DeltaMptIterator,
+        errors::*,
+        merkle_patricia_trie::{CompressedPathTrait, MptKeyValue, MptMerger},
+        storage_db::{
+            snapshot_db_manager_rocksdb::SnapshotDbManagerRocksdb,
+            snapshot_db_manager_sqlite::AlreadyOpenSnapshots,
+            snapshot_mpt::{
+                mpt_node_path_from_db_key, mpt_node_path_to_db_key,
+                SnapshotMpt, SnapshotMptLoadNode,
+            },
+        },
+    },
+    storage_db::{
+        KeyValueDbIterableTrait, KeyValueDbTraitOwnedRead, KeyValueDbTraitRead,
+        KeyValueDbTraitSingleWriter, KeyValueDbTypes, KvdbIterIterator,
+        OpenSnapshotMptTrait, SnapshotDbTrait, SnapshotMptDbValue,
+        SnapshotMptIteraterTrait, SnapshotMptNode,
+        SnapshotMptTraitReadAndIterate, SnapshotMptTraitRw,
+    },
+    utils::{
+        tuple::ElementSatisfy,
+        wrap::{Wrap, WrappedLifetimeFamily, WrappedTrait},
+    },
+    KVInserter,
+};
+use fallible_iterator::FallibleIterator;
+use kvdb::DBTransaction;
+use kvdb_rocksdb::{CompactionProfile, Database, DatabaseConfig};
+use primitives::{MerkleHash, StorageKey};
+use rlp::*;
+use std::{
+    collections::VecDeque,
+    fs, mem,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+use tokio::sync::Semaphore;
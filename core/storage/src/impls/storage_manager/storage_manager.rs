@@ -87,6 +87,9 @@ impl PersistedSnapshotInfoMap {
     }
 }
 
+/// The number of warm cache nodes preloaded with one open of the delta db.
+const DELTA_MPTS_PRELOAD_BATCH_SIZE: usize = 10_000;
+
 // FIXME: correctly order code blocks.
 pub struct StorageManager {
     delta_db_manager: Arc<DeltaDbManager>,
@@ -130,6 +133,9 @@ pub struct StorageManager {
 
     last_confirmed_snapshottable_epoch_id: Mutex<Option<EpochId>>,
 
+    /// Only opened in archive-state mode.
+    state_history: Option<Arc<StateHistory>>,
+
     storage_conf: StorageConfiguration,
 }
 
@@ -225,6 +231,13 @@ impl StorageManager {
         let delta_db_manager = Arc::new(DeltaDbManager::new(
             storage_conf.path_delta_mpts_dir.clone(),
         )?);
+        let state_history = if storage_conf.archive_state {
+            Some(Arc::new(StateHistory::open_or_create(
+                &storage_conf.path_state_history_db,
+            )?))
+        } else {
+            None
+        };
         let new_storage_manager_result = Ok(Arc::new(Self {
             delta_db_manager: delta_db_manager.clone(),
             delta_mpt_open_db_lru: Arc::new(OpenDeltaDbLru::new(
@@ -234,6 +247,7 @@ impl StorageManager {
                 snapshot_db_manager: SnapshotDbManager::new(
                     storage_conf.path_snapshot_dir.clone(),
                     storage_conf.max_open_snapshots,
+                    storage_conf.snapshot_db_backend,
                 )?,
             }),
             delta_mpts_id_gen: Default::default(),
@@ -258,6 +272,7 @@ impl StorageManager {
             current_snapshots: Default::default(),
             snapshot_info_map_by_epoch: RwLock::new(snapshot_info_map),
             last_confirmed_snapshottable_epoch_id: Default::default(),
+            state_history,
             storage_conf,
         }));
 
@@ -293,10 +308,122 @@ impl StorageManager {
             .as_ref()
             .unwrap()
             .load_persist_state()?;
+        Self::preload_delta_mpts_warm_cache_background(
+            new_storage_manager_result.as_ref().unwrap(),
+        )?;
 
         new_storage_manager_result
     }
 
+    /// Loads the delta mpt nodes saved at the last graceful shutdown into the
+    /// cache in a background thread, stopping early if the storage manager is
+    /// dropped.
+    fn preload_delta_mpts_warm_cache_background(
+        storage_manager: &Arc<StorageManager>,
+    ) -> Result<()> {
+        let mut warm_cache = match DeltaMptsWarmCache::take(
+            &storage_manager.storage_conf.path_delta_mpts_warm_cache,
+        ) {
+            Ok(Some(warm_cache)) if !warm_cache.is_empty() => warm_cache,
+            Ok(_) => return Ok(()),
+            // The warm cache is only an optimization, so a broken one falls
+            // back to a cold start.
+            Err(e) => {
+                warn!("Failed to load the delta mpts warm cache: {}", e);
+                return Ok(());
+            }
+        };
+        warm_cache.truncate(
+            storage_manager.storage_conf.delta_mpts_cache_start_size as usize,
+        );
+        storage_manager
+            .delta_mpts_node_memory_manager
+            .set_warm_start();
+
+        let storage_manager = Arc::downgrade(storage_manager);
+        thread::Builder::new()
+            .name("Delta MPTs Cache Warmer".into())
+            .spawn(move || {
+                let start = Instant::now();
+                let mut loaded = 0;
+                for (snapshot_epoch_id, db_keys) in
+                    warm_cache.iter_from_least_recent()
+                {
+                    for batch in db_keys.rchunks(DELTA_MPTS_PRELOAD_BATCH_SIZE)
+                    {
+                        let delta_mpt = match storage_manager.upgrade() {
+                            None => return,
+                            Some(storage_manager) => storage_manager
+                                .delta_mpts_by_snapshot_epoch()
+                                .remove(snapshot_epoch_id),
+                        };
+                        // The delta mpt may have been removed along with its
+                        // snapshot.
+                        let delta_mpt = match delta_mpt {
+                            None => break,
+                            Some(delta_mpt) => delta_mpt,
+                        };
+                        match delta_mpt.preload_nodes(batch.iter().rev()) {
+                            Ok(batch_loaded) => loaded += batch_loaded,
+                            Err(e) => {
+                                warn!(
+                                    "Failed to preload the delta mpt nodes \
+                                     of snapshot {:?}: {}",
+                                    snapshot_epoch_id, e
+                                );
+                                break;
+                            }
+                        }
+                    }
+                }
+                info!(
+                    "Preloaded {} of {} delta mpt nodes in {:?}",
+                    loaded,
+                    warm_cache.len(),
+                    start.elapsed()
+                );
+            })?;
+        Ok(())
+    }
+
+    /// Saves the most recently accessed delta mpt nodes, at most
+    /// delta_mpts_cache_start_size of them, to be preloaded at the next start.
+    fn save_delta_mpts_warm_cache(&self) -> Result<()> {
+        let snapshot_epoch_by_mpt_id = self
+            .delta_mpts_by_snapshot_epoch()
+            .into_iter()
+            .map(|(snapshot_epoch_id, delta_mpt)| {
+                (delta_mpt.get_mpt_id(), snapshot_epoch_id)
+            })
+            .collect::<HashMap<_, _>>();
+        let nodes = self
+            .delta_mpts_node_memory_manager
+            .most_recently_accessed_nodes(
+                self.storage_conf.delta_mpts_cache_start_size as usize,
+            );
+        let warm_cache = DeltaMptsWarmCache::from_nodes(
+            nodes.into_iter().filter_map(|(mpt_id, db_key)| {
+                Some((*snapshot_epoch_by_mpt_id.get(&mpt_id)?, db_key))
+            }),
+        );
+        warm_cache.save(&self.storage_conf.path_delta_mpts_warm_cache)?;
+        info!("Saved {} delta mpt nodes for warm start", warm_cache.len());
+        Ok(())
+    }
+
+    /// The delta mpts which are in use, by the snapshot epoch id of their db.
+    fn delta_mpts_by_snapshot_epoch(&self) -> HashMap<EpochId, Arc<DeltaMpt>> {
+        let mut delta_mpts = HashMap::new();
+        for (intermediate_mpt, delta_mpt) in
+            self.snapshot_associated_mpts_by_epoch.read().values()
+        {
+            for mpt in intermediate_mpt.iter().chain(delta_mpt.iter()) {
+                delta_mpts.insert(*mpt.get_snapshot_epoch_id(), mpt.clone());
+            }
+        }
+        delta_mpts
+    }
+
     pub fn find_merkle_root(
         current_snapshots: &Vec<SnapshotInfo>, epoch_id: &EpochId,
     ) -> Option<MerkleHash> {
@@ -373,6 +500,9 @@ impl StorageManager {
         {
             joiner.join().ok();
         }
+        if let Err(e) = self.save_delta_mpts_warm_cache() {
+            warn!("Failed to save the delta mpts warm cache: {}", e);
+        }
     }
 
     pub fn get_snapshot_manager(
@@ -389,6 +519,11 @@ impl StorageManager {
         self.storage_conf.consensus_param.snapshot_epoch_count
     }
 
+    /// Returns None if the archive-state mode is off.
+    pub fn get_state_history(&self) -> Option<&Arc<StateHistory>> {
+        self.state_history.as_ref()
+    }
+
     pub fn get_snapshot_info_at_epoch(
         &self, snapshot_epoch_id: &EpochId,
     ) -> Option<SnapshotInfo> {
@@ -398,6 +533,37 @@ impl StorageManager {
             .map(Clone::clone)
     }
 
+    /// Returns the lowest and highest heights of the snapshots which are kept
+    /// and can be served for sync.
+    pub fn get_snapshot_height_range(&self) -> Option<(u64, u64)> {
+        let snapshot_info_map = self.snapshot_info_map_by_epoch.read();
+        let heights = snapshot_info_map
+            .get_map()
+            .values()
+            .filter(|info| {
+                info.snapshot_info_kept_to_provide_sync
+                    != SnapshotKeptToProvideSyncStatus::InfoOnly
+            })
+            .map(|info| info.height);
+        Some((heights.clone().min()?, heights.max()?))
+    }
+
+    /// Returns the infos of the snapshots whose db is kept, except for the
+    /// empty snapshot at genesis.
+    pub fn get_persisted_snapshot_infos(&self) -> Vec<SnapshotInfo> {
+        self.snapshot_info_map_by_epoch
+            .read()
+            .get_map()
+            .values()
+            .filter(|info| {
+                info.snapshot_info_kept_to_provide_sync
+                    != SnapshotKeptToProvideSyncStatus::InfoOnly
+                    && *info.get_snapshot_epoch_id() != NULL_EPOCH
+            })
+            .cloned()
+            .collect()
+    }
+
     pub fn get_delta_mpt(
         &self, snapshot_epoch_id: &EpochId,
     ) -> Result<Arc<DeltaMpt>> {
@@ -649,8 +815,14 @@ impl StorageManager {
                         .or(Err(Error::from(ErrorKind::MpscError)))?;
                     drop(snapshot_info_map_locked);
 
+                    // The checker reads the dumped delta mpt of SnapshotDbSqlite.
                     let debug_snapshot_checkers =
-                        this.storage_conf.debug_snapshot_checker_threads;
+                        match this.storage_conf.snapshot_db_backend {
+                            SnapshotDbBackend::Sqlite => {
+                                this.storage_conf.debug_snapshot_checker_threads
+                            }
+                            SnapshotDbBackend::Rocksdb => 0,
+                        };
                     for snapshot_checker in 0..debug_snapshot_checkers {
                         let begin_range =
                             (256 / debug_snapshot_checkers * snapshot_checker) as u8;
@@ -672,7 +844,7 @@ impl StorageManager {
                                     .get_snapshot_by_epoch_id(
                                         &snapshot_epoch_id,
                                         /* try_open = */ false,
-                                    )?.unwrap();
+                                    )?.unwrap().to_sqlite().unwrap();
                                 let mut set_keys_iter =
                                     snapshot_db.dumped_delta_kv_set_keys_iterator()?;
                                 let mut delete_keys_iter =
@@ -681,7 +853,7 @@ impl StorageManager {
                                     .get_snapshot_by_epoch_id(
                                         &parent_snapshot_epoch_id_cloned,
                                         /* try_open = */ false,
-                                    )?.unwrap();
+                                    )?.unwrap().to_sqlite().unwrap();
                                 let mut previous_set_keys_iter = previous_snapshot_db
                                     .dumped_delta_kv_set_keys_iterator()?;
                                 let mut previous_delete_keys_iter =
@@ -1519,8 +1691,10 @@ use crate::{
                 DeltaMptsCacheAlgorithm, DeltaMptsNodeMemoryManager,
             },
             node_ref_map::DeltaMptId,
+            warm_cache::DeltaMptsWarmCache,
         },
         errors::*,
+        state_history::StateHistory,
         state_manager::{DeltaDbManager, SnapshotDb, SnapshotDbManager},
         storage_db::{
             kvdb_sqlite::{
@@ -1539,8 +1713,8 @@ use crate::{
     storage_dir,
     utils::{arc_ext::*, guarded_value::GuardedValue},
     DeltaMpt, DeltaMptIdGen, DeltaMptIterator, KeyValueDbTrait, KvdbSqlite,
-    OpenDeltaDbLru, ProvideExtraSnapshotSyncConfig, StateIndex,
-    StateRootWithAuxInfo, StorageConfiguration,
+    OpenDeltaDbLru, ProvideExtraSnapshotSyncConfig, SnapshotDbBackend,
+    StateIndex, StateRootWithAuxInfo, StorageConfiguration,
 };
 use cfx_internal_common::{
     consensus_api::StateMaintenanceTrait, StateAvailabilityBoundary,
@@ -1560,4 +1734,5 @@ use std::{
         Arc, Weak,
     },
     thread::{self, JoinHandle},
+    time::Instant,
 };
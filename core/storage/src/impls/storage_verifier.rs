@@ -0,0 +1,393 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StateTree {
+    Snapshot,
+    IntermediateDeltaMpt,
+    DeltaMpt,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum StorageCorruption {
+    /// The state trees of the epoch aren't found.
+    MissingState,
+    /// The snapshot db isn't found while its snapshot info is kept.
+    MissingSnapshot,
+    /// The merkle root of the tree doesn't match the committed state root.
+    /// For a delta mpt, the found merkle root is recomputed from the trie
+    /// nodes.
+    RootMismatch {
+        tree: StateTree,
+        expected: MerkleHash,
+        found: MerkleHash,
+    },
+    /// The key-values in the range don't match the merkle root of the tree.
+    /// For a delta mpt, the range is the subtree of a key prefix, and the keys
+    /// are padded with the delta mpt key padding.
+    CorruptedKeyRange {
+        tree: StateTree,
+        lower_bound_incl: Vec<u8>,
+        upper_bound_excl: Option<Vec<u8>>,
+    },
+}
+
+/// StorageVerifier checks the persisted state without trusting the merkles
+/// stored with the trie nodes.
+///
+/// A snapshot is sliced into key ranges as in the snapshot sync, and the
+/// key-values in each range are verified against the merkle root with the
+/// proofs of the range boundaries, so that the corrupted key ranges are
+/// found. For a delta mpt, the merkle of every trie node is recomputed from
+/// its children. The snapshots and the committed trie nodes are verified only
+/// once, because they are shared by the states of many epochs.
+pub struct StorageVerifier<'a> {
+    state_manager: &'a StateManager,
+    range_rlp_size: u64,
+    verified_snapshots: HashMap<(EpochId, MerkleHash), Vec<StorageCorruption>>,
+    verified_delta_mpt_nodes:
+        HashMap<DeltaMptId, HashMap<DeltaMptDbKey, (MerkleHash, bool)>>,
+}
+
+impl<'a> StorageVerifier<'a> {
+    pub fn new(state_manager: &'a StateManager, range_rlp_size: u64) -> Self {
+        Self {
+            state_manager,
+            range_rlp_size,
+            verified_snapshots: Default::default(),
+            verified_delta_mpt_nodes: Default::default(),
+        }
+    }
+
+    /// Verify the snapshot at snapshot_epoch_id against merkle_root.
+    pub fn verify_snapshot(
+        &mut self, snapshot_epoch_id: &EpochId, merkle_root: &MerkleHash,
+    ) -> Result<Vec<StorageCorruption>> {
+        if let Some(corruptions) = self
+            .verified_snapshots
+            .get(&(*snapshot_epoch_id, *merkle_root))
+        {
+            return Ok(corruptions.clone());
+        }
+
+        let corruptions = if *snapshot_epoch_id == NULL_EPOCH {
+            // The empty snapshot has no db.
+            if *merkle_root == MERKLE_NULL_NODE {
+                vec![]
+            } else {
+                vec![StorageCorruption::RootMismatch {
+                    tree: StateTree::Snapshot,
+                    expected: *merkle_root,
+                    found: MERKLE_NULL_NODE,
+                }]
+            }
+        } else {
+            match self.state_manager.get_storage_manager().wait_for_snapshot(
+                snapshot_epoch_id,
+                /* try_open = */ false,
+            )? {
+                None => vec![StorageCorruption::MissingSnapshot],
+                Some(snapshot_db) => Self::verify_snapshot_db(
+                    &*snapshot_db,
+                    merkle_root,
+                    self.range_rlp_size,
+                )?,
+            }
+        };
+        self.verified_snapshots
+            .insert((*snapshot_epoch_id, *merkle_root), corruptions.clone());
+        Ok(corruptions)
+    }
+
+    /// Verify the state trees of epoch_id against the state root committed by
+    /// the epoch.
+    ///
+    /// It fails with DeltaMPTEntryNotFound when the delta mpts of the state
+    /// are already removed.
+    pub fn verify_state(
+        &mut self, epoch_id: &EpochId, state_root: &StateRootWithAuxInfo,
+    ) -> Result<Vec<StorageCorruption>> {
+        let state_index = StateIndex::new_for_readonly(epoch_id, state_root);
+        let state_trees = match self
+            .state_manager
+            .get_state_trees(&state_index, /* try_open = */ false)?
+        {
+            None => return Ok(vec![StorageCorruption::MissingState]),
+            Some(state_trees) => state_trees,
+        };
+
+        let mut corruptions = vec![];
+        // Without the snapshot info, the state is read from the snapshot at
+        // the intermediate epoch, which isn't committed by this epoch.
+        if self
+            .state_manager
+            .get_storage_manager()
+            .get_snapshot_info_at_epoch(&state_index.snapshot_epoch_id)
+            .is_some()
+        {
+            corruptions.extend(self.verify_snapshot(
+                &state_index.snapshot_epoch_id,
+                &state_index.snapshot_merkle_root,
+            )?);
+        }
+        if let Some(intermediate_trie) = &state_trees.maybe_intermediate_trie {
+            self.verify_delta_mpt(
+                StateTree::IntermediateDeltaMpt,
+                intermediate_trie,
+                &state_trees.intermediate_trie_root,
+                &state_root.state_root.intermediate_delta_root,
+                &mut corruptions,
+            )?;
+        }
+        self.verify_delta_mpt(
+            StateTree::DeltaMpt,
+            &state_trees.delta_trie,
+            &state_trees.delta_trie_root,
+            &state_root.state_root.delta_root,
+            &mut corruptions,
+        )?;
+
+        Ok(corruptions)
+    }
+
+    fn verify_snapshot_db(
+        snapshot_db: &SnapshotDb, merkle_root: &MerkleHash, range_rlp_size: u64,
+    ) -> Result<Vec<StorageCorruption>> {
+        let mut corruptions = vec![];
+        let mut boundaries = vec![];
+        let mut boundary_proofs = vec![];
+        let sliced = {
+            let mut mpt = snapshot_db.open_snapshot_mpt_shared()?;
+            let stored_merkle_root = mpt.get_merkle_root();
+            if stored_merkle_root != *merkle_root {
+                corruptions.push(StorageCorruption::RootMismatch {
+                    tree: StateTree::Snapshot,
+                    expected: *merkle_root,
+                    found: stored_merkle_root,
+                });
+            }
+            // When the mpt fails to slice, the key-values after the last
+            // boundary can't be verified.
+            let mut slice = || -> Result<()> {
+                let mut slicer = MptSlicer::new(&mut mpt)?;
+                loop {
+                    slicer.advance(range_rlp_size)?;
+                    match slicer.get_range_end_key() {
+                        None => return Ok(()),
+                        Some(key) => {
+                            boundaries.push(key.to_vec());
+                            boundary_proofs.push(slicer.to_proof());
+                        }
+                    }
+                }
+            };
+            slice().is_ok()
+        };
+        let valid_boundaries: Vec<bool> = boundaries
+            .iter()
+            .zip(boundary_proofs.iter())
+            .map(|(boundary, proof)| {
+                proof.get_merkle_root() == merkle_root
+                    && proof.number_leaf_nodes() == 1
+                    && proof.if_proves_key(&*boundary)
+                        == (true, proof.get_proof_nodes().last())
+            })
+            .collect();
+
+        let mut kv_iterator = snapshot_db.snapshot_kv_iterator()?.take();
+        let number_ranges = boundaries.len() + 1;
+        for range_index in 0..number_ranges {
+            let (lower_bound_incl, maybe_left_proof) = if range_index == 0 {
+                (&[][..], None)
+            } else {
+                (
+                    &*boundaries[range_index - 1],
+                    Some(&boundary_proofs[range_index - 1]),
+                )
+            };
+            let maybe_upper_bound_excl =
+                boundaries.get(range_index).map(|key| &**key);
+            let is_valid = (range_index == 0
+                || valid_boundaries[range_index - 1])
+                && valid_boundaries.get(range_index).cloned().unwrap_or(true)
+                && (sliced || range_index + 1 < number_ranges)
+                && Self::verify_snapshot_range(
+                    &mut kv_iterator,
+                    lower_bound_incl,
+                    maybe_upper_bound_excl,
+                    maybe_left_proof,
+                    boundary_proofs.get(range_index),
+                    merkle_root,
+                )
+                .unwrap_or(false);
+            if !is_valid {
+                corruptions.push(StorageCorruption::CorruptedKeyRange {
+                    tree: StateTree::Snapshot,
+                    lower_bound_incl: lower_bound_incl.into(),
+                    upper_bound_excl: maybe_upper_bound_excl.map(Into::into),
+                });
+            }
+        }
+
+        Ok(corruptions)
+    }
+
+    fn verify_snapshot_range(
+        kv_iterator: &mut AnySnapshotKvIter, lower_bound_incl: &[u8],
+        maybe_upper_bound_excl: Option<&[u8]>,
+        maybe_left_proof: Option<&TrieProof>,
+        maybe_right_proof: Option<&TrieProof>, merkle_root: &MerkleHash,
+    ) -> Result<bool>
+    {
+        let mut keys = vec![];
+        let mut values = vec![];
+        let mut iter = kv_iterator
+            .iter_range(lower_bound_incl, maybe_upper_bound_excl)?
+            .take();
+        while let Some((key, value)) = iter.next()? {
+            keys.push(key);
+            values.push(value.into_vec());
+        }
+
+        Ok(MptSliceVerifier::new(
+            maybe_left_proof,
+            lower_bound_incl,
+            maybe_right_proof,
+            maybe_upper_bound_excl,
+            *merkle_root,
+        )
+        .restore(&keys, &values)?
+        .is_valid)
+    }
+
+    fn verify_delta_mpt(
+        &mut self, tree: StateTree, mpt: &DeltaMpt,
+        maybe_root: &Option<NodeRefDeltaMpt>, expected_merkle_root: &MerkleHash,
+        corruptions: &mut Vec<StorageCorruption>,
+    ) -> Result<()>
+    {
+        let verified_nodes = self
+            .verified_delta_mpt_nodes
+            .entry(mpt.get_mpt_id())
+            .or_default();
+        let mut corrupted_key_prefixes = vec![];
+        let merkle_root = match maybe_root {
+            None => MERKLE_NULL_NODE,
+            Some(NodeRefDeltaMpt::Committed { db_key })
+                if verified_nodes.contains_key(db_key) =>
+            {
+                verified_nodes[db_key].0
+            }
+            Some(root_node) => {
+                let arc_db = mpt.get_arc_db()?;
+                let db = &mut *arc_db.to_owned_read()?;
+                let owned_node_set = Default::default();
+                let node_memory_manager = mpt.get_node_memory_manager();
+                let allocator = node_memory_manager.get_allocator();
+                let mut cow_root_node = CowNodeRef::new(
+                    root_node.clone(),
+                    &owned_node_set,
+                    mpt.get_mpt_id(),
+                );
+                match cow_root_node
+                    .get_trie_node(node_memory_manager, &allocator, db)
+                    .map(GuardedValue::take)
+                {
+                    Err(_) => {
+                        corrupted_key_prefixes
+                            .push(CompressedPathRaw::new_zeroed(0, 0));
+                        MERKLE_NULL_NODE
+                    }
+                    Ok(guarded_trie_node) => {
+                        let (merkle, corrupted) = cow_root_node
+                            .verify_merkle_internal(
+                                &owned_node_set,
+                                mpt,
+                                guarded_trie_node,
+                                CompressedPathRaw::new_zeroed(0, 0),
+                                0,
+                                verified_nodes,
+                                &mut corrupted_key_prefixes,
+                                db,
+                            )?;
+                        if let NodeRefDeltaMpt::Committed { db_key } = root_node
+                        {
+                            verified_nodes.insert(*db_key, (merkle, corrupted));
+                        }
+                        merkle
+                    }
+                }
+            }
+        };
+
+        for key_prefix in corrupted_key_prefixes {
+            let (lower_bound_incl, upper_bound_excl) =
+                key_prefix_range(&key_prefix);
+            corruptions.push(StorageCorruption::CorruptedKeyRange {
+                tree,
+                lower_bound_incl,
+                upper_bound_excl,
+            });
+        }
+        if merkle_root != *expected_merkle_root {
+            corruptions.push(StorageCorruption::RootMismatch {
+                tree,
+                expected: *expected_merkle_root,
+                found: merkle_root,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// The range of the keys starting with the key prefix.
+fn key_prefix_range(
+    key_prefix: &CompressedPathRaw,
+) -> (Vec<u8>, Option<Vec<u8>>) {
+    let mut lower_bound_incl = key_prefix.path_slice().to_vec();
+    let mut upper_bound = lower_bound_incl.clone();
+    if !CompressedPathRaw::has_second_nibble(key_prefix.path_mask()) {
+        // Only the first nibble of the last byte is in the prefix.
+        if let Some(last_byte) = lower_bound_incl.last_mut() {
+            *last_byte &= 0xf0;
+        }
+        if let Some(last_byte) = upper_bound.last_mut() {
+            *last_byte |= 0x0f;
+        }
+    }
+    while let Some(last_byte) = upper_bound.pop() {
+        if last_byte != 0xff {
+            upper_bound.push(last_byte + 1);
+            return (lower_bound_incl, Some(upper_bound));
+        }
+    }
+    (lower_bound_incl, None)
+}
+
+use crate::{
+    impls::{
+        delta_mpt::{
+            node_ref_map::{DeltaMptDbKey, DeltaMptId},
+            CowNodeRef, DeltaMpt, NodeRefDeltaMpt,
+        },
+        errors::*,
+        merkle_patricia_trie::{
+            CompressedPathRaw, CompressedPathTrait, TrieProof,
+        },
+        snapshot_sync::{
+            restoration::mpt_slice_verifier::MptSliceVerifier, MptSlicer,
+        },
+        state_manager::{SnapshotDb, StateManager},
+        storage_db::snapshot_db_any::AnySnapshotKvIter,
+    },
+    storage_db::{
+        KeyValueDbIterableTrait, OpenSnapshotMptTrait, SnapshotDbTrait,
+        SnapshotMptTraitRead,
+    },
+    utils::guarded_value::GuardedValue,
+    StateIndex, StateRootWithAuxInfo,
+};
+use fallible_iterator::FallibleIterator;
+use primitives::{EpochId, MerkleHash, MERKLE_NULL_NODE, NULL_EPOCH};
+use std::collections::HashMap;
@@ -34,6 +34,10 @@ pub mod storage_dir {
     lazy_static! {
         pub static ref DELTA_MPTS_DIR: PathBuf =
             ["storage_db", "delta_mpts"].iter().collect::<PathBuf>();
+        pub static ref DELTA_MPTS_WARM_CACHE_PATH: PathBuf =
+            ["storage_db", "delta_mpts_warm_cache"]
+                .iter()
+                .collect::<PathBuf>();
         pub static ref SNAPSHOT_DIR: PathBuf =
             ["storage_db", "snapshot"].iter().collect::<PathBuf>();
         pub static ref SNAPSHOT_INFO_DB_NAME: &'static str = "snapshot_info";
@@ -41,6 +45,10 @@ pub mod storage_dir {
             ["storage_db", "snapshot_info_db"]
                 .iter()
                 .collect::<PathBuf>();
+        pub static ref STATE_HISTORY_DB_PATH: PathBuf =
+            ["storage_db", "state_history_db"]
+                .iter()
+                .collect::<PathBuf>();
         pub static ref STORAGE_DIR: PathBuf = "storage_db".into();
     }
 }
@@ -100,9 +108,36 @@ impl ProvideExtraSnapshotSyncConfig {
     }
 }
 
+/// The database engine of the snapshots.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SnapshotDbBackend {
+    Sqlite,
+    Rocksdb,
+}
+
+impl SnapshotDbBackend {
+    pub fn from_str(config: &str) -> std::result::Result<Self, String> {
+        match config {
+            "sqlite" => Ok(Self::Sqlite),
+            "rocksdb" => Ok(Self::Rocksdb),
+            _ => Err(format!("{} is not a valid SnapshotDbBackend", config)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Sqlite => "sqlite",
+            Self::Rocksdb => "rocksdb",
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct StorageConfiguration {
     pub additional_maintained_snapshot_count: u32,
+    /// Keep the values written by every epoch in the state history db, so
+    /// that the state of any executed epoch can be read.
+    pub archive_state: bool,
     pub consensus_param: ConsensusParam,
     pub debug_snapshot_checker_threads: u16,
     pub delta_mpts_cache_recent_lfu_factor: f64,
@@ -112,10 +147,15 @@ pub struct StorageConfiguration {
     pub delta_mpts_slab_idle_size: u32,
     pub max_open_snapshots: u16,
     pub path_delta_mpts_dir: PathBuf,
+    /// The delta mpt nodes cached at graceful shutdown, which are preloaded
+    /// at the next start.
+    pub path_delta_mpts_warm_cache: PathBuf,
     pub path_storage_dir: PathBuf,
     pub path_snapshot_dir: PathBuf,
     pub path_snapshot_info_db: PathBuf,
+    pub path_state_history_db: PathBuf,
     pub provide_more_snapshot_for_sync: Vec<ProvideExtraSnapshotSyncConfig>,
+    pub snapshot_db_backend: SnapshotDbBackend,
 }
 
 impl StorageConfiguration {
@@ -125,6 +165,7 @@ impl StorageConfiguration {
         let conflux_data_path = Path::new(conflux_data_dir);
         StorageConfiguration {
             additional_maintained_snapshot_count: 0,
+            archive_state: false,
             consensus_param: ConsensusParam {
                 snapshot_epoch_count,
             },
@@ -141,15 +182,20 @@ impl StorageConfiguration {
             max_open_snapshots: defaults::DEFAULT_MAX_OPEN_SNAPSHOTS,
             path_delta_mpts_dir: conflux_data_path
                 .join(&*storage_dir::DELTA_MPTS_DIR),
+            path_delta_mpts_warm_cache: conflux_data_path
+                .join(&*storage_dir::DELTA_MPTS_WARM_CACHE_PATH),
             path_snapshot_dir: conflux_data_path
                 .join(&*storage_dir::SNAPSHOT_DIR),
             path_snapshot_info_db: conflux_data_path
                 .join(&*storage_dir::SNAPSHOT_INFO_DB_PATH),
+            path_state_history_db: conflux_data_path
+                .join(&*storage_dir::STATE_HISTORY_DB_PATH),
             path_storage_dir: conflux_data_path
                 .join(&*storage_dir::STORAGE_DIR),
             provide_more_snapshot_for_sync: vec![
                 ProvideExtraSnapshotSyncConfig::StableCheckpoint,
             ],
+            snapshot_db_backend: SnapshotDbBackend::Sqlite,
         }
     }
 }
@@ -166,13 +212,17 @@ pub use self::{
         proof_merger::StateProofMerger,
         recording_storage::RecordingStorage,
         snapshot_sync::{FullSyncVerifier, MptSlicer},
+        state_history::{EpochByHeight, StateHistory},
         state_proof::StateProof,
         storage_db::{
             kvdb_rocksdb::KvdbRocksdb,
             kvdb_sqlite::{KvdbSqlite, KvdbSqliteStatements},
+            snapshot_db_any::AnySnapshotDb,
+            snapshot_db_manager_any::AnySnapshotDbManager,
             snapshot_db_manager_sqlite::SnapshotDbManagerSqlite,
             sqlite::SqliteConnection,
         },
+        storage_verifier::{StateTree, StorageCorruption, StorageVerifier},
     },
     state::{
         State as StorageState, StateTrait as StorageStateTrait,
@@ -50,6 +50,16 @@ pub trait StateTraitExt {
     fn get_node_merkle_all_versions<WithProof: StaticBool>(
         &self, access_key: StorageKey,
     ) -> Result<(NodeMerkleTriplet, NodeMerkleProof)>;
+
+    /// Read at most `limit` key-values with `key_prefix` from `start_key` in
+    /// key order, and the key to continue with if there are more.
+    ///
+    /// With `distinct_prefix_len`, only the first key-value of each distinct
+    /// key prefix of the length is returned, e.g. 20 to list the accounts.
+    fn read_range(
+        &self, key_prefix: Option<StorageKey>, start_key: &[u8], limit: usize,
+        distinct_prefix_len: Option<usize>,
+    ) -> Result<(Vec<MptKeyValue>, Option<Vec<u8>>)>;
 }
 
 use super::{
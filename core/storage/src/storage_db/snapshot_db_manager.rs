@@ -51,6 +51,10 @@ pub trait SnapshotDbManagerTrait {
                 continue;
             }
             let dir_name = dir_name.unwrap();
+            if self.is_temp_snapshot_for_full_sync(dir_name) {
+                // Kept to resume the interrupted full sync restoration.
+                continue;
+            }
             if !all_snapshots.contains_key(dir_name.as_bytes()) {
                 error!(
                     "Unexpected snapshot path {}, deleted.",
@@ -81,6 +85,21 @@ pub trait SnapshotDbManagerTrait {
     fn new_temp_snapshot_for_full_sync(
         &self, snapshot_epoch_id: &EpochId, merkle_root: &MerkleHash,
     ) -> Result<Self::SnapshotDb>;
+    /// Opens the temp snapshot of an interrupted full sync restoration, or
+    /// returns None if there is no such snapshot or it can't be resumed.
+    fn open_temp_snapshot_for_full_sync(
+        &self, _snapshot_epoch_id: &EpochId, _merkle_root: &MerkleHash,
+    ) -> Result<Option<Self::SnapshotDb>> {
+        Ok(None)
+    }
+    /// The path of the progress log of the full sync restoration, or None if
+    /// the restoration isn't resumable.
+    fn get_full_sync_progress_path(
+        &self, _snapshot_epoch_id: &EpochId, _merkle_root: &MerkleHash,
+    ) -> Option<PathBuf> {
+        None
+    }
+    fn is_temp_snapshot_for_full_sync(&self, _dir_name: &str) -> bool { false }
     fn finalize_full_sync_snapshot<'m>(
         &self, snapshot_epoch_id: &EpochId, merkle_root: &MerkleHash,
         snapshot_info_map_rwlock: &'m RwLock<PersistedSnapshotInfoMap>,
@@ -10,7 +10,13 @@ mod proofs;
 #[cfg(test)]
 mod sharded_iter_merger;
 #[cfg(test)]
+mod snapshot_db_backend;
+#[cfg(test)]
 mod state;
+#[cfg(test)]
+mod state_history;
+#[cfg(test)]
+mod storage_verifier;
 
 #[cfg(test)]
 const TEST_NUMBER_OF_KEYS: usize = 100000;
@@ -63,7 +69,9 @@ pub struct FakeStateManager {
 impl FakeStateManager {
     fn new(
         conflux_data_dir: String, snapshot_epoch_count: u32,
-    ) -> Result<Self> {
+        snapshot_db_backend: SnapshotDbBackend,
+    ) -> Result<Self>
+    {
         // Use a random directory to prevent conflicts in concurrently running
         // tests.
         let unit_test_data_dir =
@@ -77,6 +85,7 @@ impl FakeStateManager {
         storage_conf.delta_mpts_cache_start_size = 1_000_000;
         storage_conf.delta_mpts_node_map_vec_size = 20_000_000;
         storage_conf.delta_mpts_slab_idle_size = 200_000;
+        storage_conf.snapshot_db_backend = snapshot_db_backend;
 
         Ok(FakeStateManager {
             data_dir: unit_test_data_dir,
@@ -114,6 +123,16 @@ impl DerefMut for FakeStateManager {
 #[cfg(any(test, feature = "testonly_code"))]
 pub fn new_state_manager_for_unit_test_with_snapshot_epoch_count(
     snapshot_epoch_count: u32,
+) -> FakeStateManager {
+    new_state_manager_for_unit_test_with_snapshot_db_backend(
+        snapshot_epoch_count,
+        SnapshotDbBackend::Sqlite,
+    )
+}
+
+#[cfg(any(test, feature = "testonly_code"))]
+pub fn new_state_manager_for_unit_test_with_snapshot_db_backend(
+    snapshot_epoch_count: u32, snapshot_db_backend: SnapshotDbBackend,
 ) -> FakeStateManager {
     const WITH_LOGGER: bool = false;
     if WITH_LOGGER {
@@ -141,6 +160,7 @@ pub fn new_state_manager_for_unit_test_with_snapshot_epoch_count(
     FakeStateManager::new(
         "./conflux_unit_test_data_dir".to_string(),
         snapshot_epoch_count,
+        snapshot_db_backend,
     )
     .unwrap()
 }
@@ -251,7 +271,9 @@ pub fn print_mpt_key(key: &[u8]) {
 }
 
 #[cfg(any(test, feature = "testonly_code"))]
-use crate::{impls::state_manager::StateManager, StorageConfiguration};
+use crate::{
+    impls::state_manager::StateManager, SnapshotDbBackend, StorageConfiguration,
+};
 use crate::{
     impls::{
         errors::*,
@@ -2,6 +2,8 @@
 // Conflux is free software and distributed under GNU General Public License.
 // See http://www.gnu.org/licenses/
 
+#[cfg(test)]
+mod full_sync_progress;
 #[cfg(test)]
 mod slicer;
 #[cfg(test)]
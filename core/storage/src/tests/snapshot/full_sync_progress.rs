@@ -0,0 +1,76 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+fn manifest() -> FullSyncManifest {
+    FullSyncManifest {
+        snapshot_epoch_id: MerkleHash::repeat_byte(1),
+        merkle_root: MerkleHash::repeat_byte(2),
+        chunk_boundaries: vec![vec![1, 2], vec![3]],
+        chunk_boundary_proofs: vec![Default::default(); 2],
+    }
+}
+
+#[test]
+fn test_full_sync_progress_resume() {
+    let dir = env::temp_dir()
+        .join(format!("test_full_sync_progress_resume_{}", process::id()));
+    fs::remove_dir_all(&dir).ok();
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("progress");
+    assert!(FullSyncProgress::resume(&path).unwrap().is_none());
+    assert!(FullSyncProgress::load_manifest(&path).unwrap().is_none());
+
+    let mut progress = FullSyncProgress::create(&path, &manifest()).unwrap();
+    progress
+        .append(&RestoredChunk {
+            chunk_index: 2,
+            boundary_nodes: vec![],
+            boundary_subtree_sizes: vec![(MERKLE_NULL_NODE, 3, 100)],
+        })
+        .unwrap();
+    drop(progress);
+    // A record truncated by an interruption.
+    let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+    file.write_all(&[0, 0, 1, 0, 7]).unwrap();
+    drop(file);
+
+    assert_eq!(
+        FullSyncProgress::load_manifest(&path).unwrap(),
+        Some(manifest())
+    );
+    let (mut progress, loaded, chunks) =
+        FullSyncProgress::resume(&path).unwrap().unwrap();
+    assert_eq!(loaded, manifest());
+    assert_eq!(chunks.len(), 1);
+    assert_eq!(chunks[0].chunk_index, 2);
+    assert_eq!(
+        chunks[0].boundary_subtree_sizes,
+        vec![(MERKLE_NULL_NODE, 3, 100)]
+    );
+
+    // The truncated record is dropped before appending.
+    progress
+        .append(&RestoredChunk {
+            chunk_index: 0,
+            ..Default::default()
+        })
+        .unwrap();
+    drop(progress);
+    let (_, _, chunks) = FullSyncProgress::resume(&path).unwrap().unwrap();
+    let indices: Vec<usize> = chunks.iter().map(|c| c.chunk_index).collect();
+    assert_eq!(indices, vec![2, 0]);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+use crate::impls::snapshot_sync::restoration::full_sync_progress::{
+    FullSyncManifest, FullSyncProgress, RestoredChunk,
+};
+use primitives::{MerkleHash, MERKLE_NULL_NODE};
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::Write,
+    process,
+};
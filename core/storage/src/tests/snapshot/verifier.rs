@@ -462,6 +462,7 @@ impl SnapshotDbTrait for Arc<Mutex<FakeSnapshotDb>> {
 #[derive(Default)]
 struct FakeSnapshotDbManager {
     temp_snapshot: Arc<Mutex<FakeSnapshotDb>>,
+    progress_path: Option<PathBuf>,
 }
 
 impl SnapshotDbManagerTrait for FakeSnapshotDbManager {
@@ -508,6 +509,18 @@ impl SnapshotDbManagerTrait for FakeSnapshotDbManager {
         Ok(self.temp_snapshot.clone())
     }
 
+    fn open_temp_snapshot_for_full_sync(
+        &self, _snapshot_epoch_id: &EpochId, _merkle_root: &MerkleHash,
+    ) -> Result<Option<Self::SnapshotDb>> {
+        Ok(Some(self.temp_snapshot.clone()))
+    }
+
+    fn get_full_sync_progress_path(
+        &self, _snapshot_epoch_id: &EpochId, _merkle_root: &MerkleHash,
+    ) -> Option<PathBuf> {
+        self.progress_path.clone()
+    }
+
     fn finalize_full_sync_snapshot<'m>(
         &self, _snapshot_epoch_id: &MerkleHash, _merkle_root: &MerkleHash,
         _snapshot_info_map_rwlock: &'m RwLock<PersistedSnapshotInfoMap>,
@@ -574,7 +587,14 @@ fn test_full_sync_verifier_one_chunk() {
 }
 
 #[test]
-fn test_full_sync_verifier() {
+fn test_full_sync_verifier() { check_full_sync_verifier(None); }
+
+#[test]
+fn test_full_sync_verifier_resume() { check_full_sync_verifier(Some(2)); }
+
+/// Restores a sliced mpt, and if `resume_at` is set, interrupts the
+/// restoration before the chunk and resumes it with a new verifier.
+fn check_full_sync_verifier(resume_at: Option<usize>) {
     // Slice big mpt.
     let mut rng = get_rng_for_test();
     let mut keys: Vec<Vec<u8>> = generate_keys(TEST_NUMBER_OF_KEYS)
@@ -641,12 +661,19 @@ fn test_full_sync_verifier() {
     }
     drop(slicer);
 
-    let snapshot_db_manager = FakeSnapshotDbManager::default();
+    let progress_dir = env::temp_dir()
+        .join(format!("test_full_sync_verifier_resume_{}", process::id()));
+    let mut snapshot_db_manager = FakeSnapshotDbManager::default();
+    if resume_at.is_some() {
+        fs::remove_dir_all(&progress_dir).ok();
+        fs::create_dir_all(&progress_dir).unwrap();
+        snapshot_db_manager.progress_path = Some(progress_dir.join("progress"));
+    }
 
     let mut full_sync_verifier = FullSyncVerifier::new(
         right_bounds.len(),
         slicer_chunk_bounds.clone(),
-        slicer_chunk_proofs,
+        slicer_chunk_proofs.clone(),
         merkle_root,
         &snapshot_db_manager,
         &NULL_EPOCH,
@@ -660,6 +687,30 @@ fn test_full_sync_verifier() {
         } else {
             None
         };
+        if resume_at == Some(i) {
+            drop(full_sync_verifier);
+            let (chunk_boundaries, chunk_boundary_proofs) =
+                FullSyncVerifier::load_resumable_manifest(
+                    &snapshot_db_manager,
+                    &NULL_EPOCH,
+                    &merkle_root,
+                )
+                .unwrap()
+                .unwrap();
+            assert_eq!(chunk_boundaries, slicer_chunk_bounds);
+            full_sync_verifier = FullSyncVerifier::new(
+                right_bounds.len(),
+                chunk_boundaries,
+                chunk_boundary_proofs,
+                merkle_root,
+                &snapshot_db_manager,
+                &NULL_EPOCH,
+            )
+            .unwrap();
+            assert!(full_sync_verifier
+                .is_chunk_restored(&Some(slicer_chunk_bounds[i - 1].clone())));
+            assert!(!full_sync_verifier.is_chunk_restored(&upper_key));
+        }
         let chunk_restored = full_sync_verifier
             .restore_chunk(
                 &upper_key,
@@ -686,6 +737,10 @@ fn test_full_sync_verifier() {
 
     // Check MPT key-values and subtree size.
     temp_snapshot.mpt_db.lock().assert_eq(&snapshot_mpt);
+
+    if resume_at.is_some() {
+        fs::remove_dir_all(&progress_dir).unwrap();
+    }
 }
 
 use crate::{
@@ -726,8 +781,10 @@ use rand::Rng;
 use std::{
     cmp::{max, min},
     collections::{BTreeMap, HashMap},
+    env, fs,
     ops::Bound::{Excluded, Included, Unbounded},
     path::{Path, PathBuf},
+    process,
     sync::Arc,
 };
 use tokio::sync::Semaphore;
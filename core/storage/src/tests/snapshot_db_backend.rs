@@ -0,0 +1,345 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+fn with_snapshot_dir<F: FnOnce(&Path)>(f: F) {
+    let dir = format!("./conflux_unit_test_data_dir{}", random::<u64>());
+    fs::create_dir_all(&dir).unwrap();
+    f(&Path::new(&dir).join("snapshot"));
+    fs::remove_dir_all(&dir).ok();
+}
+
+fn sorted_kvs(number_of_keys: usize) -> Vec<MptKeyValue> {
+    let mut keys = generate_keys(number_of_keys);
+    keys.sort();
+    keys.into_iter()
+        .map(|key| {
+            let value = key.clone().into_boxed_slice();
+            (key, value)
+        })
+        .collect()
+}
+
+/// Writes the key-values and their mpt into the snapshot db.
+fn fill_snapshot_db(
+    snapshot_db: &mut AnySnapshotDb, kvs: &[MptKeyValue],
+) -> MerkleHash {
+    snapshot_db.start_transaction().unwrap();
+    for (key, value) in kvs {
+        snapshot_db.put(key, value).unwrap();
+    }
+    let merkle_root = {
+        let mut mpt = snapshot_db.open_snapshot_mpt_owned().unwrap();
+        MptMerger::new(None, &mut mpt)
+            .merge_insertion_deletion_separated(
+                fallible_iterator::convert(
+                    Vec::<Result<(Vec<u8>, ())>>::new().into_iter(),
+                ),
+                fallible_iterator::convert(kvs.to_vec().into_iter().map(Ok)),
+            )
+            .unwrap()
+    };
+    snapshot_db.commit_transaction().unwrap();
+    merkle_root
+}
+
+fn snapshot_kvs(snapshot_db: &AnySnapshotDb) -> Vec<MptKeyValue> {
+    let mut kv_iterator = snapshot_db.snapshot_kv_iterator().unwrap().take();
+    let mut iter = kv_iterator.iter_range(&[], None).unwrap().take();
+    let mut kvs = vec![];
+    while let Some(kv) = iter.next().unwrap() {
+        kvs.push(kv);
+    }
+    kvs
+}
+
+fn check_snapshot(
+    snapshot_dir: &Path, backend: SnapshotDbBackend, epoch_id: &EpochId,
+    kvs: &[MptKeyValue], merkle_root: &MerkleHash,
+)
+{
+    let manager =
+        AnySnapshotDbManager::new(snapshot_dir.into(), 2, backend).unwrap();
+    let snapshot_db = manager
+        .get_snapshot_by_epoch_id(epoch_id, /* try_open = */ false)
+        .unwrap()
+        .unwrap();
+    assert_eq!(snapshot_db.backend(), backend);
+    assert_eq!(
+        snapshot_db
+            .open_snapshot_mpt_shared()
+            .unwrap()
+            .get_merkle_root(),
+        *merkle_root
+    );
+
+    assert_eq!(snapshot_kvs(&snapshot_db), kvs);
+    for (key, value) in kvs.iter().step_by(100) {
+        assert_eq!(snapshot_db.get(key).unwrap().as_ref(), Some(value));
+    }
+}
+
+#[test]
+fn test_convert_snapshots() {
+    with_snapshot_dir(|snapshot_dir| {
+        // More than a batch of the rocksdb snapshot iterator.
+        let kvs = sorted_kvs(3000);
+        let mut epoch_id = EpochId::default();
+        epoch_id.as_bytes_mut()[0] = 1;
+
+        let merkle_root = {
+            let manager = AnySnapshotDbManager::new(
+                snapshot_dir.into(),
+                2,
+                SnapshotDbBackend::Sqlite,
+            )
+            .unwrap();
+            let mut snapshot_db = AnySnapshotDb::Sqlite(Arc::new(
+                SnapshotDbSqlite::create(
+                    &manager.get_snapshot_db_path(&epoch_id),
+                    &Default::default(),
+                    &Arc::new(Semaphore::new(0)),
+                )
+                .unwrap(),
+            ));
+            fill_snapshot_db(&mut snapshot_db, &kvs)
+        };
+        assert_ne!(merkle_root, MERKLE_NULL_NODE);
+
+        match AnySnapshotDbManager::new(
+            snapshot_dir.into(),
+            2,
+            SnapshotDbBackend::Rocksdb,
+        ) {
+            Err(e) => match e.kind() {
+                ErrorKind::SnapshotDbBackendMismatch("sqlite", "rocksdb") => {}
+                kind => panic!("unexpected error {:?}", kind),
+            },
+            Ok(_) => panic!("opened sqlite snapshots as rocksdb"),
+        }
+
+        assert_eq!(
+            AnySnapshotDbManager::convert_snapshots(
+                snapshot_dir,
+                SnapshotDbBackend::Rocksdb
+            )
+            .unwrap(),
+            1
+        );
+        check_snapshot(
+            snapshot_dir,
+            SnapshotDbBackend::Rocksdb,
+            &epoch_id,
+            &kvs,
+            &merkle_root,
+        );
+
+        assert_eq!(
+            AnySnapshotDbManager::convert_snapshots(
+                snapshot_dir,
+                SnapshotDbBackend::Sqlite
+            )
+            .unwrap(),
+            1
+        );
+        check_snapshot(
+            snapshot_dir,
+            SnapshotDbBackend::Sqlite,
+            &epoch_id,
+            &kvs,
+            &merkle_root,
+        );
+    });
+}
+
+const SNAPSHOT_EPOCH_COUNT: u32 = 3;
+
+fn account_address(index: u64) -> Address {
+    let mut address = Address::from_low_u64_be(index);
+    address.set_user_account_type_bits();
+    address
+}
+
+/// Commits the epochs up to `last_height`. Every epoch sets new keys, updates
+/// some of the keys of the previous epoch and deletes some others, so that
+/// the snapshots are merged from both key-values to set and to delete.
+fn commit_epochs(
+    state_manager: &FakeStateManager, last_height: u64,
+) -> Vec<(EpochId, StateRootWithAuxInfo)> {
+    let mut committed: Vec<(EpochId, StateRootWithAuxInfo)> = vec![];
+    for height in 0..=last_height {
+        let mut state = match committed.last() {
+            None => state_manager.get_state_for_genesis_write(),
+            Some((parent_epoch_id, parent_state_root)) => state_manager
+                .get_state_for_next_epoch(StateIndex::new_for_next_epoch(
+                    parent_epoch_id,
+                    parent_state_root,
+                    height,
+                    SNAPSHOT_EPOCH_COUNT,
+                ))
+                .unwrap()
+                .unwrap(),
+        };
+        for i in 0..100 {
+            let address = account_address(height * 1000 + i);
+            state
+                .set(
+                    StorageKey::new_account_key(&address),
+                    vec![height as u8; 32].into(),
+                )
+                .unwrap();
+        }
+        if height > 0 {
+            for i in (0..100).step_by(3) {
+                let updated = account_address((height - 1) * 1000 + i);
+                let deleted = account_address((height - 1) * 1000 + i + 1);
+                state
+                    .set(
+                        StorageKey::new_account_key(&updated),
+                        vec![0xff; 32].into(),
+                    )
+                    .unwrap();
+                state.delete(StorageKey::new_account_key(&deleted)).unwrap();
+            }
+        }
+        let epoch_id = EpochId::from_low_u64_be(height + 1);
+        state.compute_state_root().unwrap();
+        let state_root = state.commit(epoch_id).unwrap();
+        committed.push((epoch_id, state_root));
+    }
+    committed
+}
+
+#[test]
+fn test_merge_snapshots() {
+    let snapshot_epoch_count = SNAPSHOT_EPOCH_COUNT as u64;
+    let last_height = 4 * snapshot_epoch_count + 1;
+    let sqlite_state_manager =
+        new_state_manager_for_unit_test_with_snapshot_db_backend(
+            SNAPSHOT_EPOCH_COUNT,
+            SnapshotDbBackend::Sqlite,
+        );
+    let rocksdb_state_manager =
+        new_state_manager_for_unit_test_with_snapshot_db_backend(
+            SNAPSHOT_EPOCH_COUNT,
+            SnapshotDbBackend::Rocksdb,
+        );
+    let committed = commit_epochs(&sqlite_state_manager, last_height);
+    assert_eq!(
+        commit_epochs(&rocksdb_state_manager, last_height),
+        committed
+    );
+
+    // The first snapshot is made by direct_merge, the others by
+    // copy_and_merge from the previous snapshot.
+    for snapshot_height in (1..=3).map(|i| i * snapshot_epoch_count) {
+        let (snapshot_epoch_id, _) = &committed[snapshot_height as usize];
+        let sqlite_snapshot = sqlite_state_manager
+            .get_storage_manager()
+            .wait_for_snapshot(snapshot_epoch_id, /* try_open = */ false)
+            .unwrap()
+            .expect("sqlite snapshot should be made");
+        let rocksdb_snapshot = rocksdb_state_manager
+            .get_storage_manager()
+            .wait_for_snapshot(snapshot_epoch_id, /* try_open = */ false)
+            .unwrap()
+            .expect("rocksdb snapshot should be made");
+        assert_eq!(rocksdb_snapshot.backend(), SnapshotDbBackend::Rocksdb);
+
+        let merkle_root = sqlite_snapshot
+            .open_snapshot_mpt_shared()
+            .unwrap()
+            .get_merkle_root();
+        assert_ne!(merkle_root, MERKLE_NULL_NODE);
+        assert_eq!(
+            rocksdb_snapshot
+                .open_snapshot_mpt_shared()
+                .unwrap()
+                .get_merkle_root(),
+            merkle_root
+        );
+        let kvs = snapshot_kvs(&sqlite_snapshot);
+        assert!(!kvs.is_empty());
+        assert_eq!(snapshot_kvs(&rocksdb_snapshot), kvs);
+
+        // The sqlite snapshot keeps the dumped delta mpt, which shows that
+        // keys were both set and deleted by the merge. The rocksdb snapshot
+        // empties the DELTA_SET and DELTA_DELETE columns after the merge.
+        let sqlite_db = sqlite_snapshot.to_sqlite().unwrap();
+        let mut set_keys =
+            sqlite_db.dumped_delta_kv_set_keys_iterator().unwrap();
+        let mut delete_keys =
+            sqlite_db.dumped_delta_kv_delete_keys_iterator().unwrap();
+        assert_ne!(
+            set_keys
+                .iter_range(&[], None)
+                .unwrap()
+                .take()
+                .count()
+                .unwrap(),
+            0
+        );
+        assert_ne!(
+            delete_keys
+                .iter_range(&[], None)
+                .unwrap()
+                .take()
+                .count()
+                .unwrap(),
+            0
+        );
+        let rocksdb_db = rocksdb_snapshot.to_rocksdb().unwrap();
+        let mut set_keys =
+            rocksdb_db.dumped_delta_kv_set_keys_iterator().unwrap();
+        let mut delete_keys =
+            rocksdb_db.dumped_delta_kv_delete_keys_iterator().unwrap();
+        assert_eq!(
+            set_keys
+                .iter_range(&[], None)
+                .unwrap()
+                .take()
+                .count()
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            delete_keys
+                .iter_range(&[], None)
+                .unwrap()
+                .take()
+                .count()
+                .unwrap(),
+            0
+        );
+    }
+}
+
+use super::{
+    generate_keys, new_state_manager_for_unit_test_with_snapshot_db_backend,
+    FakeStateManager,
+};
+use crate::{
+    impls::{
+        errors::*,
+        merkle_patricia_trie::{MptKeyValue, MptMerger},
+        storage_db::{
+            snapshot_db_any::AnySnapshotDb,
+            snapshot_db_manager_any::AnySnapshotDbManager,
+            snapshot_db_sqlite::SnapshotDbSqlite,
+        },
+    },
+    state::StateTrait,
+    state_manager::{StateIndex, StateManagerTrait},
+    storage_db::{
+        KeyValueDbIterableTrait, KeyValueDbTraitRead,
+        KeyValueDbTraitSingleWriter, OpenSnapshotMptTrait,
+        SnapshotDbManagerTrait, SnapshotDbTrait, SnapshotMptTraitRead,
+    },
+    SnapshotDbBackend, StateRootWithAuxInfo,
+};
+use cfx_types::{address_util::AddressUtil, Address};
+use fallible_iterator::FallibleIterator;
+use primitives::{EpochId, MerkleHash, StorageKey, MERKLE_NULL_NODE};
+use rand::random;
+use std::{fs, path::Path, sync::Arc};
+use tokio::sync::Semaphore;
@@ -532,6 +532,180 @@ fn test_set_delete_all() {
     assert_eq!(state_root, empty_state_root);
 }
 
+#[test]
+fn test_read_range() {
+    let state_manager = new_state_manager_for_unit_test();
+    let addresses: Vec<Address> =
+        (1..4).map(|i| Address::repeat_byte(i)).collect();
+    let positions: Vec<H256> = (0..6).map(H256::from_low_u64_be).collect();
+    let storage_key = |i: usize| {
+        StorageKey::new_storage_key(&addresses[1], positions[i].as_ref())
+            .to_key_bytes()
+    };
+    let kv = |key: Vec<u8>, value: &[u8]| -> (Vec<u8>, Box<[u8]>) {
+        (key, value.into())
+    };
+
+    let mut state = state_manager.get_state_for_genesis_write();
+    for address in &addresses {
+        state
+            .set(StorageKey::new_account_key(address), address[..].into())
+            .unwrap();
+    }
+    for position in &positions[0..5] {
+        state
+            .set(
+                StorageKey::new_storage_key(&addresses[1], position.as_ref()),
+                position[..].into(),
+            )
+            .unwrap();
+    }
+    let mut epoch_id = H256::default();
+    epoch_id.as_bytes_mut()[0] = 1;
+    state.compute_state_root().unwrap();
+    state.commit(epoch_id).unwrap();
+
+    // Overwrite the committed key-values in the next epoch.
+    let mut state = state_manager
+        .get_state_for_next_epoch(StateIndex::new_for_test_only_delta_mpt(
+            &epoch_id,
+        ))
+        .unwrap()
+        .unwrap();
+    state
+        .delete(StorageKey::new_account_key(&addresses[0]))
+        .unwrap();
+    state
+        .delete(StorageKey::new_storage_key(
+            &addresses[1],
+            positions[1].as_ref(),
+        ))
+        .unwrap();
+    state
+        .set(
+            StorageKey::new_storage_key(&addresses[1], positions[5].as_ref()),
+            positions[5][..].into(),
+        )
+        .unwrap();
+    epoch_id.as_bytes_mut()[0] = 2;
+    state.compute_state_root().unwrap();
+    state.commit(epoch_id).unwrap();
+
+    let state = state_manager
+        .get_state_for_next_epoch(StateIndex::new_for_test_only_delta_mpt(
+            &epoch_id,
+        ))
+        .unwrap()
+        .unwrap();
+    let storage_prefix = StorageKey::new_storage_root_key(&addresses[1]);
+    let (kvs, next_key) = state
+        .read_range(Some(storage_prefix), &storage_key(0), 2, None)
+        .unwrap();
+    assert_eq!(
+        kvs.into_iter().map(|(key, _)| key).collect::<Vec<_>>(),
+        vec![storage_key(0), storage_key(2)]
+    );
+    assert_eq!(next_key, Some(storage_key(3)));
+    let (kvs, next_key) = state
+        .read_range(Some(storage_prefix), &storage_key(3), 10, None)
+        .unwrap();
+    assert_eq!(
+        kvs,
+        (3..6)
+            .map(|i| kv(storage_key(i), &positions[i][..]))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(next_key, None);
+
+    // Only the account keys are listed when the address is distinct.
+    let address_len = Some(Address::len_bytes());
+    let (kvs, next_key) = state.read_range(None, &[], 1, address_len).unwrap();
+    assert_eq!(kvs, vec![kv(addresses[1].0.to_vec(), &addresses[1][..])]);
+    assert_eq!(next_key, Some(addresses[2].0.to_vec()));
+    let (kvs, next_key) = state
+        .read_range(None, addresses[2].as_ref(), 10, address_len)
+        .unwrap();
+    assert_eq!(kvs, vec![kv(addresses[2].0.to_vec(), &addresses[2][..])]);
+    assert_eq!(next_key, None);
+}
+
+#[test]
+fn test_read_range_pages() {
+    let state_manager = new_state_manager_for_unit_test();
+    let address = Address::repeat_byte(1);
+    let positions: Vec<H256> = (0..700).map(H256::from_low_u64_be).collect();
+
+    // More keys than a loaded batch in both delta tries, with tombstones.
+    let mut state = state_manager.get_state_for_genesis_write();
+    for position in &positions[0..600] {
+        state
+            .set(
+                StorageKey::new_storage_key(&address, position.as_ref()),
+                position[..].into(),
+            )
+            .unwrap();
+    }
+    let mut epoch_id = H256::default();
+    epoch_id.as_bytes_mut()[0] = 1;
+    state.compute_state_root().unwrap();
+    state.commit(epoch_id).unwrap();
+
+    let mut state = state_manager
+        .get_state_for_next_epoch(StateIndex::new_for_test_only_delta_mpt(
+            &epoch_id,
+        ))
+        .unwrap()
+        .unwrap();
+    for position in positions[0..600].iter().step_by(3) {
+        state
+            .delete(StorageKey::new_storage_key(&address, position.as_ref()))
+            .unwrap();
+    }
+    for position in &positions[600..] {
+        state
+            .set(
+                StorageKey::new_storage_key(&address, position.as_ref()),
+                position[..].into(),
+            )
+            .unwrap();
+    }
+    epoch_id.as_bytes_mut()[0] = 2;
+    state.compute_state_root().unwrap();
+    state.commit(epoch_id).unwrap();
+
+    let state = state_manager
+        .get_state_for_next_epoch(StateIndex::new_for_test_only_delta_mpt(
+            &epoch_id,
+        ))
+        .unwrap()
+        .unwrap();
+    let storage_prefix = StorageKey::new_storage_root_key(&address);
+    let mut start_key = vec![];
+    let mut keys = vec![];
+    loop {
+        let (kvs, next_key) = state
+            .read_range(Some(storage_prefix), &start_key, 50, None)
+            .unwrap();
+        assert!(kvs.len() <= 50);
+        keys.extend(kvs.into_iter().map(|(key, _)| key));
+        match next_key {
+            Some(next_key) => start_key = next_key,
+            None => break,
+        }
+    }
+    let mut expected_keys: Vec<Vec<u8>> = positions
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i >= 600 || i % 3 != 0)
+        .map(|(_, position)| {
+            StorageKey::new_storage_key(&address, position.as_ref())
+                .to_key_bytes()
+        })
+        .collect();
+    expected_keys.sort();
+    assert_eq!(keys, expected_keys);
+}
+
 #[test]
 fn test_set_order() {
     let mut rng = get_rng_for_test();
@@ -0,0 +1,150 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+fn epoch(n: u8) -> EpochId {
+    let mut epoch_id = EpochId::default();
+    epoch_id.as_bytes_mut()[0] = n;
+    epoch_id
+}
+
+fn pivot_chain(epochs: Vec<EpochId>) -> EpochByHeight {
+    Arc::new(move |height| epochs.get(height as usize).cloned())
+}
+
+fn changes(
+    kvs: &[(&str, Option<&str>)],
+) -> BTreeMap<Vec<u8>, Option<Box<[u8]>>> {
+    kvs.iter()
+        .map(|(key, maybe_value)| {
+            (
+                key.as_bytes().to_vec(),
+                maybe_value.map(|value| value.as_bytes().into()),
+            )
+        })
+        .collect()
+}
+
+fn with_state_history_dir<F: FnOnce(&Path)>(f: F) {
+    let dir = format!("./conflux_unit_test_data_dir{}", random::<u64>());
+    fs::create_dir_all(&dir).unwrap();
+    f(&Path::new(&dir).join("state_history_db"));
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_get_latest_value_on_pivot_chain() {
+    with_state_history_dir(|path| {
+        let history = StateHistory::open_or_create(path).unwrap();
+        history
+            .record_epoch(
+                0,
+                &epoch(0),
+                &changes(&[("k1", Some("v0")), ("k2", Some("v0"))]),
+            )
+            .unwrap();
+        history
+            .record_epoch(1, &epoch(1), &changes(&[("k1", Some("v1"))]))
+            .unwrap();
+        // A fork at height 1 which deletes k2.
+        history
+            .record_epoch(
+                1,
+                &epoch(11),
+                &changes(&[("k1", Some("v11")), ("k2", None)]),
+            )
+            .unwrap();
+        history
+            .record_epoch(2, &epoch(2), &changes(&[("k3", Some("v2"))]))
+            .unwrap();
+        assert_eq!(history.height_range(), Some((0, 2)));
+
+        let main_chain = pivot_chain(vec![epoch(0), epoch(1), epoch(2)]);
+        let get = |key: &[u8], height, pivot_chain: &EpochByHeight| {
+            history
+                .get(key, height, pivot_chain)
+                .unwrap()
+                .map(|value| value.to_vec())
+        };
+        assert_eq!(get(b"k1", 0, &main_chain), Some(b"v0".to_vec()));
+        assert_eq!(get(b"k1", 1, &main_chain), Some(b"v1".to_vec()));
+        assert_eq!(get(b"k1", 2, &main_chain), Some(b"v1".to_vec()));
+        assert_eq!(get(b"k2", 2, &main_chain), Some(b"v0".to_vec()));
+        assert_eq!(get(b"k3", 1, &main_chain), None);
+        assert_eq!(get(b"k3", 2, &main_chain), Some(b"v2".to_vec()));
+        assert_eq!(get(b"k4", 2, &main_chain), None);
+
+        let fork = pivot_chain(vec![epoch(0), epoch(11)]);
+        assert_eq!(get(b"k1", 1, &fork), Some(b"v11".to_vec()));
+        assert_eq!(get(b"k2", 1, &fork), None);
+
+        assert_eq!(
+            history
+                .get_range(b"k", None, 2, &main_chain, usize::MAX)
+                .unwrap(),
+            vec![
+                (b"k1".to_vec(), b"v1".to_vec().into_boxed_slice()),
+                (b"k2".to_vec(), b"v0".to_vec().into_boxed_slice()),
+                (b"k3".to_vec(), b"v2".to_vec().into_boxed_slice()),
+            ]
+        );
+        assert_eq!(
+            history
+                .get_range(b"k1", Some(&b"k3"[..]), 1, &fork, usize::MAX)
+                .unwrap(),
+            vec![(b"k1".to_vec(), b"v11".to_vec().into_boxed_slice())]
+        );
+        assert_eq!(
+            history.get_range(b"k", None, 2, &main_chain, 2).unwrap(),
+            vec![
+                (b"k1".to_vec(), b"v1".to_vec().into_boxed_slice()),
+                (b"k2".to_vec(), b"v0".to_vec().into_boxed_slice()),
+            ]
+        );
+    });
+}
+
+#[test]
+fn test_history_restarts_after_missing_epochs() {
+    with_state_history_dir(|path| {
+        {
+            let history = StateHistory::open_or_create(path).unwrap();
+            history
+                .record_epoch(0, &epoch(0), &changes(&[("k1", Some("v0"))]))
+                .unwrap();
+            history
+                .record_epoch(1, &epoch(1), &changes(&[("k2", Some("v1"))]))
+                .unwrap();
+            // Epochs 2 to 4 are never executed, e.g. a snapshot is synced.
+            history
+                .record_epoch(5, &epoch(5), &changes(&[("k2", Some("v5"))]))
+                .unwrap();
+            assert_eq!(history.height_range(), Some((5, 5)));
+        }
+
+        // The height range is persisted.
+        let history = StateHistory::open_or_create(path).unwrap();
+        assert_eq!(history.height_range(), Some((5, 5)));
+        assert!(!history.covers_height(1));
+        assert!(history.covers_height(5));
+
+        let main_chain = pivot_chain((0..6).map(epoch).collect());
+        assert_eq!(
+            history.get(b"k2", 5, &main_chain).unwrap(),
+            Some(b"v5".to_vec().into_boxed_slice())
+        );
+        // The value of k1 is set before the history starts.
+        match history.get(b"k1", 5, &main_chain).unwrap_err().kind() {
+            ErrorKind::StateHistoryIncomplete(5) => {}
+            e => panic!("unexpected error {:?}", e),
+        }
+        assert!(history
+            .get_range(b"k", None, 5, &main_chain, usize::MAX)
+            .is_err());
+    });
+}
+
+use crate::{EpochByHeight, ErrorKind, StateHistory};
+use primitives::EpochId;
+use rand::random;
+use std::{collections::BTreeMap, fs, path::Path, sync::Arc};
@@ -0,0 +1,238 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+const SNAPSHOT_EPOCH_COUNT: u32 = 3;
+const RANGE_RLP_SIZE: u64 = 1024;
+
+fn epoch_id(height: u64) -> EpochId { EpochId::from_low_u64_be(height + 1) }
+
+fn account_value(height: u64, i: u64) -> Vec<u8> {
+    [height.to_be_bytes(), i.to_be_bytes()].concat()
+}
+
+fn commit_epoch(
+    state: &mut StorageState, epoch_id: &EpochId, height: u64,
+) -> StateRootWithAuxInfo {
+    for i in 0..100 {
+        let mut address = Address::from_low_u64_be(height * 1000 + i);
+        address.set_user_account_type_bits();
+        state
+            .set(
+                StorageKey::new_account_key(&address),
+                account_value(height, i).into(),
+            )
+            .unwrap();
+    }
+    state.compute_state_root().unwrap();
+    state.commit(*epoch_id).unwrap()
+}
+
+/// Commits the epochs up to `height`, and returns their state roots.
+fn commit_epochs(
+    state_manager: &StateManager, height: u64,
+) -> Vec<StateRootWithAuxInfo> {
+    let mut state = state_manager.get_state_for_genesis_write();
+    let mut state_roots = vec![commit_epoch(&mut state, &epoch_id(0), 0)];
+    for height in 1..=height {
+        let mut state = state_manager
+            .get_state_for_next_epoch(StateIndex::new_for_next_epoch(
+                &epoch_id(height - 1),
+                state_roots.last().unwrap(),
+                height,
+                SNAPSHOT_EPOCH_COUNT,
+            ))
+            .unwrap()
+            .unwrap();
+        state_roots.push(commit_epoch(&mut state, &epoch_id(height), height));
+    }
+    state_roots
+}
+
+#[test]
+fn test_verify_corrupted_snapshot_kv() {
+    let state_manager =
+        new_state_manager_for_unit_test_with_snapshot_epoch_count(
+            SNAPSHOT_EPOCH_COUNT,
+        );
+    let snapshot_height = SNAPSHOT_EPOCH_COUNT as u64;
+    commit_epochs(&state_manager, 2 * snapshot_height + 1);
+    let snapshot_epoch_id = epoch_id(snapshot_height);
+    let storage_manager = state_manager.get_storage_manager();
+    let keys = {
+        let snapshot_db = storage_manager
+            .wait_for_snapshot(&snapshot_epoch_id, /* try_open = */ false)
+            .unwrap()
+            .expect("snapshot should be made");
+        let mut kv_iterator =
+            snapshot_db.snapshot_kv_iterator().unwrap().take();
+        let mut iter = kv_iterator.iter_range(&[], None).unwrap().take();
+        let mut keys = vec![];
+        while let Some((key, _)) = iter.next().unwrap() {
+            keys.push(key);
+        }
+        keys
+    };
+    let merkle_root = storage_manager
+        .get_snapshot_info_at_epoch(&snapshot_epoch_id)
+        .unwrap()
+        .merkle_root;
+
+    assert_eq!(
+        StorageVerifier::new(&state_manager, RANGE_RLP_SIZE)
+            .verify_snapshot(&snapshot_epoch_id, &merkle_root)
+            .unwrap(),
+        vec![]
+    );
+
+    // Overwrite a value in the key-value table, leaving the mpt intact.
+    let corrupted_key = &keys[keys.len() / 2];
+    {
+        let mut snapshot_db = SnapshotDbSqlite::open(
+            &storage_manager
+                .get_snapshot_manager()
+                .get_snapshot_db_manager()
+                .get_snapshot_db_path(&snapshot_epoch_id),
+            /* readonly = */ false,
+            &Default::default(),
+            &Arc::new(Semaphore::new(0)),
+        )
+        .unwrap();
+        snapshot_db.start_transaction().unwrap();
+        snapshot_db.put(corrupted_key, b"corrupted").unwrap();
+        snapshot_db.commit_transaction().unwrap();
+    }
+
+    let corruptions = StorageVerifier::new(&state_manager, RANGE_RLP_SIZE)
+        .verify_snapshot(&snapshot_epoch_id, &merkle_root)
+        .unwrap();
+    assert_eq!(corruptions.len(), 1);
+    match &corruptions[0] {
+        StorageCorruption::CorruptedKeyRange {
+            tree: StateTree::Snapshot,
+            lower_bound_incl,
+            upper_bound_excl: Some(upper_bound_excl),
+        } => {
+            // Only the range of the key is reported.
+            assert!(lower_bound_incl <= corrupted_key);
+            assert!(upper_bound_excl > corrupted_key);
+            assert!(lower_bound_incl > &keys[0]);
+            assert!(upper_bound_excl <= keys.last().unwrap());
+        }
+        corruption => panic!("unexpected corruption {:?}", corruption),
+    }
+}
+
+#[test]
+fn test_verify_corrupted_delta_mpt_node() {
+    let state_manager = new_state_manager_for_unit_test();
+    let state_root = commit_epochs(&state_manager, 0).pop().unwrap();
+    let state_trees = state_manager
+        .get_state_trees(
+            &StateIndex::new_for_readonly(&epoch_id(0), &state_root),
+            /* try_open = */ false,
+        )
+        .unwrap()
+        .unwrap();
+    let delta_mpt = &state_trees.delta_trie;
+
+    assert_eq!(
+        StorageVerifier::new(&state_manager, RANGE_RLP_SIZE)
+            .verify_state(&epoch_id(0), &state_root)
+            .unwrap(),
+        vec![]
+    );
+
+    // Rewrite the value of the leaf node of an account without updating its
+    // merkle. The row numbers of the committed nodes start from 0.
+    let mut address = Address::from_low_u64_be(0);
+    address.set_user_account_type_bits();
+    let arc_db = delta_mpt.get_arc_db().unwrap();
+    let leaf_value = account_value(0, 0);
+    let mut db_key = 0;
+    let leaf_node = loop {
+        let rlp = arc_db
+            .get_with_number_key(db_key)
+            .unwrap()
+            .expect("leaf node should be committed");
+        let trie_node = TrieNodeDeltaMpt::decode(&Rlp::new(&rlp)).unwrap();
+        if trie_node.get_children_count() == 0
+            && trie_node.value_as_slice().into_option()
+                == Some(leaf_value.as_slice())
+        {
+            break trie_node;
+        }
+        db_key += 1;
+    };
+    let corrupted_node = TrieNodeDeltaMpt::new(
+        *leaf_node.get_merkle(),
+        Default::default(),
+        Some(b"corrupted".to_vec().into_boxed_slice()),
+        leaf_node.compressed_path_ref().into(),
+    );
+    let mut transaction = arc_db.start_transaction_dyn(true).unwrap();
+    transaction
+        .put_with_number_key(db_key, corrupted_node.rlp_bytes().as_slice())
+        .unwrap();
+    transaction.commit(arc_db.db_ref().as_any()).unwrap();
+    drop(transaction);
+    // Make sure that the nodes are loaded from the db.
+    delta_mpt
+        .get_node_memory_manager()
+        .delete_mpt_from_cache(delta_mpt.get_mpt_id());
+
+    let corruptions = StorageVerifier::new(&state_manager, RANGE_RLP_SIZE)
+        .verify_state(&epoch_id(0), &state_root)
+        .unwrap();
+    assert_eq!(corruptions.len(), 2);
+    // Only the key of the corrupted leaf node is reported.
+    let delta_mpt_key = StorageKey::new_account_key(&address)
+        .to_delta_mpt_key_bytes(&state_trees.delta_trie_key_padding);
+    assert_eq!(
+        corruptions[0],
+        StorageCorruption::CorruptedKeyRange {
+            tree: StateTree::DeltaMpt,
+            upper_bound_excl: to_key_prefix_iter_upper_bound(&delta_mpt_key),
+            lower_bound_incl: delta_mpt_key,
+        }
+    );
+    match &corruptions[1] {
+        StorageCorruption::RootMismatch {
+            tree: StateTree::DeltaMpt,
+            expected,
+            found,
+        } => {
+            assert_eq!(*expected, state_root.state_root.delta_root);
+            assert_ne!(found, expected);
+        }
+        corruption => panic!("unexpected corruption {:?}", corruption),
+    }
+}
+
+use crate::{
+    impls::{
+        delta_mpt::TrieNodeDeltaMpt, merkle_patricia_trie::TrieNodeTrait,
+        storage_db::snapshot_db_sqlite::SnapshotDbSqlite,
+    },
+    snapshot_manager::SnapshotManagerTrait,
+    state::*,
+    state_manager::*,
+    storage_db::{
+        KeyValueDbAsAnyTrait, KeyValueDbIterableTrait, KeyValueDbTraitRead,
+        KeyValueDbTraitSingleWriter, KeyValueDbTraitTransactionalDyn,
+        SnapshotDbManagerTrait, SnapshotDbTrait,
+    },
+    tests::{
+        new_state_manager_for_unit_test,
+        new_state_manager_for_unit_test_with_snapshot_epoch_count,
+    },
+    utils::to_key_prefix_iter_upper_bound,
+    StateRootWithAuxInfo, StateTree, StorageCorruption, StorageState,
+    StorageVerifier,
+};
+use cfx_types::{address_util::AddressUtil, Address};
+use fallible_iterator::FallibleIterator;
+use primitives::{EpochId, StorageKey};
+use rlp::{Decodable, Encodable, Rlp};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
@@ -23,7 +23,7 @@ use std::{cmp, collections::HashMap, error, fs, io, mem, path::Path, result};
 use parking_lot::{Mutex, MutexGuard, RwLock};
 use rocksdb::{
     BlockBasedOptions, CFHandle, ColumnFamilyOptions, DBOptions, ReadOptions,
-    Writable, WriteBatch, WriteOptions, DB,
+    SeekKey, Writable, WriteBatch, WriteOptions, DB,
 };
 
 use fs_swap::{swap, swap_nonatomic};
@@ -601,6 +601,36 @@ impl Database {
         }
     }
 
+    /// Get at most `max_count` key-values of the column in
+    /// [lower_bound_incl, upper_bound_excl), ordered by key. Values written by
+    /// `write_buffered` aren't visible until they are flushed.
+    pub fn get_range(
+        &self, col: u32, lower_bound_incl: &[u8],
+        upper_bound_excl: Option<&[u8]>, max_count: usize,
+    ) -> io::Result<Vec<KeyValuePair>>
+    {
+        match *self.db.read() {
+            Some(ref cfs) => {
+                let mut iter = cfs.db.iter_cf_opt(
+                    cfs.get_cf(col as usize),
+                    ReadOptions::default(),
+                );
+                let mut kvs = Vec::new();
+                let mut valid = iter.seek(SeekKey::Key(lower_bound_incl));
+                while valid && kvs.len() < max_count {
+                    let key = iter.key();
+                    if upper_bound_excl.map_or(false, |upper| key >= upper) {
+                        break;
+                    }
+                    kvs.push((key.into(), iter.value().into()));
+                    valid = iter.next();
+                }
+                Ok(kvs)
+            }
+            None => Err(other_io_err("Database is closed")),
+        }
+    }
+
     /// Close the database
     fn close(&self) {
         *self.db.write() = None;
@@ -829,6 +859,34 @@ mod tests {
         test_db(&DatabaseConfig::default());
     }
 
+    #[test]
+    fn get_range() {
+        let tempdir = TempDir::new("").unwrap();
+        let db =
+            Database::open_default(tempdir.path().to_str().unwrap()).unwrap();
+        let mut batch = db.transaction();
+        for key in &[b"a", b"b", b"c", b"d"] {
+            batch.put(0, *key, *key);
+        }
+        db.write(batch).unwrap();
+
+        let keys = |kvs: Vec<KeyValuePair>| -> Vec<Vec<u8>> {
+            kvs.into_iter().map(|(key, _)| key.into_vec()).collect()
+        };
+        assert_eq!(
+            keys(db.get_range(0, b"b", None, 10).unwrap()),
+            vec![b"b".to_vec(), b"c".to_vec(), b"d".to_vec()]
+        );
+        assert_eq!(
+            keys(db.get_range(0, b"", Some(&b"c"[..]), 10).unwrap()),
+            vec![b"a".to_vec(), b"b".to_vec()]
+        );
+        assert_eq!(
+            keys(db.get_range(0, b"a", None, 1).unwrap()),
+            vec![b"a".to_vec()]
+        );
+    }
+
     #[test]
     #[cfg(target_os = "linux")]
     fn df_to_rotational() {
@@ -0,0 +1,245 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! Bandwidth accounting of protocol packets.
+//!
+//! The bytes and packets of each protocol sent to and received from a peer
+//! are counted in its session, and reported in `net_sessions` and in the
+//! `network_bandwidth` metrics. The bytes sent to each peer could be limited
+//! per protocol, e.g. the budget to serve each light client, so that serving
+//! light clients does not starve the sync of the node itself. Packets over the
+//! limit are refused with a throttling error, which protocol handlers tolerate
+//! without disconnecting the peer.
+
+use crate::{Error, ErrorKind, ProtocolId, ThrottlingReason};
+use lazy_static::lazy_static;
+use metrics::{register_meter_with_group, Meter};
+use parking_lot::Mutex;
+use serde_derive::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+    time::Instant,
+};
+
+lazy_static! {
+    static ref PROTOCOL_METERS: Mutex<HashMap<ProtocolId, Arc<ProtocolMeters>>> =
+        Default::default();
+}
+
+struct ProtocolMeters {
+    ingress: Arc<dyn Meter>,
+    egress: Arc<dyn Meter>,
+    throttled: Arc<dyn Meter>,
+}
+
+/// Returns the meters of `protocol`, which are registered on first use.
+fn protocol_meters(protocol: &ProtocolId) -> Arc<ProtocolMeters> {
+    PROTOCOL_METERS
+        .lock()
+        .entry(*protocol)
+        .or_insert_with(|| {
+            let name = String::from_utf8_lossy(protocol);
+            let meter = |suffix: &str| {
+                register_meter_with_group(
+                    "network_bandwidth",
+                    &format!("{}_{}", name, suffix),
+                )
+            };
+            Arc::new(ProtocolMeters {
+                ingress: meter("ingress"),
+                egress: meter("egress"),
+                throttled: meter("egress_throttled"),
+            })
+        })
+        .clone()
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BandwidthConfig {
+    /// Maximum bytes per second sent to each peer for the protocols.
+    pub egress_limits: HashMap<ProtocolId, u64>,
+}
+
+/// Bandwidth used by a protocol in a session.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProtocolBandwidth {
+    pub protocol: String,
+    pub ingress_bytes: u64,
+    pub ingress_packets: u64,
+    pub egress_bytes: u64,
+    pub egress_packets: u64,
+    /// Number of packets refused for exceeding the egress limit.
+    pub throttled_packets: u64,
+    /// Maximum bytes per second sent to the peer.
+    pub egress_limit: Option<u64>,
+}
+
+/// Egress budget that recharges `limit` bytes per second, up to `limit`. A
+/// packet is allowed as long as the budget is positive and may overdraw it,
+/// so that packets larger than the limit are still sent in time.
+struct EgressBudget {
+    limit: i64,
+    budget: i64,
+    updated: Instant,
+}
+
+impl EgressBudget {
+    fn new(limit: u64, now: Instant) -> Self {
+        let limit = limit.min(i64::MAX as u64) as i64;
+        EgressBudget {
+            limit,
+            budget: limit,
+            updated: now,
+        }
+    }
+
+    fn try_consume(&mut self, size: usize, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.updated);
+        let recharged = (elapsed.as_secs_f64() * self.limit as f64) as i64;
+        if recharged > 0 {
+            self.budget = self.budget.saturating_add(recharged).min(self.limit);
+            self.updated = now;
+        }
+
+        if self.budget <= 0 {
+            return false;
+        }
+        self.budget -= size as i64;
+        true
+    }
+}
+
+struct ProtocolTraffic {
+    stat: ProtocolBandwidth,
+    budget: Option<EgressBudget>,
+    meters: Arc<ProtocolMeters>,
+}
+
+/// Bandwidth accounting and egress limits of a session.
+pub struct SessionBandwidth {
+    egress_limits: HashMap<ProtocolId, u64>,
+    protocols: BTreeMap<ProtocolId, ProtocolTraffic>,
+}
+
+impl SessionBandwidth {
+    pub fn new(config: &BandwidthConfig) -> Self {
+        SessionBandwidth {
+            egress_limits: config.egress_limits.clone(),
+            protocols: BTreeMap::new(),
+        }
+    }
+
+    fn traffic(
+        &mut self, protocol: &ProtocolId, now: Instant,
+    ) -> &mut ProtocolTraffic {
+        let limit = self.egress_limits.get(protocol).cloned();
+        self.protocols
+            .entry(*protocol)
+            .or_insert_with(|| ProtocolTraffic {
+                stat: ProtocolBandwidth {
+                    protocol: String::from_utf8_lossy(protocol).into(),
+                    egress_limit: limit,
+                    ..Default::default()
+                },
+                budget: limit.map(|limit| EgressBudget::new(limit, now)),
+                meters: protocol_meters(protocol),
+            })
+    }
+
+    /// Counts a packet of `size` bytes received for `protocol`.
+    pub fn on_ingress(&mut self, protocol: &ProtocolId, size: usize) {
+        let traffic = self.traffic(protocol, Instant::now());
+        traffic.stat.ingress_bytes += size as u64;
+        traffic.stat.ingress_packets += 1;
+        traffic.meters.ingress.mark(size);
+    }
+
+    /// Counts a packet of `size` bytes to send for `protocol`, or returns a
+    /// throttling error if the egress limit of the protocol is exceeded.
+    pub fn on_egress(
+        &mut self, protocol: &ProtocolId, size: usize,
+    ) -> Result<(), Error> {
+        self.on_egress_at(protocol, size, Instant::now())
+    }
+
+    fn on_egress_at(
+        &mut self, protocol: &ProtocolId, size: usize, now: Instant,
+    ) -> Result<(), Error> {
+        let traffic = self.traffic(protocol, now);
+        if let Some(ref mut budget) = traffic.budget {
+            if !budget.try_consume(size, now) {
+                traffic.stat.throttled_packets += 1;
+                traffic.meters.throttled.mark(1);
+                bail!(ErrorKind::Throttling(
+                    ThrottlingReason::BandwidthExceeded(*protocol)
+                ));
+            }
+        }
+
+        traffic.stat.egress_bytes += size as u64;
+        traffic.stat.egress_packets += 1;
+        traffic.meters.egress.mark(size);
+        Ok(())
+    }
+
+    /// Returns the bandwidth used by each protocol, ordered by protocol id.
+    pub fn details(&self) -> Vec<ProtocolBandwidth> {
+        self.protocols
+            .values()
+            .map(|traffic| traffic.stat.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BandwidthConfig, SessionBandwidth};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_accounting() {
+        let mut bandwidth = SessionBandwidth::new(&BandwidthConfig::default());
+        bandwidth.on_ingress(b"cfx", 100);
+        bandwidth.on_ingress(b"cfx", 50);
+        assert!(bandwidth.on_egress(b"clp", 1000).is_ok());
+
+        let details = bandwidth.details();
+        assert_eq!(details.len(), 2);
+        assert_eq!(details[0].protocol, "cfx");
+        assert_eq!(details[0].ingress_bytes, 150);
+        assert_eq!(details[0].ingress_packets, 2);
+        assert_eq!(details[0].egress_bytes, 0);
+        assert_eq!(details[1].protocol, "clp");
+        assert_eq!(details[1].egress_bytes, 1000);
+        assert_eq!(details[1].egress_limit, None);
+    }
+
+    #[test]
+    fn test_egress_limit() {
+        let mut config = BandwidthConfig::default();
+        config.egress_limits.insert(*b"clp", 1000);
+        let mut bandwidth = SessionBandwidth::new(&config);
+        let now = Instant::now();
+
+        // The budget may be overdrawn by the last packet.
+        assert!(bandwidth.on_egress_at(b"clp", 600, now).is_ok());
+        assert!(bandwidth.on_egress_at(b"clp", 600, now).is_ok());
+        assert!(bandwidth.on_egress_at(b"clp", 1, now).is_err());
+        assert!(bandwidth.on_egress_at(b"cfx", 5000, now).is_ok());
+
+        // Recharge 1000 bytes per second.
+        let later = now + Duration::from_millis(100);
+        assert!(bandwidth.on_egress_at(b"clp", 1, later).is_err());
+        let later = now + Duration::from_millis(300);
+        assert!(bandwidth.on_egress_at(b"clp", 1, later).is_ok());
+
+        let details = bandwidth.details();
+        assert_eq!(details[1].egress_bytes, 1201);
+        assert_eq!(details[1].egress_packets, 3);
+        assert_eq!(details[1].throttled_packets, 2);
+        assert_eq!(details[1].egress_limit, Some(1000));
+    }
+}
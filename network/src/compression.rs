@@ -0,0 +1,267 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! Payload compression of session packets.
+//!
+//! Peers advertise the algorithms they support in the HELLO packet. A sender
+//! only compresses a protocol packet if the remote peer supports one of its
+//! configured algorithms, and tags the compressed packet with the algorithm in
+//! the packet header, so that both directions of a session may use a
+//! different algorithm.
+
+use crate::{Error, ErrorKind, ProtocolId};
+use lazy_static::lazy_static;
+use metrics::{register_meter_with_group, Histogram, Meter, Sample};
+use std::{fmt, io::Read, str::FromStr, sync::Arc, time::Instant};
+
+lazy_static! {
+    static ref COMPRESS_INPUT_METER: Arc<dyn Meter> =
+        register_meter_with_group("network_compression", "compress_input");
+    static ref COMPRESS_OUTPUT_METER: Arc<dyn Meter> =
+        register_meter_with_group("network_compression", "compress_output");
+    static ref DECOMPRESS_INPUT_METER: Arc<dyn Meter> =
+        register_meter_with_group("network_compression", "decompress_input");
+    static ref DECOMPRESS_OUTPUT_METER: Arc<dyn Meter> =
+        register_meter_with_group("network_compression", "decompress_output");
+    /// Compressed size in percent of the raw size.
+    static ref COMPRESSION_RATIO: Arc<dyn Histogram> =
+        Sample::ExpDecay(0.015).register_with_group(
+            "network_compression",
+            "ratio_percent",
+            1024
+        );
+    static ref COMPRESS_TIME: Arc<dyn Histogram> =
+        Sample::ExpDecay(0.015).register_with_group(
+            "network_compression",
+            "compress_time",
+            1024
+        );
+    static ref DECOMPRESS_TIME: Arc<dyn Histogram> =
+        Sample::ExpDecay(0.015).register_with_group(
+            "network_compression",
+            "decompress_time",
+            1024
+        );
+}
+
+/// The default size of the smallest packet to compress.
+pub const DEFAULT_COMPRESSION_THRESHOLD: usize = 4096;
+/// The default zstd compression level.
+pub const DEFAULT_ZSTD_LEVEL: i32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressionAlgorithm {
+    Snappy,
+    Zstd,
+}
+
+impl CompressionAlgorithm {
+    /// The id of the algorithm in the HELLO packet and the packet header. It
+    /// must fit in 2 bits, and 0 stands for uncompressed packets.
+    pub fn id(&self) -> u8 {
+        match self {
+            CompressionAlgorithm::Snappy => 1,
+            CompressionAlgorithm::Zstd => 2,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(CompressionAlgorithm::Snappy),
+            2 => Some(CompressionAlgorithm::Zstd),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for CompressionAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompressionAlgorithm::Snappy => write!(f, "snappy"),
+            CompressionAlgorithm::Zstd => write!(f, "zstd"),
+        }
+    }
+}
+
+impl FromStr for CompressionAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "snappy" => Ok(CompressionAlgorithm::Snappy),
+            "zstd" => Ok(CompressionAlgorithm::Zstd),
+            _ => Err(format!("unknown compression algorithm {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompressionConfig {
+    /// Supported algorithms in the order of preference. Compression is
+    /// disabled if empty.
+    pub algorithms: Vec<CompressionAlgorithm>,
+    /// Packets with smaller payload are sent uncompressed.
+    pub threshold: usize,
+    pub zstd_level: i32,
+    /// Protocols whose packets are always sent uncompressed, e.g. for
+    /// protocols whose payload is already compressed.
+    pub disabled_protocols: Vec<ProtocolId>,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        CompressionConfig {
+            algorithms: Vec::new(),
+            threshold: DEFAULT_COMPRESSION_THRESHOLD,
+            zstd_level: DEFAULT_ZSTD_LEVEL,
+            disabled_protocols: Vec::new(),
+        }
+    }
+}
+
+impl CompressionConfig {
+    pub fn is_enabled(&self) -> bool { !self.algorithms.is_empty() }
+
+    /// The ids of the supported algorithms to advertise in HELLO.
+    pub fn algorithm_ids(&self) -> Vec<u8> {
+        self.algorithms.iter().map(|a| a.id()).collect()
+    }
+
+    /// Returns the most preferred algorithm supported by the remote peer.
+    pub fn negotiate(&self, peer_ids: &[u8]) -> Option<CompressionAlgorithm> {
+        self.algorithms
+            .iter()
+            .find(|a| peer_ids.contains(&a.id()))
+            .cloned()
+    }
+
+    pub fn is_supported(&self, algorithm: CompressionAlgorithm) -> bool {
+        self.algorithms.contains(&algorithm)
+    }
+
+    /// Whether a packet of `protocol` with `len` bytes should be compressed.
+    pub fn should_compress(&self, protocol: &ProtocolId, len: usize) -> bool {
+        len >= self.threshold && !self.disabled_protocols.contains(protocol)
+    }
+
+    /// Compresses `data`, returns `None` if the result is not smaller.
+    pub fn compress(
+        &self, algorithm: CompressionAlgorithm, data: &[u8],
+    ) -> Option<Vec<u8>> {
+        let start = Instant::now();
+        let compressed = match algorithm {
+            CompressionAlgorithm::Snappy => {
+                snap::raw::Encoder::new().compress_vec(data).ok()
+            }
+            CompressionAlgorithm::Zstd => {
+                zstd::stream::encode_all(data, self.zstd_level).ok()
+            }
+        }?;
+        COMPRESS_TIME.update_since(start);
+        COMPRESS_INPUT_METER.mark(data.len());
+        COMPRESS_OUTPUT_METER.mark(compressed.len());
+        if !data.is_empty() {
+            COMPRESSION_RATIO
+                .update((compressed.len() * 100 / data.len()) as u64);
+        }
+
+        if compressed.len() < data.len() {
+            Some(compressed)
+        } else {
+            None
+        }
+    }
+}
+
+/// Decompresses `data`, failing if the result exceeds `max_size` bytes.
+pub fn decompress(
+    algorithm: CompressionAlgorithm, data: &[u8], max_size: usize,
+) -> Result<Vec<u8>, Error> {
+    let start = Instant::now();
+    let decompressed = match algorithm {
+        CompressionAlgorithm::Snappy => {
+            let len = snap::raw::decompress_len(data)
+                .map_err(|e| ErrorKind::Compression(e.to_string()))?;
+            if len > max_size {
+                bail!(ErrorKind::OversizedPacket);
+            }
+            snap::raw::Decoder::new()
+                .decompress_vec(data)
+                .map_err(|e| ErrorKind::Compression(e.to_string()))?
+        }
+        CompressionAlgorithm::Zstd => {
+            let mut decompressed = Vec::new();
+            zstd::stream::read::Decoder::new(data)?
+                .take(max_size as u64 + 1)
+                .read_to_end(&mut decompressed)
+                .map_err(|e| ErrorKind::Compression(e.to_string()))?;
+            if decompressed.len() > max_size {
+                bail!(ErrorKind::OversizedPacket);
+            }
+            decompressed
+        }
+    };
+    DECOMPRESS_TIME.update_since(start);
+    DECOMPRESS_INPUT_METER.mark(data.len());
+    DECOMPRESS_OUTPUT_METER.mark(decompressed.len());
+    Ok(decompressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decompress, CompressionAlgorithm, CompressionConfig};
+
+    fn config() -> CompressionConfig {
+        CompressionConfig {
+            algorithms: vec![
+                CompressionAlgorithm::Zstd,
+                CompressionAlgorithm::Snappy,
+            ],
+            threshold: 16,
+            disabled_protocols: vec![*b"lgt"],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_negotiate() {
+        let config = config();
+        assert_eq!(config.negotiate(&[]), None);
+        assert_eq!(config.negotiate(&[1]), Some(CompressionAlgorithm::Snappy));
+        assert_eq!(config.negotiate(&[1, 2]), Some(CompressionAlgorithm::Zstd));
+        assert_eq!(config.negotiate(&[3]), None);
+        assert_eq!(CompressionConfig::default().negotiate(&[1, 2]), None);
+    }
+
+    #[test]
+    fn test_should_compress() {
+        let config = config();
+        assert!(config.should_compress(b"cfx", 16));
+        assert!(!config.should_compress(b"cfx", 15));
+        assert!(!config.should_compress(b"lgt", 1024));
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let config = config();
+        let data = vec![7u8; 1024];
+        for algorithm in &config.algorithms {
+            let compressed = config.compress(*algorithm, &data).unwrap();
+            assert!(compressed.len() < data.len());
+            assert_eq!(
+                decompress(*algorithm, &compressed, 1024).unwrap(),
+                data
+            );
+            // Decompressed size above the limit.
+            assert!(decompress(*algorithm, &compressed, 1023).is_err());
+            assert!(decompress(*algorithm, &data[..16], 1024).is_err());
+        }
+
+        // Incompressible data is sent as is.
+        let data: Vec<u8> = (0..=255).collect();
+        assert!(config
+            .compress(CompressionAlgorithm::Snappy, &data)
+            .is_none());
+    }
+}
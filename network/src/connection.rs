@@ -3,6 +3,7 @@
 // See http://www.gnu.org/licenses/
 
 use crate::{
+    encryption::{FrameCipher, TAG_LEN},
     io::{IoContext, StreamToken},
     throttling::THROTTLING_SERVICE,
     Error, ErrorKind,
@@ -83,7 +84,7 @@ pub enum WriteStatus {
     Complete,
 }
 
-const MAX_PAYLOAD_SIZE: usize = (1 << 24) - 1;
+pub const MAX_PAYLOAD_SIZE: usize = (1 << 24) - 1;
 
 pub trait GenericSocket: Read + Write {}
 
@@ -113,6 +114,8 @@ struct Packet {
     original_is_high_priority: bool,
     throttling_size: usize,
     creation_time: Instant,
+    // whether to encrypt the data before writing to socket.
+    encrypt: bool,
 }
 
 impl Packet {
@@ -131,6 +134,7 @@ impl Packet {
             original_is_high_priority: is_high_priority,
             throttling_size,
             creation_time: Instant::now(),
+            encrypt: false,
         })
     }
 
@@ -186,6 +190,8 @@ pub struct GenericConnection<Socket: GenericSocket> {
     registered: AtomicBool,
     /// Assemble packet with extra information before sending out.
     assembler: Box<dyn PacketAssembler>,
+    /// Encrypt and decrypt packets once the session is encrypted.
+    cipher: Option<FrameCipher>,
 }
 
 impl<Socket: GenericSocket> GenericConnection<Socket> {
@@ -225,19 +231,37 @@ impl<Socket: GenericSocket> GenericConnection<Socket> {
             }
         }
 
-        let packet = self.assembler.load(&mut self.recv_buf);
+        let packet = match self.assembler.load(&mut self.recv_buf) {
+            Some(packet) => packet,
+            None => return Ok(None),
+        };
 
-        if let Some(ref p) = packet {
-            trace!(
-                "Packet received, token = {}, size = {}",
-                self.token,
-                p.len()
-            );
+        trace!(
+            "Packet received, token = {}, size = {}",
+            self.token,
+            packet.len()
+        );
+
+        match self.cipher {
+            Some(ref mut cipher) => {
+                let mut data = packet.to_vec();
+                cipher.open(&mut data).map_err(|e| {
+                    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+                })?;
+                Ok(Some(data.into()))
+            }
+            None => Ok(Some(packet.freeze())),
         }
+    }
 
-        Ok(packet.map(|p| p.freeze()))
+    /// Encrypts all the packets sent and received from now on. Packets that
+    /// are already in the send queue are sent unencrypted.
+    pub fn enable_encryption(&mut self, cipher: FrameCipher) {
+        self.cipher = Some(cipher);
     }
 
+    pub fn is_encrypted(&self) -> bool { self.cipher.is_some() }
+
     /// Send the specified data out immediately
     pub fn write_raw_data(
         &mut self, mut data: Vec<u8>,
@@ -249,6 +273,9 @@ impl<Socket: GenericSocket> GenericConnection<Socket> {
             data
         );
 
+        if let Some(ref mut cipher) = self.cipher {
+            cipher.seal(&mut data)?;
+        }
         self.assembler.assemble(&mut data)?;
         let size = self.socket.write(&data)?;
 
@@ -273,6 +300,14 @@ impl<Socket: GenericSocket> GenericConnection<Socket> {
                 None => return Ok(WriteStatus::Complete),
             };
 
+            // Packets are encrypted in the order they are written to socket,
+            // which may differ from the order they are sent.
+            if packet.encrypt {
+                if let Some(ref mut cipher) = self.cipher {
+                    cipher.seal(&mut packet.data)?;
+                }
+            }
+
             // assemble packet to send, e.g. prefix length to packet
             self.assembler.assemble(&mut packet.data)?;
 
@@ -337,13 +372,15 @@ impl<Socket: GenericSocket> GenericConnection<Socket> {
     {
         if !data.is_empty() {
             let size = data.len();
-            if self.assembler.is_oversized(size) {
+            let tag_len = if self.cipher.is_some() { TAG_LEN } else { 0 };
+            if self.assembler.is_oversized(size + tag_len) {
                 return Err(ErrorKind::OversizedPacket.into());
             }
 
             trace!("Sending packet, token = {}, size = {}", self.token, size);
 
-            let packet = Packet::new(data, priority)?;
+            let mut packet = Packet::new(data, priority)?;
+            packet.encrypt = self.cipher.is_some();
             self.send_queue.push_back(packet, priority);
 
             SEND_METER.mark(size);
@@ -386,6 +423,7 @@ impl Connection {
             interest: Ready::hup() | Ready::readable(),
             registered: AtomicBool::new(false),
             assembler: Box::new(PacketWithLenAssembler::default()),
+            cipher: None,
         }
     }
 
@@ -589,7 +627,8 @@ impl PacketAssembler for PacketWithLenAssembler {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::io::*;
+    use crate::{encryption::SessionSecrets, io::*, node_table::NodeId};
+    use cfx_types::H256;
     use mio::Ready;
     use std::{
         cmp,
@@ -674,6 +713,7 @@ mod tests {
                 interest: Ready::hup() | Ready::readable(),
                 registered: AtomicBool::new(false),
                 assembler: Box::new(PacketWithLenAssembler::new(1, None)),
+                cipher: None,
             }
         }
     }
@@ -738,6 +778,36 @@ mod tests {
         }
     }
 
+    #[test]
+    fn connection_read_encrypted() {
+        let secrets = SessionSecrets {
+            initiator_id: NodeId::random(),
+            recipient_id: NodeId::random(),
+            initiator_nonce: H256::random(),
+            recipient_nonce: H256::random(),
+        };
+        let mut sender = secrets.cipher(true);
+        let mut connection = TestConnection::new();
+        connection.enable_encryption(secrets.cipher(false));
+
+        for _ in 0..2 {
+            let mut data = vec![1, 3, 5, 7];
+            sender.seal(&mut data).unwrap();
+            connection.assembler.assemble(&mut data).unwrap();
+            connection.socket.read_buf.extend_from_slice(&data);
+            let status = connection.readable();
+            assert_eq!(&status.unwrap().unwrap()[..], &[1, 3, 5, 7]);
+        }
+
+        // tampered packet
+        let mut data = vec![1, 3, 5, 7];
+        sender.seal(&mut data).unwrap();
+        data[0] ^= 1;
+        connection.assembler.assemble(&mut data).unwrap();
+        connection.socket.read_buf.extend_from_slice(&data);
+        assert!(connection.readable().is_err());
+    }
+
     #[test]
     fn test_assembler_oversized() {
         let assembler = PacketWithLenAssembler::default();
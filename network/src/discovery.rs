@@ -5,6 +5,7 @@
 use crate::{
     hash::keccak,
     node_database::NodeDatabase,
+    node_record::{NodeRecord, SignedNodeRecord},
     node_table::{NodeId, *},
     service::{UdpIoContext, MAX_DATAGRAM_SIZE, UDP_PROTOCOL_DISCOVERY},
     DiscoveryConfiguration, Error, ErrorKind, IpFilter, ThrottlingReason,
@@ -18,7 +19,7 @@ use rlp_derive::{RlpDecodable, RlpEncodable};
 use std::{
     collections::{hash_map::Entry, HashMap, HashSet},
     net::{IpAddr, SocketAddr},
-    time::{Instant, SystemTime, UNIX_EPOCH},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 use throttling::time_window_bucket::TimeWindowBucket;
 
@@ -31,6 +32,12 @@ const PACKET_PONG: u8 = 2;
 const PACKET_FIND_NODE: u8 = 3;
 const PACKET_NEIGHBOURS: u8 = 4;
 
+// Max number of node records kept from the other nodes.
+const MAX_NODE_RECORDS: usize = 4096;
+// Max age of the node records returned. The records are received again in the
+// PING and PONG packets whenever the nodes are checked.
+const NODE_RECORD_MAX_AGE: Duration = Duration::from_secs(30 * 60);
+
 struct PingRequest {
     // Time when the request was sent
     sent_at: Instant,
@@ -80,6 +87,11 @@ pub struct Discovery {
     adding_nodes: Vec<NodeEntry>,
     ip_filter: IpFilter,
     pub disc_option: DiscoveryOption,
+    // Record of the local node, appended to PING and PONG packets
+    local_record: Option<SignedNodeRecord>,
+    // Latest records received from the other nodes, and when they were
+    // last received
+    records: HashMap<NodeId, (NodeRecord, Instant)>,
 
     // Limits the response for PING/FIND_NODE packets
     ping_throttling: TimeWindowBucket<IpAddr>,
@@ -111,6 +123,8 @@ impl Discovery {
                 general: true,
                 archive: false,
             },
+            local_record: None,
+            records: HashMap::new(),
             ping_throttling: TimeWindowBucket::new(
                 config.throttling_interval,
                 config.throttling_limit_ping,
@@ -123,6 +137,22 @@ impl Discovery {
         }
     }
 
+    /// Sets the record of the local node advertised in PING and PONG packets.
+    pub fn set_local_record(&mut self, record: SignedNodeRecord) {
+        self.local_record = Some(record);
+    }
+
+    /// Returns the latest record received from the node `id`, unless it's
+    /// older than `NODE_RECORD_MAX_AGE`.
+    pub fn node_record(&self, id: &NodeId) -> Option<&NodeRecord> {
+        self.records
+            .get(id)
+            .filter(|(_, received_at)| {
+                received_at.elapsed() <= NODE_RECORD_MAX_AGE
+            })
+            .map(|(record, _)| record)
+    }
+
     fn is_allowed(&self, entry: &NodeEntry) -> bool {
         entry.endpoint.is_allowed(&self.ip_filter) && entry.id != self.id
     }
@@ -163,11 +193,15 @@ impl Discovery {
     fn ping(
         &mut self, uio: &UdpIoContext, node: &NodeEntry,
     ) -> Result<(), Error> {
-        let mut rlp = RlpStream::new_list(4);
+        let mut rlp =
+            RlpStream::new_list(4 + self.local_record.is_some() as usize);
         rlp.append(&DISCOVER_PROTOCOL_VERSION);
         self.public_endpoint.to_rlp_list(&mut rlp);
         node.endpoint.to_rlp_list(&mut rlp);
         rlp.append(&self.config.expire_timestamp());
+        if let Some(ref record) = self.local_record {
+            rlp.append(record);
+        }
         let hash = self.send_packet(
             uio,
             PACKET_PING,
@@ -270,8 +304,10 @@ impl Discovery {
         let ping_to = NodeEndpoint::from_rlp(&rlp.at(2)?)?;
         let timestamp: u64 = rlp.val_at(3)?;
         self.check_timestamp(timestamp)?;
+        let record = decode_node_record(rlp, 4, node_id)?;
 
-        let mut response = RlpStream::new_list(3);
+        let mut response =
+            RlpStream::new_list(3 + self.local_record.is_some() as usize);
         let pong_to = NodeEndpoint {
             address: from.clone(),
             udp_port: ping_from.udp_port,
@@ -287,6 +323,9 @@ impl Discovery {
 
         response.append(&echo_hash);
         response.append(&self.config.expire_timestamp());
+        if let Some(ref record) = self.local_record {
+            response.append(record);
+        }
         self.send_packet(uio, PACKET_PONG, from, &response.drain())?;
 
         let entry = NodeEntry {
@@ -302,6 +341,10 @@ impl Discovery {
             uio.node_db
                 .write()
                 .note_success(node_id, None, false /* trusted_only */);
+            if let Some(record) = record {
+                self.update_node_record(node_id, record);
+            }
+            self.apply_node_record(uio, node_id);
         }
         Ok(())
     }
@@ -316,6 +359,7 @@ impl Discovery {
         let echo_hash: H256 = rlp.val_at(1)?;
         let timestamp: u64 = rlp.val_at(2)?;
         self.check_timestamp(timestamp)?;
+        let record = decode_node_record(rlp, 3, node_id)?;
 
         let expected_node = match self.in_flight_pings.entry(*node_id) {
             Entry::Occupied(entry) => {
@@ -339,6 +383,10 @@ impl Discovery {
 
         if let Some(node) = expected_node {
             uio.node_db.write().insert_with_conditional_promotion(node);
+            if let Some(record) = record {
+                self.update_node_record(node_id, record);
+            }
+            self.apply_node_record(uio, node_id);
             Ok(())
         } else {
             debug!("Got unexpected Pong from {:?} ; request not found", &from);
@@ -346,6 +394,44 @@ impl Discovery {
         }
     }
 
+    /// Keeps the record received from the node `id` if it is newer. If there
+    /// are too many records, the one received least recently is evicted.
+    fn update_node_record(&mut self, id: &NodeId, record: NodeRecord) {
+        match self.records.get_mut(id) {
+            Some((old, received_at)) if old.seq >= record.seq => {
+                *received_at = Instant::now();
+                return;
+            }
+            None if self.records.len() >= MAX_NODE_RECORDS => {
+                let oldest = self
+                    .records
+                    .iter()
+                    .min_by_key(|(_, (_, received_at))| *received_at)
+                    .map(|(id, _)| *id);
+                if let Some(oldest) = oldest {
+                    debug!(
+                        "Too many node records, evict record of {:#x}",
+                        oldest
+                    );
+                    self.records.remove(&oldest);
+                }
+            }
+            _ => {}
+        }
+        self.records.insert(*id, (record, Instant::now()));
+    }
+
+    /// Tags the node `id` in the node database with its record, so that it
+    /// could be sampled by tags before any session is established.
+    fn apply_node_record(&self, uio: &UdpIoContext, id: &NodeId) {
+        if let Some((record, _)) = self.records.get(id) {
+            let mut node_db = uio.node_db.write();
+            for (key, value) in record.tags() {
+                node_db.set_tag(*id, &key, &value);
+            }
+        }
+    }
+
     fn on_find_node(
         &mut self, uio: &UdpIoContext, rlp: &Rlp, _node: &NodeId,
         from: &SocketAddr,
@@ -472,6 +558,8 @@ impl Discovery {
     }
 
     fn expire_node_request(&mut self, uio: &UdpIoContext, node_id: NodeId) {
+        // The record of an unresponsive node is likely stale.
+        self.records.remove(&node_id);
         uio.node_db.write().note_failure(
             &node_id, false, /* by_connection */
             true,  /* trusted_only */
@@ -666,6 +754,23 @@ fn assemble_packet(
     Ok(packet)
 }
 
+/// Decodes the optional node record at `index` of a PING or PONG packet, and
+/// checks that it is signed by the sender `node_id`.
+fn decode_node_record(
+    rlp: &Rlp, index: usize, node_id: &NodeId,
+) -> Result<Option<NodeRecord>, Error> {
+    if rlp.item_count()? <= index {
+        return Ok(None);
+    }
+
+    let signed: SignedNodeRecord = rlp.val_at(index)?;
+    if signed.signer()? != *node_id {
+        debug!("Node record not signed by the sender {:#x}", node_id);
+        bail!(ErrorKind::BadProtocol);
+    }
+    Ok(Some(signed.record))
+}
+
 pub struct DiscoveryOption {
     // discover nodes without any tag filter
     pub general: bool,
@@ -788,3 +893,173 @@ impl NeighborsChunkMessage {
         Ok(true)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::UdpChannel;
+    use cfx_types::H512;
+    use cfxkey::{Generator, Random};
+    use parking_lot::RwLock;
+    use std::time::Duration;
+
+    struct TestNode {
+        key: KeyPair,
+        discovery: Discovery,
+        endpoint: NodeEndpoint,
+        channel: RwLock<UdpChannel>,
+        node_db: RwLock<NodeDatabase>,
+    }
+
+    impl TestNode {
+        fn new(port: u16) -> TestNode {
+            let key = Random.generate().unwrap();
+            let endpoint = NodeEndpoint {
+                address: SocketAddr::from(([127, 0, 0, 1], port)),
+                udp_port: port,
+            };
+            let config = DiscoveryConfiguration {
+                discover_node_count: 16,
+                expire_time: Duration::from_secs(20),
+                find_node_timeout: Duration::from_secs(2),
+                max_nodes_ping: 32,
+                ping_timeout: Duration::from_secs(2),
+                throttling_interval: Duration::from_secs(1),
+                throttling_limit_ping: 20,
+                throttling_limit_find_nodes: 10,
+            };
+            let discovery = Discovery::new(
+                &key,
+                endpoint.clone(),
+                IpFilter::default(),
+                config,
+            );
+            TestNode {
+                key,
+                discovery,
+                endpoint,
+                channel: RwLock::new(UdpChannel::new()),
+                node_db: RwLock::new(NodeDatabase::new(None, 0)),
+            }
+        }
+
+        fn entry(&self) -> NodeEntry {
+            NodeEntry {
+                id: *self.key.public(),
+                endpoint: self.endpoint.clone(),
+            }
+        }
+
+        fn set_record(&mut self, record: NodeRecord) {
+            let signed = record.sign(self.key.secret()).unwrap();
+            self.discovery.set_local_record(signed);
+        }
+
+        /// Delivers the packets sent by this node to `to`.
+        fn deliver(&self, to: &mut TestNode) -> Result<(), Error> {
+            while let Some(datagram) = self.channel.write().dequeue_send() {
+                let uio = UdpIoContext::new(&to.channel, &to.node_db);
+                to.discovery.on_packet(
+                    &uio,
+                    &datagram.payload[1..],
+                    self.endpoint.address,
+                )?;
+            }
+            Ok(())
+        }
+    }
+
+    fn record(seq: u64) -> NodeRecord {
+        NodeRecord {
+            seq,
+            node_type: "full".into(),
+            protocols: vec![],
+            snapshot_range: Some((2000, 6000)),
+        }
+    }
+
+    #[test]
+    fn test_ping_pong_with_records() {
+        let mut a = TestNode::new(30303);
+        let mut b = TestNode::new(30304);
+        a.set_record(record(1));
+        b.set_record(record(2));
+
+        let uio = UdpIoContext::new(&a.channel, &a.node_db);
+        a.discovery.ping(&uio, &b.entry()).unwrap();
+        a.deliver(&mut b).unwrap();
+        assert_eq!(b.discovery.node_record(a.key.public()), Some(&record(1)));
+
+        b.deliver(&mut a).unwrap();
+        assert_eq!(a.discovery.node_record(b.key.public()), Some(&record(2)));
+        {
+            let node_db = a.node_db.read();
+            let node = node_db.get(b.key.public(), false).unwrap();
+            assert_eq!(
+                node.tags.get(NODE_TAG_NODE_TYPE),
+                Some(&"full".to_string())
+            );
+        }
+
+        // A stale record received later is ignored.
+        b.set_record(record(1));
+        let uio = UdpIoContext::new(&a.channel, &a.node_db);
+        a.discovery.ping(&uio, &b.entry()).unwrap();
+        a.deliver(&mut b).unwrap();
+        b.deliver(&mut a).unwrap();
+        assert_eq!(a.discovery.node_record(b.key.public()), Some(&record(2)));
+
+        // An outdated record is not returned.
+        if let Some(received_at) =
+            Instant::now().checked_sub(NODE_RECORD_MAX_AGE * 2)
+        {
+            a.discovery.records.get_mut(b.key.public()).unwrap().1 =
+                received_at;
+            assert_eq!(a.discovery.node_record(b.key.public()), None);
+        }
+    }
+
+    #[test]
+    fn test_ping_without_record() {
+        let mut a = TestNode::new(30303);
+        let mut b = TestNode::new(30304);
+
+        let uio = UdpIoContext::new(&a.channel, &a.node_db);
+        a.discovery.ping(&uio, &b.entry()).unwrap();
+        a.deliver(&mut b).unwrap();
+        assert!(b.discovery.node_record(a.key.public()).is_none());
+    }
+
+    #[test]
+    fn test_ping_with_record_of_other_node() {
+        let mut a = TestNode::new(30303);
+        let mut b = TestNode::new(30304);
+        let other = Random.generate().unwrap();
+        a.discovery
+            .set_local_record(record(1).sign(other.secret()).unwrap());
+
+        let uio = UdpIoContext::new(&a.channel, &a.node_db);
+        a.discovery.ping(&uio, &b.entry()).unwrap();
+        assert!(a.deliver(&mut b).is_err());
+        assert!(b.discovery.node_record(a.key.public()).is_none());
+        assert!(b.discovery.node_record(other.public()).is_none());
+    }
+
+    #[test]
+    fn test_evict_oldest_record() {
+        let mut node = TestNode::new(30303);
+        for i in 0..MAX_NODE_RECORDS as u64 {
+            node.discovery
+                .update_node_record(&H512::from_low_u64_be(i), record(1));
+        }
+        let oldest = H512::from_low_u64_be(7);
+        node.discovery.records.get_mut(&oldest).unwrap().1 -=
+            Duration::from_secs(1);
+
+        let new_id = H512::from_low_u64_be(MAX_NODE_RECORDS as u64);
+        node.discovery.update_node_record(&new_id, record(1));
+        assert_eq!(node.discovery.records.len(), MAX_NODE_RECORDS);
+        assert!(node.discovery.node_record(&new_id).is_some());
+        assert!(node.discovery.node_record(&oldest).is_none());
+    }
+}
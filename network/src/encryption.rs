@@ -0,0 +1,241 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! Authenticated encryption of session frames with ChaCha20-Poly1305.
+//!
+//! The keys are derived from the nonces exchanged in the ECIES handshake,
+//! which are only known to the two peers. Note that the keys are not forward
+//! secret: a leaked node key allows to decrypt the recorded sessions of the
+//! node.
+//!
+//! Peers advertise their `EncryptionMode` in the Hello packet. Encryption is
+//! used if both peers support it and at least one of them requires it. All the
+//! frames sent after a peer has received the Hello packet of the remote peer
+//! are then encrypted, each with its sequence number as nonce.
+
+use crate::{node_table::NodeId, Error, ErrorKind};
+use cfx_types::H256;
+use chacha20poly1305::{
+    aead::{AeadInPlace, NewAead},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use keccak_hash::keccak;
+use std::str::FromStr;
+
+/// Size of the authentication tag appended to every encrypted frame.
+pub const TAG_LEN: usize = 16;
+
+/// When to encrypt the sessions with remote peers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncryptionPolicy {
+    /// Require encryption with all peers.
+    Always,
+    /// Require encryption in a consortium network, and otherwise only encrypt
+    /// if the remote peer requires it.
+    Consortium,
+    /// Never encrypt, peers that require encryption are disconnected.
+    Never,
+}
+
+impl Default for EncryptionPolicy {
+    fn default() -> Self { EncryptionPolicy::Never }
+}
+
+impl FromStr for EncryptionPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "always" => Ok(EncryptionPolicy::Always),
+            "consortium" => Ok(EncryptionPolicy::Consortium),
+            "never" => Ok(EncryptionPolicy::Never),
+            _ => Err(format!("unknown encryption policy {}", s)),
+        }
+    }
+}
+
+impl EncryptionPolicy {
+    pub fn mode(&self, is_consortium: bool) -> EncryptionMode {
+        match self {
+            EncryptionPolicy::Always => EncryptionMode::Required,
+            EncryptionPolicy::Consortium if is_consortium => {
+                EncryptionMode::Required
+            }
+            EncryptionPolicy::Consortium => EncryptionMode::Supported,
+            EncryptionPolicy::Never => EncryptionMode::Unsupported,
+        }
+    }
+}
+
+/// The encryption mode advertised in the Hello packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EncryptionMode {
+    Unsupported = 0,
+    Supported = 1,
+    Required = 2,
+}
+
+impl EncryptionMode {
+    pub fn from_u8(mode: u8) -> Self {
+        match mode {
+            0 => EncryptionMode::Unsupported,
+            1 => EncryptionMode::Supported,
+            // Treat unknown modes of newer peers as the strictest one.
+            _ => EncryptionMode::Required,
+        }
+    }
+
+    /// Whether to encrypt the session with a remote peer in mode `remote`.
+    /// Returns an error if only one of the peers requires encryption and the
+    /// other does not support it.
+    pub fn negotiate(self, remote: EncryptionMode) -> Result<bool, Error> {
+        match (self, remote) {
+            (EncryptionMode::Unsupported, EncryptionMode::Required)
+            | (EncryptionMode::Required, EncryptionMode::Unsupported) => {
+                Err(ErrorKind::Encryption(
+                    "encryption required by only one peer".into(),
+                )
+                .into())
+            }
+            (local, remote) => Ok(local.max(remote)
+                == EncryptionMode::Required
+                && local.min(remote) != EncryptionMode::Unsupported),
+        }
+    }
+}
+
+/// The secrets of a session established in the handshake.
+#[derive(Clone)]
+pub struct SessionSecrets {
+    pub initiator_id: NodeId,
+    pub recipient_id: NodeId,
+    pub initiator_nonce: H256,
+    pub recipient_nonce: H256,
+}
+
+impl SessionSecrets {
+    fn key(&self, direction: &[u8]) -> H256 {
+        let mut data = Vec::with_capacity(direction.len() + 192);
+        data.extend_from_slice(direction);
+        data.extend_from_slice(self.initiator_nonce.as_bytes());
+        data.extend_from_slice(self.recipient_nonce.as_bytes());
+        data.extend_from_slice(self.initiator_id.as_bytes());
+        data.extend_from_slice(self.recipient_id.as_bytes());
+        keccak(&data)
+    }
+
+    /// Creates the cipher for the initiator of the session if `originated`,
+    /// otherwise for the recipient.
+    pub fn cipher(&self, originated: bool) -> FrameCipher {
+        let initiator_key = self.key(b"conflux initiator");
+        let recipient_key = self.key(b"conflux recipient");
+        if originated {
+            FrameCipher::new(&initiator_key, &recipient_key)
+        } else {
+            FrameCipher::new(&recipient_key, &initiator_key)
+        }
+    }
+}
+
+/// Encrypts the sent frames and decrypts the received frames of a session.
+pub struct FrameCipher {
+    sealer: ChaCha20Poly1305,
+    opener: ChaCha20Poly1305,
+    sent: u64,
+    received: u64,
+}
+
+impl FrameCipher {
+    fn new(send_key: &H256, recv_key: &H256) -> Self {
+        FrameCipher {
+            sealer: ChaCha20Poly1305::new(Key::from_slice(send_key.as_bytes())),
+            opener: ChaCha20Poly1305::new(Key::from_slice(recv_key.as_bytes())),
+            sent: 0,
+            received: 0,
+        }
+    }
+
+    fn nonce(sequence: u64) -> [u8; 12] {
+        let mut nonce = [0u8; 12];
+        nonce[4..].copy_from_slice(&sequence.to_le_bytes());
+        nonce
+    }
+
+    /// Encrypts the next frame to send in place, and appends the tag.
+    pub fn seal(&mut self, data: &mut Vec<u8>) -> Result<(), Error> {
+        let nonce = Self::nonce(self.sent);
+        self.sealer
+            .encrypt_in_place(Nonce::from_slice(&nonce), &[], data)
+            .map_err(|_| ErrorKind::Encryption("failed to seal".into()))?;
+        self.sent += 1;
+        Ok(())
+    }
+
+    /// Decrypts the next received frame in place, and removes the tag.
+    pub fn open(&mut self, data: &mut Vec<u8>) -> Result<(), Error> {
+        let nonce = Self::nonce(self.received);
+        self.opener
+            .decrypt_in_place(Nonce::from_slice(&nonce), &[], data)
+            .map_err(|_| {
+                ErrorKind::Encryption("failed to authenticate frame".into())
+            })?;
+        self.received += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EncryptionMode::*, EncryptionPolicy, SessionSecrets, TAG_LEN};
+    use crate::node_table::NodeId;
+    use cfx_types::H256;
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(Unsupported.negotiate(Unsupported).unwrap(), false);
+        assert_eq!(Unsupported.negotiate(Supported).unwrap(), false);
+        assert_eq!(Supported.negotiate(Supported).unwrap(), false);
+        assert_eq!(Supported.negotiate(Required).unwrap(), true);
+        assert_eq!(Required.negotiate(Supported).unwrap(), true);
+        assert_eq!(Required.negotiate(Required).unwrap(), true);
+        assert!(Required.negotiate(Unsupported).is_err());
+        assert!(Unsupported.negotiate(Required).is_err());
+
+        assert_eq!(EncryptionPolicy::Consortium.mode(false), Supported);
+        assert_eq!(EncryptionPolicy::Consortium.mode(true), Required);
+    }
+
+    #[test]
+    fn test_seal_open() {
+        let secrets = SessionSecrets {
+            initiator_id: NodeId::random(),
+            recipient_id: NodeId::random(),
+            initiator_nonce: H256::random(),
+            recipient_nonce: H256::random(),
+        };
+        let mut initiator = secrets.cipher(true);
+        let mut recipient = secrets.cipher(false);
+
+        for i in 0..3u8 {
+            let mut frame = vec![i; 100];
+            initiator.seal(&mut frame).unwrap();
+            assert_eq!(frame.len(), 100 + TAG_LEN);
+            assert_ne!(&frame[..100], &[i; 100][..]);
+            recipient.open(&mut frame).unwrap();
+            assert_eq!(frame, vec![i; 100]);
+        }
+
+        // Each direction has its own key.
+        let mut frame = vec![1; 10];
+        recipient.seal(&mut frame).unwrap();
+        let mut replayed = frame.clone();
+        initiator.open(&mut frame).unwrap();
+        // Replayed or tampered frames are rejected.
+        assert!(initiator.open(&mut replayed).is_err());
+        let mut frame = vec![1; 10];
+        recipient.seal(&mut frame).unwrap();
+        frame[0] ^= 1;
+        assert!(initiator.open(&mut frame).is_err());
+    }
+}
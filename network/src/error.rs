@@ -93,6 +93,7 @@ pub enum ThrottlingReason {
     QueueFull,
     Throttled,
     PacketThrottled(&'static str),
+    BandwidthExceeded(ProtocolId),
 }
 
 impl fmt::Display for ThrottlingReason {
@@ -106,6 +107,11 @@ impl fmt::Display for ThrottlingReason {
                 let msg = format!("packet {} throttled", name);
                 f.write_str(msg.as_str())
             }
+            ThrottlingReason::BandwidthExceeded(protocol) => write!(
+                f,
+                "egress bandwidth of protocol {} exceeded",
+                String::from_utf8_lossy(&protocol)
+            ),
         }
     }
 }
@@ -149,6 +155,16 @@ error_chain! {
             display("Decoder error"),
         }
 
+        Compression(reason: String) {
+            description("Compression error"),
+            display("Compression error: {}", reason),
+        }
+
+        Encryption(reason: String) {
+            description("Encryption error"),
+            display("Encryption error: {}", reason),
+        }
+
         Expired {
             description("Expired message"),
             display("Expired message"),
@@ -19,8 +19,8 @@
 // See http://www.gnu.org/licenses/
 
 use crate::{
-    connection::Connection, node_table::NodeId, service::HostMetadata, Error,
-    ErrorKind,
+    connection::Connection, encryption::SessionSecrets, node_table::NodeId,
+    service::HostMetadata, Error, ErrorKind,
 };
 use cfx_types::{Public, H256};
 use io::{IoContext, StreamToken};
@@ -64,6 +64,8 @@ pub struct Handshake {
     state: HandshakeState,
     /// nonce for verification
     nonce: H256,
+    /// nonce of the remote node, zero if cryptography is bypassed
+    remote_nonce: H256,
 }
 
 impl Handshake {
@@ -76,6 +78,7 @@ impl Handshake {
             connection: Connection::new(token, socket),
             state: HandshakeState::New,
             nonce: H256::random(),
+            remote_nonce: H256::zero(),
         }
     }
 
@@ -98,6 +101,29 @@ impl Handshake {
     /// Check if handshake is complete
     pub fn done(&self) -> bool { self.state == HandshakeState::StartSession }
 
+    /// Returns the secrets shared with the remote node after the handshake is
+    /// complete, or `None` if the cryptography is bypassed.
+    pub fn secrets(
+        &self, originated: bool, self_id: &NodeId,
+    ) -> Option<SessionSecrets> {
+        if !self.done() || self.remote_nonce.is_zero() {
+            return None;
+        }
+
+        let (initiator, recipient) = if originated {
+            ((*self_id, self.nonce), (self.id, self.remote_nonce))
+        } else {
+            ((self.id, self.remote_nonce), (*self_id, self.nonce))
+        };
+
+        Some(SessionSecrets {
+            initiator_id: initiator.0,
+            recipient_id: recipient.0,
+            initiator_nonce: initiator.1,
+            recipient_nonce: recipient.1,
+        })
+    }
+
     /// Readable IO handler. Drives the state change.
     pub fn readable<Message>(
         &mut self, io: &IoContext<Message>, host: &HostMetadata,
@@ -189,6 +215,7 @@ impl Handshake {
 
         let (remote_public, remote_nonce) = auth.split_at(NodeId::len_bytes());
         self.id.assign_from_slice(remote_public);
+        self.remote_nonce.assign_from_slice(remote_nonce);
 
         self.write_ack_of_auth(io, remote_nonce)
     }
@@ -264,6 +291,7 @@ impl Handshake {
             debug!("failed to read ack of auth, nonce mismatch");
             return Err(ErrorKind::BadProtocol.into());
         }
+        self.remote_nonce.assign_from_slice(remote_nonce);
 
         self.write_ack_of_ack(io, remote_nonce)
     }
@@ -37,23 +37,38 @@ pub type ProtocolId = [u8; PROTOCOL_ID_SIZE];
 pub type HandlerWorkType = u8;
 pub type PeerId = usize;
 
+pub mod bandwidth;
+pub mod compression;
 mod connection;
 mod discovery;
+pub mod encryption;
 mod error;
 mod handshake;
 mod ip;
 mod ip_utils;
+pub mod membership;
 mod node_database;
+pub mod node_record;
 pub mod node_table;
+pub mod peer_group;
+pub mod reputation;
 pub mod service;
 mod session;
 mod session_manager;
 pub mod throttling;
 
 pub use crate::{
+    bandwidth::{BandwidthConfig, ProtocolBandwidth},
+    compression::{CompressionAlgorithm, CompressionConfig},
+    encryption::EncryptionPolicy,
     error::{DisconnectReason, Error, ErrorKind, ThrottlingReason},
     ip::SessionIpLimitConfig,
+    membership::{MemberInfo, MembershipConfig, MembershipSource},
+    node_database::BannedNode,
+    node_record::NodeRecord,
     node_table::Node,
+    peer_group::{PeerGroupConfig, PeerGroupInfo},
+    reputation::{Misbehavior, PeerScore, ReputationConfig},
     service::NetworkService,
     session::SessionDetails,
 };
@@ -85,6 +100,9 @@ use std::{
 pub const NODE_TAG_NODE_TYPE: &str = "node_type";
 pub const NODE_TAG_ARCHIVE: &str = "archive";
 pub const NODE_TAG_FULL: &str = "full";
+pub const NODE_TAG_LIGHT: &str = "light";
+/// Prefix of the tag keys of protocol versions, followed by the protocol id.
+pub const NODE_TAG_PROTOCOL_PREFIX: &str = "protocol_";
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct NetworkConfiguration {
@@ -116,7 +134,8 @@ pub struct NetworkConfiguration {
     pub max_incoming_peers: usize,
     /// Maximum number of ongoing handshakes
     pub max_handshakes: usize,
-    /// List of reserved node addresses.
+    /// List of reserved node addresses. The reserved nodes are static peers
+    /// in the `reserved` group.
     pub reserved_nodes: Vec<String>,
     /// IP filter
     pub ip_filter: IpFilter,
@@ -138,6 +157,18 @@ pub struct NetworkConfiguration {
     /// Maximum number of P2P nodes for subnet B (ip/16).
     pub subnet_quota: usize,
     pub session_ip_limit_config: SessionIpLimitConfig,
+    /// Compression of protocol packets.
+    pub compression: CompressionConfig,
+    /// Egress limits of protocol packets per peer.
+    pub bandwidth: BandwidthConfig,
+    /// When to encrypt the sessions with peers.
+    pub encryption_policy: EncryptionPolicy,
+    /// Allow-list of peers in consortium networks.
+    pub membership: MembershipConfig,
+    /// Scoring of misbehaving peers.
+    pub reputation: ReputationConfig,
+    /// Static peers with guaranteed session slots.
+    pub peer_groups: PeerGroupConfig,
 
     pub discovery_config: DiscoveryConfiguration,
 }
@@ -175,6 +206,12 @@ impl NetworkConfiguration {
             test_mode: false,
             subnet_quota: 32,
             session_ip_limit_config: SessionIpLimitConfig::default(),
+            compression: CompressionConfig::default(),
+            bandwidth: BandwidthConfig::default(),
+            encryption_policy: EncryptionPolicy::default(),
+            membership: MembershipConfig::default(),
+            reputation: ReputationConfig::default(),
+            peer_groups: PeerGroupConfig::default(),
             discovery_config,
         }
     }
@@ -309,6 +346,12 @@ pub trait NetworkContext {
         &self, node_id: &NodeId, op: Option<UpdateNodeOperation>, reason: &str,
     );
 
+    /// Report a misbehavior of the peer, which lowers its reputation score.
+    /// The peer is disconnected or banned if the score drops too low.
+    fn report_peer(
+        &self, node_id: &NodeId, misbehavior: Misbehavior, reason: &str,
+    );
+
     /// Register a new IO timer. 'IoHandler::timeout' will be called with the
     /// token.
     fn register_timer(
@@ -319,6 +362,15 @@ pub trait NetworkContext {
 
     fn insert_peer_node_tag(&self, peer: NodeId, key: &str, value: &str);
 
+    /// Set the node type advertised in the local node record.
+    fn set_local_node_type(&self, node_type: &str);
+
+    /// Set the range of snapshot heights advertised in the local node record.
+    fn set_local_snapshot_range(&self, range: Option<(u64, u64)>);
+
+    /// Return the record advertised by the peer in discovery, if any.
+    fn get_peer_node_record(&self, peer: &NodeId) -> Option<NodeRecord>;
+
     fn is_peer_self(&self, _node_id: &NodeId) -> bool;
 
     fn self_node_id(&self) -> NodeId;
@@ -0,0 +1,301 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! Permissioned peer membership of consortium networks.
+//!
+//! If enabled, sessions are only established with the nodes on the allow-list.
+//! The allow-list is the union of the members from several sources: a local
+//! file, an on-chain registry contract and the admin RPC. Each source can be
+//! updated at runtime, and the sessions with the nodes that are no longer
+//! members are disconnected.
+//!
+//! A fresh node cannot read the registry before it syncs the state from other
+//! members. If the members are only from the registry, the boot nodes are
+//! allowed until the registry lists any member.
+
+use crate::node_table::{Node, NodeEntry, NodeId};
+use parking_lot::RwLock;
+use serde_derive::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+/// The default interval to reload the members file.
+pub const DEFAULT_MEMBERS_FILE_REFRESH_TIMEOUT: Duration =
+    Duration::from_secs(10);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MembershipConfig {
+    /// Only accept sessions with members. It requires `is_consortium`.
+    pub enabled: bool,
+    /// File of members, one node id or node url per line.
+    pub members_file: Option<PathBuf>,
+    /// Interval to check the members file for changes.
+    pub members_file_refresh_timeout: Duration,
+    /// Allow the boot nodes until the registry lists any member.
+    pub allow_boot_nodes_until_registry: bool,
+}
+
+impl Default for MembershipConfig {
+    fn default() -> Self {
+        MembershipConfig {
+            enabled: false,
+            members_file: None,
+            members_file_refresh_timeout: DEFAULT_MEMBERS_FILE_REFRESH_TIMEOUT,
+            allow_boot_nodes_until_registry: false,
+        }
+    }
+}
+
+/// Where a member is from.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum MembershipSource {
+    /// The members file.
+    File,
+    /// The on-chain registry contract.
+    Registry,
+    /// The admin RPC.
+    Admin,
+    /// The boot nodes, allowed until the registry lists any member.
+    BootNodes,
+}
+
+/// A member and the sources that list it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemberInfo {
+    pub node_id: NodeId,
+    pub sources: Vec<MembershipSource>,
+}
+
+pub struct Membership {
+    enabled: bool,
+    members: RwLock<HashMap<MembershipSource, HashSet<NodeId>>>,
+    /// Modification time of the members file when last loaded.
+    file_modified: RwLock<Option<SystemTime>>,
+}
+
+impl Membership {
+    pub fn new(enabled: bool) -> Self {
+        Membership {
+            enabled,
+            members: Default::default(),
+            file_modified: Default::default(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool { self.enabled }
+
+    /// Whether sessions are allowed with the node `id`. Always true if the
+    /// membership is not enabled.
+    pub fn is_allowed(&self, id: &NodeId) -> bool {
+        !self.enabled
+            || self.members.read().values().any(|ids| ids.contains(id))
+    }
+
+    /// Replaces the members from `source`, and returns the nodes that are no
+    /// longer members of any source. The boot nodes are no longer allowed
+    /// once the registry lists any member.
+    pub fn set_members(
+        &self, source: MembershipSource, ids: HashSet<NodeId>,
+    ) -> Vec<NodeId> {
+        let mut members = self.members.write();
+        let registry_ready =
+            source == MembershipSource::Registry && !ids.is_empty();
+        let mut old = members.insert(source, ids).unwrap_or_default();
+        if registry_ready {
+            if let Some(boot_nodes) =
+                members.remove(&MembershipSource::BootNodes)
+            {
+                old.extend(boot_nodes);
+            }
+        }
+        old.into_iter()
+            .filter(|id| !members.values().any(|ids| ids.contains(id)))
+            .collect()
+    }
+
+    /// Adds a member from `source`, returns false if already added.
+    pub fn add_member(&self, source: MembershipSource, id: NodeId) -> bool {
+        self.members.write().entry(source).or_default().insert(id)
+    }
+
+    /// Removes a member from `source`, and returns whether the node is no
+    /// longer a member of any source.
+    pub fn remove_member(&self, source: MembershipSource, id: &NodeId) -> bool {
+        let mut members = self.members.write();
+        let removed =
+            members.get_mut(&source).map_or(false, |ids| ids.remove(id));
+        removed && !members.values().any(|ids| ids.contains(id))
+    }
+
+    /// Returns all the members, ordered by node id.
+    pub fn members(&self) -> Vec<MemberInfo> {
+        let mut members: BTreeMap<NodeId, Vec<MembershipSource>> =
+            BTreeMap::new();
+        for (source, ids) in self.members.read().iter() {
+            for id in ids {
+                members.entry(*id).or_default().push(*source);
+            }
+        }
+        members
+            .into_iter()
+            .map(|(node_id, mut sources)| {
+                sources.sort();
+                MemberInfo { node_id, sources }
+            })
+            .collect()
+    }
+
+    /// Reloads the members file if it is modified since last loaded. Returns
+    /// the nodes that are no longer members and the endpoints of the members
+    /// listed as node urls, or `None` if the file is not modified.
+    pub fn reload_file(
+        &self, path: &Path,
+    ) -> Result<Option<(Vec<NodeId>, Vec<NodeEntry>)>, String> {
+        let modified = fs::metadata(path)
+            .and_then(|m| m.modified())
+            .map_err(|e| format!("failed to read {:?}: {}", path, e))?;
+        if *self.file_modified.read() == Some(modified) {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read {:?}: {}", path, e))?;
+        let (ids, endpoints) = parse_members(&content)?;
+        *self.file_modified.write() = Some(modified);
+        Ok(Some((
+            self.set_members(MembershipSource::File, ids),
+            endpoints,
+        )))
+    }
+}
+
+/// Parses the content of a members file. Each line is either a node id in hex
+/// or a node url `cfxnode://ID@IP:PORT`. Empty lines and lines starting with
+/// `#` are ignored.
+pub fn parse_members(
+    content: &str,
+) -> Result<(HashSet<NodeId>, Vec<NodeEntry>), String> {
+    let mut ids = HashSet::new();
+    let mut endpoints = Vec::new();
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if line.starts_with("cfxnode://") {
+            let node: Node = line
+                .parse()
+                .map_err(|e| format!("invalid node url {}: {}", line, e))?;
+            ids.insert(node.id);
+            endpoints.push(NodeEntry {
+                id: node.id,
+                endpoint: node.endpoint,
+            });
+        } else {
+            let id: NodeId = line
+                .trim_start_matches("0x")
+                .parse()
+                .map_err(|_| format!("invalid node id {}", line))?;
+            ids.insert(id);
+        }
+    }
+    Ok((ids, endpoints))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_members, Membership, MembershipSource::*};
+    use crate::node_table::NodeId;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_membership() {
+        let (a, b, c) = (NodeId::random(), NodeId::random(), NodeId::random());
+
+        let membership = Membership::new(false);
+        assert!(membership.is_allowed(&a));
+
+        let membership = Membership::new(true);
+        assert!(!membership.is_allowed(&a));
+
+        let ids: HashSet<NodeId> = vec![a, b].into_iter().collect();
+        assert!(membership.set_members(File, ids).is_empty());
+        assert!(membership.add_member(Admin, b));
+        assert!(!membership.add_member(Admin, b));
+        assert!(membership.is_allowed(&a));
+        assert!(membership.is_allowed(&b));
+        assert!(!membership.is_allowed(&c));
+
+        // b is still a member from the admin source.
+        let ids: HashSet<NodeId> = vec![c].into_iter().collect();
+        assert_eq!(membership.set_members(File, ids), vec![a]);
+        assert!(!membership.is_allowed(&a));
+        assert!(membership.is_allowed(&b));
+
+        assert!(!membership.remove_member(Admin, &c));
+        assert!(membership.remove_member(Admin, &b));
+        assert!(!membership.is_allowed(&b));
+
+        let members = membership.members();
+        assert_eq!(members.len(), 1);
+        assert_eq!(members[0].node_id, c);
+        assert_eq!(members[0].sources, vec![File]);
+    }
+
+    #[test]
+    fn test_boot_nodes_until_registry() {
+        let (a, b, c) = (NodeId::random(), NodeId::random(), NodeId::random());
+        let membership = Membership::new(true);
+        let ids: HashSet<NodeId> = vec![a, b].into_iter().collect();
+        assert!(membership.set_members(BootNodes, ids).is_empty());
+        assert!(membership.is_allowed(&a));
+
+        // Nothing is read from the registry before the state is synced.
+        assert!(membership.set_members(Registry, HashSet::new()).is_empty());
+        assert!(membership.is_allowed(&a));
+        assert!(membership.is_allowed(&b));
+
+        // b stays as a member of the registry.
+        let ids: HashSet<NodeId> = vec![b, c].into_iter().collect();
+        assert_eq!(membership.set_members(Registry, ids), vec![a]);
+        assert!(!membership.is_allowed(&a));
+        assert!(membership.is_allowed(&b));
+        assert!(membership.is_allowed(&c));
+
+        let members = membership.members();
+        assert!(members.iter().all(|m| m.sources == vec![Registry]));
+    }
+
+    #[test]
+    fn test_parse_members() {
+        let id = NodeId::random();
+        let content = format!(
+            "# members\n\n{:x}\n0x{:x}\ncfxnode://{:x}@127.0.0.1:32323\n",
+            id,
+            NodeId::repeat_byte(1),
+            NodeId::repeat_byte(2),
+        );
+        let (ids, endpoints) = parse_members(&content).unwrap();
+        assert_eq!(ids.len(), 3);
+        assert!(ids.contains(&id));
+        assert!(ids.contains(&NodeId::repeat_byte(1)));
+        assert_eq!(endpoints.len(), 1);
+        assert_eq!(endpoints[0].id, NodeId::repeat_byte(2));
+        assert_eq!(
+            endpoints[0].endpoint.address,
+            "127.0.0.1:32323".parse().unwrap()
+        );
+
+        assert!(parse_members("0x1234").is_err());
+        assert!(parse_members("cfxnode://1234@127.0.0.1:32323").is_err());
+    }
+}
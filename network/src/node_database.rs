@@ -8,11 +8,29 @@ use crate::{
     IpFilter,
 };
 use io::StreamToken;
-use std::{collections::HashSet, net::IpAddr, time::Duration};
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    net::IpAddr,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 const TRUSTED_NODES_FILE: &str = "trusted_nodes.json";
 const UNTRUSTED_NODES_FILE: &str = "untrusted_nodes.json";
 const BLACKLISTED_NODES_FILE: &str = "blacklisted_nodes.json";
+const BANNED_NODES_FILE: &str = "banned_nodes.json";
+
+/// A node that is refused to connect until the ban expires.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BannedNode {
+    pub node_id: NodeId,
+    /// Unix timestamp in seconds when the ban expires.
+    pub until: u64,
+    pub reason: String,
+}
 
 /// Node database maintains all P2P nodes in trusted and untrusted node tables,
 /// and supports to limit the number of nodes for the same IP address.
@@ -52,6 +70,13 @@ const BLACKLISTED_NODES_FILE: &str = "blacklisted_nodes.json";
 /// ## Demote
 /// Demote a node to untrusted when failed to handle protocol messages.
 ///
+/// # Ban
+///
+/// A node is banned for a while when its reputation score drops too low, or
+/// explicitly via RPC. Banned nodes are demoted and refused to connect until
+/// the ban expires. Different from blacklisted nodes, bans are persisted at
+/// once, so that they survive a restart.
+///
 /// # Subnet limitation
 ///
 /// Attacker could easily simulate a large amount of malicious nodes of
@@ -92,6 +117,10 @@ pub struct NodeDatabase {
     // Maximum duration to blacklist a node since last contact.
     blacklisted_lifetime: Duration,
 
+    // Banned nodes with the expiry time, and the file to persist them.
+    banned_nodes: HashMap<NodeId, BannedNode>,
+    banned_nodes_path: Option<PathBuf>,
+
     // IP address/subnet index for trusted and untrusted nodes.
     ip_limit: NodeIpLimit,
 
@@ -112,18 +141,24 @@ impl NodeDatabase {
         let ip_limit = NodeIpLimit::new(subnet_quota);
         let trusted_node_tag_index =
             NodeTagIndex::new_with_node_table(&trusted_nodes);
+        let banned_nodes_path = path
+            .as_ref()
+            .map(|dir| PathBuf::from(dir).join(BANNED_NODES_FILE));
 
         let mut db = NodeDatabase {
             trusted_nodes,
             untrusted_nodes,
             blacklisted_nodes: NodeTable::new(path, BLACKLISTED_NODES_FILE),
             blacklisted_lifetime: Duration::from_secs(7 * 24 * 3600),
+            banned_nodes: HashMap::new(),
+            banned_nodes_path,
             ip_limit,
             trusted_node_tag_index,
         };
 
         db.init(false /* trusted */);
         db.init(true /* trusted */);
+        db.load_banned_nodes();
 
         db
     }
@@ -400,6 +435,10 @@ impl NodeDatabase {
         self.untrusted_nodes.clear_useless();
 
         self.blacklisted_nodes.save();
+
+        let now = unix_now();
+        self.banned_nodes.retain(|_, node| node.until > now);
+        self.save_banned_nodes();
     }
 
     /// Promote untrusted nodes to trusted with the given duration.
@@ -610,6 +649,108 @@ impl NodeDatabase {
 
         true
     }
+
+    /// Ban the specified node for `duration`, and demote it if it is
+    /// trusted. The bans are persisted at once.
+    pub fn ban(&mut self, id: &NodeId, duration: Duration, reason: &str) {
+        self.demote(id);
+        self.note_demoted(id, false /* by_connection */);
+
+        let node = BannedNode {
+            node_id: *id,
+            until: unix_now().saturating_add(duration.as_secs()),
+            reason: reason.into(),
+        };
+        self.banned_nodes.insert(*id, node);
+        self.save_banned_nodes();
+    }
+
+    /// Lift the ban of the specified node, returns false if not banned.
+    pub fn unban(&mut self, id: &NodeId) -> bool {
+        if self.banned_nodes.remove(id).is_none() {
+            return false;
+        }
+
+        self.save_banned_nodes();
+        true
+    }
+
+    /// Check if the specified node is banned and the ban is not expired.
+    pub fn is_banned(&self, id: &NodeId) -> bool {
+        let now = unix_now();
+        self.banned_nodes
+            .get(id)
+            .map_or(false, |node| node.until > now)
+    }
+
+    /// Returns the banned nodes whose ban is not expired, ordered by the
+    /// expiry time.
+    pub fn banned_nodes(&self) -> Vec<BannedNode> {
+        let now = unix_now();
+        let mut nodes: Vec<BannedNode> = self
+            .banned_nodes
+            .values()
+            .filter(|node| node.until > now)
+            .cloned()
+            .collect();
+        nodes.sort_by_key(|node| (node.until, node.node_id));
+        nodes
+    }
+
+    fn load_banned_nodes(&mut self) {
+        let path = match self.banned_nodes_path {
+            Some(ref path) => path,
+            None => return,
+        };
+
+        let file = match fs::File::open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                debug!("banned nodes file not found: {:?}", e);
+                return;
+            }
+        };
+
+        match serde_json::from_reader::<_, Vec<BannedNode>>(file) {
+            Ok(nodes) => {
+                for node in nodes {
+                    self.banned_nodes.insert(node.node_id, node);
+                }
+            }
+            Err(e) => warn!("Error reading banned nodes file: {:?}", e),
+        }
+    }
+
+    fn save_banned_nodes(&self) {
+        let path = match self.banned_nodes_path {
+            Some(ref path) => path,
+            None => return,
+        };
+
+        if let Some(dir) = path.parent() {
+            if let Err(e) = fs::create_dir_all(dir) {
+                warn!("Error creating banned nodes directory: {:?}", e);
+                return;
+            }
+        }
+
+        let nodes = self.banned_nodes();
+        match fs::File::create(path) {
+            Ok(file) => {
+                if let Err(e) = serde_json::to_writer_pretty(file, &nodes) {
+                    warn!("Error writing banned nodes file: {:?}", e);
+                }
+            }
+            Err(e) => warn!("Error creating banned nodes file: {:?}", e),
+        }
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
 }
 
 #[cfg(test)]
@@ -797,4 +938,50 @@ mod tests {
         assert_eq!(db.evaluate_blacklisted(&n.id), false);
         assert_eq!(db.get(&n.id, false), None);
     }
+
+    #[test]
+    fn test_ban() {
+        let mut db = NodeDatabase::new(None, 2);
+
+        let n = new_entry("127.0.0.1:999");
+        db.insert_trusted(n.clone());
+        assert_eq!(db.is_banned(&n.id), false);
+
+        // banned node is demoted to untrusted
+        db.ban(&n.id, Duration::from_secs(60), "invalid block");
+        assert_eq!(db.is_banned(&n.id), true);
+        assert_eq!(db.get(&n.id, true /* trusted_only */), None);
+        assert!(db.get(&n.id, false /* trusted_only */).is_some());
+
+        let banned = db.banned_nodes();
+        assert_eq!(banned.len(), 1);
+        assert_eq!(banned[0].node_id, n.id);
+        assert_eq!(banned[0].reason, "invalid block");
+
+        assert_eq!(db.unban(&n.id), true);
+        assert_eq!(db.unban(&n.id), false);
+        assert_eq!(db.is_banned(&n.id), false);
+
+        // expired ban
+        db.ban(&n.id, Duration::from_secs(0), "timeout");
+        assert_eq!(db.is_banned(&n.id), false);
+        assert!(db.banned_nodes().is_empty());
+    }
+
+    #[test]
+    fn test_ban_persisted() {
+        let dir = std::env::temp_dir().join(format!(
+            "node_database_{:x}",
+            NodeId::random().to_low_u64_be()
+        ));
+        let path = Some(dir.to_str().unwrap().to_string());
+        let id = NodeId::random();
+
+        let mut db = NodeDatabase::new(path.clone(), 2);
+        db.ban(&id, Duration::from_secs(60), "timeout");
+        drop(db);
+
+        assert_eq!(NodeDatabase::new(path, 2).is_banned(&id), true);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
@@ -0,0 +1,221 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! Signed node records advertised in discovery.
+//!
+//! A node record describes what a node serves: its node type, the protocols
+//! it supports and the range of snapshots it keeps for sync. The record is
+//! signed with the node key and carried as an optional trailing item of the
+//! PING and PONG packets, which is ignored by older nodes. The sequence number
+//! is increased whenever the record changes, so that stale records received
+//! out of order are not applied.
+
+use crate::{
+    hash::keccak, node_table::NodeId, Error, ProtocolInfo, NODE_TAG_NODE_TYPE,
+    NODE_TAG_PROTOCOL_PREFIX,
+};
+use cfx_types::H520;
+use keylib::{recover, sign, Secret};
+use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
+use serde_derive::Serialize;
+
+/// Max length of the node type in a record.
+pub const MAX_NODE_TYPE_LENGTH: usize = 16;
+/// Max number of protocols in a record.
+pub const MAX_RECORD_PROTOCOLS: usize = 16;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeRecord {
+    /// Sequence number, increased whenever the record changes.
+    pub seq: u64,
+    /// Node type, e.g. `archive`, `full` or `light`. Empty if unknown.
+    pub node_type: String,
+    /// Protocols and their versions supported by the node.
+    pub protocols: Vec<ProtocolInfo>,
+    /// Lowest and highest epoch heights of the snapshots served for sync.
+    pub snapshot_range: Option<(u64, u64)>,
+}
+
+impl NodeRecord {
+    /// Returns the node tags derived from the record, i.e. the node type and
+    /// the version of each protocol.
+    pub fn tags(&self) -> Vec<(String, String)> {
+        let mut tags = Vec::with_capacity(self.protocols.len() + 1);
+        if !self.node_type.is_empty() {
+            tags.push((NODE_TAG_NODE_TYPE.into(), self.node_type.clone()));
+        }
+        for protocol in &self.protocols {
+            tags.push((
+                format!(
+                    "{}{}",
+                    NODE_TAG_PROTOCOL_PREFIX,
+                    String::from_utf8_lossy(&protocol.protocol)
+                ),
+                protocol.version.to_string(),
+            ));
+        }
+        tags
+    }
+
+    pub fn sign(self, secret: &Secret) -> Result<SignedNodeRecord, Error> {
+        let signature = sign(secret, &keccak(self.rlp_bytes()))?;
+        Ok(SignedNodeRecord {
+            record: self,
+            signature: H520::from_slice(&signature[..]),
+        })
+    }
+}
+
+impl Encodable for NodeRecord {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(4)
+            .append(&self.seq)
+            .append(&self.node_type)
+            .append_list(&self.protocols);
+        match self.snapshot_range {
+            Some((low, high)) => {
+                s.begin_list(2).append(&low).append(&high);
+            }
+            None => {
+                s.begin_list(0);
+            }
+        }
+    }
+}
+
+impl Decodable for NodeRecord {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 4 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+
+        let node_type: String = rlp.val_at(1)?;
+        if node_type.len() > MAX_NODE_TYPE_LENGTH {
+            return Err(DecoderError::Custom("Node type too long"));
+        }
+
+        let protocols = rlp.at(2)?;
+        if protocols.item_count()? > MAX_RECORD_PROTOCOLS {
+            return Err(DecoderError::Custom("Too many protocols"));
+        }
+
+        let range = rlp.at(3)?;
+        let snapshot_range = match range.item_count()? {
+            0 => None,
+            2 => {
+                let (low, high): (u64, u64) =
+                    (range.val_at(0)?, range.val_at(1)?);
+                if low > high {
+                    return Err(DecoderError::Custom("Invalid snapshot range"));
+                }
+                Some((low, high))
+            }
+            _ => return Err(DecoderError::RlpIncorrectListLen),
+        };
+
+        Ok(NodeRecord {
+            seq: rlp.val_at(0)?,
+            node_type,
+            protocols: protocols.as_list()?,
+            snapshot_range,
+        })
+    }
+}
+
+/// A node record and the signature of its rlp by the node key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignedNodeRecord {
+    pub record: NodeRecord,
+    pub signature: H520,
+}
+
+impl SignedNodeRecord {
+    /// Returns the id of the node that signed the record.
+    pub fn signer(&self) -> Result<NodeId, Error> {
+        let hash = keccak(self.record.rlp_bytes());
+        Ok(recover(&self.signature.into(), &hash)?)
+    }
+}
+
+impl Encodable for SignedNodeRecord {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(2).append(&self.record).append(&self.signature);
+    }
+}
+
+impl Decodable for SignedNodeRecord {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 2 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+
+        Ok(SignedNodeRecord {
+            record: rlp.val_at(0)?,
+            signature: rlp.val_at(1)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NodeRecord, SignedNodeRecord, MAX_RECORD_PROTOCOLS};
+    use crate::{service::ProtocolVersion, ProtocolInfo};
+    use keylib::{Generator, Random};
+
+    fn record() -> NodeRecord {
+        NodeRecord {
+            seq: 3,
+            node_type: "archive".into(),
+            protocols: vec![ProtocolInfo {
+                protocol: *b"cfx",
+                version: ProtocolVersion(3),
+            }],
+            snapshot_range: Some((2000, 6000)),
+        }
+    }
+
+    #[test]
+    fn test_signed_record() {
+        let key = Random.generate().unwrap();
+        let signed = record().sign(key.secret()).unwrap();
+
+        let decoded: SignedNodeRecord = rlp::decode(&rlp::encode(&signed))
+            .expect("signed record should be decoded");
+        assert_eq!(decoded, signed);
+        assert_eq!(decoded.signer().unwrap(), *key.public());
+
+        let mut tampered = decoded;
+        tampered.record.seq += 1;
+        assert_ne!(tampered.signer().ok(), Some(*key.public()));
+
+        let tags = record().tags();
+        assert_eq!(tags.len(), 2);
+        assert_eq!(tags[0], ("node_type".into(), "archive".into()));
+        assert_eq!(tags[1], ("protocol_cfx".into(), "3".into()));
+    }
+
+    #[test]
+    fn test_invalid_record() {
+        let mut invalid = record();
+        invalid.snapshot_range = Some((6000, 2000));
+        assert!(rlp::decode::<NodeRecord>(&rlp::encode(&invalid)).is_err());
+
+        let mut invalid = record();
+        invalid.node_type = "a".repeat(17);
+        assert!(rlp::decode::<NodeRecord>(&rlp::encode(&invalid)).is_err());
+
+        let mut invalid = record();
+        invalid.protocols =
+            vec![invalid.protocols[0].clone(); MAX_RECORD_PROTOCOLS + 1];
+        assert!(rlp::decode::<NodeRecord>(&rlp::encode(&invalid)).is_err());
+
+        let mut valid = record();
+        valid.snapshot_range = None;
+        assert_eq!(
+            rlp::decode::<NodeRecord>(&rlp::encode(&valid)).unwrap(),
+            valid
+        );
+    }
+}
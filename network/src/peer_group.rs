@@ -0,0 +1,280 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! Static peer groups.
+//!
+//! The peers in static groups, e.g. the validators behind a sentry node, get
+//! guaranteed session slots. They are connected regardless of
+//! `max_outgoing_peers`, accepted regardless of `max_incoming_peers`, exempt
+//! from the session IP limits and the subnet quota, and never disconnected for
+//! their reputation scores. A dropped static peer is reconnected at once, and
+//! then with exponential backoff until it is connected again.
+
+use crate::node_table::{NodeEndpoint, NodeEntry, NodeId};
+use parking_lot::RwLock;
+use serde_derive::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    net::{IpAddr, SocketAddr},
+    time::{Duration, Instant},
+};
+
+pub const DEFAULT_MIN_RECONNECT_BACKOFF: Duration = Duration::from_secs(1);
+pub const DEFAULT_MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(60);
+
+/// The group of the reserved nodes in the network configuration.
+pub const RESERVED_PEER_GROUP: &str = "reserved";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerGroupConfig {
+    /// Node urls of the static peers in each group.
+    pub groups: BTreeMap<String, Vec<String>>,
+    /// Backoff of the first failed reconnection.
+    pub min_reconnect_backoff: Duration,
+    /// Upper bound of the backoff, which doubles on each failure.
+    pub max_reconnect_backoff: Duration,
+}
+
+impl Default for PeerGroupConfig {
+    fn default() -> Self {
+        PeerGroupConfig {
+            groups: BTreeMap::new(),
+            min_reconnect_backoff: DEFAULT_MIN_RECONNECT_BACKOFF,
+            max_reconnect_backoff: DEFAULT_MAX_RECONNECT_BACKOFF,
+        }
+    }
+}
+
+/// A static peer and its reconnection state.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StaticPeerInfo {
+    pub node_id: NodeId,
+    pub address: SocketAddr,
+    pub connected: bool,
+    /// Number of reconnections since last connected.
+    pub reconnects: u32,
+}
+
+/// A static peer group and its peers ordered by node id.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerGroupInfo {
+    pub name: String,
+    pub peers: Vec<StaticPeerInfo>,
+}
+
+struct StaticPeer {
+    endpoint: NodeEndpoint,
+    groups: BTreeSet<String>,
+    reconnects: u32,
+    next_reconnect: Instant,
+}
+
+pub struct PeerGroups {
+    min_backoff: Duration,
+    max_backoff: Duration,
+    peers: RwLock<HashMap<NodeId, StaticPeer>>,
+}
+
+impl PeerGroups {
+    pub fn new(config: &PeerGroupConfig) -> Self {
+        PeerGroups {
+            min_backoff: config.min_reconnect_backoff,
+            max_backoff: config.max_reconnect_backoff,
+            peers: Default::default(),
+        }
+    }
+
+    /// Whether the node `id` is a static peer of any group.
+    pub fn contains(&self, id: &NodeId) -> bool {
+        self.peers.read().contains_key(id)
+    }
+
+    /// Whether `ip` is the address of any static peer, which is used to
+    /// accept the ingress sessions before the node id is known.
+    pub fn contains_ip(&self, ip: &IpAddr) -> bool {
+        self.peers
+            .read()
+            .values()
+            .any(|peer| peer.endpoint.address.ip() == *ip)
+    }
+
+    /// Returns the number of static peers.
+    pub fn count(&self) -> usize { self.peers.read().len() }
+
+    /// Returns the endpoint of the static peer `id`.
+    pub fn endpoint(&self, id: &NodeId) -> Option<NodeEndpoint> {
+        Some(self.peers.read().get(id)?.endpoint.clone())
+    }
+
+    /// Adds a static peer to `group` or updates its endpoint, returns false if
+    /// it is already in the group.
+    pub fn add_peer(&self, group: &str, entry: NodeEntry) -> bool {
+        let mut peers = self.peers.write();
+        let peer = peers.entry(entry.id).or_insert_with(|| StaticPeer {
+            endpoint: entry.endpoint.clone(),
+            groups: BTreeSet::new(),
+            reconnects: 0,
+            next_reconnect: Instant::now(),
+        });
+        peer.endpoint = entry.endpoint;
+        peer.groups.insert(group.into())
+    }
+
+    /// Removes a static peer from `group`, and returns whether the node is no
+    /// longer a static peer of any group.
+    pub fn remove_peer(&self, group: &str, id: &NodeId) -> bool {
+        let mut peers = self.peers.write();
+        let removed = match peers.get_mut(id) {
+            Some(peer) => peer.groups.remove(group),
+            None => return false,
+        };
+        if removed && peers[id].groups.is_empty() {
+            peers.remove(id);
+            return true;
+        }
+        false
+    }
+
+    /// Returns the static peers to reconnect, i.e. the peers which are not
+    /// connected and whose backoff has elapsed, and schedules their next
+    /// reconnection.
+    pub fn peers_to_reconnect<F>(&self, connected: F) -> Vec<NodeId>
+    where F: Fn(&NodeId) -> bool {
+        self.peers_to_reconnect_at(connected, Instant::now())
+    }
+
+    fn peers_to_reconnect_at<F>(
+        &self, connected: F, now: Instant,
+    ) -> Vec<NodeId>
+    where F: Fn(&NodeId) -> bool {
+        let mut result = Vec::new();
+        for (id, peer) in self.peers.write().iter_mut() {
+            if peer.next_reconnect > now || connected(id) {
+                continue;
+            }
+
+            let backoff = self
+                .min_backoff
+                .checked_mul(1 << peer.reconnects.min(16))
+                .map_or(self.max_backoff, |b| b.min(self.max_backoff));
+            peer.reconnects += 1;
+            peer.next_reconnect = now + backoff;
+            result.push(*id);
+        }
+        result
+    }
+
+    /// Resets the backoff of the static peer `id` once connected, so that it
+    /// is reconnected at once when dropped.
+    pub fn note_connected(&self, id: &NodeId) {
+        if let Some(peer) = self.peers.write().get_mut(id) {
+            peer.reconnects = 0;
+            peer.next_reconnect = Instant::now();
+        }
+    }
+
+    /// Returns all the groups ordered by name.
+    pub fn groups<F>(&self, connected: F) -> Vec<PeerGroupInfo>
+    where F: Fn(&NodeId) -> bool {
+        let mut groups: BTreeMap<String, Vec<StaticPeerInfo>> = BTreeMap::new();
+        for (id, peer) in self.peers.read().iter() {
+            for group in &peer.groups {
+                groups
+                    .entry(group.clone())
+                    .or_default()
+                    .push(StaticPeerInfo {
+                        node_id: *id,
+                        address: peer.endpoint.address,
+                        connected: connected(id),
+                        reconnects: peer.reconnects,
+                    });
+            }
+        }
+        groups
+            .into_iter()
+            .map(|(name, mut peers)| {
+                peers.sort_by_key(|peer| peer.node_id);
+                PeerGroupInfo { name, peers }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PeerGroupConfig, PeerGroups};
+    use crate::node_table::{NodeEndpoint, NodeEntry, NodeId};
+    use std::time::{Duration, Instant};
+
+    fn entry(id: NodeId, address: &str) -> NodeEntry {
+        NodeEntry {
+            id,
+            endpoint: NodeEndpoint {
+                address: address.parse().unwrap(),
+                udp_port: 32323,
+            },
+        }
+    }
+
+    #[test]
+    fn test_peer_groups() {
+        let groups = PeerGroups::new(&PeerGroupConfig::default());
+        let (a, b) = (NodeId::random(), NodeId::random());
+
+        assert!(groups.add_peer("validators", entry(a, "10.0.0.1:32323")));
+        assert!(!groups.add_peer("validators", entry(a, "10.0.0.1:32323")));
+        assert!(groups.add_peer("sentries", entry(a, "10.0.0.2:32323")));
+        assert!(groups.add_peer("sentries", entry(b, "10.0.0.3:32323")));
+        assert_eq!(groups.count(), 2);
+        assert!(groups.contains(&a));
+        assert!(groups.contains_ip(&"10.0.0.2".parse().unwrap()));
+        assert!(!groups.contains_ip(&"10.0.0.1".parse().unwrap()));
+
+        let info = groups.groups(|id| *id == a);
+        assert_eq!(info.len(), 2);
+        assert_eq!(info[0].name, "sentries");
+        assert_eq!(info[0].peers.len(), 2);
+        assert_eq!(info[1].name, "validators");
+        assert_eq!(info[1].peers[0].node_id, a);
+        assert!(info[1].peers[0].connected);
+
+        // a is still in the sentries group.
+        assert!(!groups.remove_peer("validators", &a));
+        assert!(!groups.remove_peer("validators", &b));
+        assert!(groups.contains(&a));
+        assert!(groups.remove_peer("sentries", &a));
+        assert!(!groups.contains(&a));
+        assert_eq!(groups.count(), 1);
+    }
+
+    #[test]
+    fn test_reconnect_backoff() {
+        let groups = PeerGroups::new(&PeerGroupConfig {
+            max_reconnect_backoff: Duration::from_secs(3),
+            ..Default::default()
+        });
+        let id = NodeId::random();
+        groups.add_peer("validators", entry(id, "10.0.0.1:32323"));
+
+        let now = Instant::now();
+        assert!(groups.peers_to_reconnect_at(|_| true, now).is_empty());
+
+        // Reconnect at once, then after 1s, 2s and at most 3s.
+        assert_eq!(groups.peers_to_reconnect_at(|_| false, now), vec![id]);
+        assert!(groups.peers_to_reconnect_at(|_| false, now).is_empty());
+        let mut at = now;
+        for backoff in &[1, 2, 3, 3] {
+            at += Duration::from_secs(*backoff);
+            assert!(groups
+                .peers_to_reconnect_at(|_| false, at - Duration::from_millis(1))
+                .is_empty());
+            assert_eq!(groups.peers_to_reconnect_at(|_| false, at), vec![id]);
+        }
+
+        groups.note_connected(&id);
+        assert_eq!(groups.peers_to_reconnect(|_| false), vec![id]);
+    }
+}
@@ -0,0 +1,282 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! Reputation scoring of peers.
+//!
+//! Protocol handlers report the misbehaviors of peers, and each misbehavior
+//! lowers the score of the peer by its penalty. The score recovers over time
+//! up to zero. A peer is disconnected once its score drops to the disconnect
+//! threshold, and banned for a while once it drops to the ban threshold. The
+//! bans are persisted in the node database, so that the banned peers are still
+//! refused after a restart.
+
+use crate::node_table::NodeId;
+use parking_lot::RwLock;
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+pub const DEFAULT_DISCONNECT_THRESHOLD: i64 = -100;
+pub const DEFAULT_BAN_THRESHOLD: i64 = -300;
+pub const DEFAULT_BAN_DURATION: Duration = Duration::from_secs(24 * 3600);
+pub const DEFAULT_SCORE_RECOVERY_PER_MINUTE: i64 = 10;
+
+/// Misbehaviors of peers reported by the protocol handlers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Misbehavior {
+    /// Sent a block that failed the validation.
+    InvalidBlock,
+    /// Sent a response that does not match any inflight request.
+    UselessResponse,
+    /// Did not respond to a request in time.
+    Timeout,
+    /// Kept sending requests after being throttled.
+    ThrottlingViolation,
+}
+
+impl Misbehavior {
+    pub fn penalty(&self) -> i64 {
+        match self {
+            Misbehavior::InvalidBlock => 100,
+            Misbehavior::UselessResponse => 20,
+            Misbehavior::Timeout => 10,
+            Misbehavior::ThrottlingViolation => 50,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReputationConfig {
+    /// Disconnect a peer when its score drops to this threshold.
+    pub disconnect_threshold: i64,
+    /// Ban a peer when its score drops to this threshold.
+    pub ban_threshold: i64,
+    /// Duration to ban a peer.
+    pub ban_duration: Duration,
+    /// Score recovered per minute, up to zero.
+    pub recovery_per_minute: i64,
+}
+
+impl Default for ReputationConfig {
+    fn default() -> Self {
+        ReputationConfig {
+            disconnect_threshold: DEFAULT_DISCONNECT_THRESHOLD,
+            ban_threshold: DEFAULT_BAN_THRESHOLD,
+            ban_duration: DEFAULT_BAN_DURATION,
+            recovery_per_minute: DEFAULT_SCORE_RECOVERY_PER_MINUTE,
+        }
+    }
+}
+
+/// What to do with a peer after it is reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReputationAction {
+    None,
+    Disconnect,
+    Ban(Duration),
+}
+
+/// The score of a peer and the count of its misbehaviors.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerScore {
+    pub node_id: NodeId,
+    pub score: i64,
+    pub invalid_blocks: u64,
+    pub useless_responses: u64,
+    pub timeouts: u64,
+    pub throttling_violations: u64,
+}
+
+impl PeerScore {
+    fn new(node_id: NodeId) -> Self {
+        PeerScore {
+            node_id,
+            score: 0,
+            invalid_blocks: 0,
+            useless_responses: 0,
+            timeouts: 0,
+            throttling_violations: 0,
+        }
+    }
+}
+
+struct ScoreEntry {
+    score: PeerScore,
+    /// Time when the score was last recovered.
+    recovered: Instant,
+}
+
+impl ScoreEntry {
+    fn recover(&mut self, recovery_per_minute: i64, now: Instant) {
+        if self.score.score >= 0 {
+            self.recovered = now;
+            return;
+        }
+
+        let minutes =
+            now.saturating_duration_since(self.recovered).as_secs() / 60;
+        if minutes > 0 {
+            self.score.score = (self.score.score
+                + recovery_per_minute.saturating_mul(minutes as i64))
+            .min(0);
+            self.recovered += Duration::from_secs(minutes * 60);
+        }
+    }
+}
+
+pub struct Reputation {
+    config: ReputationConfig,
+    scores: RwLock<HashMap<NodeId, ScoreEntry>>,
+}
+
+impl Reputation {
+    pub fn new(config: ReputationConfig) -> Self {
+        Reputation {
+            config,
+            scores: Default::default(),
+        }
+    }
+
+    pub fn config(&self) -> &ReputationConfig { &self.config }
+
+    /// Lowers the score of the peer `id` for `misbehavior`, and returns what
+    /// to do with the peer. The score is reset once the peer is banned.
+    pub fn report(
+        &self, id: &NodeId, misbehavior: Misbehavior,
+    ) -> ReputationAction {
+        self.report_at(id, misbehavior, Instant::now())
+    }
+
+    fn report_at(
+        &self, id: &NodeId, misbehavior: Misbehavior, now: Instant,
+    ) -> ReputationAction {
+        let mut scores = self.scores.write();
+        let entry = scores.entry(*id).or_insert_with(|| ScoreEntry {
+            score: PeerScore::new(*id),
+            recovered: now,
+        });
+        entry.recover(self.config.recovery_per_minute, now);
+
+        let score = &mut entry.score;
+        score.score -= misbehavior.penalty();
+        match misbehavior {
+            Misbehavior::InvalidBlock => score.invalid_blocks += 1,
+            Misbehavior::UselessResponse => score.useless_responses += 1,
+            Misbehavior::Timeout => score.timeouts += 1,
+            Misbehavior::ThrottlingViolation => {
+                score.throttling_violations += 1
+            }
+        }
+
+        if score.score <= self.config.ban_threshold {
+            scores.remove(id);
+            ReputationAction::Ban(self.config.ban_duration)
+        } else if score.score <= self.config.disconnect_threshold {
+            ReputationAction::Disconnect
+        } else {
+            ReputationAction::None
+        }
+    }
+
+    /// Resets the score of the peer `id`.
+    pub fn reset(&self, id: &NodeId) { self.scores.write().remove(id); }
+
+    /// Returns the scores of all the peers that have misbehaved, ordered from
+    /// the lowest score. Peers that have fully recovered are removed.
+    pub fn scores(&self) -> Vec<PeerScore> { self.scores_at(Instant::now()) }
+
+    fn scores_at(&self, now: Instant) -> Vec<PeerScore> {
+        let mut scores = self.scores.write();
+        for entry in scores.values_mut() {
+            entry.recover(self.config.recovery_per_minute, now);
+        }
+        scores.retain(|_, entry| entry.score.score < 0);
+
+        let mut result: Vec<PeerScore> =
+            scores.values().map(|entry| entry.score.clone()).collect();
+        result.sort_by(|a, b| {
+            a.score.cmp(&b.score).then(a.node_id.cmp(&b.node_id))
+        });
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Misbehavior::*, Reputation, ReputationAction, ReputationConfig,
+        DEFAULT_BAN_DURATION,
+    };
+    use crate::node_table::NodeId;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_report() {
+        let reputation = Reputation::new(ReputationConfig::default());
+        let (a, b) = (NodeId::random(), NodeId::random());
+        let now = Instant::now();
+
+        assert_eq!(
+            reputation.report_at(&a, Timeout, now),
+            ReputationAction::None
+        );
+        assert_eq!(
+            reputation.report_at(&a, InvalidBlock, now),
+            ReputationAction::Disconnect
+        );
+        assert_eq!(
+            reputation.report_at(&b, UselessResponse, now),
+            ReputationAction::None
+        );
+
+        let scores = reputation.scores_at(now);
+        assert_eq!(scores.len(), 2);
+        assert_eq!(scores[0].node_id, a);
+        assert_eq!(scores[0].score, -110);
+        assert_eq!(scores[0].timeouts, 1);
+        assert_eq!(scores[0].invalid_blocks, 1);
+        assert_eq!(scores[1].node_id, b);
+        assert_eq!(scores[1].score, -20);
+
+        // a is banned and its score is reset.
+        assert_eq!(
+            reputation.report_at(&a, InvalidBlock, now),
+            ReputationAction::Disconnect
+        );
+        assert_eq!(
+            reputation.report_at(&a, InvalidBlock, now),
+            ReputationAction::Ban(DEFAULT_BAN_DURATION)
+        );
+        let scores = reputation.scores_at(now);
+        assert_eq!(scores.len(), 1);
+        assert_eq!(scores[0].node_id, b);
+    }
+
+    #[test]
+    fn test_recovery() {
+        let reputation = Reputation::new(ReputationConfig::default());
+        let id = NodeId::random();
+        let now = Instant::now();
+
+        reputation.report_at(&id, ThrottlingViolation, now);
+        reputation.report_at(&id, ThrottlingViolation, now);
+
+        // Recover 10 per minute.
+        let later = now + Duration::from_secs(150);
+        assert_eq!(reputation.scores_at(later)[0].score, -80);
+        assert_eq!(
+            reputation.report_at(&id, Timeout, later),
+            ReputationAction::None
+        );
+        assert_eq!(reputation.scores_at(later)[0].score, -90);
+
+        // Fully recovered peers are removed.
+        let later = later + Duration::from_secs(10 * 60);
+        assert!(reputation.scores_at(later).is_empty());
+    }
+}
@@ -8,9 +8,13 @@ use crate::{
     handshake::BYPASS_CRYPTOGRAPHY,
     io::*,
     ip_utils::{map_external_address, select_public_address},
-    node_database::NodeDatabase,
+    membership::{MemberInfo, Membership, MembershipSource},
+    node_database::{BannedNode, NodeDatabase},
+    node_record::NodeRecord,
     node_table::*,
     parse_msg_id_leb128_2_bytes_at_most,
+    peer_group::{PeerGroupInfo, PeerGroups, RESERVED_PEER_GROUP},
+    reputation::{Misbehavior, PeerScore, Reputation, ReputationAction},
     session::{self, Session, SessionData, SessionDetails},
     session_manager::SessionManager,
     Error, ErrorKind, HandlerWorkType, IpFilter, NatType, NetworkConfiguration,
@@ -38,7 +42,7 @@ use std::{
     path::{Path, PathBuf},
     str::FromStr,
     sync::{atomic::Ordering as AtomicOrdering, Arc},
-    time::{Duration, Instant},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 const MAX_SESSIONS: usize = 2048;
@@ -57,6 +61,7 @@ const DISCOVERY_ROUND: TimerToken = SYS_TIMER + 6;
 const NODE_TABLE: TimerToken = SYS_TIMER + 7;
 const SEND_DELAYED_MESSAGES: TimerToken = SYS_TIMER + 8;
 const CHECK_SESSIONS: TimerToken = SYS_TIMER + 9;
+const MEMBERSHIP_REFRESH: TimerToken = SYS_TIMER + 10;
 const HANDLER_TIMER: TimerToken = LAST_SESSION + 256;
 const STOP_NET_POLL: TimerToken = HANDLER_TIMER + 1;
 
@@ -396,6 +401,147 @@ impl NetworkService {
         true
     }
 
+    /// Return the members of the consortium, or `None` if the membership is
+    /// not enabled.
+    pub fn members(&self) -> Option<Vec<MemberInfo>> {
+        let inner = self.inner.as_ref()?;
+        if !inner.membership.is_enabled() {
+            return None;
+        }
+        Some(inner.membership.members())
+    }
+
+    /// Add a member from `source`, returns false if already added.
+    pub fn add_member(
+        &self, source: MembershipSource, id: NodeId,
+    ) -> Result<bool, Error> {
+        let inner = self.membership_inner()?;
+        Ok(inner.membership.add_member(source, id))
+    }
+
+    /// Remove a member from `source`, and disconnect the node if it is no
+    /// longer a member. Returns whether the node is no longer a member.
+    pub fn remove_member(
+        &self, source: MembershipSource, id: &NodeId,
+    ) -> Result<bool, Error> {
+        let inner = self.membership_inner()?;
+        let removed = inner.membership.remove_member(source, id);
+        if removed {
+            let io =
+                IoContext::new(self.io_service.as_ref().unwrap().channel(), 0);
+            inner.disconnect_non_members(&[*id], &io);
+        }
+        Ok(removed)
+    }
+
+    /// Replace the members from `source`, and disconnect the nodes that are no
+    /// longer members.
+    pub fn set_members(
+        &self, source: MembershipSource, ids: HashSet<NodeId>,
+    ) -> Result<(), Error> {
+        let inner = self.membership_inner()?;
+        let removed = inner.membership.set_members(source, ids);
+        if !removed.is_empty() {
+            let io =
+                IoContext::new(self.io_service.as_ref().unwrap().channel(), 0);
+            inner.disconnect_non_members(&removed, &io);
+        }
+        Ok(())
+    }
+
+    fn membership_inner(&self) -> Result<&Arc<NetworkServiceInner>, Error> {
+        match self.inner {
+            Some(ref inner) if inner.membership.is_enabled() => Ok(inner),
+            Some(_) => Err("Membership is not enabled".into()),
+            None => Err("Network service not started yet!".into()),
+        }
+    }
+
+    /// Return the reputation scores of the peers that have misbehaved.
+    pub fn peer_scores(&self) -> Option<Vec<PeerScore>> {
+        let inner = self.inner.as_ref()?;
+        Some(inner.reputation.scores())
+    }
+
+    /// Return the banned nodes whose ban is not expired.
+    pub fn banned_nodes(&self) -> Option<Vec<BannedNode>> {
+        let inner = self.inner.as_ref()?;
+        Some(inner.node_db.read().banned_nodes())
+    }
+
+    /// Ban the node for `duration`, or the configured ban duration if not
+    /// specified, and disconnect it.
+    pub fn ban_node(
+        &self, id: &NodeId, duration: Option<Duration>, reason: &str,
+    ) -> Result<(), Error> {
+        let inner = match self.inner {
+            Some(ref inner) => inner,
+            None => return Err("Network service not started yet!".into()),
+        };
+        let duration = duration.unwrap_or(inner.config.reputation.ban_duration);
+        let io = IoContext::new(self.io_service.as_ref().unwrap().channel(), 0);
+        inner.ban_node(id, duration, reason, &io);
+        Ok(())
+    }
+
+    /// Lift the ban of the node and reset its reputation score. Returns false
+    /// if the node is not banned.
+    pub fn unban_node(&self, id: &NodeId) -> Result<bool, Error> {
+        let inner = match self.inner {
+            Some(ref inner) => inner,
+            None => return Err("Network service not started yet!".into()),
+        };
+        inner.reputation.reset(id);
+        Ok(inner.node_db.write().unban(id))
+    }
+
+    /// Return the record of the node `id` received in discovery, or the
+    /// record of the local node if `id` is the local node id.
+    pub fn node_record(&self, id: &NodeId) -> Option<NodeRecord> {
+        let inner = self.inner.as_ref()?;
+        if id == inner.metadata.id() {
+            return Some(inner.local_record.read().clone());
+        }
+        let discovery = inner.discovery.lock();
+        discovery.as_ref()?.node_record(id).cloned()
+    }
+
+    /// Return the static peer groups.
+    pub fn peer_groups(&self) -> Option<Vec<PeerGroupInfo>> {
+        let inner = self.inner.as_ref()?;
+        Some(
+            inner
+                .peer_groups
+                .groups(|id| inner.sessions.contains_node(id)),
+        )
+    }
+
+    /// Add the node url to the static peer `group`, returns false if already
+    /// added. The peer is connected in the next housekeeping round.
+    pub fn add_group_peer(
+        &self, group: &str, url: &str,
+    ) -> Result<bool, Error> {
+        match self.inner {
+            Some(ref inner) => inner.add_static_peer(group, url),
+            None => Err("Network service not started yet!".into()),
+        }
+    }
+
+    /// Remove the node from the static peer `group`, returns whether the node
+    /// is no longer a static peer. The session with the node is kept if
+    /// allowed by the IP policy, but it is no longer guaranteed.
+    pub fn remove_group_peer(
+        &self, group: &str, id: &NodeId,
+    ) -> Result<bool, Error> {
+        match (&self.inner, &self.io_service) {
+            (Some(inner), Some(io_service)) => {
+                let io = IoContext::new(io_service.channel(), 0);
+                Ok(inner.remove_static_peer(group, id, &io))
+            }
+            _ => Err("Network service not started yet!".into()),
+        }
+    }
+
     pub fn save_node_db(&self) {
         if let Some(inner) = &self.inner {
             inner.node_db.write().save();
@@ -439,16 +585,21 @@ pub struct NetworkServiceInner {
     udp_socket: Mutex<UdpSocket>,
     tcp_listener: Mutex<TcpListener>,
     udp_channel: RwLock<UdpChannel>,
+    /// Record of the local node advertised in discovery.
+    local_record: RwLock<NodeRecord>,
     discovery: Mutex<Option<Discovery>>,
     handlers:
         RwLock<HashMap<ProtocolId, Arc<dyn NetworkProtocolHandler + Sync>>>,
     timers: RwLock<HashMap<TimerToken, ProtocolTimer>>,
     timer_counter: RwLock<usize>,
     pub node_db: RwLock<NodeDatabase>,
-    reserved_nodes: RwLock<HashSet<NodeId>>,
     dropped_nodes: RwLock<HashSet<NodeId>>,
-
-    is_consortium: bool,
+    /// Allow-list of peers, only enabled in consortium networks.
+    pub membership: Membership,
+    /// Static peers with guaranteed session slots.
+    pub peer_groups: PeerGroups,
+    /// Reputation scores of the misbehaving peers.
+    pub reputation: Reputation,
 
     /// Delayed message queue and corresponding latency
     delayed_queue: Option<DelayedQueue>,
@@ -488,6 +639,12 @@ impl DelayedQueue {
                     context.peer
                 );
             }
+            Err(Error(ErrorKind::Throttling(reason), _)) => {
+                debug!(
+                    "Delayed message throttled: peer={:?} reason={}",
+                    context.peer, reason
+                );
+            }
             Err(e) => {
                 info!(
                     "Error sending delayed message: peer={:?} err={:?}",
@@ -617,6 +774,15 @@ impl NetworkServiceInner {
             },
             config: config.clone(),
             udp_channel: RwLock::new(UdpChannel::new()),
+            // Start the sequence number from the current time, so that it
+            // still increases after a restart.
+            local_record: RwLock::new(NodeRecord {
+                seq: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0),
+                ..Default::default()
+            }),
             discovery: Mutex::new(discovery),
             udp_socket: Mutex::new(udp_socket),
             tcp_listener: Mutex::new(tcp_listener),
@@ -633,23 +799,49 @@ impl NetworkServiceInner {
                 nodes_path,
                 config.subnet_quota,
             )),
-            reserved_nodes: RwLock::new(HashSet::new()),
             dropped_nodes: RwLock::new(HashSet::new()),
-            is_consortium: config.is_consortium,
+            membership: Membership::new(
+                config.is_consortium && config.membership.enabled,
+            ),
+            peer_groups: PeerGroups::new(&config.peer_groups),
+            reputation: Reputation::new(config.reputation.clone()),
             delayed_queue: None,
         };
 
+        inner.advertise_local_record(&*inner.local_record.read());
+
         for n in &config.boot_nodes {
             inner.add_boot_node(n);
         }
 
-        let reserved_nodes = config.reserved_nodes.clone();
-        for n in reserved_nodes {
-            if let Err(e) = inner.add_reserved_node(&n) {
+        if inner.membership.is_enabled()
+            && config.membership.allow_boot_nodes_until_registry
+        {
+            let boot_nodes = config
+                .boot_nodes
+                .iter()
+                .filter_map(|n| Node::from_str(n).ok())
+                .map(|n| n.id)
+                .collect();
+            inner
+                .membership
+                .set_members(MembershipSource::BootNodes, boot_nodes);
+        }
+
+        for n in &config.reserved_nodes {
+            if let Err(e) = inner.add_static_peer(RESERVED_PEER_GROUP, n) {
                 debug!("Error parsing node id: {}: {:?}", n, e);
             }
         }
 
+        for (group, nodes) in &config.peer_groups.groups {
+            for n in nodes {
+                if let Err(e) = inner.add_static_peer(group, n) {
+                    warn!("Error adding static peer {}: {:?}", n, e);
+                }
+            }
+        }
+
         Ok(inner)
     }
 
@@ -684,6 +876,33 @@ impl NetworkServiceInner {
 
     pub fn get_ip_filter(&self) -> &IpFilter { &self.config.ip_filter }
 
+    /// Updates the record of the local node, and advertises it in discovery
+    /// with a new sequence number if changed.
+    fn update_local_record<F>(&self, update: F)
+    where F: FnOnce(&mut NodeRecord) {
+        let mut record = self.local_record.write();
+        let mut updated = record.clone();
+        update(&mut updated);
+        if updated == *record {
+            return;
+        }
+
+        updated.seq += 1;
+        *record = updated;
+        self.advertise_local_record(&*record);
+    }
+
+    fn advertise_local_record(&self, record: &NodeRecord) {
+        match record.clone().sign(self.metadata.secret()) {
+            Ok(signed) => {
+                if let Some(d) = self.discovery.lock().as_mut() {
+                    d.set_local_record(signed);
+                }
+            }
+            Err(e) => warn!("Error signing local node record: {:?}", e),
+        }
+    }
+
     fn add_boot_node(&self, id: &str) {
         match Node::from_str(id) {
             Err(e) => {
@@ -698,14 +917,42 @@ impl NetworkServiceInner {
         }
     }
 
-    fn add_reserved_node(&mut self, id: &str) -> Result<(), Error> {
+    /// Adds the node url `id` to the static peer `group`, returns false if
+    /// already added.
+    pub fn add_static_peer(
+        &self, group: &str, id: &str,
+    ) -> Result<bool, Error> {
         let n = Node::from_str(id)?;
-        self.node_db.write().insert_trusted(NodeEntry {
-            id: n.id.clone(),
-            endpoint: n.endpoint.clone(),
-        });
-        self.reserved_nodes.write().insert(n.id);
-        Ok(())
+        let entry = NodeEntry {
+            id: n.id,
+            endpoint: n.endpoint,
+        };
+        self.node_db.write().insert_trusted(entry.clone());
+        Ok(self.peer_groups.add_peer(group, entry))
+    }
+
+    /// Removes the node `id` from the static peer `group`, returns whether the
+    /// node is no longer a static peer. If so, its session is counted against
+    /// the limits, or disconnected if not allowed by the IP policy.
+    pub fn remove_static_peer(
+        &self, group: &str, id: &NodeId, io: &IoContext<NetworkIoMessage>,
+    ) -> bool {
+        if !self.peer_groups.remove_peer(group, id) {
+            return false;
+        }
+
+        if let Some(idx) = self.sessions.get_index_by_id(id) {
+            if !self.sessions.unset_static(idx) {
+                self.kill_connection(
+                    id,
+                    io,
+                    true,
+                    None,
+                    "static peer removed", // reason
+                );
+            }
+        }
+        true
     }
 
     fn initialize_udp_protocols(
@@ -737,6 +984,15 @@ impl NetworkServiceInner {
         }
         io.register_timer(NODE_TABLE, self.config.node_table_timeout)?;
         io.register_timer(CHECK_SESSIONS, DEFAULT_CHECK_SESSIONS_TIMEOUT)?;
+        if self.membership.is_enabled()
+            && self.config.membership.members_file.is_some()
+        {
+            self.refresh_members_file(io);
+            io.register_timer(
+                MEMBERSHIP_REFRESH,
+                self.config.membership.members_file_refresh_timeout,
+            )?;
+        }
 
         Ok(())
     }
@@ -791,15 +1047,92 @@ impl NetworkServiceInner {
     }
 
     fn on_housekeeping(&self, io: &IoContext<NetworkIoMessage>) {
-        if self.is_consortium {
-            unimplemented!();
-        } else {
-            self.connect_peers(io);
-        }
+        self.connect_peers(io);
         self.drop_peers(io);
     }
 
-    // Connect to all reserved and trusted peers if not yet
+    /// Reload the members file if modified, and disconnect the nodes that are
+    /// no longer members.
+    fn refresh_members_file(&self, io: &IoContext<NetworkIoMessage>) {
+        let path = match self.config.membership.members_file {
+            Some(ref path) if self.membership.is_enabled() => path,
+            _ => return,
+        };
+
+        match self.membership.reload_file(path) {
+            Ok(Some((removed, entries))) => {
+                info!("Members file reloaded, {} removed", removed.len());
+                for entry in entries {
+                    self.node_db.write().insert_trusted(entry);
+                }
+                self.disconnect_non_members(&removed, io);
+            }
+            Ok(None) => {}
+            Err(e) => warn!("Failed to reload members file: {}", e),
+        }
+    }
+
+    /// Disconnect the sessions with the nodes that are no longer members.
+    pub fn disconnect_non_members(
+        &self, ids: &[NodeId], io: &IoContext<NetworkIoMessage>,
+    ) {
+        for id in ids {
+            if !self.membership.is_allowed(id) {
+                self.kill_connection(
+                    id,
+                    io,
+                    true,
+                    None,
+                    "membership revoked", // reason
+                );
+            }
+        }
+    }
+
+    /// Lower the reputation score of the peer for `misbehavior`, and
+    /// disconnect or ban the peer if the score drops too low.
+    pub fn report_peer(
+        &self, id: &NodeId, misbehavior: Misbehavior, reason: &str,
+        io: &IoContext<NetworkIoMessage>,
+    )
+    {
+        match self.reputation.report(id, misbehavior) {
+            // Static peers are never disconnected for their scores.
+            _ if self.peer_groups.contains(id) => debug!(
+                "Static peer misbehaved, id = {:?}, misbehavior = {:?}, reason = {}",
+                id, misbehavior, reason
+            ),
+            ReputationAction::None => debug!(
+                "Peer misbehaved, id = {:?}, misbehavior = {:?}, reason = {}",
+                id, misbehavior, reason
+            ),
+            ReputationAction::Disconnect => {
+                self.kill_connection(
+                    id,
+                    io,
+                    true,
+                    Some(UpdateNodeOperation::Failure),
+                    reason,
+                );
+            }
+            ReputationAction::Ban(duration) => {
+                self.ban_node(id, duration, reason, io)
+            }
+        }
+    }
+
+    /// Ban the node for `duration`, and disconnect it.
+    fn ban_node(
+        &self, id: &NodeId, duration: Duration, reason: &str,
+        io: &IoContext<NetworkIoMessage>,
+    )
+    {
+        info!("Ban node {:?} for {:?}, reason = {}", id, duration, reason);
+        self.node_db.write().ban(id, duration, reason);
+        self.kill_connection(id, io, true, None, reason);
+    }
+
+    // Connect to all static and trusted peers if not yet
     fn connect_peers(&self, io: &IoContext<NetworkIoMessage>) {
         if self.metadata.minimum_peer_protocol_version.read().len() == 0 {
             // The protocol handler has not been registered, we just wait for
@@ -809,6 +1142,17 @@ impl NetworkServiceInner {
 
         let self_id = self.metadata.id().clone();
 
+        // Static peers are reconnected regardless of the outgoing slots and
+        // the handshake limit.
+        for id in self
+            .peer_groups
+            .peers_to_reconnect(|id| self.sessions.contains_node(id))
+        {
+            if id != self_id && self.membership.is_allowed(&id) {
+                self.connect_peer(&id, io);
+            }
+        }
+
         let sampled_archive_nodes = self.sample_archive_nodes();
 
         let (handshake_count, egress_count, ingress_count) =
@@ -830,18 +1174,17 @@ impl NetworkServiceInner {
             );
         }
 
-        let reserved_nodes = self.reserved_nodes.read();
-        // Try to connect all reserved peers and trusted peers
-        let nodes = reserved_nodes
-            .iter()
-            .cloned()
-            .chain(sampled_archive_nodes)
-            .chain(samples);
+        // Try to connect the sampled trusted peers
+        let nodes = sampled_archive_nodes.into_iter().chain(samples);
 
         let max_handshakes_per_round = self.config.max_handshakes / 2;
         let mut started: usize = 0;
         for id in nodes
-            .filter(|id| !self.sessions.contains_node(id) && *id != self_id)
+            .filter(|id| {
+                !self.sessions.contains_node(id)
+                    && *id != self_id
+                    && self.membership.is_allowed(id)
+            })
             .take(min(
                 max_handshakes_per_round,
                 self.config.max_handshakes.saturating_sub(handshake_count),
@@ -918,22 +1261,34 @@ impl NetworkServiceInner {
             return;
         }
 
+        if self.node_db.read().is_banned(id) {
+            debug!("Abort connect. Node is banned");
+            return;
+        }
+
         let (socket, address) = {
-            let address = {
-                // outgoing connection must pick node from trusted node table
-                if let Some(node) = self.node_db.read().get(id, true) {
-                    node.endpoint.address
-                } else {
-                    debug!("Abort connect. Node expired");
-                    return;
+            let address = match self.peer_groups.endpoint(id) {
+                // static peers are exempt from the session IP limits
+                Some(endpoint) => endpoint.address,
+                None => {
+                    // outgoing connection must pick node from trusted node
+                    // table
+                    let address =
+                        if let Some(node) = self.node_db.read().get(id, true) {
+                            node.endpoint.address
+                        } else {
+                            debug!("Abort connect. Node expired");
+                            return;
+                        };
+
+                    if !self.sessions.is_ip_allowed(&address.ip()) {
+                        debug!("cannot create outgoing connection to node, id = {:?}, address = {:?}", id, address);
+                        return;
+                    }
+                    address
                 }
             };
 
-            if !self.sessions.is_ip_allowed(&address.ip()) {
-                debug!("cannot create outgoing connection to node, id = {:?}, address = {:?}", id, address);
-                return;
-            }
-
             match TcpStream::connect(&address) {
                 Ok(socket) => {
                     trace!("{}: connecting to {:?}", id, address);
@@ -1124,6 +1479,8 @@ impl NetworkServiceInner {
             // Handshake is just finished, first process the outcome from the
             // handshake.
             if handshake_done {
+                self.peer_groups
+                    .note_connected(session_node_id.as_ref().unwrap());
                 {
                     let handlers = self.handlers.read();
                     // Clone the data to prevent deadlock, because handler may
@@ -1607,6 +1964,7 @@ impl IoHandler<NetworkIoMessage> for NetworkServiceInner {
                 self.node_db.write().save();
             }
             CHECK_SESSIONS => self.on_check_sessions(io),
+            MEMBERSHIP_REFRESH => self.refresh_members_file(io),
             SEND_DELAYED_MESSAGES => {
                 if let Some(ref queue) = self.delayed_queue {
                     queue.send_delayed_messages(self);
@@ -1679,6 +2037,9 @@ impl IoHandler<NetworkIoMessage> for NetworkServiceInner {
                     "Protocol {:?} version {:?} registered.",
                     protocol, version
                 );
+                self.update_local_record(|record| {
+                    record.protocols = protocols.clone()
+                });
             }
             NetworkIoMessage::AddTimer {
                 ref protocol,
@@ -2014,6 +2375,13 @@ impl<'a> NetworkContextTrait for NetworkContext<'a> {
             .kill_connection(node_id, self.io, true, op, reason);
     }
 
+    fn report_peer(
+        &self, node_id: &NodeId, misbehavior: Misbehavior, reason: &str,
+    ) {
+        self.network_service
+            .report_peer(node_id, misbehavior, reason, self.io);
+    }
+
     fn register_timer(
         &self, token: TimerToken, delay: Duration,
     ) -> Result<(), Error> {
@@ -2044,6 +2412,21 @@ impl<'a> NetworkContextTrait for NetworkContext<'a> {
             .write()
             .set_tag(peer, key, value);
     }
+
+    fn set_local_node_type(&self, node_type: &str) {
+        self.network_service
+            .update_local_record(|record| record.node_type = node_type.into());
+    }
+
+    fn set_local_snapshot_range(&self, range: Option<(u64, u64)>) {
+        self.network_service
+            .update_local_record(|record| record.snapshot_range = range);
+    }
+
+    fn get_peer_node_record(&self, peer: &NodeId) -> Option<NodeRecord> {
+        let discovery = self.network_service.discovery.lock();
+        discovery.as_ref()?.node_record(peer).cloned()
+    }
 }
 
 fn save_key(path: &Path, key: &Secret) {
@@ -3,7 +3,13 @@
 // See http://www.gnu.org/licenses/
 
 use crate::{
-    connection::{Connection, ConnectionDetails, SendQueueStatus, WriteStatus},
+    bandwidth::{ProtocolBandwidth, SessionBandwidth},
+    compression::{decompress, CompressionAlgorithm, CompressionConfig},
+    connection::{
+        Connection, ConnectionDetails, SendQueueStatus, WriteStatus,
+        MAX_PAYLOAD_SIZE,
+    },
+    encryption::{EncryptionMode, SessionSecrets},
     handshake::Handshake,
     node_table::{NodeEndpoint, NodeEntry, NodeId},
     parse_msg_id_leb128_2_bytes_at_most,
@@ -31,9 +37,19 @@ use std::{
 /// packet to exchange the supported protocols. Then, session is ready to send
 /// and receive protocol packets.
 ///
-/// Conflux do not use AES based encrypted connection to send protocol packets.
+/// By default, Conflux do not encrypt the connection to send protocol packets.
 /// This is because that Conflux has high TPS, and the encryption/decryption
-/// workloads are very heavy (about 20% CPU time in 3000 TPS).
+/// workloads are very heavy (about 20% CPU time in 3000 TPS). Encryption can
+/// be enabled with the encryption policy, e.g. for consortium networks. The
+/// encryption modes are exchanged in the Hello packet, and all the packets
+/// after the Hello packets are encrypted if negotiated.
+///
+/// If compression is configured, the supported algorithms are exchanged in
+/// the Hello packet, and large protocol packets are compressed with the
+/// preferred algorithm that the remote peer supports.
+///
+/// The bytes of protocol packets are accounted per protocol, and the packets
+/// over the configured egress limit of a protocol are refused.
 pub struct Session {
     /// Session information
     pub metadata: SessionMetadata,
@@ -47,6 +63,15 @@ pub struct Session {
     had_hello: Option<Instant>,
     /// Session is no longer active flag.
     expired: Option<Instant>,
+    compression_config: CompressionConfig,
+    /// Algorithm to compress the sent packets, negotiated in Hello.
+    compression: Option<CompressionAlgorithm>,
+    /// Encryption mode advertised in Hello.
+    encryption_mode: EncryptionMode,
+    /// Secrets established in handshake to encrypt the session.
+    secrets: Option<SessionSecrets>,
+    /// Bandwidth used by each protocol.
+    bandwidth: SessionBandwidth,
 
     // statistics for read/write
     last_read: Instant,
@@ -119,6 +144,14 @@ impl Session {
             sent_hello: Instant::now(),
             had_hello: None,
             expired: None,
+            compression_config: host.config.compression.clone(),
+            compression: None,
+            encryption_mode: host
+                .config
+                .encryption_policy
+                .mode(host.config.is_consortium),
+            secrets: None,
+            bandwidth: SessionBandwidth::new(&host.config.bandwidth),
             last_read: Instant::now(),
             last_write: (Instant::now(), WriteStatus::Complete),
         })
@@ -191,7 +224,8 @@ impl Session {
     }
 
     /// Complete the handshake process:
-    /// 1. For incoming session, check if the remote peer is blacklisted.
+    /// 1. For incoming session, check if the remote peer is blacklisted or not
+    ///    a member of the consortium.
     /// 2. Change the session state to `State::Session`.
     /// 3. Send Hello packet to remote peer.
     fn complete_handshake<Message>(
@@ -212,9 +246,39 @@ impl Session {
                 return Err(self.send_disconnect(DisconnectReason::Blacklisted));
             }
 
+            // refuse incoming session if the node is banned
+            if host.node_db.read().is_banned(&id) {
+                return Err(self.send_disconnect(DisconnectReason::Blacklisted));
+            }
+
+            // refuse incoming session if the node is not a member
+            if !host.membership.is_allowed(&id) {
+                return Err(self.send_disconnect(DisconnectReason::Custom(
+                    "not a member".into(),
+                )));
+            }
+
+            // refuse incoming session exempt from limits by the address of a
+            // static peer, but not from the static peer itself
+            if host.sessions.is_static(self.token())
+                && !host.peer_groups.contains(&id)
+            {
+                return Err(self.send_disconnect(DisconnectReason::Custom(
+                    "not a static peer".into(),
+                )));
+            }
+
             self.metadata.id = Some(id);
         }
 
+        // Encryption is not supported if the handshake bypassed cryptography.
+        self.secrets = wrapper
+            .get()
+            .secrets(self.metadata.originated, host.metadata.id());
+        if self.secrets.is_none() {
+            self.encryption_mode = EncryptionMode::Unsupported;
+        }
+
         // write HELLO packet to remote peer
         self.state = State::Session(wrapper.take().connection);
         self.write_hello(io, host)?;
@@ -274,6 +338,7 @@ impl Session {
     fn read_packet(
         &mut self, data: Bytes, host: &NetworkServiceInner,
     ) -> Result<SessionDataWithDisconnectInfo, Error> {
+        let size = data.len();
         let packet = SessionPacket::parse(data)?;
 
         // For protocol packet, the Hello packet should already been received.
@@ -316,15 +381,29 @@ impl Session {
                 );
                 Err(ErrorKind::Disconnect(reason).into())
             }
-            PACKET_USER => Ok(SessionDataWithDisconnectInfo {
-                session_data: SessionData::Message {
-                    data: packet.data.to_vec(),
-                    protocol: packet
-                        .protocol
-                        .expect("protocol should available for USER packet"),
-                },
-                token_to_disconnect: None,
-            }),
+            PACKET_USER => {
+                let protocol = packet
+                    .protocol
+                    .expect("protocol should available for USER packet");
+                self.bandwidth.on_ingress(&protocol, size);
+                let data = match packet.compression {
+                    None => packet.data.to_vec(),
+                    Some(algorithm) => {
+                        if !self.compression_config.is_supported(algorithm) {
+                            debug!(
+                                "read packet compressed with unsupported algorithm {}, session = {:?}",
+                                algorithm, self
+                            );
+                            bail!(ErrorKind::BadProtocol);
+                        }
+                        decompress(algorithm, &packet.data, MAX_PAYLOAD_SIZE)?
+                    }
+                };
+                Ok(SessionDataWithDisconnectInfo {
+                    session_data: SessionData::Message { data, protocol },
+                    token_to_disconnect: None,
+                })
+            }
             _ => {
                 debug!(
                     "read packet UNKNOWN, packet_id = {:?}, session = {:?}",
@@ -410,6 +489,44 @@ impl Session {
             return Err(self.send_disconnect(DisconnectReason::UselessPeer));
         }
 
+        // Peers without compression support send Hello without the list of
+        // compression algorithms.
+        if rlp.item_count()? > 3 {
+            let peer_algorithms: Vec<u8> = rlp.list_at(3)?;
+            self.compression =
+                self.compression_config.negotiate(&peer_algorithms);
+        }
+
+        // Peers without encryption support send Hello without the encryption
+        // mode.
+        let peer_encryption_mode = if rlp.item_count()? > 4 {
+            EncryptionMode::from_u8(rlp.val_at(4)?)
+        } else {
+            EncryptionMode::Unsupported
+        };
+        match self.encryption_mode.negotiate(peer_encryption_mode) {
+            Ok(true) => {
+                let cipher = self
+                    .secrets
+                    .as_ref()
+                    .expect("encryption is supported only with secrets")
+                    .cipher(self.metadata.originated);
+                // The Hello packet is already queued, so that only the packets
+                // after Hello are encrypted.
+                self.connection_mut().enable_encryption(cipher);
+            }
+            Ok(false) => {}
+            Err(e) => {
+                debug!(
+                    "failed to read hello, {}, self = {:?}, remote = {:?}, session = {:?}",
+                    e, self.encryption_mode, peer_encryption_mode, self
+                );
+                return Err(self.send_disconnect(DisconnectReason::Custom(
+                    "encryption mode mismatch".into(),
+                )));
+            }
+        }
+
         let mut hello_from = NodeEndpoint::from_rlp(&rlp.at(2)?)?;
         // Use the ip of the socket as endpoint ip directly.
         // We do not allow peers to specify the ip to avoid being used to DDoS
@@ -473,10 +590,25 @@ impl Session {
             return Err(ErrorKind::Expired.into());
         }
 
+        let (data, compression) = match (protocol, self.compression) {
+            (Some(ref protocol), Some(algorithm))
+                if self
+                    .compression_config
+                    .should_compress(protocol, data.len()) =>
+            {
+                match self.compression_config.compress(algorithm, &data) {
+                    Some(compressed) => (compressed, Some(algorithm)),
+                    None => (data, None),
+                }
+            }
+            _ => (data, None),
+        };
+
         Ok(SessionPacket::assemble(
             packet_id,
             self.metadata.peer_header_version,
             protocol,
+            compression,
             data,
         ))
     }
@@ -524,6 +656,9 @@ impl Session {
             &data,
         )?;
         let packet = self.prepare_packet(protocol, packet_id, data)?;
+        if let Some(ref protocol) = protocol {
+            self.bandwidth.on_egress(protocol, packet.len())?;
+        }
         self.connection_mut().send(io, packet, priority)
     }
 
@@ -539,6 +674,9 @@ impl Session {
             &data,
         )?;
         let packet = self.prepare_packet(protocol, packet_id, data)?;
+        if let Some(ref protocol) = protocol {
+            self.bandwidth.on_egress(protocol, packet.len())?;
+        }
         self.connection_mut().write_raw_data(packet)
     }
 
@@ -559,10 +697,30 @@ impl Session {
         &mut self, io: &IoContext<Message>, host: &NetworkServiceInner,
     ) -> Result<(), Error> {
         debug!("Sending Hello, session = {:?}", self);
-        let mut rlp = RlpStream::new_list(3);
+        // The list of compression algorithms and the encryption mode are only
+        // appended if enabled, to stay compatible with peers that expect
+        // exactly 3 items.
+        let encryption_enabled =
+            self.encryption_mode != EncryptionMode::Unsupported;
+        let compression_enabled =
+            encryption_enabled || self.compression_config.is_enabled();
+        let item_count = if encryption_enabled {
+            5
+        } else if compression_enabled {
+            4
+        } else {
+            3
+        };
+        let mut rlp = RlpStream::new_list(item_count);
         rlp.append(&host.metadata.network_id);
         rlp.append_list(&*host.metadata.protocols.read());
         host.metadata.public_endpoint.to_rlp_list(&mut rlp);
+        if compression_enabled {
+            rlp.append_list(&self.compression_config.algorithm_ids());
+        }
+        if encryption_enabled {
+            rlp.append(&(self.encryption_mode as u8));
+        }
         self.send_packet(
             io,
             None,
@@ -601,6 +759,9 @@ impl Session {
             last_read: format!("{:?}", self.last_read.elapsed()),
             last_write: format!("{:?}", self.last_write.0.elapsed()),
             last_write_status: format!("{:?}", self.last_write.1),
+            compression: self.compression.map(|a| a.to_string()),
+            encrypted: self.connection().is_encrypted(),
+            bandwidth: self.bandwidth.details(),
         }
     }
 
@@ -648,6 +809,11 @@ pub struct SessionDetails {
     pub last_read: String,
     pub last_write: String,
     pub last_write_status: String,
+    /// Algorithm to compress the packets sent to the peer.
+    pub compression: Option<String>,
+    pub encrypted: bool,
+    /// Bandwidth used by each protocol.
+    pub bandwidth: Vec<ProtocolBandwidth>,
 }
 
 /// MovableWrapper is a util to move a value out of a struct.
@@ -691,10 +857,13 @@ impl<T> MovableWrapper<T> {
 ///
 /// The header format is:
 /// [  extensions (0 to more bytes) || protocol (0 or 3 bytes if protocol_flag)
-///   || reserved (3 bit), has_extension (1 bit), header_version (3 bit),
-///      protocol_flag (1 bit)
+///   || reserved (1 bit), compression (2 bit), has_extension (1 bit),
+///      header_version (3 bit), protocol_flag (1 bit)
 ///   || packet_id]
 ///
+/// The compression is the id of the algorithm used to compress the data, or 0
+/// if the data is not compressed. Only protocol packets can be compressed.
+///
 /// The protocol format is:
 ///     [ protocol_id (3 bytes)]
 ///
@@ -709,13 +878,14 @@ struct SessionPacket {
     pub data: Bytes,
     pub header_version: u8,
     pub extensions: Vec<Vec<u8>>,
+    pub compression: Option<CompressionAlgorithm>,
 }
 
 impl SessionPacket {
     // data + Option<protocol> + protocol_flag + packet_id
     fn assemble(
         id: u8, header_version: u8, protocol: Option<ProtocolId>,
-        mut data: Vec<u8>,
+        compression: Option<CompressionAlgorithm>, mut data: Vec<u8>,
     ) -> Vec<u8>
     {
         let mut protocol_flag = 0;
@@ -724,7 +894,9 @@ impl SessionPacket {
             protocol_flag = 1;
         }
 
-        let header_byte = (header_version << 1) + protocol_flag;
+        let compression_id = compression.map_or(0, |a| a.id());
+        let header_byte =
+            (compression_id << 5) + (header_version << 1) + protocol_flag;
         data.push(header_byte);
         data.push(id);
 
@@ -754,6 +926,17 @@ impl SessionPacket {
             return Err(ErrorKind::BadProtocol.into());
         }
         let has_extension = (header_byte & 0x10) >> 4;
+        let compression_id = (header_byte & 0x60) >> 5;
+        let compression = match compression_id {
+            0 => None,
+            id => match CompressionAlgorithm::from_id(id) {
+                Some(algorithm) => Some(algorithm),
+                None => {
+                    debug!("unsupported compression algorithm {}", id);
+                    return Err(ErrorKind::BadProtocol.into());
+                }
+            },
+        };
 
         // without protocol
         if protocol_flag == 0 {
@@ -762,6 +945,11 @@ impl SessionPacket {
                 return Err(ErrorKind::BadProtocol.into());
             }
 
+            if compression.is_some() {
+                debug!("failed to parse session packet, compressed packet without protocol");
+                return Err(ErrorKind::BadProtocol.into());
+            }
+
             let (data, extensions) =
                 Self::parse_extensions(data, has_extension != 0)?;
 
@@ -771,6 +959,7 @@ impl SessionPacket {
                 protocol: None,
                 data,
                 extensions,
+                compression: None,
             });
         }
 
@@ -799,6 +988,7 @@ impl SessionPacket {
             header_version,
             data,
             extensions,
+            compression,
         })
     }
 
@@ -826,10 +1016,11 @@ impl fmt::Debug for SessionPacket {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "SessionPacket {{ id: {}, protocol: {:?}, date_len: {} }}",
+            "SessionPacket {{ id: {}, protocol: {:?}, date_len: {}, compression: {:?} }}",
             self.id,
             self.protocol,
-            self.data.len()
+            self.data.len(),
+            self.compression
         )
     }
 }
@@ -840,17 +1031,32 @@ mod tests {
 
     #[test]
     fn test_packet_assemble() {
-        let packet =
-            SessionPacket::assemble(5, PACKET_HEADER_VERSION, None, vec![1, 3]);
+        let packet = SessionPacket::assemble(
+            5,
+            PACKET_HEADER_VERSION,
+            None,
+            None,
+            vec![1, 3],
+        );
         assert_eq!(packet, vec![1, 3, 0, 5]);
 
         let packet = SessionPacket::assemble(
             6,
             PACKET_HEADER_VERSION,
             Some([8; 3]),
+            None,
             vec![2, 4],
         );
         assert_eq!(packet, vec![2, 4, 8, 8, 8, 1, 6]);
+
+        let packet = SessionPacket::assemble(
+            PACKET_USER,
+            PACKET_HEADER_VERSION,
+            Some([8; 3]),
+            Some(CompressionAlgorithm::Zstd),
+            vec![2, 4],
+        );
+        assert_eq!(packet, vec![2, 4, 8, 8, 8, 0x41, PACKET_USER]);
     }
 
     #[test]
@@ -877,6 +1083,7 @@ mod tests {
                 protocol: None,
                 data: vec![1, 2].into(),
                 extensions: vec![],
+                compression: None,
             }
         );
 
@@ -900,7 +1107,24 @@ mod tests {
                 protocol: Some([3; 3]),
                 data: vec![1, 9].into(),
                 extensions: vec![],
+                compression: None,
             }
         );
+
+        // compressed user packet
+        let packet =
+            SessionPacket::parse(vec![1, 9, 3, 3, 3, 0x21, PACKET_USER].into())
+                .unwrap();
+        assert_eq!(packet.compression, Some(CompressionAlgorithm::Snappy));
+        assert_eq!(packet.data, Bytes::from(vec![1, 9]));
+
+        // unknown compression algorithm
+        assert!(SessionPacket::parse(
+            vec![1, 9, 3, 3, 3, 0x61, PACKET_USER].into()
+        )
+        .is_err());
+
+        // compressed packet without protocol
+        assert!(SessionPacket::parse(vec![1, 2, 0x20, 20].into()).is_err());
     }
 }
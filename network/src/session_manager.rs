@@ -31,6 +31,16 @@ use std::{
 ///
 /// The session manager also limits the maximum number of incoming TCP
 /// connections, so as to establish some trusted outgoing connections.
+///
+/// In consortium networks with membership enabled, sessions are only created
+/// with the members. Ingress sessions are checked once the node id is known
+/// at the end of the handshake.
+///
+/// Sessions with static peers are exempt from the ingress and IP limits, and
+/// not counted in `stat`, so that static peers always get their slots. An
+/// ingress session is regarded as static if it comes from the address of a
+/// static peer, and is checked against the node id at the end of the
+/// handshake.
 pub struct SessionManager {
     sessions: RwLock<Slab<Arc<RwLock<Session>>>>,
     capacity: usize,
@@ -47,6 +57,8 @@ pub struct SessionManager {
     /// session indices
     node_id_index: RwLock<HashMap<NodeId, usize>>,
     ip_limit: RwLock<Box<dyn SessionIpLimit>>,
+    /// indices of the sessions with static peers.
+    static_sessions: RwLock<HashSet<usize>>,
     tag_index: RwLock<SessionTagIndex>,
 }
 
@@ -65,6 +77,7 @@ impl SessionManager {
             cur_ingress_sessions: AtomicUsize::new(0),
             node_id_index: RwLock::new(HashMap::new()),
             ip_limit: RwLock::new(new_session_ip_limit(ip_limit_config)),
+            static_sessions: Default::default(),
             tag_index: Default::default(),
         }
     }
@@ -105,13 +118,20 @@ impl SessionManager {
         self.tag_index.read().count_with_tag(key, value)
     }
 
-    /// Retrieves the session count of handshakes, egress and ingress.
+    /// Retrieves the session count of handshakes, egress and ingress, excluding
+    /// the sessions with static peers.
     pub fn stat(&self) -> (usize, usize, usize) {
         let mut handshakes = 0;
         let mut egress = 0;
         let mut ingress = 0;
 
-        for (_, s) in self.sessions.read().iter() {
+        let sessions = self.sessions.read();
+        let static_sessions = self.static_sessions.read();
+        for (idx, s) in sessions.iter() {
+            if static_sessions.contains(&idx) {
+                continue;
+            }
+
             match s.try_read() {
                 Some(ref s) if s.is_ready() && s.metadata.originated => {
                     egress += 1
@@ -136,6 +156,47 @@ impl SessionManager {
         self.node_id_index.read().get(id).cloned()
     }
 
+    /// Check if the session of specified index is with a static peer.
+    pub fn is_static(&self, idx: usize) -> bool {
+        self.static_sessions.read().contains(&idx)
+    }
+
+    /// Count the session of specified index against the limits again, e.g.
+    /// when the node is removed from the static peers. Returns false if the
+    /// session is not allowed by the IP policy and should be disconnected.
+    pub fn unset_static(&self, idx: usize) -> bool {
+        let sessions = self.sessions.read();
+        let (ip, originated) = match sessions.get(idx) {
+            Some(session) => {
+                let session = session.read();
+                (session.address().ip(), session.metadata.originated)
+            }
+            None => return true,
+        };
+        self.unset_static_session(idx, ip, originated)
+    }
+
+    fn unset_static_session(
+        &self, idx: usize, ip: IpAddr, originated: bool,
+    ) -> bool {
+        let mut static_sessions = self.static_sessions.write();
+        if !static_sessions.contains(&idx) {
+            return true;
+        }
+
+        // keep the session static until disconnected, so that it is not
+        // removed from the IP limit without being added.
+        if !self.ip_limit.write().add(ip) {
+            return false;
+        }
+
+        static_sessions.remove(&idx);
+        if !originated {
+            self.cur_ingress_sessions.fetch_add(1, Ordering::Relaxed);
+        }
+        true
+    }
+
     /// Check if the specified IP address is allowed to create a new session.
     pub fn is_ip_allowed(&self, ip: &IpAddr) -> bool {
         self.ip_limit.read().is_allowed(ip)
@@ -153,13 +214,26 @@ impl SessionManager {
             address, id
         );
 
+        // check static peers before locking the sessions, since static peers
+        // are reconnected while checking the sessions.
+        let static_peers = host.peer_groups.count();
+        let is_static_peer = match id {
+            Some(node_id) => host.peer_groups.contains(node_id),
+            None => host.peer_groups.contains_ip(&address.ip()),
+        };
+
         let mut sessions = self.sessions.write();
         let mut node_id_index = self.node_id_index.write();
         let mut ip_limit = self.ip_limit.write();
+        let mut static_sessions = self.static_sessions.write();
+
+        // at most one session for each static peer is exempt from limits.
+        let is_static = is_static_peer
+            && (id.is_some() || static_sessions.len() < static_peers);
 
         // limits ingress sessions whose node id is `None`.
         let ingress = self.cur_ingress_sessions.load(Ordering::Relaxed);
-        if id.is_none() && ingress >= self.max_ingress_sessions {
+        if id.is_none() && !is_static && ingress >= self.max_ingress_sessions {
             debug!("SessionManager.create: leave on maximum ingress sessions reached");
             return Err(format!(
                 "maximum ingress sessions reached, current = {}, max = {}",
@@ -169,6 +243,14 @@ impl SessionManager {
 
         // ensure the node id is unique if specified.
         if let Some(node_id) = id {
+            if !host.membership.is_allowed(node_id) {
+                debug!("SessionManager.create: leave on node not a member");
+                return Err(format!(
+                    "node is not a member, nodeId = {:?}",
+                    node_id
+                ));
+            }
+
             if node_id_index.contains_key(node_id) {
                 debug!(
                     "SessionManager.create: leave on node_id already exists"
@@ -182,7 +264,7 @@ impl SessionManager {
 
         // validate against node IP policy.
         let ip = address.ip();
-        if !ip_limit.is_allowed(&ip) {
+        if !is_static && !ip_limit.is_allowed(&ip) {
             debug!("SessionManager.create: leave on IP policy limited");
             return Err(format!(
                 "IP policy limited, nodeId = {:?}, addr = {:?}",
@@ -219,10 +301,14 @@ impl SessionManager {
             node_id_index.insert(node_id.clone(), index);
         }
 
-        assert!(ip_limit.add(ip));
+        if is_static {
+            static_sessions.insert(index);
+        } else {
+            assert!(ip_limit.add(ip));
 
-        if id.is_none() {
-            self.cur_ingress_sessions.fetch_add(1, Ordering::Relaxed);
+            if id.is_none() {
+                self.cur_ingress_sessions.fetch_add(1, Ordering::Relaxed);
+            }
         }
 
         debug!("SessionManager.create: leave");
@@ -248,10 +334,12 @@ impl SessionManager {
                 }
             }
 
-            assert!(self.ip_limit.write().remove(&session.address().ip()));
+            if !self.static_sessions.write().remove(&session.token()) {
+                assert!(self.ip_limit.write().remove(&session.address().ip()));
 
-            if !session.metadata.originated {
-                self.cur_ingress_sessions.fetch_sub(1, Ordering::Relaxed);
+                if !session.metadata.originated {
+                    self.cur_ingress_sessions.fetch_sub(1, Ordering::Relaxed);
+                }
             }
 
             self.tag_index.write().remove(session.token());
@@ -381,7 +469,11 @@ impl SessionTagIndex {
 
 #[cfg(test)]
 mod tests {
-    use crate::session_manager::SessionTagIndex;
+    use crate::{
+        ip::SessionIpLimitConfig,
+        session_manager::{SessionManager, SessionTagIndex},
+    };
+    use std::{convert::TryFrom, net::IpAddr, sync::atomic::Ordering};
 
     #[test]
     fn test_tag_index() {
@@ -430,4 +522,28 @@ mod tests {
         assert_eq!(index.count_with_tag(&k1, &v2), 0);
         assert_eq!(index.count_with_tag(&k2, &v1), 0);
     }
+
+    #[test]
+    fn test_unset_static() {
+        let config = SessionIpLimitConfig::try_from("1,0,0,0".to_string())
+            .expect("valid config");
+        let manager = SessionManager::new(0, 10, 10, &config);
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+
+        // not a static session
+        assert!(manager.unset_static_session(0, ip, true));
+
+        // static ingress session is counted against the limits
+        manager.static_sessions.write().insert(0);
+        assert!(manager.unset_static_session(0, ip, false));
+        assert!(!manager.is_static(0));
+        assert!(!manager.is_ip_allowed(&ip));
+        assert_eq!(manager.cur_ingress_sessions.load(Ordering::Relaxed), 1);
+
+        // IP quota is used up, so the session is kept static
+        manager.static_sessions.write().insert(1);
+        assert!(!manager.unset_static_session(1, ip, true));
+        assert!(manager.is_static(1));
+        assert_eq!(manager.cur_ingress_sessions.load(Ordering::Relaxed), 1);
+    }
 }
@@ -20,19 +20,30 @@
 
 extern crate cfxcore_accounts;
 
-use super::helpers::{password_from_file, password_prompt};
+use super::helpers::{input_password, password_from_file, password_prompt};
+use cfx_types::Address;
+use cfxkey::Password;
 use cfxstore::{
     accounts_dir::RootDiskDirectory, import_account, import_accounts,
 };
 use clap;
 use client::accounts::{account_provider, keys_dir, keys_path};
-use std::path::PathBuf;
+use std::{fs, path::PathBuf};
 
 #[derive(Debug, PartialEq)]
 pub enum AccountCmd {
     New(NewAccount),
     List(ListAccounts),
     Import(ImportAccounts),
+    ImportGeth(ImportGethAccounts),
+    Rename(RenameAccount),
+    ChangePassword(ChangePassword),
+    Export(ExportAccount),
+    NewVault(NewVault),
+    ListVaults(ListVaults),
+    ChangeVaultPassword(ChangeVaultPassword),
+    DeleteVault(DeleteVault),
+    Move(MoveAccount),
 }
 
 #[derive(Debug, PartialEq)]
@@ -87,11 +98,223 @@ impl ImportAccounts {
     }
 }
 
+#[derive(Debug, PartialEq)]
+pub struct ImportGethAccounts {
+    pub path: Option<String>,
+    pub addresses: Vec<String>,
+    pub testnet: bool,
+}
+
+impl ImportGethAccounts {
+    pub fn new(matches: &clap::ArgMatches) -> Self {
+        let addresses = matches
+            .values_of("address")
+            .map(|values| values.map(|s| s.to_string()).collect())
+            .unwrap_or_default();
+        Self {
+            path: None,
+            addresses,
+            testnet: matches.is_present("testnet"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RenameAccount {
+    pub path: Option<String>,
+    pub address: String,
+    pub name: String,
+}
+
+impl RenameAccount {
+    pub fn new(matches: &clap::ArgMatches) -> Self {
+        Self {
+            path: None,
+            address: required_value(matches, "address"),
+            name: required_value(matches, "name"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ChangePassword {
+    pub path: Option<String>,
+    pub address: String,
+    pub password_file: Option<String>,
+    pub new_password_file: Option<String>,
+}
+
+impl ChangePassword {
+    pub fn new(matches: &clap::ArgMatches) -> Self {
+        Self {
+            path: None,
+            address: required_value(matches, "address"),
+            password_file: matches.value_of("password").map(Into::into),
+            new_password_file: matches.value_of("new-password").map(Into::into),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ExportAccount {
+    pub path: Option<String>,
+    pub address: String,
+    pub password_file: Option<String>,
+    pub output: Option<String>,
+}
+
+impl ExportAccount {
+    pub fn new(matches: &clap::ArgMatches) -> Self {
+        Self {
+            path: None,
+            address: required_value(matches, "address"),
+            password_file: matches.value_of("password").map(Into::into),
+            output: matches.value_of("output").map(Into::into),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct NewVault {
+    pub path: Option<String>,
+    pub name: String,
+    pub password_file: Option<String>,
+}
+
+impl NewVault {
+    pub fn new(matches: &clap::ArgMatches) -> Self {
+        Self {
+            path: None,
+            name: required_value(matches, "vault"),
+            password_file: matches.value_of("vault-password").map(Into::into),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ListVaults {
+    pub path: Option<String>,
+}
+
+impl ListVaults {
+    pub fn new(_matches: &clap::ArgMatches) -> Self { Self { path: None } }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ChangeVaultPassword {
+    pub path: Option<String>,
+    pub name: String,
+    pub password_file: Option<String>,
+    pub new_password_file: Option<String>,
+}
+
+impl ChangeVaultPassword {
+    pub fn new(matches: &clap::ArgMatches) -> Self {
+        Self {
+            path: None,
+            name: required_value(matches, "vault"),
+            password_file: matches.value_of("vault-password").map(Into::into),
+            new_password_file: matches.value_of("new-password").map(Into::into),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DeleteVault {
+    pub path: Option<String>,
+    pub name: String,
+    pub password_file: Option<String>,
+}
+
+impl DeleteVault {
+    pub fn new(matches: &clap::ArgMatches) -> Self {
+        Self {
+            path: None,
+            name: required_value(matches, "vault"),
+            password_file: matches.value_of("vault-password").map(Into::into),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct MoveAccount {
+    pub path: Option<String>,
+    pub address: String,
+    /// The destination vault, or the root keystore if `None`.
+    pub vault: Option<String>,
+    pub vault_password_file: Option<String>,
+    pub from_vault: Option<String>,
+    pub from_vault_password_file: Option<String>,
+}
+
+impl MoveAccount {
+    pub fn new(matches: &clap::ArgMatches) -> Self {
+        Self {
+            path: None,
+            address: required_value(matches, "address"),
+            vault: matches.value_of("vault").map(Into::into),
+            vault_password_file: matches
+                .value_of("vault-password")
+                .map(Into::into),
+            from_vault: matches.value_of("from-vault").map(Into::into),
+            from_vault_password_file: matches
+                .value_of("from-vault-password")
+                .map(Into::into),
+        }
+    }
+}
+
+fn required_value(matches: &clap::ArgMatches, name: &str) -> String {
+    matches
+        .value_of(name)
+        .expect("CLI argument is required; qed")
+        .to_string()
+}
+
 pub fn execute(cmd: AccountCmd) -> Result<String, String> {
     match cmd {
         AccountCmd::New(new_cmd) => new(new_cmd),
         AccountCmd::List(list_cmd) => list(list_cmd),
         AccountCmd::Import(import_cmd) => import(import_cmd),
+        AccountCmd::ImportGeth(import_cmd) => import_geth(import_cmd),
+        AccountCmd::Rename(rename_cmd) => rename(rename_cmd),
+        AccountCmd::ChangePassword(change_cmd) => change_password(change_cmd),
+        AccountCmd::Export(export_cmd) => export(export_cmd),
+        AccountCmd::NewVault(vault_cmd) => new_vault(vault_cmd),
+        AccountCmd::ListVaults(vault_cmd) => list_vaults(vault_cmd),
+        AccountCmd::ChangeVaultPassword(vault_cmd) => {
+            change_vault_password(vault_cmd)
+        }
+        AccountCmd::DeleteVault(vault_cmd) => delete_vault(vault_cmd),
+        AccountCmd::Move(move_cmd) => move_account(move_cmd),
+    }
+}
+
+fn parse_address(address: &str) -> Result<Address, String> {
+    address
+        .trim_start_matches("0x")
+        .parse()
+        .map_err(|_| format!("Invalid hex address: {}", address))
+}
+
+/// Read the password from `file`, or ask for it if no file is given.
+fn read_password(
+    file: Option<String>, subject: &str,
+) -> Result<Password, String> {
+    match file {
+        Some(file) => password_from_file(file),
+        None => {
+            println!("Password of {}:", subject);
+            input_password()
+        }
+    }
+}
+
+/// Read a new password from `file`, or ask for it twice if no file is given.
+fn read_new_password(file: Option<String>) -> Result<Password, String> {
+    match file {
+        Some(file) => password_from_file(file),
+        None => password_prompt(),
     }
 }
 
@@ -153,3 +376,316 @@ fn import(import_cmd: ImportAccounts) -> Result<String, String> {
 
     Ok(format!("{} account(s) imported", imported))
 }
+
+fn import_geth(import_cmd: ImportGethAccounts) -> Result<String, String> {
+    let acc_provider = account_provider(
+        import_cmd.path,
+        None, /* sstore_iterations */
+        None, /* refresh_time */
+    )?;
+
+    let desired = if import_cmd.addresses.is_empty() {
+        acc_provider.list_geth_accounts(import_cmd.testnet)
+    } else {
+        import_cmd
+            .addresses
+            .iter()
+            .map(|a| parse_address(a))
+            .collect::<Result<Vec<_>, _>>()?
+    };
+    let imported = acc_provider
+        .import_geth_accounts(desired, import_cmd.testnet)
+        .map_err(|e| format!("Importing geth accounts failed: {}", e))?;
+
+    Ok(format!("{} account(s) imported", imported.len()))
+}
+
+fn rename(rename_cmd: RenameAccount) -> Result<String, String> {
+    let address = parse_address(&rename_cmd.address)?;
+    let acc_provider = account_provider(rename_cmd.path, None, None)?;
+
+    acc_provider
+        .set_account_name(address, rename_cmd.name)
+        .map_err(|e| format!("Could not rename account: {}", e))?;
+    Ok(format!("0x{:x}", address))
+}
+
+fn change_password(change_cmd: ChangePassword) -> Result<String, String> {
+    let address = parse_address(&change_cmd.address)?;
+    let acc_provider = account_provider(change_cmd.path, None, None)?;
+
+    let password = read_password(change_cmd.password_file, "the account")?;
+    let new_password = read_new_password(change_cmd.new_password_file)?;
+    acc_provider
+        .change_password(&address, password, new_password)
+        .map_err(|e| format!("Could not change password: {}", e))?;
+    Ok(format!("0x{:x}", address))
+}
+
+fn export(export_cmd: ExportAccount) -> Result<String, String> {
+    let address = parse_address(&export_cmd.address)?;
+    let acc_provider = account_provider(export_cmd.path, None, None)?;
+
+    let password = read_password(export_cmd.password_file, "the account")?;
+    let key_file = acc_provider
+        .export_account(&address, password)
+        .map_err(|e| format!("Could not export account: {}", e))?;
+    let json = serde_json::to_string(&key_file)
+        .map_err(|e| format!("Could not serialize keystore file: {}", e))?;
+
+    match export_cmd.output {
+        Some(output) => {
+            fs::write(&output, json).map_err(|e| {
+                format!("Could not write keystore file {}: {}", output, e)
+            })?;
+            Ok(format!("Keystore file written to {}", output))
+        }
+        None => Ok(json),
+    }
+}
+
+fn new_vault(vault_cmd: NewVault) -> Result<String, String> {
+    let acc_provider = account_provider(vault_cmd.path, None, None)?;
+
+    let password = read_new_password(vault_cmd.password_file)?;
+    acc_provider
+        .create_vault(&vault_cmd.name, &password)
+        .map_err(|e| format!("Could not create vault: {}", e))?;
+    Ok(vault_cmd.name)
+}
+
+fn list_vaults(vault_cmd: ListVaults) -> Result<String, String> {
+    let acc_provider = account_provider(vault_cmd.path, None, None)?;
+
+    let vaults = acc_provider
+        .list_vaults()
+        .map_err(|e| format!("Could not list vaults: {}", e))?;
+    Ok(vaults.join("\n"))
+}
+
+fn change_vault_password(
+    vault_cmd: ChangeVaultPassword,
+) -> Result<String, String> {
+    let acc_provider = account_provider(vault_cmd.path, None, None)?;
+
+    let password = read_password(vault_cmd.password_file, "the vault")?;
+    acc_provider
+        .open_vault(&vault_cmd.name, &password)
+        .map_err(|e| format!("Could not open vault: {}", e))?;
+    let new_password = read_new_password(vault_cmd.new_password_file)?;
+    acc_provider
+        .change_vault_password(&vault_cmd.name, &new_password)
+        .map_err(|e| format!("Could not change vault password: {}", e))?;
+    Ok(vault_cmd.name)
+}
+
+fn delete_vault(vault_cmd: DeleteVault) -> Result<String, String> {
+    let acc_provider = account_provider(vault_cmd.path, None, None)?;
+
+    let password = read_password(vault_cmd.password_file, "the vault")?;
+    acc_provider
+        .delete_vault(&vault_cmd.name, &password)
+        .map_err(|e| format!("Could not delete vault: {}", e))?;
+    Ok(vault_cmd.name)
+}
+
+fn move_account(move_cmd: MoveAccount) -> Result<String, String> {
+    let address = parse_address(&move_cmd.address)?;
+    let acc_provider = account_provider(move_cmd.path, None, None)?;
+
+    // Both vaults need to be opened so that the account can be found and
+    // re-encrypted with the password of the destination vault.
+    if let Some(from_vault) = &move_cmd.from_vault {
+        let password = read_password(
+            move_cmd.from_vault_password_file,
+            "the source vault",
+        )?;
+        acc_provider
+            .open_vault(from_vault, &password)
+            .map_err(|e| {
+                format!("Could not open vault {}: {}", from_vault, e)
+            })?;
+    }
+    let vault = match move_cmd.vault {
+        Some(vault) => {
+            let password = read_password(
+                move_cmd.vault_password_file,
+                "the destination vault",
+            )?;
+            acc_provider.open_vault(&vault, &password).map_err(|e| {
+                format!("Could not open vault {}: {}", vault, e)
+            })?;
+            vault
+        }
+        // An empty vault name means the root keystore.
+        None => String::new(),
+    };
+    acc_provider
+        .change_vault(address, &vault)
+        .map_err(|e| format!("Could not move account: {}", e))?;
+    Ok(format!("0x{:x}", address))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, path::Path};
+
+    struct TempKeysDir(PathBuf);
+
+    impl TempKeysDir {
+        fn new(name: &str) -> Self {
+            let dir = env::temp_dir().join(format!(
+                "conflux_account_cmd_{}_{}",
+                name,
+                std::process::id()
+            ));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            TempKeysDir(dir)
+        }
+
+        fn keys(&self) -> Option<String> {
+            Some(self.0.join("keys").to_str().unwrap().into())
+        }
+
+        fn password_file(&self, password: &str) -> Option<String> {
+            let path = self.0.join(format!("{}.pwd", password));
+            fs::write(&path, password).unwrap();
+            Some(path.to_str().unwrap().into())
+        }
+    }
+
+    impl Drop for TempKeysDir {
+        fn drop(&mut self) { let _ = fs::remove_dir_all(&self.0); }
+    }
+
+    fn new_account(dir: &TempKeysDir, password: &str) -> String {
+        execute(AccountCmd::New(NewAccount {
+            iterations: 1024,
+            path: dir.keys(),
+            password_file: dir.password_file(password),
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_rename_change_password_and_export() {
+        let dir = TempKeysDir::new("export");
+        let address = new_account(&dir, "password1");
+
+        execute(AccountCmd::Rename(RenameAccount {
+            path: dir.keys(),
+            address: address.clone(),
+            name: "alice".into(),
+        }))
+        .unwrap();
+        assert_eq!(
+            execute(AccountCmd::ChangePassword(ChangePassword {
+                path: dir.keys(),
+                address: address.clone(),
+                password_file: dir.password_file("password1"),
+                new_password_file: dir.password_file("password2"),
+            })),
+            Ok(address.clone())
+        );
+
+        let export = |password: &str, output: Option<String>| {
+            execute(AccountCmd::Export(ExportAccount {
+                path: dir.keys(),
+                address: address.clone(),
+                password_file: dir.password_file(password),
+                output,
+            }))
+        };
+        // The old password no longer works.
+        assert!(export("password1", None).is_err());
+        let key_file: serde_json::Value =
+            serde_json::from_str(&export("password2", None).unwrap()).unwrap();
+        assert_eq!(key_file["name"], "alice");
+        assert_eq!(
+            key_file["address"],
+            address.trim_start_matches("0x").to_string()
+        );
+
+        let output = dir.0.join("exported.json");
+        export("password2", Some(output.to_str().unwrap().into())).unwrap();
+        assert!(Path::new(&output).is_file());
+
+        // Invalid addresses are rejected before touching the key store.
+        assert!(execute(AccountCmd::Rename(RenameAccount {
+            path: dir.keys(),
+            address: "0xinvalid".into(),
+            name: "bob".into(),
+        }))
+        .is_err());
+    }
+
+    #[test]
+    fn test_vault_management() {
+        let dir = TempKeysDir::new("vault");
+        let list_vaults = || {
+            execute(AccountCmd::ListVaults(ListVaults { path: dir.keys() }))
+                .unwrap()
+        };
+        let delete_vault = |password: &str| {
+            execute(AccountCmd::DeleteVault(DeleteVault {
+                path: dir.keys(),
+                name: "vault1".into(),
+                password_file: dir.password_file(password),
+            }))
+        };
+
+        execute(AccountCmd::NewVault(NewVault {
+            path: dir.keys(),
+            name: "vault1".into(),
+            password_file: dir.password_file("vault_password1"),
+        }))
+        .unwrap();
+        assert_eq!(list_vaults(), "vault1");
+
+        execute(AccountCmd::ChangeVaultPassword(ChangeVaultPassword {
+            path: dir.keys(),
+            name: "vault1".into(),
+            password_file: dir.password_file("vault_password1"),
+            new_password_file: dir.password_file("vault_password2"),
+        }))
+        .unwrap();
+
+        // Move an account into the vault.
+        let address = new_account(&dir, "password");
+        execute(AccountCmd::Move(MoveAccount {
+            path: dir.keys(),
+            address: address.clone(),
+            vault: Some("vault1".into()),
+            vault_password_file: dir.password_file("vault_password2"),
+            from_vault: None,
+            from_vault_password_file: None,
+        }))
+        .unwrap();
+        let list_root = || {
+            execute(AccountCmd::List(ListAccounts { path: dir.keys() }))
+                .unwrap()
+        };
+        assert_eq!(list_root(), "");
+
+        // A vault with accounts, or with a wrong password, is not deleted.
+        assert!(delete_vault("vault_password1").is_err());
+        assert!(delete_vault("vault_password2").is_err());
+        assert_eq!(list_vaults(), "vault1");
+
+        // Move the account back to the root keystore and delete the vault.
+        execute(AccountCmd::Move(MoveAccount {
+            path: dir.keys(),
+            address: address.clone(),
+            vault: None,
+            vault_password_file: None,
+            from_vault: Some("vault1".into()),
+            from_vault_password_file: dir.password_file("vault_password2"),
+        }))
+        .unwrap();
+        assert_eq!(list_root(), address);
+        assert_eq!(delete_vault("vault_password2"), Ok("vault1".into()));
+        assert_eq!(list_vaults(), "");
+    }
+}
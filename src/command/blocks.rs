@@ -0,0 +1,103 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfxcore::sync::block_archive::{export_blocks, BlockImportProgress};
+use clap;
+use client::{
+    common::{import_blocks_offline, open_ledger_offline},
+    configuration::Configuration,
+};
+use std::path::Path;
+
+#[derive(Debug, PartialEq)]
+pub enum BlocksCmd {
+    Export(ExportBlocks),
+    Import(ImportBlocks),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ExportBlocks {
+    pub from_epoch: u64,
+    pub to_epoch: u64,
+    pub output: String,
+}
+
+impl ExportBlocks {
+    pub fn new(matches: &clap::ArgMatches) -> Result<Self, String> {
+        let epoch = |name: &str| -> Result<u64, String> {
+            let epoch = matches.value_of(name).unwrap();
+            epoch
+                .parse()
+                .map_err(|_| format!("Invalid epoch number: {}", epoch))
+        };
+        let from_epoch = epoch("from-epoch")?;
+        let to_epoch = epoch("to-epoch")?;
+        if from_epoch > to_epoch {
+            return Err(format!(
+                "from-epoch {} is larger than to-epoch {}",
+                from_epoch, to_epoch
+            ));
+        }
+        Ok(Self {
+            from_epoch,
+            to_epoch,
+            output: matches.value_of("output").unwrap().to_string(),
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ImportBlocks {
+    pub input: String,
+}
+
+impl ImportBlocks {
+    pub fn new(matches: &clap::ArgMatches) -> Self {
+        Self {
+            input: matches.value_of("input").unwrap().to_string(),
+        }
+    }
+}
+
+pub fn execute(cmd: BlocksCmd, conf: &Configuration) -> Result<String, String> {
+    match cmd {
+        BlocksCmd::Export(export_cmd) => export(export_cmd, conf),
+        BlocksCmd::Import(import_cmd) => import(import_cmd, conf),
+    }
+}
+
+fn export(cmd: ExportBlocks, conf: &Configuration) -> Result<String, String> {
+    let (db_manager, _) = open_ledger_offline(conf)?;
+    let exported = export_blocks(
+        &db_manager,
+        cmd.from_epoch,
+        cmd.to_epoch,
+        Path::new(&cmd.output),
+    )
+    .map_err(|e| format!("Failed to export blocks: {}", e))?;
+    Ok(format!(
+        "Exported {} blocks of epochs {} to {} to {}",
+        exported, cmd.from_epoch, cmd.to_epoch, cmd.output
+    ))
+}
+
+fn import(cmd: ImportBlocks, conf: &Configuration) -> Result<String, String> {
+    let progress =
+        import_blocks_offline(conf, Path::new(&cmd.input), |progress| {
+            println!("{}", format_progress(progress))
+        })?;
+    Ok(format!("Finished: {}", format_progress(&progress)))
+}
+
+fn format_progress(progress: &BlockImportProgress) -> String {
+    format!(
+        "{} blocks imported, {} already existing, {} invalid in {:.1}s \
+         ({:.1} blocks/s)",
+        progress.imported,
+        progress.existing,
+        progress.invalid,
+        progress.elapsed.as_secs_f64(),
+        progress.blocks_per_second()
+    )
+}
@@ -0,0 +1,258 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::H256;
+use cfxcore::block_data_manager::{
+    db_inspector::{
+        column_stats, decode_entry, dir_entries_disk_usage, disk_usage,
+        DbEntryKey, EntryStats, DB_COLUMNS,
+    },
+    storage_verifier::{
+        verify_storage, CorruptedStorage, StateTree, StorageCorruption,
+    },
+};
+use clap;
+use client::{
+    common::{open_block_db_offline, open_ledger_offline},
+    configuration::Configuration,
+};
+use rustc_hex::{FromHex, ToHex};
+use std::path::Path;
+
+#[derive(Debug, PartialEq)]
+pub enum DbCmd {
+    Verify(VerifyDb),
+    Inspect(InspectDb),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct VerifyDb {
+    pub range_size: Option<u64>,
+}
+
+impl VerifyDb {
+    pub fn new(matches: &clap::ArgMatches) -> Result<Self, String> {
+        let range_size = match matches.value_of("range-size") {
+            Some(size) => Some(
+                size.parse()
+                    .map_err(|_| format!("Invalid range size: {}", size))?,
+            ),
+            None => None,
+        };
+        Ok(Self { range_size })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct InspectDb {
+    pub column: Option<String>,
+    pub prefix: Vec<u8>,
+    pub entry: Option<DbEntryKey>,
+}
+
+impl InspectDb {
+    pub fn new(matches: &clap::ArgMatches) -> Result<Self, String> {
+        let prefix = match matches.value_of("prefix") {
+            Some(prefix) => prefix
+                .trim_start_matches("0x")
+                .from_hex::<Vec<u8>>()
+                .map_err(|_| format!("Invalid key prefix: {}", prefix))?,
+            None => vec![],
+        };
+        let entry = match matches.value_of("entry") {
+            Some(kind) => Some(parse_entry_key(kind, matches.value_of("key"))?),
+            None => None,
+        };
+        Ok(Self {
+            column: matches.value_of("column").map(Into::into),
+            prefix,
+            entry,
+        })
+    }
+}
+
+fn parse_entry_key(
+    kind: &str, key: Option<&str>,
+) -> Result<DbEntryKey, String> {
+    let required_key =
+        || key.ok_or_else(|| format!("--key is required for {}", kind));
+    let hash = || -> Result<H256, String> {
+        let key = required_key()?;
+        key.trim_start_matches("0x")
+            .parse()
+            .map_err(|_| format!("Invalid hash: {}", key))
+    };
+    Ok(match kind {
+        "header" => DbEntryKey::BlockHeader(hash()?),
+        "block" => DbEntryKey::Block(hash()?),
+        "receipts" => DbEntryKey::Receipts(hash()?),
+        "traces" => DbEntryKey::Traces(hash()?),
+        "commitment" => DbEntryKey::ExecutionCommitment(hash()?),
+        "tx-index" => DbEntryKey::TransactionIndex(hash()?),
+        "epoch" => {
+            let key = required_key()?;
+            DbEntryKey::EpochSet(
+                key.parse()
+                    .map_err(|_| format!("Invalid epoch height: {}", key))?,
+            )
+        }
+        "checkpoint" => DbEntryKey::Checkpoint,
+        "gc-progress" => DbEntryKey::GcProgress,
+        _ => return Err(format!("Unknown entry kind: {}", kind)),
+    })
+}
+
+pub fn execute(cmd: DbCmd, conf: &Configuration) -> Result<String, String> {
+    match cmd {
+        DbCmd::Verify(verify_cmd) => verify(verify_cmd, conf),
+        DbCmd::Inspect(inspect_cmd) => inspect(inspect_cmd, conf),
+    }
+}
+
+fn verify(cmd: VerifyDb, conf: &Configuration) -> Result<String, String> {
+    let (db_manager, storage_manager) = open_ledger_offline(conf)?;
+    let report = verify_storage(
+        &db_manager,
+        &storage_manager,
+        cmd.range_size.unwrap_or(conf.raw_conf.chunk_size_byte),
+    )
+    .map_err(|e| format!("Failed to verify storage: {}", e))?;
+
+    let mut output = format!(
+        "Verified {} snapshots and the states of {} epochs",
+        report.verified_snapshots, report.verified_epochs
+    );
+    if let Some((lowest, highest)) = report.verified_height_range {
+        output += &format!(" from height {} to {}", lowest, highest);
+    }
+    if report.is_clean() {
+        return Ok(output);
+    }
+
+    for corrupted in &report.corrupted_snapshots {
+        output += &format!(
+            "\nSnapshot of epoch {:?} at height {} is corrupted:",
+            corrupted.epoch_id, corrupted.height
+        );
+        output += &format_corruptions(corrupted);
+    }
+    for corrupted in &report.corrupted_states {
+        output += &format!(
+            "\nState of epoch {:?} at height {} is corrupted:",
+            corrupted.epoch_id, corrupted.height
+        );
+        output += &format_corruptions(corrupted);
+    }
+    Err(output)
+}
+
+fn inspect(cmd: InspectDb, conf: &Configuration) -> Result<String, String> {
+    let (ledger_db, db_manager) = open_block_db_offline(conf)?;
+    if let Some(entry_key) = &cmd.entry {
+        return decode_entry(&db_manager, entry_key)
+            .ok_or_else(|| format!("Entry {:?} not found", entry_key));
+    }
+
+    let mut output = String::new();
+    for (col, name) in DB_COLUMNS.iter() {
+        if cmd.column.as_ref().map_or(false, |column| column != name) {
+            continue;
+        }
+        let stats = column_stats(&ledger_db, *col, &cmd.prefix)
+            .map_err(|e| format!("Failed to read column {}: {}", name, e))?;
+        output += &format!("Column {}: {}\n", name, format_stats(&stats.total));
+        for (kind, kind_stats) in &stats.by_kind {
+            output += &format!("    {}: {}\n", kind, format_stats(kind_stats));
+        }
+    }
+    if cmd.column.is_some() {
+        return Ok(output);
+    }
+
+    let storage_conf = conf.storage_config();
+    output += "Disk usage:\n";
+    output += &format!("    block db: {} bytes\n", usage(&conf.db_config().0)?);
+    output += &format!(
+        "    snapshots: {} bytes\n",
+        usage(&storage_conf.path_snapshot_dir)?
+    );
+    let snapshots_usage = dir_entries_disk_usage(
+        &storage_conf.path_snapshot_dir,
+    )
+    .map_err(|e| format!("Failed to read the snapshot directory: {}", e))?;
+    for (snapshot, size) in snapshots_usage {
+        output += &format!("        {}: {} bytes\n", snapshot, size);
+    }
+    output += &format!(
+        "    delta mpts: {} bytes\n",
+        usage(&storage_conf.path_delta_mpts_dir)?
+    );
+    output += &format!(
+        "    snapshot info db: {} bytes\n",
+        usage(&storage_conf.path_snapshot_info_db)?
+    );
+    output += &format!(
+        "    state history db: {} bytes",
+        usage(&storage_conf.path_state_history_db)?
+    );
+    Ok(output)
+}
+
+fn usage(path: &Path) -> Result<u64, String> {
+    disk_usage(path).map_err(|e| {
+        format!("Failed to read the disk usage of {:?}: {}", path, e)
+    })
+}
+
+fn format_stats(stats: &EntryStats) -> String {
+    format!(
+        "{} entries, {} key bytes, {} value bytes",
+        stats.count, stats.key_bytes, stats.value_bytes
+    )
+}
+
+fn format_corruptions(corrupted: &CorruptedStorage) -> String {
+    let mut output = String::new();
+    for corruption in &corrupted.corruptions {
+        output += "\n    ";
+        output += &match corruption {
+            StorageCorruption::MissingState => "state not found".into(),
+            StorageCorruption::MissingSnapshot => "snapshot not found".into(),
+            StorageCorruption::RootMismatch {
+                tree,
+                expected,
+                found,
+            } => format!(
+                "{} merkle root mismatch, expected {:?}, found {:?}",
+                tree_name(*tree),
+                expected,
+                found
+            ),
+            StorageCorruption::CorruptedKeyRange {
+                tree,
+                lower_bound_incl,
+                upper_bound_excl,
+            } => format!(
+                "{} key range [0x{}, {}) corrupted",
+                tree_name(*tree),
+                lower_bound_incl.to_hex::<String>(),
+                upper_bound_excl.as_ref().map_or("end".into(), |key| {
+                    format!("0x{}", key.to_hex::<String>())
+                })
+            ),
+        };
+    }
+    if let Some(error) = &corrupted.error {
+        output += &format!("\n    verification failed: {}", error);
+    }
+    output
+}
+
+fn tree_name(tree: StateTree) -> &'static str {
+    match tree {
+        StateTree::Snapshot => "snapshot",
+        StateTree::IntermediateDeltaMpt => "intermediate delta mpt",
+        StateTree::DeltaMpt => "delta mpt",
+    }
+}
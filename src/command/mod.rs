@@ -3,5 +3,9 @@
 // See http://www.gnu.org/licenses/
 
 pub mod account;
+pub mod blocks;
+pub mod db;
 pub mod helpers;
+pub mod replay;
 pub mod rpc;
+pub mod snapshot;
@@ -0,0 +1,174 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfxcore::consensus::epoch_replay::{
+    ComputeEpochDebugRecord, EpochReplayResult,
+};
+use clap;
+use client::{common::replay_epochs_offline, configuration::Configuration};
+use serde_json;
+use std::{fs, path::Path, time::Duration};
+
+#[derive(Debug, PartialEq)]
+pub struct ReplayCmd {
+    pub from_epoch: u64,
+    pub to_epoch: u64,
+    pub timings: bool,
+    pub trace_dir: Option<String>,
+    pub dump_dir: Option<String>,
+}
+
+impl ReplayCmd {
+    pub fn new(matches: &clap::ArgMatches) -> Result<Self, String> {
+        let epoch = |name: &str| -> Result<u64, String> {
+            let epoch = matches.value_of(name).unwrap();
+            epoch
+                .parse()
+                .map_err(|_| format!("Invalid epoch number: {}", epoch))
+        };
+        let from_epoch = epoch("from")?;
+        let to_epoch = epoch("to")?;
+        if from_epoch > to_epoch {
+            return Err(format!(
+                "from {} is larger than to {}",
+                from_epoch, to_epoch
+            ));
+        }
+        Ok(Self {
+            from_epoch,
+            to_epoch,
+            timings: matches.is_present("timings"),
+            trace_dir: matches.value_of("trace-dir").map(Into::into),
+            dump_dir: matches.value_of("dump-dir").map(Into::into),
+        })
+    }
+}
+
+pub fn execute(cmd: ReplayCmd, conf: &Configuration) -> Result<String, String> {
+    if let Some(trace_dir) = &cmd.trace_dir {
+        if !conf.raw_conf.executive_trace {
+            return Err("--trace-dir requires executive_trace = true".into());
+        }
+        create_dir(trace_dir)?;
+    }
+    if let Some(dump_dir) = &cmd.dump_dir {
+        create_dir(dump_dir)?;
+    }
+
+    let mut replayed_epochs = 0;
+    let mut replayed_transactions = 0;
+    let mut elapsed = Duration::default();
+    let mut divergence = None;
+    replay_epochs_offline(
+        conf,
+        cmd.from_epoch,
+        cmd.to_epoch,
+        cmd.trace_dir.is_some(),
+        |consensus, result| {
+            replayed_epochs += 1;
+            replayed_transactions += result.transaction_count;
+            elapsed += result.elapsed;
+            if cmd.timings {
+                println!(
+                    "Epoch {} {:?}: {} blocks, {} transactions in {:.3}s",
+                    result.height,
+                    result.epoch_hash,
+                    result.block_count,
+                    result.transaction_count,
+                    result.elapsed.as_secs_f64()
+                );
+            }
+            if let Some(trace_dir) = &cmd.trace_dir {
+                write_file(
+                    &file_path(trace_dir, &result, ".txt"),
+                    &format!("{:#?}", result.traces),
+                )?;
+            }
+            if result.is_consistent() {
+                return Ok(true);
+            }
+
+            if let Some(dump_dir) = &cmd.dump_dir {
+                let mut debug_record = ComputeEpochDebugRecord::default();
+                consensus.replay_epoch(
+                    result.height,
+                    false, /* with_traces */
+                    Some(&mut debug_record),
+                )?;
+                write_file(
+                    &file_path(dump_dir, &result, ".txt"),
+                    &format!("{:?}", debug_record),
+                )?;
+                write_file(
+                    &file_path(dump_dir, &result, ".json.txt"),
+                    &serde_json::to_string(&debug_record)
+                        .map_err(|e| e.to_string())?,
+                )?;
+            }
+            divergence = Some(format_divergence(&result));
+            Ok(false)
+        },
+    )?;
+
+    let output = format!(
+        "Replayed {} epochs from {} with {} transactions in {:.3}s \
+         ({:.1} tx/s)",
+        replayed_epochs,
+        cmd.from_epoch,
+        replayed_transactions,
+        elapsed.as_secs_f64(),
+        replayed_transactions as f64 / elapsed.as_secs_f64().max(1e-3)
+    );
+    match divergence {
+        None => Ok(output),
+        Some(divergence) => Err(format!("{}\n{}", output, divergence)),
+    }
+}
+
+fn format_divergence(result: &EpochReplayResult) -> String {
+    let mut output = format!(
+        "Epoch {} {:?} diverges from the stored commitment:",
+        result.height, result.epoch_hash
+    );
+    if !result.state_root_matches() {
+        output += &format!(
+            "\n    state root: expected {:?}, computed {:?}",
+            result.expected.state_root_with_aux_info,
+            result.computed.state_root_with_aux_info
+        );
+    }
+    if !result.receipts_root_matches() {
+        output += &format!(
+            "\n    receipts root: expected {:?}, computed {:?}",
+            result.expected.receipts_root, result.computed.receipts_root
+        );
+    }
+    if !result.logs_bloom_hash_matches() {
+        output += &format!(
+            "\n    logs bloom hash: expected {:?}, computed {:?}",
+            result.expected.logs_bloom_hash, result.computed.logs_bloom_hash
+        );
+    }
+    output
+}
+
+fn file_path(dir: &str, result: &EpochReplayResult, extension: &str) -> String {
+    Path::new(dir)
+        .join(format!(
+            "{}_{:?}{}",
+            result.height, result.epoch_hash, extension
+        ))
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn create_dir(dir: &str) -> Result<(), String> {
+    fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create directory {}: {}", dir, e))
+}
+
+fn write_file(path: &str, content: &str) -> Result<(), String> {
+    fs::write(path, content)
+        .map_err(|e| format!("Failed to write {}: {}", path, e))
+}
@@ -0,0 +1,126 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::H256;
+use cfxcore::sync::snapshot_archive::{export_snapshot, import_snapshot};
+use clap;
+use client::{
+    common::{convert_snapshots_offline, open_ledger_offline},
+    configuration::Configuration,
+};
+use std::path::Path;
+
+#[derive(Debug, PartialEq)]
+pub enum SnapshotCmd {
+    Export(ExportSnapshot),
+    Import(ImportSnapshot),
+    Convert(ConvertSnapshot),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ExportSnapshot {
+    pub epoch: String,
+    pub output: String,
+    pub chunk_size: Option<u64>,
+}
+
+impl ExportSnapshot {
+    pub fn new(matches: &clap::ArgMatches) -> Result<Self, String> {
+        let chunk_size = match matches.value_of("chunk-size") {
+            Some(size) => Some(
+                size.parse()
+                    .map_err(|_| format!("Invalid chunk size: {}", size))?,
+            ),
+            None => None,
+        };
+        Ok(Self {
+            epoch: matches.value_of("epoch").unwrap().to_string(),
+            output: matches.value_of("output").unwrap().to_string(),
+            chunk_size,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ImportSnapshot {
+    pub archive: String,
+}
+
+impl ImportSnapshot {
+    pub fn new(matches: &clap::ArgMatches) -> Self {
+        Self {
+            archive: matches.value_of("archive").unwrap().to_string(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ConvertSnapshot {
+    pub to: String,
+}
+
+impl ConvertSnapshot {
+    pub fn new(matches: &clap::ArgMatches) -> Self {
+        Self {
+            to: matches.value_of("to").unwrap().to_string(),
+        }
+    }
+}
+
+pub fn execute(
+    cmd: SnapshotCmd, conf: &Configuration,
+) -> Result<String, String> {
+    match cmd {
+        SnapshotCmd::Export(export_cmd) => export(export_cmd, conf),
+        SnapshotCmd::Import(import_cmd) => import(import_cmd, conf),
+        SnapshotCmd::Convert(convert_cmd) => convert(convert_cmd, conf),
+    }
+}
+
+fn export(cmd: ExportSnapshot, conf: &Configuration) -> Result<String, String> {
+    let epoch: H256 = cmd
+        .epoch
+        .trim_start_matches("0x")
+        .parse()
+        .map_err(|_| format!("Invalid epoch hash: {}", cmd.epoch))?;
+    let (db_manager, storage_manager) = open_ledger_offline(conf)?;
+    let header = export_snapshot(
+        &db_manager,
+        &storage_manager,
+        &epoch,
+        Path::new(&cmd.output),
+        cmd.chunk_size.unwrap_or(conf.raw_conf.chunk_size_byte),
+    )
+    .map_err(|e| format!("Failed to export snapshot: {}", e))?;
+    Ok(format!(
+        "Exported snapshot of epoch {:?} at height {} with {} chunks to {}",
+        header.snapshot_epoch_id,
+        header.snapshot_height(),
+        header.chunk_boundaries.len() + 1,
+        cmd.output
+    ))
+}
+
+fn import(cmd: ImportSnapshot, conf: &Configuration) -> Result<String, String> {
+    let (_, storage_manager) = open_ledger_offline(conf)?;
+    let header = import_snapshot(&storage_manager, Path::new(&cmd.archive))
+        .map_err(|e| format!("Failed to import snapshot: {}", e))?;
+    Ok(format!(
+        "Imported snapshot of epoch {:?} at height {}, which is restored \
+         when the node syncs the checkpoint",
+        header.snapshot_epoch_id,
+        header.snapshot_height(),
+    ))
+}
+
+fn convert(
+    cmd: ConvertSnapshot, conf: &Configuration,
+) -> Result<String, String> {
+    let converted = convert_snapshots_offline(conf, &cmd.to)?;
+    Ok(format!(
+        "Converted {} snapshots to {}, set snapshot_db_backend = \"{}\" \
+         before starting the node",
+        converted, cmd.to, cmd.to
+    ))
+}
@@ -17,7 +17,17 @@ use client::{
     full::FullClient,
     light::LightClient,
 };
-use command::account::{AccountCmd, ImportAccounts, ListAccounts, NewAccount};
+use command::{
+    account::{
+        AccountCmd, ChangePassword, ChangeVaultPassword, DeleteVault,
+        ExportAccount, ImportAccounts, ImportGethAccounts, ListAccounts,
+        ListVaults, MoveAccount, NewAccount, NewVault, RenameAccount,
+    },
+    blocks::{BlocksCmd, ExportBlocks, ImportBlocks},
+    db::{DbCmd, InspectDb, VerifyDb},
+    replay::ReplayCmd,
+    snapshot::{ConvertSnapshot, ExportSnapshot, ImportSnapshot, SnapshotCmd},
+};
 use log::{info, LevelFilter};
 use log4rs::{
     append::{console::ConsoleAppender, file::FileAppender},
@@ -195,12 +205,102 @@ fn handle_sub_command(matches: &ArgMatches) -> Result<Option<String>, String> {
             ("import", Some(import_acc_matches)) => {
                 AccountCmd::Import(ImportAccounts::new(import_acc_matches))
             }
+            ("import-geth", Some(import_acc_matches)) => {
+                AccountCmd::ImportGeth(ImportGethAccounts::new(
+                    import_acc_matches,
+                ))
+            }
+            ("rename", Some(rename_acc_matches)) => {
+                AccountCmd::Rename(RenameAccount::new(rename_acc_matches))
+            }
+            ("change-password", Some(change_matches)) => {
+                AccountCmd::ChangePassword(ChangePassword::new(change_matches))
+            }
+            ("export", Some(export_acc_matches)) => {
+                AccountCmd::Export(ExportAccount::new(export_acc_matches))
+            }
+            ("new-vault", Some(vault_matches)) => {
+                AccountCmd::NewVault(NewVault::new(vault_matches))
+            }
+            ("list-vaults", Some(vault_matches)) => {
+                AccountCmd::ListVaults(ListVaults::new(vault_matches))
+            }
+            ("change-vault-password", Some(vault_matches)) => {
+                AccountCmd::ChangeVaultPassword(ChangeVaultPassword::new(
+                    vault_matches,
+                ))
+            }
+            ("delete-vault", Some(vault_matches)) => {
+                AccountCmd::DeleteVault(DeleteVault::new(vault_matches))
+            }
+            ("move", Some(move_acc_matches)) => {
+                AccountCmd::Move(MoveAccount::new(move_acc_matches))
+            }
             _ => unreachable!(),
         };
         let execute_output = command::account::execute(account_cmd)?;
         return Ok(Some(execute_output));
     }
 
+    // snapshot sub-commands
+    if let ("snapshot", Some(snapshot_matches)) = matches.subcommand() {
+        let snapshot_cmd = match snapshot_matches.subcommand() {
+            ("export", Some(export_matches)) => {
+                SnapshotCmd::Export(ExportSnapshot::new(export_matches)?)
+            }
+            ("import", Some(import_matches)) => {
+                SnapshotCmd::Import(ImportSnapshot::new(import_matches))
+            }
+            ("convert", Some(convert_matches)) => {
+                SnapshotCmd::Convert(ConvertSnapshot::new(convert_matches))
+            }
+            _ => unreachable!(),
+        };
+        let conf = Configuration::parse(matches)?;
+        let execute_output = command::snapshot::execute(snapshot_cmd, &conf)?;
+        return Ok(Some(execute_output));
+    }
+
+    // blocks sub-commands
+    if let ("blocks", Some(blocks_matches)) = matches.subcommand() {
+        let blocks_cmd = match blocks_matches.subcommand() {
+            ("export", Some(export_matches)) => {
+                BlocksCmd::Export(ExportBlocks::new(export_matches)?)
+            }
+            ("import", Some(import_matches)) => {
+                BlocksCmd::Import(ImportBlocks::new(import_matches))
+            }
+            _ => unreachable!(),
+        };
+        let conf = Configuration::parse(matches)?;
+        let execute_output = command::blocks::execute(blocks_cmd, &conf)?;
+        return Ok(Some(execute_output));
+    }
+
+    // db sub-commands
+    if let ("db", Some(db_matches)) = matches.subcommand() {
+        let db_cmd = match db_matches.subcommand() {
+            ("verify", Some(verify_matches)) => {
+                DbCmd::Verify(VerifyDb::new(verify_matches)?)
+            }
+            ("inspect", Some(inspect_matches)) => {
+                DbCmd::Inspect(InspectDb::new(inspect_matches)?)
+            }
+            _ => unreachable!(),
+        };
+        let conf = Configuration::parse(matches)?;
+        let execute_output = command::db::execute(db_cmd, &conf)?;
+        return Ok(Some(execute_output));
+    }
+
+    // replay command
+    if let ("replay", Some(replay_matches)) = matches.subcommand() {
+        let replay_cmd = ReplayCmd::new(replay_matches)?;
+        let conf = Configuration::parse(matches)?;
+        let execute_output = command::replay::execute(replay_cmd, &conf)?;
+        return Ok(Some(execute_output));
+    }
+
     // general RPC commands
     let mut subcmd_matches = matches;
     while let Some(m) = subcmd_matches.subcommand().1 {
@@ -146,6 +146,17 @@ class RpcClient:
 
         return res
 
+    def get_storage_range(self, addr: str, start_key: str = None, limit: int = 100, epoch: str = None) -> dict:
+        assert_is_hash_string(addr, length=40)
+        addr = hex_to_b32_address(addr)
+
+        if epoch is None:
+            res = self.node.cfx_getStorageRange(addr, start_key, hex(limit))
+        else:
+            res = self.node.cfx_getStorageRange(addr, start_key, hex(limit), epoch)
+
+        return res
+
     def get_storage_root(self, addr: str, epoch: str = None) -> str:
         assert_is_hash_string(addr, length=40)
         addr = hex_to_b32_address(addr)
@@ -93,6 +93,24 @@ class StorageRpcTest(ConfluxTestFramework):
 
         self.log.info("Pass\n")
 
+        # test paging through the storage
+        self.log.info("Retrieving the storage by pages of one entry from full node...")
+        storage = {}
+        start_key = None
+        while True:
+            res = self.rpc[FULLNODE1].get_storage_range(contractAddr, start_key, limit=1)
+            assert(len(res["storage"]) <= 1)
+            for entry in res["storage"]:
+                storage[entry["key"]] = entry["value"]
+            start_key = res["nextKey"]
+            if start_key is None:
+                break
+        assert_equal(storage["0x0000000000000000000000000000000000000000000000000000000000000000"], "0x00000000000000000000000000000000000000000000000000000000000004d2")
+        assert_equal(storage["0x6661e9d6d8b923d5bbaab1b96e1dd51ff6ea2a93520fdc9eb75d059238b8c5e9"], "0x000000000000000000000000000000000000000000000000000000000000162e")
+        assert("0x0000000000000000000000000000000000000000000000000000000000000002" not in storage)
+
+        self.log.info("Pass\n")
+
     def deploy_contract(self, sender, priv_key, data_hex):
         tx = self.rpc[FULLNODE0].new_contract_tx(receiver="", data_hex=data_hex, sender=sender, priv_key=priv_key, storage_limit=20000)
         assert_equal(self.rpc[FULLNODE0].send_tx(tx, True), tx.hash_hex())